@@ -0,0 +1,274 @@
+//! Criterion benchmark suite, intended to be tracked across releases.
+//!
+//! Run with `cargo bench --features bench --bench suite`.
+//! The benchmark ids are stable: `group/function/parameter`, for example `flat_decode/f32/zip`.
+//! Criterion writes the machine-readable results of each benchmark to
+//! `target/criterion/<group>/<function>/<parameter>/new/estimates.json`.
+//! Use `-- --save-baseline <release>` and `-- --baseline <release>` to compare releases.
+
+extern crate exr;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use exr::block::deep::{
+    compress_deep_scanline_block, decompress_deep_scanline_block, pack_deep_channels,
+};
+use exr::image::deep::{DeepChannelData, DeepSamples};
+use exr::image::read::deep::read_deep;
+use exr::meta::attribute::ChannelList;
+use exr::prelude::*;
+use half::slice::HalfFloatSliceExt;
+use smallvec::smallvec;
+use std::fs;
+use std::io::Cursor;
+
+const COMPRESSION_METHODS: [(&str, Compression); 8] = [
+    ("uncompressed", Compression::Uncompressed),
+    ("rle", Compression::RLE),
+    ("zips", Compression::ZIP1),
+    ("zip", Compression::ZIP16),
+    ("piz", Compression::PIZ),
+    ("pxr24", Compression::PXR24),
+    ("b44", Compression::B44),
+    ("b44a", Compression::B44A),
+];
+
+const DEEP_FILES: [(&str, &str); 2] = [
+    (
+        "balls",
+        "tests/images/valid/openexr/v2/LowResLeftView/Balls.exr",
+    ),
+    (
+        "ground",
+        "tests/images/valid/openexr/v2/LowResLeftView/Ground.exr",
+    ),
+];
+
+/// Decode each compression method, from the files in memory.
+fn flat_decode(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("flat_decode");
+
+    for sample_type in ["f16", "f32"] {
+        for (name, _) in COMPRESSION_METHODS {
+            let path = format!(
+                "tests/images/valid/custom/compression_methods/{}/{}.exr",
+                sample_type, name
+            );
+
+            let bytes = fs::read(&path).unwrap();
+            group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+            group.bench_with_input(
+                BenchmarkId::new(sample_type, name),
+                &bytes,
+                |bench, bytes| {
+                    bench.iter(|| read_all_flat_layers_from_bytes(black_box(bytes)).unwrap())
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+/// Encode the same image with each compression method, to memory.
+fn flat_encode(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("flat_encode");
+    let path = "tests/images/valid/custom/compression_methods/f32/uncompressed.exr";
+
+    for (name, compression) in COMPRESSION_METHODS {
+        let mut image = read_all_flat_layers_from_file(path).unwrap();
+
+        for layer in &mut image.layer_data {
+            layer.encoding.compression = compression;
+        }
+
+        group.bench_with_input(BenchmarkId::new("f32", name), &image, |bench, image| {
+            bench.iter(|| {
+                let mut bytes = Vec::new();
+                image.write().to_buffered(Cursor::new(&mut bytes)).unwrap();
+                bytes
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Decode deep files from memory, in parallel and sequentially.
+fn deep_decode(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("deep_decode");
+    group.sample_size(20);
+
+    for (name, path) in DEEP_FILES {
+        let bytes = fs::read(path).unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("parallel", name),
+            &bytes,
+            |bench, bytes| {
+                bench.iter(|| {
+                    read_deep()
+                        .all_channels()
+                        .first_valid_layer()
+                        .all_attributes()
+                        .from_buffered(Cursor::new(black_box(bytes)))
+                        .unwrap()
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("sequential", name),
+            &bytes,
+            |bench, bytes| {
+                bench.iter(|| {
+                    read_deep()
+                        .all_channels()
+                        .first_valid_layer()
+                        .all_attributes()
+                        .non_parallel()
+                        .from_buffered(Cursor::new(black_box(bytes)))
+                        .unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Pack and unpack the interleaved sample bytes of a single large deep block,
+/// with channels of all sample types, and with only `f32` channels.
+fn deep_channels(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("deep_channels");
+
+    let mixed = mixed_deep_samples(1280, 720);
+    let (samples, channels) = &mixed;
+    group.throughput(Throughput::Elements(samples.total_samples() as u64));
+
+    group.bench_function("pack", |bench| {
+        bench.iter(|| pack_deep_channels(black_box(samples), channels))
+    });
+
+    let f32_only = f32_deep_samples(1280, 720);
+
+    for (name, (samples, channels)) in [("unpack", &mixed), ("unpack_f32", &f32_only)] {
+        let block =
+            compress_deep_scanline_block(samples, Compression::Uncompressed, channels, 0).unwrap();
+
+        group.bench_function(name, |bench| {
+            bench.iter(|| {
+                decompress_deep_scanline_block(
+                    black_box(&block),
+                    Compression::Uncompressed,
+                    channels,
+                    samples.width,
+                    samples.height,
+                    true,
+                )
+                .unwrap()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Convert a half float channel of a 4k image to floats, one value at a time and in batches.
+fn half_conversion(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("half_conversion");
+    let halves: Vec<f16> = (0..3840 * 2160)
+        .map(|index| f16::from_f32((index % 4096) as f32 / 4096.0))
+        .collect();
+
+    group.throughput(Throughput::Elements(halves.len() as u64));
+
+    group.bench_function("per_value", |bench| {
+        bench.iter(|| {
+            black_box(&halves)
+                .iter()
+                .map(|value| value.to_f32())
+                .collect::<Vec<f32>>()
+        })
+    });
+
+    group.bench_function("batched", |bench| {
+        bench.iter(|| black_box(&halves).to_f32_vec())
+    });
+
+    group.finish();
+}
+
+/// Deep samples with up to six samples per pixel, and channels of all sample types.
+fn mixed_deep_samples(width: usize, height: usize) -> (DeepSamples, ChannelList) {
+    let channels = ChannelList::new(smallvec![
+        ChannelDescription::new("A", SampleType::F16, true),
+        ChannelDescription::new("B", SampleType::F16, false),
+        ChannelDescription::new("G", SampleType::F16, false),
+        ChannelDescription::new("R", SampleType::F16, false),
+        ChannelDescription::new("Z", SampleType::F32, false),
+        ChannelDescription::new("id", SampleType::U32, false),
+    ]);
+
+    (deep_samples(width, height, &channels), channels)
+}
+
+/// Deep samples with up to six samples per pixel, and only `f32` channels.
+fn f32_deep_samples(width: usize, height: usize) -> (DeepSamples, ChannelList) {
+    let channels = ChannelList::new(smallvec![
+        ChannelDescription::new("A", SampleType::F32, true),
+        ChannelDescription::new("B", SampleType::F32, false),
+        ChannelDescription::new("G", SampleType::F32, false),
+        ChannelDescription::new("R", SampleType::F32, false),
+        ChannelDescription::new("Z", SampleType::F32, false),
+    ]);
+
+    (deep_samples(width, height, &channels), channels)
+}
+
+/// Deep samples with up to six samples per pixel, containing arbitrary values.
+fn deep_samples(width: usize, height: usize, channels: &ChannelList) -> DeepSamples {
+    let counts = (0..width * height)
+        .scan(0, |total, pixel| {
+            *total += (pixel % 7) as u32;
+            Some(*total)
+        })
+        .collect();
+
+    let mut samples = DeepSamples::new(width, height);
+    samples.set_cumulative_counts(counts).unwrap();
+    samples.allocate_channels(channels);
+
+    for (index, channel) in samples.channels.iter_mut().enumerate() {
+        match channel {
+            DeepChannelData::F16(values) => {
+                for (sample, value) in values.iter_mut().enumerate() {
+                    *value = f16::from_f32((sample % 256) as f32 / 255.0);
+                }
+            }
+            DeepChannelData::F32(values) => {
+                for (sample, value) in values.iter_mut().enumerate() {
+                    *value = sample as f32 * 0.25;
+                }
+            }
+            DeepChannelData::U32(values) => {
+                for (sample, value) in values.iter_mut().enumerate() {
+                    *value = (sample + index) as u32;
+                }
+            }
+        }
+    }
+
+    samples
+}
+
+criterion_group!(
+    benches,
+    flat_decode,
+    flat_encode,
+    deep_decode,
+    deep_channels,
+    half_conversion
+);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+//! Regenerates `include/exrs.h` from the exported functions.
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let directory = std::env::var("CARGO_MANIFEST_DIR").expect("cargo sets the manifest directory");
+
+    cbindgen::generate(&directory)
+        .expect("cannot generate the C header")
+        .write_to_file(std::path::Path::new(&directory).join("include/exrs.h"));
+}
@@ -0,0 +1,40 @@
+// Prints the parts and channels of an exr file.
+//
+//     cc examples/info.c -I include -L target/release -l exrs -o info
+//     ./info image.exr
+
+#include <stdio.h>
+#include "exrs.h"
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s <file.exr>\n", argv[0]);
+    return 2;
+  }
+
+  ExrsImage *image = NULL;
+  if (exrs_image_read(argv[1], &image) != EXRS_ERROR_OK) {
+    fprintf(stderr, "cannot read %s: %s\n", argv[1], exrs_last_error_message());
+    return 1;
+  }
+
+  static const char *type_names[] = { "f16", "f32", "u32" };
+
+  for (size_t part = 0; part < exrs_image_part_count(image); part++) {
+    size_t width = 0, height = 0;
+    exrs_part_size(image, part, &width, &height);
+
+    const char *name = exrs_part_name(image, part);
+    printf("part %zu '%s': %zu x %zu\n", part, name ? name : "", width, height);
+
+    for (size_t channel = 0; channel < exrs_part_channel_count(image, part); channel++) {
+      ExrsSampleType type;
+      size_t count = 0;
+      exrs_channel_info(image, part, channel, &type, &count);
+      printf("    %s: %s, %zu samples\n", exrs_channel_name(image, part, channel), type_names[type], count);
+    }
+  }
+
+  exrs_image_free(image);
+  return 0;
+}
@@ -0,0 +1,168 @@
+#ifndef EXRS_H
+#define EXRS_H
+
+/* This file is generated by build.rs with cbindgen. Do not edit it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a function.
+typedef enum ExrsError {
+  // The function succeeded.
+  EXRS_ERROR_OK = 0,
+  // A file could not be read or written.
+  EXRS_ERROR_IO = 1,
+  // The file or the provided data is invalid.
+  EXRS_ERROR_INVALID = 2,
+  // The file uses a feature that is not supported.
+  EXRS_ERROR_UNSUPPORTED = 3,
+  // A pointer was null, an index was out of range, or a buffer was too small.
+  EXRS_ERROR_ARGUMENT = 4,
+  // An unexpected internal error occurred.
+  EXRS_ERROR_PANIC = 5,
+} ExrsError;
+
+// The type of the samples of a channel.
+typedef enum ExrsSampleType {
+  // 16-bit float, stored as the bits of an IEEE 754 half.
+  EXRS_SAMPLE_TYPE_F16 = 0,
+  // 32-bit float.
+  EXRS_SAMPLE_TYPE_F32 = 1,
+  // 32-bit unsigned integer.
+  EXRS_SAMPLE_TYPE_U32 = 2,
+} ExrsSampleType;
+
+// An image with flat parts. Opaque in C.
+typedef struct ExrsImage ExrsImage;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last error that occurred on this thread, or null if there was none.
+// The string is valid until the next function call on this thread.
+const char *exrs_last_error_message(void);
+
+// Read all flat parts of a file. On success, stores a new image in `image`,
+// which must be released with `exrs_image_free`.
+//
+// # Safety
+// `path` must be a null-terminated UTF-8 string, and `image` must point to writable memory.
+ExrsError exrs_image_read(const char *path, ExrsImage **image);
+
+// Create an image without any parts, to be filled with `exrs_image_add_part`.
+// Must be released with `exrs_image_free`.
+ExrsImage *exrs_image_new(void);
+
+// Release an image and all strings returned for it. Does nothing for null pointers.
+//
+// # Safety
+// `image` must have been returned by this API, and must not be used afterwards.
+void exrs_image_free(ExrsImage *image);
+
+// The number of parts of the image. Returns 0 for null pointers.
+//
+// # Safety
+// `image` must be null or a valid image.
+size_t exrs_image_part_count(const ExrsImage *image);
+
+// The name of a part, or null if the part has no name or does not exist.
+//
+// # Safety
+// `image` must be null or a valid image.
+const char *exrs_part_name(const ExrsImage *image, size_t part);
+
+// The width and height of a part in pixels.
+//
+// # Safety
+// `image` must be null or a valid image, `width` and `height` must point to writable memory.
+ExrsError exrs_part_size(const ExrsImage *image, size_t part, size_t *width, size_t *height);
+
+// The number of channels of a part. Returns 0 if the part does not exist.
+//
+// # Safety
+// `image` must be null or a valid image.
+size_t exrs_part_channel_count(const ExrsImage *image, size_t part);
+
+// The name of a channel, or null if the channel does not exist.
+// Channels are sorted alphabetically.
+//
+// # Safety
+// `image` must be null or a valid image.
+const char *exrs_channel_name(const ExrsImage *image, size_t part, size_t channel);
+
+// The sample type of a channel, and the number of samples.
+// Subsampled channels contain fewer samples than the part has pixels.
+//
+// # Safety
+// `image` must be null or a valid image, `sample_type` and `sample_count` must point to writable memory.
+ExrsError exrs_channel_info(const ExrsImage *image,
+                            size_t part,
+                            size_t channel,
+                            ExrsSampleType *sample_type,
+                            size_t *sample_count);
+
+// The samples of a channel in their own type, without copying, row after row.
+// Stores the size of the buffer in bytes in `byte_size`. Returns null if the channel does not exist.
+// The buffer stays valid until the image is freed or modified.
+//
+// # Safety
+// `image` must be null or a valid image, and `byte_size` must be null or point to writable memory.
+const void *exrs_channel_data(const ExrsImage *image,
+                              size_t part,
+                              size_t channel,
+                              size_t *byte_size);
+
+// Copy the samples of a channel of any type into a `float` buffer, converting them.
+// The buffer must have room for the sample count returned by `exrs_channel_info`.
+//
+// # Safety
+// `image` must be null or a valid image, and `buffer` must point to `buffer_length` writable floats.
+ExrsError exrs_channel_read_f32(const ExrsImage *image,
+                                size_t part,
+                                size_t channel,
+                                float *buffer,
+                                size_t buffer_length);
+
+// Add a part to the image, and store its index in `part` if not null.
+// `name` may be null for single-part files. `compression` is a name like `"zip"`, `"piz"`,
+// or `"dwab"`, or null for zip compression.
+//
+// # Safety
+// `image` must be a valid image, `name` and `compression` must be null or null-terminated UTF-8 strings,
+// and `part` must be null or point to writable memory.
+ExrsError exrs_image_add_part(ExrsImage *image,
+                              const char *name,
+                              size_t width,
+                              size_t height,
+                              const char *compression,
+                              size_t *part);
+
+// Add a channel to a part, copying the samples, which are stored row after row.
+// `samples` must contain `width * height` values of the sample type:
+// `uint16_t` half bits, `float`, or `uint32_t`.
+//
+// # Safety
+// `image` must be a valid image, `name` a null-terminated UTF-8 string,
+// and `samples` must point to `sample_count` values of the sample type.
+ExrsError exrs_part_add_channel(ExrsImage *image,
+                                size_t part,
+                                const char *name,
+                                ExrsSampleType sample_type,
+                                const void *samples,
+                                size_t sample_count);
+
+// Write all parts of the image to a file. Every part needs at least one channel.
+//
+// # Safety
+// `image` must be a valid image, and `path` a null-terminated UTF-8 string.
+ExrsError exrs_image_write(const ExrsImage *image, const char *path);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* EXRS_H */
@@ -0,0 +1,764 @@
+//! C API for reading and writing flat multi-part exr files.
+//!
+//! Images are opaque handles that are created by `exrs_image_read` or `exrs_image_new`,
+//! and must be released with `exrs_image_free`. Parts and channels are addressed by index.
+//! Functions that can fail return an `ExrsError`, and the message of the last error
+//! on the current thread is available through `exrs_last_error_message`.
+//!
+//! Strings returned by this API are owned by the image, and stay valid until the image is freed.
+//! Only the largest resolution level is read. Deep parts are skipped.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use exr::error::Error;
+use exr::meta::describe::parse_compression;
+use exr::prelude::*;
+
+/// The result of a function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExrsError {
+    /// The function succeeded.
+    Ok = 0,
+
+    /// A file could not be read or written.
+    Io = 1,
+
+    /// The file or the provided data is invalid.
+    Invalid = 2,
+
+    /// The file uses a feature that is not supported.
+    Unsupported = 3,
+
+    /// A pointer was null, an index was out of range, or a buffer was too small.
+    Argument = 4,
+
+    /// An unexpected internal error occurred.
+    Panic = 5,
+}
+
+/// The type of the samples of a channel.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExrsSampleType {
+    /// 16-bit float, stored as the bits of an IEEE 754 half.
+    F16 = 0,
+
+    /// 32-bit float.
+    F32 = 1,
+
+    /// 32-bit unsigned integer.
+    U32 = 2,
+}
+
+/// An image with flat parts. Opaque in C.
+pub struct ExrsImage {
+    parts: Vec<Part>,
+}
+
+struct Part {
+    name: Option<CString>,
+    size: Vec2<usize>,
+    encoding: Encoding,
+    channels: Vec<Channel>,
+}
+
+struct Channel {
+    name: CString,
+    sampling: Vec2<usize>,
+    samples: FlatSamples,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn fail(code: ExrsError, message: impl Into<String>) -> ExrsError {
+    set_last_error(message.into());
+    code
+}
+
+fn from_exr_error(error: Error) -> ExrsError {
+    let code = match &error {
+        Error::Io(_) => ExrsError::Io,
+        Error::Invalid(_) => ExrsError::Invalid,
+        Error::NotSupported(_) => ExrsError::Unsupported,
+        Error::Aborted => ExrsError::Panic,
+    };
+
+    fail(code, error.to_string())
+}
+
+/// Run the function, and convert a panic to an error instead of unwinding into C.
+fn guard(function: impl FnOnce() -> ExrsError) -> ExrsError {
+    match catch_unwind(AssertUnwindSafe(function)) {
+        Ok(code) => code,
+        Err(_) => fail(ExrsError::Panic, "internal error"),
+    }
+}
+
+/// Converts a null-terminated UTF-8 string. Returns `None` for null pointers and invalid text.
+unsafe fn string<'s>(pointer: *const c_char) -> Option<&'s str> {
+    if pointer.is_null() {
+        None
+    } else {
+        CStr::from_ptr(pointer).to_str().ok()
+    }
+}
+
+unsafe fn slice_from<'s, T>(pointer: *const T, length: usize) -> Option<&'s [T]> {
+    if pointer.is_null() && length != 0 {
+        None
+    } else if length == 0 {
+        Some(&[])
+    } else {
+        Some(slice::from_raw_parts(pointer, length))
+    }
+}
+
+fn cstring(text: String) -> CString {
+    CString::new(text).unwrap_or_default()
+}
+
+impl ExrsImage {
+    fn part(&self, part: usize) -> Result<&Part, ExrsError> {
+        self.parts.get(part).ok_or_else(|| {
+            fail(
+                ExrsError::Argument,
+                format!("part index {} out of range", part),
+            )
+        })
+    }
+
+    fn channel(&self, part: usize, channel: usize) -> Result<&Channel, ExrsError> {
+        self.part(part)?.channels.get(channel).ok_or_else(|| {
+            fail(
+                ExrsError::Argument,
+                format!("channel index {} out of range", channel),
+            )
+        })
+    }
+
+    fn from_flat_image(image: FlatImage) -> Self {
+        let parts = image
+            .layer_data
+            .into_iter()
+            .map(|layer| Part {
+                name: layer
+                    .attributes
+                    .layer_name
+                    .as_ref()
+                    .map(|name| cstring(name.to_string())),
+                size: layer.size,
+                encoding: layer.encoding,
+                channels: layer
+                    .channel_data
+                    .list
+                    .into_iter()
+                    .map(|channel| Channel {
+                        name: cstring(channel.name.to_string()),
+                        sampling: channel.sampling,
+                        samples: channel.sample_data,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        ExrsImage { parts }
+    }
+
+    fn to_flat_image(&self) -> exr::error::Result<FlatImage> {
+        let layers =
+            self.parts
+                .iter()
+                .map(|part| {
+                    let attributes = match &part.name {
+                        Some(name) => {
+                            let name = Text::new_or_none(name.to_string_lossy())
+                                .ok_or_else(|| Error::Invalid("part name".into()))?;
+                            LayerAttributes::named(name)
+                        }
+                        None => LayerAttributes::default(),
+                    };
+
+                    let channels =
+                        part.channels
+                            .iter()
+                            .map(|channel| {
+                                let name = Text::new_or_none(channel.name.to_string_lossy())
+                                    .ok_or_else(|| Error::Invalid("channel name".into()))?;
+
+                                Ok(AnyChannel {
+                                    quantize_linearly:
+                                        ChannelDescription::guess_quantization_linearity(&name),
+                                    name,
+                                    sampling: channel.sampling,
+                                    sample_data: channel.samples.clone(),
+                                })
+                            })
+                            .collect::<exr::error::Result<SmallVec<_>>>()?;
+
+                    Ok(Layer::new(
+                        part.size,
+                        attributes,
+                        part.encoding,
+                        AnyChannels::sort(channels),
+                    ))
+                })
+                .collect::<exr::error::Result<Vec<_>>>()?;
+
+        let display_size = layers.iter().fold(Vec2(0, 0), |size, layer| {
+            Vec2(
+                size.width().max(layer.size.width()),
+                size.height().max(layer.size.height()),
+            )
+        });
+
+        Ok(Image::from_layers(
+            ImageAttributes::new(IntegerBounds::from_dimensions(display_size)),
+            layers,
+        ))
+    }
+}
+
+/// The message of the last error that occurred on this thread, or null if there was none.
+/// The string is valid until the next function call on this thread.
+#[no_mangle]
+pub extern "C" fn exrs_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Read all flat parts of a file. On success, stores a new image in `image`,
+/// which must be released with `exrs_image_free`.
+///
+/// # Safety
+/// `path` must be a null-terminated UTF-8 string, and `image` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_image_read(
+    path: *const c_char,
+    image: *mut *mut ExrsImage,
+) -> ExrsError {
+    guard(|| {
+        let path = match string(path) {
+            Some(path) if !image.is_null() => path,
+            _ => return fail(ExrsError::Argument, "invalid path or image pointer"),
+        };
+
+        match read_all_flat_layers_from_file(path) {
+            Ok(flat_image) => {
+                *image = Box::into_raw(Box::new(ExrsImage::from_flat_image(flat_image)));
+                ExrsError::Ok
+            }
+
+            Err(error) => from_exr_error(error),
+        }
+    })
+}
+
+/// Create an image without any parts, to be filled with `exrs_image_add_part`.
+/// Must be released with `exrs_image_free`.
+#[no_mangle]
+pub extern "C" fn exrs_image_new() -> *mut ExrsImage {
+    Box::into_raw(Box::new(ExrsImage { parts: Vec::new() }))
+}
+
+/// Release an image and all strings returned for it. Does nothing for null pointers.
+///
+/// # Safety
+/// `image` must have been returned by this API, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_image_free(image: *mut ExrsImage) {
+    if !image.is_null() {
+        drop(Box::from_raw(image));
+    }
+}
+
+/// The number of parts of the image. Returns 0 for null pointers.
+///
+/// # Safety
+/// `image` must be null or a valid image.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_image_part_count(image: *const ExrsImage) -> usize {
+    image.as_ref().map_or(0, |image| image.parts.len())
+}
+
+/// The name of a part, or null if the part has no name or does not exist.
+///
+/// # Safety
+/// `image` must be null or a valid image.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_part_name(image: *const ExrsImage, part: usize) -> *const c_char {
+    image
+        .as_ref()
+        .and_then(|image| image.parts.get(part))
+        .and_then(|part| part.name.as_ref())
+        .map_or(ptr::null(), |name| name.as_ptr())
+}
+
+/// The width and height of a part in pixels.
+///
+/// # Safety
+/// `image` must be null or a valid image, `width` and `height` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_part_size(
+    image: *const ExrsImage,
+    part: usize,
+    width: *mut usize,
+    height: *mut usize,
+) -> ExrsError {
+    guard(|| {
+        let image = match image.as_ref() {
+            Some(image) if !width.is_null() && !height.is_null() => image,
+            _ => return fail(ExrsError::Argument, "null pointer"),
+        };
+
+        match image.part(part) {
+            Ok(part) => {
+                *width = part.size.width();
+                *height = part.size.height();
+                ExrsError::Ok
+            }
+
+            Err(code) => code,
+        }
+    })
+}
+
+/// The number of channels of a part. Returns 0 if the part does not exist.
+///
+/// # Safety
+/// `image` must be null or a valid image.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_part_channel_count(image: *const ExrsImage, part: usize) -> usize {
+    image
+        .as_ref()
+        .and_then(|image| image.parts.get(part))
+        .map_or(0, |part| part.channels.len())
+}
+
+/// The name of a channel, or null if the channel does not exist.
+/// Channels are sorted alphabetically.
+///
+/// # Safety
+/// `image` must be null or a valid image.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_channel_name(
+    image: *const ExrsImage,
+    part: usize,
+    channel: usize,
+) -> *const c_char {
+    image
+        .as_ref()
+        .and_then(|image| image.parts.get(part))
+        .and_then(|part| part.channels.get(channel))
+        .map_or(ptr::null(), |channel| channel.name.as_ptr())
+}
+
+/// The sample type of a channel, and the number of samples.
+/// Subsampled channels contain fewer samples than the part has pixels.
+///
+/// # Safety
+/// `image` must be null or a valid image, `sample_type` and `sample_count` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_channel_info(
+    image: *const ExrsImage,
+    part: usize,
+    channel: usize,
+    sample_type: *mut ExrsSampleType,
+    sample_count: *mut usize,
+) -> ExrsError {
+    guard(|| {
+        let image = match image.as_ref() {
+            Some(image) if !sample_type.is_null() && !sample_count.is_null() => image,
+            _ => return fail(ExrsError::Argument, "null pointer"),
+        };
+
+        match image.channel(part, channel) {
+            Ok(channel) => {
+                *sample_type = match channel.samples {
+                    FlatSamples::F16(_) => ExrsSampleType::F16,
+                    FlatSamples::F32(_) => ExrsSampleType::F32,
+                    FlatSamples::U32(_) => ExrsSampleType::U32,
+                };
+
+                *sample_count = channel.samples.len();
+                ExrsError::Ok
+            }
+
+            Err(code) => code,
+        }
+    })
+}
+
+/// The samples of a channel in their own type, without copying, row after row.
+/// Stores the size of the buffer in bytes in `byte_size`. Returns null if the channel does not exist.
+/// The buffer stays valid until the image is freed or modified.
+///
+/// # Safety
+/// `image` must be null or a valid image, and `byte_size` must be null or point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_channel_data(
+    image: *const ExrsImage,
+    part: usize,
+    channel: usize,
+    byte_size: *mut usize,
+) -> *const c_void {
+    let channel = image
+        .as_ref()
+        .and_then(|image| image.parts.get(part))
+        .and_then(|part| part.channels.get(channel));
+
+    let (pointer, size) = match channel.map(|channel| &channel.samples) {
+        Some(FlatSamples::F16(samples)) => (samples.as_ptr() as *const c_void, samples.len() * 2),
+        Some(FlatSamples::F32(samples)) => (samples.as_ptr() as *const c_void, samples.len() * 4),
+        Some(FlatSamples::U32(samples)) => (samples.as_ptr() as *const c_void, samples.len() * 4),
+        None => (ptr::null(), 0),
+    };
+
+    if !byte_size.is_null() {
+        *byte_size = size;
+    }
+
+    pointer
+}
+
+/// Copy the samples of a channel of any type into a `float` buffer, converting them.
+/// The buffer must have room for the sample count returned by `exrs_channel_info`.
+///
+/// # Safety
+/// `image` must be null or a valid image, and `buffer` must point to `buffer_length` writable floats.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_channel_read_f32(
+    image: *const ExrsImage,
+    part: usize,
+    channel: usize,
+    buffer: *mut f32,
+    buffer_length: usize,
+) -> ExrsError {
+    guard(|| {
+        let image = match image.as_ref() {
+            Some(image) if !buffer.is_null() => image,
+            _ => return fail(ExrsError::Argument, "null pointer"),
+        };
+
+        let channel = match image.channel(part, channel) {
+            Ok(channel) => channel,
+            Err(code) => return code,
+        };
+
+        if buffer_length < channel.samples.len() {
+            return fail(
+                ExrsError::Argument,
+                format!(
+                    "the buffer has room for {} samples, but the channel contains {}",
+                    buffer_length,
+                    channel.samples.len()
+                ),
+            );
+        }
+
+        let buffer = slice::from_raw_parts_mut(buffer, channel.samples.len());
+        for (target, value) in buffer.iter_mut().zip(channel.samples.values_as_f32()) {
+            *target = value;
+        }
+
+        ExrsError::Ok
+    })
+}
+
+/// Add a part to the image, and store its index in `part` if not null.
+/// `name` may be null for single-part files. `compression` is a name like `"zip"`, `"piz"`,
+/// or `"dwab"`, or null for zip compression.
+///
+/// # Safety
+/// `image` must be a valid image, `name` and `compression` must be null or null-terminated UTF-8 strings,
+/// and `part` must be null or point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_image_add_part(
+    image: *mut ExrsImage,
+    name: *const c_char,
+    width: usize,
+    height: usize,
+    compression: *const c_char,
+    part: *mut usize,
+) -> ExrsError {
+    guard(|| {
+        let image = match image.as_mut() {
+            Some(image) => image,
+            None => return fail(ExrsError::Argument, "null pointer"),
+        };
+
+        let name = if name.is_null() {
+            None
+        } else {
+            match string(name) {
+                Some(name) => Some(cstring(name.to_string())),
+                None => return fail(ExrsError::Argument, "part name is not valid UTF-8"),
+            }
+        };
+
+        let compression = if compression.is_null() {
+            Compression::ZIP16
+        } else {
+            match string(compression).and_then(parse_compression) {
+                Some(compression) => compression,
+                None => return fail(ExrsError::Argument, "unknown compression"),
+            }
+        };
+
+        if !part.is_null() {
+            *part = image.parts.len();
+        }
+
+        image.parts.push(Part {
+            name,
+            size: Vec2(width, height),
+            encoding: Encoding {
+                compression,
+                blocks: Blocks::ScanLines,
+                line_order: LineOrder::Increasing,
+            },
+            channels: Vec::new(),
+        });
+
+        ExrsError::Ok
+    })
+}
+
+/// Add a channel to a part, copying the samples, which are stored row after row.
+/// `samples` must contain `width * height` values of the sample type:
+/// `uint16_t` half bits, `float`, or `uint32_t`.
+///
+/// # Safety
+/// `image` must be a valid image, `name` a null-terminated UTF-8 string,
+/// and `samples` must point to `sample_count` values of the sample type.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_part_add_channel(
+    image: *mut ExrsImage,
+    part: usize,
+    name: *const c_char,
+    sample_type: ExrsSampleType,
+    samples: *const c_void,
+    sample_count: usize,
+) -> ExrsError {
+    guard(|| {
+        let (image, name) = match (image.as_mut(), string(name)) {
+            (Some(image), Some(name)) => (image, name),
+            _ => return fail(ExrsError::Argument, "invalid image or channel name"),
+        };
+
+        if Text::new_or_none(name).is_none() {
+            return fail(ExrsError::Argument, "invalid channel name");
+        }
+
+        let target = match image.parts.get_mut(part) {
+            Some(target) => target,
+            None => return fail(ExrsError::Argument, "part index out of range"),
+        };
+
+        if sample_count != target.size.area() {
+            return fail(
+                ExrsError::Argument,
+                format!(
+                    "the part has {} pixels, but {} samples were provided",
+                    target.size.area(),
+                    sample_count
+                ),
+            );
+        }
+
+        let samples = match sample_type {
+            ExrsSampleType::F16 => slice_from(samples as *const u16, sample_count).map(|bits| {
+                FlatSamples::F16(bits.iter().map(|&bits| f16::from_bits(bits)).collect())
+            }),
+            ExrsSampleType::F32 => slice_from(samples as *const f32, sample_count)
+                .map(|values| FlatSamples::F32(values.to_vec())),
+            ExrsSampleType::U32 => slice_from(samples as *const u32, sample_count)
+                .map(|values| FlatSamples::U32(values.to_vec())),
+        };
+
+        let samples = match samples {
+            Some(samples) => samples,
+            None => return fail(ExrsError::Argument, "null sample pointer"),
+        };
+
+        target.channels.push(Channel {
+            name: cstring(name.to_string()),
+            sampling: Vec2(1, 1),
+            samples,
+        });
+
+        ExrsError::Ok
+    })
+}
+
+/// Write all parts of the image to a file. Every part needs at least one channel.
+///
+/// # Safety
+/// `image` must be a valid image, and `path` a null-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn exrs_image_write(
+    image: *const ExrsImage,
+    path: *const c_char,
+) -> ExrsError {
+    guard(|| {
+        let (image, path) = match (image.as_ref(), string(path)) {
+            (Some(image), Some(path)) => (image, path),
+            _ => return fail(ExrsError::Argument, "invalid image or path"),
+        };
+
+        if image.parts.is_empty() {
+            return fail(ExrsError::Argument, "the image has no parts");
+        }
+
+        let result = image
+            .to_flat_image()
+            .and_then(|flat_image| flat_image.write().to_file(path));
+
+        match result {
+            Ok(()) => ExrsError::Ok,
+            Err(error) => from_exr_error(error),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn c_string(text: &str) -> CString {
+        CString::new(text).unwrap()
+    }
+
+    #[test]
+    fn write_and_read_parts() {
+        let path = std::env::temp_dir().join("exrs_capi_test.exr");
+        let path = c_string(path.to_str().unwrap());
+
+        unsafe {
+            let image = exrs_image_new();
+
+            let mut part = usize::MAX;
+            let name = c_string("beauty");
+            let compression = c_string("piz");
+            assert_eq!(
+                exrs_image_add_part(image, name.as_ptr(), 3, 2, compression.as_ptr(), &mut part),
+                ExrsError::Ok
+            );
+            assert_eq!(part, 0);
+
+            let red = [0.0_f32, 0.5, 1.0, 1.5, 2.0, 2.5];
+            let ids = [1_u32, 2, 3, 4, 5, 6];
+            let r = c_string("R");
+            let id = c_string("id");
+
+            assert_eq!(
+                exrs_part_add_channel(
+                    image,
+                    0,
+                    r.as_ptr(),
+                    ExrsSampleType::F32,
+                    red.as_ptr() as *const c_void,
+                    6
+                ),
+                ExrsError::Ok
+            );
+            assert_eq!(
+                exrs_part_add_channel(
+                    image,
+                    0,
+                    id.as_ptr(),
+                    ExrsSampleType::U32,
+                    ids.as_ptr() as *const c_void,
+                    6
+                ),
+                ExrsError::Ok
+            );
+            assert_eq!(
+                exrs_part_add_channel(
+                    image,
+                    0,
+                    r.as_ptr(),
+                    ExrsSampleType::F32,
+                    red.as_ptr() as *const c_void,
+                    5
+                ),
+                ExrsError::Argument
+            );
+            assert!(!exrs_last_error_message().is_null());
+
+            assert_eq!(exrs_image_write(image, path.as_ptr()), ExrsError::Ok);
+            exrs_image_free(image);
+
+            let mut read = ptr::null_mut();
+            assert_eq!(exrs_image_read(path.as_ptr(), &mut read), ExrsError::Ok);
+            assert_eq!(exrs_image_part_count(read), 1);
+            assert_eq!(
+                CStr::from_ptr(exrs_part_name(read, 0)).to_str(),
+                Ok("beauty")
+            );
+
+            let (mut width, mut height) = (0, 0);
+            assert_eq!(
+                exrs_part_size(read, 0, &mut width, &mut height),
+                ExrsError::Ok
+            );
+            assert_eq!((width, height), (3, 2));
+
+            assert_eq!(exrs_part_channel_count(read, 0), 2);
+            assert_eq!(
+                CStr::from_ptr(exrs_channel_name(read, 0, 0)).to_str(),
+                Ok("R")
+            );
+
+            let mut sample_type = ExrsSampleType::F16;
+            let mut sample_count = 0;
+            assert_eq!(
+                exrs_channel_info(read, 0, 1, &mut sample_type, &mut sample_count),
+                ExrsError::Ok
+            );
+            assert_eq!((sample_type, sample_count), (ExrsSampleType::U32, 6));
+
+            let mut values = [0.0_f32; 6];
+            assert_eq!(
+                exrs_channel_read_f32(read, 0, 0, values.as_mut_ptr(), 6),
+                ExrsError::Ok
+            );
+            assert_eq!(values, red);
+
+            let mut byte_size = 0;
+            let data = exrs_channel_data(read, 0, 1, &mut byte_size) as *const u32;
+            assert_eq!(byte_size, 24);
+            assert_eq!(slice::from_raw_parts(data, 6), &ids);
+
+            assert!(exrs_channel_name(read, 0, 2).is_null());
+            exrs_image_free(read);
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = c_string("does/not/exist.exr");
+        let mut image = ptr::null_mut();
+
+        unsafe {
+            assert_eq!(exrs_image_read(path.as_ptr(), &mut image), ExrsError::Io);
+            assert!(image.is_null());
+            assert!(!exrs_last_error_message().is_null());
+        }
+    }
+}
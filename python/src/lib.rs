@@ -0,0 +1,409 @@
+//! Python bindings for the `exr` crate.
+//!
+//! Flat channels are exchanged as two-dimensional numpy arrays, indexed by `[y, x]`.
+//! Deep layers are exchanged as the number of samples of each pixel, and one flat array
+//! per channel that contains the samples of all pixels, pixel after pixel.
+//! The sample types `float16`, `float32`, and `uint32` are kept as they are.
+
+use std::path::PathBuf;
+
+use exr::error::Error;
+use exr::image::deep::{DeepChannelData, DeepSamples};
+use exr::image::read::deep::read_all_deep_layers_from_file;
+use exr::image::write::deep::write_deep_image_to_file;
+use exr::meta::describe::{parse_compression, JsonValue};
+use exr::prelude::*;
+use half::f16;
+use numpy::{Element, PyArray1, PyArrayMethods, PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+#[pymodule]
+fn exrs(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(read, module)?)?;
+    module.add_function(wrap_pyfunction!(write, module)?)?;
+    module.add_function(wrap_pyfunction!(read_deep, module)?)?;
+    module.add_function(wrap_pyfunction!(write_deep, module)?)?;
+    module.add_function(wrap_pyfunction!(read_header, module)?)?;
+    Ok(())
+}
+
+/// File system errors become `OSError`, invalid and unsupported files become `ValueError`.
+fn to_python_error(error: Error) -> PyErr {
+    match error {
+        Error::Io(error) => error.into(),
+        error => PyValueError::new_err(error.to_string()),
+    }
+}
+
+fn compression_from_name(name: &str) -> PyResult<Compression> {
+    parse_compression(name)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown compression '{}'", name)))
+}
+
+fn text(name: &str) -> PyResult<Text> {
+    Text::new_or_none(name).ok_or_else(|| PyValueError::new_err(format!("invalid name '{}'", name)))
+}
+
+fn scan_line_encoding(compression: Compression) -> Encoding {
+    Encoding {
+        compression,
+        blocks: Blocks::ScanLines,
+        line_order: LineOrder::Increasing,
+    }
+}
+
+/// A dictionary with the `name` and the `size` of a layer, as `(width, height)`.
+fn layer_dictionary<'py>(
+    py: Python<'py>,
+    attributes: &LayerAttributes,
+    size: Vec2<usize>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dictionary = PyDict::new_bound(py);
+    dictionary.set_item("name", attributes.layer_name.as_ref().map(Text::to_string))?;
+    dictionary.set_item("size", (size.width(), size.height()))?;
+    Ok(dictionary)
+}
+
+fn array_2d<T: Element>(
+    py: Python<'_>,
+    samples: Vec<T>,
+    size: Vec2<usize>,
+) -> PyResult<Bound<'_, PyAny>> {
+    let array = PyArray1::from_vec_bound(py, samples).reshape([size.height(), size.width()])?;
+    Ok(array.into_any())
+}
+
+/// Read all flat layers of a file.
+/// Returns a list of dictionaries with `name`, `size`, and `channels`,
+/// which maps each channel name to an array of shape `(height, width)`.
+/// Subsampled channels have a smaller shape.
+#[pyfunction]
+fn read(py: Python<'_>, path: PathBuf) -> PyResult<Bound<'_, PyList>> {
+    let image = py
+        .allow_threads(|| read_all_flat_layers_from_file(&path))
+        .map_err(to_python_error)?;
+
+    let layers = PyList::empty_bound(py);
+
+    for layer in image.layer_data {
+        let dictionary = layer_dictionary(py, &layer.attributes, layer.size)?;
+        let channels = PyDict::new_bound(py);
+
+        for channel in layer.channel_data.list {
+            let size = layer.size / channel.sampling;
+
+            let array = match channel.sample_data {
+                FlatSamples::F16(samples) => array_2d(py, samples, size)?,
+                FlatSamples::F32(samples) => array_2d(py, samples, size)?,
+                FlatSamples::U32(samples) => array_2d(py, samples, size)?,
+            };
+
+            channels.set_item(channel.name.to_string(), array)?;
+        }
+
+        dictionary.set_item("channels", channels)?;
+        layers.append(dictionary)?;
+    }
+
+    Ok(layers)
+}
+
+fn extract_2d<T: Element + Copy>(array: &Bound<'_, PyAny>) -> Option<(Vec<T>, Vec2<usize>)> {
+    let array = array.extract::<PyReadonlyArray2<'_, T>>().ok()?;
+    let view = array.as_array();
+    let (height, width) = view.dim();
+    Some((view.iter().copied().collect(), Vec2(width, height)))
+}
+
+fn flat_samples(array: &Bound<'_, PyAny>) -> PyResult<(FlatSamples, Vec2<usize>)> {
+    if let Some((samples, size)) = extract_2d::<f16>(array) {
+        Ok((FlatSamples::F16(samples), size))
+    } else if let Some((samples, size)) = extract_2d::<f32>(array) {
+        Ok((FlatSamples::F32(samples), size))
+    } else if let Some((samples, size)) = extract_2d::<u32>(array) {
+        Ok((FlatSamples::U32(samples), size))
+    } else {
+        Err(PyTypeError::new_err(
+            "channels must be two-dimensional float16, float32, or uint32 arrays",
+        ))
+    }
+}
+
+/// Write flat layers to a file. Each layer is a dictionary with `channels`,
+/// which maps channel names to arrays of shape `(height, width)`, and an optional `name`.
+/// All channels of a layer must have the same shape.
+#[pyfunction]
+#[pyo3(signature = (path, layers, compression = "zip"))]
+fn write(
+    py: Python<'_>,
+    path: PathBuf,
+    layers: &Bound<'_, PyList>,
+    compression: &str,
+) -> PyResult<()> {
+    let compression = compression_from_name(compression)?;
+    let mut flat_layers = Vec::new();
+
+    for layer in layers.iter() {
+        let layer = layer.downcast::<PyDict>()?;
+
+        let attributes = match layer.get_item("name")? {
+            Some(name) if !name.is_none() => {
+                LayerAttributes::named(text(&name.extract::<String>()?)?)
+            }
+            _ => LayerAttributes::default(),
+        };
+
+        let channels = layer
+            .get_item("channels")?
+            .ok_or_else(|| PyValueError::new_err("layer has no 'channels'"))?;
+
+        let mut size = None;
+        let mut list = SmallVec::new();
+
+        for (name, array) in channels.downcast::<PyDict>()?.iter() {
+            let (samples, channel_size) = flat_samples(&array)?;
+
+            if *size.get_or_insert(channel_size) != channel_size {
+                return Err(PyValueError::new_err(
+                    "all channels of a layer must have the same shape",
+                ));
+            }
+
+            list.push(AnyChannel::new(text(&name.extract::<String>()?)?, samples));
+        }
+
+        let size = size.ok_or_else(|| PyValueError::new_err("layer has no channels"))?;
+
+        flat_layers.push(Layer::new(
+            size,
+            attributes,
+            scan_line_encoding(compression),
+            AnyChannels::sort(list),
+        ));
+    }
+
+    if flat_layers.is_empty() {
+        return Err(PyValueError::new_err("at least one layer is required"));
+    }
+
+    let display_size = flat_layers.iter().fold(Vec2(0, 0), |size, layer| {
+        Vec2(
+            size.width().max(layer.size.width()),
+            size.height().max(layer.size.height()),
+        )
+    });
+
+    let image = Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions(display_size)),
+        flat_layers,
+    );
+
+    py.allow_threads(|| image.write().to_file(&path))
+        .map_err(to_python_error)
+}
+
+/// Read all deep layers of a file.
+/// Returns a list of dictionaries with `name`, `size`, `sample_counts`, and `channels`.
+/// The sample counts are an array of shape `(height, width)`. Each channel is a flat array
+/// with the samples of all pixels, such that the samples of a pixel start
+/// at the sum of the sample counts of all previous pixels.
+#[pyfunction]
+fn read_deep(py: Python<'_>, path: PathBuf) -> PyResult<Bound<'_, PyList>> {
+    let image = py
+        .allow_threads(|| read_all_deep_layers_from_file(&path))
+        .map_err(to_python_error)?;
+
+    let layers = PyList::empty_bound(py);
+
+    for layer in image.layer_data {
+        let dictionary = layer_dictionary(py, &layer.attributes, layer.size)?;
+        let channels = PyDict::new_bound(py);
+
+        // the samples of all channels are stored in the first channel
+        let samples = match layer.channel_data.list.first() {
+            Some(first) => &first.sample_data,
+            None => continue,
+        };
+
+        let counts: Vec<u32> = (0..samples.pixel_count())
+            .map(|index| samples.sample_count_at_index(index) as u32)
+            .collect();
+
+        dictionary.set_item("sample_counts", array_2d(py, counts, layer.size)?)?;
+
+        for (channel, data) in layer.channel_data.list.iter().zip(&samples.channels) {
+            let array = match data {
+                DeepChannelData::F16(data) => PyArray1::from_slice_bound(py, data).into_any(),
+                DeepChannelData::F32(data) => PyArray1::from_slice_bound(py, data).into_any(),
+                DeepChannelData::U32(data) => PyArray1::from_slice_bound(py, data).into_any(),
+            };
+
+            channels.set_item(channel.name.to_string(), array)?;
+        }
+
+        dictionary.set_item("channels", channels)?;
+        layers.append(dictionary)?;
+    }
+
+    Ok(layers)
+}
+
+fn extract_1d<T: Element + Copy>(array: &Bound<'_, PyAny>) -> Option<Vec<T>> {
+    let array = array.extract::<PyReadonlyArray1<'_, T>>().ok()?;
+    let samples = array.as_array().iter().copied().collect();
+    Some(samples)
+}
+
+fn deep_samples(array: &Bound<'_, PyAny>) -> PyResult<DeepChannelData> {
+    if let Some(samples) = extract_1d::<f16>(array) {
+        Ok(DeepChannelData::F16(samples))
+    } else if let Some(samples) = extract_1d::<f32>(array) {
+        Ok(DeepChannelData::F32(samples))
+    } else if let Some(samples) = extract_1d::<u32>(array) {
+        Ok(DeepChannelData::U32(samples))
+    } else {
+        Err(PyTypeError::new_err(
+            "deep channels must be one-dimensional float16, float32, or uint32 arrays",
+        ))
+    }
+}
+
+/// Write a single deep layer to a file.
+/// The sample counts are an array of shape `(height, width)`, and each channel is a flat array
+/// that contains as many samples as the sample counts add up to, in the layout of `read_deep`.
+#[pyfunction]
+#[pyo3(signature = (path, sample_counts, channels, compression = "zip", name = None))]
+fn write_deep(
+    py: Python<'_>,
+    path: PathBuf,
+    sample_counts: PyReadonlyArray2<'_, u32>,
+    channels: &Bound<'_, PyDict>,
+    compression: &str,
+    name: Option<&str>,
+) -> PyResult<()> {
+    let compression = compression_from_name(compression)?;
+
+    let counts = sample_counts.as_array();
+    let (height, width) = counts.dim();
+
+    let mut total: u32 = 0;
+    let mut offsets = Vec::with_capacity(width * height);
+
+    for &count in counts.iter() {
+        total = total
+            .checked_add(count)
+            .ok_or_else(|| PyValueError::new_err("too many samples"))?;
+
+        offsets.push(total);
+    }
+
+    let mut samples = DeepSamples::new(width, height);
+    samples
+        .set_cumulative_counts(offsets)
+        .map_err(to_python_error)?;
+
+    let mut named_data = Vec::new();
+    for (channel_name, array) in channels.iter() {
+        let channel_name: String = channel_name.extract()?;
+        let data = deep_samples(&array)?;
+
+        if data.len() != total as usize {
+            return Err(PyValueError::new_err(format!(
+                "channel '{}' has {} samples, but the sample counts add up to {}",
+                channel_name,
+                data.len(),
+                total
+            )));
+        }
+
+        named_data.push((text(&channel_name)?, data));
+    }
+
+    if named_data.is_empty() {
+        return Err(PyValueError::new_err("at least one channel is required"));
+    }
+
+    // the channels of a file are sorted alphabetically
+    named_data.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let names: Vec<Text> = named_data.iter().map(|(name, _)| name.clone()).collect();
+    samples.channels = named_data.into_iter().map(|(_, data)| data).collect();
+
+    // like the deep reader, store the samples of all channels in the first channel
+    let mut samples = Some(samples);
+    let list = names
+        .into_iter()
+        .map(|name| AnyChannel {
+            quantize_linearly: ChannelDescription::guess_quantization_linearity(&name),
+            name,
+            sample_data: samples.take().unwrap_or_else(|| DeepSamples::new(0, 0)),
+            sampling: Vec2(1, 1),
+        })
+        .collect();
+
+    let attributes = match name {
+        Some(name) => LayerAttributes::named(text(name)?),
+        None => LayerAttributes::default(),
+    };
+
+    let size = Vec2(width, height);
+    let image = Image {
+        attributes: ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+        layer_data: Layer {
+            channel_data: AnyChannels { list },
+            attributes,
+            size,
+            encoding: scan_line_encoding(compression),
+        },
+    };
+
+    py.allow_threads(|| write_deep_image_to_file(&path, &image, compression))
+        .map_err(to_python_error)
+}
+
+/// Read the headers of a file without reading any pixels.
+/// Returns the same document as `exrs info --json`, as nested dictionaries and lists.
+#[pyfunction]
+fn read_header(py: Python<'_>, path: PathBuf) -> PyResult<PyObject> {
+    let meta_data = py
+        .allow_threads(|| MetaData::read_from_file(&path, false))
+        .map_err(to_python_error)?;
+
+    json_to_python(py, &meta_data.describe())
+}
+
+fn json_to_python(py: Python<'_>, value: &JsonValue) -> PyResult<PyObject> {
+    Ok(match value {
+        JsonValue::Null => py.None(),
+        JsonValue::Bool(value) => value.to_object(py),
+        JsonValue::String(text) => text.to_object(py),
+
+        JsonValue::Number(number) => match number.parse::<i64>() {
+            Ok(integer) => integer.to_object(py),
+            Err(_) => match number.parse::<f64>() {
+                Ok(float) => float.to_object(py),
+                Err(_) => number.to_object(py),
+            },
+        },
+
+        JsonValue::Array(values) => {
+            let list = PyList::empty_bound(py);
+            for value in values {
+                list.append(json_to_python(py, value)?)?;
+            }
+
+            list.into_any().unbind()
+        }
+
+        JsonValue::Object(fields) => {
+            let dictionary = PyDict::new_bound(py);
+            for (name, value) in fields {
+                dictionary.set_item(name, json_to_python(py, value)?)?;
+            }
+
+            dictionary.into_any().unbind()
+        }
+    })
+}
@@ -0,0 +1,43 @@
+import numpy as np
+import pytest
+
+import exrs
+
+
+def test_flat_round_trip(tmp_path):
+    red = np.linspace(0, 1, 12, dtype=np.float32).reshape(3, 4)
+    ids = np.arange(12, dtype=np.uint32).reshape(3, 4)
+    path = str(tmp_path / "flat.exr")
+
+    exrs.write(path, [{"name": "main", "channels": {"R": red, "id": ids}}])
+    layer = exrs.read(path)[0]
+
+    assert layer["name"] == "main"
+    assert layer["size"] == (4, 3)
+    np.testing.assert_array_equal(layer["channels"]["R"], red)
+    np.testing.assert_array_equal(layer["channels"]["id"], ids)
+
+    header = exrs.read_header(path)
+    assert header["layers"][0]["name"] == "main"
+
+
+def test_deep_round_trip(tmp_path):
+    counts = np.array([[0, 2], [1, 3]], dtype=np.uint32)
+    z = np.arange(6, dtype=np.float32)
+    alpha = np.full(6, 0.5, dtype=np.float16)
+    path = str(tmp_path / "deep.exr")
+
+    exrs.write_deep(path, counts, {"Z": z, "A": alpha})
+    layer = exrs.read_deep(path)[0]
+
+    np.testing.assert_array_equal(layer["sample_counts"], counts)
+    np.testing.assert_array_equal(layer["channels"]["Z"], z)
+    np.testing.assert_array_equal(layer["channels"]["A"], alpha)
+
+
+def test_invalid_input(tmp_path):
+    with pytest.raises(OSError):
+        exrs.read(str(tmp_path / "missing.exr"))
+
+    with pytest.raises(ValueError):
+        exrs.write_deep(str(tmp_path / "deep.exr"), np.ones((2, 2), dtype=np.uint32), {"Z": np.zeros(3, dtype=np.float32)})
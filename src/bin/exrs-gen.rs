@@ -58,7 +58,7 @@ fn run_viewer(args: &[String]) -> ExitCode {
         i += 1;
     }
     
-    let config = ViewerConfig { verbose };
+    let config = ViewerConfig { verbose, ..Default::default() };
     
     let exit_code = match file {
         Some(path) => run(path, config),
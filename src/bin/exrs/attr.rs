@@ -0,0 +1,577 @@
+//! `exrs attr`: print, set, or delete header attributes of existing files, without decompressing the pixels.
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use exr::math::Vec2;
+use exr::meta::attribute::{
+    type_names, AttributeValue, Chromaticities, FloatRect, IntegerBounds, Text, TimeCode,
+};
+use exr::meta::describe::JsonValue;
+use exr::meta::edit::{edit_header_attributes, standard_attribute_type, HeaderEditResult};
+use exr::meta::header::Header;
+use exr::meta::MetaData;
+
+pub fn run(args: &[String]) -> ExitCode {
+    let command = match Command::parse(args) {
+        Ok(Some(command)) => command,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut success = true;
+    for file in &command.files {
+        if let Err(message) = command.apply(file) {
+            eprintln!("Error: {}: {message}", file.display());
+            success = false;
+        }
+    }
+
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    /// Print the attributes with these names, or all attributes if empty.
+    Get(Vec<String>),
+
+    Set {
+        name: String,
+        value: String,
+
+        /// Infer the type if not specified.
+        kind: Option<String>,
+    },
+
+    Delete(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Command {
+    action: Action,
+    files: Vec<PathBuf>,
+
+    /// Apply to all parts if not specified.
+    part: Option<String>,
+}
+
+impl Command {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let (action, args) = match args.split_first() {
+            Some((action, args)) => (action.as_str(), args),
+            None => return Ok(None),
+        };
+
+        let mut positional = Vec::new();
+        let mut part = None;
+        let mut kind = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-p" | "--part" => part = Some(value(arg)?),
+                "-t" | "--type" => kind = Some(value(arg)?),
+
+                // allows values that start with a dash, such as negative numbers
+                "--" => positional.extend(args.by_ref().cloned()),
+
+                _ if !arg.starts_with('-') || arg.parse::<f64>().is_ok() => {
+                    positional.push(arg.clone())
+                }
+
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        let usage = "Use 'exrs attr --help' for usage.";
+
+        let (action, files) = match action {
+            "-h" | "--help" => return Ok(None),
+
+            "get" => {
+                // leading arguments ending with `.exr` are files, the rest are attribute names
+                let split = positional
+                    .iter()
+                    .position(|arg| !arg.to_ascii_lowercase().ends_with(".exr"))
+                    .unwrap_or(positional.len());
+
+                let names = positional.split_off(split);
+                (Action::Get(names), positional)
+            }
+
+            "set" => {
+                if positional.len() < 3 {
+                    return Err(format!("Expected files, a name, and a value. {usage}"));
+                }
+
+                let value = positional.pop().expect("argument count bug");
+                let name = positional.pop().expect("argument count bug");
+                (Action::Set { name, value, kind }, positional)
+            }
+
+            "delete" => {
+                if positional.len() < 2 {
+                    return Err(format!("Expected files and a name. {usage}"));
+                }
+
+                let name = positional.pop().expect("argument count bug");
+                (Action::Delete(name), positional)
+            }
+
+            other => return Err(format!("Unknown attr command '{other}'. {usage}")),
+        };
+
+        if files.is_empty() {
+            return Err(format!("No input files. {usage}"));
+        }
+
+        Ok(Some(Command {
+            action,
+            files: files.into_iter().map(PathBuf::from).collect(),
+            part,
+        }))
+    }
+
+    fn apply(&self, file: &PathBuf) -> Result<(), String> {
+        match &self.action {
+            Action::Get(names) => {
+                let meta_data =
+                    MetaData::read_from_file(file, false).map_err(|error| error.to_string())?;
+
+                for index in self.selected_parts(&meta_data.headers)? {
+                    print_attributes(file, index, &meta_data.headers[index], names)?;
+                }
+
+                Ok(())
+            }
+
+            Action::Set { name, value, kind } => {
+                let name_text =
+                    Text::new_or_none(name).ok_or_else(|| format!("Invalid name '{name}'"))?;
+
+                self.edit(file, |header| {
+                    let kind = match kind {
+                        Some(kind) => kind.as_bytes().to_vec(),
+                        None => infer_type(header, name_text.as_slice(), value),
+                    };
+
+                    let value = parse_value(&kind, value)?;
+                    header
+                        .set_named_attribute(name_text.clone(), value)
+                        .map_err(|error| error.to_string())
+                })
+            }
+
+            Action::Delete(name) => self.edit(file, |header| {
+                header
+                    .remove_named_attribute(name.as_bytes())
+                    .map(|_| ())
+                    .map_err(|error| error.to_string())
+            }),
+        }
+    }
+
+    /// Apply the change to all selected parts of the file.
+    fn edit(
+        &self,
+        file: &PathBuf,
+        mut change: impl FnMut(&mut Header) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut message = None;
+
+        let result = edit_header_attributes(file, |headers| {
+            let parts = self.selected_parts(headers).and_then(|parts| {
+                parts
+                    .into_iter()
+                    .try_for_each(|index| change(&mut headers[index]))
+            });
+
+            // the closure must return an exr error, so the message is passed on separately
+            parts.map_err(|error| {
+                message = Some(error);
+                exr::error::Error::Aborted
+            })
+        });
+
+        match (result, message) {
+            (_, Some(message)) => Err(message),
+            (Err(error), None) => Err(error.to_string()),
+            (Ok(result), None) => {
+                if result == HeaderEditResult::Rewritten {
+                    println!(
+                        "{}: headers did not fit, the file was rewritten",
+                        file.display()
+                    );
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// The indices of the parts selected by index or by name.
+    fn selected_parts(&self, headers: &[Header]) -> Result<Vec<usize>, String> {
+        let part = match &self.part {
+            None => return Ok((0..headers.len()).collect()),
+            Some(part) => part,
+        };
+
+        if let Ok(index) = part.parse::<usize>() {
+            if index < headers.len() {
+                return Ok(vec![index]);
+            }
+
+            return Err(format!("part {index} does not exist"));
+        }
+
+        headers
+            .iter()
+            .position(|header| {
+                header
+                    .own_attributes
+                    .layer_name
+                    .as_ref()
+                    .map_or(false, |name| name.to_string() == *part)
+            })
+            .map(|index| vec![index])
+            .ok_or_else(|| format!("no part named '{part}'"))
+    }
+}
+
+fn print_attributes(
+    file: &PathBuf,
+    part: usize,
+    header: &Header,
+    names: &[String],
+) -> Result<(), String> {
+    let mut attributes: Vec<(Vec<u8>, AttributeValue)> = if names.is_empty() {
+        header
+            .all_named_attributes()
+            .map(|(name, value)| (name.to_vec(), value))
+            .collect()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                header
+                    .named_attribute(name.as_bytes())
+                    .map(|value| (name.as_bytes().to_vec(), value))
+                    .ok_or_else(|| format!("part {part} has no attribute '{name}'"))
+            })
+            .collect::<Result<_, String>>()?
+    };
+
+    if names.is_empty() {
+        attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+
+    for (name, value) in attributes {
+        println!(
+            "{}:{part}: {} ({}) = {}",
+            file.display(),
+            Text::from_slice_unchecked(&name),
+            Text::from_slice_unchecked(value.kind_name()),
+            JsonValue::from(&value).to_compact_string()
+        );
+    }
+
+    Ok(())
+}
+
+/// The type of an existing attribute, otherwise the type of a standard attribute,
+/// otherwise an integer, float, rational, or string, depending on the text.
+fn infer_type(header: &Header, name: &[u8], value: &str) -> Vec<u8> {
+    if let Some(existing) = header.named_attribute(name) {
+        return existing.kind_name().to_vec();
+    }
+
+    if let Some(kind) = standard_attribute_type(name) {
+        return kind.to_vec();
+    }
+
+    let kind = if value.parse::<i32>().is_ok() {
+        type_names::I32
+    } else if value.parse::<f32>().is_ok() {
+        type_names::F32
+    } else if parse_rational(value).is_ok() {
+        type_names::RATIONAL
+    } else {
+        type_names::TEXT
+    };
+
+    kind.to_vec()
+}
+
+/// Parse the text representation of an attribute of the specified type.
+/// Numbers of vectors and matrices are separated by commas or spaces,
+/// boxes are written as `xMin,yMin,xMax,yMax`, as in `exrheader`.
+fn parse_value(kind: &[u8], text: &str) -> Result<AttributeValue, String> {
+    let text_value =
+        |text: &str| Text::new_or_none(text).ok_or_else(|| format!("Invalid text '{text}'"));
+
+    let value = match kind {
+        type_names::TEXT => AttributeValue::Text(text_value(text)?),
+        type_names::TEXT_VECTOR => AttributeValue::TextVector(
+            text.split(',')
+                .map(|item| text_value(item.trim()))
+                .collect::<Result<_, String>>()?,
+        ),
+
+        type_names::I32 => AttributeValue::I32(parse_number(text)?),
+        type_names::F32 => AttributeValue::F32(parse_number(text)?),
+        type_names::F64 => AttributeValue::F64(parse_number(text)?),
+        type_names::RATIONAL => AttributeValue::Rational(parse_rational(text)?),
+
+        type_names::I32VEC2 => {
+            let [x, y] = parse_numbers::<i32, 2>(text)?;
+            AttributeValue::IntVec2(Vec2(x, y))
+        }
+        type_names::F32VEC2 => {
+            let [x, y] = parse_numbers::<f32, 2>(text)?;
+            AttributeValue::FloatVec2(Vec2(x, y))
+        }
+        type_names::I32VEC3 => {
+            let [x, y, z] = parse_numbers::<i32, 3>(text)?;
+            AttributeValue::IntVec3((x, y, z))
+        }
+        type_names::F32VEC3 => {
+            let [x, y, z] = parse_numbers::<f32, 3>(text)?;
+            AttributeValue::FloatVec3((x, y, z))
+        }
+
+        type_names::I32BOX2 => {
+            let [x_min, y_min, x_max, y_max] = parse_numbers::<i32, 4>(text)?;
+            if x_max < x_min || y_max < y_min {
+                return Err(format!(
+                    "Invalid box '{text}', expected 'xMin,yMin,xMax,yMax'"
+                ));
+            }
+
+            let size = Vec2((x_max - x_min) as usize + 1, (y_max - y_min) as usize + 1);
+            AttributeValue::IntegerBounds(IntegerBounds::new(Vec2(x_min, y_min), size))
+        }
+        type_names::F32BOX2 => {
+            let [x_min, y_min, x_max, y_max] = parse_numbers::<f32, 4>(text)?;
+            AttributeValue::FloatRect(FloatRect {
+                min: Vec2(x_min, y_min),
+                max: Vec2(x_max, y_max),
+            })
+        }
+
+        type_names::F32MATRIX3X3 => AttributeValue::Matrix3x3(parse_numbers::<f32, 9>(text)?),
+        type_names::F32MATRIX4X4 => AttributeValue::Matrix4x4(parse_numbers::<f32, 16>(text)?),
+
+        type_names::CHROMATICITIES => {
+            let [rx, ry, gx, gy, bx, by, wx, wy] = parse_numbers::<f32, 8>(text)?;
+            AttributeValue::Chromaticities(Chromaticities {
+                red: Vec2(rx, ry),
+                green: Vec2(gx, gy),
+                blue: Vec2(bx, by),
+                white: Vec2(wx, wy),
+            })
+        }
+
+        type_names::TIME_CODE => {
+            let parts: Vec<u8> = text
+                .split(':')
+                .map(|part| part.parse::<u8>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("Invalid time code '{text}', expected 'hh:mm:ss:ff'"))?;
+
+            match parts.as_slice() {
+                &[hours, minutes, seconds, frame] => AttributeValue::TimeCode(
+                    TimeCode::new(hours, minutes, seconds, frame)
+                        .map_err(|error| error.to_string())?,
+                ),
+                _ => {
+                    return Err(format!(
+                        "Invalid time code '{text}', expected 'hh:mm:ss:ff'"
+                    ))
+                }
+            }
+        }
+
+        other => {
+            return Err(format!(
+                "Setting attributes of type '{}' is not supported",
+                Text::from_slice_unchecked(other)
+            ))
+        }
+    };
+
+    Ok(value)
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("Invalid number '{text}'"))
+}
+
+fn parse_numbers<T: std::str::FromStr + Copy + Default, const N: usize>(
+    text: &str,
+) -> Result<[T; N], String> {
+    let numbers: Vec<T> = text
+        .split(|char: char| char == ',' || char.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(parse_number)
+        .collect::<Result<_, String>>()?;
+
+    <[T; N]>::try_from(numbers)
+        .map_err(|numbers| format!("Expected {N} numbers, found {}", numbers.len()))
+}
+
+/// Parses `numerator/denominator`, or a whole number.
+fn parse_rational(text: &str) -> Result<(i32, u32), String> {
+    let invalid = || format!("Invalid rational '{text}', expected 'numerator/denominator'");
+
+    match text.split_once('/') {
+        Some((numerator, denominator)) => {
+            let numerator = numerator.trim().parse().map_err(|_| invalid())?;
+            let denominator = denominator.trim().parse().map_err(|_| invalid())?;
+            Ok((numerator, denominator))
+        }
+
+        None => Ok((text.trim().parse().map_err(|_| invalid())?, 1)),
+    }
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs attr - Print, set, or delete header attributes without decompressing the pixels
+
+USAGE:
+    exrs attr get <FILE.exr>... [NAME]...
+    exrs attr set [OPTIONS] <FILE.exr>... <NAME> <VALUE>
+    exrs attr delete [OPTIONS] <FILE.exr>... <NAME>
+
+OPTIONS:
+    -p, --part <INDEX|NAME>    Only use this part [default: all parts]
+    -t, --type <TYPE>          The type of the new value: string, int, float,
+                               double, rational, v2i, v2f, v3i, v3f, box2i,
+                               box2f, m33f, m44f, stringvector, chromaticities,
+                               timecode
+    -h, --help                 Show this help
+
+Without '--type', the type of the existing attribute or of the standard
+attribute with that name is used. Otherwise, the type is inferred from the
+value: int, float, rational such as '24/1', or string.
+
+Numbers of vectors and matrices are separated by commas. Boxes are written
+as 'xMin,yMin,xMax,yMax', time codes as 'hh:mm:ss:ff', and string vectors
+as comma separated strings. Use '--' before values starting with a dash.
+
+Attributes that describe the pixel data, such as 'channels', 'compression',
+or 'dataWindow', cannot be edited.
+
+EXAMPLES:
+    exrs attr get render.exr owner comments
+    exrs attr set render.exr comments "approved v3"
+    exrs attr set shot.*.exr framesPerSecond 24000/1001
+    exrs attr set -t v2f render.exr screenWindowCenter 0.5,0
+    exrs attr delete -p beauty render.exr comments
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn arguments(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_commands() {
+        let command = Command::parse(&arguments(&[
+            "set",
+            "a.exr",
+            "b.exr",
+            "comments",
+            "approved v3",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            command.files,
+            vec![PathBuf::from("a.exr"), PathBuf::from("b.exr")]
+        );
+        assert_eq!(
+            command.action,
+            Action::Set {
+                name: "comments".to_string(),
+                value: "approved v3".to_string(),
+                kind: None
+            }
+        );
+
+        let command = Command::parse(&arguments(&["get", "a.exr", "owner"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(command.action, Action::Get(vec!["owner".to_string()]));
+
+        let command = Command::parse(&arguments(&["set", "a.exr", "offset", "-2.5"]))
+            .unwrap()
+            .unwrap();
+        assert!(matches!(command.action, Action::Set { ref value, .. } if value == "-2.5"));
+
+        assert!(Command::parse(&arguments(&["delete", "a.exr"])).is_err());
+    }
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(
+            parse_value(type_names::RATIONAL, "24000/1001"),
+            Ok(AttributeValue::Rational((24000, 1001)))
+        );
+        assert_eq!(
+            parse_value(type_names::F32VEC2, "0.5, 1"),
+            Ok(AttributeValue::FloatVec2(Vec2(0.5, 1.0)))
+        );
+        assert_eq!(
+            parse_value(type_names::I32BOX2, "0,0,1919,1079"),
+            Ok(AttributeValue::IntegerBounds(IntegerBounds::new(
+                (0, 0),
+                (1920, 1080)
+            )))
+        );
+        assert!(parse_value(type_names::F32MATRIX3X3, "1,2,3").is_err());
+        assert!(parse_value(type_names::CHANNEL_LIST, "R").is_err());
+    }
+
+    #[test]
+    fn infer_types() {
+        let header = Header::new("layer".into(), (4, 4), Default::default());
+
+        assert_eq!(infer_type(&header, b"xDensity", "72"), type_names::F32);
+        assert_eq!(infer_type(&header, b"shot", "42"), type_names::I32);
+        assert_eq!(infer_type(&header, b"gain", "1.5"), type_names::F32);
+        assert_eq!(infer_type(&header, b"rate", "30/1"), type_names::RATIONAL);
+        assert_eq!(
+            infer_type(&header, b"note", "approved v3"),
+            type_names::TEXT
+        );
+    }
+}
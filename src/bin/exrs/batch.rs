@@ -0,0 +1,662 @@
+//! Processing many files at once: expanding glob and frame sequence patterns,
+//! running jobs on multiple threads, drawing a progress bar, and writing a JSON report.
+
+use std::cmp::Ordering;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use exr::meta::describe::JsonValue;
+
+/// Options shared by all commands that process many files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOptions {
+    /// The number of files processed in parallel.
+    pub threads: usize,
+
+    /// Draw a progress bar on standard error.
+    pub progress: bool,
+
+    /// Write a JSON report of all files to this path.
+    pub report: Option<PathBuf>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            threads: thread::available_parallelism().map_or(1, |count| count.get()),
+            progress: false,
+            report: None,
+        }
+    }
+}
+
+pub fn parse_job_count(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(0) | Err(_) => Err(format!("Invalid job count '{text}'")),
+        Ok(count) => Ok(count),
+    }
+}
+
+/// Whether the path contains wildcards like `*`, or frame numbers like `####` or `%04d`.
+pub fn is_pattern(path: &Path) -> bool {
+    tokenize(&path.to_string_lossy())
+        .iter()
+        .any(|token| !matches!(token, Token::Literal(_)))
+}
+
+/// Replace a pattern by the existing files that match it, in natural order,
+/// such that frame `999` comes before frame `1000`. Paths without patterns are kept,
+/// even if they do not exist, such that the error is reported when processing the file.
+///
+/// Supports `*`, `?`, and `[a-z]` in any part of the path.
+/// Frame numbers are written as `####` or `%04d`, where the count is the minimum number of digits.
+pub fn expand_pattern(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !is_pattern(path) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let components: Vec<_> = path.components().collect();
+    let mut candidates = vec![PathBuf::new()];
+
+    for (index, component) in components.iter().enumerate() {
+        let is_last = index + 1 == components.len();
+        let text = component.as_os_str().to_string_lossy();
+
+        if !is_pattern(Path::new(text.as_ref())) {
+            for candidate in &mut candidates {
+                candidate.push(component);
+            }
+
+            continue;
+        }
+
+        let tokens = tokenize(&text);
+        let mut matches = Vec::new();
+
+        for candidate in &candidates {
+            let directory = if candidate.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                candidate.as_path()
+            };
+
+            // directories that cannot be read simply contain no matches
+            let entries = match fs::read_dir(directory) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name: Vec<char> = name.to_string_lossy().chars().collect();
+
+                if matches_tokens(&tokens, &name) {
+                    let path = candidate.join(entry.file_name());
+
+                    if is_last || path.is_dir() {
+                        matches.push(path);
+                    }
+                }
+            }
+        }
+
+        candidates = matches;
+    }
+
+    candidates.retain(|path| path.is_file());
+    candidates.sort_by(|a, b| natural_order(a, b));
+
+    if candidates.is_empty() {
+        Err(format!("No files match '{}'", path.display()))
+    } else {
+        Ok(candidates)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(char),
+
+    /// `?`
+    AnyCharacter,
+
+    /// `*`
+    AnyText,
+
+    /// `[a-z]` or `[!0-9]`
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+
+    /// `####` or `%04d`
+    Frame {
+        padding: usize,
+    },
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let characters: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < characters.len() {
+        let rest = &characters[index..];
+
+        match rest[0] {
+            '*' => {
+                tokens.push(Token::AnyText);
+                index += 1;
+            }
+
+            '?' => {
+                tokens.push(Token::AnyCharacter);
+                index += 1;
+            }
+
+            '#' => {
+                let padding = rest
+                    .iter()
+                    .take_while(|&&character| character == '#')
+                    .count();
+                tokens.push(Token::Frame { padding });
+                index += padding;
+            }
+
+            '[' => {
+                let negated = matches!(rest.get(1), Some('!') | Some('^'));
+                let start = if negated { 2 } else { 1 };
+
+                // a closing bracket right at the start is a member of the class
+                let end = rest
+                    .iter()
+                    .enumerate()
+                    .skip(start + 1)
+                    .find(|&(_, &character)| character == ']')
+                    .map(|(end, _)| end);
+
+                let end = match end {
+                    Some(end) => end,
+                    None => {
+                        tokens.push(Token::Literal('['));
+                        index += 1;
+                        continue;
+                    }
+                };
+
+                let members = &rest[start..end];
+                let mut ranges = Vec::new();
+                let mut member = 0;
+
+                while member < members.len() {
+                    if member + 2 < members.len() && members[member + 1] == '-' {
+                        ranges.push((members[member], members[member + 2]));
+                        member += 3;
+                    } else {
+                        ranges.push((members[member], members[member]));
+                        member += 1;
+                    }
+                }
+
+                tokens.push(Token::Class { ranges, negated });
+                index += end + 1;
+            }
+
+            '%' => {
+                let digits = rest[1..]
+                    .iter()
+                    .take_while(|character| character.is_ascii_digit())
+                    .count();
+
+                if rest.get(1 + digits) == Some(&'d') {
+                    let padding: String = rest[1..1 + digits].iter().collect();
+                    let padding = padding.parse().unwrap_or(1).max(1);
+                    tokens.push(Token::Frame { padding });
+                    index += digits + 2;
+                } else {
+                    tokens.push(Token::Literal('%'));
+                    index += 1;
+                }
+            }
+
+            character => {
+                tokens.push(Token::Literal(character));
+                index += 1;
+            }
+        }
+    }
+
+    tokens
+}
+
+fn matches_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return text.is_empty(),
+    };
+
+    match token {
+        Token::Literal(literal) => {
+            text.first() == Some(literal) && matches_tokens(rest, &text[1..])
+        }
+
+        Token::AnyCharacter => !text.is_empty() && matches_tokens(rest, &text[1..]),
+
+        Token::AnyText => (0..=text.len()).any(|skip| matches_tokens(rest, &text[skip..])),
+
+        Token::Class { ranges, negated } => match text.first() {
+            Some(&character) => {
+                let contained = ranges
+                    .iter()
+                    .any(|&(first, last)| first <= character && character <= last);
+
+                contained != *negated && matches_tokens(rest, &text[1..])
+            }
+
+            None => false,
+        },
+
+        // numbers longer than the padding must not start with a zero
+        Token::Frame { padding } => {
+            let digits = text
+                .iter()
+                .take_while(|character| character.is_ascii_digit())
+                .count();
+
+            (*padding..=digits).any(|length| {
+                (length == *padding || text[0] != '0') && matches_tokens(rest, &text[length..])
+            })
+        }
+    }
+}
+
+/// Compares numbers in the paths by their value, and everything else by character.
+pub fn natural_order(a: &Path, b: &Path) -> Ordering {
+    let a: Vec<char> = a.to_string_lossy().chars().collect();
+    let b: Vec<char> = b.to_string_lossy().chars().collect();
+    let (mut index_a, mut index_b) = (0, 0);
+
+    while index_a < a.len() && index_b < b.len() {
+        if a[index_a].is_ascii_digit() && b[index_b].is_ascii_digit() {
+            fn number(text: &[char], start: usize) -> (&[char], usize) {
+                let length = text[start..]
+                    .iter()
+                    .take_while(|character| character.is_ascii_digit())
+                    .count();
+
+                let digits = &text[start..start + length];
+                let zeroes = digits
+                    .iter()
+                    .take_while(|&&character| character == '0')
+                    .count();
+                (&digits[zeroes..], length)
+            }
+
+            let (digits_a, length_a) = number(&a, index_a);
+            let (digits_b, length_b) = number(&b, index_b);
+
+            let order = digits_a
+                .len()
+                .cmp(&digits_b.len())
+                .then_with(|| digits_a.cmp(digits_b))
+                .then_with(|| length_a.cmp(&length_b));
+
+            if order != Ordering::Equal {
+                return order;
+            }
+
+            index_a += length_a;
+            index_b += length_b;
+        } else {
+            let order = a[index_a].cmp(&b[index_b]);
+            if order != Ordering::Equal {
+                return order;
+            }
+
+            index_a += 1;
+            index_b += 1;
+        }
+    }
+
+    (a.len() - index_a).cmp(&(b.len() - index_b))
+}
+
+/// Processes all jobs on multiple threads, calling `finished` on the current thread
+/// in the order in which the jobs complete. Returns false if a job panicked.
+pub fn run_jobs<Job, Output, Work, Finished>(
+    jobs: Vec<Job>,
+    threads: usize,
+    work: Work,
+    mut finished: Finished,
+) -> bool
+where
+    Job: Send + 'static,
+    Output: Send + 'static,
+    Work: Fn(&Job) -> Result<Output, String> + Send + Sync + 'static,
+    Finished: FnMut(Job, Result<Output, String>, Duration),
+{
+    let thread_count = threads.min(jobs.len()).max(1);
+    let queue = Arc::new(Mutex::new(jobs.into_iter()));
+    let work = Arc::new(work);
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = (0..thread_count)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let work = Arc::clone(&work);
+            let sender = sender.clone();
+
+            thread::spawn(move || loop {
+                let job = queue.lock().expect("job queue poisoned").next();
+
+                match job {
+                    Some(job) => {
+                        let start = Instant::now();
+                        let result = work(&job);
+
+                        if sender.send((job, result, start.elapsed())).is_err() {
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            })
+        })
+        .collect();
+
+    drop(sender);
+
+    for (job, result, duration) in receiver {
+        finished(job, result, duration);
+    }
+
+    workers.into_iter().all(|worker| worker.join().is_ok())
+}
+
+/// A progress bar on standard error, which is redrawn after each file.
+/// Messages should be printed after calling `clear`, and before calling `advance`.
+pub struct Progress {
+    enabled: bool,
+    total: usize,
+    done: usize,
+    failed: usize,
+    start: Instant,
+}
+
+impl Progress {
+    pub fn new(total: usize, enabled: bool) -> Self {
+        Progress {
+            enabled,
+            total,
+            done: 0,
+            failed: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// Remove the progress bar, such that a message can be printed in its place.
+    pub fn clear(&self) {
+        if self.enabled {
+            eprint!("\r\x1b[K");
+        }
+    }
+
+    /// Count a finished file and redraw the progress bar.
+    pub fn advance(&mut self, success: bool) {
+        self.done += 1;
+        if !success {
+            self.failed += 1;
+        }
+
+        if self.enabled {
+            eprint!("\r\x1b[K{}", self.line());
+            std::io::stderr().flush().ok();
+        }
+    }
+
+    /// Remove the progress bar after the last file.
+    pub fn finish(&self) {
+        self.clear();
+    }
+
+    fn line(&self) -> String {
+        const WIDTH: usize = 30;
+
+        let filled = WIDTH * self.done / self.total.max(1);
+        let bar: String = (0..WIDTH)
+            .map(|index| if index < filled { '#' } else { '-' })
+            .collect();
+
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let remaining = if self.done == 0 {
+            0.0
+        } else {
+            elapsed / self.done as f64 * (self.total - self.done) as f64
+        };
+
+        let failed = if self.failed == 0 {
+            String::new()
+        } else {
+            format!(", {} failed", self.failed)
+        };
+
+        format!(
+            "[{bar}] {}/{} files{failed}, {} remaining",
+            self.done,
+            self.total,
+            duration(remaining)
+        )
+    }
+}
+
+/// Formats seconds as `1:05:09`, `5:09`, or `9s`.
+fn duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else if minutes > 0 {
+        format!("{minutes}:{seconds:02}")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// Collects the results of all files, to write them as a JSON object:
+/// `{ "command", "succeeded", "failed", "seconds", "files": [{ "input", "output", "seconds", "error" or other fields }] }`.
+pub struct Report {
+    command: &'static str,
+    files: Vec<JsonValue>,
+    failed: usize,
+    start: Instant,
+}
+
+impl Report {
+    pub fn new(command: &'static str) -> Self {
+        Report {
+            command,
+            files: Vec::new(),
+            failed: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// Add a file. On success, the fields contain command specific results, such as file sizes.
+    pub fn add(
+        &mut self,
+        input: &Path,
+        output: Option<&Path>,
+        result: Result<Vec<(&str, JsonValue)>, &str>,
+        duration: Duration,
+    ) {
+        let path = |path: &Path| JsonValue::from(path.display().to_string().as_str());
+
+        let mut fields = vec![("input", path(input))];
+        if let Some(output) = output {
+            fields.push(("output", path(output)));
+        }
+
+        fields.push(("seconds", JsonValue::from(duration.as_secs_f64())));
+
+        match result {
+            Ok(results) => {
+                fields.push(("success", JsonValue::Bool(true)));
+                fields.extend(results);
+            }
+
+            Err(message) => {
+                self.failed += 1;
+                fields.push(("success", JsonValue::Bool(false)));
+                fields.push(("error", JsonValue::from(message)));
+            }
+        }
+
+        self.files.push(JsonValue::object(fields));
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("command", JsonValue::from(self.command)),
+            ("succeeded", JsonValue::from(self.files.len() - self.failed)),
+            ("failed", JsonValue::from(self.failed)),
+            (
+                "seconds",
+                JsonValue::from(self.start.elapsed().as_secs_f64()),
+            ),
+            ("files", JsonValue::Array(self.files.clone())),
+        ])
+    }
+
+    /// Write the report to the path, if any.
+    pub fn write(&self, path: Option<&Path>) -> Result<(), String> {
+        match path {
+            Some(path) => fs::write(path, format!("{}\n", self.to_json()))
+                .map_err(|error| format!("{}: {error}", path.display())),
+
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        matches_tokens(&tokenize(pattern), &name)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.exr", "beauty.0001.exr"));
+        assert!(!matches("*.exr", "beauty.0001.png"));
+        assert!(matches("beauty.?.exr", "beauty.1.exr"));
+        assert!(matches("[ab]*.exr", "b.exr"));
+        assert!(!matches("[!ab]*.exr", "b.exr"));
+        assert!(matches("shot_[0-9].exr", "shot_7.exr"));
+        assert!(matches("[.exr", "[.exr"));
+    }
+
+    #[test]
+    fn frame_numbers() {
+        assert!(matches("beauty.####.exr", "beauty.0001.exr"));
+        assert!(matches("beauty.####.exr", "beauty.12345.exr"));
+        assert!(!matches("beauty.####.exr", "beauty.001.exr"));
+        assert!(!matches("beauty.####.exr", "beauty.01234.exr"));
+        assert!(matches("beauty.%04d.exr", "beauty.1001.exr"));
+        assert!(matches("beauty.%d.exr", "beauty.7.exr"));
+        assert!(matches("100%.exr", "100%.exr"));
+
+        assert!(is_pattern(Path::new("renders/beauty.%04d.exr")));
+        assert!(!is_pattern(Path::new("renders/100%.exr")));
+    }
+
+    #[test]
+    fn natural_frame_order() {
+        let mut paths: Vec<PathBuf> = ["f.1000.exr", "f.999.exr", "f.10.exr", "e.exr"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        paths.sort_by(|a, b| natural_order(a, b));
+        assert_eq!(
+            paths,
+            ["e.exr", "f.10.exr", "f.999.exr", "f.1000.exr"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn expand_test_images() {
+        let files =
+            expand_pattern(Path::new("tests/images/valid/openexr/Beachball/*.0001.exr")).unwrap();
+        assert!(files.len() > 1);
+        assert!(files.iter().all(|file| file.is_file()));
+
+        let frames = expand_pattern(Path::new(
+            "tests/images/valid/openexr/Beachball/multipart.####.exr",
+        ))
+        .unwrap();
+        assert!(!frames.is_empty());
+
+        assert!(expand_pattern(Path::new("tests/images/*.nothing")).is_err());
+        assert_eq!(
+            expand_pattern(Path::new("missing.exr")).unwrap(),
+            vec![PathBuf::from("missing.exr")]
+        );
+    }
+
+    #[test]
+    fn run_all_jobs() {
+        let mut results = Vec::new();
+        let success = run_jobs(
+            (0..20).collect(),
+            4,
+            |&job: &i32| {
+                if job % 5 == 0 {
+                    Err(format!("job {job} failed"))
+                } else {
+                    Ok(job * 2)
+                }
+            },
+            |job, result, _| results.push((job, result)),
+        );
+
+        assert!(success);
+        assert_eq!(results.len(), 20);
+        assert_eq!(
+            results.iter().filter(|(_, result)| result.is_err()).count(),
+            4
+        );
+    }
+
+    #[test]
+    fn report_json() {
+        let mut report = Report::new("test");
+        report.add(
+            Path::new("a.exr"),
+            None,
+            Ok(vec![("bytes", JsonValue::from(5_u64))]),
+            Duration::from_millis(10),
+        );
+        report.add(
+            Path::new("b.exr"),
+            Some(Path::new("b.png")),
+            Err("broken"),
+            Duration::from_millis(10),
+        );
+
+        let json = report.to_json().to_string();
+        assert!(json.contains("\"failed\": 1"));
+        assert!(json.contains("broken"));
+    }
+}
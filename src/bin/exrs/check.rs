@@ -0,0 +1,343 @@
+//! `exrs check`: validate the structure of files and decode all of their pixels, like `exrcheck`.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use exr::block::verify::{verify_file, ChunkReport};
+use exr::image::read::deep::read_deep;
+use exr::image::AnyImage;
+use exr::meta::MetaData;
+use exr::prelude::*;
+
+use crate::recompress::find_exr_files;
+use crate::verify::describe_error;
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut files = Vec::new();
+    for input in &options.inputs {
+        if input.is_dir() {
+            let mut found = Vec::new();
+            if let Err(error) = find_exr_files(input, options.recursive, &mut found) {
+                eprintln!("Error: {}: {error}", input.display());
+                return ExitCode::FAILURE;
+            }
+
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(input.clone());
+        }
+    }
+
+    if files.is_empty() {
+        eprintln!("Error: No input files. Use 'exrs check --help' for usage.");
+        return ExitCode::FAILURE;
+    }
+
+    let mut invalid_count = 0;
+    let mut unsupported_count = 0;
+
+    for file in &files {
+        match check(file, &options) {
+            Outcome::Valid => {
+                if !options.quiet {
+                    println!("{}: OK", file.display());
+                }
+            }
+
+            Outcome::Unsupported(reason) => {
+                unsupported_count += 1;
+
+                if !options.quiet {
+                    println!("{}: UNSUPPORTED ({reason})", file.display());
+                }
+            }
+
+            Outcome::Invalid(problems) => {
+                invalid_count += 1;
+                println!("{}: INVALID", file.display());
+
+                for problem in problems {
+                    println!("    {problem}");
+                }
+            }
+        }
+    }
+
+    if files.len() > 1 {
+        println!(
+            "{} files, {} valid, {unsupported_count} unsupported, {invalid_count} invalid",
+            files.len(),
+            files.len() - invalid_count - unsupported_count
+        );
+    }
+
+    if invalid_count == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    inputs: Vec<PathBuf>,
+    recursive: bool,
+
+    /// Only print invalid files.
+    quiet: bool,
+
+    /// Only validate the headers, without reading the offset tables or any pixels.
+    headers_only: bool,
+
+    /// Files that would exceed these limits are invalid, and are not decoded.
+    limits: ReadLimits,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> std::result::Result<Option<Self>, String> {
+        let mut options = Options {
+            inputs: Vec::new(),
+            recursive: false,
+            quiet: false,
+            headers_only: false,
+            limits: ReadLimits::UNLIMITED,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| -> std::result::Result<usize, String> {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))?;
+
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid value '{value}' for '{name}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-r" | "--recursive" => options.recursive = true,
+                "-q" | "--quiet" => options.quiet = true,
+                "--headers-only" => options.headers_only = true,
+                "--max-memory" => {
+                    let mebibytes = value(arg)?;
+                    options.limits.max_decompressed_bytes = mebibytes.saturating_mul(1 << 20);
+                }
+                "--max-pixels" => options.limits.max_pixels = value(arg)?,
+                "--max-samples" => options.limits.max_sample_count = value(arg)?,
+                "--max-parts" => options.limits.max_parts = value(arg)?,
+                _ if !arg.starts_with('-') => options.inputs.push(PathBuf::from(arg)),
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        Ok(Some(options))
+    }
+}
+
+/// The result of checking a file.
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Valid,
+
+    /// The file uses features this implementation cannot decode,
+    /// so its pixels could not be checked completely.
+    Unsupported(String),
+
+    /// The problems that were found by the first step that failed.
+    Invalid(Vec<String>),
+}
+
+impl Outcome {
+    fn from_error(step: &str, error: Error) -> Self {
+        match error {
+            Error::NotSupported(reason) => Outcome::Unsupported(reason.into_owned()),
+            error => Outcome::Invalid(vec![format!("{step}: {error}")]),
+        }
+    }
+}
+
+/// Check the headers, the offset tables, each chunk on its own,
+/// and finally decode all pixels of the file. Stops at the first step that finds problems.
+fn check(path: &Path, options: &Options) -> Outcome {
+    let meta_data = match MetaData::read_from_file(path, false) {
+        Ok(meta_data) => meta_data,
+        Err(error) => return Outcome::from_error("headers", error),
+    };
+
+    if let Err(error) = MetaData::validate(&meta_data.headers, false) {
+        return Outcome::from_error("headers", error);
+    }
+
+    if let Err(error) = options.limits.validate_meta_data(&meta_data) {
+        return Outcome::Invalid(vec![format!("limits: {error}")]);
+    }
+
+    if options.headers_only {
+        return Outcome::Valid;
+    }
+
+    let verification = match verify_file(path) {
+        Ok(verification) => verification,
+        Err(error) => return Outcome::from_error("offset tables", error),
+    };
+
+    let (unsupported, invalid): (Vec<&ChunkReport>, Vec<&ChunkReport>) = verification
+        .errors()
+        .partition(|chunk| matches!(chunk.error, Some(Error::NotSupported(_))));
+
+    if !invalid.is_empty() {
+        return Outcome::Invalid(invalid.into_iter().map(describe_error).collect());
+    }
+
+    if let Some(error) = unsupported.first().and_then(|chunk| chunk.error.as_ref()) {
+        return Outcome::Unsupported(error.to_string());
+    }
+
+    // the chunks are valid on their own, so decode the image like an application would,
+    // which also finds missing chunks and exceeded sample limits
+    let deep_count = meta_data
+        .headers
+        .iter()
+        .filter(|header| header.deep)
+        .count();
+
+    let decoded = if deep_count == 0 {
+        read()
+            .no_deep_data()
+            .all_resolution_levels()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .with_limits(options.limits)
+            .from_file(path)
+            .map(|_: AnyImage| ())
+    } else if deep_count == meta_data.headers.len() {
+        read_deep()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .with_limits(options.limits)
+            .from_file(path)
+            .map(|_| ())
+    } else {
+        // files with both deep and flat parts cannot be decoded as a whole
+        Ok(())
+    };
+
+    match decoded {
+        Ok(()) => Outcome::Valid,
+        Err(error) => Outcome::from_error("pixels", error),
+    }
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs check - Validate the structure and the pixels of EXR files
+
+USAGE:
+    exrs check [OPTIONS] <FILE.exr | DIRECTORY>...
+
+OPTIONS:
+    --headers-only       Only validate the headers, without reading any pixels
+    --max-memory MIB     Reject files whose decoded pixels need more memory
+    --max-pixels COUNT   Reject files with more pixels
+    --max-samples COUNT  Reject deep files with more samples
+    --max-parts COUNT    Reject files with more parts
+    -r, --recursive      Also check files in subdirectories of directories
+    -q, --quiet          Only print invalid files
+    -h, --help           Show this help
+
+Each file is checked in steps, and the first step that finds problems is reported:
+the headers must be consistent, the offset tables must point to distinct
+chunks inside the file, each chunk must contain the expected block and decompress
+on its own, and finally all pixels are decoded like an application would.
+Files with both deep and flat parts are only checked chunk by chunk.
+Files that use features this implementation cannot decode, like some
+compression methods, are reported as unsupported, but not as invalid.
+
+The limits protect against files that would exhaust memory. Files that exceed
+a limit are reported as invalid without decoding their pixels.
+
+The exit code is non-zero if any file is invalid.
+
+EXAMPLES:
+    exrs check render.exr
+    exrs check -r -q --max-memory 4096 incoming/
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_limits() {
+        let options = Options::parse(&args(&["--max-memory", "2", "--max-parts", "3", "a.exr"]))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(options.limits.max_decompressed_bytes, 2 << 20);
+        assert_eq!(options.limits.max_parts, 3);
+        assert_eq!(options.limits.max_pixels, usize::MAX);
+        assert_eq!(options.inputs, vec![PathBuf::from("a.exr")]);
+
+        assert!(Options::parse(&args(&["--max-pixels"])).is_err());
+        assert!(Options::parse(&args(&["--max-pixels", "many"])).is_err());
+    }
+
+    #[test]
+    fn check_valid_and_truncated_files() {
+        let directory = std::env::temp_dir().join("exrs_check_test");
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let valid = directory.join("valid.exr");
+        let pixels = SpecificChannels::rgb(|position: Vec2<usize>| {
+            (position.x() as f32, position.y() as f32, 0.5_f32)
+        });
+
+        Image::from_channels((16, 16), pixels)
+            .write()
+            .to_file(&valid)
+            .unwrap();
+
+        let options = Options::parse(&args(&[])).unwrap().unwrap();
+        assert_eq!(check(&valid, &options), Outcome::Valid);
+
+        let truncated = directory.join("truncated.exr");
+        let bytes = std::fs::read(&valid).unwrap();
+        std::fs::write(&truncated, &bytes[..bytes.len() - 20]).unwrap();
+        assert!(matches!(check(&truncated, &options), Outcome::Invalid(_)));
+
+        let limited = Options::parse(&args(&["--max-pixels", "100"]))
+            .unwrap()
+            .unwrap();
+        match check(&valid, &limited) {
+            Outcome::Invalid(problems) => assert!(problems[0].starts_with("limits")),
+            other => panic!("expected exceeded limits, found {:?}", other),
+        }
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+}
@@ -0,0 +1,977 @@
+//! `exrs convert`: convert EXR files to image, NumPy, or texture files, and LDR images to EXR files.
+
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use exr::export::Transfer;
+use exr::image::resize::ResizeFilter;
+use exr::meta::describe::parse_compression;
+
+use crate::batch::{self, run_jobs, BatchOptions, Progress, Report};
+use crate::ocio::DisplayOptions;
+use crate::stdio::{is_standard_stream, STANDARD_STREAM};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let mut options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(batch) = options.batch.take() {
+        return if convert_all(options, batch) {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
+    }
+
+    match convert(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "convert"), allow(dead_code))]
+struct Options {
+    input: PathBuf,
+    output: PathBuf,
+    layer: Option<String>,
+    channels: Option<Vec<String>>,
+    exposure: f32,
+    transfer: Transfer,
+    sixteen_bit: bool,
+
+    /// Resize the layer by this factor before converting it.
+    scale: Option<f32>,
+    filter: ResizeFilter,
+
+    /// Replaces the transfer function with an OCIO display transform if specified.
+    display: DisplayOptions,
+
+    /// Compress KTX2 and DDS textures with BC6H instead of storing RGBA16F pixels.
+    bc6h: bool,
+
+    /// The compression of EXR output, ZIP if not specified.
+    compression: Option<exr::compression::Compression>,
+
+    /// Convert many files instead of the input and output file, if `--format` is specified.
+    batch: Option<Batch>,
+}
+
+/// Many files to convert, each to a file with the same name and a different extension.
+#[derive(Debug, Clone)]
+struct Batch {
+    inputs: Vec<PathBuf>,
+
+    /// The extension of the output files.
+    format: String,
+
+    /// Write the files next to the input files if not specified.
+    output_directory: Option<PathBuf>,
+
+    options: BatchOptions,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut files = Vec::new();
+        let mut layer = None;
+        let mut channels = None;
+        let mut exposure = 0.0;
+        let mut transfer = Transfer::Srgb;
+        let mut sixteen_bit = false;
+        let mut scale = None;
+        let mut filter = ResizeFilter::Mitchell;
+        let mut bc6h = false;
+        let mut compression = None;
+        let mut display = DisplayOptions::default();
+        let mut format = None;
+        let mut output_directory = None;
+        let mut batch_options = BatchOptions::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-l" | "--layer" => layer = Some(value(arg)?),
+                "-c" | "--channels" => {
+                    let list = value(arg)?;
+                    channels = Some(list.split(',').map(str::to_string).collect());
+                }
+                "-e" | "--exposure" => {
+                    let stops = value(arg)?;
+                    exposure = stops
+                        .parse()
+                        .map_err(|_| format!("Invalid exposure '{stops}'"))?;
+                }
+                "-g" | "--gamma" => {
+                    let gamma = value(arg)?;
+                    let gamma: f32 = gamma
+                        .parse()
+                        .map_err(|_| format!("Invalid gamma '{gamma}'"))?;
+                    transfer = Transfer::Gamma(gamma);
+                }
+                "--srgb" => transfer = Transfer::Srgb,
+                "--linear" => transfer = Transfer::Linear,
+                "--16bit" => sixteen_bit = true,
+                "-s" | "--scale" => {
+                    let factor = value(arg)?;
+                    scale = Some(
+                        factor
+                            .parse::<f32>()
+                            .ok()
+                            .filter(|factor| factor.is_finite() && *factor > 0.0)
+                            .ok_or_else(|| format!("Invalid scale '{factor}'"))?,
+                    );
+                }
+                "--filter" => {
+                    let name = value(arg)?;
+                    filter = ResizeFilter::from_name(&name)
+                        .ok_or_else(|| format!("Unknown filter '{name}'"))?;
+                }
+                "--bc6h" => bc6h = true,
+                "-z" | "--compression" => {
+                    let name = value(arg)?;
+                    compression = Some(
+                        parse_compression(&name)
+                            .ok_or_else(|| format!("Unknown compression '{name}'"))?,
+                    );
+                }
+                "-f" | "--format" => {
+                    let extension = value(arg)?;
+                    format = Some(extension.trim_start_matches('.').to_ascii_lowercase());
+                }
+                "-d" | "--output-dir" => output_directory = Some(PathBuf::from(value(arg)?)),
+                "-j" | "--jobs" => batch_options.threads = batch::parse_job_count(&value(arg)?)?,
+                "--progress" => batch_options.progress = true,
+                "--report" => batch_options.report = Some(PathBuf::from(value(arg)?)),
+                _ if display.parse_arg(arg, &mut value)? => {}
+                _ if !arg.starts_with('-') || arg == STANDARD_STREAM => {
+                    files.push(PathBuf::from(arg))
+                }
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        if let Some(format) = format {
+            if files.is_empty() {
+                return Err("No input files. Use 'exrs convert --help' for usage.".to_string());
+            }
+
+            if files.iter().any(|file| is_standard_stream(file)) {
+                return Err("Standard input cannot be combined with '--format'".to_string());
+            }
+
+            return Ok(Some(Options {
+                input: PathBuf::new(),
+                output: PathBuf::new(),
+                layer,
+                channels,
+                exposure,
+                transfer,
+                sixteen_bit,
+                scale,
+                filter,
+                display,
+                bc6h,
+                compression,
+                batch: Some(Batch {
+                    inputs: files,
+                    format,
+                    output_directory,
+                    options: batch_options,
+                }),
+            }));
+        }
+
+        if output_directory.is_some() {
+            return Err("'--output-dir' requires '--format'".to_string());
+        }
+
+        match <[PathBuf; 2]>::try_from(files) {
+            Ok([input, output]) => Ok(Some(Options {
+                input,
+                output,
+                layer,
+                channels,
+                exposure,
+                transfer,
+                sixteen_bit,
+                scale,
+                filter,
+                display,
+                bc6h,
+                compression,
+                batch: None,
+            })),
+
+            Err(_) => Err(
+                "Expected an input and an output file. Use 'exrs convert --help' for usage."
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+/// Expands the patterns, and converts all files on multiple threads.
+/// Returns whether all files were successful.
+fn convert_all(options: Options, batch: Batch) -> bool {
+    let mut jobs = Vec::new();
+
+    for input in &batch.inputs {
+        let files = match batch::expand_pattern(input) {
+            Ok(files) => files,
+            Err(message) => {
+                eprintln!("Error: {message}");
+                return false;
+            }
+        };
+
+        for file in files {
+            let output = match (&batch.output_directory, file.file_name()) {
+                (Some(directory), Some(name)) => directory.join(name),
+                _ => file.clone(),
+            };
+
+            jobs.push(Options {
+                input: file,
+                output: output.with_extension(&batch.format),
+                ..options.clone()
+            });
+        }
+    }
+
+    let mut progress = Progress::new(jobs.len(), batch.options.progress);
+    let mut report = Report::new("convert");
+    let mut success = true;
+
+    let all_finished = run_jobs(
+        jobs,
+        batch.options.threads,
+        |job| {
+            if let Some(parent) = job.output.parent() {
+                std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+            }
+
+            convert(job)
+        },
+        |job, result, duration| {
+            progress.clear();
+
+            match &result {
+                Ok(()) => println!("{} -> {}", job.input.display(), job.output.display()),
+                Err(message) => {
+                    success = false;
+                    eprintln!("Error: {}: {message}", job.input.display());
+                }
+            }
+
+            let fields = result.as_ref().map(|_| Vec::new()).map_err(String::as_str);
+            report.add(&job.input, Some(&job.output), fields, duration);
+            progress.advance(result.is_ok());
+        },
+    );
+
+    progress.finish();
+
+    if let Err(message) = report.write(batch.options.report.as_deref()) {
+        eprintln!("Error: {message}");
+        success = false;
+    }
+
+    success && all_finished
+}
+
+/// Standard input and standard output always contain exr files.
+#[cfg_attr(not(feature = "convert"), allow(dead_code))]
+fn is_exr(path: &Path) -> bool {
+    is_standard_stream(path)
+        || path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("exr"))
+}
+
+#[cfg(not(feature = "convert"))]
+fn convert(_options: &Options) -> Result<(), String> {
+    Err("Conversion not available. Rebuild with --features convert".to_string())
+}
+
+#[cfg(feature = "convert")]
+fn convert(options: &Options) -> Result<(), String> {
+    let is_image = |path: &Path| !is_exr(path) && !is_numpy(path) && !is_texture(path);
+    if options.display.is_specified() && !(is_exr(&options.input) && is_image(&options.output)) {
+        return Err("OCIO transforms only apply to EXR files converted to images".to_string());
+    }
+
+    if options.scale.is_some() && is_texture(&options.output) {
+        return Err("Textures keep the mip levels of the file and cannot be scaled".to_string());
+    }
+
+    match (is_exr(&options.input), is_exr(&options.output)) {
+        (true, false) if is_numpy(&options.output) => exr_to_numpy(options),
+        (true, false) if is_texture(&options.output) => exr_to_texture(options),
+        (true, false) => exr_to_image(options),
+        (false, true) => image_to_exr(options),
+        (true, true) => Err("Both files are EXR files, nothing to convert".to_string()),
+        (false, false) => Err("Either the input or the output must be an EXR file".to_string()),
+    }
+}
+
+/// The layer is either a separate part of the file, or a channel name prefix like `diffuse.R`.
+/// Returns the layer and the prefix of the selected channels.
+#[cfg(feature = "convert")]
+fn select_layer<'i, Samples>(
+    image: &'i exr::image::Image<exr::image::Layers<exr::image::AnyChannels<Samples>>>,
+    options: &Options,
+) -> (
+    &'i exr::image::Layer<exr::image::AnyChannels<Samples>>,
+    String,
+) {
+    match &options.layer {
+        None => (&image.layer_data[0], String::new()),
+        Some(name) => {
+            let part = image.layer_data.iter().find(|layer| {
+                layer
+                    .attributes
+                    .layer_name
+                    .as_ref()
+                    .map(exr::meta::attribute::Text::to_string)
+                    .as_deref()
+                    == Some(name.as_str())
+            });
+
+            match part {
+                Some(part) => (part, String::new()),
+                None => (&image.layer_data[0], format!("{name}.")),
+            }
+        }
+    }
+}
+
+/// A copy of the layer resized by the factor of `--scale`, if specified.
+#[cfg(feature = "convert")]
+fn scale_layer(
+    layer: &exr::image::Layer<exr::image::AnyChannels<exr::image::FlatSamples>>,
+    options: &Options,
+) -> Result<Option<exr::image::Layer<exr::image::AnyChannels<exr::image::FlatSamples>>>, String> {
+    options
+        .scale
+        .map(|scale| layer.scaled(exr::math::Vec2(scale, scale), options.filter))
+        .transpose()
+        .map_err(|error| error.to_string())
+}
+
+/// The channels specified with `--channels`, without the prefix, or otherwise
+/// rgb and alpha if present, luminance and alpha if present, or the first channel.
+#[cfg(feature = "convert")]
+fn selected_channels<Samples>(
+    layer: &exr::image::Layer<exr::image::AnyChannels<Samples>>,
+    prefix: &str,
+    options: &Options,
+) -> Result<Vec<String>, String> {
+    let find = |name: &str| {
+        let full_name = format!("{prefix}{name}");
+        layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.to_string() == full_name)
+    };
+
+    Ok(match &options.channels {
+        Some(names) => names.clone(),
+        None if find("R").is_some() && find("G").is_some() && find("B").is_some() => {
+            let rgb = ["R", "G", "B"].iter().map(|name| name.to_string());
+            rgb.chain(find("A").map(|_| "A".to_string())).collect()
+        }
+        None if find("Y").is_some() => {
+            let luminance = std::iter::once("Y".to_string());
+            luminance
+                .chain(find("A").map(|_| "A".to_string()))
+                .collect()
+        }
+        None => {
+            let first = layer
+                .channel_data
+                .list
+                .iter()
+                .map(|channel| channel.name.to_string())
+                .find_map(|name| name.strip_prefix(prefix).map(str::to_string))
+                .ok_or_else(|| format!("No channels in layer '{prefix}'"))?;
+
+            vec![first]
+        }
+    })
+}
+
+/// Whether the output is a numpy array or archive, which keeps the original sample type.
+#[cfg(feature = "convert")]
+fn is_numpy(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            extension.eq_ignore_ascii_case("npy") || extension.eq_ignore_ascii_case("npz")
+        })
+}
+
+/// Write the selected channels, or all channels of the layer, to a `.npy` or `.npz` file.
+#[cfg(feature = "convert")]
+fn exr_to_numpy(options: &Options) -> Result<(), String> {
+    use exr::interop::numpy::{write_channels_npy, write_npz};
+
+    let image = crate::stdio::Input::open(&options.input)
+        .and_then(crate::stdio::read_flat_layers)
+        .map_err(|error| error.to_string())?;
+
+    let (layer, prefix) = select_layer(&image, options);
+    let scaled = scale_layer(layer, options)?;
+    let layer = scaled.as_ref().unwrap_or(layer);
+
+    let names: Vec<String> = match &options.channels {
+        Some(names) => names.iter().map(|name| format!("{prefix}{name}")).collect(),
+        None => layer
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .filter(|name| name.starts_with(&prefix))
+            .collect(),
+    };
+
+    if names.is_empty() {
+        return Err(format!("No channels in layer '{prefix}'"));
+    }
+
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let file = std::fs::File::create(&options.output).map_err(|error| error.to_string())?;
+    let file = std::io::BufWriter::new(file);
+
+    let npz = options
+        .output
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("npz"));
+
+    let result = if npz {
+        write_npz(file, layer, &names, true)
+    } else {
+        write_channels_npy(file, layer, &names)
+    };
+
+    result.map_err(|error| error.to_string())
+}
+
+/// Whether the output is a KTX2 or DDS texture, which keeps the mip levels of the file.
+#[cfg(feature = "convert")]
+fn is_texture(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            extension.eq_ignore_ascii_case("ktx2") || extension.eq_ignore_ascii_case("dds")
+        })
+}
+
+/// Write the selected channels of all mip levels to a `.ktx2` or `.dds` texture.
+#[cfg(feature = "convert")]
+fn exr_to_texture(options: &Options) -> Result<(), String> {
+    use exr::interop::texture::{rgba_levels, write_dds, write_ktx2, TextureFormat};
+
+    let image = crate::stdio::Input::open(&options.input)
+        .and_then(crate::stdio::read_all_data)
+        .map_err(|error| error.to_string())?;
+
+    let (layer, prefix) = select_layer(&image, options);
+    let names: Vec<String> = selected_channels(layer, &prefix, options)?
+        .iter()
+        .map(|name| format!("{prefix}{name}"))
+        .collect();
+
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let levels = rgba_levels(layer, &names).map_err(|error| error.to_string())?;
+
+    let format = if options.bc6h {
+        TextureFormat::Bc6h
+    } else {
+        TextureFormat::Rgba16Float
+    };
+
+    let file = std::fs::File::create(&options.output).map_err(|error| error.to_string())?;
+    let file = std::io::BufWriter::new(file);
+
+    let dds = options
+        .output
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("dds"));
+
+    let result = if dds {
+        write_dds(file, &levels, format)
+    } else {
+        write_ktx2(file, &levels, format)
+    };
+
+    result.map_err(|error| error.to_string())
+}
+
+#[cfg(feature = "convert")]
+fn exr_to_image(options: &Options) -> Result<(), String> {
+    use ::image::{DynamicImage, ImageBuffer};
+    use exr::prelude::*;
+
+    let image = crate::stdio::Input::open(&options.input)
+        .and_then(crate::stdio::read_flat_layers)
+        .map_err(|error| error.to_string())?;
+
+    let (layer, prefix) = select_layer(&image, options);
+    let scaled = scale_layer(layer, options)?;
+    let layer = scaled.as_ref().unwrap_or(layer);
+
+    let find = |name: &str| {
+        let full_name = format!("{prefix}{name}");
+        layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.to_string() == full_name)
+    };
+
+    let names = selected_channels(layer, &prefix, options)?;
+
+    if names.is_empty() || names.len() > 4 {
+        return Err("Select one to four channels: gray, gray and alpha, rgb, or rgba".to_string());
+    }
+
+    let mut channels = names
+        .iter()
+        .map(|name| {
+            let channel = find(name).ok_or_else(|| format!("No channel named '{prefix}{name}'"))?;
+
+            if channel.sampling != Vec2(1, 1) {
+                return Err(format!("Channel '{prefix}{name}' is subsampled"));
+            }
+
+            Ok(channel.sample_data.to_f32_vec())
+        })
+        .collect::<std::result::Result<Vec<_>, String>>()?;
+
+    let display_transform = options.display.transform()?;
+
+    // without a display transform, rgb is encoded as srgb, which has the primaries of rec709
+    if display_transform.is_none() && names.len() >= 3 && names[..3] == ["R", "G", "B"] {
+        let source = exr::color::ColorSpace::of_image(&image.attributes);
+
+        if let [red, green, blue, ..] = channels.as_mut_slice() {
+            source
+                .convert_rgb_samples(&exr::color::ColorSpace::LinearRec709, red, green, blue)
+                .map_err(|error| error.to_string())?;
+        }
+    }
+
+    let (width, height) = (layer.size.width() as u32, layer.size.height() as u32);
+    let channel_count = channels.len();
+    let alpha_index = if channel_count % 2 == 0 {
+        Some(channel_count - 1)
+    } else {
+        None
+    };
+    let color_count = channel_count - alpha_index.map_or(0, |_| 1);
+    let exposure = 2.0_f32.powf(options.exposure);
+
+    let extension = options
+        .output
+        .extension()
+        .and_then(|extension| extension.to_str());
+    let extension = extension.unwrap_or_default().to_ascii_lowercase();
+
+    // radiance files store linear values and only support rgb
+    if extension == "hdr" {
+        if display_transform.is_some() {
+            return Err(
+                "HDR files contain linear values, OCIO transforms do not apply".to_string(),
+            );
+        }
+
+        let pixels: Vec<::image::Rgb<f32>> = (0..layer.size.area())
+            .map(|index| {
+                // gray images are written as rgb
+                let color =
+                    |channel: usize| channels[channel.min(color_count - 1)][index] * exposure;
+                ::image::Rgb([color(0), color(1), color(2)])
+            })
+            .collect();
+
+        let file = std::fs::File::create(&options.output).map_err(|error| error.to_string())?;
+        return ::image::codecs::hdr::HdrEncoder::new(std::io::BufWriter::new(file))
+            .encode(&pixels, width as usize, height as usize)
+            .map_err(|error| error.to_string());
+    }
+
+    // the display transform converts all colors at once, and gray to the mean of the colors
+    let display_colors: Option<Vec<[f32; 3]>> = display_transform.map(|transform| {
+        (0..layer.size.area())
+            .map(|index| {
+                let color = |channel: usize| channels[channel.min(color_count - 1)][index];
+                let rgb = transform
+                    .apply_rgb([color(0), color(1), color(2)].map(|value| value * exposure));
+
+                if color_count == 3 {
+                    rgb
+                } else {
+                    [(rgb[0] + rgb[1] + rgb[2]) / 3.0; 3]
+                }
+            })
+            .collect()
+    });
+
+    let interleaved: Vec<f32> = (0..layer.size.area())
+        .flat_map(|index| {
+            let channels = &channels;
+            let display_colors = &display_colors;
+
+            (0..channel_count).map(move |channel| {
+                let value = channels[channel][index];
+
+                if Some(channel) == alpha_index {
+                    value.max(0.0).min(1.0)
+                } else if let Some(display_colors) = display_colors {
+                    display_colors[index][channel].max(0.0).min(1.0)
+                } else {
+                    options.transfer.encode(value * exposure)
+                }
+            })
+        })
+        .collect();
+
+    let invalid_size = || "Image size is invalid".to_string();
+
+    let output = if options.sixteen_bit {
+        let samples: Vec<u16> = interleaved
+            .iter()
+            .map(|&value| (value * 65535.0).round() as u16)
+            .collect();
+
+        match channel_count {
+            1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLuma16),
+            2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLumaA16),
+            3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb16),
+            _ => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba16),
+        }
+    } else {
+        let samples: Vec<u8> = interleaved
+            .iter()
+            .map(|&value| (value * 255.0).round() as u8)
+            .collect();
+
+        match channel_count {
+            1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLuma8),
+            2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLumaA8),
+            3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb8),
+            _ => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba8),
+        }
+    };
+
+    let mut output = output.ok_or_else(invalid_size)?;
+
+    // jpeg files only support 8 bits and no alpha
+    if extension == "jpg" || extension == "jpeg" {
+        if options.sixteen_bit {
+            return Err("JPEG files do not support 16 bit output".to_string());
+        }
+
+        output = match channel_count {
+            1 | 2 => DynamicImage::ImageLuma8(output.to_luma8()),
+            _ => DynamicImage::ImageRgb8(output.to_rgb8()),
+        };
+    }
+
+    output
+        .save(&options.output)
+        .map_err(|error| error.to_string())
+}
+
+#[cfg(feature = "convert")]
+fn image_to_exr(options: &Options) -> Result<(), String> {
+    use exr::import::ImportFormat;
+    use exr::prelude::*;
+
+    let format = ImportFormat::from_path(&options.input).ok();
+
+    // hdr and pfm files contain linear floats, and the image crate cannot read float tiff files
+    let input = match format {
+        Some(ImportFormat::Hdr) | Some(ImportFormat::Pfm) => None,
+        _ => match ::image::open(&options.input) {
+            Ok(input) => Some(input),
+            Err(::image::ImageError::Unsupported(_)) if format == Some(ImportFormat::Tiff) => None,
+            Err(error) => return Err(error.to_string()),
+        },
+    };
+
+    let mut layer = match input {
+        Some(input) => ldr_layer(&input, options),
+        None => float_layer(options)?,
+    };
+
+    if let Some(name) = &options.layer {
+        layer.attributes.layer_name = Some(Text::from(name.as_str()));
+    }
+
+    layer.encoding = Encoding {
+        compression: options.compression.unwrap_or(Compression::ZIP16),
+        ..Encoding::SMALL_LOSSLESS
+    };
+
+    let layer = scale_layer(&layer, options)?.unwrap_or(layer);
+    let image = Image::from_layer(layer);
+
+    crate::stdio::write(&options.output, |output| {
+        image.write().to_unbuffered(output)
+    })
+    .map_err(|error| error.to_string())
+}
+
+/// Decode the colors of an LDR image with the transfer function, and store them as `f16`.
+#[cfg(feature = "convert")]
+fn ldr_layer(
+    input: &::image::DynamicImage,
+    options: &Options,
+) -> exr::image::Layer<exr::image::AnyChannels<exr::image::FlatSamples>> {
+    use exr::prelude::*;
+
+    let has_alpha = input.color().has_alpha();
+    let has_color = input.color().has_color();
+    let exposure = 2.0_f32.powf(options.exposure);
+
+    let pixels = input.to_rgba32f();
+    let size = (pixels.width() as usize, pixels.height() as usize);
+
+    let channel = |name: &str, index: usize, is_color: bool| {
+        let samples = pixels
+            .pixels()
+            .map(|pixel| {
+                let value = pixel.0[index];
+                let value = if is_color {
+                    options.transfer.decode(value) * exposure
+                } else {
+                    value
+                };
+                f16::from_f32(value)
+            })
+            .collect();
+
+        AnyChannel::new(name, FlatSamples::F16(samples))
+    };
+
+    let mut channels = SmallVec::new();
+
+    if has_color {
+        channels.push(channel("R", 0, true));
+        channels.push(channel("G", 1, true));
+        channels.push(channel("B", 2, true));
+    } else {
+        channels.push(channel("Y", 0, true));
+    }
+
+    if has_alpha {
+        channels.push(channel("A", 3, false));
+    }
+
+    Layer::new(
+        size,
+        LayerAttributes::default(),
+        Encoding::SMALL_LOSSLESS,
+        AnyChannels::sort(channels),
+    )
+}
+
+/// Read the linear `f32` samples of an HDR, PFM, or float TIFF file, and apply the exposure.
+#[cfg(feature = "convert")]
+fn float_layer(
+    options: &Options,
+) -> Result<exr::image::Layer<exr::image::AnyChannels<exr::image::FlatSamples>>, String> {
+    use exr::prelude::*;
+
+    let mut layer =
+        exr::import::read_layer_from_file(&options.input).map_err(|error| error.to_string())?;
+
+    let exposure = 2.0_f32.powf(options.exposure);
+    for channel in &mut layer.channel_data.list {
+        // alpha is not a color
+        if channel.name.eq("A") {
+            continue;
+        }
+
+        if let FlatSamples::F32(samples) = &mut channel.sample_data {
+            for sample in samples {
+                *sample *= exposure;
+            }
+        }
+    }
+
+    Ok(layer)
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs convert - Convert between EXR and PNG, JPEG, TIFF, HDR, NumPy, or texture files
+
+USAGE:
+    exrs convert [OPTIONS] <INPUT> <OUTPUT>
+    exrs convert [OPTIONS] -f <EXTENSION> <FILE | PATTERN>...
+
+The output format is chosen by the file extension.
+EXR files are converted to .png, .jpg, .tif, .hdr, .npy, .npz, .ktx2, or .dds files,
+and .png, .jpg, .tif, .hdr, or .pfm files are converted to EXR files.
+
+OPTIONS:
+    -l, --layer <NAME>       Layer to convert: the name of a part, or a channel
+                             name prefix like 'diffuse' for 'diffuse.R'
+                             (default: first part, unprefixed channels).
+                             For EXR output, the name of the written layer.
+    -c, --channels <LIST>    Comma separated channels: gray, gray and alpha,
+                             rgb, or rgba (default: R,G,B,A or Y,A if present)
+    -e, --exposure <STOPS>   Multiply colors by 2^STOPS (default: 0)
+    --srgb                   Use the sRGB transfer function (default)
+    -g, --gamma <VALUE>      Use a simple gamma instead of sRGB
+    --linear                 Do not apply any transfer function
+    --16bit                  Write 16 bits per sample to PNG or TIFF files
+    -s, --scale <FACTOR>     Resize the image by this factor, such as 0.5
+    --filter <FILTER>        Filter for resizing: box, triangle, lanczos3,
+                             mitchell, or gaussian (default: mitchell)
+    --bc6h                   Compress KTX2 or DDS textures with BC6H
+                             instead of storing RGBA16F pixels
+    -z, --compression <NAME> Compression of EXR output: none, rle, zips, zip,
+                             piz, pxr24, b44, b44a (default: zip)
+    -h, --help               Show this help
+
+OCIO OPTIONS:
+{ocio_help}
+
+BATCH OPTIONS:
+    -f, --format <EXTENSION> Convert all inputs to files with this extension,
+                             such as 'png' or 'exr'
+    -d, --output-dir <DIR>   Write the files to this directory instead of
+                             next to the input files
+    -j, --jobs <COUNT>       Number of files converted in parallel
+                             [default: number of processors]
+    --progress               Draw a progress bar on standard error
+    --report <FILE.json>     Write the result of each file to a JSON report
+
+HDR files always contain linear values, only the exposure is applied.
+HDR, PFM, and float TIFF input is stored as f32, other input as f16.
+OCIO display transforms replace the transfer function of PNG, JPEG, and TIFF
+output, and require the 'ocio' feature.
+NumPy files contain the unmodified samples with their original type (f16, f32,
+or u32) and all channels of the layer unless --channels is specified:
+.npy files one array of shape (height, width, channels), which requires
+channels of the same type, and .npz files one compressed array per channel.
+KTX2 and DDS textures contain linear values and all mip levels of the file,
+which must be rounded down.
+Patterns may contain wildcards and frame numbers like 'beauty.####.exr',
+see 'exrs recompress --help'.
+
+EXAMPLES:
+    exrs convert render.exr preview.jpg
+    exrs convert -l diffuse -e 1.5 render.exr diffuse.png
+    exrs convert -c Z --linear --16bit render.exr depth.tif
+    exrs convert texture.png texture.exr
+    exrs convert -s 0.25 --filter lanczos3 render.exr small.png
+    exrs convert -l diffuse -c R,G,B render.exr diffuse.npy
+    exrs convert render.exr aovs.npz
+    exrs convert --bc6h lightmap.exr lightmap.ktx2
+    exrs convert --ocio studio.ocio --view Film render.exr preview.jpg
+    exrs convert -f jpg -d previews/ -j 8 --progress 'shot/beauty.####.exr'
+"#,
+        ocio_help = crate::ocio::HELP
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_options() {
+        let args: Vec<String> = ["-e", "2", "--16bit", "in.exr", "out.png", "-s", "0.5"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        let options = Options::parse(&args).unwrap().unwrap();
+        assert_eq!(options.exposure, 2.0);
+        assert!(options.sixteen_bit);
+        assert_eq!(options.scale, Some(0.5));
+        assert_eq!(options.filter, ResizeFilter::Mitchell);
+        assert_eq!(options.output, PathBuf::from("out.png"));
+
+        assert!(Options::parse(&args[..4]).is_err());
+
+        let invalid_scale: Vec<String> = ["-s", "0", "in.exr", "out.png"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        assert!(Options::parse(&invalid_scale).is_err());
+        assert!(options.batch.is_none());
+        assert_eq!(options.compression, None);
+
+        let compressed: Vec<String> = ["-z", "piz", "sky.hdr", "sky.exr"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        let options = Options::parse(&compressed).unwrap().unwrap();
+        assert_eq!(
+            options.compression,
+            Some(exr::compression::Compression::PIZ)
+        );
+        assert!(Options::parse(&["-z".to_string(), "zstd".to_string()]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "convert")]
+    fn detect_array_and_texture_output() {
+        assert!(is_numpy(Path::new("aovs.npz")));
+        assert!(is_numpy(Path::new("rgb.NPY")));
+        assert!(!is_numpy(Path::new("preview.png")));
+        assert!(!is_numpy(Path::new("-")));
+        assert!(is_texture(Path::new("lightmap.KTX2")));
+        assert!(is_texture(Path::new("sky.dds")));
+    }
+
+    #[test]
+    fn parse_batch_options() {
+        let args: Vec<String> = ["-f", ".PNG", "-d", "previews", "-j", "3", "a.exr", "b.exr"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        let options = Options::parse(&args).unwrap().unwrap();
+        let batch = options.batch.unwrap();
+        assert_eq!(batch.format, "png");
+        assert_eq!(batch.inputs.len(), 2);
+        assert_eq!(batch.output_directory, Some(PathBuf::from("previews")));
+        assert_eq!(batch.options.threads, 3);
+
+        assert!(Options::parse(&args[2..]).is_err());
+        assert!(Options::parse(&args[..2]).is_err());
+    }
+}
@@ -0,0 +1,518 @@
+//! `exrs diff`: compare the pixels of two files, for example in render regression tests.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use exr::prelude::*;
+
+use crate::stdio::{self, is_standard_stream, Input, STANDARD_STREAM};
+
+/// The images are equal within the thresholds.
+const PASS: u8 = 0;
+
+/// The images differ more than the thresholds allow.
+const FAIL: u8 = 1;
+
+/// The images could not be compared.
+const ERROR: u8 = 2;
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::from(ERROR);
+        }
+    };
+
+    match compare(&options) {
+        Ok(true) => ExitCode::from(PASS),
+        Ok(false) => ExitCode::from(FAIL),
+        Err(message) => {
+            eprintln!("Error: {message}");
+            ExitCode::from(ERROR)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    old: PathBuf,
+    new: PathBuf,
+    max_error: f32,
+    min_psnr: Option<f32>,
+    max_delta_e: Option<f32>,
+    peak: f32,
+    heatmap: Option<PathBuf>,
+    quiet: bool,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> std::result::Result<Option<Self>, String> {
+        let mut files = Vec::new();
+        let mut max_error = 0.0;
+        let mut min_psnr = None;
+        let mut max_delta_e = None;
+        let mut peak = 1.0;
+        let mut heatmap = None;
+        let mut quiet = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut number = |name: &str| -> std::result::Result<f32, String> {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))?;
+
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid number '{value}' for '{name}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-t" | "--max-error" => max_error = number(arg)?,
+                "--min-psnr" => min_psnr = Some(number(arg)?),
+                "--max-delta-e" => max_delta_e = Some(number(arg)?),
+                "--peak" => peak = number(arg)?,
+                "-q" | "--quiet" => quiet = true,
+                "--heatmap" => {
+                    let path = args.next().ok_or("Missing value for '--heatmap'")?;
+                    heatmap = Some(PathBuf::from(path));
+                }
+                _ if !arg.starts_with('-') || arg == STANDARD_STREAM => {
+                    files.push(PathBuf::from(arg))
+                }
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        let mut files = files.into_iter();
+        match (files.next(), files.next(), files.next()) {
+            (Some(old), Some(new), None)
+                if is_standard_stream(&old) && is_standard_stream(&new) =>
+            {
+                Err("Only one of the files can be read from standard input".to_string())
+            }
+
+            (Some(old), Some(new), None) => Ok(Some(Options {
+                old,
+                new,
+                max_error,
+                min_psnr,
+                max_delta_e,
+                peak,
+                heatmap,
+                quiet,
+            })),
+
+            _ => {
+                Err("Expected two files to compare. Use 'exrs diff --help' for usage.".to_string())
+            }
+        }
+    }
+}
+
+/// The difference between two versions of a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct ChannelStatistics {
+    max_error: f32,
+    mean_error: f64,
+    mean_squared_error: f64,
+
+    /// Number of samples where exactly one of the two values is `NaN`.
+    nan_mismatches: usize,
+}
+
+impl ChannelStatistics {
+    fn compute(old: &[f32], new: &[f32]) -> Self {
+        let mut statistics = ChannelStatistics::default();
+        let mut sum = 0.0_f64;
+        let mut squared_sum = 0.0_f64;
+
+        for (&old, &new) in old.iter().zip(new) {
+            if old.is_nan() || new.is_nan() {
+                if old.is_nan() != new.is_nan() {
+                    statistics.nan_mismatches += 1;
+                }
+
+                continue;
+            }
+
+            // infinite values are equal if they have the same sign
+            let error = if old == new { 0.0 } else { (old - new).abs() };
+
+            statistics.max_error = statistics.max_error.max(error);
+            sum += error as f64;
+            squared_sum += (error as f64) * (error as f64);
+        }
+
+        let count = old.len().max(1) as f64;
+        statistics.mean_error = sum / count;
+        statistics.mean_squared_error = squared_sum / count;
+        statistics
+    }
+
+    /// Peak signal to noise ratio in decibels, infinite for equal channels.
+    fn psnr(&self, peak: f32) -> f64 {
+        let peak = peak as f64;
+        10.0 * (peak * peak / self.mean_squared_error).log10()
+    }
+}
+
+/// Mean and maximum CIE76 color difference between two linear rgb images.
+/// Colors are clamped to the displayable range and converted to CIELAB,
+/// so differences in very bright areas are not taken into account.
+fn delta_e(old: [&[f32]; 3], new: [&[f32]; 3]) -> (f64, f32) {
+    let pixel_count = old[0].len();
+    let mut sum = 0.0_f64;
+    let mut max = 0.0_f32;
+
+    for index in 0..pixel_count {
+        let old = lab([old[0][index], old[1][index], old[2][index]]);
+        let new = lab([new[0][index], new[1][index], new[2][index]]);
+
+        let difference =
+            ((old[0] - new[0]).powi(2) + (old[1] - new[1]).powi(2) + (old[2] - new[2]).powi(2))
+                .sqrt();
+
+        if difference.is_finite() {
+            sum += difference as f64;
+            max = max.max(difference);
+        }
+    }
+
+    (sum / pixel_count.max(1) as f64, max)
+}
+
+/// Convert linear Rec.709 rgb to CIELAB with a D65 white point.
+fn lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|value| value.max(0.0).min(1.0));
+
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+
+    let f = |t: f32| {
+        if t > 0.008_856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn compare(options: &Options) -> std::result::Result<bool, String> {
+    let read = |path: &PathBuf| {
+        Input::open(path)
+            .and_then(stdio::read_flat_layers)
+            .map_err(|error| format!("{}: {error}", path.display()))
+    };
+
+    let old = read(&options.old)?;
+    let new = read(&options.new)?;
+    let mut passed = true;
+
+    let report = |line: String| {
+        if !options.quiet {
+            println!("{line}");
+        }
+    };
+
+    if old.layer_data.len() != new.layer_data.len() {
+        report(format!(
+            "layer count differs: {} -> {}",
+            old.layer_data.len(),
+            new.layer_data.len()
+        ));
+
+        passed = false;
+    }
+
+    // the per-pixel maximum error of all channels in the first layer
+    let mut heatmap: Option<(Vec2<usize>, Vec<f32>)> = None;
+
+    for (index, (old_layer, new_layer)) in old.layer_data.iter().zip(&new.layer_data).enumerate() {
+        let name = old_layer
+            .attributes
+            .layer_name
+            .as_ref()
+            .map_or(String::new(), |name| format!(" `{name}`"));
+
+        report(format!("layer {index}{name}:"));
+
+        if old_layer.size != new_layer.size {
+            report(format!(
+                "  size differs: {}x{} -> {}x{}",
+                old_layer.size.width(),
+                old_layer.size.height(),
+                new_layer.size.width(),
+                new_layer.size.height()
+            ));
+
+            passed = false;
+            continue;
+        }
+
+        let find = |layer: &Layer<AnyChannels<FlatSamples>>, name: &Text| {
+            layer
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| &channel.name == name)
+                .map(|channel| channel.sample_data.to_f32_vec())
+        };
+
+        for channel in &new_layer.channel_data.list {
+            if find(old_layer, &channel.name).is_none() {
+                report(format!("  {}: added", channel.name));
+                passed = false;
+            }
+        }
+
+        let mut compared = Vec::new();
+
+        for channel in &old_layer.channel_data.list {
+            let old_samples = channel.sample_data.to_f32_vec();
+
+            let new_samples = match find(new_layer, &channel.name) {
+                Some(samples) if samples.len() == old_samples.len() => samples,
+                Some(_) => {
+                    report(format!("  {}: sampling differs", channel.name));
+                    passed = false;
+                    continue;
+                }
+                None => {
+                    report(format!("  {}: removed", channel.name));
+                    passed = false;
+                    continue;
+                }
+            };
+
+            let statistics = ChannelStatistics::compute(&old_samples, &new_samples);
+            let psnr = statistics.psnr(options.peak);
+
+            let channel_passed = statistics.nan_mismatches == 0
+                && statistics.max_error <= options.max_error
+                && options.min_psnr.map_or(true, |min| psnr >= min as f64);
+
+            report(format!(
+                "  {}: max {:.6}, mean {:.6}, rmse {:.6}, psnr {:.2} dB{}{}",
+                channel.name,
+                statistics.max_error,
+                statistics.mean_error,
+                statistics.mean_squared_error.sqrt(),
+                psnr,
+                if statistics.nan_mismatches != 0 {
+                    format!(", {} NaN mismatches", statistics.nan_mismatches)
+                } else {
+                    String::new()
+                },
+                if channel_passed { "" } else { "  FAIL" },
+            ));
+
+            passed &= channel_passed;
+
+            if index == 0 && channel.sampling == Vec2(1, 1) {
+                let (_, errors) = heatmap
+                    .get_or_insert_with(|| (old_layer.size, vec![0.0; old_layer.size.area()]));
+
+                for ((error, old), new) in errors.iter_mut().zip(&old_samples).zip(&new_samples) {
+                    let difference = if old == new { 0.0 } else { (old - new).abs() };
+                    *error = error.max(if difference.is_nan() {
+                        f32::INFINITY
+                    } else {
+                        difference
+                    });
+                }
+            }
+
+            compared.push((channel.name.to_string(), old_samples, new_samples));
+        }
+
+        if let Some(max_delta_e) = options.max_delta_e {
+            let rgb = |version: usize| -> Option<[&[f32]; 3]> {
+                let channel = |name: &str| {
+                    compared
+                        .iter()
+                        .find(|(channel, _, _)| channel == name)
+                        .map(|(_, old, new)| {
+                            if version == 0 {
+                                old.as_slice()
+                            } else {
+                                new.as_slice()
+                            }
+                        })
+                };
+
+                Some([channel("R")?, channel("G")?, channel("B")?])
+            };
+
+            match (rgb(0), rgb(1)) {
+                (Some(old), Some(new)) => {
+                    let (mean, max) = delta_e(old, new);
+                    let delta_e_passed = mean <= max_delta_e as f64;
+
+                    report(format!(
+                        "  delta e: mean {mean:.4}, max {max:.4}{}",
+                        if delta_e_passed { "" } else { "  FAIL" }
+                    ));
+
+                    passed &= delta_e_passed;
+                }
+
+                _ => report("  delta e: skipped, no R, G, and B channels".to_string()),
+            }
+        }
+    }
+
+    if let (Some(path), Some((size, errors))) = (&options.heatmap, heatmap) {
+        write_heatmap(path, size, errors)?;
+    }
+
+    report(if passed { "PASS" } else { "FAIL" }.to_string());
+    Ok(passed)
+}
+
+/// Write the per-pixel error as a single channel EXR file, or as a colored PNG file.
+fn write_heatmap(
+    path: &PathBuf,
+    size: Vec2<usize>,
+    errors: Vec<f32>,
+) -> std::result::Result<(), String> {
+    let is_exr = path
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("exr"));
+
+    if is_exr {
+        let channel = AnyChannel::new("Y", FlatSamples::F32(errors));
+        let layer = Layer::new(
+            size,
+            LayerAttributes::named("error"),
+            Encoding::SMALL_LOSSLESS,
+            AnyChannels::sort(smallvec::smallvec![channel]),
+        );
+
+        return Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .map_err(|error| error.to_string());
+    }
+
+    write_heatmap_image(path, size, &errors)
+}
+
+#[cfg(feature = "convert")]
+fn write_heatmap_image(
+    path: &PathBuf,
+    size: Vec2<usize>,
+    errors: &[f32],
+) -> std::result::Result<(), String> {
+    let max = errors
+        .iter()
+        .copied()
+        .filter(|error| error.is_finite())
+        .fold(0.0_f32, f32::max);
+
+    let mut image = ::image::RgbImage::new(size.width() as u32, size.height() as u32);
+
+    for (pixel, &error) in image.pixels_mut().zip(errors) {
+        // black, red, yellow, white
+        let t = if error.is_finite() && max > 0.0 {
+            error / max
+        } else if error > 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+        let channel = |start: f32| ((t * 3.0 - start).max(0.0).min(1.0) * 255.0) as u8;
+        *pixel = ::image::Rgb([channel(0.0), channel(1.0), channel(2.0)]);
+    }
+
+    image.save(path).map_err(|error| error.to_string())
+}
+
+#[cfg(not(feature = "convert"))]
+fn write_heatmap_image(
+    _path: &PathBuf,
+    _size: Vec2<usize>,
+    _errors: &[f32],
+) -> std::result::Result<(), String> {
+    Err(
+        "Heatmap images are not available, use an .exr file or rebuild with --features convert"
+            .to_string(),
+    )
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs diff - Compare the pixels of two EXR files
+
+USAGE:
+    exrs diff [OPTIONS] <OLD.exr> <NEW.exr>
+
+Layers are compared by index, channels by name.
+For each channel, prints the maximum and mean absolute error,
+the root mean squared error, and the peak signal to noise ratio.
+
+OPTIONS:
+    -t, --max-error <X>      Fail if any sample differs by more than X (default: 0)
+    --min-psnr <DB>          Fail if any channel has a lower PSNR
+    --max-delta-e <X>        Compare R, G, B perceptually, using the CIE76 color
+                             difference of the clamped colors, and fail if the
+                             mean difference is larger than X (1 is barely visible)
+    --peak <X>               Peak value for the PSNR (default: 1)
+    --heatmap <FILE>         Write the per-pixel maximum error of the first layer,
+                             as a 'Y' channel to an .exr file or colored to a .png file
+    -q, --quiet              Do not print anything
+    -h, --help               Show this help
+
+EXIT CODE:
+    0    The images are equal within the thresholds
+    1    The images differ
+    2    The images could not be read
+
+EXAMPLES:
+    exrs diff reference.exr render.exr
+    exrs diff -t 0.001 --min-psnr 60 reference.exr render.exr
+    exrs diff --max-delta-e 1 --heatmap error.png reference.exr render.exr
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn channel_statistics() {
+        let statistics =
+            ChannelStatistics::compute(&[0.0, 1.0, f32::NAN, 2.0], &[0.0, 0.5, f32::NAN, f32::NAN]);
+        assert_eq!(statistics.max_error, 0.5);
+        assert_eq!(statistics.nan_mismatches, 1);
+        assert_eq!(statistics.mean_error, 0.125);
+
+        let equal = ChannelStatistics::compute(&[1.0, f32::INFINITY], &[1.0, f32::INFINITY]);
+        assert_eq!(equal, ChannelStatistics::default());
+        assert!(equal.psnr(1.0).is_infinite());
+    }
+
+    #[test]
+    fn delta_e_of_equal_colors() {
+        let colors: &[f32] = &[0.0, 0.5, 1.0];
+        assert_eq!(delta_e([colors; 3], [colors; 3]), (0.0, 0.0));
+
+        let brighter: &[f32] = &[0.1, 0.6, 1.0];
+        let (mean, max) = delta_e([colors; 3], [brighter; 3]);
+        assert!(mean > 1.0 && max > 1.0);
+    }
+}
@@ -0,0 +1,344 @@
+//! `exrs envmap`: convert between latitude-longitude and cube environment maps, like `exrenvmap`.
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use exr::image::environment_map::resample_samples;
+use exr::image::write::WritableImage;
+use exr::image::{
+    AnyChannel, AnyChannels, Blocks, Encoding, FlatSamples, Image, Layer, Layers, Levels,
+};
+use exr::math::{RoundingMode, Vec2};
+use exr::meta::attribute::{EnvironmentMap, IntegerBounds, LineOrder};
+use exr::meta::describe::parse_compression;
+use exr::meta::mip_map_levels;
+use exr::prelude::Compression;
+
+use crate::stdio::{self, Input, STANDARD_STREAM};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match convert(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    input: PathBuf,
+    output: PathBuf,
+
+    /// Overrides the `envmap` attribute of the input.
+    source: Option<EnvironmentMap>,
+
+    /// The opposite of the source layout if not specified.
+    target: Option<EnvironmentMap>,
+
+    /// The width of a latitude-longitude map, or the face size of a cube map.
+    /// Keeps the horizontal resolution of the input if not specified.
+    width: Option<usize>,
+
+    /// Lookups per pixel along each axis. Depends on the size reduction if not specified.
+    samples: Option<usize>,
+
+    /// Write a tiled file with mip maps, using this tile size.
+    mip_map_tile_size: Option<usize>,
+
+    /// Keep the compression of each layer if not specified.
+    compression: Option<Compression>,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut files = Vec::new();
+        let mut source = None;
+        let mut target = None;
+        let mut width = None;
+        let mut samples = None;
+        let mut mip_map = false;
+        let mut tile_size = 64;
+        let mut compression = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+
+            let number = |text: String| match text.parse::<usize>() {
+                Ok(0) | Err(_) => Err(format!("Invalid number '{text}'")),
+                Ok(number) => Ok(number),
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--from" => source = Some(parse_layout(&value(arg)?)?),
+                "--to" => target = Some(parse_layout(&value(arg)?)?),
+                "-w" | "--width" => width = Some(number(value(arg)?)?),
+                "-s" | "--samples" => samples = Some(number(value(arg)?)?),
+                "-m" | "--mipmap" => mip_map = true,
+                "-t" | "--tile-size" => tile_size = number(value(arg)?)?,
+                "-z" | "--compression" => {
+                    let name = value(arg)?;
+                    compression = Some(
+                        parse_compression(&name)
+                            .ok_or_else(|| format!("Unknown compression '{name}'"))?,
+                    );
+                }
+                _ if !arg.starts_with('-') || arg == STANDARD_STREAM => {
+                    files.push(PathBuf::from(arg))
+                }
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        match <[PathBuf; 2]>::try_from(files) {
+            Ok([input, output]) => Ok(Some(Options {
+                input,
+                output,
+                source,
+                target,
+                width,
+                samples,
+                mip_map_tile_size: if mip_map { Some(tile_size) } else { None },
+                compression,
+            })),
+
+            Err(_) => Err(
+                "Expected an input and an output file. Use 'exrs envmap --help' for usage."
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+fn parse_layout(name: &str) -> Result<EnvironmentMap, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "latlong" | "latlon" => Ok(EnvironmentMap::LatitudeLongitude),
+        "cube" => Ok(EnvironmentMap::Cube),
+        _ => Err(format!(
+            "Unknown environment map '{name}', expected 'latlong' or 'cube'"
+        )),
+    }
+}
+
+fn convert(options: &Options) -> Result<(), String> {
+    let input = &options.input;
+    let image = Input::open(input)
+        .and_then(stdio::read_flat_layers)
+        .map_err(|error| format!("{}: {error}", input.display()))?;
+
+    let mut display_size = None;
+    let mut layers = Vec::with_capacity(image.layer_data.len());
+
+    for layer in image.layer_data {
+        let source = options
+            .source
+            .or(layer.attributes.environment_map)
+            .ok_or_else(|| {
+                format!(
+                    "{}: the file has no 'envmap' attribute, use '--from' to specify the layout",
+                    input.display()
+                )
+            })?;
+
+        let source_size = layer.size;
+        source
+            .validate_size(source_size)
+            .map_err(|error| format!("{}: {error}", input.display()))?;
+
+        let target = options.target.unwrap_or(match source {
+            EnvironmentMap::LatitudeLongitude => EnvironmentMap::Cube,
+            EnvironmentMap::Cube => EnvironmentMap::LatitudeLongitude,
+        });
+
+        // the width option is the face size of cube maps
+        let resolution = match options.width {
+            Some(width) if target == EnvironmentMap::Cube => width * 4,
+            Some(width) => width,
+            None => source.horizontal_resolution(source_size),
+        };
+
+        let size = target.size_for_horizontal_resolution(resolution);
+        display_size.get_or_insert(size);
+
+        if let Some(channel) = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.sampling != Vec2(1, 1))
+        {
+            return Err(format!(
+                "{}: channel '{}' is subsampled, which is not supported for environment maps",
+                input.display(),
+                channel.name
+            ));
+        }
+
+        // every level is resampled from the input, such that cube faces never blend into each other
+        let level_sizes: Vec<Vec2<usize>> = match options.mip_map_tile_size {
+            None => vec![size],
+            Some(_) => mip_map_levels(RoundingMode::Down, size)
+                .map(|(_, level_size)| level_size)
+                .collect(),
+        };
+
+        let list = layer
+            .channel_data
+            .list
+            .into_iter()
+            .map(|channel| {
+                let levels = level_sizes
+                    .iter()
+                    .map(|&level_size| {
+                        let samples_per_axis = options.samples.unwrap_or_else(|| {
+                            source.samples_per_axis(source_size, target, level_size)
+                        });
+
+                        resample_samples(
+                            &channel.sample_data,
+                            source,
+                            source_size,
+                            target,
+                            level_size,
+                            samples_per_axis,
+                        )
+                        .map_err(|error| error.to_string())
+                    })
+                    .collect::<Result<Vec<FlatSamples>, String>>()?;
+
+                let sample_data = match options.mip_map_tile_size {
+                    None => Levels::Singular(levels.into_iter().next().expect("level bug")),
+                    Some(_) => Levels::Mip {
+                        rounding_mode: RoundingMode::Down,
+                        level_data: levels,
+                    },
+                };
+
+                Ok(AnyChannel {
+                    name: channel.name,
+                    sample_data,
+                    quantize_linearly: channel.quantize_linearly,
+                    sampling: channel.sampling,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        let mut attributes = layer.attributes;
+        attributes.environment_map = Some(target);
+        attributes.layer_position = Vec2(0, 0);
+
+        let encoding = match options.mip_map_tile_size {
+            None => layer.encoding,
+            Some(tile_size) => Encoding {
+                blocks: Blocks::Tiles(Vec2(tile_size, tile_size)),
+                line_order: LineOrder::Increasing,
+                ..layer.encoding
+            },
+        };
+
+        layers.push(Layer {
+            channel_data: AnyChannels { list },
+            attributes,
+            size,
+            encoding: Encoding {
+                compression: options.compression.unwrap_or(encoding.compression),
+                ..encoding
+            },
+        });
+    }
+
+    let mut attributes = image.attributes;
+    if let Some(size) = display_size {
+        attributes.display_window = IntegerBounds::from_dimensions(size);
+    }
+
+    let image = Image {
+        attributes,
+        layer_data: layers.into_iter().collect::<Layers<_>>(),
+    };
+
+    stdio::write(&options.output, |output| {
+        image.write().to_unbuffered(output)
+    })
+    .map_err(|error| format!("{}: {error}", options.output.display()))
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs envmap - Convert between latitude-longitude and cube environment maps
+
+USAGE:
+    exrs envmap [OPTIONS] <INPUT.exr> <OUTPUT.exr>
+
+OPTIONS:
+    --from <LAYOUT>             Layout of the input: latlong or cube [default: envmap attribute]
+    --to <LAYOUT>               Layout of the output: latlong or cube [default: the other one]
+    -w, --width <N>             Width of a latlong map, or face size of a cube map
+                                [default: same resolution as the input]
+    -s, --samples <N>           Average NxN lookups per pixel [default: by size reduction]
+    -m, --mipmap                Write a tiled file with mip maps
+    -t, --tile-size <N>         Tile size for mip maps [default: 64]
+    -z, --compression <NAME>    Compression, such as zip or piz [default: keep]
+    -h, --help                  Show this help
+
+Cube maps stack the faces +X, -X, +Y, -Y, +Z, -Z from top to bottom, and are six
+times as high as they are wide. Latitude-longitude maps are twice as wide as high.
+Each pixel averages lookups spread over its area, with bilinear interpolation.
+Each mip level is resampled from the input, so cube faces do not blend into
+each other. Integer channels, such as ids, use the nearest pixel instead.
+
+EXAMPLES:
+    exrs envmap sky.exr sky_cube.exr
+    exrs envmap --to latlong -w 2048 sky_cube.exr sky.exr
+    exrs envmap --from latlong -m -z piz sky.exr sky_cube.tx.exr
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn arguments(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = Options::parse(&arguments(&[
+            "--from", "latlong", "-w", "256", "-m", "in.exr", "out.exr",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.source, Some(EnvironmentMap::LatitudeLongitude));
+        assert_eq!(options.target, None);
+        assert_eq!(options.width, Some(256));
+        assert_eq!(options.mip_map_tile_size, Some(64));
+
+        assert!(Options::parse(&arguments(&["--to", "sphere", "a.exr", "b.exr"])).is_err());
+        assert!(Options::parse(&arguments(&["-w", "0", "a.exr", "b.exr"])).is_err());
+        assert!(Options::parse(&arguments(&["a.exr"])).is_err());
+    }
+}
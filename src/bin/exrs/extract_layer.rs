@@ -0,0 +1,402 @@
+//! `exrs extract-layer`: copy layers out of files with many layers, such as packed AOV deliveries.
+//! A layer is either a group of channels with a common prefix, like `diffuse.R`, or a whole part.
+
+use std::io::Seek;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use exr::image::write::WritableImage;
+use exr::image::{AnyChannel, AnyChannels, FlatSamples, Image, Layer, Layers, Levels};
+use exr::meta::attribute::Text;
+use exr::meta::header::{Header, ImageAttributes};
+use exr::meta::MetaData;
+
+use crate::multipart::split_file_name;
+use crate::stdio::{self, Input, STANDARD_STREAM};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match extract(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Destination {
+    /// Write all extracted layers to this file, one part per layer.
+    File(PathBuf),
+
+    /// Write each layer to its own file, named `BASE.LAYER.exr`.
+    Split(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    input: PathBuf,
+    layers: Vec<String>,
+    destination: Destination,
+
+    /// Keep the layer prefix of the channel names, instead of `diffuse.R` becoming `R`.
+    keep_names: bool,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut positional = Vec::new();
+        let mut destination = None;
+        let mut keep_names = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .map(PathBuf::from)
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-o" | "--output" => destination = Some(Destination::File(value(arg)?)),
+                "-s" | "--split" => destination = Some(Destination::Split(value(arg)?)),
+                "--keep-names" => keep_names = true,
+                _ if !arg.starts_with('-') || arg == STANDARD_STREAM => {
+                    positional.push(arg.clone())
+                }
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        let usage = "Use 'exrs extract-layer --help' for usage.";
+        let destination =
+            destination.ok_or_else(|| format!("Missing '--output' or '--split'. {usage}"))?;
+
+        if positional.len() < 2 {
+            return Err(format!("Expected an input file and layer names. {usage}"));
+        }
+
+        let input = PathBuf::from(positional.remove(0));
+
+        let mut layers: Vec<String> = Vec::new();
+        for layer in positional {
+            let layer = layer.trim_end_matches('.').to_string();
+            if !layers.contains(&layer) {
+                layers.push(layer);
+            }
+        }
+
+        Ok(Some(Options {
+            input,
+            layers,
+            destination,
+            keep_names,
+        }))
+    }
+}
+
+/// Where a requested layer was found in the input file.
+#[derive(Debug, Clone, PartialEq)]
+struct LayerLocation {
+    part: usize,
+
+    /// The prefix of the channels, or `None` if the whole part is extracted.
+    channel_layer: Option<String>,
+}
+
+/// Prefers channel layers, and falls back to part names. Searches the parts in order.
+fn find_layer(headers: &[Header], name: &str) -> Option<LayerLocation> {
+    let channel_layer = headers.iter().position(|header| {
+        header
+            .channel_layer_names()
+            .iter()
+            .any(|layer| layer.eq(name))
+    });
+
+    if let Some(part) = channel_layer {
+        return Some(LayerLocation {
+            part,
+            channel_layer: Some(name.to_string()),
+        });
+    }
+
+    headers
+        .iter()
+        .position(|header| {
+            header
+                .own_attributes
+                .layer_name
+                .as_ref()
+                .map_or(false, |layer_name| layer_name.eq(name))
+        })
+        .map(|part| LayerLocation {
+            part,
+            channel_layer: None,
+        })
+}
+
+/// All layers of the file, for the error message if a layer was not found.
+fn available_layers(headers: &[Header]) -> Vec<String> {
+    let mut layers: Vec<String> = Vec::new();
+
+    for header in headers {
+        if let Some(name) = &header.own_attributes.layer_name {
+            layers.push(name.to_string());
+        }
+
+        layers.extend(header.channel_layer_names().iter().map(Text::to_string));
+    }
+
+    layers.dedup();
+    layers
+}
+
+/// Keep only the channels of the layer, including nested layers, and optionally remove the prefix.
+fn layer_channels<Samples>(
+    channels: AnyChannels<Samples>,
+    layer: &str,
+    keep_names: bool,
+) -> AnyChannels<Samples> {
+    let prefix = format!("{layer}.");
+
+    let list = channels
+        .list
+        .into_iter()
+        .filter_map(|channel| {
+            let name = channel.name.to_string();
+            let short_name = name.strip_prefix(&prefix)?;
+
+            let name = if keep_names {
+                channel.name
+            } else {
+                Text::from(short_name)
+            };
+
+            Some(AnyChannel { name, ..channel })
+        })
+        .collect();
+
+    AnyChannels::sort(list)
+}
+
+fn extract(options: &Options) -> Result<(), String> {
+    let input = &options.input;
+    let in_file = |error: exr::error::Error| format!("{}: {error}", input.display());
+
+    // standard input can only be read once, so the meta data and the pixels are read from the same source
+    let mut source = Input::open(input).map_err(in_file)?;
+    let meta_data = MetaData::read_from_buffered(&mut source, false).map_err(in_file)?;
+    source.rewind().map_err(|error| in_file(error.into()))?;
+
+    let locations = options
+        .layers
+        .iter()
+        .map(|name| {
+            find_layer(&meta_data.headers, name).ok_or_else(|| {
+                format!(
+                    "{}: no layer named '{name}', available layers: {}",
+                    input.display(),
+                    available_layers(&meta_data.headers).join(", ")
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let image = stdio::read_all_data(source).map_err(in_file)?;
+
+    let layers: Vec<(String, Layer<AnyChannels<Levels<FlatSamples>>>)> = options
+        .layers
+        .iter()
+        .zip(&locations)
+        .map(|(name, location)| {
+            let source = image.layer_data[location.part].clone();
+
+            let layer = match &location.channel_layer {
+                None => source,
+                Some(channel_layer) => {
+                    let header = &meta_data.headers[location.part];
+
+                    Layer {
+                        channel_data: layer_channels(
+                            source.channel_data,
+                            channel_layer,
+                            options.keep_names,
+                        ),
+                        attributes: header.layer_scope(channel_layer).extracted_attributes(),
+                        ..source
+                    }
+                }
+            };
+
+            (name.clone(), layer)
+        })
+        .collect();
+
+    match &options.destination {
+        Destination::File(output) => {
+            let layers: Layers<_> = layers.into_iter().map(|(_, layer)| layer).collect();
+            write_layers(image.attributes.clone(), layers, output)
+        }
+
+        Destination::Split(base) => {
+            for (name, layer) in layers {
+                let output = split_file_name(base, 0, Some(&Text::from(name.as_str())));
+                write_layers(
+                    image.attributes.clone(),
+                    std::iter::once(layer).collect(),
+                    &output,
+                )?;
+                println!("{}", output.display());
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn write_layers(
+    attributes: ImageAttributes,
+    layers: Layers<AnyChannels<Levels<FlatSamples>>>,
+    output: &Path,
+) -> Result<(), String> {
+    let image = Image {
+        attributes,
+        layer_data: layers,
+    };
+
+    stdio::write(output, |write| image.write().to_unbuffered(write))
+        .map_err(|error| format!("{}: {error}", output.display()))
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs extract-layer - Copy layers of an EXR file into new files
+
+USAGE:
+    exrs extract-layer [OPTIONS] <INPUT.exr> <LAYER>... (-o <OUTPUT.exr> | -s <OUTPUT_BASE>)
+
+OPTIONS:
+    -o, --output <FILE>     Write all layers to this file, one part per layer
+    -s, --split <BASE>      Write each layer to its own file, named 'BASE.LAYER.exr'
+    --keep-names            Keep the layer prefix of channel names, like 'diffuse.R'
+    -h, --help              Show this help
+
+A layer is a group of channels with a common prefix, such as 'diffuse' for the
+channels 'diffuse.R', 'diffuse.G', and 'diffuse.B', including nested layers like
+'diffuse.left'. If no channels have the prefix, the part with that name is used.
+
+Channel types and resolution levels are preserved. Attributes prefixed with the
+layer name, like 'diffuse.blurAmount', replace the unprefixed attributes, and
+attributes of other layers are removed.
+
+EXAMPLES:
+    exrs extract-layer beauty.exr diffuse specular -o lighting.exr
+    exrs extract-layer beauty.exr diffuse specular depth -s aovs/beauty
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use exr::meta::attribute::{ChannelDescription, SampleType};
+
+    fn arguments(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = Options::parse(&arguments(&[
+            "in.exr", "diffuse.", "specular", "diffuse", "-s", "out/base",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.input, PathBuf::from("in.exr"));
+        assert_eq!(options.layers, vec!["diffuse", "specular"]);
+        assert_eq!(
+            options.destination,
+            Destination::Split(PathBuf::from("out/base"))
+        );
+
+        assert!(Options::parse(&arguments(&["in.exr", "diffuse"])).is_err());
+        assert!(Options::parse(&arguments(&["in.exr", "-o", "out.exr"])).is_err());
+    }
+
+    #[test]
+    fn find_layers() {
+        let channels = ["A", "diffuse.R", "diffuse.left.R"]
+            .iter()
+            .map(|&name| ChannelDescription::named(name, SampleType::F16))
+            .collect();
+
+        let headers = vec![
+            Header::new(Text::from("beauty"), (4, 4), channels),
+            Header::new(Text::from("depth"), (4, 4), Default::default()),
+        ];
+
+        assert_eq!(
+            find_layer(&headers, "diffuse.left"),
+            Some(LayerLocation {
+                part: 0,
+                channel_layer: Some("diffuse.left".to_string())
+            })
+        );
+
+        assert_eq!(
+            find_layer(&headers, "depth"),
+            Some(LayerLocation {
+                part: 1,
+                channel_layer: None
+            })
+        );
+
+        assert_eq!(find_layer(&headers, "specular"), None);
+    }
+
+    #[test]
+    fn strip_channel_prefixes() {
+        let channel = |name: &str| AnyChannel::new(name, FlatSamples::F32(vec![0.0]));
+        let channels = AnyChannels::sort(
+            vec![
+                channel("R"),
+                channel("diffuse.R"),
+                channel("diffuse.left.R"),
+            ]
+            .into(),
+        );
+
+        let names = |channels: AnyChannels<FlatSamples>| -> Vec<String> {
+            channels
+                .list
+                .iter()
+                .map(|channel| channel.name.to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            names(layer_channels(channels.clone(), "diffuse", false)),
+            vec!["R", "left.R"]
+        );
+        assert_eq!(
+            names(layer_channels(channels, "diffuse", true)),
+            vec!["diffuse.R", "diffuse.left.R"]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! `exrs flatten`: composite deep files into flat files, optionally only a slice of the depth range.
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use exr::image::flatten::{CompositingOrder, FlattenOptions};
+use exr::image::write::WritableImage;
+use exr::image::{FlatImage, Layers};
+use exr::meta::describe::parse_compression;
+use exr::prelude::Compression;
+
+use crate::stdio::{self, Input, STANDARD_STREAM};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match flatten(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    input: PathBuf,
+    output: PathBuf,
+    flatten: FlattenOptions,
+
+    /// Keep the compression of each layer if not specified.
+    compression: Option<Compression>,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut files = Vec::new();
+        let mut near = None;
+        let mut far = None;
+        let mut keep_depth = false;
+        let mut order = CompositingOrder::FrontToBack;
+        let mut compression = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+
+            let depth = |text: String| {
+                text.parse::<f32>()
+                    .map_err(|_| format!("Invalid depth '{text}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--near" => near = Some(depth(value(arg)?)?),
+                "--far" => far = Some(depth(value(arg)?)?),
+                "--keep-z" => keep_depth = true,
+                "--back-to-front" => order = CompositingOrder::BackToFront,
+                "-z" | "--compression" => {
+                    let name = value(arg)?;
+                    compression = Some(
+                        parse_compression(&name)
+                            .ok_or_else(|| format!("Unknown compression '{name}'"))?,
+                    );
+                }
+                _ if !arg.starts_with('-') || arg == STANDARD_STREAM => {
+                    files.push(PathBuf::from(arg))
+                }
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        let depth_range = match (near, far) {
+            (None, None) => None,
+            (near, far) => {
+                let range = (
+                    near.unwrap_or(f32::NEG_INFINITY),
+                    far.unwrap_or(f32::INFINITY),
+                );
+
+                if range.0 > range.1 {
+                    return Err(format!(
+                        "The near depth {} is behind the far depth {}",
+                        range.0, range.1
+                    ));
+                }
+
+                Some(range)
+            }
+        };
+
+        match <[PathBuf; 2]>::try_from(files) {
+            Ok([input, output]) => Ok(Some(Options {
+                input,
+                output,
+                flatten: FlattenOptions {
+                    depth_range,
+                    keep_depth,
+                    order,
+                },
+                compression,
+            })),
+
+            Err(_) => Err(
+                "Expected an input and an output file. Use 'exrs flatten --help' for usage."
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+fn flatten(options: &Options) -> Result<(), String> {
+    let image = Input::open(&options.input)
+        .and_then(stdio::read_deep_layers)
+        .map_err(|error| format!("{}: {error}", options.input.display()))?;
+
+    let layers = image
+        .layer_data
+        .into_iter()
+        .map(|layer| {
+            let mut layer = layer
+                .flatten(options.flatten)
+                .map_err(|error| format!("{}: {error}", options.input.display()))?;
+
+            if let Some(compression) = options.compression {
+                layer.encoding.compression = compression;
+            }
+
+            Ok(layer)
+        })
+        .collect::<Result<Layers<_>, String>>()?;
+
+    let image = FlatImage {
+        attributes: image.attributes,
+        layer_data: layers,
+    };
+
+    stdio::write(&options.output, |output| {
+        image.write().to_unbuffered(output)
+    })
+    .map_err(|error| format!("{}: {error}", options.output.display()))
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs flatten - Composite deep EXR files into flat EXR files
+
+USAGE:
+    exrs flatten [OPTIONS] <INPUT.exr> <OUTPUT.exr>
+
+OPTIONS:
+    --near <DEPTH>              Ignore samples in front of this depth
+    --far <DEPTH>               Ignore samples behind this depth
+    --keep-z                    Add a Z channel with the depth of the front-most sample
+    --back-to-front             Composite the sorted samples back to front
+    -z, --compression <NAME>    Compression, such as zip or piz [default: keep]
+    -h, --help                  Show this help
+
+The samples of each pixel are sorted by Z and composited front to back,
+or back to front if specified.
+Color channels are composited with the alpha channel of their layer, or 'A'.
+Channels without alpha, and integer channels such as ids, contain the value
+of the front-most sample. Volumetric samples with a 'ZBack' channel are split
+at the near and far depths. Pixels without samples have a Z of infinity.
+
+EXAMPLES:
+    exrs flatten deep.exr flat.exr
+    exrs flatten --near 10 --far 25 --keep-z deep.exr slice.exr
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn arguments(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = Options::parse(&arguments(&[
+            "--far", "25", "--keep-z", "in.exr", "out.exr",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.flatten.depth_range, Some((f32::NEG_INFINITY, 25.0)));
+        assert!(options.flatten.keep_depth);
+        assert_eq!(options.compression, None);
+
+        assert!(
+            Options::parse(&arguments(&["--near", "5", "--far", "1", "a.exr", "b.exr"])).is_err()
+        );
+        assert!(Options::parse(&arguments(&["in.exr"])).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! `exrs info`: print the meta data of files, like `exrheader`.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use exr::math::Vec2;
+use exr::meta::attribute::{IntegerBounds, Text};
+use exr::meta::describe::{compression_name, line_order_name, JsonValue};
+use exr::meta::header::Header;
+use exr::meta::{BlockDescription, MetaData};
+
+use crate::stdio::{self, STANDARD_STREAM};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let mut json = false;
+    let mut files = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print_help();
+                return ExitCode::SUCCESS;
+            }
+            "--json" => json = true,
+            _ if !arg.starts_with('-') || arg == STANDARD_STREAM => files.push(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Error: Unknown option '{arg}'");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if files.is_empty() {
+        eprintln!("Error: No input files. Use 'exrs info --help' for usage.");
+        return ExitCode::FAILURE;
+    }
+
+    let results: Vec<_> = files
+        .into_iter()
+        .map(|path| {
+            let meta_data = stdio::read_meta_data(&path);
+            (path, meta_data)
+        })
+        .collect();
+
+    if json {
+        print_json(&results);
+    } else {
+        print_text(&results);
+    }
+
+    if results.iter().all(|(_, meta_data)| meta_data.is_ok()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Prints an array with one object per file,
+/// containing the `path` and either the `meta_data` or an `error` message.
+fn print_json(results: &[(PathBuf, exr::error::Result<MetaData>)]) {
+    let files = results
+        .iter()
+        .map(|(path, meta_data)| {
+            let path = ("path", JsonValue::from(path.display().to_string().as_str()));
+
+            match meta_data {
+                Ok(meta_data) => JsonValue::object(vec![path, ("meta_data", meta_data.describe())]),
+                Err(error) => JsonValue::object(vec![
+                    path,
+                    ("error", JsonValue::from(error.to_string().as_str())),
+                ]),
+            }
+        })
+        .collect();
+
+    println!("{}", JsonValue::Array(files));
+}
+
+fn print_text(results: &[(PathBuf, exr::error::Result<MetaData>)]) {
+    for (index, (path, meta_data)) in results.iter().enumerate() {
+        if index != 0 {
+            println!();
+        }
+
+        println!("file {}:", path.display());
+
+        let meta_data = match meta_data {
+            Ok(meta_data) => meta_data,
+            Err(error) => {
+                eprintln!("Error: {}: {error}", path.display());
+                continue;
+            }
+        };
+
+        let requirements = &meta_data.requirements;
+        let mut flags = Vec::new();
+        if requirements.is_single_layer_and_tiled {
+            flags.push("tiled");
+        }
+        if requirements.has_long_names {
+            flags.push("long names");
+        }
+        if requirements.has_deep_data {
+            flags.push("deep");
+        }
+        if requirements.has_multiple_layers {
+            flags.push("multipart");
+        }
+
+        println!(
+            "  file format version: {}",
+            requirements.file_format_version
+        );
+        if !flags.is_empty() {
+            println!("  flags: {}", flags.join(", "));
+        }
+
+        for (index, header) in meta_data.headers.iter().enumerate() {
+            print_header(index, header);
+        }
+    }
+}
+
+fn print_header(index: usize, header: &Header) {
+    match &header.own_attributes.layer_name {
+        Some(name) => println!("\n  part {index}: {name}"),
+        None => println!("\n  part {index}"),
+    }
+
+    let block_type = match (header.blocks, header.deep) {
+        (BlockDescription::ScanLines, false) => "scanline",
+        (BlockDescription::ScanLines, true) => "deep scanline",
+        (BlockDescription::Tiles(_), false) => "tiled",
+        (BlockDescription::Tiles(_), true) => "deep tiled",
+    };
+
+    println!("    type: {block_type}");
+    println!("    compression: {}", compression_name(header.compression));
+    println!("    line order: {}", line_order_name(header.line_order));
+    println!("    data window: {}", bounds(header.data_window()));
+    println!(
+        "    display window: {}",
+        bounds(header.shared_attributes.display_window)
+    );
+    println!(
+        "    pixel aspect ratio: {}",
+        header.shared_attributes.pixel_aspect
+    );
+
+    if let BlockDescription::Tiles(tiles) = header.blocks {
+        println!(
+            "    tiles: {}, {:?}, rounding {:?}",
+            size(tiles.tile_size),
+            tiles.level_mode,
+            tiles.rounding_mode
+        );
+    }
+
+    println!("    chunks: {}", header.chunk_count);
+
+    println!("    channels:");
+    for channel in &header.channels.list {
+        print!("      {} ({:?}", channel.name, channel.sample_type);
+        if channel.sampling != Vec2(1, 1) {
+            print!(", sampling {}", size(channel.sampling));
+        }
+        if channel.quantize_linearly {
+            print!(", linear");
+        }
+        println!(")");
+    }
+
+    let mut attributes: Vec<_> = header.all_named_attributes().collect();
+    attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    println!("    attributes:");
+    for (name, value) in attributes {
+        println!(
+            "      {} ({}): {}",
+            Text::from_slice_unchecked(name),
+            Text::from_slice_unchecked(value.kind_name()),
+            JsonValue::from(&value).to_compact_string()
+        );
+    }
+}
+
+fn bounds(bounds: IntegerBounds) -> String {
+    format!(
+        "({}, {}) {}",
+        bounds.position.x(),
+        bounds.position.y(),
+        size(bounds.size)
+    )
+}
+
+fn size(size: Vec2<usize>) -> String {
+    format!("{}x{}", size.width(), size.height())
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs info - Print layers, channels, and attributes of EXR files
+
+USAGE:
+    exrs info [OPTIONS] <FILE.exr>...
+
+OPTIONS:
+    --json        Print a JSON array with one object per file, containing
+                  the 'path' and either 'meta_data' or an 'error' message.
+                  The meta data schema is documented in 'exr::meta::describe'.
+    -h, --help    Show this help
+
+The exit code is non-zero if any file could not be read.
+
+EXAMPLES:
+    exrs info render.exr
+    exrs info --json shots/*.exr > meta.json
+"#
+    );
+}
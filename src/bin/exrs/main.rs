@@ -0,0 +1,128 @@
+//! Command line tools for inspecting and processing EXR files.
+//!
+//! Usage:
+//!   exrs <COMMAND> [OPTIONS]
+//!
+//! Use `exrs --help` for a list of commands.
+
+use std::env;
+use std::process::ExitCode;
+
+mod attr;
+mod batch;
+mod check;
+mod convert;
+mod diff;
+mod envmap;
+mod extract_layer;
+mod flatten;
+mod info;
+mod maketiled;
+mod multipart;
+mod ocio;
+mod recompress;
+mod stats;
+mod stdio;
+mod thumbnail;
+mod verify;
+#[cfg(feature = "view")]
+mod view;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => {
+            print_help();
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match command {
+        "info" => info::run(args),
+        "convert" => convert::run(args),
+        "diff" => diff::run(args),
+        "stats" => stats::run(args),
+        "maketiled" => maketiled::run(args),
+        "multipart" => multipart::run(args),
+        "recompress" => recompress::run(args),
+        "attr" => attr::run(args),
+        "flatten" => flatten::run(args),
+        "extract-layer" => extract_layer::run(args),
+        "thumbnail" => thumbnail::run(args),
+        "verify" => verify::run(args),
+        "envmap" => envmap::run(args),
+        "check" => check::run(args),
+
+        #[cfg(feature = "view")]
+        "view" => view::run(args),
+
+        #[cfg(not(feature = "view"))]
+        "view" => {
+            eprintln!("Error: The viewer is not included. Rebuild with '--features view'.");
+            ExitCode::FAILURE
+        }
+
+        "-h" | "--help" | "help" => {
+            print_help();
+            ExitCode::SUCCESS
+        }
+
+        "-V" | "--version" => {
+            println!("exrs {VERSION}");
+            ExitCode::SUCCESS
+        }
+
+        other => {
+            eprintln!("Error: Unknown command '{other}'. Use --help for a list of commands.");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs - EXR command line tools v{VERSION}
+
+USAGE:
+    exrs <COMMAND> [OPTIONS]
+
+COMMANDS:
+    info           Print layers, channels, and attributes of files
+    convert        Convert between EXR and PNG, JPEG, TIFF, HDR, NumPy, or texture files
+    diff           Compare the pixels of two files
+    stats          Print pixel statistics and compression ratios of files
+    maketiled      Convert files to tiled files with mip maps or rip maps
+    multipart      Split multi-part files or merge files into a multi-part file
+    recompress     Rewrite files with a different compression
+    attr           Get, set, or delete attributes of files
+    flatten        Composite deep files into flat files
+    extract-layer  Copy layers of a file into new files
+    thumbnail      Write small PNG or JPEG images of files
+    verify         Decode all chunks of files and check their checksums
+    envmap         Convert between latitude-longitude and cube environment maps
+    check          Validate the headers, chunks, and pixels of files, like exrcheck
+    view           Open files in the interactive viewer (requires the 'view' feature)
+
+OPTIONS:
+    -h, --help       Show this help
+    -V, --version    Show version
+
+Use 'exrs <COMMAND> --help' for the options of a command.
+
+The commands info, convert, diff, stats, maketiled, recompress, flatten,
+extract-layer, and envmap accept '-' as a file name, to read EXR files from
+standard input or write them to standard output:
+    render | exrs recompress -z zip - -o out.exr
+    exrs flatten deep.exr - | exrs convert - preview.png
+
+The commands recompress, convert, and thumbnail process many files at once,
+selected by patterns like 'shot/beauty.####.exr', with '--jobs', '--progress',
+and '--report' for a JSON summary of all files.
+"#
+    );
+}
@@ -0,0 +1,238 @@
+//! `exrs maketiled`: convert images to tiled images with optional resolution levels, like `exrmaketiled`.
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use exr::image::resize::ResizeFilter;
+use exr::image::write::WritableImage;
+use exr::image::{Image, Layers};
+use exr::math::{RoundingMode, Vec2};
+use exr::meta::attribute::LevelMode;
+use exr::meta::describe::parse_compression;
+use exr::prelude::Compression;
+
+use crate::stdio::{self, Input, STANDARD_STREAM};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match make_tiled(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    input: PathBuf,
+    output: PathBuf,
+    tile_size: Vec2<usize>,
+    level_mode: LevelMode,
+    rounding_mode: RoundingMode,
+    filter: ResizeFilter,
+
+    /// Keep the compression of each layer if not specified.
+    compression: Option<Compression>,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut files = Vec::new();
+        let mut tile_size = Vec2(64, 64);
+        let mut level_mode = LevelMode::Singular;
+        let mut rounding_mode = RoundingMode::Down;
+        let mut filter = ResizeFilter::Box;
+        let mut compression = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-t" | "--tile-size" => tile_size = parse_tile_size(&value(arg)?)?,
+                "-o" | "--one-level" => level_mode = LevelMode::Singular,
+                "-m" | "--mipmap" => level_mode = LevelMode::MipMap,
+                "-r" | "--ripmap" => level_mode = LevelMode::RipMap,
+                "--round-up" => rounding_mode = RoundingMode::Up,
+                "-f" | "--filter" => {
+                    let name = value(arg)?;
+                    filter = ResizeFilter::from_name(&name)
+                        .ok_or_else(|| format!("Unknown filter '{name}'"))?;
+                }
+                "-z" | "--compression" => {
+                    let name = value(arg)?;
+                    compression = Some(
+                        parse_compression(&name)
+                            .ok_or_else(|| format!("Unknown compression '{name}'"))?,
+                    );
+                }
+                _ if !arg.starts_with('-') || arg == STANDARD_STREAM => {
+                    files.push(PathBuf::from(arg))
+                }
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        match <[PathBuf; 2]>::try_from(files) {
+            Ok([input, output]) => Ok(Some(Options {
+                input,
+                output,
+                tile_size,
+                level_mode,
+                rounding_mode,
+                filter,
+                compression,
+            })),
+
+            Err(_) => Err(
+                "Expected an input and an output file. Use 'exrs maketiled --help' for usage."
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+/// Parses either a single number for square tiles, or `WIDTHxHEIGHT`.
+fn parse_tile_size(text: &str) -> Result<Vec2<usize>, String> {
+    let invalid = || format!("Invalid tile size '{text}'");
+    let number = |text: &str| -> Result<usize, String> {
+        match text.parse() {
+            Ok(0) | Err(_) => Err(invalid()),
+            Ok(number) => Ok(number),
+        }
+    };
+
+    match text.split_once('x') {
+        Some((width, height)) => Ok(Vec2(number(width)?, number(height)?)),
+        None => {
+            let size = number(text)?;
+            Ok(Vec2(size, size))
+        }
+    }
+}
+
+fn make_tiled(options: &Options) -> Result<(), String> {
+    let image = Input::open(&options.input)
+        .and_then(stdio::read_flat_layers)
+        .map_err(|error| format!("{}: {error}", options.input.display()))?;
+
+    let layers = image
+        .layer_data
+        .into_iter()
+        .map(|layer| {
+            if let Some(channel) = layer
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.sampling != Vec2(1, 1))
+            {
+                return Err(format!(
+                    "Channel '{}' is subsampled, which is not supported for tiled images",
+                    channel.name
+                ));
+            }
+
+            let mut layer = layer
+                .generate_levels(
+                    options.tile_size,
+                    options.level_mode,
+                    options.rounding_mode,
+                    options.filter,
+                )
+                .map_err(|error| error.to_string())?;
+
+            if let Some(compression) = options.compression {
+                layer.encoding.compression = compression;
+            }
+
+            Ok(layer)
+        })
+        .collect::<Result<Layers<_>, String>>()?;
+
+    let image = Image {
+        attributes: image.attributes,
+        layer_data: layers,
+    };
+
+    stdio::write(&options.output, |output| {
+        image.write().to_unbuffered(output)
+    })
+    .map_err(|error| format!("{}: {error}", options.output.display()))
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs maketiled - Convert EXR files to tiled EXR files, optionally with mip maps or rip maps
+
+USAGE:
+    exrs maketiled [OPTIONS] <INPUT.exr> <OUTPUT.exr>
+
+OPTIONS:
+    -t, --tile-size <SIZE>      Tile size, either 'N' or 'WIDTHxHEIGHT' [default: 64]
+    -o, --one-level             Write only the full resolution [default]
+    -m, --mipmap                Write mip map levels
+    -r, --ripmap                Write rip map levels
+    --round-up                  Round level sizes up instead of down
+    -f, --filter <FILTER>       Filter for smaller levels: box, triangle, lanczos3,
+                                mitchell, gaussian [default: box]
+    -z, --compression <NAME>    Compression, such as zip or piz [default: keep]
+    -h, --help                  Show this help
+
+All layers of the input file are converted. Integer channels are not filtered,
+but use the nearest pixel of the larger level instead.
+
+EXAMPLES:
+    exrs maketiled -m texture.exr texture.tx.exr
+    exrs maketiled -r -t 32 -f lanczos3 -z piz env.exr env.tiled.exr
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_options() {
+        let args: Vec<String> = ["-m", "-t", "32x16", "-z", "piz", "in.exr", "out.exr"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        let options = Options::parse(&args).unwrap().unwrap();
+        assert_eq!(options.level_mode, LevelMode::MipMap);
+        assert_eq!(options.tile_size, Vec2(32, 16));
+        assert_eq!(options.compression, Some(Compression::PIZ));
+
+        assert!(Options::parse(&args[..6]).is_err());
+    }
+
+    #[test]
+    fn tile_sizes() {
+        assert_eq!(parse_tile_size("128"), Ok(Vec2(128, 128)));
+        assert_eq!(parse_tile_size("64x32"), Ok(Vec2(64, 32)));
+        assert!(parse_tile_size("0").is_err());
+        assert!(parse_tile_size("x").is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! `exrs multipart`: split multi-part files into single-part files and merge files into a multi-part file,
+//! like `exrmultipart`. Compressed chunks are copied without decompressing any pixels.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use exr::block::chunk::TileCoordinates;
+use exr::block::writer::ChunksWriter;
+use exr::error::{Error, UnitResult};
+use exr::meta::attribute::Text;
+use exr::meta::header::Header;
+use exr::meta::{Headers, MetaData};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let result = match args.split_first() {
+        Some((command, args)) => match command.as_str() {
+            "split" => split(args),
+            "merge" => merge(args),
+            "-h" | "--help" => {
+                print_help();
+                return ExitCode::SUCCESS;
+            }
+            other => Err(format!(
+                "Unknown multipart command '{other}'. Use 'exrs multipart --help' for usage."
+            )),
+        },
+
+        None => {
+            print_help();
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// A part of an input file to copy, written as `FILE[:PART][::NAME]` on the command line.
+#[derive(Debug, Clone, PartialEq)]
+struct PartSelection {
+    path: PathBuf,
+
+    /// Copy all parts if not specified.
+    part: Option<usize>,
+
+    /// Keep the original name if not specified.
+    name: Option<String>,
+}
+
+impl PartSelection {
+    fn parse(text: &str) -> Result<Self, String> {
+        let (text, name) = match text.rsplit_once("::") {
+            Some((text, name)) if !name.is_empty() => (text, Some(name.to_string())),
+            _ => (text, None),
+        };
+
+        // the part index must be a number, such that windows drive letters are not mistaken for part indices
+        let (path, part) = match text.rsplit_once(':') {
+            Some((path, part))
+                if !part.is_empty() && part.chars().all(|char| char.is_ascii_digit()) =>
+            {
+                let part = part
+                    .parse()
+                    .map_err(|_| format!("Invalid part index '{part}'"))?;
+                (path, Some(part))
+            }
+            _ => (text, None),
+        };
+
+        Ok(PartSelection {
+            path: PathBuf::from(path),
+            part,
+            name,
+        })
+    }
+}
+
+/// A part of an input file, with the header that it will have in the output file.
+#[derive(Debug, Clone)]
+struct SourcePart {
+    path: PathBuf,
+    part: usize,
+    header: Header,
+}
+
+fn split(args: &[String]) -> Result<(), String> {
+    let (input, output_base) = match args {
+        [flag] if flag == "-h" || flag == "--help" => {
+            print_help();
+            return Ok(());
+        }
+        [input, output_base] => (PathBuf::from(input), PathBuf::from(output_base)),
+        _ => {
+            return Err(
+                "Expected an input file and an output base name. Use 'exrs multipart --help' for usage."
+                    .to_string(),
+            )
+        }
+    };
+
+    let meta_data = MetaData::read_from_file(&input, false)
+        .map_err(|error| format!("{}: {error}", input.display()))?;
+
+    for (index, header) in meta_data.headers.into_iter().enumerate() {
+        let output = split_file_name(
+            &output_base,
+            index,
+            header.own_attributes.layer_name.as_ref(),
+        );
+
+        let source = SourcePart {
+            path: input.clone(),
+            part: index,
+            header,
+        };
+
+        copy_parts(&[source], &output).map_err(|error| format!("{}: {error}", output.display()))?;
+        println!("{}", output.display());
+    }
+
+    Ok(())
+}
+
+/// The file name of a single part, `BASE.NAME.exr`, or `BASE.INDEX.exr` for parts without a name.
+pub(crate) fn split_file_name(base: &Path, index: usize, name: Option<&Text>) -> PathBuf {
+    let suffix = match name {
+        Some(name) => name
+            .to_string()
+            .chars()
+            .map(|char| match char {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                char => char,
+            })
+            .collect(),
+
+        None => index.to_string(),
+    };
+
+    let mut file_name = base.as_os_str().to_owned();
+    file_name.push(format!(".{suffix}.exr"));
+    PathBuf::from(file_name)
+}
+
+fn merge(args: &[String]) -> Result<(), String> {
+    let mut selections = Vec::new();
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print_help();
+                return Ok(());
+            }
+            "-o" | "--output" => {
+                let path = args.next().ok_or("Missing value for '--output'")?;
+                output = Some(PathBuf::from(path));
+            }
+            _ if !arg.starts_with('-') => selections.push(PartSelection::parse(arg)?),
+            _ => return Err(format!("Unknown option '{arg}'")),
+        }
+    }
+
+    let output = output.ok_or("Missing output file. Use 'exrs multipart --help' for usage.")?;
+    if selections.is_empty() {
+        return Err("No input files. Use 'exrs multipart --help' for usage.".to_string());
+    }
+
+    let mut sources = Vec::new();
+    for selection in selections {
+        sources.extend(resolve_selection(&selection)?);
+    }
+
+    assign_unique_names(&mut sources)?;
+
+    if let Some((first, rest)) = sources.split_first() {
+        if rest
+            .iter()
+            .any(|source| source.header.shared_attributes != first.header.shared_attributes)
+        {
+            eprintln!(
+                "Warning: the display window, pixel aspect, chromaticities, or time code differ between parts"
+            );
+        }
+    }
+
+    copy_parts(&sources, &output).map_err(|error| format!("{}: {error}", output.display()))
+}
+
+fn resolve_selection(selection: &PartSelection) -> Result<Vec<SourcePart>, String> {
+    let path = &selection.path;
+    let meta_data = MetaData::read_from_file(path, false)
+        .map_err(|error| format!("{}: {error}", path.display()))?;
+
+    let part_count = meta_data.headers.len();
+    let parts: Vec<usize> = match selection.part {
+        Some(part) if part < part_count => vec![part],
+        Some(part) => {
+            return Err(format!(
+                "{}: part {part} does not exist, the file has {part_count} parts",
+                path.display()
+            ))
+        }
+        None => (0..part_count).collect(),
+    };
+
+    if selection.name.is_some() && parts.len() > 1 {
+        return Err(format!(
+            "{}: a part name can only be assigned to a single part, use 'FILE:PART::NAME'",
+            path.display()
+        ));
+    }
+
+    parts
+        .into_iter()
+        .map(|part| {
+            let mut header = meta_data.headers[part].clone();
+
+            if let Some(name) = &selection.name {
+                let name =
+                    Text::new_or_none(name).ok_or_else(|| format!("Invalid part name '{name}'"))?;
+                header.own_attributes.layer_name = Some(name);
+            }
+
+            Ok(SourcePart {
+                path: path.clone(),
+                part,
+                header,
+            })
+        })
+        .collect()
+}
+
+/// Parts in a multi-part file need unique names.
+/// Unnamed parts are named after their file, and duplicates get a numbered suffix.
+fn assign_unique_names(sources: &mut [SourcePart]) -> Result<(), String> {
+    let mut used = HashSet::new();
+
+    for source in sources {
+        let name = match &source.header.own_attributes.layer_name {
+            Some(name) => name.to_string(),
+            None => source
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "part".to_string()),
+        };
+
+        let mut unique = name.clone();
+        let mut counter = 1;
+        while !used.insert(unique.clone()) {
+            unique = format!("{name}_{counter}");
+            counter += 1;
+        }
+
+        let text =
+            Text::new_or_none(&unique).ok_or_else(|| format!("Invalid part name '{unique}'"))?;
+        source.header.own_attributes.layer_name = Some(text);
+    }
+
+    Ok(())
+}
+
+/// Write the chunks of all source parts to a new file, in the specified order.
+/// The chunks are copied as they are, without recompressing the pixels.
+fn copy_parts(sources: &[SourcePart], output: &Path) -> UnitResult {
+    let headers: Headers = sources.iter().map(|source| source.header.clone()).collect();
+    let file = BufWriter::new(File::create(output)?);
+
+    exr::block::write(file, headers, false, |_meta_data, writer| {
+        for (output_index, source) in sources.iter().enumerate() {
+            // the chunk table of the output file is ordered by increasing y coordinate
+            let chunk_indices: HashMap<TileCoordinates, usize> = source
+                .header
+                .blocks_increasing_y_order()
+                .enumerate()
+                .map(|(index, tile)| (tile.location, index))
+                .collect();
+
+            let reader = exr::block::read(BufReader::new(File::open(&source.path)?), false)?;
+            let chunks = reader.filter_chunks(false, |_, _, block| block.layer == source.part)?;
+
+            for chunk in chunks {
+                let mut chunk = chunk?;
+
+                let coordinates = source
+                    .header
+                    .get_block_data_indices(&chunk.compressed_block)?;
+                let index = *chunk_indices
+                    .get(&coordinates)
+                    .ok_or(Error::Invalid("chunk coordinates".into()))?;
+
+                chunk.layer_index = output_index;
+                writer.write_chunk(index, chunk)?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs multipart - Split and merge multi-part EXR files without recompressing
+
+USAGE:
+    exrs multipart split <INPUT.exr> <OUTPUT_BASE>
+    exrs multipart merge <INPUT>... -o <OUTPUT.exr>
+
+COMMANDS:
+    split    Write each part to its own file, named 'OUTPUT_BASE.NAME.exr',
+             or 'OUTPUT_BASE.INDEX.exr' for parts without a name
+    merge    Combine the parts of all inputs into a single file
+
+INPUTS:
+    FILE               All parts of the file
+    FILE:PART          Only the part with this index
+    FILE::NAME         The single part of the file, renamed
+    FILE:PART::NAME    Only the part with this index, renamed
+
+Unnamed parts are named after their file when merging.
+Duplicate names get a numbered suffix.
+
+OPTIONS:
+    -o, --output <FILE>    Output file of the merge command
+    -h, --help             Show this help
+
+EXAMPLES:
+    exrs multipart split beauty.exr parts/beauty
+    exrs multipart merge diffuse.exr specular.exr::spec depth.exr:1 -o combined.exr
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_part_selections() {
+        let selection = PartSelection::parse("shot.exr:2::beauty").unwrap();
+        assert_eq!(selection.path, PathBuf::from("shot.exr"));
+        assert_eq!(selection.part, Some(2));
+        assert_eq!(selection.name.as_deref(), Some("beauty"));
+
+        let selection = PartSelection::parse("C:\\renders\\shot.exr").unwrap();
+        assert_eq!(selection.path, PathBuf::from("C:\\renders\\shot.exr"));
+        assert_eq!(selection.part, None);
+        assert_eq!(selection.name, None);
+    }
+
+    #[test]
+    fn split_file_names() {
+        let base = Path::new("out/shot");
+        assert_eq!(
+            split_file_name(base, 3, None),
+            PathBuf::from("out/shot.3.exr")
+        );
+        assert_eq!(
+            split_file_name(base, 0, Some(&Text::from("left/rgba"))),
+            PathBuf::from("out/shot.left_rgba.exr")
+        );
+    }
+
+    #[test]
+    fn split_and_merge_round_trip() {
+        let input = Path::new("tests/images/valid/openexr/Beachball/multipart.0001.exr");
+        let meta_data = MetaData::read_from_file(input, false).unwrap();
+
+        let directory = std::env::temp_dir().join("exrs_multipart_test");
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut sources = Vec::new();
+        for (index, header) in meta_data.headers.iter().enumerate() {
+            let single = directory.join(format!("part{index}.exr"));
+            let source = SourcePart {
+                path: input.to_path_buf(),
+                part: index,
+                header: header.clone(),
+            };
+
+            copy_parts(&[source], &single).unwrap();
+
+            sources.push(SourcePart {
+                path: single,
+                part: 0,
+                header: header.clone(),
+            });
+        }
+
+        let merged = directory.join("merged.exr");
+        copy_parts(&sources, &merged).unwrap();
+
+        let original = exr::prelude::read_all_flat_layers_from_file(input).unwrap();
+        let copied = exr::prelude::read_all_flat_layers_from_file(&merged).unwrap();
+        assert_eq!(original.layer_data.len(), copied.layer_data.len());
+
+        for (original, copied) in original.layer_data.iter().zip(&copied.layer_data) {
+            assert_eq!(original.channel_data, copied.channel_data);
+        }
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+}
@@ -0,0 +1,136 @@
+//! OpenColorIO display transforms for `exrs convert` and `exrs thumbnail`,
+//! so that previews look like in the compositing tools that use the same studio config.
+
+use std::path::PathBuf;
+
+/// The help text of the options, indented like the other options.
+pub const HELP: &str = "    --ocio <CONFIG>          Apply a display transform of this OCIO config
+                             (default: the config of $OCIO, if another
+                             OCIO option is specified)
+    --colorspace <NAME>      The color space of the EXR file
+                             (default: the scene_linear role)
+    --display <NAME>         OCIO display (default: the first active display)
+    --view <NAME>            OCIO view (default: the first active view)";
+
+/// Selects the OpenColorIO display transform that replaces the transfer function.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayOptions {
+    /// Uses the `OCIO` environment variable if not specified.
+    pub config: Option<PathBuf>,
+
+    /// Uses the `scene_linear` role if not specified.
+    pub color_space: Option<String>,
+
+    /// Uses the defaults of the config if not specified.
+    pub display: Option<String>,
+    pub view: Option<String>,
+}
+
+#[cfg(feature = "ocio")]
+pub use exr::interop::ocio::Processor as DisplayTransform;
+
+/// Cannot be created without the `ocio` feature.
+#[cfg(not(feature = "ocio"))]
+#[derive(Debug, Clone)]
+pub enum DisplayTransform {}
+
+#[cfg(not(feature = "ocio"))]
+impl DisplayTransform {
+    pub fn apply_rgb(&self, _rgb: [f32; 3]) -> [f32; 3] {
+        match *self {}
+    }
+}
+
+impl DisplayOptions {
+    /// Parses the argument if it is one of the OCIO options. Returns whether it was.
+    pub fn parse_arg(
+        &mut self,
+        arg: &str,
+        value: impl FnOnce(&str) -> Result<String, String>,
+    ) -> Result<bool, String> {
+        match arg {
+            "--ocio" => self.config = Some(PathBuf::from(value(arg)?)),
+            "--colorspace" => self.color_space = Some(value(arg)?),
+            "--display" => self.display = Some(value(arg)?),
+            "--view" => self.view = Some(value(arg)?),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Whether any of the OCIO options was specified.
+    pub fn is_specified(&self) -> bool {
+        self.config.is_some()
+            || self.color_space.is_some()
+            || self.display.is_some()
+            || self.view.is_some()
+    }
+
+    /// Reads the config and creates the transform, if any of the OCIO options was specified.
+    #[cfg(feature = "ocio")]
+    pub fn transform(&self) -> Result<Option<DisplayTransform>, String> {
+        use exr::interop::ocio::OcioConfig;
+
+        if !self.is_specified() {
+            return Ok(None);
+        }
+
+        let config = match &self.config {
+            Some(path) => OcioConfig::from_file(path),
+            None => OcioConfig::from_env(),
+        };
+
+        let config = config.map_err(|error| format!("Cannot read OCIO config: {error}"))?;
+
+        let display = match &self.display {
+            Some(display) => display.as_str(),
+            None => config
+                .default_display()
+                .ok_or("The OCIO config has no displays")?,
+        };
+
+        let view = match &self.view {
+            Some(view) => view.as_str(),
+            None => config
+                .default_view(display)
+                .ok_or_else(|| format!("The OCIO display '{display}' has no views"))?,
+        };
+
+        let color_space = self.color_space.as_deref().unwrap_or("scene_linear");
+
+        config
+            .display_processor(color_space, display, view)
+            .map(Some)
+            .map_err(|error| error.to_string())
+    }
+
+    #[cfg(not(feature = "ocio"))]
+    pub fn transform(&self) -> Result<Option<DisplayTransform>, String> {
+        if self.is_specified() {
+            Err("OCIO transforms are not available. Rebuild with --features ocio".to_string())
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_display_options() {
+        let mut options = DisplayOptions::default();
+        assert!(!options.is_specified());
+
+        let value = |_: &str| Ok("sRGB".to_string());
+        assert_eq!(options.parse_arg("--display", value), Ok(true));
+        assert_eq!(options.parse_arg("--exposure", value), Ok(false));
+        assert_eq!(options.display.as_deref(), Some("sRGB"));
+        assert!(options.is_specified());
+
+        let missing = |name: &str| Err(format!("Missing value for '{name}'"));
+        assert!(options.parse_arg("--view", missing).is_err());
+    }
+}
@@ -0,0 +1,587 @@
+//! `exrs recompress`: rewrite files with a different compression, optionally converting `f32` channels to `f16`.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use exr::image::write::WritableImage;
+use exr::image::FlatSamples;
+use exr::meta::describe::{compression_name, parse_compression, JsonValue};
+use exr::prelude::{f16, Compression};
+
+use crate::batch::{self, run_jobs, BatchOptions, Progress, Report};
+use crate::stdio::{self, is_standard_stream, Input, STANDARD_STREAM};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let jobs = match collect_jobs(&options) {
+        Ok(jobs) => jobs,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if jobs.is_empty() {
+        eprintln!("Error: No input files. Use 'exrs recompress --help' for usage.");
+        return ExitCode::FAILURE;
+    }
+
+    if recompress_all(jobs, options) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    inputs: Vec<PathBuf>,
+
+    /// Replace the input files if not specified.
+    /// When reading standard input, this is the output file instead, and standard output if not specified.
+    output_directory: Option<PathBuf>,
+
+    compression: Compression,
+    half: bool,
+
+    /// Channels that are not converted to `f16`.
+    keep_f32: HashSet<String>,
+
+    recursive: bool,
+    only_smaller: bool,
+
+    /// Fill the pixels of damaged blocks with this value instead of failing.
+    salvage: Option<f32>,
+
+    batch: BatchOptions,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut inputs = Vec::new();
+        let mut output_directory = None;
+        let mut compression = None;
+        let mut half = false;
+        let mut keep_f32 = HashSet::new();
+        let mut recursive = false;
+        let mut only_smaller = false;
+        let mut salvage = None;
+        let mut batch = BatchOptions::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-z" | "--compression" => {
+                    compression = Some(parse_writable_compression(&value(arg)?)?);
+                }
+                "-o" | "--output" => output_directory = Some(PathBuf::from(value(arg)?)),
+                "--half" => half = true,
+                "--keep-f32" => {
+                    let list = value(arg)?;
+                    keep_f32.extend(list.split(',').map(str::to_string));
+                }
+                "-r" | "--recursive" => recursive = true,
+                "--only-smaller" => only_smaller = true,
+                "--salvage" => {
+                    let fill = value(arg)?;
+                    salvage = Some(
+                        fill.parse()
+                            .map_err(|_| format!("Invalid salvage value '{fill}'"))?,
+                    );
+                }
+                "-j" | "--jobs" => batch.threads = batch::parse_job_count(&value(arg)?)?,
+                "--progress" => batch.progress = true,
+                "--report" => batch.report = Some(PathBuf::from(value(arg)?)),
+                _ if !arg.starts_with('-') || arg == STANDARD_STREAM => {
+                    inputs.push(PathBuf::from(arg))
+                }
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        let compression = compression
+            .ok_or("Missing '--compression'. Use 'exrs recompress --help' for usage.")?;
+
+        Ok(Some(Options {
+            inputs,
+            output_directory,
+            compression,
+            half,
+            keep_f32,
+            recursive,
+            only_smaller,
+            salvage,
+            batch,
+        }))
+    }
+}
+
+/// A file to recompress, and where to write the result.
+#[derive(Debug, Clone, PartialEq)]
+struct Job {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+/// Finds the exr files in directories and patterns, and computes the output paths.
+/// Files in directories keep their relative path inside the output directory.
+fn collect_jobs(options: &Options) -> Result<Vec<Job>, String> {
+    let mut jobs = Vec::new();
+
+    for input in &options.inputs {
+        if is_standard_stream(input) {
+            if options.inputs.len() > 1 {
+                return Err("Standard input cannot be combined with other inputs".to_string());
+            }
+
+            let output = options
+                .output_directory
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(STANDARD_STREAM));
+
+            jobs.push(Job {
+                input: input.clone(),
+                output,
+            });
+        } else if input.is_dir() {
+            let mut files = Vec::new();
+            find_exr_files(input, options.recursive, &mut files)
+                .map_err(|error| format!("{}: {error}", input.display()))?;
+
+            files.sort();
+            jobs.extend(files.into_iter().map(|file| {
+                let output = match &options.output_directory {
+                    Some(directory) => directory.join(file.strip_prefix(input).unwrap_or(&file)),
+                    None => file.clone(),
+                };
+
+                Job {
+                    input: file,
+                    output,
+                }
+            }));
+        } else {
+            for file in batch::expand_pattern(input)? {
+                let output = match (&options.output_directory, file.file_name()) {
+                    (Some(directory), Some(name)) => directory.join(name),
+                    _ => file.clone(),
+                };
+
+                jobs.push(Job {
+                    input: file,
+                    output,
+                });
+            }
+        }
+    }
+
+    Ok(jobs)
+}
+
+pub(crate) fn find_exr_files(
+    directory: &Path,
+    recursive: bool,
+    files: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            if recursive {
+                find_exr_files(&path, recursive, files)?;
+            }
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("exr"))
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The file sizes before and after recompressing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Savings {
+    old_bytes: u64,
+    new_bytes: u64,
+
+    /// Whether the original file was kept, because the result was not smaller.
+    kept_original: bool,
+}
+
+/// Processes all jobs on multiple threads. Returns whether all files were successful.
+fn recompress_all(jobs: Vec<Job>, options: Options) -> bool {
+    // keep standard output clean if it contains the image
+    let to_stderr = jobs.iter().any(|job| is_standard_stream(&job.output));
+    let print = |line: String| {
+        if to_stderr {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+    };
+
+    let compression = options.compression;
+    let batch = options.batch.clone();
+    let mut progress = Progress::new(jobs.len(), batch.progress);
+    let mut report = Report::new("recompress");
+
+    let mut success = true;
+    let mut total_old = 0;
+    let mut total_new = 0;
+    let mut file_count = 0;
+
+    let all_finished = run_jobs(
+        jobs,
+        batch.threads,
+        move |job| recompress(job, &options),
+        |job, result, duration| {
+            progress.clear();
+
+            match &result {
+                Ok(savings) => {
+                    file_count += 1;
+                    total_old += savings.old_bytes;
+                    total_new += savings.new_bytes;
+
+                    let note = if savings.kept_original {
+                        " (kept original)"
+                    } else {
+                        ""
+                    };
+
+                    print(format!(
+                        "{}: {} -> {} ({}){note}",
+                        job.input.display(),
+                        bytes(savings.old_bytes),
+                        bytes(savings.new_bytes),
+                        percent(savings.old_bytes, savings.new_bytes)
+                    ));
+                }
+
+                Err(message) => {
+                    success = false;
+                    eprintln!("Error: {}: {message}", job.input.display());
+                }
+            }
+
+            let fields = result.as_ref().map_err(String::as_str).map(|savings| {
+                vec![
+                    ("old_bytes", JsonValue::from(savings.old_bytes)),
+                    ("new_bytes", JsonValue::from(savings.new_bytes)),
+                    ("kept_original", JsonValue::Bool(savings.kept_original)),
+                ]
+            });
+
+            report.add(&job.input, Some(&job.output), fields, duration);
+            progress.advance(result.is_ok());
+        },
+    );
+
+    progress.finish();
+
+    print(format!(
+        "{file_count} files, {} -> {} ({}) with {}",
+        bytes(total_old),
+        bytes(total_new),
+        percent(total_old, total_new),
+        compression_name(compression)
+    ));
+
+    if let Err(message) = report.write(batch.report.as_deref()) {
+        eprintln!("Error: {message}");
+        success = false;
+    }
+
+    success && all_finished
+}
+
+fn recompress(job: &Job, options: &Options) -> Result<Savings, String> {
+    let input = Input::open(&job.input).map_err(|error| error.to_string())?;
+    let old_bytes = input.byte_size().map_err(|error| error.to_string())?;
+    let image = match options.salvage {
+        None => stdio::read_all_data(input),
+        Some(fill) => stdio::salvage_all_data(input, fill),
+    };
+
+    let mut image = image.map_err(|error| error.to_string())?;
+
+    for layer in &mut image.layer_data {
+        layer.encoding.compression = options.compression;
+
+        if options.half {
+            for channel in &mut layer.channel_data.list {
+                if options.keep_f32.contains(&channel.name.to_string()) {
+                    continue;
+                }
+
+                for level in channel.sample_data.levels_as_slice_mut() {
+                    let converted = match level {
+                        FlatSamples::F32(samples) => FlatSamples::F16(
+                            samples
+                                .iter()
+                                .map(|&sample| f16::from_f32(sample))
+                                .collect(),
+                        ),
+                        _ => continue,
+                    };
+
+                    *level = converted;
+                }
+            }
+        }
+    }
+
+    // there is no input file to protect, so streams are written directly
+    if is_standard_stream(&job.input) {
+        let mut new_bytes = 0;
+
+        stdio::write(&job.output, |output| {
+            image.write().to_unbuffered(&mut *output)?;
+            new_bytes = output.seek(SeekFrom::End(0))?;
+            Ok(())
+        })
+        .map_err(|error| error.to_string())?;
+
+        return Ok(Savings {
+            old_bytes,
+            new_bytes,
+            kept_original: false,
+        });
+    }
+
+    if let Some(parent) = job.output.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+
+    // write to a temporary file first, such that the input is never left incomplete
+    let mut temporary = job.output.clone().into_os_string();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+
+    image
+        .write()
+        .to_file(&temporary)
+        .map_err(|error| error.to_string())?;
+
+    let new_bytes = fs::metadata(&temporary)
+        .map_err(|error| error.to_string())?
+        .len();
+
+    if options.only_smaller && new_bytes >= old_bytes {
+        fs::remove_file(&temporary).map_err(|error| error.to_string())?;
+
+        if job.output != job.input {
+            fs::copy(&job.input, &job.output).map_err(|error| error.to_string())?;
+        }
+
+        return Ok(Savings {
+            old_bytes,
+            new_bytes: old_bytes,
+            kept_original: true,
+        });
+    }
+
+    fs::rename(&temporary, &job.output).map_err(|error| error.to_string())?;
+
+    Ok(Savings {
+        old_bytes,
+        new_bytes,
+        kept_original: false,
+    })
+}
+
+/// The relative size change, such as `-25.0%`.
+fn percent(old_bytes: u64, new_bytes: u64) -> String {
+    if old_bytes == 0 {
+        return "-".to_string();
+    }
+
+    let change = (new_bytes as f64 - old_bytes as f64) / old_bytes as f64 * 100.0;
+    format!("{change:+.1}%")
+}
+
+fn bytes(count: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = count as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{count} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Find a compression method by its name, rejecting those that cannot be written yet.
+fn parse_writable_compression(name: &str) -> Result<Compression, String> {
+    match parse_compression(name) {
+        Some(
+            Compression::DWAA(_)
+            | Compression::DWAB(_)
+            | Compression::HTJ2K32
+            | Compression::HTJ2K256,
+        ) => Err(format!(
+            "Compression '{name}' is not supported for writing. \
+            Use one of none, rle, zips, zip, piz, pxr24, b44, b44a"
+        )),
+        Some(compression) => Ok(compression),
+        None => Err(format!("Unknown compression '{name}'")),
+    }
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs recompress - Rewrite EXR files with a different compression
+
+USAGE:
+    exrs recompress [OPTIONS] -z <COMPRESSION> <FILE.exr | PATTERN | DIRECTORY>...
+
+OPTIONS:
+    -z, --compression <NAME>    The new compression: none, rle, zips, zip, piz,
+                                pxr24, b44, b44a
+    -o, --output <DIRECTORY>    Write the files to this directory instead of
+                                replacing the input files
+    --half                      Convert f32 channels to f16
+    --keep-f32 <CHANNELS>       Comma separated channels to keep in f32, such as 'Z'
+    -r, --recursive             Also process files in subdirectories
+    --only-smaller              Keep the original file if the result is not smaller
+    --salvage <VALUE>           Repair damaged or truncated files, filling the
+                                pixels of unreadable blocks with this value
+    -j, --jobs <COUNT>          Number of files processed in parallel
+                                [default: number of processors]
+    --progress                  Draw a progress bar on standard error
+    --report <FILE.json>        Write the result of each file to a JSON report
+    -h, --help                  Show this help
+
+All resolution levels are kept. Deep files are not supported.
+Files are written to a temporary file first, and replace the input only on success.
+
+Patterns may contain '*', '?', and '[a-z]' wildcards, and frame numbers written
+as '####' or '%04d', where the count is the minimum number of digits. Quote them
+to prevent the shell from expanding them. Matching files are processed in frame order.
+
+Use '-' to read a single file from standard input. Then '--output' is the output
+file, which is standard output if not specified or '-'. '--only-smaller' is ignored.
+
+EXAMPLES:
+    exrs recompress -z piz --half --keep-f32 Z -r renders/
+    exrs recompress -z zip --only-smaller -o archive/ shots/*.exr
+    exrs recompress -z zip --salvage 0 -o repaired/ crashed_render.exr
+    exrs recompress -z piz -j 16 --progress --report report.json 'shot/beauty.####.exr'
+    render | exrs recompress -z zip - -o out.exr
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn arguments(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = Options::parse(&arguments(&[
+            "-z",
+            "piz",
+            "--half",
+            "--keep-f32",
+            "Z,depth",
+            "-j",
+            "2",
+            "--salvage",
+            "0.5",
+            "a.exr",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.compression, Compression::PIZ);
+        assert!(options.half);
+        assert!(options.keep_f32.contains("Z") && options.keep_f32.contains("depth"));
+        assert_eq!(options.batch.threads, 2);
+        assert_eq!(options.salvage, Some(0.5));
+        assert_eq!(options.inputs, vec![PathBuf::from("a.exr")]);
+
+        assert!(Options::parse(&arguments(&["a.exr"])).is_err());
+        assert!(Options::parse(&arguments(&["-z", "lzw", "a.exr"])).is_err());
+        assert!(Options::parse(&arguments(&["-z", "dwab", "a.exr"])).is_err());
+        assert!(Options::parse(&arguments(&["-z", "htj2k32", "a.exr"])).is_err());
+        assert!(Options::parse(&arguments(&["-z", "zip", "--salvage", "x", "a.exr"])).is_err());
+    }
+
+    #[test]
+    fn output_paths() {
+        let mut options = Options::parse(&arguments(&["-z", "zip", "shots/a.exr"]))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            collect_jobs(&options).unwrap()[0].output,
+            PathBuf::from("shots/a.exr")
+        );
+
+        options.output_directory = Some(PathBuf::from("archive"));
+        assert_eq!(
+            collect_jobs(&options).unwrap()[0].output,
+            PathBuf::from("archive/a.exr")
+        );
+
+        let options = Options::parse(&arguments(&["-z", "zip", "-"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            collect_jobs(&options).unwrap(),
+            vec![Job {
+                input: PathBuf::from("-"),
+                output: PathBuf::from("-"),
+            }]
+        );
+
+        let options = Options::parse(&arguments(&["-z", "zip", "-", "a.exr"]))
+            .unwrap()
+            .unwrap();
+        assert!(collect_jobs(&options).is_err());
+    }
+
+    #[test]
+    fn size_changes() {
+        assert_eq!(percent(200, 150), "-25.0%");
+        assert_eq!(percent(100, 110), "+10.0%");
+        assert_eq!(bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
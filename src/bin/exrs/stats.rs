@@ -0,0 +1,511 @@
+//! `exrs stats`: print pixel statistics and compression ratios of files.
+
+use std::fs;
+use std::io::Seek;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use exr::block::chunk::CompressedBlock;
+use exr::image::deep::DeepChannelData;
+use exr::image::deep_stats::DeepStats;
+use exr::meta::describe::compression_name;
+use exr::prelude::*;
+
+use crate::stdio::{self, Input, STANDARD_STREAM};
+
+pub fn run(args: &[String]) -> ExitCode {
+    let mut summary_only = false;
+    let mut paths = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print_help();
+                return ExitCode::SUCCESS;
+            }
+            "-s" | "--summary" => summary_only = true,
+            _ if !arg.starts_with('-') || arg == STANDARD_STREAM => paths.push(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Error: Unknown option '{arg}'");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let files = match expand_directories(paths) {
+        Ok(files) => files,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if files.is_empty() {
+        eprintln!("Error: No input files. Use 'exrs stats --help' for usage.");
+        return ExitCode::FAILURE;
+    }
+
+    let mut results = Vec::with_capacity(files.len());
+    for path in files {
+        let stats = FileStatistics::read(&path);
+
+        match &stats {
+            Ok(stats) if !summary_only => stats.print(&path),
+            Err(error) => eprintln!("Error: {}: {error}", path.display()),
+            _ => {}
+        }
+
+        results.push((path, stats));
+    }
+
+    print_summary(&results);
+
+    if results.iter().all(|(_, stats)| stats.is_ok()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Replaces each directory by the exr files it contains, sorted by name.
+fn expand_directories(paths: Vec<PathBuf>) -> std::result::Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path);
+            continue;
+        }
+
+        let entries =
+            fs::read_dir(&path).map_err(|error| format!("{}: {error}", path.display()))?;
+
+        let mut found: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.is_file() && is_exr(file))
+            .collect();
+
+        found.sort();
+        files.extend(found);
+    }
+
+    Ok(files)
+}
+
+fn is_exr(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("exr"))
+}
+
+#[derive(Debug, Clone)]
+struct FileStatistics {
+    file_bytes: u64,
+    parts: Vec<PartStatistics>,
+}
+
+#[derive(Debug, Clone)]
+struct PartStatistics {
+    name: Option<Text>,
+    compression: Compression,
+
+    /// Sum of the compressed pixel data of all chunks, excluding chunk headers.
+    compressed_bytes: usize,
+
+    /// Size of the pixel data without compression.
+    uncompressed_bytes: usize,
+
+    channels: Vec<(Text, ChannelStatistics)>,
+    deep: Option<DeepStats>,
+}
+
+impl FileStatistics {
+    fn read(path: &Path) -> Result<Self> {
+        let mut input = Input::open(path)?;
+        let file_bytes = input.byte_size()?;
+
+        let reader = exr::block::read(&mut input, false)?;
+        let meta_data: MetaData = reader.meta_data().clone();
+
+        let mut compressed_bytes = vec![0_usize; meta_data.headers.len()];
+        for chunk in reader.all_chunks(false)? {
+            let chunk = chunk?;
+
+            let size = match &chunk.compressed_block {
+                CompressedBlock::ScanLine(block) => block.compressed_pixels_le.len(),
+                CompressedBlock::Tile(block) => block.compressed_pixels_le.len(),
+                CompressedBlock::DeepScanLine(block) => {
+                    block.compressed_pixel_offset_table.len()
+                        + block.compressed_sample_data_le.len()
+                }
+                CompressedBlock::DeepTile(block) => {
+                    block.compressed_pixel_offset_table.len()
+                        + block.compressed_sample_data_le.len()
+                }
+            };
+
+            compressed_bytes[chunk.layer_index] += size;
+        }
+
+        let mut parts: Vec<PartStatistics> = meta_data
+            .headers
+            .iter()
+            .zip(compressed_bytes)
+            .map(|(header, compressed_bytes)| PartStatistics {
+                name: header.own_attributes.layer_name.clone(),
+                compression: header.compression,
+                compressed_bytes,
+                uncompressed_bytes: if header.deep {
+                    0
+                } else {
+                    header.total_pixel_bytes()
+                },
+                channels: Vec::new(),
+                deep: None,
+            })
+            .collect();
+
+        let deep_count = meta_data
+            .headers
+            .iter()
+            .filter(|header| header.deep)
+            .count();
+
+        if deep_count == 0 {
+            input.rewind()?;
+            let image = stdio::read_flat_layers(input)?;
+
+            for (part, layer) in parts.iter_mut().zip(&image.layer_data) {
+                part.channels = layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .map(|channel| {
+                        let statistics =
+                            ChannelStatistics::compute(channel.sample_data.values_as_f32());
+                        (channel.name.clone(), statistics)
+                    })
+                    .collect();
+            }
+        } else if deep_count == meta_data.headers.len() {
+            input.rewind()?;
+            let image = stdio::read_deep_layers(input)?;
+
+            for (part, layer) in parts.iter_mut().zip(&image.layer_data) {
+                // the first channel holds the samples of all channels
+                let samples = match layer.channel_data.list.first() {
+                    Some(channel) => &channel.sample_data,
+                    None => continue,
+                };
+
+                part.channels = layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .zip(&samples.channels)
+                    .map(|(channel, data)| {
+                        (
+                            channel.name.clone(),
+                            ChannelStatistics::compute(deep_values(data)),
+                        )
+                    })
+                    .collect();
+
+                let bytes_per_sample: usize = samples
+                    .channels
+                    .iter()
+                    .map(|data| data.bytes_per_sample())
+                    .sum();
+
+                part.uncompressed_bytes = samples.pixel_count() * std::mem::size_of::<u32>()
+                    + samples.total_samples() * bytes_per_sample;
+
+                part.deep = Some(layer.channel_data.stats());
+            }
+        } else {
+            eprintln!(
+                "Warning: {}: skipping pixel statistics of files with both deep and flat parts",
+                path.display()
+            );
+        }
+
+        Ok(FileStatistics { file_bytes, parts })
+    }
+
+    fn print(&self, path: &Path) {
+        println!(
+            "file {} ({}):",
+            path.display(),
+            bytes(self.file_bytes as usize)
+        );
+
+        for (index, part) in self.parts.iter().enumerate() {
+            match &part.name {
+                Some(name) => println!("  part {index}: {name}"),
+                None => println!("  part {index}"),
+            }
+
+            println!(
+                "    compression: {}, {} -> {} (ratio {})",
+                compression_name(part.compression),
+                bytes(part.uncompressed_bytes),
+                bytes(part.compressed_bytes),
+                ratio(part.uncompressed_bytes, part.compressed_bytes)
+            );
+
+            if let Some(deep) = &part.deep {
+                println!(
+                    "    deep samples: {} total, {:.2} per pixel, {} min, {} max",
+                    deep.total_samples,
+                    deep.mean_samples_per_pixel(),
+                    deep.min_samples_per_pixel,
+                    deep.max_samples_per_pixel
+                );
+
+                println!(
+                    "    empty pixels: {} ({:.1}%)",
+                    deep.empty_pixels,
+                    deep.empty_percentage()
+                );
+
+                if let Some((min, max)) = deep.depth_range {
+                    println!("    depth: {} to {}", number(min), number(max));
+                }
+
+                println!("    memory: {}", bytes(deep.memory.total()));
+            }
+
+            if !part.channels.is_empty() {
+                println!(
+                    "    {:<16} {:>12} {:>12} {:>12} {:>8} {:>8}",
+                    "channel", "min", "max", "mean", "nan", "inf"
+                );
+            }
+
+            for (name, channel) in &part.channels {
+                println!(
+                    "    {:<16} {:>12} {:>12} {:>12} {:>8} {:>8}",
+                    name.to_string(),
+                    number(channel.min),
+                    number(channel.max),
+                    number(channel.mean as f32),
+                    channel.nan_count,
+                    channel.infinite_count
+                );
+            }
+        }
+
+        println!();
+    }
+}
+
+fn print_summary(results: &[(PathBuf, Result<FileStatistics>)]) {
+    println!(
+        "{:<40} {:>5} {:>10} {:>10} {:>7} {:>8} {:>8}",
+        "file", "parts", "size", "pixels", "ratio", "nan", "inf"
+    );
+
+    let mut total_file_bytes = 0;
+    let mut total_uncompressed = 0;
+    let mut total_compressed = 0;
+    let mut total_nan = 0;
+    let mut total_infinite = 0;
+
+    for (path, stats) in results {
+        let stats = match stats {
+            Ok(stats) => stats,
+            Err(_) => {
+                println!("{:<40} error", path.display().to_string());
+                continue;
+            }
+        };
+
+        let uncompressed: usize = stats.parts.iter().map(|part| part.uncompressed_bytes).sum();
+        let compressed: usize = stats.parts.iter().map(|part| part.compressed_bytes).sum();
+        let channels = stats.parts.iter().flat_map(|part| &part.channels);
+        let nan: usize = channels.clone().map(|(_, channel)| channel.nan_count).sum();
+        let infinite: usize = channels.map(|(_, channel)| channel.infinite_count).sum();
+
+        println!(
+            "{:<40} {:>5} {:>10} {:>10} {:>7} {:>8} {:>8}",
+            path.display().to_string(),
+            stats.parts.len(),
+            bytes(stats.file_bytes as usize),
+            bytes(uncompressed),
+            ratio(uncompressed, compressed),
+            nan,
+            infinite
+        );
+
+        total_file_bytes += stats.file_bytes as usize;
+        total_uncompressed += uncompressed;
+        total_compressed += compressed;
+        total_nan += nan;
+        total_infinite += infinite;
+    }
+
+    if results.len() > 1 {
+        println!(
+            "{:<40} {:>5} {:>10} {:>10} {:>7} {:>8} {:>8}",
+            format!("total ({} files)", results.len()),
+            "",
+            bytes(total_file_bytes),
+            bytes(total_uncompressed),
+            ratio(total_uncompressed, total_compressed),
+            total_nan,
+            total_infinite
+        );
+    }
+}
+
+/// Value statistics of a single channel. `NaN` and infinite values are counted,
+/// but excluded from the minimum, maximum, and mean.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChannelStatistics {
+    min: f32,
+    max: f32,
+    mean: f64,
+    nan_count: usize,
+    infinite_count: usize,
+}
+
+impl ChannelStatistics {
+    fn compute(values: impl Iterator<Item = f32>) -> Self {
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        let mut sum = 0.0_f64;
+        let mut finite_count = 0_usize;
+        let mut nan_count = 0;
+        let mut infinite_count = 0;
+
+        for value in values {
+            if value.is_nan() {
+                nan_count += 1;
+            } else if value.is_infinite() {
+                infinite_count += 1;
+            } else {
+                min = min.min(value);
+                max = max.max(value);
+                sum += value as f64;
+                finite_count += 1;
+            }
+        }
+
+        if finite_count == 0 {
+            min = f32::NAN;
+            max = f32::NAN;
+        }
+
+        ChannelStatistics {
+            min,
+            max,
+            mean: if finite_count == 0 {
+                f64::NAN
+            } else {
+                sum / finite_count as f64
+            },
+            nan_count,
+            infinite_count,
+        }
+    }
+}
+
+fn deep_values(data: &DeepChannelData) -> Box<dyn Iterator<Item = f32> + '_> {
+    match data {
+        DeepChannelData::F16(values) => Box::new(values.iter().map(|value| value.to_f32())),
+        DeepChannelData::F32(values) => Box::new(values.iter().copied()),
+        DeepChannelData::U32(values) => Box::new(values.iter().map(|&value| value as f32)),
+    }
+}
+
+fn ratio(uncompressed: usize, compressed: usize) -> String {
+    if compressed == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.2}", uncompressed as f64 / compressed as f64)
+    }
+}
+
+fn number(value: f32) -> String {
+    if value.is_nan() {
+        "-".to_string()
+    } else {
+        format!("{value:.6}")
+    }
+}
+
+fn bytes(count: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = count as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{count} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs stats - Print pixel statistics and compression ratios of EXR files
+
+USAGE:
+    exrs stats [OPTIONS] <FILE.exr | DIRECTORY>...
+
+OPTIONS:
+    -s, --summary    Only print the summary table
+    -h, --help       Show this help
+
+For each part, prints the compression ratio and the minimum, maximum, mean,
+NaN count, and infinity count of each channel. Deep parts additionally print
+sample count statistics, the depth range, and the memory of the samples.
+NaN and infinite values are excluded from the minimum, maximum, and mean.
+Directories are replaced by the EXR files they contain.
+
+The exit code is non-zero if any file could not be read.
+
+EXAMPLES:
+    exrs stats render.exr
+    exrs stats --summary shots/*.exr
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn channel_statistics_exclude_non_finite_values() {
+        let values = [1.0, -2.0, f32::NAN, f32::INFINITY, 4.0, f32::NEG_INFINITY];
+        let statistics = ChannelStatistics::compute(values.iter().copied());
+
+        assert_eq!(statistics.min, -2.0);
+        assert_eq!(statistics.max, 4.0);
+        assert_eq!(statistics.mean, 1.0);
+        assert_eq!(statistics.nan_count, 1);
+        assert_eq!(statistics.infinite_count, 2);
+    }
+
+    #[test]
+    fn channel_statistics_of_empty_channel() {
+        let statistics = ChannelStatistics::compute(std::iter::empty());
+        assert!(statistics.min.is_nan() && statistics.mean.is_nan());
+        assert_eq!(statistics.nan_count, 0);
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(bytes(512), "512 B");
+        assert_eq!(bytes(1536), "1.5 KiB");
+        assert_eq!(ratio(300, 100), "3.00");
+        assert_eq!(ratio(300, 0), "-");
+    }
+}
@@ -0,0 +1,214 @@
+//! Use `-` as a file name to read from standard input or write to standard output,
+//! such that commands can be combined in shell pipelines.
+//!
+//! Exr files cannot be processed strictly sequentially: reading looks up chunks through
+//! the offset tables, and writing fills in the offset tables after all chunks.
+//! Therefore, the streams are buffered in memory completely.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use exr::error::{Result, UnitResult};
+use exr::image::read::deep::{read_deep, DeepLayersImage};
+use exr::image::{AnyImage, FlatImage};
+use exr::meta::MetaData;
+use exr::prelude::traits::*;
+
+/// The file name that stands for standard input or standard output.
+pub const STANDARD_STREAM: &str = "-";
+
+/// Whether the path stands for standard input or standard output.
+pub fn is_standard_stream(path: &Path) -> bool {
+    path.as_os_str() == STANDARD_STREAM
+}
+
+/// A buffered file, or all bytes of standard input.
+pub enum Input {
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Input {
+    /// Open the file, or read all of standard input if the path is `-`.
+    pub fn open(path: &Path) -> Result<Self> {
+        if is_standard_stream(path) {
+            let mut bytes = Vec::new();
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            Ok(Input::Memory(Cursor::new(bytes)))
+        } else {
+            Ok(Input::File(BufReader::new(File::open(path)?)))
+        }
+    }
+
+    /// The total number of bytes of the file or stream.
+    pub fn byte_size(&self) -> io::Result<u64> {
+        match self {
+            Input::File(file) => Ok(file.get_ref().metadata()?.len()),
+            Input::Memory(bytes) => Ok(bytes.get_ref().len() as u64),
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::File(file) => file.read(buffer),
+            Input::Memory(bytes) => bytes.read(buffer),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self {
+            Input::File(file) => file.seek(position),
+            Input::Memory(bytes) => bytes.seek(position),
+        }
+    }
+}
+
+/// A file, or the bytes that will be written to standard output.
+pub enum Output {
+    File(File),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Write for Output {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::File(file) => file.write(buffer),
+            Output::Memory(bytes) => bytes.write(buffer),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::File(file) => file.flush(),
+            Output::Memory(bytes) => bytes.flush(),
+        }
+    }
+}
+
+impl Seek for Output {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self {
+            Output::File(file) => file.seek(position),
+            Output::Memory(bytes) => bytes.seek(position),
+        }
+    }
+}
+
+/// Write to the file, or to standard output if the path is `-`.
+/// If an error occurs, the partially written file is deleted, and nothing is written to standard output.
+pub fn write(path: &Path, write: impl FnOnce(&mut Output) -> UnitResult) -> UnitResult {
+    if is_standard_stream(path) {
+        let mut output = Output::Memory(Cursor::new(Vec::new()));
+        write(&mut output)?;
+
+        if let Output::Memory(bytes) = output {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            stdout.write_all(bytes.get_ref())?;
+            stdout.flush()?;
+        }
+
+        Ok(())
+    } else {
+        let mut output = Output::File(File::create(path)?);
+        let result = write(&mut output);
+        drop(output);
+
+        if result.is_err() {
+            fs::remove_file(path).ok();
+        }
+
+        result
+    }
+}
+
+/// Read the meta data of the file, or of standard input if the path is `-`.
+pub fn read_meta_data(path: &Path) -> Result<MetaData> {
+    MetaData::read_from_buffered(Input::open(path)?, false)
+}
+
+/// Like `exr::image::read::read_all_data_from_file`, but for any input.
+pub fn read_all_data(input: Input) -> Result<AnyImage> {
+    read()
+        .no_deep_data()
+        .all_resolution_levels()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .from_buffered(input)
+}
+
+/// Like `read_all_data`, but fills the pixels of damaged blocks with the value instead of failing.
+pub fn salvage_all_data(input: Input, fill: f32) -> Result<AnyImage> {
+    read()
+        .no_deep_data()
+        .all_resolution_levels()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .salvage_corrupt_blocks(fill)
+        .from_buffered(input)
+}
+
+/// Like `exr::image::read::read_all_flat_layers_from_file`, but for any input.
+pub fn read_flat_layers(input: Input) -> Result<FlatImage> {
+    read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .from_buffered(input)
+}
+
+/// Like `exr::image::read::deep::read_all_deep_layers_from_file`, but for any input.
+pub fn read_deep_layers(input: Input) -> Result<DeepLayersImage> {
+    read_deep()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .from_buffered(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use exr::image::write::WritableImage;
+    use std::path::PathBuf;
+
+    #[test]
+    fn standard_stream_names() {
+        assert!(is_standard_stream(Path::new("-")));
+        assert!(!is_standard_stream(Path::new("-.exr")));
+        assert!(!is_standard_stream(Path::new("./-")));
+    }
+
+    #[test]
+    fn read_and_write_in_memory() {
+        let path = PathBuf::from("tests/images/valid/openexr/Beachball/multipart.0001.exr");
+
+        let input = Input::open(&path).unwrap();
+        assert_eq!(
+            input.byte_size().unwrap(),
+            fs::metadata(&path).unwrap().len()
+        );
+
+        let image = read_flat_layers(input).unwrap();
+
+        let mut output = Output::Memory(Cursor::new(Vec::new()));
+        image.write().to_unbuffered(&mut output).unwrap();
+
+        let bytes = match output {
+            Output::Memory(bytes) => bytes.into_inner(),
+            Output::File(_) => unreachable!(),
+        };
+
+        let copy = read_flat_layers(Input::Memory(Cursor::new(bytes))).unwrap();
+        assert_eq!(copy.layer_data.len(), image.layer_data.len());
+    }
+}
@@ -0,0 +1,512 @@
+//! `exrs thumbnail`: write small PNG or JPEG proxies of EXR files, and optionally embed them as preview attribute.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use exr::export::Transfer;
+use exr::image::read::read_all_flat_layers_from_file;
+use exr::image::resize::{resize_f32, ResizeFilter};
+use exr::math::Vec2;
+use exr::meta::attribute::Preview;
+use exr::meta::edit::edit_header_attributes;
+
+use crate::batch::{self, run_jobs, BatchOptions, Progress, Report};
+use crate::ocio::{DisplayOptions, DisplayTransform};
+use crate::recompress::find_exr_files;
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let jobs = match collect_jobs(&options) {
+        Ok(jobs) => jobs,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if jobs.is_empty() {
+        eprintln!("Error: No input files. Use 'exrs thumbnail --help' for usage.");
+        return ExitCode::FAILURE;
+    }
+
+    let transform = match options.display.transform() {
+        Ok(transform) => transform,
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if thumbnail_all(jobs, options, transform) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Png,
+    Jpeg,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpg",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    inputs: Vec<PathBuf>,
+
+    /// Write the thumbnails next to the input files if not specified.
+    output_directory: Option<PathBuf>,
+
+    /// The maximum width and height of the thumbnails.
+    size: usize,
+
+    format: Format,
+    exposure: f32,
+
+    /// Replaces the sRGB transfer function with an OCIO display transform if specified.
+    display: DisplayOptions,
+
+    /// Add the thumbnail to the input file as preview attribute.
+    preview: bool,
+
+    /// Do not write any image files, only add the preview attribute.
+    only_preview: bool,
+
+    recursive: bool,
+    batch: BatchOptions,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut inputs = Vec::new();
+        let mut output_directory = None;
+        let mut size = 256;
+        let mut format = Format::Png;
+        let mut exposure = 0.0;
+        let mut display = DisplayOptions::default();
+        let mut preview = false;
+        let mut only_preview = false;
+        let mut recursive = false;
+        let mut batch = BatchOptions::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for '{name}'"))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-o" | "--output" => output_directory = Some(PathBuf::from(value(arg)?)),
+                "-s" | "--size" => {
+                    let text = value(arg)?;
+                    size = match text.parse() {
+                        Ok(0) | Err(_) => return Err(format!("Invalid size '{text}'")),
+                        Ok(size) => size,
+                    };
+                }
+                "-f" | "--format" => {
+                    let name = value(arg)?;
+                    format = match name.to_ascii_lowercase().as_str() {
+                        "png" => Format::Png,
+                        "jpg" | "jpeg" => Format::Jpeg,
+                        _ => return Err(format!("Unknown format '{name}'")),
+                    };
+                }
+                "-e" | "--exposure" => {
+                    let stops = value(arg)?;
+                    exposure = stops
+                        .parse()
+                        .map_err(|_| format!("Invalid exposure '{stops}'"))?;
+                }
+                "--preview" => preview = true,
+                "--only-preview" => {
+                    preview = true;
+                    only_preview = true;
+                }
+                "-r" | "--recursive" => recursive = true,
+                "-j" | "--jobs" => batch.threads = batch::parse_job_count(&value(arg)?)?,
+                "--progress" => batch.progress = true,
+                "--report" => batch.report = Some(PathBuf::from(value(arg)?)),
+                _ if display.parse_arg(arg, &mut value)? => {}
+                _ if !arg.starts_with('-') => inputs.push(PathBuf::from(arg)),
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        Ok(Some(Options {
+            inputs,
+            output_directory,
+            size,
+            format,
+            exposure,
+            display,
+            preview,
+            only_preview,
+            recursive,
+            batch,
+        }))
+    }
+}
+
+/// A file to create a thumbnail of, and where to write the thumbnail.
+#[derive(Debug, Clone, PartialEq)]
+struct Job {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+/// Finds the exr files in directories and patterns, and computes the thumbnail paths.
+/// Files in directories keep their relative path inside the output directory.
+fn collect_jobs(options: &Options) -> Result<Vec<Job>, String> {
+    let extension = options.format.extension();
+    let mut jobs = Vec::new();
+
+    for input in &options.inputs {
+        if input.is_dir() {
+            let mut files = Vec::new();
+            find_exr_files(input, options.recursive, &mut files)
+                .map_err(|error| format!("{}: {error}", input.display()))?;
+
+            files.sort();
+            jobs.extend(files.into_iter().map(|file| {
+                let output = match &options.output_directory {
+                    Some(directory) => directory.join(file.strip_prefix(input).unwrap_or(&file)),
+                    None => file.clone(),
+                };
+
+                Job {
+                    input: file,
+                    output: output.with_extension(extension),
+                }
+            }));
+        } else {
+            for file in batch::expand_pattern(input)? {
+                let output = match (&options.output_directory, file.file_name()) {
+                    (Some(directory), Some(name)) => directory.join(name),
+                    _ => file.clone(),
+                };
+
+                jobs.push(Job {
+                    input: file,
+                    output: output.with_extension(extension),
+                });
+            }
+        }
+    }
+
+    Ok(jobs)
+}
+
+/// Processes all jobs on multiple threads. Returns whether all files were successful.
+fn thumbnail_all(jobs: Vec<Job>, options: Options, transform: Option<DisplayTransform>) -> bool {
+    let only_preview = options.only_preview;
+    let batch = options.batch.clone();
+    let mut progress = Progress::new(jobs.len(), batch.progress);
+    let mut report = Report::new("thumbnail");
+    let mut success = true;
+
+    let all_finished = run_jobs(
+        jobs,
+        batch.threads,
+        move |job| thumbnail(job, &options, transform.as_ref()),
+        |job, result, duration| {
+            progress.clear();
+
+            match &result {
+                Ok(()) if only_preview => println!("{}", job.input.display()),
+                Ok(()) => println!("{} -> {}", job.input.display(), job.output.display()),
+
+                Err(message) => {
+                    success = false;
+                    eprintln!("Error: {}: {message}", job.input.display());
+                }
+            }
+
+            let output = if only_preview {
+                None
+            } else {
+                Some(job.output.as_path())
+            };
+
+            let fields = result.as_ref().map(|_| Vec::new()).map_err(String::as_str);
+            report.add(&job.input, output, fields, duration);
+            progress.advance(result.is_ok());
+        },
+    );
+
+    progress.finish();
+
+    if let Err(message) = report.write(batch.report.as_deref()) {
+        eprintln!("Error: {message}");
+        success = false;
+    }
+
+    success && all_finished
+}
+
+/// A small image with 8-bit samples in the order red, green, blue, alpha.
+/// The colors are not premultiplied with alpha, as expected by PNG files and EXR previews.
+#[derive(Debug, Clone, PartialEq)]
+struct Thumbnail {
+    size: Vec2<usize>,
+    rgba: Vec<u8>,
+}
+
+fn thumbnail(
+    job: &Job,
+    options: &Options,
+    transform: Option<&DisplayTransform>,
+) -> Result<(), String> {
+    let thumbnail = create_thumbnail(&job.input, options.size, options.exposure, transform)?;
+
+    if !options.only_preview {
+        if let Some(parent) = job.output.parent() {
+            std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+
+        save(&thumbnail, &job.output, options.format)?;
+    }
+
+    if options.preview {
+        let preview = Preview {
+            size: thumbnail.size,
+            pixel_data: thumbnail.rgba.iter().map(|&byte| byte as i8).collect(),
+        };
+
+        // by convention, the preview is stored in the first part
+        edit_header_attributes(&job.input, |headers| {
+            headers[0].own_attributes.preview = Some(preview);
+            Ok(())
+        })
+        .map_err(|error| format!("cannot add preview: {error}"))?;
+    }
+
+    Ok(())
+}
+
+/// Reads the first layer of the file that contains colors and resizes it.
+/// Colors are converted to sRGB, unless a display transform is specified.
+fn create_thumbnail(
+    path: &Path,
+    max_size: usize,
+    exposure: f32,
+    transform: Option<&DisplayTransform>,
+) -> Result<Thumbnail, String> {
+    let image = read_all_flat_layers_from_file(path).map_err(|error| error.to_string())?;
+
+    let layer = image
+        .layer_data
+        .iter()
+        .find(|layer| {
+            layer
+                .channel_data
+                .list
+                .iter()
+                .any(|channel| channel.name.eq("R") || channel.name.eq("Y"))
+        })
+        .or_else(|| image.layer_data.first())
+        .ok_or("the file contains no layers")?;
+
+    let find = |name: &str| {
+        layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.eq(name) && channel.sampling == Vec2(1, 1))
+            .map(|channel| channel.sample_data.to_f32_vec())
+    };
+
+    let gray = || {
+        find("Y").or_else(|| {
+            layer
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.sampling == Vec2(1, 1))
+                .map(|channel| channel.sample_data.to_f32_vec())
+        })
+    };
+
+    let [red, green, blue] = match (find("R"), find("G"), find("B")) {
+        (Some(red), Some(green), Some(blue)) => [red, green, blue],
+        _ => {
+            let gray = gray().ok_or("the layer contains no channels without subsampling")?;
+            [gray.clone(), gray.clone(), gray]
+        }
+    };
+
+    let alpha = find("A").unwrap_or_else(|| vec![1.0; layer.size.area()]);
+
+    let size = thumbnail_size(layer.size, max_size);
+    let resize = |samples: Vec<f32>| resize_f32(&samples, layer.size, size, ResizeFilter::Triangle);
+    let (red, green, blue, alpha) = (resize(red), resize(green), resize(blue), resize(alpha));
+
+    let exposure = 2.0_f32.powf(exposure);
+    let mut rgba = Vec::with_capacity(size.area() * 4);
+
+    for index in 0..size.area() {
+        let alpha = alpha[index].max(0.0).min(1.0);
+
+        // exr colors are premultiplied with alpha
+        let color = |value: f32| {
+            let value = if alpha > 0.0 { value / alpha } else { value };
+            value * exposure
+        };
+
+        let rgb = [color(red[index]), color(green[index]), color(blue[index])];
+        let [red, green, blue] = match transform {
+            Some(transform) => transform.apply_rgb(rgb).map(to_byte),
+            None => rgb.map(|value| to_byte(Transfer::Srgb.encode(value))),
+        };
+
+        rgba.extend_from_slice(&[red, green, blue, to_byte(alpha)]);
+    }
+
+    Ok(Thumbnail { size, rgba })
+}
+
+/// Fits the size into a square of the specified size, keeping the aspect ratio. Never enlarges the image.
+fn thumbnail_size(size: Vec2<usize>, max_size: usize) -> Vec2<usize> {
+    let largest = size.width().max(size.height());
+    if largest <= max_size {
+        return size;
+    }
+
+    let scale = |length: usize| ((length * max_size + largest / 2) / largest).max(1);
+    Vec2(scale(size.width()), scale(size.height()))
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+#[cfg(not(feature = "convert"))]
+fn save(_thumbnail: &Thumbnail, _path: &Path, _format: Format) -> Result<(), String> {
+    Err("Writing PNG or JPEG files is not available. Rebuild with --features convert, or use --only-preview".to_string())
+}
+
+#[cfg(feature = "convert")]
+fn save(thumbnail: &Thumbnail, path: &Path, format: Format) -> Result<(), String> {
+    use ::image::{DynamicImage, ImageBuffer};
+
+    let (width, height) = (
+        thumbnail.size.width() as u32,
+        thumbnail.size.height() as u32,
+    );
+    let image = ImageBuffer::from_raw(width, height, thumbnail.rgba.clone())
+        .map(DynamicImage::ImageRgba8)
+        .ok_or("Image size is invalid")?;
+
+    // jpeg files do not support alpha
+    let image = match format {
+        Format::Png => image,
+        Format::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+    };
+
+    image.save(path).map_err(|error| error.to_string())
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs thumbnail - Write small PNG or JPEG images of EXR files
+
+USAGE:
+    exrs thumbnail [OPTIONS] <FILE.exr | PATTERN | DIRECTORY>...
+
+OPTIONS:
+    -s, --size <PIXELS>         Maximum width and height [default: 256]
+    -f, --format <FORMAT>       Image format: png, jpg [default: png]
+    -o, --output <DIRECTORY>    Write the images to this directory instead of
+                                next to the input files
+    -e, --exposure <STOPS>      Multiply colors by 2^STOPS [default: 0]
+{ocio_help}
+    --preview                   Also add the image as preview attribute to the
+                                input files
+    --only-preview              Only add the preview attribute, without
+                                writing image files
+    -r, --recursive             Also process files in subdirectories
+    -j, --jobs <COUNT>          Number of files processed in parallel
+                                [default: number of processors]
+    --progress                  Draw a progress bar on standard error
+    --report <FILE.json>        Write the result of each file to a JSON report
+    -h, --help                  Show this help
+
+The first layer with R, G, B or Y channels is used, converted to sRGB,
+or with the OCIO display transform if any OCIO option is specified.
+Images are never enlarged. Writing PNG and JPEG files requires the
+'convert' feature.
+
+Patterns may contain wildcards and frame numbers like 'beauty.####.exr',
+see 'exrs recompress --help'.
+
+EXAMPLES:
+    exrs thumbnail -s 128 -o proxies/ -r renders/
+    exrs thumbnail --only-preview -s 100 shot.0001.exr
+    exrs thumbnail -j 8 --progress -o proxies/ 'shot/beauty.%04d.exr'
+    exrs thumbnail --display sRGB --view Film -o proxies/ renders/
+"#,
+        ocio_help = crate::ocio::HELP
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_options() {
+        let args: Vec<String> = ["-s", "128", "-f", "jpg", "--only-preview", "renders"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        let options = Options::parse(&args).unwrap().unwrap();
+        assert_eq!(options.size, 128);
+        assert_eq!(options.format, Format::Jpeg);
+        assert!(options.preview && options.only_preview);
+
+        assert!(Options::parse(&args[..1]).is_err());
+    }
+
+    #[test]
+    fn thumbnail_sizes() {
+        assert_eq!(thumbnail_size(Vec2(1920, 1080), 256), Vec2(256, 144));
+        assert_eq!(thumbnail_size(Vec2(100, 4000), 200), Vec2(5, 200));
+        assert_eq!(thumbnail_size(Vec2(64, 32), 256), Vec2(64, 32));
+        assert_eq!(thumbnail_size(Vec2(1000, 1), 10), Vec2(10, 1));
+    }
+
+    #[test]
+    fn thumbnail_of_test_image() {
+        let path = Path::new("tests/images/valid/openexr/MultiResolution/Kapaa.exr");
+        let thumbnail = create_thumbnail(path, 32, 0.0, None).unwrap();
+
+        assert!(thumbnail.size.width() <= 32 && thumbnail.size.height() <= 32);
+        assert_eq!(thumbnail.rgba.len(), thumbnail.size.area() * 4);
+    }
+}
@@ -0,0 +1,360 @@
+//! `exrs verify`: decode every chunk of files to find corruption, and store or compare per-chunk checksums.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use exr::block::chunk::TileCoordinates;
+use exr::block::verify::{verify_file, ChunkReport, Verification};
+
+use crate::recompress::find_exr_files;
+
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print_help();
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("Error: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut files = Vec::new();
+    for input in &options.inputs {
+        if input.is_dir() {
+            let mut found = Vec::new();
+            if let Err(error) = find_exr_files(input, options.recursive, &mut found) {
+                eprintln!("Error: {}: {error}", input.display());
+                return ExitCode::FAILURE;
+            }
+
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(input.clone());
+        }
+    }
+
+    if files.is_empty() {
+        eprintln!("Error: No input files. Use 'exrs verify --help' for usage.");
+        return ExitCode::FAILURE;
+    }
+
+    let mut invalid_count = 0;
+    for file in &files {
+        if !verify(file, &options) {
+            invalid_count += 1;
+        }
+    }
+
+    if files.len() > 1 {
+        println!(
+            "{} files, {} valid, {invalid_count} invalid",
+            files.len(),
+            files.len() - invalid_count
+        );
+    }
+
+    if invalid_count == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChecksumMode {
+    Ignore,
+    Write,
+    Compare,
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    inputs: Vec<PathBuf>,
+    checksums: ChecksumMode,
+    recursive: bool,
+
+    /// Only print invalid files.
+    quiet: bool,
+}
+
+impl Options {
+    /// Returns `None` if help was requested.
+    fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        let mut inputs = Vec::new();
+        let mut checksums = ChecksumMode::Ignore;
+        let mut recursive = false;
+        let mut quiet = false;
+
+        for arg in args {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-w" | "--write-checksums" => checksums = ChecksumMode::Write,
+                "-c" | "--check-checksums" => checksums = ChecksumMode::Compare,
+                "-r" | "--recursive" => recursive = true,
+                "-q" | "--quiet" => quiet = true,
+                _ if !arg.starts_with('-') => inputs.push(PathBuf::from(arg)),
+                _ => return Err(format!("Unknown option '{arg}'")),
+            }
+        }
+
+        Ok(Some(Options {
+            inputs,
+            checksums,
+            recursive,
+            quiet,
+        }))
+    }
+}
+
+/// Verifies a single file and prints the result. Returns whether the file is valid.
+fn verify(path: &Path, options: &Options) -> bool {
+    let verification = match verify_file(path) {
+        Ok(verification) => verification,
+        Err(error) => {
+            println!("{}: INVALID: {error}", path.display());
+            return false;
+        }
+    };
+
+    let mut problems: Vec<String> = verification.errors().map(describe_error).collect();
+
+    match options.checksums {
+        ChecksumMode::Ignore => {}
+
+        ChecksumMode::Write => {
+            if problems.is_empty() {
+                if let Err(error) = fs::write(sidecar_path(path), format_checksums(&verification)) {
+                    problems.push(format!("cannot write checksums: {error}"));
+                }
+            } else {
+                problems.push("checksums not written, because the file is invalid".to_string());
+            }
+        }
+
+        ChecksumMode::Compare => match fs::read_to_string(sidecar_path(path)) {
+            Ok(text) => match parse_checksums(&text) {
+                Ok(expected) => problems.extend(compare_checksums(&verification, &expected)),
+                Err(message) => problems.push(format!("invalid checksum file: {message}")),
+            },
+            Err(error) => problems.push(format!("cannot read checksums: {error}")),
+        },
+    }
+
+    if problems.is_empty() {
+        if !options.quiet {
+            println!(
+                "{}: OK ({} chunks)",
+                path.display(),
+                verification.chunks.len()
+            );
+        }
+
+        true
+    } else {
+        println!("{}: INVALID", path.display());
+        for problem in problems {
+            println!("    {problem}");
+        }
+
+        false
+    }
+}
+
+fn describe_chunk(chunk: &ChunkReport) -> String {
+    format!(
+        "part {}, chunk {} ({})",
+        chunk.layer_index,
+        chunk.chunk_index,
+        describe_coordinates(chunk.coordinates)
+    )
+}
+
+pub(crate) fn describe_error(chunk: &ChunkReport) -> String {
+    let error = chunk
+        .error
+        .as_ref()
+        .map_or_else(String::new, ToString::to_string);
+
+    format!(
+        "{} at byte {}: {error}",
+        describe_chunk(chunk),
+        chunk.byte_offset
+    )
+}
+
+fn describe_coordinates(coordinates: TileCoordinates) -> String {
+    let level = coordinates.level_index;
+    let tile = coordinates.tile_index;
+
+    if level == exr::math::Vec2(0, 0) {
+        format!("block {}, {}", tile.x(), tile.y())
+    } else {
+        format!(
+            "block {}, {} of level {}, {}",
+            tile.x(),
+            tile.y(),
+            level.x(),
+            level.y()
+        )
+    }
+}
+
+/// The checksums are stored next to the file, in `FILE.exr.chunks`.
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".chunks");
+    PathBuf::from(name)
+}
+
+/// The size and checksum of a chunk, identified by part and index in the offset table.
+type Checksums = HashMap<(usize, usize), (usize, u32)>;
+
+fn format_checksums(verification: &Verification) -> String {
+    let mut text = String::from("# exrs chunk checksums: part chunk offset bytes crc32\n");
+
+    for chunk in &verification.chunks {
+        if let Some(checksum) = chunk.checksum {
+            text.push_str(&format!(
+                "{} {} {} {} {checksum:08x}\n",
+                chunk.layer_index, chunk.chunk_index, chunk.byte_offset, chunk.byte_size
+            ));
+        }
+    }
+
+    text
+}
+
+fn parse_checksums(text: &str) -> Result<Checksums, String> {
+    let mut checksums = HashMap::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = || format!("line {}", line_index + 1);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        match fields.as_slice() {
+            [part, chunk, _offset, size, checksum] => {
+                let part = part.parse().map_err(|_| invalid())?;
+                let chunk = chunk.parse().map_err(|_| invalid())?;
+                let size = size.parse().map_err(|_| invalid())?;
+                let checksum = u32::from_str_radix(checksum, 16).map_err(|_| invalid())?;
+                checksums.insert((part, chunk), (size, checksum));
+            }
+
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(checksums)
+}
+
+/// Chunks are identified by their position in the offset table, not by their byte offset,
+/// such that editing the headers of a file does not invalidate the checksums.
+fn compare_checksums(verification: &Verification, expected: &Checksums) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for chunk in &verification.chunks {
+        let key = (chunk.layer_index, chunk.chunk_index);
+
+        match (expected.get(&key), chunk.checksum) {
+            (Some(&expected), Some(checksum)) if expected == (chunk.byte_size, checksum) => {}
+            (Some(_), Some(_)) => {
+                problems.push(format!("{}: checksum differs", describe_chunk(chunk)))
+            }
+            (None, _) => problems.push(format!("{}: no stored checksum", describe_chunk(chunk))),
+
+            // unreadable chunks are already reported
+            (Some(_), None) => {}
+        }
+    }
+
+    if expected.len() > verification.chunks.len() {
+        problems.push(format!(
+            "the file has {} chunks, but {} checksums are stored",
+            verification.chunks.len(),
+            expected.len()
+        ));
+    }
+
+    problems
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs verify - Check EXR files for corruption by decoding every chunk
+
+USAGE:
+    exrs verify [OPTIONS] <FILE.exr | DIRECTORY>...
+
+OPTIONS:
+    -w, --write-checksums    Store the checksum of each chunk in 'FILE.exr.chunks'
+    -c, --check-checksums    Compare the chunks to the stored checksums
+    -r, --recursive          Also check files in subdirectories
+    -q, --quiet              Only print invalid files
+    -h, --help               Show this help
+
+Each chunk is read and decompressed on its own, such that all corrupt chunks
+are reported with their part, offset table index, and block coordinates.
+The offset tables are checked for entries outside of the file, entries that
+point to the same chunk, and chunks that contain a different block.
+
+Checksums are CRC-32 values of the compressed bytes of each chunk. They are
+only written for valid files. Editing attributes does not change them.
+
+EXAMPLES:
+    exrs verify -r renders/
+    exrs verify -w archive/*.exr
+    exrs verify -c -q -r archive/
+"#
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checksums_round_trip() {
+        let path = Path::new("tests/images/valid/openexr/Beachball/multipart.0001.exr");
+        let verification = verify_file(path).unwrap();
+
+        let text = format_checksums(&verification);
+        let checksums = parse_checksums(&text).unwrap();
+        assert_eq!(checksums.len(), verification.chunks.len());
+        assert!(compare_checksums(&verification, &checksums).is_empty());
+
+        let mut changed = checksums;
+        let first = changed.get_mut(&(0, 0)).unwrap();
+        first.1 ^= 1;
+
+        let problems = compare_checksums(&verification, &changed);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("part 0, chunk 0"));
+    }
+
+    #[test]
+    fn invalid_checksum_files() {
+        assert!(parse_checksums("# comment\n\n0 1 100 20 ff\n").is_ok());
+        assert!(parse_checksums("0 1 100 20\n").is_err());
+        assert!(parse_checksums("0 1 100 20 xyz\n").is_err());
+    }
+
+    #[test]
+    fn sidecar_paths() {
+        assert_eq!(
+            sidecar_path(Path::new("a/b.exr")),
+            PathBuf::from("a/b.exr.chunks")
+        );
+    }
+}
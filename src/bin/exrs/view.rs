@@ -0,0 +1,123 @@
+//! `exrs view`: open files in the image viewer.
+//! Also the entry point of the `exrs-view` binary.
+
+use std::process::ExitCode;
+
+use exr::interop::tev::DEFAULT_ADDRESS;
+use exr::view::{run as run_viewer, run_empty, ViewerConfig};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub fn run(args: &[String]) -> ExitCode {
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        print_help();
+        return ExitCode::SUCCESS;
+    }
+
+    if args.iter().any(|a| a == "-V" || a == "--version") {
+        println!("exrs view {VERSION}");
+        return ExitCode::SUCCESS;
+    }
+
+    let verbose = args.iter().any(|a| a == "-v" || a == "--verbose");
+    let ignore_settings = args.iter().any(|a| a == "--no-settings");
+
+    // The address is optional, and defaults to the address of tev
+    let listen_index = args.iter().position(|a| a == "--listen");
+    let listen_address = listen_index
+        .and_then(|index| args.get(index + 1))
+        .filter(|a| a.contains(':') && !a.starts_with('-'));
+
+    let listen = listen_index
+        .map(|_| listen_address.map_or_else(|| DEFAULT_ADDRESS.to_string(), |a| a.to_string()));
+
+    // Find file argument (first non-flag argument)
+    let file_path = args
+        .iter()
+        .filter(|a| Some(*a) != listen_address)
+        .find(|a| !a.starts_with('-'))
+        .map(|s| s.to_string());
+
+    let config = ViewerConfig {
+        verbose: if verbose { 1 } else { 0 },
+        listen,
+        ignore_settings,
+    };
+
+    let exit_code = if let Some(path) = file_path {
+        run_viewer(&path, config)
+    } else {
+        run_empty(config)
+    };
+
+    ExitCode::from(exit_code as u8)
+}
+
+fn print_help() {
+    println!(
+        r#"
+exrs view - EXR Image Viewer v{VERSION}
+
+USAGE:
+    exrs view [OPTIONS] [FILE.exr]
+    exrs-view [OPTIONS] [FILE.exr]
+
+OPTIONS:
+    -v, --verbose    Verbose output
+    --listen [ADDR]  Accept commands from renderers and other tools on a
+                     local socket, using the tev protocol
+                     (default address: {DEFAULT_ADDRESS})
+    --no-settings    Start with the default settings, and do not save them
+    -h, --help       Show this help
+    -V, --version    Show version
+
+FEATURES:
+    - Multi-layer EXR support
+    - Channel selection (R/G/B/A/Z/L/custom)
+    - Deep data visualization
+      - Flattened (over composite)
+      - Sample count heatmap
+      - Depth slice
+      - First/last sample
+    - Depth normalization
+      - Auto (min-max)
+      - Manual range
+      - Logarithmic scale
+    - Motion vectors colored by direction, with arrows
+    - Exposure control (EV stops)
+    - sRGB gamma toggle
+    - Zoom/pan (scroll wheel, drag)
+    - Drag & drop support
+
+KEYBOARD SHORTCUTS:
+    R/G/B/A/Z  Channel modes
+    C          Color mode
+    L          Luminance
+    F          Fit to window
+    H          Home (1:1 zoom)
+    +/-        Zoom in/out
+    Ctrl+O     Open file
+    Esc        Exit
+
+SETTINGS:
+    The window size, the recent files, the exposure, the display transform,
+    the background, and the keyboard shortcuts are saved on exit to
+    viewer.toml in the exrs folder of the user configuration directory,
+    like ~/.config/exrs/viewer.toml, or to the file named by the
+    EXRS_VIEWER_CONFIG environment variable. Shortcuts are overridden
+    in its [shortcuts] table, like 'fit = "F"' or 'home = "H, Num0"'.
+
+IPC:
+    With --listen, the viewer accepts the tev commands to open files,
+    create images, and update image regions, so that renderers can stream
+    progressive results. Display settings use the additional packet type
+    128, see exr::view::DisplaySettings.
+
+EXAMPLES:
+    exrs view image.exr
+    exrs view -v render.exr
+    exrs view --listen           # Receive images from a renderer
+    exrs view                    # Opens empty, use Ctrl+O or drag & drop
+"#
+    );
+}
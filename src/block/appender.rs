@@ -0,0 +1,499 @@
+//! Write an exr file incrementally, one scan line block at a time.
+//!
+//! Renderers often finish the buckets of an image in an unpredictable order,
+//! and should not keep the whole image in memory until the last bucket is done.
+//! The [`BlockAppender`] writes the meta data immediately,
+//! compresses each block as soon as it is appended, and writes it to the file.
+//! The offset tables are written when the appender is finished.
+//!
+//! If the line order of a header is `Increasing` or `Decreasing`,
+//! blocks that are appended too early are kept in memory, compressed,
+//! until all previous blocks have been appended.
+//! With `LineOrder::Unspecified`, every block is written immediately,
+//! but the specification does not allow this for scan line images,
+//! so the meta data must be written without the `pedantic` checks.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+
+use crate::block::chunk::{Chunk, CompressedBlock};
+use crate::block::deep::{compress_deep_scanline_block_with_buffers, DeepBlockBuffers};
+use crate::block::writer::{ChunkWriter, ChunksWriter};
+use crate::block::UncompressedBlock;
+use crate::error::{usize_to_i32, Error, Result, UnitResult};
+use crate::image::deep::DeepSamples;
+use crate::math::Vec2;
+use crate::meta::attribute::LineOrder;
+use crate::meta::header::Header;
+use crate::meta::{BlockDescription, Headers, MetaData};
+
+/// Appends flat or deep scan line blocks to a file, in any order.
+/// Call `finish` after all blocks of all headers have been appended,
+/// which writes the offset tables and flushes the file.
+/// A file that has not been finished is incomplete and cannot be read.
+///
+/// ```no_run
+/// use exr::prelude::*;
+/// use exr::block::appender::BlockAppender;
+/// use exr::block::{BlockIndex, UncompressedBlock};
+/// use exr::meta::header::Header;
+/// use exr::meta::BlockDescription;
+///
+/// let size = Vec2(1920, 1080);
+/// let header = Header::new(
+///     Text::from("beauty"),
+///     size,
+///     smallvec::smallvec![ChannelDescription::named("Y", SampleType::F32)],
+/// )
+/// .with_encoding(Compression::ZIP16, BlockDescription::ScanLines, LineOrder::Increasing);
+///
+/// let mut appender = BlockAppender::create_file("beauty.exr", smallvec::smallvec![header])?;
+///
+/// // append the blocks as soon as the renderer has finished them, in any order
+/// for y in (0..size.height()).step_by(16).rev() {
+///     let height = 16.min(size.height() - y);
+///     let lines = vec![0.5_f32; size.width() * height];
+///
+///     appender.append_block(UncompressedBlock {
+///         index: BlockIndex {
+///             layer: 0,
+///             pixel_position: Vec2(0, y),
+///             pixel_size: Vec2(size.width(), height),
+///             level: Vec2(0, 0),
+///         },
+///         data: lines.iter().flat_map(|value| value.to_ne_bytes()).collect(),
+///     })?;
+/// }
+///
+/// appender.finish()?;
+/// # Ok::<(), exr::error::Error>(())
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct BlockAppender<W> {
+    meta_data: MetaData,
+    chunk_writer: ChunkWriter<W>,
+
+    /// The index in the file of the first chunk of each header.
+    header_starts: Vec<usize>,
+
+    /// Compressed chunks that must wait for a previous chunk, by their index in the file.
+    pending_chunks: BTreeMap<usize, (usize, Chunk)>,
+    next_chunk_in_file: usize,
+
+    /// Whether any header requires its chunks to be written in a specific order.
+    requires_sorting: bool,
+
+    deep_buffers: DeepBlockBuffers,
+}
+
+impl BlockAppender<BufWriter<File>> {
+    /// Create a file and immediately write the meta data to it.
+    /// All headers must use scan line blocks.
+    pub fn create_file(path: impl AsRef<Path>, headers: Headers) -> Result<Self> {
+        Self::new_for_buffered(BufWriter::new(File::create(path)?), headers, true)
+    }
+}
+
+impl<W: Write + Seek> BlockAppender<W> {
+    /// Immediately write the meta data to the byte destination,
+    /// reserving space for the offset tables.
+    /// All headers must use scan line blocks. The writer is assumed to be buffered.
+    /// Set `pedantic` to check that the file can be opened by other exr software.
+    pub fn new_for_buffered(buffered_write: W, headers: Headers, pedantic: bool) -> Result<Self> {
+        if headers
+            .iter()
+            .any(|header| header.blocks != BlockDescription::ScanLines)
+        {
+            return Err(Error::unsupported("appending tiled blocks"));
+        }
+
+        let mut header_starts = Vec::with_capacity(headers.len());
+        let mut chunk_count = 0;
+
+        for header in &headers {
+            header_starts.push(chunk_count);
+            chunk_count += header.chunk_count;
+        }
+
+        let requires_sorting = headers
+            .iter()
+            .any(|header| header.line_order != LineOrder::Unspecified);
+
+        let (meta_data, chunk_writer) =
+            ChunkWriter::new_for_buffered(buffered_write, headers, pedantic)?;
+
+        Ok(Self {
+            meta_data,
+            chunk_writer,
+            header_starts,
+            pending_chunks: BTreeMap::new(),
+            next_chunk_in_file: 0,
+            requires_sorting,
+            deep_buffers: DeepBlockBuffers::new(),
+        })
+    }
+
+    /// The meta data that has been written to the file.
+    pub fn meta_data(&self) -> &MetaData {
+        &self.meta_data
+    }
+
+    /// The number of compressed chunks that wait for a previous chunk before they can be written.
+    pub fn pending_chunk_count(&self) -> usize {
+        self.pending_chunks.len()
+    }
+
+    /// Compress a flat scan line block and write it to the file, or keep it until it can be written.
+    /// The block must contain exactly one scan line block of the header, with all channels,
+    /// as described in `UncompressedBlock`.
+    pub fn append_block(&mut self, block: UncompressedBlock) -> UnitResult {
+        let header = layer_header(&self.meta_data, block.index.layer)?;
+
+        if header.deep {
+            return Err(Error::invalid("flat block for deep header"));
+        }
+
+        validate_block_bounds(
+            header,
+            block.index.pixel_position.y(),
+            block.index.pixel_size.height(),
+        )?;
+
+        if block.index.pixel_position.x() != 0
+            || block.index.pixel_size.width() != header.layer_size.width()
+            || block.index.level != Vec2(0, 0)
+        {
+            return Err(Error::invalid("scan line block bounds"));
+        }
+
+        if block.data.len() != header.channels.byte_size_of_section(block.index.section()) {
+            return Err(Error::invalid("block byte size"));
+        }
+
+        let chunk = block.compress_to_chunk(&self.meta_data.headers)?;
+        self.append_chunk(chunk)
+    }
+
+    /// Compress the deep samples of one scan line block and write them to the file,
+    /// or keep them until they can be written.
+    /// The `y` coordinate is the first line of the block, starting at zero
+    /// at the top of the data window. The samples must contain the whole block,
+    /// and store the values of all channels of the header, in the same order.
+    pub fn append_deep_block(
+        &mut self,
+        layer_index: usize,
+        y: usize,
+        samples: &DeepSamples,
+    ) -> UnitResult {
+        let header = layer_header(&self.meta_data, layer_index)?;
+
+        if !header.deep {
+            return Err(Error::invalid("deep block for flat header"));
+        }
+
+        validate_block_bounds(header, y, samples.height)?;
+
+        if samples.width != header.layer_size.width() {
+            return Err(Error::invalid("deep block width"));
+        }
+
+        if samples.channels.len() != header.channels.list.len() {
+            return Err(Error::invalid("deep block channel count"));
+        }
+
+        let y_coordinate =
+            usize_to_i32(y, "block y coordinate")? + header.own_attributes.layer_position.y();
+
+        let block = compress_deep_scanline_block_with_buffers(
+            samples,
+            header.compression,
+            &header.channels,
+            y_coordinate,
+            &mut self.deep_buffers,
+        )?;
+
+        self.append_chunk(Chunk {
+            layer_index,
+            compressed_block: CompressedBlock::DeepScanLine(block),
+        })
+    }
+
+    /// Write a chunk that has already been compressed, for example on another thread,
+    /// or keep it until it can be written. The chunk must be a flat or deep scan line block.
+    pub fn append_chunk(&mut self, chunk: Chunk) -> UnitResult {
+        let header = layer_header(&self.meta_data, chunk.layer_index)?;
+
+        let is_deep_block = match chunk.compressed_block {
+            CompressedBlock::ScanLine(_) => false,
+            CompressedBlock::DeepScanLine(_) => true,
+            _ => return Err(Error::unsupported("appending tiled blocks")),
+        };
+
+        if is_deep_block != header.deep {
+            return Err(Error::invalid("chunk type does not match header"));
+        }
+
+        let chunk_y_index = header
+            .get_block_data_indices(&chunk.compressed_block)?
+            .tile_index
+            .y();
+
+        if chunk_y_index >= header.chunk_count {
+            return Err(Error::invalid("too large chunk index"));
+        }
+
+        if !self.requires_sorting {
+            return self.chunk_writer.write_chunk(chunk_y_index, chunk);
+        }
+
+        let chunk_index_in_file = self.header_starts[chunk.layer_index]
+            + match header.line_order {
+                LineOrder::Decreasing => header.chunk_count - 1 - chunk_y_index,
+                _ => chunk_y_index,
+            };
+
+        if chunk_index_in_file < self.next_chunk_in_file
+            || self.pending_chunks.contains_key(&chunk_index_in_file)
+        {
+            return Err(Error::invalid(format!(
+                "chunk at index {} is already written",
+                chunk_y_index
+            )));
+        }
+
+        self.pending_chunks
+            .insert(chunk_index_in_file, (chunk_y_index, chunk));
+
+        // write this chunk and all pending chunks that immediately follow it
+        while let Some((chunk_y_index, chunk)) =
+            self.pending_chunks.remove(&self.next_chunk_in_file)
+        {
+            self.chunk_writer.write_chunk(chunk_y_index, chunk)?;
+            self.next_chunk_in_file += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Write the offset tables and flush the byte destination.
+    /// Errors if any block has not been appended yet.
+    pub fn finish(self) -> UnitResult {
+        if !self.pending_chunks.is_empty() {
+            return Err(Error::invalid("some chunks are not written yet"));
+        }
+
+        self.chunk_writer.complete_meta_data()
+    }
+}
+
+fn layer_header(meta_data: &MetaData, layer_index: usize) -> Result<&Header> {
+    meta_data
+        .headers
+        .get(layer_index)
+        .ok_or(Error::invalid("block layer index"))
+}
+
+/// Check that the lines are exactly one scan line block of the header.
+fn validate_block_bounds(header: &Header, y: usize, height: usize) -> UnitResult {
+    let lines_per_block = header.compression.scan_lines_per_block();
+    let layer_height = header.layer_size.height();
+
+    let is_valid = y % lines_per_block == 0
+        && y < layer_height
+        && height == lines_per_block.min(layer_height - y);
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(Error::invalid("scan line block bounds"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block::BlockIndex;
+    use crate::image::deep::DeepChannelData;
+    use crate::image::read::deep::read_deep;
+    use crate::prelude::*;
+    use std::io::Cursor;
+
+    fn flat_header(line_order: LineOrder) -> Header {
+        Header::new(
+            Text::from("appended"),
+            Vec2(5, 37),
+            smallvec::smallvec![ChannelDescription::named("Y", SampleType::F32)],
+        )
+        .with_encoding(Compression::ZIP16, BlockDescription::ScanLines, line_order)
+    }
+
+    fn flat_block(header: &Header, y: usize) -> UncompressedBlock {
+        let width = header.layer_size.width();
+        let height = 16.min(header.layer_size.height() - y);
+
+        UncompressedBlock {
+            index: BlockIndex {
+                layer: 0,
+                pixel_position: Vec2(0, y),
+                pixel_size: Vec2(width, height),
+                level: Vec2(0, 0),
+            },
+            data: (y * width..(y + height) * width)
+                .flat_map(|index| (index as f32).to_ne_bytes())
+                .collect(),
+        }
+    }
+
+    fn read_flat(bytes: &[u8]) -> FlatImage {
+        read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .from_buffered(Cursor::new(bytes))
+            .unwrap()
+    }
+
+    #[test]
+    fn append_flat_blocks_in_any_order() {
+        for line_order in [
+            LineOrder::Increasing,
+            LineOrder::Decreasing,
+            LineOrder::Unspecified,
+        ] {
+            let header = flat_header(line_order);
+
+            let mut bytes = Vec::new();
+            let pedantic = line_order != LineOrder::Unspecified;
+            let mut appender = BlockAppender::new_for_buffered(
+                Cursor::new(&mut bytes),
+                smallvec::smallvec![header.clone()],
+                pedantic,
+            )
+            .unwrap();
+
+            appender.append_block(flat_block(&header, 16)).unwrap();
+            appender.append_block(flat_block(&header, 32)).unwrap();
+
+            if line_order == LineOrder::Increasing {
+                assert_eq!(appender.pending_chunk_count(), 2);
+            }
+
+            // an appender with missing blocks cannot be finished
+            appender.append_block(flat_block(&header, 0)).unwrap();
+            assert_eq!(appender.pending_chunk_count(), 0);
+            assert!(appender.append_block(flat_block(&header, 16)).is_err());
+            appender.finish().unwrap();
+
+            let image = read_flat(&bytes);
+            let samples = &image.layer_data[0].channel_data.list[0].sample_data;
+            let expected: Vec<f32> = (0..5 * 37).map(|index| index as f32).collect();
+            assert_eq!(samples, &FlatSamples::F32(expected));
+        }
+    }
+
+    #[test]
+    fn unfinished_files_are_an_error() {
+        let header = flat_header(LineOrder::Increasing);
+
+        let mut bytes = Vec::new();
+        let mut appender = BlockAppender::new_for_buffered(
+            Cursor::new(&mut bytes),
+            smallvec::smallvec![header.clone()],
+            true,
+        )
+        .unwrap();
+
+        appender.append_block(flat_block(&header, 32)).unwrap();
+        assert!(appender.finish().is_err());
+
+        let mut appender = BlockAppender::new_for_buffered(
+            Cursor::new(Vec::new()),
+            smallvec::smallvec![header.clone()],
+            true,
+        )
+        .unwrap();
+
+        let mut misaligned = flat_block(&header, 16);
+        misaligned.index.pixel_position = Vec2(0, 8);
+        assert!(appender.append_block(misaligned).is_err());
+    }
+
+    #[test]
+    fn append_deep_blocks() {
+        let size = Vec2(3, 4);
+
+        let mut header = Header::new(
+            Text::from("deep"),
+            size,
+            smallvec::smallvec![
+                ChannelDescription::named("A", SampleType::F32),
+                ChannelDescription::named("Z", SampleType::F32),
+            ],
+        )
+        .with_encoding(
+            Compression::ZIP1,
+            BlockDescription::ScanLines,
+            LineOrder::Increasing,
+        );
+
+        header.deep = true;
+        header.deep_data_version = Some(1);
+        header.max_samples_per_pixel = Some(2);
+
+        // one sample in each pixel of even lines, two samples in each pixel of odd lines
+        let line = |y: usize| {
+            let sample_count = 1 + y % 2;
+            let mut samples = DeepSamples::new(size.width(), 1);
+            samples.sample_offsets = (1..=size.width())
+                .map(|pixel| (pixel * sample_count) as u32)
+                .collect();
+
+            let values = vec![y as f32; size.width() * sample_count];
+            samples.channels = vec![
+                DeepChannelData::F32(values.clone()),
+                DeepChannelData::F32(values),
+            ];
+
+            samples
+        };
+
+        let mut bytes = Vec::new();
+        let mut appender = BlockAppender::new_for_buffered(
+            Cursor::new(&mut bytes),
+            smallvec::smallvec![header],
+            true,
+        )
+        .unwrap();
+
+        for y in [3, 1, 0, 2] {
+            appender.append_deep_block(0, y, &line(y)).unwrap();
+        }
+
+        assert!(appender
+            .append_block(flat_block(&flat_header(LineOrder::Increasing), 0))
+            .is_err());
+        appender.finish().unwrap();
+
+        let image = read_deep()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(Cursor::new(&bytes))
+            .unwrap();
+
+        let samples = &image.layer_data.channel_data.list[0].sample_data;
+
+        assert_eq!(samples.total_samples(), 3 + 6 + 3 + 6);
+        assert_eq!(samples.sample_count(0, 1), 2);
+        assert_eq!(samples.sample_count(2, 3), 2);
+        assert_eq!(
+            samples.channels[1].get_as_f32(samples.sample_range(6).0),
+            2.0
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Read exr files from asynchronous byte sources, using `tokio`.
+//!
+//! The meta data and the chunks are read with `AsyncRead + AsyncSeek`,
+//! so that no thread is blocked while waiting for the bytes.
+//! Decompression is cpu bound, so it is dispatched to the blocking thread pool of `tokio`.
+//!
+//! To read a complete image, use `from_async` on the image reading builders,
+//! for example `read().no_deep_data()...from_async(file).await`.
+//! Use the [`AsyncReader`] to fetch only some of the chunks.
+
+use crate::block::chunk::Chunk;
+use crate::block::deep::{decompress_deep_chunk, DeepUncompressedBlock};
+use crate::block::limits::ReadLimits;
+use crate::block::UncompressedBlock;
+use crate::error::{u64_to_usize, Error, Result};
+use crate::io::{PeekRead, Tracking};
+use crate::meta::header::Header;
+use crate::meta::{MetaData, OffsetTables};
+use std::io::SeekFrom;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+/// The number of bytes that are requested at once while the meta data is incomplete.
+const META_DATA_READ_SIZE: usize = 16 * 1024;
+
+/// Decodes the meta data from an asynchronous byte source,
+/// and then reads the chunks at the positions of the offset tables.
+#[derive(Debug)]
+pub struct AsyncReader<R> {
+    meta_data: Arc<MetaData>,
+    offset_tables: OffsetTables,
+
+    /// All offsets, sorted, to find the end of each chunk.
+    sorted_offsets: Vec<u64>,
+
+    /// All bytes that were read while decoding the meta data.
+    meta_data_bytes: Vec<u8>,
+    byte_count: u64,
+
+    read: R,
+    pedantic: bool,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncReader<R> {
+    /// Start the reading process, immediately decoding the meta data and the offset tables.
+    /// The byte source must be positioned at the start of the file.
+    pub async fn read_from(mut read: R, pedantic: bool) -> Result<Self> {
+        let mut bytes = Vec::new();
+
+        let (meta_data, offset_tables, chunks_start) = loop {
+            bytes.reserve(bytes.len().max(META_DATA_READ_SIZE));
+            let stream_ended = read.read_buf(&mut bytes).await? == 0;
+
+            match decode_meta_data(&bytes, pedantic) {
+                Err(error) if is_missing_bytes(&error) && !stream_ended => continue,
+                result => break result?,
+            }
+        };
+
+        let byte_count = read.seek(SeekFrom::End(0)).await?;
+
+        let mut sorted_offsets: Vec<u64> = offset_tables.iter().flatten().copied().collect();
+        sorted_offsets.sort_unstable();
+        sorted_offsets.dedup();
+
+        let chunks_start = chunks_start as u64;
+        let is_invalid = sorted_offsets
+            .iter()
+            .any(|&offset| offset < chunks_start || offset >= byte_count);
+
+        if pedantic && is_invalid {
+            return Err(Error::invalid("offset table"));
+        }
+
+        Ok(Self {
+            meta_data: Arc::new(meta_data),
+            offset_tables,
+            sorted_offsets,
+            meta_data_bytes: bytes,
+            byte_count,
+            read,
+            pedantic,
+        })
+    }
+
+    /// The decoded exr meta data from the file.
+    pub fn meta_data(&self) -> &MetaData {
+        &self.meta_data
+    }
+
+    /// The decoded exr meta data from the file.
+    pub fn headers(&self) -> &[Header] {
+        &self.meta_data.headers
+    }
+
+    /// The number of chunks in all layers of the file.
+    pub fn chunk_count(&self) -> usize {
+        self.offset_tables.iter().map(|table| table.len()).sum()
+    }
+
+    /// Read a single chunk.
+    /// The chunk index refers to the offset table of the layer, in increasing y order.
+    pub async fn read_chunk(&mut self, layer_index: usize, chunk_index: usize) -> Result<Chunk> {
+        let start = *self
+            .offset_tables
+            .get(layer_index)
+            .and_then(|table| table.get(chunk_index))
+            .ok_or(Error::invalid("chunk index"))?;
+
+        // a chunk ends where the next chunk in the file starts
+        let next = self
+            .sorted_offsets
+            .partition_point(|&offset| offset <= start);
+        let end = self
+            .sorted_offsets
+            .get(next)
+            .copied()
+            .unwrap_or(self.byte_count);
+
+        if start >= end || end > self.byte_count {
+            return Err(Error::invalid("chunk start"));
+        }
+
+        let mut bytes = vec![0_u8; u64_to_usize(end - start, "chunk size")?];
+        self.read.seek(SeekFrom::Start(start)).await?;
+        self.read.read_exact(&mut bytes).await?;
+
+        Chunk::read(&mut bytes.as_slice(), &self.meta_data)
+    }
+
+    /// Read all chunks, in the order of the offset tables.
+    pub async fn read_all_chunks(&mut self) -> Result<Vec<Chunk>> {
+        let mut chunks = Vec::with_capacity(self.chunk_count());
+
+        for layer_index in 0..self.offset_tables.len() {
+            for chunk_index in 0..self.offset_tables[layer_index].len() {
+                chunks.push(self.read_chunk(layer_index, chunk_index).await?);
+            }
+        }
+
+        Ok(chunks)
+    }
+
+    /// Decompress a flat chunk on the blocking thread pool.
+    pub async fn decompress_block(&self, chunk: Chunk) -> Result<UncompressedBlock> {
+        let meta_data = self.meta_data.clone();
+        let pedantic = self.pedantic;
+
+        spawn_blocking(move || UncompressedBlock::decompress_chunk(chunk, &meta_data, pedantic))
+            .await
+    }
+
+    /// Decompress a deep chunk on the blocking thread pool.
+    /// Pass the indices of the channels to unpack, in increasing order, or `None` for all channels.
+    pub async fn decompress_deep_block(
+        &self,
+        chunk: Chunk,
+        selected_channels: Option<Vec<usize>>,
+    ) -> Result<DeepUncompressedBlock> {
+        let meta_data = self.meta_data.clone();
+        let pedantic = self.pedantic;
+
+        spawn_blocking(move || {
+            decompress_deep_chunk(
+                &chunk.compressed_block,
+                &meta_data,
+                chunk.layer_index,
+                selected_channels.as_deref(),
+                pedantic,
+                &ReadLimits::UNLIMITED,
+            )
+        })
+        .await
+    }
+
+    /// Read the remaining bytes of the file,
+    /// returning all bytes of the file, including the meta data.
+    pub async fn into_file_bytes(self) -> Result<Vec<u8>> {
+        let Self {
+            mut meta_data_bytes,
+            mut read,
+            byte_count,
+            ..
+        } = self;
+
+        let position = meta_data_bytes.len() as u64;
+        meta_data_bytes.reserve(u64_to_usize(
+            byte_count.saturating_sub(position),
+            "file size",
+        )?);
+
+        read.seek(SeekFrom::Start(position)).await?;
+        read.read_to_end(&mut meta_data_bytes).await?;
+
+        Ok(meta_data_bytes)
+    }
+}
+
+/// Read all bytes of a file asynchronously, but fail early if the meta data is invalid.
+pub(crate) async fn read_file_bytes(
+    read: impl AsyncRead + AsyncSeek + Unpin,
+    pedantic: bool,
+) -> Result<Vec<u8>> {
+    AsyncReader::read_from(read, pedantic)
+        .await?
+        .into_file_bytes()
+        .await
+}
+
+/// Run the cpu bound function on the blocking thread pool of `tokio`.
+/// Panics inside the function are resumed on the calling task.
+pub(crate) async fn spawn_blocking<T: Send + 'static>(
+    function: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    match tokio::task::spawn_blocking(function).await {
+        Ok(result) => result,
+        Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+        Err(_) => Err(Error::Aborted),
+    }
+}
+
+/// Decode the meta data and the offset tables, returning the byte position of the first chunk.
+fn decode_meta_data(bytes: &[u8], pedantic: bool) -> Result<(MetaData, OffsetTables, usize)> {
+    let mut read = PeekRead::new(Tracking::new(bytes));
+    let meta_data = MetaData::read_validated_from_buffered_peekable(&mut read, pedantic)?;
+    let offset_tables = MetaData::read_offset_tables(&mut read, &meta_data.headers)?;
+    Ok((meta_data, offset_tables, read.byte_position()))
+}
+
+/// Whether decoding failed only because the bytes ended too early.
+/// See the conversion of `ErrorKind::UnexpectedEof` in `crate::error`.
+fn is_missing_bytes(error: &Error) -> bool {
+    matches!(error, Error::Invalid(message) if message == "reference to missing bytes")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block::reader::Reader;
+    use crate::image::read::read;
+    use crate::prelude::*;
+    use std::io::Cursor;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    fn file_bytes() -> Vec<u8> {
+        let size = Vec2(9, 70);
+        let samples = FlatSamples::F32((0..size.area()).map(|index| index as f32).collect());
+        let layer = Layer::new(
+            size,
+            LayerAttributes::named("async"),
+            Encoding::SMALL_LOSSLESS,
+            AnyChannels::sort(smallvec::smallvec![AnyChannel::new("Y", samples)]),
+        );
+
+        let mut bytes = Vec::new();
+        Image::from_layer(layer)
+            .write()
+            .to_buffered(Cursor::new(&mut bytes))
+            .unwrap();
+
+        bytes
+    }
+
+    fn chunk_bytes(chunks: &[Chunk]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for chunk in chunks {
+            chunk.write(&mut bytes, 1).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn chunks_match_sync_reader() {
+        let bytes = file_bytes();
+
+        let expected: Vec<Chunk> = Reader::read_from_buffered(Cursor::new(&bytes), true)
+            .unwrap()
+            .all_chunks(true)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        runtime().block_on(async {
+            let mut reader = AsyncReader::read_from(Cursor::new(&bytes), true)
+                .await
+                .unwrap();
+
+            assert_eq!(reader.chunk_count(), expected.len());
+
+            let chunks = reader.read_all_chunks().await.unwrap();
+            assert_eq!(chunk_bytes(&chunks), chunk_bytes(&expected));
+
+            let block = reader.decompress_block(chunks[0].clone()).await.unwrap();
+            assert_eq!(block.index.layer, 0);
+
+            assert_eq!(reader.into_file_bytes().await.unwrap(), bytes);
+        });
+    }
+
+    #[test]
+    fn read_image_async() {
+        let bytes = file_bytes();
+
+        let reader = || {
+            read()
+                .no_deep_data()
+                .largest_resolution_level()
+                .all_channels()
+                .first_valid_layer()
+                .all_attributes()
+        };
+
+        let expected = reader().from_buffered(Cursor::new(&bytes)).unwrap();
+
+        let image = runtime()
+            .block_on(reader().from_async(Cursor::new(bytes)))
+            .unwrap();
+
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn truncated_meta_data_is_an_error() {
+        let bytes = file_bytes();
+        let truncated = Cursor::new(bytes[..40].to_vec());
+
+        let result = runtime().block_on(AsyncReader::read_from(truncated, false));
+        assert!(result.is_err());
+    }
+}
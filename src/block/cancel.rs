@@ -0,0 +1,116 @@
+//! Stop reading or writing an image from another thread.
+
+use crate::error::{Error, UnitResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag that stops reading or writing an image as soon as it is set, usually from another thread.
+/// The readers and writers check the flag between two blocks,
+/// and return `Error::Aborted` once it has been set.
+/// All clones of a token share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// A token that has not been cancelled yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop all reading and writing processes that use this token or one of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel` has been called on this token or one of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns `Error::Aborted` if this token has been cancelled.
+    pub fn check(&self) -> UnitResult {
+        if self.is_cancelled() {
+            Err(Error::Aborted)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Tokens are equal if they share the same flag.
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_flag() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(clone.check().is_ok());
+
+        token.cancel();
+        assert!(clone.is_cancelled());
+        assert!(matches!(clone.check(), Err(Error::Aborted)));
+
+        assert_eq!(token, clone);
+        assert_ne!(token, CancellationToken::new());
+    }
+
+    #[test]
+    fn cancelled_reading_and_writing_is_aborted() {
+        use crate::image::read::read;
+        use crate::prelude::*;
+        use std::io::Cursor;
+
+        let size = Vec2(8, 64);
+        let image = Image::from_channels(
+            size,
+            SpecificChannels::rgb(|position: Vec2<usize>| (position.x() as f32, 0.5_f32, 1.0_f32)),
+        );
+
+        let cancelled = CancellationToken::new();
+        cancelled.cancel();
+
+        let written = image
+            .write()
+            .with_cancellation(cancelled.clone())
+            .to_buffered(Cursor::new(Vec::new()));
+        assert!(matches!(written, Err(Error::Aborted)));
+
+        let mut bytes = Vec::new();
+        let mut progress = Vec::new();
+        image
+            .write()
+            .on_progress(|value| progress.push(value))
+            .to_buffered(Cursor::new(&mut bytes))
+            .unwrap();
+        assert_eq!(progress.last(), Some(&1.0));
+
+        let reader = || {
+            read()
+                .no_deep_data()
+                .largest_resolution_level()
+                .all_channels()
+                .first_valid_layer()
+                .all_attributes()
+        };
+
+        let read_image = reader()
+            .with_cancellation(cancelled)
+            .from_buffered(Cursor::new(&bytes));
+        assert!(matches!(read_image, Err(Error::Aborted)));
+
+        let read_image = reader()
+            .with_cancellation(CancellationToken::new())
+            .from_buffered(Cursor::new(&bytes));
+        assert!(read_image.is_ok());
+    }
+}
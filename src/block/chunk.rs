@@ -103,7 +103,7 @@ pub struct CompressedDeepScanLineBlock {
     /// The pixel offset table is a list of integers, one for each pixel column within the data window.
     /// Each entry in the table indicates the total number of samples required
     /// to store the pixel in it as well as all pixels to the left of it.
-    pub compressed_pixel_offset_table: Vec<i8>,
+    pub compressed_pixel_offset_table: Vec<u8>,
 
     /// One or more scan lines may be stored together as a scan line block.
     /// The number of scan lines per block depends on how the pixel data are compressed.
@@ -126,7 +126,7 @@ pub struct CompressedDeepTileBlock {
     /// The pixel offset table is a list of integers, one for each pixel column within the data window.
     /// Each entry in the table indicates the total number of samples required
     /// to store the pixel in it as well as all pixels to the left of it.
-    pub compressed_pixel_offset_table: Vec<i8>,
+    pub compressed_pixel_offset_table: Vec<u8>,
 
     /// One or more scan lines may be stored together as a scan line block.
     /// The number of scan lines per block depends on how the pixel data are compressed.
@@ -225,8 +225,10 @@ impl CompressedScanLineBlock {
     /// Read the value without validating.
     pub fn read(read: &mut impl Read, max_block_byte_size: usize) -> Result<Self> {
         let y_coordinate = i32::read_le(read)?;
-        let compressed_pixels_le = u8::read_i32_sized_vec_le(
+        let byte_count = i32_to_usize(i32::read_le(read)?, "scan line block sample count")?;
+        let compressed_pixels_le = read_bytes(
             read,
+            byte_count,
             max_block_byte_size,
             Some(max_block_byte_size),
             "scan line block sample count",
@@ -255,8 +257,10 @@ impl CompressedTileBlock {
     /// Read the value without validating.
     pub fn read(read: &mut impl Read, max_block_byte_size: usize) -> Result<Self> {
         let coordinates = TileCoordinates::read(read)?;
-        let compressed_pixels_le = u8::read_i32_sized_vec_le(
+        let byte_count = i32_to_usize(i32::read_le(read)?, "tile block sample count")?;
+        let compressed_pixels_le = read_bytes(
             read,
+            byte_count,
             max_block_byte_size,
             Some(max_block_byte_size),
             "tile block sample count",
@@ -278,7 +282,7 @@ impl CompressedDeepScanLineBlock {
         u64::write_le(self.compressed_pixel_offset_table.len() as u64, write)?;
         u64::write_le(self.compressed_sample_data_le.len() as u64, write)?; // TODO just guessed
         u64::write_le(self.decompressed_sample_data_size as u64, write)?;
-        i8::write_slice_le(write, &self.compressed_pixel_offset_table)?;
+        u8::write_slice_le(write, &self.compressed_pixel_offset_table)?;
         u8::write_slice_le(write, &self.compressed_sample_data_le)?;
         Ok(())
     }
@@ -292,7 +296,7 @@ impl CompressedDeepScanLineBlock {
         let decompressed_sample_data_size = u64_to_usize(u64::read_le(read)?, "raw deep size")?;
 
         // doc said i32, try u8
-        let compressed_pixel_offset_table = i8::read_vec_le(
+        let compressed_pixel_offset_table = read_bytes(
             read,
             compressed_pixel_offset_table_size,
             6 * u16::MAX as usize,
@@ -300,7 +304,7 @@ impl CompressedDeepScanLineBlock {
             "deep scan line block table size",
         )?;
 
-        let compressed_sample_data_le = u8::read_vec_le(
+        let compressed_sample_data_le = read_bytes(
             read,
             compressed_sample_data_size,
             6 * u16::MAX as usize,
@@ -319,18 +323,13 @@ impl CompressedDeepScanLineBlock {
 
 impl CompressedDeepTileBlock {
     /// Without validation, write this instance to the byte stream.
+    /// Like deep scan line blocks, tiles without any samples have no sample data.
     pub fn write<W: Write>(&self, write: &mut W) -> UnitResult {
-        debug_assert_ne!(
-            self.compressed_sample_data_le.len(),
-            0,
-            "empty blocks should not be put in the file bug"
-        );
-
         self.coordinates.write(write)?;
         u64::write_le(self.compressed_pixel_offset_table.len() as u64, write)?;
         u64::write_le(self.compressed_sample_data_le.len() as u64, write)?; // TODO just guessed
         u64::write_le(self.decompressed_sample_data_size as u64, write)?;
-        i8::write_slice_le(write, &self.compressed_pixel_offset_table)?;
+        u8::write_slice_le(write, &self.compressed_pixel_offset_table)?;
         u8::write_slice_le(write, &self.compressed_sample_data_le)?;
         Ok(())
     }
@@ -343,7 +342,7 @@ impl CompressedDeepTileBlock {
         let compressed_sample_data_size = u64_to_usize(u64::read_le(read)?, "deep size")?; // per OpenEXR 2.0 spec
         let decompressed_sample_data_size = u64_to_usize(u64::read_le(read)?, "raw deep size")?;
 
-        let compressed_pixel_offset_table = i8::read_vec_le(
+        let compressed_pixel_offset_table = read_bytes(
             read,
             compressed_pixel_offset_table_size,
             6 * u16::MAX as usize,
@@ -351,7 +350,7 @@ impl CompressedDeepTileBlock {
             "deep tile block table size",
         )?;
 
-        let compressed_sample_data_le = u8::read_vec_le(
+        let compressed_sample_data_le = read_bytes(
             read,
             compressed_sample_data_size,
             6 * u16::MAX as usize,
@@ -368,6 +367,7 @@ impl CompressedDeepTileBlock {
     }
 }
 
+use crate::block::pool::read_bytes;
 use crate::error::{i32_to_usize, u64_to_usize, usize_to_i32, Error, Result, UnitResult};
 use crate::math::Vec2;
 
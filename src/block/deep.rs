@@ -60,12 +60,18 @@
 //! - [`crate::image::deep`] - High-level [`DeepSamples`] type
 //! - [`crate::block::chunk`] - Block types (`CompressedDeepScanLineBlock`)
 
-use crate::block::chunk::{CompressedDeepScanLineBlock, CompressedDeepTileBlock};
+use crate::block::chunk::{CompressedDeepScanLineBlock, CompressedDeepTileBlock, TileCoordinates};
+use crate::block::deinterleave::{deinterleave, deinterleave_channels};
+use crate::block::limits::{DeepBudget, ReadLimits};
+use crate::block::pool::recycle_buffer;
 use crate::compression::{deep as deep_compress, Compression};
 use crate::error::{Error, Result};
-use crate::image::deep::{DeepChannelData, DeepSamples};
-use crate::meta::attribute::{ChannelList, SampleType};
+use crate::image::deep::{DeepChannelData, DeepSample, DeepSamples};
+use crate::math::Vec2;
+use crate::meta::attribute::{ChannelList, SampleType, Text};
+use crate::meta::header::Header;
 use half::f16;
+use smallvec::SmallVec;
 
 /// Decompress a deep scanline block into [`DeepSamples`].
 ///
@@ -98,43 +104,50 @@ pub fn decompress_deep_scanline_block(
     lines_per_block: usize,
     pedantic: bool,
 ) -> Result<DeepSamples> {
-    let width = data_window_width;
-    let height = lines_per_block;
-
-    // Decompress sample count table
-    let table_bytes: Vec<u8> = block
-        .compressed_pixel_offset_table
-        .iter()
-        .map(|&b| b as u8)
-        .collect();
-
-    let cumulative_counts =
-        deep_compress::decompress_sample_table(compression, &table_bytes, width, height, pedantic)?;
-
-    // Validate counts
-    deep_compress::validate_sample_table(&cumulative_counts)?;
-
-    // Create DeepSamples structure
-    let mut samples = DeepSamples::new(width, height);
-
-    // Convert i32 cumulative to u32
-    let cumulative_u32: Vec<u32> = cumulative_counts.iter().map(|&c| c as u32).collect();
-
-    samples.set_cumulative_counts(cumulative_u32)?;
-
-    // Decompress sample data
-    let decompressed_data = deep_compress::decompress_sample_data(
+    decompress_deep_scanline_block_with_channels(
+        block,
         compression,
+        channels,
+        None,
+        data_window_width,
+        lines_per_block,
+        pedantic,
+        &ReadLimits::UNLIMITED,
+    )
+}
+
+/// Decompress a deep scanline block, but only unpack the selected channels.
+///
+/// `selected_channels` contains the indices of the channels in the channel list,
+/// in increasing order, or `None` to unpack all channels.
+/// The channels of the resulting [`DeepSamples`] contain only the selected channels.
+/// The values of the other channels are skipped, and no memory is allocated for them.
+///
+/// The pixel count, the decompressed size, and the sample count of the block
+/// are checked against the limits before the memory for them is allocated.
+#[allow(clippy::too_many_arguments)]
+pub fn decompress_deep_scanline_block_with_channels(
+    block: &CompressedDeepScanLineBlock,
+    compression: Compression,
+    channels: &ChannelList,
+    selected_channels: Option<&[usize]>,
+    data_window_width: usize,
+    lines_per_block: usize,
+    pedantic: bool,
+    limits: &ReadLimits,
+) -> Result<DeepSamples> {
+    decompress_deep_samples(
+        &block.compressed_pixel_offset_table,
         &block.compressed_sample_data_le,
         block.decompressed_sample_data_size,
+        compression,
+        channels,
+        selected_channels,
+        data_window_width,
+        lines_per_block,
         pedantic,
-    )?;
-
-    // Unpack channel data
-    unpack_deep_channels(&decompressed_data, &mut samples, channels)?;
-
-    samples.validate()?;
-    Ok(samples)
+        limits,
+    )
 }
 
 /// Decompress a deep tile block into DeepSamples.
@@ -146,41 +159,114 @@ pub fn decompress_deep_tile_block(
     tile_height: usize,
     pedantic: bool,
 ) -> Result<DeepSamples> {
-    // Decompress sample count table
-    let table_bytes: Vec<u8> = block
-        .compressed_pixel_offset_table
-        .iter()
-        .map(|&b| b as u8)
-        .collect();
+    decompress_deep_tile_block_with_channels(
+        block,
+        compression,
+        channels,
+        None,
+        tile_width,
+        tile_height,
+        pedantic,
+        &ReadLimits::UNLIMITED,
+    )
+}
 
-    let cumulative_counts = deep_compress::decompress_sample_table(
+/// Decompress a deep tile block, but only unpack the selected channels.
+/// See [`decompress_deep_scanline_block_with_channels`].
+#[allow(clippy::too_many_arguments)]
+pub fn decompress_deep_tile_block_with_channels(
+    block: &CompressedDeepTileBlock,
+    compression: Compression,
+    channels: &ChannelList,
+    selected_channels: Option<&[usize]>,
+    tile_width: usize,
+    tile_height: usize,
+    pedantic: bool,
+    limits: &ReadLimits,
+) -> Result<DeepSamples> {
+    decompress_deep_samples(
+        &block.compressed_pixel_offset_table,
+        &block.compressed_sample_data_le,
+        block.decompressed_sample_data_size,
         compression,
-        &table_bytes,
+        channels,
+        selected_channels,
         tile_width,
         tile_height,
         pedantic,
+        limits,
+    )
+}
+
+/// Decompress the two sections of a deep block, borrowing the compressed bytes.
+/// Uncompressed sample data is unpacked directly from the borrowed bytes, without copying it first.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn decompress_deep_samples(
+    compressed_pixel_offset_table: &[u8],
+    compressed_sample_data_le: &[u8],
+    decompressed_sample_data_size: usize,
+    compression: Compression,
+    channels: &ChannelList,
+    selected_channels: Option<&[usize]>,
+    width: usize,
+    height: usize,
+    pedantic: bool,
+    limits: &ReadLimits,
+) -> Result<DeepSamples> {
+    // the sample count table contains one `i32` per pixel
+    limits.validate_pixel_count(width.saturating_mul(height))?;
+    limits.validate_decompressed_byte_count(decompressed_sample_data_size)?;
+
+    // Decompress sample count table
+    let cumulative_counts = deep_compress::decompress_sample_table(
+        compression,
+        compressed_pixel_offset_table,
+        width,
+        height,
+        pedantic,
     )?;
 
     // Validate counts
     deep_compress::validate_sample_table(&cumulative_counts)?;
 
+    let sample_count = cumulative_counts.last().map_or(0, |&count| count as usize);
+    limits.validate_sample_count(sample_count)?;
+
     // Create DeepSamples structure
-    let mut samples = DeepSamples::new(tile_width, tile_height);
+    let mut samples = DeepSamples::new(width, height);
 
+    // Convert i32 cumulative to u32
     let cumulative_u32: Vec<u32> = cumulative_counts.iter().map(|&c| c as u32).collect();
 
     samples.set_cumulative_counts(cumulative_u32)?;
 
-    // Decompress sample data
-    let decompressed_data = deep_compress::decompress_sample_data(
-        compression,
-        &block.compressed_sample_data_le,
-        block.decompressed_sample_data_size,
-        pedantic,
-    )?;
-
-    // Unpack channel data
-    unpack_deep_channels(&decompressed_data, &mut samples, channels)?;
+    // raw sample data is stored when compression would not make it smaller
+    let is_raw = compression == Compression::Uncompressed
+        || compressed_sample_data_le.len() == decompressed_sample_data_size;
+
+    if is_raw {
+        unpack_deep_channels(
+            compressed_sample_data_le,
+            &mut samples,
+            channels,
+            selected_channels,
+        )?;
+    } else {
+        let decompressed_data = deep_compress::decompress_sample_data(
+            compression,
+            compressed_sample_data_le,
+            decompressed_sample_data_size,
+            pedantic,
+        )?;
+
+        unpack_deep_channels(
+            &decompressed_data,
+            &mut samples,
+            channels,
+            selected_channels,
+        )?;
+        recycle_buffer(decompressed_data);
+    }
 
     samples.validate()?;
     Ok(samples)
@@ -188,22 +274,15 @@ pub fn decompress_deep_tile_block(
 
 /// Unpack decompressed bytes into DeepSamples channels.
 /// Data layout: for each pixel, for each sample, for each channel - channel value in LE format.
+/// Only the selected channels are unpacked, or all channels if there is no selection.
 fn unpack_deep_channels(
     data: &[u8],
     samples: &mut DeepSamples,
     channels: &ChannelList,
+    selected_channels: Option<&[usize]>,
 ) -> Result<()> {
     let total_samples = samples.total_samples();
 
-    if total_samples == 0 {
-        // No samples, just allocate empty channels
-        samples.allocate_channels(channels);
-        return Ok(());
-    }
-
-    // Allocate channel storage
-    samples.allocate_channels(channels);
-
     // Calculate bytes per sample (sum of all channel bytes)
     let bytes_per_sample: usize = channels
         .list
@@ -212,7 +291,7 @@ fn unpack_deep_channels(
         .sum();
 
     let expected_size = total_samples * bytes_per_sample;
-    if data.len() != expected_size {
+    if total_samples != 0 && data.len() != expected_size {
         return Err(Error::invalid(format!(
             "deep sample data size mismatch: got {}, expected {} ({} samples * {} bytes)",
             data.len(),
@@ -225,71 +304,148 @@ fn unpack_deep_channels(
     // Deep data is stored pixel-interleaved:
     // For each pixel, for each sample in that pixel, for each channel: value
     //
-    // We need to distribute samples to channels in SoA format.
-    let mut data_offset = 0;
-    let pixel_count = samples.pixel_count();
-
-    for pixel_idx in 0..pixel_count {
-        let (start, end) = samples.sample_range(pixel_idx);
-        let sample_count = end - start;
-
-        for sample_idx in 0..sample_count {
-            let dest_idx = start + sample_idx;
-
-            for (ch_idx, channel_desc) in channels.list.iter().enumerate() {
-                let channel_data = &mut samples.channels[ch_idx];
-
-                match channel_desc.sample_type {
-                    SampleType::F16 => {
-                        let bytes = [data[data_offset], data[data_offset + 1]];
-                        let value = f16::from_le_bytes(bytes);
-                        if let DeepChannelData::F16(ref mut v) = channel_data {
-                            v[dest_idx] = value;
-                        }
-                        data_offset += 2;
-                    }
-                    SampleType::F32 => {
-                        let bytes = [
-                            data[data_offset],
-                            data[data_offset + 1],
-                            data[data_offset + 2],
-                            data[data_offset + 3],
-                        ];
-                        let value = f32::from_le_bytes(bytes);
-                        if let DeepChannelData::F32(ref mut v) = channel_data {
-                            v[dest_idx] = value;
-                        }
-                        data_offset += 4;
-                    }
-                    SampleType::U32 => {
-                        let bytes = [
-                            data[data_offset],
-                            data[data_offset + 1],
-                            data[data_offset + 2],
-                            data[data_offset + 3],
-                        ];
-                        let value = u32::from_le_bytes(bytes);
-                        if let DeepChannelData::U32(ref mut v) = channel_data {
-                            v[dest_idx] = value;
-                        }
-                        data_offset += 4;
-                    }
-                }
-            }
+    // The values of one channel are therefore found at a fixed byte offset within each sample.
+    // Resolve the byte offset and the sample type of each selected channel once, before copying.
+    let mut unpacked_channels = Vec::with_capacity(channels.list.len());
+    let mut channel_offset = 0;
+
+    for (channel_index, channel_desc) in channels.list.iter().enumerate() {
+        let sample_type = channel_desc.sample_type;
+        let offset = channel_offset;
+        channel_offset += sample_type.bytes_per_sample();
+
+        // the bytes of unselected channels are simply skipped
+        let is_selected =
+            selected_channels.map_or(true, |selected| selected.contains(&channel_index));
+
+        if is_selected {
+            unpacked_channels.push((offset, sample_type));
         }
     }
 
-    debug_assert_eq!(data_offset, data.len(), "not all deep data was consumed");
+    samples.channels.clear();
+
+    // when all channels have the same type, each sample is visited only once,
+    // instead of visiting all samples again for each channel
+    let uniform_type = unpacked_channels
+        .first()
+        .map(|&(_, sample_type)| sample_type)
+        .filter(|&first| {
+            unpacked_channels
+                .iter()
+                .all(|&(_, sample_type)| sample_type == first)
+        });
+
+    if let Some(sample_type) = uniform_type.filter(|_| unpacked_channels.len() > 1) {
+        let offsets: SmallVec<[usize; 8]> = unpacked_channels
+            .iter()
+            .map(|&(offset, _)| offset)
+            .collect();
+
+        let unpack_channels = match sample_type {
+            SampleType::F16 => unpack_uniform_channel_values::<f16>,
+            SampleType::F32 => unpack_uniform_channel_values::<f32>,
+            SampleType::U32 => unpack_uniform_channel_values::<u32>,
+        };
+
+        samples.channels = unpack_channels(data, bytes_per_sample, &offsets, total_samples);
+        return Ok(());
+    }
+
+    for (offset, sample_type) in unpacked_channels {
+        let unpack_channel = match sample_type {
+            SampleType::F16 => unpack_channel_values::<f16>,
+            SampleType::F32 => unpack_channel_values::<f32>,
+            SampleType::U32 => unpack_channel_values::<u32>,
+        };
+
+        let channel_data = unpack_channel(data, bytes_per_sample, offset, total_samples);
+        samples.channels.push(channel_data);
+    }
+
     Ok(())
 }
 
+/// The indices of the channels with the specified names, in increasing order.
+/// Names that are not in the channel list are ignored.
+pub fn channel_indices(channels: &ChannelList, names: &[Text]) -> Vec<usize> {
+    channels
+        .list
+        .iter()
+        .enumerate()
+        .filter(|(_, channel)| names.contains(&channel.name))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// The indices of the channels with the specified names, for each layer.
+fn channel_indices_per_layer(
+    meta: &crate::meta::MetaData,
+    names: &[Text],
+) -> std::sync::Arc<Vec<Vec<usize>>> {
+    std::sync::Arc::new(
+        meta.headers
+            .iter()
+            .map(|header| channel_indices(&header.channels, names))
+            .collect(),
+    )
+}
+
+/// Copy the values of one channel out of the interleaved sample bytes.
+/// Each sample occupies `stride` bytes, and the value of this channel starts at `offset`.
+fn unpack_channel_values<T: DeepSample>(
+    data: &[u8],
+    stride: usize,
+    offset: usize,
+    sample_count: usize,
+) -> DeepChannelData {
+    let mut values = vec![T::default(); sample_count];
+    deinterleave(data, stride, offset, &mut values);
+    T::into_channel_data(values)
+}
+
+/// Copy the values of multiple channels of the same type out of the interleaved sample bytes,
+/// visiting each sample only once. The value of each channel starts at its offset within the sample.
+fn unpack_uniform_channel_values<T: DeepSample>(
+    data: &[u8],
+    stride: usize,
+    offsets: &[usize],
+    sample_count: usize,
+) -> Vec<DeepChannelData> {
+    let mut channels: SmallVec<[Vec<T>; 8]> = offsets
+        .iter()
+        .map(|_| vec![T::default(); sample_count])
+        .collect();
+
+    {
+        let mut outputs: SmallVec<[&mut [T]; 8]> = channels
+            .iter_mut()
+            .map(|values| values.as_mut_slice())
+            .collect();
+
+        deinterleave_channels(data, stride, offsets, &mut outputs);
+    }
+
+    channels.into_iter().map(T::into_channel_data).collect()
+}
+
 /// Pack DeepSamples channels into bytes for compression.
 /// Returns the data in pixel-interleaved LE format.
 pub fn pack_deep_channels(samples: &DeepSamples, channels: &ChannelList) -> Vec<u8> {
+    let mut data = Vec::new();
+    pack_deep_channels_into(samples, channels, &mut data);
+    data
+}
+
+/// Pack DeepSamples channels into the buffer, replacing its contents.
+/// Writes the data in pixel-interleaved LE format, like [`pack_deep_channels`].
+pub fn pack_deep_channels_into(samples: &DeepSamples, channels: &ChannelList, data: &mut Vec<u8>) {
+    data.clear();
+
     let total_samples = samples.total_samples();
 
     if total_samples == 0 {
-        return Vec::new();
+        return;
     }
 
     let bytes_per_sample: usize = channels
@@ -298,41 +454,115 @@ pub fn pack_deep_channels(samples: &DeepSamples, channels: &ChannelList) -> Vec<
         .map(|ch| ch.sample_type.bytes_per_sample())
         .sum();
 
-    let mut data = Vec::with_capacity(total_samples * bytes_per_sample);
-    let pixel_count = samples.pixel_count();
-
-    for pixel_idx in 0..pixel_count {
-        let (start, end) = samples.sample_range(pixel_idx);
-        let sample_count = end - start;
-
-        for sample_idx in 0..sample_count {
-            let src_idx = start + sample_idx;
-
-            for (ch_idx, channel_desc) in channels.list.iter().enumerate() {
-                let channel_data = &samples.channels[ch_idx];
-
-                match channel_desc.sample_type {
-                    SampleType::F16 => {
-                        if let DeepChannelData::F16(ref v) = channel_data {
-                            data.extend_from_slice(&v[src_idx].to_le_bytes());
-                        }
-                    }
-                    SampleType::F32 => {
-                        if let DeepChannelData::F32(ref v) = channel_data {
-                            data.extend_from_slice(&v[src_idx].to_le_bytes());
-                        }
-                    }
-                    SampleType::U32 => {
-                        if let DeepChannelData::U32(ref v) = channel_data {
-                            data.extend_from_slice(&v[src_idx].to_le_bytes());
-                        }
-                    }
-                }
-            }
+    // samples are stored in the same order as the interleaved bytes,
+    // so each channel can be scattered into the bytes in a single run
+    data.resize(total_samples * bytes_per_sample, 0);
+    let mut offset = 0;
+
+    for (channel_desc, channel_data) in channels.list.iter().zip(&samples.channels) {
+        let pack_channel = match channel_desc.sample_type {
+            SampleType::F16 => pack_channel_values::<f16>,
+            SampleType::F32 => pack_channel_values::<f32>,
+            SampleType::U32 => pack_channel_values::<u32>,
+        };
+
+        pack_channel(channel_data, data, bytes_per_sample, offset);
+        offset += channel_desc.sample_type.bytes_per_sample();
+    }
+}
+
+/// Copy the values of one channel into the interleaved sample bytes.
+/// Each sample occupies `stride` bytes, and the value of this channel starts at `offset`.
+/// Leaves the bytes unchanged if the channel does not contain values of this type.
+fn pack_channel_values<T: DeepSample>(
+    channel_data: &DeepChannelData,
+    data: &mut [u8],
+    stride: usize,
+    offset: usize,
+) {
+    let size = std::mem::size_of::<T>();
+
+    if let Some(values) = T::channel_values(channel_data) {
+        for (sample, value) in data.chunks_exact_mut(stride).zip(values) {
+            value.write_le_slice(&mut sample[offset..offset + size]);
         }
     }
+}
 
-    data
+/// Buffers that are reused when compressing many deep blocks.
+///
+/// Writers that emit thousands of blocks can keep one instance,
+/// and return each block to it after the block has been written,
+/// so that packing and compressing the next blocks does not allocate.
+#[derive(Debug, Clone, Default)]
+pub struct DeepBlockBuffers {
+    /// The sample offset table as `i32`.
+    sample_table: Vec<i32>,
+
+    /// The pixel-interleaved LE sample bytes.
+    packed: Vec<u8>,
+
+    /// The reordered bytes before compression.
+    scratch: Vec<u8>,
+
+    /// Byte vectors of written blocks, used for the compressed output of the next blocks.
+    unused: Vec<Vec<u8>>,
+}
+
+impl DeepBlockBuffers {
+    /// Create empty buffers, which grow to the size of the largest block.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reuse the byte vectors of a scan line block that has been written.
+    pub fn recycle_scan_line_block(&mut self, block: CompressedDeepScanLineBlock) {
+        self.unused.push(block.compressed_pixel_offset_table);
+        self.unused.push(block.compressed_sample_data_le);
+    }
+
+    /// Reuse the byte vectors of a tile block that has been written.
+    pub fn recycle_tile_block(&mut self, block: CompressedDeepTileBlock) {
+        self.unused.push(block.compressed_pixel_offset_table);
+        self.unused.push(block.compressed_sample_data_le);
+    }
+
+    /// Compress the sample offset table and the sample data into reused byte vectors.
+    /// Returns the table, the data, and the size of the uncompressed data.
+    fn compress(
+        &mut self,
+        samples: &DeepSamples,
+        compression: Compression,
+        channels: &ChannelList,
+    ) -> Result<(Vec<u8>, Vec<u8>, usize)> {
+        let mut compressed_table = self.unused.pop().unwrap_or_default();
+        let mut compressed_data = self.unused.pop().unwrap_or_default();
+
+        // Get cumulative counts as i32
+        self.sample_table.clear();
+        self.sample_table
+            .extend(samples.sample_offsets.iter().map(|&c| c as i32));
+
+        // Compress sample count table
+        deep_compress::compress_sample_table_into(
+            compression,
+            &self.sample_table,
+            &mut self.scratch,
+            &mut compressed_table,
+        )?;
+
+        // Pack and compress sample data
+        pack_deep_channels_into(samples, channels, &mut self.packed);
+
+        deep_compress::compress_sample_data_into(
+            compression,
+            &self.packed,
+            &mut self.scratch,
+            &mut compressed_data,
+        )?;
+
+        Ok((compressed_table, compressed_data, self.packed.len()))
+    }
 }
 
 /// Compress DeepSamples into a CompressedDeepScanLineBlock.
@@ -342,22 +572,32 @@ pub fn compress_deep_scanline_block(
     channels: &ChannelList,
     y_coordinate: i32,
 ) -> Result<CompressedDeepScanLineBlock> {
-    // Get cumulative counts as i32
-    let cumulative_i32: Vec<i32> = samples.sample_offsets.iter().map(|&c| c as i32).collect();
-
-    // Compress sample count table
-    let compressed_table = deep_compress::compress_sample_table(compression, &cumulative_i32)?;
-
-    // Pack and compress sample data
-    let packed_data = pack_deep_channels(samples, channels);
-    let decompressed_size = packed_data.len();
+    let mut buffers = DeepBlockBuffers::new();
+    compress_deep_scanline_block_with_buffers(
+        samples,
+        compression,
+        channels,
+        y_coordinate,
+        &mut buffers,
+    )
+}
 
-    let compressed_data = deep_compress::compress_sample_data(compression, &packed_data)?;
+/// Compress DeepSamples into a CompressedDeepScanLineBlock,
+/// reusing the buffers of previously compressed blocks.
+pub fn compress_deep_scanline_block_with_buffers(
+    samples: &DeepSamples,
+    compression: Compression,
+    channels: &ChannelList,
+    y_coordinate: i32,
+    buffers: &mut DeepBlockBuffers,
+) -> Result<CompressedDeepScanLineBlock> {
+    let (compressed_table, compressed_data, decompressed_size) =
+        buffers.compress(samples, compression, channels)?;
 
     Ok(CompressedDeepScanLineBlock {
         y_coordinate,
         decompressed_sample_data_size: decompressed_size,
-        compressed_pixel_offset_table: compressed_table.iter().map(|&b| b as i8).collect(),
+        compressed_pixel_offset_table: compressed_table,
         compressed_sample_data_le: compressed_data,
     })
 }
@@ -369,26 +609,247 @@ pub fn compress_deep_tile_block(
     channels: &ChannelList,
     coordinates: crate::block::chunk::TileCoordinates,
 ) -> Result<CompressedDeepTileBlock> {
-    // Get cumulative counts as i32
-    let cumulative_i32: Vec<i32> = samples.sample_offsets.iter().map(|&c| c as i32).collect();
-
-    // Compress sample count table
-    let compressed_table = deep_compress::compress_sample_table(compression, &cumulative_i32)?;
-
-    // Pack and compress sample data
-    let packed_data = pack_deep_channels(samples, channels);
-    let decompressed_size = packed_data.len();
+    let mut buffers = DeepBlockBuffers::new();
+    compress_deep_tile_block_with_buffers(samples, compression, channels, coordinates, &mut buffers)
+}
 
-    let compressed_data = deep_compress::compress_sample_data(compression, &packed_data)?;
+/// Compress DeepSamples into a CompressedDeepTileBlock,
+/// reusing the buffers of previously compressed blocks.
+pub fn compress_deep_tile_block_with_buffers(
+    samples: &DeepSamples,
+    compression: Compression,
+    channels: &ChannelList,
+    coordinates: crate::block::chunk::TileCoordinates,
+    buffers: &mut DeepBlockBuffers,
+) -> Result<CompressedDeepTileBlock> {
+    let (compressed_table, compressed_data, decompressed_size) =
+        buffers.compress(samples, compression, channels)?;
 
     Ok(CompressedDeepTileBlock {
         coordinates,
         decompressed_sample_data_size: decompressed_size,
-        compressed_pixel_offset_table: compressed_table.iter().map(|&b| b as i8).collect(),
+        compressed_pixel_offset_table: compressed_table,
         compressed_sample_data_le: compressed_data,
     })
 }
 
+// ============================================================================
+// Parallel Deep Block Compression
+// ============================================================================
+
+/// Compress deep scan line blocks to a chunk writer with multiple threads.
+///
+/// This is the deep data equivalent of
+/// [`ParallelBlocksCompressor`](super::writer::ParallelBlocksCompressor).
+/// Each block is packed and compressed on the thread pool,
+/// and the compressed chunks are reordered before writing,
+/// so the file contains the chunks in the same order as with sequential compression.
+///
+/// Unlike flat blocks, uncompressed deep blocks still benefit from multiple threads,
+/// because the channels of each block must be interleaved per pixel.
+#[cfg(feature = "rayon")]
+#[derive(Debug)]
+#[must_use]
+pub struct ParallelDeepBlocksCompressor<'w, W> {
+    shared_meta_data_ref: std::sync::Arc<crate::meta::MetaData>,
+    sorted_writer: super::writer::SortedBlocksWriter<'w, W>,
+
+    sender: std::sync::mpsc::Sender<CompressedDeepChunk>,
+    receiver: std::sync::mpsc::Receiver<CompressedDeepChunk>,
+    pool: rayon_core::ThreadPool,
+
+    /// Buffers of finished jobs, handed to the next jobs.
+    idle_buffers: Vec<DeepBlockBuffers>,
+
+    currently_compressing_count: usize,
+    written_chunk_count: usize, // used to check for last chunk
+    max_threads: usize,
+    next_incoming_chunk_index: usize, // used to remember original chunk order
+}
+
+/// The result of a compression job: the index of the chunk in the file,
+/// the index of the chunk in the header, the chunk, and the buffers of the job.
+#[cfg(feature = "rayon")]
+type CompressedDeepChunk = (
+    Result<(usize, usize, crate::block::chunk::Chunk)>,
+    DeepBlockBuffers,
+);
+
+#[cfg(feature = "rayon")]
+impl<'w, W> ParallelDeepBlocksCompressor<'w, W>
+where
+    W: 'w + super::writer::ChunksWriter,
+{
+    /// Create a new parallel deep block compressor.
+    /// Returns `None` if the thread pool cannot be created.
+    pub fn new(meta: &'w crate::meta::MetaData, chunks_writer: &'w mut W) -> Option<Self> {
+        Self::new_with_thread_pool(meta, chunks_writer, || {
+            rayon_core::ThreadPoolBuilder::new()
+                .thread_name(|index| format!("Deep Block Compressor #{}", index))
+                .build()
+        })
+    }
+
+    /// Create a parallel deep block compressor that keeps at most `max_blocks_in_flight` blocks
+    /// in memory, compressing them on as many threads, regardless of the number of processors.
+    /// Returns `None` if the thread pool cannot be created.
+    pub fn new_with_max_blocks_in_flight(
+        meta: &'w crate::meta::MetaData,
+        chunks_writer: &'w mut W,
+        max_blocks_in_flight: usize,
+    ) -> Option<Self> {
+        let max_blocks_in_flight = max_blocks_in_flight.max(1);
+
+        let mut compressor = Self::new_with_thread_pool(meta, chunks_writer, || {
+            rayon_core::ThreadPoolBuilder::new()
+                .num_threads(max_blocks_in_flight)
+                .thread_name(|index| format!("Deep Block Compressor #{}", index))
+                .build()
+        })?;
+
+        compressor.max_threads = max_blocks_in_flight;
+        Some(compressor)
+    }
+
+    /// Create with a custom thread pool builder.
+    pub fn new_with_thread_pool<CreatePool>(
+        meta: &'w crate::meta::MetaData,
+        chunks_writer: &'w mut W,
+        try_create_thread_pool: CreatePool,
+    ) -> Option<Self>
+    where
+        CreatePool:
+            FnOnce()
+                -> std::result::Result<rayon_core::ThreadPool, rayon_core::ThreadPoolBuildError>,
+    {
+        // in case thread pool creation fails (for example on WASM currently),
+        // the caller reverts to sequential compression
+        let pool = try_create_thread_pool().ok()?;
+
+        let max_threads = pool
+            .current_num_threads()
+            .max(1)
+            .min(chunks_writer.total_chunks_count())
+            + 2; // ca one block for each thread at all times
+
+        let (send, recv) = std::sync::mpsc::channel();
+
+        Some(Self {
+            shared_meta_data_ref: std::sync::Arc::new(meta.clone()),
+            sorted_writer: super::writer::SortedBlocksWriter::new(meta, chunks_writer),
+            idle_buffers: Vec::with_capacity(max_threads),
+            next_incoming_chunk_index: 0,
+            currently_compressing_count: 0,
+            written_chunk_count: 0,
+            sender: send,
+            receiver: recv,
+            max_threads,
+            pool,
+        })
+    }
+
+    /// This is where the compressed blocks are written to.
+    pub fn inner_chunks_writer(&self) -> &W {
+        self.sorted_writer.inner_chunks_writer()
+    }
+
+    // private, as may underflow counter in release mode
+    fn write_next_queued_chunk(&mut self) -> crate::error::UnitResult {
+        debug_assert!(
+            self.currently_compressing_count > 0,
+            "cannot wait for chunks as there are none left"
+        );
+
+        let (compressed_chunk, buffers) = self
+            .receiver
+            .recv()
+            .expect("cannot receive compressed block");
+
+        self.currently_compressing_count -= 1;
+        self.idle_buffers.push(buffers);
+
+        let (chunk_file_index, chunk_y_index, chunk) = compressed_chunk?;
+        self.sorted_writer
+            .write_or_stash_chunk(chunk_file_index, chunk_y_index, chunk)?;
+
+        self.written_chunk_count += 1;
+        Ok(())
+    }
+
+    /// Wait until all currently compressing chunks in the compressor have been written.
+    pub fn write_all_queued_chunks(&mut self) -> crate::error::UnitResult {
+        while self.currently_compressing_count > 0 {
+            self.write_next_queued_chunk()?;
+        }
+
+        Ok(())
+    }
+
+    /// Add a single deep scan line block to the compressor queue.
+    /// The index of the block must be in increasing line order.
+    /// When calling this function for the last block, this method waits until all the blocks have been written.
+    /// Waits for a block from the queue to be written, if the queue already has enough items.
+    pub fn add_block_to_compression_queue(
+        &mut self,
+        index_in_header_increasing_y: usize,
+        block: DeepUncompressedBlock,
+    ) -> crate::error::UnitResult {
+        // if pipe is full, block to wait for a slot to free up.
+        // chunks that wait for a previous chunk also occupy a slot,
+        // and they can only wait for a chunk that is still being compressed
+        while self.currently_compressing_count + self.sorted_writer.pending_chunk_count()
+            >= self.max_threads
+        {
+            self.write_next_queued_chunk()?;
+        }
+
+        let index_in_file = self.next_incoming_chunk_index;
+        let sender = self.sender.clone();
+        let meta = self.shared_meta_data_ref.clone();
+        let mut buffers = self.idle_buffers.pop().unwrap_or_default();
+
+        self.pool.spawn(move || {
+            let header = &meta.headers[block.layer_index];
+
+            let compressed = compress_deep_scanline_block_with_buffers(
+                &block.samples,
+                header.compression,
+                &header.channels,
+                block.y_coordinate,
+                &mut buffers,
+            );
+
+            let chunk = compressed.map(|compressed| {
+                let chunk = crate::block::chunk::Chunk {
+                    layer_index: block.layer_index,
+                    compressed_block: crate::block::chunk::CompressedBlock::DeepScanLine(
+                        compressed,
+                    ),
+                };
+
+                (index_in_file, index_in_header_increasing_y, chunk)
+            });
+
+            // by now, compressing could have failed in another thread.
+            // the error is then already handled, so we simply
+            // don't care whether the block is received
+            let _ = sender.send((chunk, buffers));
+        });
+
+        self.currently_compressing_count += 1;
+        self.next_incoming_chunk_index += 1;
+
+        // if this is the last chunk, wait for all chunks to complete before returning
+        if self.written_chunk_count + self.currently_compressing_count
+            == self.inner_chunks_writer().total_chunks_count()
+        {
+            self.write_all_queued_chunks()?;
+        }
+
+        Ok(())
+    }
+}
+
 // ============================================================================
 // Parallel Deep Block Decompression
 // ============================================================================
@@ -402,9 +863,12 @@ pub struct DeepUncompressedBlock {
     /// Layer index this block belongs to.
     pub layer_index: usize,
 
-    /// Y coordinate for scanline blocks, or tile coordinates for tiled.
+    /// Y coordinate for scanline blocks, or the vertical tile index for tiled blocks.
     pub y_coordinate: i32,
 
+    /// The tile index and resolution level of tiled blocks, `None` for scan line blocks.
+    pub tile: Option<TileCoordinates>,
+
     /// The decompressed deep samples.
     pub samples: DeepSamples,
 }
@@ -490,6 +954,9 @@ pub struct ParallelDeepBlockDecompressor<R: super::reader::ChunksReader> {
     shared_meta_data_ref: std::sync::Arc<crate::meta::MetaData>,
     pedantic: bool,
     pool: rayon_core::ThreadPool,
+    buffers: std::sync::Arc<crate::block::pool::BufferPool>,
+    selected_channels: Option<std::sync::Arc<Vec<Vec<usize>>>>,
+    budget: DeepBudget,
 }
 
 #[cfg(feature = "rayon")]
@@ -547,29 +1014,73 @@ impl<R: super::reader::ChunksReader> ParallelDeepBlockDecompressor<R> {
             pedantic,
             max_threads,
             pool,
+            buffers: std::sync::Arc::new(crate::block::pool::BufferPool::new(max_threads * 4)),
+            selected_channels: None,
+            budget: DeepBudget::new(ReadLimits::UNLIMITED),
         })
     }
 
+    /// Only unpack the channels with the specified names.
+    /// The blocks then contain only these channels, in the order of the channel list.
+    pub fn select_channels(mut self, names: &[Text]) -> Self {
+        self.selected_channels = Some(channel_indices_per_layer(
+            self.remaining_chunks.meta_data(),
+            names,
+        ));
+
+        self
+    }
+
+    /// Fail with `Error::NotSupported` as soon as the blocks exceed the limits.
+    /// The decompressed size of each block is checked before a job is spawned for it.
+    pub fn with_limits(mut self, limits: ReadLimits) -> Self {
+        self.budget = DeepBudget::new(limits);
+        self
+    }
+
     /// Decompress the next block, spawning parallel jobs as needed.
     pub fn decompress_next_block(&mut self) -> Option<Result<DeepUncompressedBlock>> {
         // Fill thread pool with jobs
         while self.currently_decompressing_count < self.max_threads {
-            let chunk = self.remaining_chunks.next();
+            let buffers = self.buffers.clone();
+            let chunk = buffers.install(|| self.remaining_chunks.next());
             if let Some(chunk_result) = chunk {
                 let chunk = match chunk_result {
                     Ok(c) => c,
                     Err(e) => return Some(Err(e)),
                 };
 
+                if let Err(error) = self.budget.reserve_block(&chunk.compressed_block) {
+                    return Some(Err(error));
+                }
+
                 let sender = self.sender.clone();
+                let limits = self.budget.limits;
                 let meta = self.shared_meta_data_ref.clone();
+                let buffers = self.buffers.clone();
                 let pedantic = self.pedantic;
                 let layer_index = chunk.layer_index;
+                let selected_channels = self.selected_channels.clone();
 
                 self.currently_decompressing_count += 1;
 
                 self.pool.spawn(move || {
-                    let result = decompress_deep_chunk(&chunk.compressed_block, &meta, layer_index, pedantic);
+                    let result = buffers.install(|| {
+                        let result = decompress_deep_chunk(
+                            &chunk.compressed_block,
+                            &meta,
+                            layer_index,
+                            selected_channels
+                                .as_ref()
+                                .map(|selected| selected[layer_index].as_slice()),
+                            pedantic,
+                            &limits,
+                        );
+
+                        recycle_compressed_buffers(chunk.compressed_block);
+                        result
+                    });
+
                     let _ = sender.send(result);
                 });
             } else {
@@ -584,7 +1095,10 @@ impl<R: super::reader::ChunksReader> ParallelDeepBlockDecompressor<R> {
                 .expect("all decompressing senders hung up");
 
             self.currently_decompressing_count -= 1;
-            Some(next)
+            Some(next.and_then(|block| {
+                self.budget.add_samples(block.samples.total_samples())?;
+                Ok(block)
+            }))
         } else {
             None
         }
@@ -613,14 +1127,34 @@ impl<R: super::reader::ChunksReader> Iterator for ParallelDeepBlockDecompressor<
     }
 }
 
+/// Return the compressed bytes of a deep block to the buffer pool of the current thread.
+#[cfg(feature = "rayon")]
+fn recycle_compressed_buffers(block: crate::block::chunk::CompressedBlock) {
+    use crate::block::chunk::CompressedBlock;
+
+    match block {
+        CompressedBlock::DeepScanLine(block) => {
+            recycle_buffer(block.compressed_pixel_offset_table);
+            recycle_buffer(block.compressed_sample_data_le);
+        }
+        CompressedBlock::DeepTile(block) => {
+            recycle_buffer(block.compressed_pixel_offset_table);
+            recycle_buffer(block.compressed_sample_data_le);
+        }
+        _ => {}
+    }
+}
+
 /// Decompress a single compressed chunk into a `DeepUncompressedBlock`.
 ///
 /// Helper function used by both sequential and parallel decompression.
-fn decompress_deep_chunk(
+pub(crate) fn decompress_deep_chunk(
     compressed: &crate::block::chunk::CompressedBlock,
     meta: &crate::meta::MetaData,
     layer_index: usize,
+    selected_channels: Option<&[usize]>,
     pedantic: bool,
+    limits: &ReadLimits,
 ) -> Result<DeepUncompressedBlock> {
     use crate::block::chunk::CompressedBlock;
 
@@ -628,40 +1162,42 @@ fn decompress_deep_chunk(
 
     match compressed {
         CompressedBlock::DeepScanLine(ref block) => {
-            let samples = decompress_deep_scanline_block(
+            let samples = decompress_deep_scanline_block_with_channels(
                 block,
                 header.compression,
                 &header.channels,
+                selected_channels,
                 header.layer_size.width(),
-                header.compression.scan_lines_per_block(),
+                deep_scan_line_block_height(header, block.y_coordinate)?,
                 pedantic,
+                limits,
             )?;
 
             Ok(DeepUncompressedBlock {
                 layer_index,
                 y_coordinate: block.y_coordinate,
+                tile: None,
                 samples,
             })
         }
         CompressedBlock::DeepTile(ref block) => {
-            // For tiles, tile size comes from header
-            let tile_desc = match header.blocks {
-                crate::meta::BlockDescription::Tiles(ref tiles) => tiles,
-                _ => return Err(Error::invalid("deep tile block in non-tiled layer")),
-            };
+            let tile_size = deep_tile_size(header, block.coordinates)?;
 
-            let samples = decompress_deep_tile_block(
+            let samples = decompress_deep_tile_block_with_channels(
                 block,
                 header.compression,
                 &header.channels,
-                tile_desc.tile_size.width(),
-                tile_desc.tile_size.height(),
+                selected_channels,
+                tile_size.width(),
+                tile_size.height(),
                 pedantic,
+                limits,
             )?;
 
             Ok(DeepUncompressedBlock {
                 layer_index,
                 y_coordinate: block.coordinates.tile_index.y() as i32,
+                tile: Some(block.coordinates),
                 samples,
             })
         }
@@ -669,17 +1205,60 @@ fn decompress_deep_chunk(
     }
 }
 
+/// The number of lines in the deep scan line block.
+/// The last block of a layer only contains the lines inside the layer, and so does its sample count table.
+pub(crate) fn deep_scan_line_block_height(header: &Header, y_coordinate: i32) -> Result<usize> {
+    let block = header.get_scan_line_block_tile_coordinates(y_coordinate)?;
+    Ok(header
+        .get_absolute_block_pixel_coordinates(block)?
+        .size
+        .height())
+}
+
+/// The number of pixels in the deep tile.
+/// Tiles at the right and bottom edge of a resolution level only contain the pixels
+/// inside the level, and so does their sample count table.
+pub(crate) fn deep_tile_size(header: &Header, coordinates: TileCoordinates) -> Result<Vec2<usize>> {
+    match header.blocks {
+        crate::meta::BlockDescription::Tiles(_) => Ok(header
+            .get_absolute_block_pixel_coordinates(coordinates)?
+            .size),
+        _ => Err(Error::invalid("deep tile block in non-tiled layer")),
+    }
+}
+
 /// Sequential deep block decompressor (fallback when rayon is disabled or unhelpful).
 #[derive(Debug)]
 pub struct SequentialDeepBlockDecompressor<R: super::reader::ChunksReader> {
     chunks: R,
     pedantic: bool,
+    selected_channels: Option<std::sync::Arc<Vec<Vec<usize>>>>,
+    budget: DeepBudget,
 }
 
 impl<R: super::reader::ChunksReader> SequentialDeepBlockDecompressor<R> {
     /// Create a new sequential decompressor.
     pub fn new(chunks: R, pedantic: bool) -> Self {
-        Self { chunks, pedantic }
+        Self {
+            chunks,
+            pedantic,
+            selected_channels: None,
+            budget: DeepBudget::new(ReadLimits::UNLIMITED),
+        }
+    }
+
+    /// Only unpack the channels with the specified names.
+    /// The blocks then contain only these channels, in the order of the channel list.
+    pub fn select_channels(mut self, names: &[Text]) -> Self {
+        self.selected_channels = Some(channel_indices_per_layer(self.chunks.meta_data(), names));
+        self
+    }
+
+    /// Fail with `Error::NotSupported` as soon as the blocks exceed the limits.
+    /// The decompressed size of each block is checked before it is decompressed.
+    pub fn with_limits(mut self, limits: ReadLimits) -> Self {
+        self.budget = DeepBudget::new(limits);
+        self
     }
 
     /// Access the metadata.
@@ -700,12 +1279,27 @@ impl<R: super::reader::ChunksReader> Iterator for SequentialDeepBlockDecompresso
             Err(e) => return Some(Err(e)),
         };
 
-        Some(decompress_deep_chunk(
-            &chunk.compressed_block,
-            self.chunks.meta_data(),
-            chunk.layer_index,
-            self.pedantic,
-        ))
+        let block = self
+            .budget
+            .reserve_block(&chunk.compressed_block)
+            .and_then(|()| {
+                decompress_deep_chunk(
+                    &chunk.compressed_block,
+                    self.chunks.meta_data(),
+                    chunk.layer_index,
+                    self.selected_channels
+                        .as_ref()
+                        .map(|selected| selected[chunk.layer_index].as_slice()),
+                    self.pedantic,
+                    &self.budget.limits,
+                )
+            })
+            .and_then(|block| {
+                self.budget.add_samples(block.samples.total_samples())?;
+                Ok(block)
+            });
+
+        Some(block)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -836,8 +1430,210 @@ mod test {
         // Unpack into new samples
         let mut recovered = DeepSamples::new(2, 1);
         recovered.set_cumulative_counts(vec![2, 5]).unwrap();
-        unpack_deep_channels(&packed, &mut recovered, &channels).unwrap();
+        unpack_deep_channels(&packed, &mut recovered, &channels, None).unwrap();
 
         assert_eq!(samples.channels, recovered.channels);
+
+        // all channels are f32, which visits each sample only once
+        for selection in [&[0, 2][..], &[1], &[]] {
+            let mut selected = DeepSamples::new(2, 1);
+            selected.set_cumulative_counts(vec![2, 5]).unwrap();
+            unpack_deep_channels(&packed, &mut selected, &channels, Some(selection)).unwrap();
+
+            let expected: Vec<DeepChannelData> = selection
+                .iter()
+                .map(|&channel| samples.channels[channel].clone())
+                .collect();
+
+            assert_eq!(selected.channels, expected);
+        }
+    }
+
+    #[test]
+    fn compress_with_reused_buffers() {
+        let channels = make_test_channels();
+
+        let mut samples = DeepSamples::new(4, 1);
+        samples.set_cumulative_counts(vec![1, 1, 4, 6]).unwrap();
+        samples.allocate_channels(&channels);
+
+        let mut buffers = DeepBlockBuffers::new();
+
+        for compression in [
+            Compression::Uncompressed,
+            Compression::RLE,
+            Compression::ZIP1,
+            Compression::ZIP16,
+        ] {
+            let expected =
+                compress_deep_scanline_block(&samples, compression, &channels, 3).unwrap();
+
+            // the second block reuses the byte vectors of the first block
+            for _ in 0..2 {
+                let block = compress_deep_scanline_block_with_buffers(
+                    &samples,
+                    compression,
+                    &channels,
+                    3,
+                    &mut buffers,
+                )
+                .unwrap();
+
+                assert_eq!(
+                    block.compressed_pixel_offset_table,
+                    expected.compressed_pixel_offset_table
+                );
+                assert_eq!(
+                    block.compressed_sample_data_le,
+                    expected.compressed_sample_data_le
+                );
+                assert_eq!(
+                    block.decompressed_sample_data_size,
+                    expected.decompressed_sample_data_size
+                );
+
+                buffers.recycle_scan_line_block(block);
+            }
+        }
+    }
+
+    #[test]
+    fn unpack_mixed_sample_types() {
+        let channels = ChannelList::new(smallvec![
+            ChannelDescription::new("A", SampleType::F16, true),
+            ChannelDescription::new("id", SampleType::U32, false),
+            ChannelDescription::new("Z", SampleType::F32, false),
+        ]);
+
+        let mut packed = Vec::new();
+        for sample in 0..3_u32 {
+            packed.extend_from_slice(&f16::from_f32(sample as f32 * 0.5).to_le_bytes());
+            packed.extend_from_slice(&(sample + 7).to_le_bytes());
+            packed.extend_from_slice(&(sample as f32 * 10.0).to_le_bytes());
+        }
+
+        let mut samples = DeepSamples::new(2, 1);
+        samples.set_cumulative_counts(vec![1, 3]).unwrap();
+        unpack_deep_channels(&packed, &mut samples, &channels, None).unwrap();
+
+        assert_eq!(
+            samples.channels,
+            vec![
+                DeepChannelData::F16(vec![
+                    f16::from_f32(0.0),
+                    f16::from_f32(0.5),
+                    f16::from_f32(1.0)
+                ]),
+                DeepChannelData::U32(vec![7, 8, 9]),
+                DeepChannelData::F32(vec![0.0, 10.0, 20.0]),
+            ]
+        );
+
+        assert_eq!(pack_deep_channels(&samples, &channels), packed);
+
+        let mut selected = DeepSamples::new(2, 1);
+        selected.set_cumulative_counts(vec![1, 3]).unwrap();
+        unpack_deep_channels(&packed, &mut selected, &channels, Some(&[0, 2])).unwrap();
+
+        assert_eq!(
+            selected.channels,
+            vec![samples.channels[0].clone(), samples.channels[2].clone()]
+        );
+    }
+
+    #[test]
+    fn roundtrip_all_deep_compressions() {
+        let channels = ChannelList::new(smallvec![
+            ChannelDescription::new("A", SampleType::F16, true),
+            ChannelDescription::new("id", SampleType::U32, false),
+            ChannelDescription::new("Z", SampleType::F32, false),
+        ]);
+
+        // repeating counts and values, so that each compression actually compresses
+        let counts: Vec<u32> = (0..32_u32).map(|pixel| pixel % 4).collect();
+        let mut samples = DeepSamples::new(8, 4);
+        samples
+            .set_cumulative_counts(
+                counts
+                    .iter()
+                    .scan(0, |sum, &count| {
+                        *sum += count;
+                        Some(*sum)
+                    })
+                    .collect(),
+            )
+            .unwrap();
+
+        let sample_count = samples.total_samples();
+        samples.channels = vec![
+            DeepChannelData::F16(
+                (0..sample_count)
+                    .map(|i| f16::from_f32(0.25 * (i % 3) as f32))
+                    .collect(),
+            ),
+            DeepChannelData::U32((0..sample_count as u32).map(|i| i / 5).collect()),
+            DeepChannelData::F32((0..sample_count).map(|i| i as f32 * 0.5).collect()),
+        ];
+
+        for compression in [
+            Compression::Uncompressed,
+            Compression::RLE,
+            Compression::ZIP1,
+            Compression::ZIP16,
+        ] {
+            let block = compress_deep_scanline_block(&samples, compression, &channels, 0).unwrap();
+            let recovered =
+                decompress_deep_scanline_block(&block, compression, &channels, 8, 4, true).unwrap();
+
+            assert_eq!(
+                recovered.sample_offsets, samples.sample_offsets,
+                "{compression}"
+            );
+            assert_eq!(recovered.channels, samples.channels, "{compression}");
+
+            let coordinates = TileCoordinates {
+                tile_index: Vec2(1, 2),
+                level_index: Vec2(0, 0),
+            };
+
+            let block =
+                compress_deep_tile_block(&samples, compression, &channels, coordinates).unwrap();
+            let recovered =
+                decompress_deep_tile_block(&block, compression, &channels, 8, 4, true).unwrap();
+
+            assert_eq!(block.coordinates, coordinates);
+            assert_eq!(
+                recovered.sample_offsets, samples.sample_offsets,
+                "{compression}"
+            );
+            assert_eq!(recovered.channels, samples.channels, "{compression}");
+        }
+    }
+
+    #[test]
+    fn reject_compressions_not_permitted_for_deep_data() {
+        let channels = make_test_channels();
+
+        let mut samples = DeepSamples::new(2, 1);
+        samples.set_cumulative_counts(vec![1, 3]).unwrap();
+        samples.allocate_channels(&channels);
+
+        for compression in [
+            Compression::PXR24,
+            Compression::PIZ,
+            Compression::B44,
+            Compression::B44A,
+            Compression::HTJ2K32,
+        ] {
+            assert!(!compression.supports_deep_data());
+
+            let error = compress_deep_scanline_block(&samples, compression, &channels, 0)
+                .expect_err("compression is not permitted for deep data");
+
+            match error {
+                Error::Invalid(message) => assert!(message.contains("deep data"), "{}", message),
+                other => panic!("unexpected error {:?}", other),
+            }
+        }
     }
 }
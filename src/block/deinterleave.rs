@@ -0,0 +1,192 @@
+//! Deinterleave sample bytes that store all channels of a sample next to each other, as in deep blocks,
+//! into one vector per channel.
+//!
+//! Samples that consist of up to eight channels of the same type
+//! are copied in groups of [`GROUP_SIZE`] samples. The number of channels is a constant
+//! in these loops, so that the compiler can unroll them. Other samples are copied one value at a time.
+
+use crate::image::deep::DeepSample;
+
+/// The number of samples that are copied at once when all channels have the same type.
+pub const GROUP_SIZE: usize = 8;
+
+/// Copy the little-endian values of one channel out of the interleaved sample bytes.
+/// Each sample occupies `stride` bytes, and the value of this channel starts at `offset`.
+/// Reads one value for each element of `to`. Panics if there are not enough bytes.
+pub fn deinterleave<T: DeepSample>(bytes: &[u8], stride: usize, offset: usize, to: &mut [T]) {
+    let size = std::mem::size_of::<T>();
+    assert!(
+        offset + size <= stride,
+        "channel offset outside of the sample"
+    );
+    assert!(bytes.len() >= to.len() * stride, "not enough sample bytes");
+
+    for (value, sample) in to.iter_mut().zip(bytes.chunks_exact(stride)) {
+        *value = T::from_le_slice(&sample[offset..offset + size]);
+    }
+}
+
+/// Copy the little-endian values of multiple channels of the same type
+/// out of the interleaved sample bytes, visiting each sample only once.
+/// The value of each channel starts at the corresponding offset within each sample.
+/// Reads one value for each element of the output slices, which must have the same length.
+/// Panics if there are not enough bytes.
+pub fn deinterleave_channels<T: DeepSample>(
+    bytes: &[u8],
+    stride: usize,
+    offsets: &[usize],
+    to: &mut [&mut [T]],
+) {
+    let size = std::mem::size_of::<T>();
+    assert_eq!(offsets.len(), to.len(), "one offset per channel required");
+    assert!(
+        offsets.iter().all(|&offset| offset + size <= stride),
+        "channel offset outside of the sample"
+    );
+
+    let sample_count = to.first().map_or(0, |values| values.len());
+    assert!(
+        to.iter().all(|values| values.len() == sample_count),
+        "channels must have the same length"
+    );
+    assert!(
+        bytes.len() >= sample_count * stride,
+        "not enough sample bytes"
+    );
+
+    // whole groups of samples are deinterleaved first
+    let start = deinterleave_dense(bytes, stride, offsets, to);
+
+    for (index, sample) in bytes[start * stride..sample_count * stride]
+        .chunks_exact(stride)
+        .enumerate()
+    {
+        for (values, &offset) in to.iter_mut().zip(offsets) {
+            values[start + index] = T::from_le_slice(&sample[offset..offset + size]);
+        }
+    }
+}
+
+/// Deinterleave whole groups of `GROUP_SIZE` samples, if the channels fill the whole sample.
+/// Returns the number of samples that have been deinterleaved.
+fn deinterleave_dense<T: DeepSample>(
+    bytes: &[u8],
+    stride: usize,
+    offsets: &[usize],
+    to: &mut [&mut [T]],
+) -> usize {
+    let size = std::mem::size_of::<T>();
+
+    let is_dense = stride == offsets.len() * size
+        && offsets
+            .iter()
+            .enumerate()
+            .all(|(index, &offset)| offset == index * size);
+
+    if !is_dense {
+        return 0;
+    }
+
+    // the number of channels is a constant in the specialized loops
+    match offsets.len() {
+        2 => deinterleave_dense_groups::<T, 2>(bytes, to),
+        3 => deinterleave_dense_groups::<T, 3>(bytes, to),
+        4 => deinterleave_dense_groups::<T, 4>(bytes, to),
+        5 => deinterleave_dense_groups::<T, 5>(bytes, to),
+        6 => deinterleave_dense_groups::<T, 6>(bytes, to),
+        7 => deinterleave_dense_groups::<T, 7>(bytes, to),
+        8 => deinterleave_dense_groups::<T, 8>(bytes, to),
+        _ => 0,
+    }
+}
+
+/// Deinterleave whole groups of `GROUP_SIZE` samples that consist of exactly `CHANNELS` values.
+/// Returns the number of samples that have been deinterleaved.
+fn deinterleave_dense_groups<T: DeepSample, const CHANNELS: usize>(
+    bytes: &[u8],
+    to: &mut [&mut [T]],
+) -> usize {
+    let size = std::mem::size_of::<T>();
+    let group_count = to[0].len() / GROUP_SIZE;
+    let group_size = CHANNELS * size * GROUP_SIZE;
+
+    for (group_index, group) in bytes[..group_count * group_size]
+        .chunks_exact(group_size)
+        .enumerate()
+    {
+        let mut group_values = [[T::default(); GROUP_SIZE]; CHANNELS];
+
+        for (index, sample) in group.chunks_exact(CHANNELS * size).enumerate() {
+            for (channel, value) in sample.chunks_exact(size).enumerate() {
+                group_values[channel][index] = T::from_le_slice(value);
+            }
+        }
+
+        let start = group_index * GROUP_SIZE;
+        for (values, group_values) in to.iter_mut().zip(&group_values) {
+            values[start..start + GROUP_SIZE].copy_from_slice(group_values);
+        }
+    }
+
+    group_count * GROUP_SIZE
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use half::f16;
+
+    /// Interleaved samples with an `f16`, a `u32`, and an `f32` value.
+    fn mixed_bytes(sample_count: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for sample in 0..sample_count {
+            bytes.extend_from_slice(&f16::from_f32(sample as f32).to_le_bytes());
+            bytes.extend_from_slice(&(sample as u32 * 3).to_le_bytes());
+            bytes.extend_from_slice(&(sample as f32 * 0.5).to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn deinterleave_single_channels() {
+        // whole groups, and a remainder
+        for sample_count in [0, 3, GROUP_SIZE, 3 * GROUP_SIZE + 5] {
+            let bytes = mixed_bytes(sample_count);
+
+            let mut halves = vec![f16::ZERO; sample_count];
+            deinterleave(&bytes, 10, 0, &mut halves);
+
+            let mut ids = vec![0_u32; sample_count];
+            deinterleave(&bytes, 10, 2, &mut ids);
+
+            let mut floats = vec![0_f32; sample_count];
+            deinterleave(&bytes, 10, 6, &mut floats);
+
+            for sample in 0..sample_count {
+                assert_eq!(halves[sample], f16::from_f32(sample as f32));
+                assert_eq!(ids[sample], sample as u32 * 3);
+                assert_eq!(floats[sample], sample as f32 * 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn deinterleave_uniform_channels() {
+        for sample_count in [0, 5, 2 * GROUP_SIZE, 4 * GROUP_SIZE + 7] {
+            let bytes: Vec<u8> = (0..sample_count * 3)
+                .flat_map(|value| (value as f32).to_le_bytes())
+                .collect();
+
+            let mut red = vec![0_f32; sample_count];
+            let mut blue = vec![0_f32; sample_count];
+            deinterleave_channels(&bytes, 12, &[0, 8], &mut [&mut red, &mut blue]);
+
+            for sample in 0..sample_count {
+                assert_eq!(red[sample], (sample * 3) as f32);
+                assert_eq!(blue[sample], (sample * 3 + 2) as f32);
+            }
+        }
+    }
+}
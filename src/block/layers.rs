@@ -0,0 +1,628 @@
+//! Extract, rename, and merge the layers of exr files, without decoding the pixels.
+//!
+//! The compressed chunks of each layer are copied to the new file as they are,
+//! so the compression and the tiling of each layer stay the same.
+//! Only if renaming channels changes their alphabetical order,
+//! the blocks of that layer are decompressed, their channels are reordered,
+//! and the blocks are compressed again.
+//!
+//! All layers of a file must have the same image attributes, such as the display window.
+//! The new file uses the image attributes of the first layer for all layers.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+use crate::block::chunk::{Chunk, CompressedBlock, TileCoordinates};
+use crate::block::deep::{
+    compress_deep_scanline_block, compress_deep_tile_block, decompress_deep_chunk,
+};
+use crate::block::limits::ReadLimits;
+use crate::block::reader::Reader;
+use crate::block::writer::{write_chunks_with, ChunksWriter};
+use crate::block::{BlockIndex, UncompressedBlock};
+use crate::error::{Error, Result, UnitResult};
+use crate::meta::attribute::{ChannelDescription, ChannelList, Text};
+use crate::meta::header::Header;
+use crate::meta::{Headers, MetaData};
+use smallvec::SmallVec;
+
+/// Selects a layer of one of the source files, and specifies its names in the new file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerCopy {
+    /// The index of the file in the list of source files.
+    pub source_index: usize,
+
+    /// The index of the layer in the source file.
+    pub layer_index: usize,
+
+    /// The name of the layer in the new file. Keeps the original name if `None`.
+    pub layer_name: Option<Text>,
+
+    /// Pairs of original and new channel names.
+    /// Channels that are not listed keep their original name.
+    pub channel_names: Vec<(Text, Text)>,
+}
+
+impl LayerCopy {
+    /// Copy a layer of a source file, keeping all names.
+    pub fn new(source_index: usize, layer_index: usize) -> Self {
+        Self {
+            source_index,
+            layer_index,
+            layer_name: None,
+            channel_names: Vec::new(),
+        }
+    }
+
+    /// Rename the layer in the new file.
+    pub fn with_layer_name(self, layer_name: impl Into<Text>) -> Self {
+        Self {
+            layer_name: Some(layer_name.into()),
+            ..self
+        }
+    }
+
+    /// Rename a channel of the layer in the new file.
+    pub fn with_channel_name(mut self, original: impl Into<Text>, new: impl Into<Text>) -> Self {
+        self.channel_names.push((original.into(), new.into()));
+        self
+    }
+}
+
+/// Write a new file that contains only a single layer of the source file.
+pub fn extract_layer(
+    source: impl AsRef<Path>,
+    layer_index: usize,
+    destination: impl AsRef<Path>,
+) -> UnitResult {
+    copy_layers_between_files(&[source], &[LayerCopy::new(0, layer_index)], destination)
+}
+
+/// Write a new file that contains all layers of all source files, in order.
+/// Layers without a name are named after the file they come from,
+/// for example `diffuse` for the layer of `diffuse.exr`.
+pub fn merge_files(sources: &[impl AsRef<Path>], destination: impl AsRef<Path>) -> UnitResult {
+    let mut layers = Vec::new();
+
+    for (source_index, source) in sources.iter().enumerate() {
+        let read = BufReader::new(File::open(source)?);
+        let meta_data = MetaData::read_from_buffered(read, false)?;
+
+        for (layer_index, header) in meta_data.headers.iter().enumerate() {
+            let mut layer = LayerCopy::new(source_index, layer_index);
+
+            if header.own_attributes.layer_name.is_none() {
+                let file_name = source
+                    .as_ref()
+                    .file_stem()
+                    .and_then(|name| name.to_str())
+                    .and_then(Text::new_or_none)
+                    .ok_or(Error::invalid("file name cannot be used as layer name"))?;
+
+                layer.layer_name = Some(file_name);
+            }
+
+            layers.push(layer);
+        }
+    }
+
+    copy_layers_between_files(sources, &layers, destination)
+}
+
+/// Write a new file that contains the selected layers of the source files, in order.
+pub fn copy_layers_between_files(
+    sources: &[impl AsRef<Path>],
+    layers: &[LayerCopy],
+    destination: impl AsRef<Path>,
+) -> UnitResult {
+    let sources = sources
+        .iter()
+        .map(|path| Ok(BufReader::new(File::open(path)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let write = BufWriter::new(File::create(destination)?);
+    copy_layers(sources, layers, write, true)
+}
+
+/// Write the selected layers of the source byte sources to a new file, in order.
+/// The same layer may be selected multiple times, with different names.
+/// The readers and the writer are assumed to be buffered.
+/// Set `pedantic` to check that the sources are valid,
+/// and that the file can be opened by other exr software.
+pub fn copy_layers<R: Read + Seek, W: Write + Seek>(
+    sources: Vec<R>,
+    layers: &[LayerCopy],
+    write: W,
+    pedantic: bool,
+) -> UnitResult {
+    let readers = sources
+        .into_iter()
+        .map(|read| Reader::read_from_buffered(read, pedantic))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut headers = Headers::with_capacity(layers.len());
+    let mut channel_orders = Vec::with_capacity(layers.len());
+
+    for layer in layers {
+        let header = readers
+            .get(layer.source_index)
+            .ok_or(Error::invalid("source file index"))?
+            .headers()
+            .get(layer.layer_index)
+            .ok_or(Error::invalid("layer index"))?;
+
+        let (header, channel_order) = renamed_header(header, layer)?;
+        headers.push(header);
+        channel_orders.push(channel_order);
+    }
+
+    if let Some(shared_attributes) = headers
+        .first()
+        .map(|header| header.shared_attributes.clone())
+    {
+        for header in &mut headers {
+            header.shared_attributes = shared_attributes.clone();
+        }
+    }
+
+    write_chunks_with(write, headers, pedantic, |meta_data, chunk_writer| {
+        for (source_index, reader) in readers.into_iter().enumerate() {
+            let is_selected = |layer_index: usize| {
+                layers.iter().any(|layer| {
+                    layer.source_index == source_index && layer.layer_index == layer_index
+                })
+            };
+
+            if !(0..reader.headers().len()).any(is_selected) {
+                continue;
+            }
+
+            let source_meta_data = reader.meta_data().clone();
+
+            // the index of each block in the offset table of its layer
+            let chunk_indices: Vec<HashMap<TileCoordinates, usize>> = source_meta_data
+                .headers
+                .iter()
+                .enumerate()
+                .map(|(layer_index, header)| {
+                    if !is_selected(layer_index) {
+                        return HashMap::new();
+                    }
+
+                    header
+                        .blocks_increasing_y_order()
+                        .enumerate()
+                        .map(|(chunk_index, tile)| (tile.location, chunk_index))
+                        .collect()
+                })
+                .collect();
+
+            let chunks = reader.filter_chunks(pedantic, |_, _, block| is_selected(block.layer))?;
+
+            for chunk in chunks {
+                let chunk = chunk?;
+                let source_header = &source_meta_data.headers[chunk.layer_index];
+
+                let coordinates = source_header.get_block_data_indices(&chunk.compressed_block)?;
+                let chunk_index = *chunk_indices[chunk.layer_index]
+                    .get(&coordinates)
+                    .ok_or(Error::invalid("chunk coordinates"))?;
+
+                let output_layers: SmallVec<[usize; 2]> = layers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, layer)| {
+                        layer.source_index == source_index && layer.layer_index == chunk.layer_index
+                    })
+                    .map(|(output_layer, _)| output_layer)
+                    .collect();
+
+                // only clone the chunk if the layer is copied multiple times
+                if let Some((&last, others)) = output_layers.split_last() {
+                    for &output_layer in others {
+                        let copy = copy_chunk(
+                            chunk.clone(),
+                            output_layer,
+                            &source_meta_data,
+                            &meta_data,
+                            channel_orders[output_layer].as_deref(),
+                            pedantic,
+                        )?;
+
+                        chunk_writer.write_chunk(chunk_index, copy)?;
+                    }
+
+                    let copy = copy_chunk(
+                        chunk,
+                        last,
+                        &source_meta_data,
+                        &meta_data,
+                        channel_orders[last].as_deref(),
+                        pedantic,
+                    )?;
+
+                    chunk_writer.write_chunk(chunk_index, copy)?;
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Apply the new names to a copy of the header.
+/// Also returns the index of the original channel for each new channel,
+/// if renaming has changed the alphabetical order of the channels.
+fn renamed_header(source: &Header, layer: &LayerCopy) -> Result<(Header, Option<Vec<usize>>)> {
+    let mut header = source.clone();
+
+    if let Some(layer_name) = &layer.layer_name {
+        header.own_attributes.layer_name = Some(layer_name.clone());
+    }
+
+    for (original, _) in &layer.channel_names {
+        if !source
+            .channels
+            .list
+            .iter()
+            .any(|channel| &channel.name == original)
+        {
+            return Err(Error::invalid(format!("no channel named `{}`", original)));
+        }
+    }
+
+    let mut channels: Vec<(usize, ChannelDescription)> = source
+        .channels
+        .list
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(index, mut channel)| {
+            let new_name = layer
+                .channel_names
+                .iter()
+                .find(|(original, _)| original == &channel.name);
+
+            if let Some((_, new_name)) = new_name {
+                channel.name = new_name.clone();
+            }
+
+            (index, channel)
+        })
+        .collect();
+
+    channels.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+
+    let channel_order: Vec<usize> = channels.iter().map(|&(index, _)| index).collect();
+    let is_reordered = channel_order
+        .iter()
+        .enumerate()
+        .any(|(new_index, &original_index)| new_index != original_index);
+
+    header.channels = ChannelList::new(channels.into_iter().map(|(_, channel)| channel).collect());
+    Ok((
+        header,
+        if is_reordered {
+            Some(channel_order)
+        } else {
+            None
+        },
+    ))
+}
+
+/// Move the chunk to another layer, reordering its channels if required.
+fn copy_chunk(
+    chunk: Chunk,
+    output_layer: usize,
+    source: &MetaData,
+    output: &MetaData,
+    channel_order: Option<&[usize]>,
+    pedantic: bool,
+) -> Result<Chunk> {
+    let channel_order = match channel_order {
+        None => {
+            return Ok(Chunk {
+                layer_index: output_layer,
+                ..chunk
+            })
+        }
+
+        Some(channel_order) => channel_order,
+    };
+
+    let header = &output.headers[output_layer];
+
+    match chunk.compressed_block {
+        CompressedBlock::ScanLine(_) | CompressedBlock::Tile(_) => {
+            let block = UncompressedBlock::decompress_chunk(chunk, source, pedantic)?;
+            let source_channels = &source.headers[block.index.layer].channels;
+
+            // the lines of a block contain each channel once for each row
+            let lines: Vec<&[u8]> = block
+                .lines(source_channels)
+                .map(|line| line.value)
+                .collect();
+
+            let index = BlockIndex {
+                layer: output_layer,
+                ..block.index
+            };
+
+            let reordered = UncompressedBlock::from_lines(&header.channels, index, |line| {
+                let row = line.location.position.y() - index.pixel_position.y();
+                let source_line =
+                    lines[row * channel_order.len() + channel_order[line.location.channel]];
+                line.value.copy_from_slice(source_line);
+            });
+
+            reordered.compress_to_chunk(&output.headers)
+        }
+
+        compressed_block => {
+            let mut block = decompress_deep_chunk(
+                &compressed_block,
+                source,
+                chunk.layer_index,
+                None,
+                pedantic,
+                &ReadLimits::UNLIMITED,
+            )?;
+
+            let mut channels: Vec<_> = block.samples.channels.drain(..).map(Some).collect();
+            block.samples.channels = channel_order
+                .iter()
+                .map(|&index| channels[index].take().expect("channel order bug"))
+                .collect();
+
+            let compressed_block = match compressed_block {
+                CompressedBlock::DeepScanLine(scan_line) => {
+                    CompressedBlock::DeepScanLine(compress_deep_scanline_block(
+                        &block.samples,
+                        header.compression,
+                        &header.channels,
+                        scan_line.y_coordinate,
+                    )?)
+                }
+
+                CompressedBlock::DeepTile(tile) => {
+                    CompressedBlock::DeepTile(compress_deep_tile_block(
+                        &block.samples,
+                        header.compression,
+                        &header.channels,
+                        tile.coordinates,
+                    )?)
+                }
+
+                _ => unreachable!("flat block in deep layer"),
+            };
+
+            Ok(Chunk {
+                layer_index: output_layer,
+                compressed_block,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::deep::{DeepChannelData, DeepSamples};
+    use crate::image::write::deep::DeepImage;
+    use crate::prelude::*;
+    use std::io::Cursor;
+
+    /// A single unnamed layer with channels `B`, `G`, and `R`.
+    fn flat_file(size: Vec2<usize>, encoding: Encoding, offset: f32) -> Vec<u8> {
+        let channel = |name: &str, scale: f32| {
+            AnyChannel::new(
+                name,
+                FlatSamples::F32(
+                    (0..size.area())
+                        .map(|index| offset + scale * index as f32)
+                        .collect(),
+                ),
+            )
+        };
+
+        let layer = Layer::new(
+            size,
+            LayerAttributes::default(),
+            encoding,
+            AnyChannels::sort(smallvec![
+                channel("R", 1.0),
+                channel("G", 2.0),
+                channel("B", 3.0)
+            ]),
+        );
+
+        let mut bytes = Vec::new();
+        Image::from_layer(layer)
+            .write()
+            .to_buffered(Cursor::new(&mut bytes))
+            .unwrap();
+
+        bytes
+    }
+
+    fn read_flat(bytes: &[u8]) -> FlatImage {
+        read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .from_buffered(Cursor::new(bytes))
+            .unwrap()
+    }
+
+    fn copy(sources: &[&[u8]], layers: &[LayerCopy]) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let sources = sources.iter().map(|bytes| Cursor::new(*bytes)).collect();
+        copy_layers(sources, layers, Cursor::new(&mut bytes), true)?;
+        Ok(bytes)
+    }
+
+    #[test]
+    fn merge_layers_of_multiple_files() {
+        let size = Vec2(13, 40);
+        let diffuse = flat_file(size, Encoding::SMALL_LOSSLESS, 0.0);
+        let specular = flat_file(
+            size,
+            Encoding {
+                compression: Compression::RLE,
+                blocks: Blocks::Tiles(Vec2(8, 8)),
+                line_order: LineOrder::Increasing,
+            },
+            100.0,
+        );
+
+        let merged = copy(
+            &[&diffuse, &specular],
+            &[
+                LayerCopy::new(0, 0).with_layer_name("diffuse"),
+                LayerCopy::new(1, 0).with_layer_name("specular"),
+            ],
+        )
+        .unwrap();
+
+        let merged = read_flat(&merged);
+        assert_eq!(merged.layer_data.len(), 2);
+
+        for (layer, source) in merged.layer_data.iter().zip([&diffuse, &specular]) {
+            let source = read_flat(source);
+            assert_eq!(layer.encoding, source.layer_data[0].encoding);
+            assert_eq!(layer.channel_data, source.layer_data[0].channel_data);
+        }
+
+        let names: Vec<_> = merged
+            .layer_data
+            .iter()
+            .map(|layer| layer.attributes.layer_name.clone().unwrap())
+            .collect();
+        assert_eq!(names, [Text::from("diffuse"), Text::from("specular")]);
+
+        // merging two unnamed layers is not valid
+        assert!(copy(
+            &[&diffuse, &specular],
+            &[LayerCopy::new(0, 0), LayerCopy::new(1, 0)]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn extract_and_rename_channels() {
+        let size = Vec2(7, 35);
+        let bytes = flat_file(size, Encoding::SMALL_LOSSLESS, 0.0);
+        let merged = copy(
+            &[&bytes, &bytes],
+            &[
+                LayerCopy::new(0, 0).with_layer_name("first"),
+                LayerCopy::new(1, 0).with_layer_name("second"),
+            ],
+        )
+        .unwrap();
+
+        // renaming `R` to `A` moves it to the front
+        let extracted = copy(
+            &[&merged],
+            &[LayerCopy::new(0, 1)
+                .with_layer_name("alpha")
+                .with_channel_name("R", "A")],
+        )
+        .unwrap();
+
+        let original = read_flat(&bytes);
+        let extracted = read_flat(&extracted);
+        assert_eq!(extracted.layer_data.len(), 1);
+
+        let original = &original.layer_data[0].channel_data.list;
+        let extracted = &extracted.layer_data[0].channel_data.list;
+
+        let names: Vec<_> = extracted
+            .iter()
+            .map(|channel| channel.name.clone())
+            .collect();
+        assert_eq!(names, [Text::from("A"), Text::from("B"), Text::from("G")]);
+
+        assert_eq!(extracted[0].sample_data, original[2].sample_data);
+        assert_eq!(extracted[1].sample_data, original[0].sample_data);
+        assert_eq!(extracted[2].sample_data, original[1].sample_data);
+
+        assert!(copy(
+            &[&bytes],
+            &[LayerCopy::new(0, 0).with_channel_name("X", "Y")]
+        )
+        .is_err());
+        assert!(copy(&[&bytes], &[LayerCopy::new(0, 1)]).is_err());
+    }
+
+    fn deep_channel(name: &str, sample_data: DeepSamples) -> AnyChannel<DeepSamples> {
+        AnyChannel {
+            name: name.into(),
+            sample_data,
+            quantize_linearly: false,
+            sampling: Vec2(1, 1),
+        }
+    }
+
+    #[test]
+    fn rename_deep_channels() {
+        let size = Vec2(4, 3);
+        let mut samples = DeepSamples::new(size.width(), size.height());
+        samples.sample_offsets = (1..=size.area()).map(|pixel| (pixel * 2) as u32).collect();
+        samples.channels = vec![
+            DeepChannelData::F32((0..24).map(|value| value as f32).collect()),
+            DeepChannelData::F32((0..24).map(|value| -(value as f32)).collect()),
+        ];
+
+        let image: DeepImage = Image {
+            attributes: ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+            layer_data: Layer {
+                // the first channel contains the samples of all channels
+                channel_data: AnyChannels {
+                    list: smallvec![
+                        deep_channel("A", samples.clone()),
+                        deep_channel("Z", DeepSamples::new(0, 0)),
+                    ],
+                },
+                attributes: LayerAttributes::named("deep"),
+                size,
+                encoding: Encoding {
+                    compression: Compression::ZIP1,
+                    ..Encoding::default()
+                },
+            },
+        };
+
+        let mut bytes = Vec::new();
+        image
+            .write_deep()
+            .to_buffered(Cursor::new(&mut bytes))
+            .unwrap();
+
+        let renamed = copy(
+            &[&bytes],
+            &[LayerCopy::new(0, 0).with_channel_name("A", "ZZ")],
+        )
+        .unwrap();
+
+        let renamed = crate::image::read::deep::read_deep()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(Cursor::new(&renamed))
+            .unwrap();
+
+        let channels = &renamed.layer_data.channel_data.list;
+        assert_eq!(channels[0].name, Text::from("Z"));
+        assert_eq!(channels[1].name, Text::from("ZZ"));
+
+        let renamed_samples = &channels[0].sample_data;
+        assert_eq!(renamed_samples.sample_offsets, samples.sample_offsets);
+        assert_eq!(renamed_samples.channels[0], samples.channels[1]);
+        assert_eq!(renamed_samples.channels[1], samples.channels[0]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Limit the resources that reading an untrusted file may consume.
+
+use crate::block::chunk::CompressedBlock;
+use crate::error::{Error, UnitResult};
+use crate::meta::MetaData;
+
+/// Upper bounds for the memory that reading a file may allocate.
+/// The sizes in the meta data are checked before any pixels are allocated,
+/// and the sizes of each deep block are checked before the block is decompressed.
+/// Exceeding a limit results in an `Error::NotSupported`.
+///
+/// The default value does not limit anything.
+/// When reading files from untrusted sources, choose limits that fit your application:
+/// ```
+/// use exr::prelude::*;
+///
+/// let limits = ReadLimits {
+///     max_pixels: 8192 * 8192,
+///     max_decompressed_bytes: 1 << 30,
+///     ..ReadLimits::UNLIMITED
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ReadLimits {
+    /// The maximum number of pixels in the data windows of all parts of the file.
+    /// Also limits the pixels of a single block.
+    pub max_pixels: usize,
+
+    /// The maximum number of deep samples in the image, summed over all blocks that are read.
+    pub max_sample_count: usize,
+
+    /// The maximum number of bytes of all decompressed blocks that are read.
+    /// For flat images, this is checked before reading the first block.
+    /// For deep images, the size of each block is checked before the block is decompressed.
+    pub max_decompressed_bytes: usize,
+
+    /// The maximum number of parts (layers) in the file.
+    pub max_parts: usize,
+}
+
+impl ReadLimits {
+    /// Does not limit anything.
+    pub const UNLIMITED: Self = ReadLimits {
+        max_pixels: usize::MAX,
+        max_sample_count: usize::MAX,
+        max_decompressed_bytes: usize::MAX,
+        max_parts: usize::MAX,
+    };
+
+    /// Check the part count, the pixel count, and the size of the flat pixels of the file.
+    /// Called before any pixels are allocated.
+    pub fn validate_meta_data(&self, meta_data: &MetaData) -> UnitResult {
+        if meta_data.headers.len() > self.max_parts {
+            return Err(Error::unsupported("part count exceeds the read limits"));
+        }
+
+        let mut pixel_count = 0_usize;
+        for header in &meta_data.headers {
+            pixel_count = pixel_count.saturating_add(header.layer_size.area());
+            self.validate_pixel_count(header.max_block_pixel_size().area())?;
+        }
+
+        self.validate_pixel_count(pixel_count)?;
+
+        let flat_byte_count = meta_data
+            .headers
+            .iter()
+            .filter(|header| !header.deep)
+            .fold(0_usize, |sum, header| {
+                sum.saturating_add(header.total_pixel_bytes())
+            });
+
+        self.validate_decompressed_byte_count(flat_byte_count)
+    }
+
+    pub(crate) fn validate_pixel_count(&self, pixel_count: usize) -> UnitResult {
+        if pixel_count > self.max_pixels {
+            Err(Error::unsupported("pixel count exceeds the read limits"))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn validate_sample_count(&self, sample_count: usize) -> UnitResult {
+        if sample_count > self.max_sample_count {
+            Err(Error::unsupported(
+                "deep sample count exceeds the read limits",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn validate_decompressed_byte_count(&self, byte_count: usize) -> UnitResult {
+        if byte_count > self.max_decompressed_bytes {
+            Err(Error::unsupported(
+                "decompressed size exceeds the read limits",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+/// Sums the decompressed bytes and the samples of all deep blocks of an image,
+/// to enforce the limits for the whole image instead of each block.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct DeepBudget {
+    pub limits: ReadLimits,
+    decompressed_byte_count: usize,
+    sample_count: usize,
+}
+
+impl DeepBudget {
+    pub fn new(limits: ReadLimits) -> Self {
+        Self {
+            limits,
+            decompressed_byte_count: 0,
+            sample_count: 0,
+        }
+    }
+
+    /// Add the decompressed size of the block, as declared in the file, before decompressing it.
+    pub fn reserve_block(&mut self, block: &CompressedBlock) -> UnitResult {
+        let byte_count = match block {
+            CompressedBlock::DeepScanLine(block) => block.decompressed_sample_data_size,
+            CompressedBlock::DeepTile(block) => block.decompressed_sample_data_size,
+            _ => return Ok(()),
+        };
+
+        self.decompressed_byte_count = self.decompressed_byte_count.saturating_add(byte_count);
+        self.limits
+            .validate_decompressed_byte_count(self.decompressed_byte_count)
+    }
+
+    /// Add the samples of a decompressed block.
+    pub fn add_samples(&mut self, sample_count: usize) -> UnitResult {
+        self.sample_count = self.sample_count.saturating_add(sample_count);
+        self.limits.validate_sample_count(self.sample_count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    fn file_bytes() -> Vec<u8> {
+        let layer = Layer::new(
+            Vec2(64, 32),
+            LayerAttributes::named("limited"),
+            Encoding::UNCOMPRESSED,
+            AnyChannels::sort(smallvec::smallvec![AnyChannel::new(
+                "Y",
+                FlatSamples::F32(vec![0.0; 64 * 32])
+            )]),
+        );
+
+        let mut bytes = Vec::new();
+        Image::from_layer(layer)
+            .write()
+            .to_buffered(std::io::Cursor::new(&mut bytes))
+            .unwrap();
+
+        bytes
+    }
+
+    fn meta_data() -> MetaData {
+        MetaData::read_from_buffered(file_bytes().as_slice(), false).unwrap()
+    }
+
+    #[test]
+    fn unlimited_accepts_everything() {
+        assert!(ReadLimits::default()
+            .validate_meta_data(&meta_data())
+            .is_ok());
+    }
+
+    #[test]
+    fn limits_reject_large_files() {
+        let meta_data = meta_data();
+
+        let pixels = ReadLimits {
+            max_pixels: 64 * 32 - 1,
+            ..ReadLimits::UNLIMITED
+        };
+        assert!(matches!(
+            pixels.validate_meta_data(&meta_data),
+            Err(Error::NotSupported(_))
+        ));
+
+        let bytes = ReadLimits {
+            max_decompressed_bytes: 64 * 32 * 4 - 1,
+            ..ReadLimits::UNLIMITED
+        };
+        assert!(bytes.validate_meta_data(&meta_data).is_err());
+
+        let parts = ReadLimits {
+            max_parts: 0,
+            ..ReadLimits::UNLIMITED
+        };
+        assert!(parts.validate_meta_data(&meta_data).is_err());
+
+        let exact = ReadLimits {
+            max_pixels: 64 * 32,
+            max_decompressed_bytes: 64 * 32 * 4,
+            max_parts: 1,
+            ..ReadLimits::UNLIMITED
+        };
+        assert!(exact.validate_meta_data(&meta_data).is_ok());
+    }
+
+    #[test]
+    fn reading_respects_limits() {
+        let bytes = file_bytes();
+
+        let read_with = |limits: ReadLimits| {
+            crate::image::read::read()
+                .no_deep_data()
+                .largest_resolution_level()
+                .all_channels()
+                .first_valid_layer()
+                .all_attributes()
+                .with_limits(limits)
+                .from_buffered(std::io::Cursor::new(&bytes))
+        };
+
+        assert!(read_with(ReadLimits::UNLIMITED).is_ok());
+
+        let pixels = ReadLimits {
+            max_pixels: 1024,
+            ..ReadLimits::UNLIMITED
+        };
+        assert!(matches!(read_with(pixels), Err(Error::NotSupported(_))));
+    }
+}
@@ -60,6 +60,8 @@ impl LineIndex {
     /// Iterates the lines of this block index in interleaved fashion:
     /// For each line in this block, this iterator steps once through each channel.
     /// This is how lines are stored in a pixel data block.
+    /// Subsampled channels only have lines and samples at coordinates divisible by their sampling rate.
+    /// The positions of their lines are indices into the subsampled resolution of the channel.
     ///
     /// Does not check whether `self.layer_index`, `self.level`, `self.size` and `self.position` are valid indices.__
     // TODO be sure this cannot produce incorrect data, as this is not further checked but only handled with panics
@@ -69,70 +71,90 @@ impl LineIndex {
         block: BlockIndex,
         channels: &ChannelList,
     ) -> impl Iterator<Item = (Range<usize>, LineIndex)> {
+        /// The samples of one channel in each line of the block.
+        struct ChannelLine {
+            byte_len: usize,
+            sample_count: usize,
+            x: usize,
+            y_sampling: usize,
+        }
+
         struct LineIter {
             layer: usize,
             level: Vec2<usize>,
-            width: usize,
             end_y: usize,
-            x: usize,
-            channel_sizes: SmallVec<[usize; 8]>,
+            channel_lines: SmallVec<[ChannelLine; 8]>,
             byte: usize,
             channel: usize,
             y: usize,
         }
 
-        // FIXME what about sub sampling??
-
         impl Iterator for LineIter {
             type Item = (Range<usize>, LineIndex);
             // TODO size hint?
 
             fn next(&mut self) -> Option<Self::Item> {
-                if self.y < self.end_y {
-                    // compute return value before incrementing
-                    let byte_len = self.channel_sizes[self.channel];
-                    let return_value = (
-                        (self.byte..self.byte + byte_len),
-                        LineIndex {
-                            channel: self.channel,
-                            layer: self.layer,
-                            level: self.level,
-                            position: Vec2(self.x, self.y),
-                            sample_count: self.width,
-                        },
-                    );
+                while self.y < self.end_y {
+                    let line = &self.channel_lines[self.channel];
+                    let (channel, y) = (self.channel, self.y);
 
                     {
                         // increment indices
-                        self.byte += byte_len;
                         self.channel += 1;
 
-                        if self.channel == self.channel_sizes.len() {
+                        if self.channel == self.channel_lines.len() {
                             self.channel = 0;
                             self.y += 1;
                         }
                     }
 
-                    Some(return_value)
-                } else {
-                    None
+                    // this line of the block has no samples of this subsampled channel
+                    if y % line.y_sampling != 0 || line.sample_count == 0 {
+                        continue;
+                    }
+
+                    let bytes = self.byte..self.byte + line.byte_len;
+                    self.byte += line.byte_len;
+
+                    return Some((
+                        bytes,
+                        LineIndex {
+                            channel,
+                            layer: self.layer,
+                            level: self.level,
+                            position: Vec2(line.x, y / line.y_sampling),
+                            sample_count: line.sample_count,
+                        },
+                    ));
                 }
+
+                None
             }
         }
 
-        let channel_line_sizes: SmallVec<[usize; 8]> = channels
+        let section = block.section();
+
+        let channel_lines: SmallVec<[ChannelLine; 8]> = channels
             .list
             .iter()
-            .map(move |channel| block.pixel_size.0 * channel.sample_type.bytes_per_sample()) // FIXME is it fewer samples per tile or just fewer tiles for sampled images???
+            .map(|channel| {
+                let sampling = channel.sampling;
+                let sample_count = channel.subsampled_section_resolution(section).width();
+
+                ChannelLine {
+                    byte_len: sample_count * channel.sample_type.bytes_per_sample(),
+                    sample_count,
+                    x: (block.pixel_position.x() + sampling.x() - 1) / sampling.x(),
+                    y_sampling: sampling.y(),
+                }
+            })
             .collect();
 
         LineIter {
             layer: block.layer,
             level: block.level,
-            width: block.pixel_size.0,
-            x: block.pixel_position.0,
             end_y: block.pixel_position.y() + block.pixel_size.height(),
-            channel_sizes: channel_line_sizes,
+            channel_lines,
 
             byte: 0,
             channel: 0,
@@ -0,0 +1,583 @@
+//! Read chunks directly from the bytes of a file in memory, such as a memory-mapped file.
+//!
+//! The regular [`Reader`](super::reader::Reader) copies the compressed bytes
+//! of every chunk into a new buffer before decompressing them.
+//! In contrast, [`MappedReader`] parses the offset tables and chunk headers directly from a byte slice,
+//! and each chunk only borrows its compressed bytes from that slice.
+//! Deep blocks are decompressed straight from the borrowed bytes,
+//! and uncompressed deep samples are unpacked without any intermediate copy.
+//!
+//! This crate does not contain unsafe code, so it does not map files by itself.
+//! Map the file with a crate like `memmap2`, and pass the mapping to [`MappedReader::new`]:
+//!
+//! ```ignore
+//! let file = std::fs::File::open("deep.exr")?;
+//! let mapping = unsafe { memmap2::Mmap::map(&file)? };
+//! let reader = exr::block::mapped::MappedReader::new(mapping, true)?;
+//!
+//! for chunk in reader.chunks() {
+//!     let block = reader.decompress_deep_block(chunk?, None)?;
+//!     println!("{} samples at y {}", block.samples.total_samples(), block.y_coordinate);
+//! }
+//! ```
+
+use crate::block::chunk::{
+    Chunk, CompressedBlock, CompressedDeepScanLineBlock, CompressedDeepTileBlock,
+    CompressedScanLineBlock, CompressedTileBlock, TileCoordinates,
+};
+use crate::block::deep::{
+    decompress_deep_samples, deep_scan_line_block_height, deep_tile_size, DeepUncompressedBlock,
+};
+use crate::block::limits::ReadLimits;
+use crate::block::UncompressedBlock;
+use crate::error::{i32_to_usize, u64_to_usize, Error, Result};
+use crate::io::{Data, PeekRead, Tracking};
+use crate::meta::header::Header;
+use crate::meta::{BlockDescription, MetaData, OffsetTables};
+use std::convert::TryFrom;
+
+/// Reads the meta data and chunks of an exr file from bytes in memory, without copying them.
+/// The bytes can be anything that dereferences to a byte slice,
+/// for example a `Vec<u8>` or a memory-mapped file.
+#[derive(Debug)]
+pub struct MappedReader<B> {
+    bytes: B,
+    meta_data: MetaData,
+    offset_tables: OffsetTables,
+    pedantic: bool,
+}
+
+/// A chunk whose compressed bytes are borrowed from the bytes of a [`MappedReader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef<'b> {
+    /// The index of the layer that this chunk belongs to.
+    pub layer_index: usize,
+
+    /// The borrowed compressed pixel data.
+    pub block: BlockRef<'b>,
+}
+
+/// The borrowed contents of a [`ChunkRef`], mirroring [`CompressedBlock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRef<'b> {
+    /// Scan line blocks of flat data.
+    ScanLine {
+        /// The first scan line in this block, see [`CompressedScanLineBlock::y_coordinate`].
+        y_coordinate: i32,
+
+        /// The compressed pixels, borrowed from the file bytes.
+        compressed_pixels_le: &'b [u8],
+    },
+
+    /// Tiles of flat data.
+    Tile {
+        /// The tile location.
+        coordinates: TileCoordinates,
+
+        /// The compressed pixels, borrowed from the file bytes.
+        compressed_pixels_le: &'b [u8],
+    },
+
+    /// Scan line blocks of deep data.
+    DeepScanLine {
+        /// The first scan line in this block.
+        y_coordinate: i32,
+
+        /// The byte count of the sample data after decompression.
+        decompressed_sample_data_size: usize,
+
+        /// The compressed sample count table, borrowed from the file bytes.
+        compressed_pixel_offset_table: &'b [u8],
+
+        /// The compressed samples, borrowed from the file bytes.
+        compressed_sample_data_le: &'b [u8],
+    },
+
+    /// Tiles of deep data.
+    DeepTile {
+        /// The tile location.
+        coordinates: TileCoordinates,
+
+        /// The byte count of the sample data after decompression.
+        decompressed_sample_data_size: usize,
+
+        /// The compressed sample count table, borrowed from the file bytes.
+        compressed_pixel_offset_table: &'b [u8],
+
+        /// The compressed samples, borrowed from the file bytes.
+        compressed_sample_data_le: &'b [u8],
+    },
+}
+
+impl<B: AsRef<[u8]>> MappedReader<B> {
+    /// Decode the meta data and the offset tables from the start of the bytes.
+    /// The chunks are not parsed until they are requested.
+    pub fn new(bytes: B, pedantic: bool) -> Result<Self> {
+        let (meta_data, offset_tables) = {
+            let mut read = PeekRead::new(Tracking::new(bytes.as_ref()));
+            let meta_data = MetaData::read_validated_from_buffered_peekable(&mut read, pedantic)?;
+            let offset_tables = MetaData::read_offset_tables(&mut read, &meta_data.headers)?;
+
+            // the size of deep chunks is not known in advance, but every chunk must be inside the bytes
+            let chunks_byte_range = read.byte_position()..bytes.as_ref().len();
+            let is_invalid = offset_tables.iter().flatten().any(|&offset| {
+                usize::try_from(offset).map_or(true, |offset| !chunks_byte_range.contains(&offset))
+            });
+
+            if pedantic && is_invalid {
+                return Err(Error::invalid("offset table"));
+            }
+
+            (meta_data, offset_tables)
+        };
+
+        Ok(Self {
+            bytes,
+            meta_data,
+            offset_tables,
+            pedantic,
+        })
+    }
+
+    /// The decoded exr meta data from the file.
+    pub fn meta_data(&self) -> &MetaData {
+        &self.meta_data
+    }
+
+    /// The decoded exr meta data from the file.
+    pub fn headers(&self) -> &[Header] {
+        &self.meta_data.headers
+    }
+
+    /// The bytes of the whole file.
+    pub fn bytes(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+
+    /// Obtain the ownership of the file bytes.
+    pub fn into_bytes(self) -> B {
+        self.bytes
+    }
+
+    /// The number of chunks in all layers of the file.
+    pub fn chunk_count(&self) -> usize {
+        self.offset_tables.iter().map(|table| table.len()).sum()
+    }
+
+    /// Parse the header of a single chunk, borrowing its compressed bytes.
+    /// The chunk index refers to the offset table of the layer, in increasing y order.
+    pub fn chunk(&self, layer_index: usize, chunk_index: usize) -> Result<ChunkRef<'_>> {
+        let offset = *self
+            .offset_tables
+            .get(layer_index)
+            .and_then(|table| table.get(chunk_index))
+            .ok_or(Error::invalid("chunk index"))?;
+
+        self.chunk_at(u64_to_usize(offset, "chunk start")?)
+    }
+
+    /// Parse all chunks, in the order of the offset tables.
+    pub fn chunks(&self) -> impl '_ + Iterator<Item = Result<ChunkRef<'_>>> {
+        self.offset_tables.iter().flatten().map(move |&offset| {
+            u64_to_usize(offset, "chunk start").and_then(|offset| self.chunk_at(offset))
+        })
+    }
+
+    /// Parse the chunk that starts at the specified byte position.
+    fn chunk_at(&self, byte_position: usize) -> Result<ChunkRef<'_>> {
+        let mut remaining = self
+            .bytes
+            .as_ref()
+            .get(byte_position..)
+            .ok_or(Error::invalid("chunk start"))?;
+
+        let layer_index = if self.meta_data.requirements.is_multilayer() {
+            i32_to_usize(i32::read_le(&mut remaining)?, "chunk data part number")?
+        } else {
+            0
+        };
+
+        let header = self
+            .meta_data
+            .headers
+            .get(layer_index)
+            .ok_or(Error::invalid("chunk data part number"))?;
+
+        let max_block_byte_size = header.max_block_byte_size();
+
+        let block = match header.blocks {
+            BlockDescription::ScanLines if !header.deep => {
+                let y_coordinate = i32::read_le(&mut remaining)?;
+                let byte_count = i32_to_usize(
+                    i32::read_le(&mut remaining)?,
+                    "scan line block sample count",
+                )?;
+
+                BlockRef::ScanLine {
+                    y_coordinate,
+                    compressed_pixels_le: take_bytes(
+                        &mut remaining,
+                        byte_count,
+                        max_block_byte_size,
+                        "scan line block sample count",
+                    )?,
+                }
+            }
+
+            BlockDescription::Tiles(_) if !header.deep => {
+                let coordinates = TileCoordinates::read(&mut remaining)?;
+                let byte_count =
+                    i32_to_usize(i32::read_le(&mut remaining)?, "tile block sample count")?;
+
+                BlockRef::Tile {
+                    coordinates,
+                    compressed_pixels_le: take_bytes(
+                        &mut remaining,
+                        byte_count,
+                        max_block_byte_size,
+                        "tile block sample count",
+                    )?,
+                }
+            }
+
+            BlockDescription::ScanLines => {
+                let y_coordinate = i32::read_le(&mut remaining)?;
+                let (table, data, decompressed_sample_data_size) =
+                    take_deep_sections(&mut remaining, max_block_byte_size)?;
+
+                BlockRef::DeepScanLine {
+                    y_coordinate,
+                    decompressed_sample_data_size,
+                    compressed_pixel_offset_table: table,
+                    compressed_sample_data_le: data,
+                }
+            }
+
+            BlockDescription::Tiles(_) => {
+                let coordinates = TileCoordinates::read(&mut remaining)?;
+                let (table, data, decompressed_sample_data_size) =
+                    take_deep_sections(&mut remaining, max_block_byte_size)?;
+
+                BlockRef::DeepTile {
+                    coordinates,
+                    decompressed_sample_data_size,
+                    compressed_pixel_offset_table: table,
+                    compressed_sample_data_le: data,
+                }
+            }
+        };
+
+        Ok(ChunkRef { layer_index, block })
+    }
+
+    /// Decompress a flat chunk.
+    /// The flat decompressors take ownership of their input,
+    /// so the compressed bytes of the chunk are copied once.
+    pub fn decompress_block(&self, chunk: ChunkRef<'_>) -> Result<UncompressedBlock> {
+        UncompressedBlock::decompress_chunk(chunk.to_chunk(), &self.meta_data, self.pedantic)
+    }
+
+    /// Decompress a deep chunk directly from the borrowed bytes.
+    /// Pass the indices of the channels to unpack, in increasing order, or `None` for all channels.
+    pub fn decompress_deep_block(
+        &self,
+        chunk: ChunkRef<'_>,
+        selected_channels: Option<&[usize]>,
+    ) -> Result<DeepUncompressedBlock> {
+        let header = self
+            .meta_data
+            .headers
+            .get(chunk.layer_index)
+            .ok_or(Error::invalid("chunk layer index"))?;
+
+        let mut tile = None;
+        let (y_coordinate, table, data, decompressed_size, width, height) = match chunk.block {
+            BlockRef::DeepScanLine {
+                y_coordinate,
+                decompressed_sample_data_size,
+                compressed_pixel_offset_table,
+                compressed_sample_data_le,
+            } => (
+                y_coordinate,
+                compressed_pixel_offset_table,
+                compressed_sample_data_le,
+                decompressed_sample_data_size,
+                header.layer_size.width(),
+                deep_scan_line_block_height(header, y_coordinate)?,
+            ),
+
+            BlockRef::DeepTile {
+                coordinates,
+                decompressed_sample_data_size,
+                compressed_pixel_offset_table,
+                compressed_sample_data_le,
+            } => {
+                let tile_size = deep_tile_size(header, coordinates)?;
+                tile = Some(coordinates);
+
+                (
+                    coordinates.tile_index.y() as i32,
+                    compressed_pixel_offset_table,
+                    compressed_sample_data_le,
+                    decompressed_sample_data_size,
+                    tile_size.width(),
+                    tile_size.height(),
+                )
+            }
+
+            _ => return Err(Error::invalid("expected deep block, got flat block")),
+        };
+
+        let samples = decompress_deep_samples(
+            table,
+            data,
+            decompressed_size,
+            header.compression,
+            &header.channels,
+            selected_channels,
+            width,
+            height,
+            self.pedantic,
+            &ReadLimits::UNLIMITED,
+        )?;
+
+        Ok(DeepUncompressedBlock {
+            layer_index: chunk.layer_index,
+            y_coordinate,
+            tile,
+            samples,
+        })
+    }
+}
+
+impl ChunkRef<'_> {
+    /// Copy the borrowed bytes into an owned chunk.
+    pub fn to_chunk(&self) -> Chunk {
+        let compressed_block = match self.block {
+            BlockRef::ScanLine {
+                y_coordinate,
+                compressed_pixels_le,
+            } => CompressedBlock::ScanLine(CompressedScanLineBlock {
+                y_coordinate,
+                compressed_pixels_le: compressed_pixels_le.to_vec(),
+            }),
+
+            BlockRef::Tile {
+                coordinates,
+                compressed_pixels_le,
+            } => CompressedBlock::Tile(CompressedTileBlock {
+                coordinates,
+                compressed_pixels_le: compressed_pixels_le.to_vec(),
+            }),
+
+            BlockRef::DeepScanLine {
+                y_coordinate,
+                decompressed_sample_data_size,
+                compressed_pixel_offset_table,
+                compressed_sample_data_le,
+            } => CompressedBlock::DeepScanLine(CompressedDeepScanLineBlock {
+                y_coordinate,
+                decompressed_sample_data_size,
+                compressed_pixel_offset_table: compressed_pixel_offset_table.to_vec(),
+                compressed_sample_data_le: compressed_sample_data_le.to_vec(),
+            }),
+
+            BlockRef::DeepTile {
+                coordinates,
+                decompressed_sample_data_size,
+                compressed_pixel_offset_table,
+                compressed_sample_data_le,
+            } => CompressedBlock::DeepTile(CompressedDeepTileBlock {
+                coordinates,
+                decompressed_sample_data_size,
+                compressed_pixel_offset_table: compressed_pixel_offset_table.to_vec(),
+                compressed_sample_data_le: compressed_sample_data_le.to_vec(),
+            }),
+        };
+
+        Chunk {
+            layer_index: self.layer_index,
+            compressed_block,
+        }
+    }
+}
+
+/// Split the next bytes off the remaining slice, without copying them.
+fn take_bytes<'b>(
+    remaining: &mut &'b [u8],
+    byte_count: usize,
+    hard_max: usize,
+    purpose: &'static str,
+) -> Result<&'b [u8]> {
+    if byte_count > hard_max || byte_count > remaining.len() {
+        return Err(Error::invalid(purpose));
+    }
+
+    let (bytes, rest) = remaining.split_at(byte_count);
+    *remaining = rest;
+    Ok(bytes)
+}
+
+/// Split the sample count table and the sample data of a deep block off the remaining slice.
+fn take_deep_sections<'b>(
+    remaining: &mut &'b [u8],
+    max_block_byte_size: usize,
+) -> Result<(&'b [u8], &'b [u8], usize)> {
+    let table_size = u64_to_usize(u64::read_le(remaining)?, "deep table size")?;
+    let data_size = u64_to_usize(u64::read_le(remaining)?, "deep size")?;
+    let decompressed_size = u64_to_usize(u64::read_le(remaining)?, "raw deep size")?;
+
+    let table = take_bytes(
+        remaining,
+        table_size,
+        max_block_byte_size,
+        "deep block table size",
+    )?;
+
+    let data = take_bytes(
+        remaining,
+        data_size,
+        max_block_byte_size,
+        "deep block sample count",
+    )?;
+
+    Ok((table, data, decompressed_size))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block::deep::{decompress_deep_chunk, DeepUncompressedBlock};
+    use crate::block::reader::{ChunksReader, Reader};
+    use crate::compression::Compression;
+    use crate::image::deep::{DeepChannelData, DeepSamples};
+    use crate::image::write::deep::write_deep_scanlines_to_buffered;
+    use crate::meta::attribute::{ChannelDescription, ChannelList, SampleType};
+    use smallvec::smallvec;
+    use std::io::Cursor;
+
+    fn deep_file(compression: Compression) -> Vec<u8> {
+        let channels = ChannelList::new(smallvec![
+            ChannelDescription::new("A", SampleType::F16, true),
+            ChannelDescription::new("Z", SampleType::F32, true),
+        ]);
+
+        let mut samples = DeepSamples::new(5, 40);
+        let cumulative_counts: Vec<u32> = (0..5 * 40_u32)
+            .scan(0, |total, index| {
+                *total += index % 4;
+                Some(*total)
+            })
+            .collect();
+        samples.set_cumulative_counts(cumulative_counts).unwrap();
+        samples.allocate_channels(&channels);
+
+        for channel in &mut samples.channels {
+            match channel {
+                DeepChannelData::F16(values) => values
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(index, value)| *value = half::f16::from_f32(index as f32 * 0.5)),
+                DeepChannelData::F32(values) => values
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(index, value)| *value = index as f32 * 3.0),
+                DeepChannelData::U32(_) => unreachable!(),
+            }
+        }
+
+        let mut bytes = Vec::new();
+        write_deep_scanlines_to_buffered(
+            Cursor::new(&mut bytes),
+            &samples,
+            &channels,
+            compression,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        bytes
+    }
+
+    fn read_with_copies(bytes: &[u8]) -> Vec<DeepUncompressedBlock> {
+        let chunks = Reader::read_from_buffered(Cursor::new(bytes), false)
+            .unwrap()
+            .all_chunks(false)
+            .unwrap();
+
+        let meta_data = chunks.meta_data().clone();
+        chunks
+            .map(|chunk| {
+                let chunk = chunk.unwrap();
+                decompress_deep_chunk(
+                    &chunk.compressed_block,
+                    &meta_data,
+                    chunk.layer_index,
+                    None,
+                    true,
+                    &ReadLimits::UNLIMITED,
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deep_blocks_match_copying_reader() {
+        for compression in [
+            Compression::Uncompressed,
+            Compression::RLE,
+            Compression::ZIP1,
+            Compression::ZIP16,
+        ] {
+            let bytes = deep_file(compression);
+            let expected = read_with_copies(&bytes);
+
+            let reader = MappedReader::new(bytes.as_slice(), true).unwrap();
+            assert_eq!(reader.chunk_count(), expected.len());
+
+            for (chunk, expected) in reader.chunks().zip(&expected) {
+                let block = reader.decompress_deep_block(chunk.unwrap(), None).unwrap();
+                assert_eq!(block.y_coordinate, expected.y_coordinate);
+                assert_eq!(block.samples, expected.samples, "{}", compression);
+            }
+        }
+    }
+
+    #[test]
+    fn borrowed_chunk_converts_to_owned_chunk() {
+        let bytes = deep_file(Compression::ZIP1);
+        let reader = MappedReader::new(bytes.as_slice(), true).unwrap();
+        let chunk = reader.chunk(0, 1).unwrap();
+
+        let borrowed_table = match chunk.block {
+            BlockRef::DeepScanLine {
+                compressed_pixel_offset_table,
+                ..
+            } => compressed_pixel_offset_table,
+            _ => panic!("expected deep scan line block"),
+        };
+
+        // the borrowed bytes point into the file bytes
+        let file_range = bytes.as_ptr_range();
+        assert!(file_range.contains(&borrowed_table.as_ptr()));
+
+        match chunk.to_chunk().compressed_block {
+            CompressedBlock::DeepScanLine(block) => {
+                assert_eq!(block.compressed_pixel_offset_table, borrowed_table)
+            }
+            _ => panic!("expected deep scan line block"),
+        }
+
+        assert!(reader.chunk(0, reader.chunk_count()).is_err());
+    }
+
+    #[test]
+    fn truncated_chunk_is_an_error() {
+        let bytes = deep_file(Compression::Uncompressed);
+        let reader = MappedReader::new(&bytes[..bytes.len() - 3], false).unwrap();
+        let last = reader.chunk_count() - 1;
+        assert!(reader.chunk(0, last).is_err());
+    }
+}
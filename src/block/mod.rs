@@ -10,10 +10,22 @@
 pub mod reader;
 pub mod writer;
 
+pub mod appender;
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+pub mod cancel;
 pub mod chunk;
 pub mod deep;
+pub mod deinterleave;
+pub mod layers;
+pub mod limits;
 pub mod lines;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+pub mod pool;
 pub mod samples;
+pub mod source;
+pub mod verify;
 
 use crate::block::chunk::{
     Chunk, CompressedBlock, CompressedScanLineBlock, CompressedTileBlock, TileCoordinates,
@@ -22,7 +34,7 @@ use crate::block::lines::{LineIndex, LineRef, LineRefMut, LineSlice};
 use crate::compression::ByteVec;
 use crate::error::{usize_to_i32, Error, Result, UnitResult};
 use crate::math::Vec2;
-use crate::meta::attribute::ChannelList;
+use crate::meta::attribute::{ChannelList, IntegerBounds};
 use crate::meta::header::Header;
 use crate::meta::{BlockDescription, Headers, MetaData};
 use std::io::{Read, Seek, Write};
@@ -46,6 +58,13 @@ pub struct BlockIndex {
     pub level: Vec2<usize>,
 }
 
+impl BlockIndex {
+    /// The pixels of this block, relative to the data window of its resolution level.
+    pub fn section(self) -> IntegerBounds {
+        IntegerBounds::new(self.pixel_position.to_i32(), self.pixel_size)
+    }
+}
+
 /// Contains a block of pixel data and where that data should be placed in the actual image.
 /// The bytes must be encoded in native-endian format.
 /// The conversion to little-endian format happens when converting to chunks (potentially in parallel).
@@ -157,7 +176,7 @@ impl UncompressedBlock {
 
         let header: &Header = headers.get(index.layer).expect("block layer index bug");
 
-        let expected_byte_size = header.channels.bytes_per_pixel * self.index.pixel_size.area(); // TODO sampling??
+        let expected_byte_size = header.channels.byte_size_of_section(self.index.section());
         if expected_byte_size != data.len() {
             panic!(
                 "get_line byte size should be {} but was {}",
@@ -248,12 +267,11 @@ impl UncompressedBlock {
         block_index: BlockIndex,
         mut extract_line: impl FnMut(LineRefMut<'_>),
     ) -> Vec<u8> {
-        let byte_count = block_index.pixel_size.area() * channels.bytes_per_pixel;
+        let byte_count = channels.byte_size_of_section(block_index.section());
         let mut block_bytes = vec![0_u8; byte_count];
 
         for (byte_range, line_index) in LineIndex::lines_in_block(block_index, channels) {
             extract_line(LineRefMut {
-                // TODO subsampling
                 value: &mut block_bytes[byte_range],
                 location: line_index,
             });
@@ -0,0 +1,169 @@
+//! Reuse the byte buffers of blocks while reading a file.
+//!
+//! Each block requires a few temporary buffers: the compressed bytes,
+//! the decompressed bytes, and the intermediate buffers of the compression method.
+//! These buffers are usually allocated on one thread and freed on another thread,
+//! which causes contention in the allocator when many threads decompress blocks.
+//!
+//! The decompressors install a shared [`BufferPool`] on all threads that handle their blocks.
+//! Reading chunks and decompressing blocks then takes the buffers from the installed pool,
+//! and returns them to the pool when they are no longer needed.
+//! Without an installed pool, buffers are simply allocated and dropped.
+
+use crate::compression::ByteVec;
+use crate::error::{Error, Result};
+use crate::io::{Data, Read};
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+/// A collection of unused byte buffers, shared by multiple threads.
+#[derive(Debug, Default)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<ByteVec>>,
+    max_buffer_count: usize,
+}
+
+thread_local! {
+    /// The pool used by the current thread, if any.
+    static INSTALLED_POOL: RefCell<Option<Arc<BufferPool>>> = RefCell::new(None);
+}
+
+impl BufferPool {
+    /// Create an empty pool that keeps at most the specified number of unused buffers.
+    pub fn new(max_buffer_count: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::with_capacity(max_buffer_count)),
+            max_buffer_count,
+        }
+    }
+
+    /// An empty buffer with at least the specified capacity.
+    /// Reuses an unused buffer, or allocates a new buffer if the pool is empty.
+    pub fn take(&self, capacity: usize) -> ByteVec {
+        let unused = self
+            .buffers
+            .lock()
+            .ok()
+            .and_then(|mut buffers| buffers.pop());
+
+        match unused {
+            Some(mut buffer) => {
+                buffer.clear();
+                buffer.reserve(capacity);
+                buffer
+            }
+
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Keep the buffer for later use. Drops the buffer if the pool is full.
+    pub fn recycle(&self, buffer: ByteVec) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+
+        if let Ok(mut buffers) = self.buffers.lock() {
+            if buffers.len() < self.max_buffer_count {
+                buffers.push(buffer);
+            }
+        }
+    }
+
+    /// The number of unused buffers that are currently kept in this pool.
+    pub fn len(&self) -> usize {
+        self.buffers.lock().map_or(0, |buffers| buffers.len())
+    }
+
+    /// Whether this pool currently keeps no unused buffers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Use this pool for all buffers that the current thread takes and recycles
+    /// while running the closure. Restores the previously installed pool afterwards.
+    pub fn install<T>(self: Arc<Self>, run: impl FnOnce() -> T) -> T {
+        let previous = INSTALLED_POOL.with(|installed| installed.replace(Some(self)));
+        let _restore = RestorePool(previous);
+        run()
+    }
+}
+
+/// Restores the previously installed pool, even if the closure panics.
+struct RestorePool(Option<Arc<BufferPool>>);
+
+impl Drop for RestorePool {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        INSTALLED_POOL.with(|installed| *installed.borrow_mut() = previous);
+    }
+}
+
+/// An empty buffer with at least the specified capacity,
+/// from the pool installed on the current thread, or newly allocated.
+pub fn take_buffer(capacity: usize) -> ByteVec {
+    INSTALLED_POOL.with(|installed| match &*installed.borrow() {
+        Some(pool) => pool.take(capacity),
+        None => Vec::with_capacity(capacity),
+    })
+}
+
+/// Return a buffer that is no longer needed to the pool installed on the current thread.
+/// Drops the buffer if no pool is installed.
+pub fn recycle_buffer(buffer: ByteVec) {
+    INSTALLED_POOL.with(|installed| {
+        if let Some(pool) = &*installed.borrow() {
+            pool.recycle(buffer);
+        }
+    })
+}
+
+/// Read the specified number of bytes into a buffer from the installed pool.
+/// Like `u8::read_vec_le`, this will not allocate more than `soft_max` bytes at once,
+/// and fails if the byte count exceeds `hard_max`.
+pub(crate) fn read_bytes(
+    read: &mut impl Read,
+    byte_count: usize,
+    soft_max: usize,
+    hard_max: Option<usize>,
+    purpose: &'static str,
+) -> Result<ByteVec> {
+    if let Some(max) = hard_max {
+        if byte_count > max {
+            return Err(Error::invalid(purpose));
+        }
+    }
+
+    let mut bytes = take_buffer(byte_count.min(soft_max));
+    u8::read_into_vec_le(read, &mut bytes, byte_count, soft_max, hard_max, purpose)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reuse_installed_buffers() {
+        let pool = Arc::new(BufferPool::new(1));
+
+        // without an installed pool, buffers are simply dropped
+        recycle_buffer(vec![0; 16]);
+        assert!(pool.is_empty());
+
+        pool.clone().install(|| {
+            let buffer = take_buffer(64);
+            let address = buffer.as_ptr();
+            recycle_buffer(buffer);
+            recycle_buffer(vec![0; 8]); // the pool is full
+
+            let reused = take_buffer(32);
+            assert_eq!(reused.as_ptr(), address);
+            assert!(reused.is_empty());
+            assert!(reused.capacity() >= 64);
+        });
+
+        recycle_buffer(vec![0; 16]);
+        assert!(pool.is_empty());
+    }
+}
@@ -1,11 +1,13 @@
 //! Composable structures to handle reading an image.
 
+use crate::block::cancel::CancellationToken;
 use crate::block::chunk::{Chunk, TileCoordinates};
 use crate::block::{BlockIndex, UncompressedBlock};
 use crate::error::{u64_to_usize, Error, Result, UnitResult};
 use crate::io::{PeekRead, Tracking};
 use crate::meta::header::Header;
 use crate::meta::{MetaData, OffsetTables};
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::io::{Read, Seek};
@@ -139,6 +141,45 @@ impl<R: Read + Seek> Reader<R> {
             remaining_bytes: self.remaining_reader,
         })
     }
+    /// Prepare to read single tiles from the file on demand.
+    /// Keeps the offset tables in memory, but does not read any chunks yet.
+    /// Each tile of each resolution level can then be read and decompressed
+    /// individually, without reading the rest of the image.
+    /// Also works for scan line images, where each block is a tile that spans the whole width.
+    pub fn tiled_random_access(mut self, pedantic: bool) -> Result<TiledRandomAccess<R>> {
+        let offset_tables =
+            MetaData::read_offset_tables(&mut self.remaining_reader, &self.meta_data.headers)?;
+
+        if pedantic {
+            validate_offset_tables(
+                self.meta_data.headers.as_slice(),
+                &offset_tables,
+                self.remaining_reader.byte_position(),
+            )?;
+        }
+
+        // offset tables are stored in the same order as the blocks in increasing y order
+        let chunk_offsets = self
+            .meta_data
+            .headers
+            .iter()
+            .zip(offset_tables)
+            .map(|(header, offsets)| {
+                header
+                    .blocks_increasing_y_order()
+                    .map(|tile| tile.location)
+                    .zip(offsets)
+                    .collect()
+            })
+            .collect();
+
+        Ok(TiledRandomAccess {
+            meta_data: self.meta_data,
+            chunk_offsets,
+            remaining_bytes: self.remaining_reader,
+            pedantic,
+        })
+    }
 }
 
 fn validate_offset_tables(
@@ -146,13 +187,19 @@ fn validate_offset_tables(
     offset_tables: &OffsetTables,
     chunks_start_byte: usize,
 ) -> UnitResult {
-    let max_pixel_bytes: usize = headers
-        .iter() // when compressed, chunks are smaller, but never larger than max
-        .map(|header| header.max_pixel_file_bytes())
-        .sum();
+    // the number of samples in deep layers is not known in advance, so their size is not limited
+    let end_byte = if headers.iter().any(|header| header.deep) {
+        usize::MAX
+    } else {
+        let max_pixel_bytes: usize = headers
+            .iter() // when compressed, chunks are smaller, but never larger than max
+            .map(|header| header.max_pixel_file_bytes())
+            .sum();
+
+        chunks_start_byte + max_pixel_bytes
+    };
 
     // check that each offset is within the bounds
-    let end_byte = chunks_start_byte + max_pixel_bytes;
     let is_invalid = offset_tables
         .iter()
         .flatten()
@@ -195,6 +242,19 @@ pub struct AllChunksReader<R> {
     pedantic: bool,
 }
 
+/// Read and decompress single tiles of the file on demand, in any order.
+/// Create this reader by calling `tiled_random_access` on a `Reader`.
+/// Only the offset tables are kept in memory,
+/// which makes it suitable for streaming textures into a renderer.
+/// Also contains the image meta data.
+#[derive(Debug)]
+pub struct TiledRandomAccess<R> {
+    meta_data: MetaData,
+    chunk_offsets: Vec<HashMap<TileCoordinates, u64>>,
+    remaining_bytes: PeekRead<Tracking<R>>,
+    pedantic: bool,
+}
+
 /// Decode chunks in the file without seeking.
 /// Calls the supplied closure for each chunk.
 /// The decoded chunks can be decompressed by calling
@@ -205,6 +265,22 @@ pub struct OnProgressChunksReader<R, F> {
     chunks_reader: R,
     decoded_chunks: usize,
     callback: F,
+    cancellation: Option<CancellationToken>,
+}
+
+/// Decode chunks in the file on a dedicated thread,
+/// while the previously decoded chunks are being decompressed.
+/// Create this reader by calling `prefetch` on another chunks reader.
+/// The decoded chunks can be decompressed by calling
+/// `decompress_parallel`, `decompress_sequential`, or `sequential_decompressor`.
+/// Also contains the image meta data.
+#[derive(Debug)]
+pub struct PrefetchingChunksReader {
+    meta_data: MetaData,
+    expected_chunk_count: usize,
+    remaining_chunk_count: usize,
+    receiver: std::sync::mpsc::Receiver<Result<Chunk>>,
+    reading_thread: Option<std::thread::JoinHandle<()>>,
 }
 
 /// Decode chunks in the file.
@@ -244,9 +320,21 @@ pub trait ChunksReader: Sized + Iterator<Item = Result<Chunk>> + ExactSizeIterat
             chunks_reader: self,
             callback: on_progress,
             decoded_chunks: 0,
+            cancellation: None,
         }
     }
 
+    /// Create a new reader that reads the chunks on a dedicated thread.
+    /// While the current chunk is being decompressed, the next chunk is already read from the file.
+    /// This hides the latency of slow byte sources, such as files on network file systems.
+    /// Returns an error if the thread cannot be created.
+    fn prefetch(self) -> Result<PrefetchingChunksReader>
+    where
+        Self: Send + 'static,
+    {
+        PrefetchingChunksReader::new(self)
+    }
+
     #[cfg(feature = "rayon")]
     /// Decompress all blocks in the file, using multiple cpu cores, and call the supplied closure for each block.
     /// The order of the blocks is not deterministic.
@@ -263,9 +351,15 @@ pub trait ChunksReader: Sized + Iterator<Item = Result<Chunk>> + ExactSizeIterat
             Ok(decompressor) => decompressor,
         };
 
-        while let Some(block) = decompressor.next() {
-            insert_block(decompressor.meta_data(), block?)?;
-        }
+        // the inserted blocks return their bytes to the pool of the decompressor
+        let buffers = decompressor.buffers.clone();
+        buffers.install(|| -> UnitResult {
+            while let Some(block) = decompressor.next() {
+                insert_block(decompressor.meta_data(), block?)?;
+            }
+
+            Ok(())
+        })?;
 
         debug_assert_eq!(
             decompressor.len(),
@@ -317,6 +411,16 @@ pub trait ChunksReader: Sized + Iterator<Item = Result<Chunk>> + ExactSizeIterat
     }
 }
 
+impl<R, F> OnProgressChunksReader<R, F> {
+    /// Stop reading with `Error::Aborted` before the next chunk, once the token has been cancelled.
+    pub fn cancel_with(self, cancellation: Option<CancellationToken>) -> Self {
+        Self {
+            cancellation,
+            ..self
+        }
+    }
+}
+
 impl<R, F> ChunksReader for OnProgressChunksReader<R, F>
 where
     R: ChunksReader,
@@ -344,6 +448,12 @@ where
     type Item = Result<Chunk>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(cancellation) = &self.cancellation {
+            if let Err(aborted) = cancellation.check() {
+                return Some(Err(aborted));
+            }
+        }
+
         self.chunks_reader
             .next()
             .map(|item| {
@@ -374,6 +484,91 @@ where
     }
 }
 
+impl PrefetchingChunksReader {
+    /// The number of decoded chunks that wait to be decompressed.
+    /// One more chunk is decoded by the reading thread while these are waiting,
+    /// so that two chunks are always ready.
+    const READ_AHEAD_CHUNK_COUNT: usize = 1;
+
+    /// Start reading the chunks on a dedicated thread.
+    pub fn new<R>(mut chunks_reader: R) -> Result<Self>
+    where
+        R: ChunksReader + Send + 'static,
+    {
+        let meta_data = chunks_reader.meta_data().clone();
+        let expected_chunk_count = chunks_reader.expected_chunk_count();
+        let remaining_chunk_count = chunks_reader.len();
+
+        let (sender, receiver) = std::sync::mpsc::sync_channel(Self::READ_AHEAD_CHUNK_COUNT);
+
+        let reading_thread = std::thread::Builder::new()
+            .name(String::from("OpenEXR Chunk Reader"))
+            .spawn(move || {
+                while let Some(chunk) = chunks_reader.next() {
+                    let is_error = chunk.is_err();
+
+                    // stop reading if the chunks are no longer needed
+                    if sender.send(chunk).is_err() || is_error {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            meta_data,
+            expected_chunk_count,
+            remaining_chunk_count,
+            receiver,
+            reading_thread: Some(reading_thread),
+        })
+    }
+}
+
+impl ChunksReader for PrefetchingChunksReader {
+    fn meta_data(&self) -> &MetaData {
+        &self.meta_data
+    }
+    fn expected_chunk_count(&self) -> usize {
+        self.expected_chunk_count
+    }
+}
+
+impl ExactSizeIterator for PrefetchingChunksReader {}
+impl Iterator for PrefetchingChunksReader {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.receiver.recv() {
+            Ok(chunk) => {
+                self.remaining_chunk_count = self.remaining_chunk_count.saturating_sub(1);
+
+                // no more chunks will be read after an error
+                if chunk.is_err() {
+                    self.remaining_chunk_count = 0;
+                }
+
+                Some(chunk)
+            }
+
+            // the thread has finished reading all chunks, or it has panicked
+            Err(_) => {
+                if let Some(thread) = self.reading_thread.take() {
+                    if let Err(panic) = thread.join() {
+                        std::panic::resume_unwind(panic);
+                    }
+                }
+
+                self.remaining_chunk_count = 0;
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining_chunk_count, Some(self.remaining_chunk_count))
+    }
+}
+
 impl<R: Read + Seek> ChunksReader for AllChunksReader<R> {
     fn meta_data(&self) -> &MetaData {
         &self.meta_data
@@ -448,6 +643,65 @@ impl<R: Read + Seek> Iterator for FilteredChunksReader<R> {
     }
 }
 
+impl<R: Read + Seek> TiledRandomAccess<R> {
+    /// The decoded exr meta data from the file.
+    pub fn meta_data(&self) -> &MetaData {
+        &self.meta_data
+    }
+
+    /// The decoded exr headers from the file.
+    pub fn headers(&self) -> &[Header] {
+        &self.meta_data.headers
+    }
+
+    /// Obtain the meta data ownership.
+    pub fn into_meta_data(self) -> MetaData {
+        self.meta_data
+    }
+
+    /// Whether the layer contains a tile at the specified tile index and resolution level.
+    pub fn contains_tile(&self, layer_index: usize, tile: TileCoordinates) -> bool {
+        self.chunk_offsets
+            .get(layer_index)
+            .map_or(false, |offsets| offsets.contains_key(&tile))
+    }
+
+    /// Read the compressed chunk of a single tile, seeking to its position in the file.
+    /// Returns an error if the layer has no tile at these coordinates.
+    pub fn read_chunk(&mut self, layer_index: usize, tile: TileCoordinates) -> Result<Chunk> {
+        let offset = *self
+            .chunk_offsets
+            .get(layer_index)
+            .ok_or(Error::invalid("layer index"))?
+            .get(&tile)
+            .ok_or(Error::invalid("tile coordinates"))?;
+
+        self.remaining_bytes.skip_to(u64_to_usize(offset, "chunk start")?)?;
+        let chunk = Chunk::read(&mut self.remaining_bytes, &self.meta_data)?;
+
+        // the offset table may point to a different chunk than expected
+        let header = &self.meta_data.headers[layer_index];
+        if chunk.layer_index != layer_index
+            || header.get_block_data_indices(&chunk.compressed_block)? != tile
+        {
+            return Err(Error::invalid("chunk offset table"));
+        }
+
+        Ok(chunk)
+    }
+
+    /// Read and decompress a single tile, seeking to its position in the file.
+    /// Returns an error if the layer has no tile at these coordinates.
+    pub fn read_tile(
+        &mut self,
+        layer_index: usize,
+        tile: TileCoordinates,
+    ) -> Result<UncompressedBlock> {
+        let chunk = self.read_chunk(layer_index, tile)?;
+        UncompressedBlock::decompress_chunk(chunk, &self.meta_data, self.pedantic)
+    }
+}
+
 /// Read all chunks from the file, decompressing each chunk immediately.
 /// Implements iterator.
 #[derive(Debug)]
@@ -494,6 +748,9 @@ pub struct ParallelBlockDecompressor<R: ChunksReader> {
     pedantic: bool,
 
     pool: rayon_core::ThreadPool,
+
+    /// Shared by the reading thread and the decompressing threads.
+    buffers: std::sync::Arc<crate::block::pool::BufferPool>,
 }
 
 #[cfg(feature = "rayon")]
@@ -559,13 +816,18 @@ impl<R: ChunksReader> ParallelBlockDecompressor<R> {
             max_threads,
 
             pool,
+
+            // each block in flight requires a few buffers
+            buffers: std::sync::Arc::new(crate::block::pool::BufferPool::new(max_threads * 4)),
         })
     }
 
     /// Fill the pool with decompression jobs. Returns the first job that finishes.
     pub fn decompress_next_block(&mut self) -> Option<Result<UncompressedBlock>> {
         while self.currently_decompressing_count < self.max_threads {
-            let block = self.remaining_chunks.next();
+            let buffers = self.buffers.clone();
+            let block = buffers.install(|| self.remaining_chunks.next());
+
             if let Some(block) = block {
                 let block = match block {
                     Ok(block) => block,
@@ -574,13 +836,14 @@ impl<R: ChunksReader> ParallelBlockDecompressor<R> {
 
                 let sender = self.sender.clone();
                 let meta = self.shared_meta_data_ref.clone();
+                let buffers = self.buffers.clone();
                 let pedantic = self.pedantic;
 
                 self.currently_decompressing_count += 1;
 
                 self.pool.spawn(move || {
-                    let decompressed_or_err =
-                        UncompressedBlock::decompress_chunk(block, &meta, pedantic);
+                    let decompressed_or_err = buffers
+                        .install(|| UncompressedBlock::decompress_chunk(block, &meta, pedantic));
 
                     // by now, decompressing could have failed in another thread.
                     // the error is then already handled, so we simply
@@ -239,6 +239,23 @@ impl FromNativeSample for u32 {
     fn from_u32(value: u32) -> Self {
         value
     }
+
+    // convert the half floats to floats in batches first,
+    // such that only the conversion to integers happens one value at a time
+    #[inline]
+    fn from_f16s(from: &[f16], to: &mut [Self]) {
+        assert_eq!(from.len(), to.len(), "slices must have the same length");
+        let mut floats = [0.0_f32; 16];
+
+        for (from, to) in from.chunks(floats.len()).zip(to.chunks_mut(floats.len())) {
+            let floats = &mut floats[..from.len()];
+            from.convert_to_f32_slice(floats);
+
+            for (to, &float) in to.iter_mut().zip(floats.iter()) {
+                *to = float as u32;
+            }
+        }
+    }
 }
 
 impl FromNativeSample for f16 {
@@ -339,3 +356,27 @@ impl IntoNativeSample for Sample {
         Sample::to_u32(*self)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_half_float_slices() {
+        // more than one batch, with a remainder
+        let halves: Vec<f16> = (0..37)
+            .map(|value| f16::from_f32(value as f32 * 1.5))
+            .collect();
+
+        let mut floats = vec![0.0_f32; halves.len()];
+        f32::from_f16s(&halves, &mut floats);
+
+        let mut integers = vec![0_u32; halves.len()];
+        u32::from_f16s(&halves, &mut integers);
+
+        for (index, half) in halves.iter().enumerate() {
+            assert_eq!(floats[index], half.to_f32());
+            assert_eq!(integers[index], u32::from_f16(*half));
+        }
+    }
+}
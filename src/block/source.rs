@@ -0,0 +1,434 @@
+//! Read exr files from byte sources that fetch ranges of bytes on request,
+//! such as object stores, HTTP range requests, or an in-memory asset cache.
+//!
+//! Implement [`ChunkSource`] for the byte source, and wrap it in a [`SourceRead`].
+//! The `SourceRead` can be passed to every reader of this crate that requires `Read + Seek`.
+//! Because the readers seek to the chunks using the offset tables,
+//! only the meta data, the offset tables, and the chunks that are actually decoded are fetched.
+//! For example, reading a single tile with `tiled_random_access`,
+//! or the first layer of a multi-layer file, skips the bytes of all other chunks.
+//!
+//! ```ignore
+//! struct Remote { client: Client, url: String }
+//!
+//! impl ChunkSource for Remote {
+//!     fn byte_size(&mut self) -> std::io::Result<u64> {
+//!         self.client.head(&self.url).content_length()
+//!     }
+//!
+//!     fn read_byte_range(&mut self, range: Range<u64>) -> std::io::Result<Vec<u8>> {
+//!         self.client.get_range(&self.url, range)
+//!     }
+//! }
+//!
+//! let image = read().no_deep_data().largest_resolution_level().all_channels()
+//!     .first_valid_layer().all_attributes().from_source(Remote::new(url))?;
+//! ```
+
+use crate::error::{IoError, IoResult};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+/// The number of bytes that a [`SourceRead`] fetches at once, unless a single read requests more.
+pub const DEFAULT_FETCH_SIZE: usize = 64 * 1024;
+
+/// A byte source that can read any range of the bytes of an exr file.
+/// Implemented for files, byte slices, and any seekable reader wrapped in a [`SeekSource`].
+pub trait ChunkSource {
+    /// The number of bytes of the whole file.
+    fn byte_size(&mut self) -> IoResult<u64>;
+
+    /// Read the bytes at this range of the file, which is never outside of the file.
+    /// Must return all bytes of the range.
+    fn read_byte_range(&mut self, range: Range<u64>) -> IoResult<Vec<u8>>;
+
+    /// Read the first bytes of the file, which contain the meta data and the offset tables.
+    /// The byte count never exceeds the byte size of the file.
+    /// Every reader requests these bytes first,
+    /// so this can be overridden to serve them from a cache.
+    fn read_header_bytes(&mut self, byte_count: usize) -> IoResult<Vec<u8>> {
+        self.read_byte_range(0..byte_count as u64)
+    }
+}
+
+impl ChunkSource for File {
+    fn byte_size(&mut self) -> IoResult<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn read_byte_range(&mut self, range: Range<u64>) -> IoResult<Vec<u8>> {
+        read_range(self, range)
+    }
+}
+
+impl ChunkSource for &[u8] {
+    fn byte_size(&mut self) -> IoResult<u64> {
+        Ok(self.len() as u64)
+    }
+
+    fn read_byte_range(&mut self, range: Range<u64>) -> IoResult<Vec<u8>> {
+        let start = usize::try_from(range.start).map_err(|_| missing_bytes())?;
+        let end = usize::try_from(range.end).map_err(|_| missing_bytes())?;
+        self.get(start..end)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(missing_bytes)
+    }
+}
+
+impl<S: ChunkSource + ?Sized> ChunkSource for &mut S {
+    fn byte_size(&mut self) -> IoResult<u64> {
+        (**self).byte_size()
+    }
+
+    fn read_byte_range(&mut self, range: Range<u64>) -> IoResult<Vec<u8>> {
+        (**self).read_byte_range(range)
+    }
+
+    fn read_header_bytes(&mut self, byte_count: usize) -> IoResult<Vec<u8>> {
+        (**self).read_header_bytes(byte_count)
+    }
+}
+
+/// Reads the byte ranges of a seekable reader, like a `Cursor` or a `BufReader`.
+#[derive(Debug)]
+pub struct SeekSource<R> {
+    inner: R,
+}
+
+impl<R: Read + Seek> SeekSource<R> {
+    /// Read the byte ranges from this reader, at any position.
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    /// Obtain the ownership of the reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read + Seek> ChunkSource for SeekSource<R> {
+    fn byte_size(&mut self) -> IoResult<u64> {
+        self.inner.seek(SeekFrom::End(0))
+    }
+
+    fn read_byte_range(&mut self, range: Range<u64>) -> IoResult<Vec<u8>> {
+        read_range(&mut self.inner, range)
+    }
+}
+
+/// Reads and seeks the bytes of a [`ChunkSource`], fetching only the byte ranges that are read.
+/// Small reads are served from the most recently fetched range,
+/// and a read larger than the fetch size, like the bytes of a large chunk, is fetched at once.
+/// Seeking does not fetch any bytes.
+#[derive(Debug)]
+pub struct SourceRead<S> {
+    source: S,
+    byte_size: u64,
+    position: u64,
+    fetch_size: usize,
+
+    /// The most recently fetched bytes, which start at `fetched_start` in the file.
+    fetched: Vec<u8>,
+    fetched_start: u64,
+}
+
+impl<S: ChunkSource> SourceRead<S> {
+    /// Read from the start of the source, fetching [`DEFAULT_FETCH_SIZE`] bytes at once.
+    /// Requests the byte size of the source, but no bytes yet.
+    pub fn new(mut source: S) -> IoResult<Self> {
+        Ok(Self {
+            byte_size: source.byte_size()?,
+            source,
+            position: 0,
+            fetch_size: DEFAULT_FETCH_SIZE,
+            fetched: Vec::new(),
+            fetched_start: 0,
+        })
+    }
+
+    /// Fetch at least this many bytes at once.
+    /// Larger sizes need fewer requests, smaller sizes fetch fewer unused bytes.
+    pub fn with_fetch_size(self, fetch_size: usize) -> Self {
+        Self {
+            fetch_size: fetch_size.max(1),
+            ..self
+        }
+    }
+
+    /// The number of bytes of the whole file.
+    pub fn byte_size(&self) -> u64 {
+        self.byte_size
+    }
+
+    /// Obtain the ownership of the source.
+    pub fn into_source(self) -> S {
+        self.source
+    }
+
+    /// The fetched bytes from the current position on, which may be empty.
+    fn fetched_remaining(&self) -> &[u8] {
+        self.position
+            .checked_sub(self.fetched_start)
+            .and_then(|offset| usize::try_from(offset).ok())
+            .and_then(|offset| self.fetched.get(offset..))
+            .unwrap_or(&[])
+    }
+
+    /// Replace the fetched bytes with at least `byte_count` bytes at the current position,
+    /// or fewer at the end of the file.
+    fn fetch(&mut self, byte_count: usize) -> IoResult<()> {
+        let remaining = self.byte_size - self.position;
+        let byte_count = (byte_count.max(self.fetch_size) as u64).min(remaining);
+        let range = self.position..self.position + byte_count;
+
+        let bytes = if self.position == 0 {
+            self.source.read_header_bytes(byte_count as usize)?
+        } else {
+            self.source.read_byte_range(range)?
+        };
+
+        if bytes.len() as u64 != byte_count {
+            return Err(missing_bytes());
+        }
+
+        self.fetched = bytes;
+        self.fetched_start = self.position;
+        Ok(())
+    }
+}
+
+impl<S: ChunkSource> Read for SourceRead<S> {
+    fn read(&mut self, buffer: &mut [u8]) -> IoResult<usize> {
+        if buffer.is_empty() || self.position >= self.byte_size {
+            return Ok(0);
+        }
+
+        if self.fetched_remaining().is_empty() {
+            self.fetch(buffer.len())?;
+        }
+
+        let fetched = self.fetched_remaining();
+        let count = fetched.len().min(buffer.len());
+        buffer[..count].copy_from_slice(&fetched[..count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<S: ChunkSource> Seek for SourceRead<S> {
+    fn seek(&mut self, target: SeekFrom) -> IoResult<u64> {
+        let position = match target {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(offset) => offset_position(self.byte_size, offset),
+            SeekFrom::Current(offset) => offset_position(self.position, offset),
+        };
+
+        self.position = position
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "seek to a negative position"))?;
+
+        Ok(self.position)
+    }
+}
+
+/// Add a signed offset to a position, or `None` if the result is negative.
+fn offset_position(position: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        position.checked_sub(offset.unsigned_abs())
+    } else {
+        position.checked_add(offset as u64)
+    }
+}
+
+/// Seek to the start of the range and read all of its bytes.
+fn read_range(read: &mut (impl Read + Seek), range: Range<u64>) -> IoResult<Vec<u8>> {
+    let byte_count = range.end.saturating_sub(range.start);
+    let mut bytes = vec![0_u8; usize::try_from(byte_count).map_err(|_| missing_bytes())?];
+    read.seek(SeekFrom::Start(range.start))?;
+    read.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// The error of a range outside of the source, which is converted to an invalid file error.
+fn missing_bytes() -> IoError {
+    IoError::new(ErrorKind::UnexpectedEof, "byte range outside of the source")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block::chunk::TileCoordinates;
+    use crate::block::reader::Reader;
+    use crate::math::Vec2;
+    use crate::prelude::*;
+    use std::io::Cursor;
+
+    /// Records the byte ranges that are requested from the bytes.
+    struct Recording<'b> {
+        bytes: &'b [u8],
+        ranges: Vec<Range<u64>>,
+    }
+
+    impl ChunkSource for Recording<'_> {
+        fn byte_size(&mut self) -> IoResult<u64> {
+            self.bytes.byte_size()
+        }
+
+        fn read_byte_range(&mut self, range: Range<u64>) -> IoResult<Vec<u8>> {
+            self.ranges.push(range.clone());
+            self.bytes.read_byte_range(range)
+        }
+    }
+
+    impl Recording<'_> {
+        fn fetched_byte_count(&self) -> u64 {
+            self.ranges
+                .iter()
+                .map(|range| range.end - range.start)
+                .sum()
+        }
+    }
+
+    fn tiled_file_bytes() -> Vec<u8> {
+        let size = Vec2(256, 256);
+        let samples = FlatSamples::F32((0..size.area()).map(|index| index as f32).collect());
+        let layer = Layer::new(
+            size,
+            LayerAttributes::named("source"),
+            Encoding {
+                compression: Compression::Uncompressed,
+                blocks: Blocks::Tiles(Vec2(32, 32)),
+                line_order: LineOrder::Increasing,
+            },
+            AnyChannels::sort(smallvec::smallvec![AnyChannel::new("Y", samples)]),
+        );
+
+        let mut bytes = Vec::new();
+        Image::from_layer(layer)
+            .write()
+            .to_buffered(Cursor::new(&mut bytes))
+            .unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn read_image_from_sources() {
+        let bytes = tiled_file_bytes();
+        let reader = || {
+            read()
+                .no_deep_data()
+                .largest_resolution_level()
+                .all_channels()
+                .first_valid_layer()
+                .all_attributes()
+        };
+
+        let expected = reader().from_buffered(Cursor::new(&bytes)).unwrap();
+
+        let slice = reader().from_source(bytes.as_slice()).unwrap();
+        assert_eq!(slice, expected);
+
+        let seek = reader()
+            .from_source(SeekSource::new(Cursor::new(&bytes)))
+            .unwrap();
+        assert_eq!(seek, expected);
+
+        let path = std::env::temp_dir().join(format!("exrs_source_{}.exr", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let file = reader().from_source(File::open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(file.unwrap(), expected);
+
+        // a large fetch size fetches the whole file at once
+        let mut recording = Recording {
+            bytes: &bytes,
+            ranges: Vec::new(),
+        };
+        let source = SourceRead::new(&mut recording)
+            .unwrap()
+            .with_fetch_size(1 << 20);
+        assert_eq!(reader().from_buffered(source).unwrap(), expected);
+        assert_eq!(recording.ranges, vec![0..bytes.len() as u64]);
+    }
+
+    #[test]
+    fn fetch_only_the_read_tile() {
+        let bytes = tiled_file_bytes();
+        let tile = TileCoordinates {
+            tile_index: Vec2(3, 2),
+            level_index: Vec2(0, 0),
+        };
+
+        let expected = Reader::read_from_buffered(Cursor::new(&bytes), true)
+            .unwrap()
+            .tiled_random_access(true)
+            .unwrap()
+            .read_tile(0, tile)
+            .unwrap();
+
+        let mut recording = Recording {
+            bytes: &bytes,
+            ranges: Vec::new(),
+        };
+        let source = SourceRead::new(&mut recording)
+            .unwrap()
+            .with_fetch_size(1024);
+        let block = Reader::read_from_buffered(source, true)
+            .unwrap()
+            .tiled_random_access(true)
+            .unwrap()
+            .read_tile(0, tile)
+            .unwrap();
+
+        assert_eq!(block.data, expected.data);
+
+        // the meta data, the offset table, and the 4 KiB of the tile
+        let fetched = recording.fetched_byte_count();
+        assert!(
+            fetched < bytes.len() as u64 / 16,
+            "{} of {}",
+            fetched,
+            bytes.len()
+        );
+        assert_eq!(recording.ranges[0].start, 0);
+    }
+
+    #[test]
+    fn ranges_outside_of_the_source() {
+        let bytes = tiled_file_bytes();
+        let mut slice = bytes.as_slice();
+        assert_eq!(slice.read_byte_range(4..8).unwrap(), &bytes[4..8]);
+        assert!(slice.read_byte_range(8..bytes.len() as u64 + 1).is_err());
+
+        let mut source = SourceRead::new(&bytes[..]).unwrap();
+        assert_eq!(
+            source.seek(SeekFrom::End(-4)).unwrap(),
+            bytes.len() as u64 - 4
+        );
+        assert!(source
+            .seek(SeekFrom::Current(-(bytes.len() as i64)))
+            .is_err());
+
+        let mut end = Vec::new();
+        source.read_to_end(&mut end).unwrap();
+        assert_eq!(end, &bytes[bytes.len() - 4..]);
+
+        // a truncated file
+        let truncated = &bytes[..bytes.len() / 2];
+        let result = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_source(Recording {
+                bytes: truncated,
+                ranges: Vec::new(),
+            });
+
+        assert!(result.is_err());
+    }
+}
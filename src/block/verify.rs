@@ -0,0 +1,429 @@
+//! Check the integrity of a file by decoding every chunk, independently of all other chunks.
+//! Also computes a checksum of the bytes of each chunk, which can be stored
+//! next to archived files to detect later corruption.
+
+use crate::block::chunk::{
+    Chunk, CompressedBlock, CompressedDeepScanLineBlock, CompressedDeepTileBlock,
+    CompressedScanLineBlock, CompressedTileBlock, TileCoordinates,
+};
+use crate::block::deep::decompress_deep_chunk;
+use crate::block::limits::ReadLimits;
+use crate::block::UncompressedBlock;
+use crate::error::{Error, Result, UnitResult};
+use crate::io::{Data, PeekRead, Tracking};
+use crate::meta::attribute::IntegerBounds;
+use crate::meta::header::Header;
+use crate::meta::MetaData;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The result of checking all chunks of a file.
+#[derive(Debug)]
+pub struct Verification {
+    /// The headers of the file.
+    pub meta_data: MetaData,
+
+    /// One report for each entry in the offset tables, ordered by part and by increasing y coordinate.
+    pub chunks: Vec<ChunkReport>,
+}
+
+/// The result of checking a single chunk.
+#[derive(Debug)]
+pub struct ChunkReport {
+    /// The index of the part that the offset table entry belongs to.
+    pub layer_index: usize,
+
+    /// The index of the entry in the offset table of the part.
+    pub chunk_index: usize,
+
+    /// Where the chunk starts in the file, as stored in the offset table.
+    pub byte_offset: u64,
+
+    /// The expected tile or scan line block, derived from the position in the offset table.
+    pub coordinates: TileCoordinates,
+
+    /// The number of bytes of the chunk, or zero if the chunk could not be read.
+    pub byte_size: usize,
+
+    /// The CRC-32 of the bytes of the chunk, or `None` if the chunk could not be read.
+    pub checksum: Option<u32>,
+
+    /// Why the chunk is invalid, or `None` if the chunk could be decompressed.
+    pub error: Option<Error>,
+}
+
+impl Verification {
+    /// Whether all chunks could be decompressed.
+    pub fn is_valid(&self) -> bool {
+        self.chunks.iter().all(|chunk| chunk.error.is_none())
+    }
+
+    /// The reports of all invalid chunks.
+    pub fn errors(&self) -> impl Iterator<Item = &ChunkReport> {
+        self.chunks.iter().filter(|chunk| chunk.error.is_some())
+    }
+}
+
+/// Read and decompress every chunk of the file, reporting the errors of each chunk separately.
+/// Validates that the offset tables point inside the file, that no two entries point to the same chunk,
+/// that each chunk contains the block expected at its position in the offset table,
+/// and that the sizes declared in each chunk fit the block and do not overlap the next chunk.
+///
+/// Returns an error only if the headers or the offset tables cannot be read at all.
+pub fn verify_file(path: impl AsRef<Path>) -> Result<Verification> {
+    verify(BufReader::new(File::open(path)?))
+}
+
+/// Read and decompress every chunk of the buffered byte source,
+/// which must be positioned at the start of the file. See `verify_file`.
+pub fn verify(mut file: impl Read + Seek) -> Result<Verification> {
+    let file_size = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let (meta_data, offset_tables, chunks_start) = {
+        let mut read = PeekRead::new(Tracking::new(&mut file));
+        let meta_data = MetaData::read_validated_from_buffered_peekable(&mut read, false)?;
+        let offset_tables = MetaData::read_offset_tables(&mut read, &meta_data.headers)?;
+        (meta_data, offset_tables, read.byte_position() as u64)
+    };
+
+    // a chunk must end before the next chunk starts
+    let mut sorted_offsets: Vec<u64> = offset_tables.iter().flatten().copied().collect();
+    sorted_offsets.sort_unstable();
+    sorted_offsets.dedup();
+
+    let mut chunks = Vec::with_capacity(offset_tables.iter().map(Vec::len).sum());
+    let mut first_use_of_offset: HashMap<u64, (usize, usize)> = HashMap::new();
+
+    for (layer_index, (header, offset_table)) in
+        meta_data.headers.iter().zip(&offset_tables).enumerate()
+    {
+        let expected_blocks: Vec<TileCoordinates> = header
+            .blocks_increasing_y_order()
+            .map(|tile| tile.location)
+            .collect();
+
+        for (chunk_index, &byte_offset) in offset_table.iter().enumerate() {
+            let coordinates = match expected_blocks.get(chunk_index) {
+                Some(&coordinates) => coordinates,
+                None => return Err(Error::invalid("offset table size")),
+            };
+
+            let mut report = ChunkReport {
+                layer_index,
+                chunk_index,
+                byte_offset,
+                coordinates,
+                byte_size: 0,
+                checksum: None,
+                error: None,
+            };
+
+            if byte_offset < chunks_start || byte_offset >= file_size {
+                report.error = Some(Error::invalid("chunk offset outside of the file"));
+                chunks.push(report);
+                continue;
+            }
+
+            if let Some(&(other_layer, other_chunk)) = first_use_of_offset.get(&byte_offset) {
+                report.error = Some(Error::invalid(format!(
+                    "chunk offset also used by chunk {other_chunk} of part {other_layer}"
+                )));
+                chunks.push(report);
+                continue;
+            }
+
+            first_use_of_offset.insert(byte_offset, (layer_index, chunk_index));
+
+            let result = file
+                .seek(SeekFrom::Start(byte_offset))
+                .map_err(Error::from)
+                .and_then(|_| {
+                    let mut read = ChecksumRead::new(&mut file);
+                    let chunk = Chunk::read(&mut read, &meta_data)?;
+                    report.byte_size = read.byte_count;
+                    report.checksum = Some(read.crc.finish());
+                    Ok(chunk)
+                })
+                .and_then(|chunk| {
+                    let next_index =
+                        sorted_offsets.partition_point(|&offset| offset <= byte_offset);
+                    let end = byte_offset + report.byte_size as u64;
+
+                    match sorted_offsets.get(next_index) {
+                        Some(&next_offset) if end > next_offset => {
+                            Err(Error::invalid("chunk overlaps the following chunk"))
+                        }
+                        _ => Ok(chunk),
+                    }
+                })
+                .and_then(|chunk| verify_chunk(chunk, &meta_data, layer_index, coordinates));
+
+            if let Err(error) = result {
+                report.error = Some(error);
+            }
+
+            chunks.push(report);
+        }
+    }
+
+    Ok(Verification { meta_data, chunks })
+}
+
+/// Check that the chunk belongs to the expected part and block, and decompress it.
+fn verify_chunk(
+    chunk: Chunk,
+    meta_data: &MetaData,
+    layer_index: usize,
+    expected: TileCoordinates,
+) -> Result<()> {
+    if chunk.layer_index != layer_index {
+        return Err(Error::invalid(format!(
+            "chunk belongs to part {} instead of part {layer_index}",
+            chunk.layer_index
+        )));
+    }
+
+    let header = &meta_data.headers[layer_index];
+    let coordinates = header.get_block_data_indices(&chunk.compressed_block)?;
+
+    if coordinates != expected {
+        return Err(Error::invalid(
+            "chunk contains a different block than its offset table entry",
+        ));
+    }
+
+    let section = header.get_absolute_block_pixel_coordinates(coordinates)?;
+    verify_declared_sizes(&chunk.compressed_block, header, section)?;
+
+    match chunk.compressed_block {
+        CompressedBlock::DeepScanLine(_) | CompressedBlock::DeepTile(_) => {
+            decompress_deep_chunk(
+                &chunk.compressed_block,
+                meta_data,
+                layer_index,
+                None,
+                true,
+                &ReadLimits::UNLIMITED,
+            )?;
+        }
+
+        CompressedBlock::ScanLine(_) | CompressedBlock::Tile(_) => {
+            UncompressedBlock::decompress_chunk(chunk, meta_data, true)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the byte sizes stored in the chunk against the size of its block.
+/// Compressed data is never larger than the uncompressed data,
+/// because blocks that do not get smaller are stored uncompressed.
+fn verify_declared_sizes(
+    block: &CompressedBlock,
+    header: &Header,
+    section: IntegerBounds,
+) -> UnitResult {
+    let too_large = |name: &str, size: usize, max: usize| {
+        Err(Error::invalid(format!(
+            "chunk declares {size} bytes of {name}, but the block has at most {max} bytes"
+        )))
+    };
+
+    match block {
+        CompressedBlock::ScanLine(CompressedScanLineBlock {
+            compressed_pixels_le,
+            ..
+        })
+        | CompressedBlock::Tile(CompressedTileBlock {
+            compressed_pixels_le,
+            ..
+        }) => {
+            let max = header.channels.byte_size_of_section(section);
+            if compressed_pixels_le.len() > max {
+                return too_large("pixel data", compressed_pixels_le.len(), max);
+            }
+        }
+
+        CompressedBlock::DeepScanLine(CompressedDeepScanLineBlock {
+            compressed_pixel_offset_table,
+            compressed_sample_data_le,
+            decompressed_sample_data_size,
+            ..
+        })
+        | CompressedBlock::DeepTile(CompressedDeepTileBlock {
+            compressed_pixel_offset_table,
+            compressed_sample_data_le,
+            decompressed_sample_data_size,
+            ..
+        }) => {
+            let max_table_size = section.size.area() * i32::BYTE_SIZE;
+            if compressed_pixel_offset_table.len() > max_table_size {
+                return too_large(
+                    "pixel offset table",
+                    compressed_pixel_offset_table.len(),
+                    max_table_size,
+                );
+            }
+
+            if compressed_sample_data_le.len() > *decompressed_sample_data_size {
+                return too_large(
+                    "sample data",
+                    compressed_sample_data_le.len(),
+                    *decompressed_sample_data_size,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes the checksum of all bytes that are read.
+struct ChecksumRead<R> {
+    inner: R,
+    crc: Crc32,
+    byte_count: usize,
+}
+
+impl<R: Read> ChecksumRead<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            crc: Crc32::new(),
+            byte_count: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for ChecksumRead<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buffer)?;
+        self.crc.update(&buffer[..count]);
+        self.byte_count += count;
+        Ok(count)
+    }
+}
+
+/// The CRC-32 checksum used by zip and png files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc32 {
+    state: u32,
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0_u32; 256];
+
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 {
+                0xEDB8_8320 ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+
+            bit += 1;
+        }
+
+        table[index] = value;
+        index += 1;
+    }
+
+    table
+}
+
+impl Crc32 {
+    /// Start a new checksum.
+    pub fn new() -> Self {
+        Crc32 { state: !0 }
+    }
+
+    /// Add bytes to the checksum.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state =
+                CRC32_TABLE[((self.state ^ byte as u32) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    /// The checksum of all bytes so far.
+    pub fn finish(self) -> u32 {
+        !self.state
+    }
+
+    /// The checksum of the bytes.
+    pub fn of(bytes: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(bytes);
+        crc.finish()
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(Crc32::of(b"123456789"), 0xCBF4_3926);
+        assert_eq!(Crc32::of(b""), 0);
+    }
+
+    #[test]
+    fn valid_file_has_no_errors() {
+        let verification =
+            verify_file("tests/images/valid/openexr/Beachball/multipart.0001.exr").unwrap();
+
+        assert!(verification.is_valid());
+        assert_eq!(
+            verification.chunks.len(),
+            verification
+                .meta_data
+                .headers
+                .iter()
+                .map(|header| header.chunk_count)
+                .sum::<usize>()
+        );
+        assert!(verification
+            .chunks
+            .iter()
+            .all(|chunk| chunk.checksum.is_some()));
+    }
+
+    #[test]
+    fn corrupt_chunk_is_reported() {
+        let original = "tests/images/valid/openexr/Beachball/multipart.0001.exr";
+        let verification = verify_file(original).unwrap();
+
+        let corrupt = std::env::temp_dir().join("exrs_verify_corrupt.exr");
+        let mut bytes = std::fs::read(original).unwrap();
+
+        // overwrite the coordinates of the last chunk
+        let last = verification.chunks.last().unwrap();
+        let start = last.byte_offset as usize;
+        bytes[start..start + 8].copy_from_slice(&[0xFF; 8]);
+        std::fs::write(&corrupt, &bytes).unwrap();
+
+        let corrupted = verify_file(&corrupt).unwrap();
+        let errors: Vec<&ChunkReport> = corrupted.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].chunk_index, last.chunk_index);
+        assert_eq!(errors[0].layer_index, last.layer_index);
+
+        std::fs::remove_file(&corrupt).ok();
+    }
+}
@@ -5,13 +5,16 @@ use std::io::Seek;
 use std::iter::Peekable;
 use std::ops::Not;
 
+use crate::block::cancel::CancellationToken;
 use crate::block::chunk::Chunk;
 use crate::block::UncompressedBlock;
 use crate::error::{usize_to_u64, Error, Result, UnitResult};
 use crate::io::{Data, Tracking, Write};
 use crate::meta::attribute::LineOrder;
-use crate::meta::{Headers, MetaData, OffsetTables};
+use crate::meta::header::Header;
+use crate::meta::{Headers, MetaData};
 use smallvec::alloc::collections::BTreeMap;
+use smallvec::SmallVec;
 
 /// Write an exr file by writing one chunk after another in a closure.
 /// In the closure, you are provided a chunk writer, which should be used to write all the chunks.
@@ -40,10 +43,21 @@ pub struct ChunkWriter<W> {
     header_count: usize,
     byte_writer: Tracking<W>,
     chunk_indices_byte_location: std::ops::Range<usize>,
-    chunk_indices_increasing_y: OffsetTables,
+    chunk_offsets: ChunkOffsets,
     chunk_count: usize, // TODO compose?
 }
 
+/// The byte positions of all chunks in the file, in the order of the offset tables.
+/// The tables of all headers are stored in one flat list, just like in the file,
+/// so that they can be serialized and written in a single pass.
+#[derive(Debug, Clone, PartialEq)]
+struct ChunkOffsets {
+    /// The index of the first offset of each header.
+    header_starts: SmallVec<[usize; 3]>,
+    offsets: Vec<u64>,
+    written_count: usize,
+}
+
 /// A new writer that triggers a callback
 /// for each block written to the inner writer.
 #[derive(Debug)]
@@ -52,6 +66,7 @@ pub struct OnProgressChunkWriter<'w, W, F> {
     chunk_writer: &'w mut W,
     written_chunks: usize,
     on_progress: F,
+    cancellation: Option<CancellationToken>,
 }
 
 /// Write chunks to a byte destination.
@@ -75,6 +90,7 @@ pub trait ChunksWriter: Sized {
             chunk_writer: self,
             written_chunks: 0,
             on_progress,
+            cancellation: None,
         }
     }
 
@@ -115,6 +131,31 @@ pub trait ChunksWriter: Sized {
         Ok(())
     }
 
+    #[cfg(feature = "rayon")]
+    /// Compresses all blocks to the file, like `compress_all_blocks_parallel`,
+    /// but keeps at most `max_blocks_in_flight` blocks in memory, and uses as many threads.
+    fn compress_all_blocks_parallel_with_max_blocks_in_flight(
+        mut self,
+        meta: &MetaData,
+        blocks: impl Iterator<Item = (usize, UncompressedBlock)>,
+        max_blocks_in_flight: usize,
+    ) -> UnitResult {
+        let mut parallel_writer = match ParallelBlocksCompressor::new_with_max_blocks_in_flight(
+            meta,
+            &mut self,
+            max_blocks_in_flight,
+        ) {
+            None => return self.compress_all_blocks_sequential(meta, blocks),
+            Some(writer) => writer,
+        };
+
+        for (index_in_header_increasing_y, block) in blocks {
+            parallel_writer.add_block_to_compression_queue(index_in_header_increasing_y, block)?;
+        }
+
+        Ok(())
+    }
+
     #[cfg(feature = "rayon")]
     /// Compresses all blocks to the file.
     /// The index of the block must be in increasing line order within the header.
@@ -154,21 +195,12 @@ where
     /// may remain in an invalid state and should not be used further.
     /// Errors when the chunk at this index was already written.
     fn write_chunk(&mut self, index_in_header_increasing_y: usize, chunk: Chunk) -> UnitResult {
-        let header_chunk_indices = &mut self.chunk_indices_increasing_y[chunk.layer_index];
-
-        if index_in_header_increasing_y >= header_chunk_indices.len() {
-            return Err(Error::invalid("too large chunk index"));
-        }
-
-        let chunk_index_slot = &mut header_chunk_indices[index_in_header_increasing_y];
-        if *chunk_index_slot != 0 {
-            return Err(Error::invalid(format!(
-                "chunk at index {} is already written",
-                index_in_header_increasing_y
-            )));
-        }
+        self.chunk_offsets.insert(
+            chunk.layer_index,
+            index_in_header_increasing_y,
+            usize_to_u64(self.byte_writer.byte_position(), "seek position")?,
+        )?;
 
-        *chunk_index_slot = usize_to_u64(self.byte_writer.byte_position(), "seek position")?;
         chunk.write(&mut self.byte_writer, self.header_count)?;
         Ok(())
     }
@@ -178,10 +210,10 @@ impl<W> ChunkWriter<W>
 where
     W: Write + Seek,
 {
-    // -- the following functions are private, because they must be called in a strict order --
+    // -- the following functions are not public, because they must be called in a strict order --
 
     /// Writes the meta data and zeroed offset tables as a placeholder.
-    fn new_for_buffered(
+    pub(crate) fn new_for_buffered(
         buffered_byte_writer: W,
         headers: Headers,
         pedantic: bool,
@@ -209,10 +241,7 @@ where
         write.seek_write_to(offset_table_end_byte)?;
 
         let header_count = headers.len();
-        let chunk_indices_increasing_y = headers
-            .iter()
-            .map(|header| vec![0_u64; header.chunk_count])
-            .collect();
+        let chunk_offsets = ChunkOffsets::new(headers.as_slice());
 
         let meta_data = MetaData {
             requirements,
@@ -226,23 +255,21 @@ where
                 byte_writer: write,
                 chunk_count: offset_table_size,
                 chunk_indices_byte_location: offset_table_start_byte..offset_table_end_byte,
-                chunk_indices_increasing_y,
+                chunk_offsets,
             },
         ))
     }
 
     /// Seek back to the meta data, write offset tables, and flush the byte writer.
     /// Leaves the writer seeked to the middle of the file, therefore we drop it.
-    fn complete_meta_data(mut self) -> UnitResult {
-        if self
-            .chunk_indices_increasing_y
-            .iter()
-            .flatten()
-            .any(|&index| index == 0)
-        {
+    pub(crate) fn complete_meta_data(mut self) -> UnitResult {
+        if !self.chunk_offsets.is_complete() {
             return Err(Error::invalid("some chunks are not written yet"));
         }
 
+        // serialize all offset tables before seeking back, to write them with a single call
+        let offset_table_bytes = self.chunk_offsets.to_le_bytes();
+
         // write all offset tables
         debug_assert_ne!(
             self.byte_writer.byte_position(),
@@ -252,15 +279,93 @@ where
         self.byte_writer
             .seek_write_to(self.chunk_indices_byte_location.start)?;
 
-        for table in self.chunk_indices_increasing_y {
-            u64::write_slice_le(&mut self.byte_writer, table.as_slice())?;
-        }
+        u8::write_slice_le(&mut self.byte_writer, offset_table_bytes.as_slice())?;
 
         self.byte_writer.flush()?; // make sure we catch all (possibly delayed) io errors before returning
         Ok(())
     }
 }
 
+impl ChunkOffsets {
+    /// Zeroed offset tables for all chunks of the headers.
+    fn new(headers: &[Header]) -> Self {
+        let mut header_starts = SmallVec::with_capacity(headers.len());
+        let mut chunk_count = 0;
+
+        for header in headers {
+            header_starts.push(chunk_count);
+            chunk_count += header.chunk_count;
+        }
+
+        ChunkOffsets {
+            header_starts,
+            offsets: vec![0; chunk_count],
+            written_count: 0,
+        }
+    }
+
+    /// Remember the byte position of a chunk. Errors if the chunk has already been written.
+    fn insert(
+        &mut self,
+        layer_index: usize,
+        index_in_header_increasing_y: usize,
+        byte_position: u64,
+    ) -> UnitResult {
+        let start = *self
+            .header_starts
+            .get(layer_index)
+            .ok_or(Error::invalid("chunk layer index"))?;
+
+        let end = self
+            .header_starts
+            .get(layer_index + 1)
+            .copied()
+            .unwrap_or(self.offsets.len());
+
+        if index_in_header_increasing_y >= end - start {
+            return Err(Error::invalid("too large chunk index"));
+        }
+
+        let chunk_index_slot = &mut self.offsets[start + index_in_header_increasing_y];
+        if *chunk_index_slot != 0 {
+            return Err(Error::invalid(format!(
+                "chunk at index {} is already written",
+                index_in_header_increasing_y
+            )));
+        }
+
+        *chunk_index_slot = byte_position;
+        self.written_count += 1;
+        Ok(())
+    }
+
+    /// Whether the byte positions of all chunks are known.
+    fn is_complete(&self) -> bool {
+        self.written_count == self.offsets.len()
+    }
+
+    /// The little-endian bytes of all offset tables, in the order of the file.
+    fn to_le_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0_u8; self.offsets.len() * u64::BYTE_SIZE];
+
+        for (bytes, offset) in bytes.chunks_exact_mut(u64::BYTE_SIZE).zip(&self.offsets) {
+            bytes.copy_from_slice(&offset.to_le_bytes());
+        }
+
+        bytes
+    }
+}
+
+impl<'w, W, F> OnProgressChunkWriter<'w, W, F> {
+    /// Stop writing with `Error::Aborted` before the next chunk, once the token has been cancelled.
+    pub fn cancel_with(self, cancellation: Option<CancellationToken>) -> Self {
+        Self {
+            cancellation,
+            ..self
+        }
+    }
+}
+
 impl<'w, W, F> ChunksWriter for OnProgressChunkWriter<'w, W, F>
 where
     W: 'w + ChunksWriter,
@@ -271,6 +376,10 @@ where
     }
 
     fn write_chunk(&mut self, index_in_header_increasing_y: usize, chunk: Chunk) -> UnitResult {
+        if let Some(cancellation) = &self.cancellation {
+            cancellation.check()?;
+        }
+
         let total_chunks = self.total_chunks_count();
         let on_progress = &mut self.on_progress;
 
@@ -370,6 +479,11 @@ where
         Ok(())
     }
 
+    /// The number of chunks that wait for a previous chunk before they can be written.
+    pub fn pending_chunk_count(&self) -> usize {
+        self.pending_chunks.len()
+    }
+
     /// Where the chunks will be written to.
     pub fn inner_chunks_writer(&self) -> &W {
         &self.chunk_writer
@@ -447,6 +561,28 @@ where
         })
     }
 
+    /// New blocks writer that keeps at most `max_blocks_in_flight` blocks in memory,
+    /// compressing them on as many threads, regardless of the number of processors.
+    /// This includes compressed blocks that wait for a previous block before they can be written.
+    /// Returns none if sequential compression should be used.
+    pub fn new_with_max_blocks_in_flight(
+        meta: &'w MetaData,
+        chunks_writer: &'w mut W,
+        max_blocks_in_flight: usize,
+    ) -> Option<Self> {
+        let max_blocks_in_flight = max_blocks_in_flight.max(1);
+
+        let mut compressor = Self::new_with_thread_pool(meta, chunks_writer, || {
+            rayon_core::ThreadPoolBuilder::new()
+                .num_threads(max_blocks_in_flight)
+                .thread_name(|index| format!("OpenEXR Block Compressor Thread #{}", index))
+                .build()
+        })?;
+
+        compressor.max_threads = max_blocks_in_flight;
+        Some(compressor)
+    }
+
     /// New blocks writer. Returns none if sequential compression should be used.
     pub fn new_with_thread_pool<CreatePool>(
         meta: &'w MetaData,
@@ -546,8 +682,12 @@ where
         index_in_header_increasing_y: usize,
         block: UncompressedBlock,
     ) -> UnitResult {
-        // if pipe is full, block to wait for a slot to free up
-        if self.currently_compressing_count >= self.max_threads {
+        // if pipe is full, block to wait for a slot to free up.
+        // chunks that wait for a previous chunk also occupy a slot,
+        // and they can only wait for a chunk that is still being compressed
+        while self.currently_compressing_count + self.sorted_writer.pending_chunk_count()
+            >= self.max_threads
+        {
             self.write_next_queued_chunk()?;
         }
 
@@ -586,3 +726,54 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn offsets_for_chunk_counts(chunk_counts: &[usize]) -> ChunkOffsets {
+        let mut offsets = ChunkOffsets {
+            header_starts: SmallVec::new(),
+            offsets: Vec::new(),
+            written_count: 0,
+        };
+
+        for &chunk_count in chunk_counts {
+            offsets.header_starts.push(offsets.offsets.len());
+            offsets
+                .offsets
+                .resize(offsets.offsets.len() + chunk_count, 0);
+        }
+
+        offsets
+    }
+
+    #[test]
+    fn insert_chunk_offsets_of_multiple_headers() {
+        let mut offsets = offsets_for_chunk_counts(&[2, 3]);
+
+        offsets.insert(1, 2, 500).unwrap();
+        offsets.insert(0, 1, 200).unwrap();
+        offsets.insert(1, 0, 300).unwrap();
+        offsets.insert(0, 0, 100).unwrap();
+        assert!(!offsets.is_complete());
+
+        assert!(
+            offsets.insert(0, 2, 600).is_err(),
+            "index outside of the first header"
+        );
+        assert!(offsets.insert(2, 0, 600).is_err(), "header does not exist");
+        assert!(offsets.insert(1, 0, 600).is_err(), "chunk already written");
+
+        offsets.insert(1, 1, 400).unwrap();
+        assert!(offsets.is_complete());
+
+        let bytes = offsets.to_le_bytes();
+        let expected: Vec<u8> = [100_u64, 200, 300, 400, 500]
+            .iter()
+            .flat_map(|offset| offset.to_le_bytes())
+            .collect();
+
+        assert_eq!(bytes, expected);
+    }
+}
@@ -0,0 +1,301 @@
+//! Convert linear colors between common working spaces.
+//!
+//! The colors in exr files are linear. Their primaries and white point are stored
+//! in the optional `chromaticities` attribute of the image. Files without this attribute
+//! are assumed to contain linear Rec. 709 colors, which have the same primaries as sRGB.
+//!
+//! ```no_run
+//! use exr::color::{convert_image, ColorSpace};
+//! use exr::prelude::*;
+//!
+//! let mut image = read_all_flat_layers_from_file("render.exr").unwrap();
+//! convert_image(&mut image, ColorSpace::AcesCg).unwrap();
+//! image.write().to_file("render_acescg.exr").unwrap();
+//! ```
+
+use crate::error::{Error, Result, UnitResult};
+use crate::image::{AnyChannels, FlatSamples, Image, Layers};
+use crate::meta::attribute::{Chromaticities, Matrix3x3};
+use crate::meta::header::ImageAttributes;
+use half::slice::HalfFloatSliceExt;
+
+/// A linear rgb color space, defined by its primaries and white point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    /// Linear Rec. 709, with the primaries of sRGB and a D65 white point.
+    /// This is the color space of exr files without chromaticities.
+    LinearRec709,
+
+    /// ACEScg, with the AP1 primaries. The ACES working space for rendering and compositing.
+    AcesCg,
+
+    /// ACES 2065-1, with the AP0 primaries. The ACES space for interchange and archival.
+    Aces2065_1,
+
+    /// Any other primaries and white point, for example from the header of a file.
+    Custom(Chromaticities),
+}
+
+impl ColorSpace {
+    /// The primaries and white point of this color space.
+    pub fn chromaticities(&self) -> Chromaticities {
+        match *self {
+            ColorSpace::LinearRec709 => Chromaticities::rec709(),
+            ColorSpace::AcesCg => Chromaticities::aces_ap1(),
+            ColorSpace::Aces2065_1 => Chromaticities::aces_ap0(),
+            ColorSpace::Custom(chromaticities) => chromaticities,
+        }
+    }
+
+    /// The named color space with exactly these chromaticities, or a custom color space.
+    pub fn from_chromaticities(chromaticities: Chromaticities) -> Self {
+        [
+            ColorSpace::LinearRec709,
+            ColorSpace::AcesCg,
+            ColorSpace::Aces2065_1,
+        ]
+        .iter()
+        .copied()
+        .find(|space| space.chromaticities() == chromaticities)
+        .unwrap_or(ColorSpace::Custom(chromaticities))
+    }
+
+    /// The color space of an image, from its chromaticities attribute.
+    /// Images without chromaticities are linear Rec. 709.
+    pub fn of_image(attributes: &ImageAttributes) -> Self {
+        attributes
+            .chromaticities
+            .map_or(ColorSpace::LinearRec709, Self::from_chromaticities)
+    }
+
+    /// Parse a color space name, ignoring case:
+    /// `rec709`, `srgb` or `linear` for linear Rec. 709,
+    /// `acescg` or `ap1` for ACEScg, and `aces2065-1`, `aces` or `ap0` for ACES 2065-1.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "rec709" | "lin_rec709" | "srgb" | "lin_srgb" | "linear" => {
+                Some(ColorSpace::LinearRec709)
+            }
+            "acescg" | "ap1" => Some(ColorSpace::AcesCg),
+            "aces2065-1" | "aces" | "ap0" => Some(ColorSpace::Aces2065_1),
+            _ => None,
+        }
+    }
+
+    /// The matrix that converts linear rgb values from this color space to the target color space.
+    /// Includes a Bradford chromatic adaptation if the white points differ.
+    /// Use `Chromaticities::transform_rgb` to apply the matrix.
+    pub fn conversion_to(&self, target: &ColorSpace) -> Result<Matrix3x3> {
+        self.chromaticities()
+            .conversion_to(&target.chromaticities())
+    }
+
+    /// Convert the linear rgb samples from this color space to the target color space, in place.
+    /// All three slices must have the same length.
+    pub fn convert_rgb_samples(
+        &self,
+        target: &ColorSpace,
+        red: &mut [f32],
+        green: &mut [f32],
+        blue: &mut [f32],
+    ) -> UnitResult {
+        if self == target {
+            return Ok(());
+        }
+
+        self.chromaticities()
+            .convert_rgb_samples(&target.chromaticities(), red, green, blue)
+    }
+}
+
+/// Convert all color channels of a layer between color spaces.
+/// Converts each group of `R`, `G`, and `B` channels, for example also `diffuse.R`, `diffuse.G`, and `diffuse.B`.
+/// Other channels, and `u32` channels, are left unchanged.
+/// `f16` channels are converted with `f32` precision.
+pub fn convert_channels(
+    channels: &mut AnyChannels<FlatSamples>,
+    from: &ColorSpace,
+    to: &ColorSpace,
+) -> UnitResult {
+    if from == to {
+        return Ok(());
+    }
+
+    for [red, green, blue] in rgb_channel_indices(channels) {
+        let list = &mut channels.list;
+
+        let is_integer = [red, green, blue]
+            .iter()
+            .any(|&index| matches!(list[index].sample_data, FlatSamples::U32(_)));
+
+        if is_integer {
+            continue;
+        }
+
+        let mut r = list[red].sample_data.to_f32_vec();
+        let mut g = list[green].sample_data.to_f32_vec();
+        let mut b = list[blue].sample_data.to_f32_vec();
+        from.convert_rgb_samples(to, &mut r, &mut g, &mut b)?;
+
+        set_f32_values(&mut list[red].sample_data, &r);
+        set_f32_values(&mut list[green].sample_data, &g);
+        set_f32_values(&mut list[blue].sample_data, &b);
+    }
+
+    Ok(())
+}
+
+/// Convert all layers of an image from its color space to the target color space,
+/// and update the chromaticities attribute of the image.
+pub fn convert_image(
+    image: &mut Image<Layers<AnyChannels<FlatSamples>>>,
+    target: ColorSpace,
+) -> UnitResult {
+    let source = ColorSpace::of_image(&image.attributes);
+
+    for layer in &mut image.layer_data {
+        convert_channels(&mut layer.channel_data, &source, &target)?;
+    }
+
+    image.attributes.chromaticities = Some(target.chromaticities());
+    Ok(())
+}
+
+/// The indices of the red, green, and blue channel of each layer that contains all three.
+fn rgb_channel_indices(channels: &AnyChannels<FlatSamples>) -> Vec<[usize; 3]> {
+    let index_of = |name: &str| {
+        channels
+            .list
+            .iter()
+            .position(|channel| channel.name.eq(name))
+    };
+
+    channels
+        .list
+        .iter()
+        .filter_map(|channel| {
+            let name = channel.name.to_string();
+            let prefix = name.strip_suffix('R')?;
+
+            if !(prefix.is_empty() || prefix.ends_with('.')) {
+                return None;
+            }
+
+            Some([
+                index_of(&name)?,
+                index_of(&format!("{}G", prefix))?,
+                index_of(&format!("{}B", prefix))?,
+            ])
+        })
+        .collect()
+}
+
+/// Replace the samples with the values, rounding them once to `f16` if required.
+fn set_f32_values(samples: &mut FlatSamples, values: &[f32]) {
+    match samples {
+        FlatSamples::F16(samples) => samples.convert_from_f32_slice(values),
+        FlatSamples::F32(samples) => samples.copy_from_slice(values),
+        FlatSamples::U32(_) => unreachable!("u32 channels are not converted"),
+    }
+}
+
+impl std::str::FromStr for ColorSpace {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Self::from_name(name).ok_or_else(|| Error::unsupported("color space name"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::AnyChannel;
+    use crate::math::Vec2;
+    use half::f16;
+    use smallvec::smallvec;
+
+    fn channel(name: &str, sample_data: FlatSamples) -> AnyChannel<FlatSamples> {
+        AnyChannel {
+            name: name.into(),
+            sample_data,
+            quantize_linearly: false,
+            sampling: Vec2(1, 1),
+        }
+    }
+
+    #[test]
+    fn named_color_spaces() {
+        assert_eq!(ColorSpace::from_name("ACEScg"), Some(ColorSpace::AcesCg));
+        assert_eq!(
+            ColorSpace::from_name("sRGB"),
+            Some(ColorSpace::LinearRec709)
+        );
+        assert_eq!(ColorSpace::from_name("unknown"), None);
+
+        assert_eq!(
+            ColorSpace::from_chromaticities(Chromaticities::aces_ap0()),
+            ColorSpace::Aces2065_1
+        );
+
+        let custom = Chromaticities {
+            white: Vec2(0.3457, 0.3585),
+            ..Chromaticities::rec709()
+        };
+
+        assert_eq!(
+            ColorSpace::from_chromaticities(custom),
+            ColorSpace::Custom(custom)
+        );
+    }
+
+    #[test]
+    fn convert_rgb_groups_and_back() {
+        let mut channels = AnyChannels::sort(smallvec![
+            channel("R", FlatSamples::F32(vec![1.0, 0.0])),
+            channel("G", FlatSamples::F32(vec![0.0, 0.5])),
+            channel("B", FlatSamples::F32(vec![0.0, 0.25])),
+            channel("A", FlatSamples::F32(vec![0.5, 1.0])),
+            channel("spec.R", FlatSamples::F16(vec![f16::ONE; 2])),
+            channel("spec.G", FlatSamples::F16(vec![f16::ONE; 2])),
+            channel("spec.B", FlatSamples::F16(vec![f16::ONE; 2])),
+            channel("id.R", FlatSamples::U32(vec![1, 2])),
+        ]);
+
+        let original = channels.clone();
+        let rec709 = ColorSpace::LinearRec709;
+        let acescg = ColorSpace::AcesCg;
+
+        convert_channels(&mut channels, &rec709, &acescg).unwrap();
+
+        let values = |channels: &AnyChannels<FlatSamples>, name: &str| {
+            let channel = channels.list.iter().find(|channel| channel.name.eq(name));
+            channel.unwrap().sample_data.to_f32_vec()
+        };
+
+        // pure red in rec709 contains some green and blue in ACEScg
+        let green = values(&channels, "G");
+        assert!(green[0] > 0.0);
+        assert_eq!(values(&channels, "A"), values(&original, "A"));
+        assert_eq!(values(&channels, "id.R"), values(&original, "id.R"));
+
+        // white stays white, apart from rounding
+        for value in values(&channels, "spec.G") {
+            assert!((value - 1.0).abs() < 0.002, "{}", value);
+        }
+
+        convert_channels(&mut channels, &acescg, &rec709).unwrap();
+
+        for name in ["R", "G", "B"] {
+            for (converted, original) in values(&channels, name).iter().zip(values(&original, name))
+            {
+                assert!(
+                    (converted - original).abs() < 1.0e-4,
+                    "{}: {}",
+                    name,
+                    converted
+                );
+            }
+        }
+    }
+}
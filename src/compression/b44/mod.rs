@@ -1,5 +1,6 @@
 mod table;
 
+use crate::block::pool::{recycle_buffer, take_buffer};
 use crate::compression::{mod_p, ByteVec};
 use crate::error::usize_to_i32;
 use crate::io::Data;
@@ -177,32 +178,31 @@ fn unpack14(b: &[u8], s: &mut [u16; 16]) {
     let shift = b32!(b, 2) >> 2;
     let bias = 0x20 << shift;
 
-    s[4] = (s[0] as u32 + ((((b32!(b, 2) << 4) | (b32!(b, 3) >> 4)) & SIX_BITS) << shift) - bias)
-        as u16;
-    s[8] = (s[4] as u32 + ((((b32!(b, 3) << 2) | (b32!(b, 4) >> 6)) & SIX_BITS) << shift) - bias)
-        as u16;
-    s[12] = (s[8] as u32 + ((b32!(b, 4) & SIX_BITS) << shift) - bias) as u16;
-
-    s[1] = (s[0] as u32 + ((b32!(b, 5) >> 2) << shift) - bias) as u16;
-    s[5] = (s[4] as u32 + ((((b32!(b, 5) << 4) | (b32!(b, 6) >> 4)) & SIX_BITS) << shift) - bias)
-        as u16;
-    s[9] = (s[8] as u32 + ((((b32!(b, 6) << 2) | (b32!(b, 7) >> 6)) & SIX_BITS) << shift) - bias)
-        as u16;
-    s[13] = (s[12] as u32 + ((b32!(b, 7) & SIX_BITS) << shift) - bias) as u16;
-
-    s[2] = (s[1] as u32 + ((b32!(b, 8) >> 2) << shift) - bias) as u16;
-    s[6] = (s[5] as u32 + ((((b32!(b, 8) << 4) | (b32!(b, 9) >> 4)) & SIX_BITS) << shift) - bias)
-        as u16;
-    s[10] = (s[9] as u32 + ((((b32!(b, 9) << 2) | (b32!(b, 10) >> 6)) & SIX_BITS) << shift) - bias)
-        as u16;
-    s[14] = (s[13] as u32 + ((b32!(b, 10) & SIX_BITS) << shift) - bias) as u16;
-
-    s[3] = (s[2] as u32 + ((b32!(b, 11) >> 2) << shift) - bias) as u16;
-    s[7] = (s[6] as u32 + ((((b32!(b, 11) << 4) | (b32!(b, 12) >> 4)) & SIX_BITS) << shift) - bias)
-        as u16;
-    s[11] = (s[10] as u32 + ((((b32!(b, 12) << 2) | (b32!(b, 13) >> 6)) & SIX_BITS) << shift)
-        - bias) as u16;
-    s[15] = (s[14] as u32 + ((b32!(b, 13) & SIX_BITS) << shift) - bias) as u16;
+    // wraps like the reference implementation, as corrupt blocks may over- or underflow
+    let delta = |previous: u16, bits: u32| {
+        (previous as u32)
+            .wrapping_add(bits << shift)
+            .wrapping_sub(bias) as u16
+    };
+
+    s[4] = delta(s[0], ((b32!(b, 2) << 4) | (b32!(b, 3) >> 4)) & SIX_BITS);
+    s[8] = delta(s[4], ((b32!(b, 3) << 2) | (b32!(b, 4) >> 6)) & SIX_BITS);
+    s[12] = delta(s[8], b32!(b, 4) & SIX_BITS);
+
+    s[1] = delta(s[0], b32!(b, 5) >> 2);
+    s[5] = delta(s[4], ((b32!(b, 5) << 4) | (b32!(b, 6) >> 4)) & SIX_BITS);
+    s[9] = delta(s[8], ((b32!(b, 6) << 2) | (b32!(b, 7) >> 6)) & SIX_BITS);
+    s[13] = delta(s[12], b32!(b, 7) & SIX_BITS);
+
+    s[2] = delta(s[1], b32!(b, 8) >> 2);
+    s[6] = delta(s[5], ((b32!(b, 8) << 4) | (b32!(b, 9) >> 4)) & SIX_BITS);
+    s[10] = delta(s[9], ((b32!(b, 9) << 2) | (b32!(b, 10) >> 6)) & SIX_BITS);
+    s[14] = delta(s[13], b32!(b, 10) & SIX_BITS);
+
+    s[3] = delta(s[2], b32!(b, 11) >> 2);
+    s[7] = delta(s[6], ((b32!(b, 11) << 4) | (b32!(b, 12) >> 4)) & SIX_BITS);
+    s[11] = delta(s[10], ((b32!(b, 12) << 2) | (b32!(b, 13) >> 6)) & SIX_BITS);
+    s[15] = delta(s[14], b32!(b, 13) & SIX_BITS);
 
     for i in 0..16 {
         if (s[i] & 0x8000) != 0 {
@@ -241,7 +241,6 @@ struct ChannelData {
     y_sampling: usize,
     sample_type: SampleType,
     quantize_linearly: bool,
-    samples_per_pixel: usize,
 }
 
 // TODO: Unsafe seems to be required to efficiently copy whole slice of u16 ot u8. For now, we use
@@ -273,7 +272,7 @@ pub fn decompress(
 ) -> Result<ByteVec> {
     debug_assert_eq!(
         expected_byte_size,
-        rectangle.size.area() * channels.bytes_per_pixel,
+        channels.byte_size_of_section(rectangle),
         "expected byte size does not match header" // TODO compute instead of passing argument?
     );
 
@@ -291,23 +290,20 @@ pub fn decompress(
         let channel = ChannelData {
             tmp_start_index: tmp_read_index,
             tmp_end_index: tmp_read_index,
-            resolution: channel.subsampled_resolution(rectangle.size),
+            resolution: channel.subsampled_section_resolution(rectangle),
             y_sampling: channel.sampling.y(),
             sample_type: channel.sample_type,
             quantize_linearly: channel.quantize_linearly,
-            samples_per_pixel: channel.sampling.area(),
         };
 
-        tmp_read_index += channel.resolution.area()
-            * channel.samples_per_pixel
-            * channel.sample_type.bytes_per_sample();
+        tmp_read_index += channel.resolution.area() * channel.sample_type.bytes_per_sample();
 
         channel_data.push(channel);
     }
 
     // Temporary buffer is used to decompress B44 datas the way they are stored in the compressed
     // buffer (channel by channel). We interleave the final result later.
-    let mut tmp = Vec::with_capacity(expected_byte_size);
+    let mut tmp = take_buffer(expected_byte_size);
 
     // Index in the compressed buffer.
     let mut in_i = 0usize;
@@ -318,7 +314,7 @@ pub fn decompress(
         debug_assert_eq!(remaining_le, compressed_le.len() - in_i);
 
         // Compute information for current channel.
-        let sample_count = channel.resolution.area() * channel.samples_per_pixel;
+        let sample_count = channel.resolution.area();
         let byte_count = sample_count * channel.sample_type.bytes_per_sample();
 
         // Sample types that does not support B44 compression (u32 and f32) are raw copied.
@@ -346,8 +342,8 @@ pub fn decompress(
         // Increase buffer to get new uncompressed datas.
         tmp.resize(tmp.len() + byte_count, 0);
 
-        let x_sample_count = channel.resolution.x() * channel.samples_per_pixel;
-        let y_sample_count = channel.resolution.y() * channel.samples_per_pixel;
+        let x_sample_count = channel.resolution.x();
+        let y_sample_count = channel.resolution.y();
 
         let bytes_per_sample = size_of::<u16>();
 
@@ -436,8 +432,10 @@ pub fn decompress(
 
     debug_assert_eq!(tmp.len(), expected_byte_size);
 
+    recycle_buffer(compressed_le);
+
     // Interleave uncompressed channel data.
-    let mut out = Vec::with_capacity(expected_byte_size);
+    let mut out = take_buffer(expected_byte_size);
 
     for y in rectangle.position.y()..rectangle.end().y() {
         for channel in &mut channel_data {
@@ -446,7 +444,7 @@ pub fn decompress(
             }
 
             // Find data location in temporary buffer.
-            let x_sample_count = channel.resolution.x() * channel.samples_per_pixel;
+            let x_sample_count = channel.resolution.x();
             let bytes_per_line = x_sample_count * channel.sample_type.bytes_per_sample();
             let next_tmp_end_index = channel.tmp_end_index + bytes_per_line;
             let channel_bytes = &tmp[channel.tmp_end_index..next_tmp_end_index];
@@ -482,6 +480,7 @@ pub fn decompress(
     }
 
     debug_assert_eq!(out.len(), expected_byte_size);
+    recycle_buffer(tmp);
 
     // TODO do not convert endianness for f16-only images
     //      see https://github.com/AcademySoftwareFoundation/openexr/blob/3bd93f85bcb74c77255f28cdbb913fdbfbb39dfe/OpenEXR/IlmImf/ImfTiledOutputFile.cpp#L750-L842
@@ -508,9 +507,9 @@ pub fn compress(
 
     let mut tmp_end_index = 0;
     for channel in &channels.list {
-        let number_samples = channel.subsampled_resolution(rectangle.size);
+        let number_samples = channel.subsampled_section_resolution(rectangle);
 
-        let sample_count = channel.subsampled_resolution(rectangle.size).area();
+        let sample_count = number_samples.area();
         let byte_count = sample_count * channel.sample_type.bytes_per_sample();
 
         let channel = ChannelData {
@@ -520,7 +519,6 @@ pub fn compress(
             resolution: number_samples,
             sample_type: channel.sample_type,
             quantize_linearly: channel.quantize_linearly,
-            samples_per_pixel: channel.sampling.area(),
         };
 
         tmp_end_index += byte_count;
@@ -539,7 +537,7 @@ pub fn compress(
                 continue;
             }
 
-            let x_sample_count = channel.resolution.x() * channel.samples_per_pixel;
+            let x_sample_count = channel.resolution.x();
             let bytes_per_line = x_sample_count * channel.sample_type.bytes_per_sample();
             let next_tmp_end_index = channel.tmp_end_index + bytes_per_line;
             let target = &mut tmp[channel.tmp_end_index..next_tmp_end_index];
@@ -571,8 +569,23 @@ pub fn compress(
         }
     }
 
+    // Each block of 4x4 half samples takes at most 14 bytes, which for narrow or subsampled
+    // channels can be more than the uncompressed size, as partial blocks are padded.
+    let max_compressed_size = channel_data
+        .iter()
+        .map(|channel| {
+            if channel.sample_type == SampleType::F16 {
+                let block_count =
+                    |samples: usize| (samples + BLOCK_SAMPLE_COUNT - 1) / BLOCK_SAMPLE_COUNT;
+                block_count(channel.resolution.x()) * block_count(channel.resolution.y()) * 14
+            } else {
+                channel.resolution.area() * channel.sample_type.bytes_per_sample()
+            }
+        })
+        .sum();
+
     // Generate a whole buffer that we will crop to proper size once compression is done.
-    let mut b44_compressed = vec![0; std::cmp::max(2048, uncompressed_le.len())];
+    let mut b44_compressed = vec![0; max_compressed_size];
     let mut b44_end = 0; // Buffer byte index for storing next compressed values.
 
     for channel in &channel_data {
@@ -594,8 +607,8 @@ pub fn compress(
         debug_assert_eq!(channel.sample_type, SampleType::F16);
         debug_assert_eq!(channel.sample_type.bytes_per_sample(), size_of::<u16>());
 
-        let x_sample_count = channel.resolution.x() * channel.samples_per_pixel;
-        let y_sample_count = channel.resolution.y() * channel.samples_per_pixel;
+        let x_sample_count = channel.resolution.x();
+        let y_sample_count = channel.resolution.y();
 
         let x_byte_count = x_sample_count * size_of::<u16>();
         let cd_start = channel.tmp_start_index;
@@ -712,13 +725,7 @@ mod test {
         channels: ChannelList,
         rectangle: IntegerBounds,
     ) -> (ByteVec, ByteVec, ByteVec) {
-        let byte_count = channels
-            .list
-            .iter()
-            .map(|c| {
-                c.subsampled_resolution(rectangle.size).area() * c.sample_type.bytes_per_sample()
-            })
-            .sum();
+        let byte_count = channels.byte_size_of_section(rectangle);
 
         assert!(byte_count > 0);
 
@@ -999,4 +1006,89 @@ mod test {
 
         image.assert_equals_result(&image2);
     }
+
+    fn fuzz_channels() -> ChannelList {
+        let channel = |sample_type, quantize_linearly| ChannelDescription {
+            sample_type,
+            name: Default::default(),
+            quantize_linearly,
+            sampling: Vec2(1, 1),
+        };
+
+        ChannelList::new(smallvec![
+            channel(SampleType::F16, false),
+            channel(SampleType::F16, true),
+            channel(SampleType::F32, false),
+        ])
+    }
+
+    #[test]
+    fn flat_fields_pack_to_three_bytes() {
+        let mut block = [0_u8; 14];
+        let flat = [f16::from_f32(0.5).to_bits(); 16];
+
+        assert_eq!(b44::pack(flat, &mut block, true, false), 3);
+        assert_eq!(b44::pack(flat, &mut block, false, false), 14);
+
+        let mut unpacked = [0_u16; 16];
+        b44::pack(flat, &mut block, true, false);
+        b44::unpack3(&block[..3], &mut unpacked);
+        assert_eq!(unpacked, flat);
+    }
+
+    #[test]
+    fn decompress_random_bytes_without_panic() {
+        use rand::{Rng, SeedableRng};
+
+        let mut random = rand::rngs::StdRng::from_seed([7; 32]);
+        let channels = fuzz_channels();
+
+        for _ in 0..2000 {
+            let size = Vec2(random.gen_range(1..12), random.gen_range(1..12));
+            let rectangle = IntegerBounds::new(Vec2(0, 0), size);
+            let expected_byte_size = size.area() * channels.bytes_per_pixel;
+
+            let byte_count = random.gen_range(1..expected_byte_size * 2);
+            let bytes: ByteVec = (0..byte_count).map(|_| random.gen()).collect();
+
+            if let Ok(decompressed) =
+                b44::decompress(&channels, bytes, rectangle, expected_byte_size, true)
+            {
+                assert_eq!(decompressed.len(), expected_byte_size);
+            }
+        }
+    }
+
+    #[test]
+    fn decompress_truncated_and_corrupted_data_without_panic() {
+        use rand::{Rng, SeedableRng};
+
+        let mut random = rand::rngs::StdRng::from_seed([11; 32]);
+        let channels = fuzz_channels();
+        let rectangle = IntegerBounds::new(Vec2(3, -2), Vec2(9, 6));
+        let expected_byte_size = rectangle.size.area() * channels.bytes_per_pixel;
+
+        let pixel_bytes: ByteVec = (0..expected_byte_size).map(|_| random.gen()).collect();
+        let compressed = b44::compress(&channels, pixel_bytes, rectangle, true).unwrap();
+
+        for length in 0..compressed.len() {
+            let truncated = compressed[..length].to_vec();
+            let result = b44::decompress(&channels, truncated, rectangle, expected_byte_size, true);
+            assert!(length == 0 || result.is_err(), "accepted {} bytes", length);
+        }
+
+        for _ in 0..500 {
+            let mut corrupted = compressed.clone();
+            for _ in 0..random.gen_range(1..8) {
+                let index = random.gen_range(0..corrupted.len());
+                corrupted[index] = random.gen();
+            }
+
+            if let Ok(decompressed) =
+                b44::decompress(&channels, corrupted, rectangle, expected_byte_size, true)
+            {
+                assert_eq!(decompressed.len(), expected_byte_size);
+            }
+        }
+    }
 }
@@ -8,6 +8,7 @@ mod pxr24;
 mod rle;
 mod zip;
 
+use crate::block::pool::{recycle_buffer, take_buffer};
 use crate::error::{usize_to_i32, Error, Result, UnitResult};
 use crate::meta::attribute::{ChannelList, IntegerBounds, SampleType};
 use crate::meta::header::Header;
@@ -24,6 +25,7 @@ pub type Bytes<'s> = &'s [u8];
 /// Use RLE compression for fast loading and writing with slight memory savings.
 /// Use ZIP compression for slow processing with large memory savings.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Compression {
     /// Store uncompressed values.
     /// Produces large files that can be read and written very quickly.
@@ -270,7 +272,7 @@ impl Compression {
             assert!(self.supports_deep_data())
         }
 
-        let expected_byte_size = pixel_section.size.area() * header.channels.bytes_per_pixel; // FIXME this needs to account for subsampling anywhere
+        let expected_byte_size = header.channels.byte_size_of_section(pixel_section);
 
         // note: always true where self == Uncompressed
         if compressed_le.len() == expected_byte_size {
@@ -367,16 +369,25 @@ impl Compression {
         }
     }
 
-    /// Deep data can only be compressed using RLE or ZIP compression.
+    /// Deep data can only be compressed using RLE, ZIP1, or ZIP16 compression, or stored uncompressed.
+    /// These are the compression methods that OpenEXR permits for deep parts.
     pub fn supports_deep_data(self) -> bool {
         use self::Compression::*;
         match self {
-            Uncompressed | RLE | ZIP1 => true,
+            Uncompressed | RLE | ZIP1 | ZIP16 => true,
 
-            ZIP16 | PXR24 | PIZ | B44 | B44A | DWAA(_) | DWAB(_) | HTJ2K256 | HTJ2K32 => false,
+            PXR24 | PIZ | B44 | B44A | DWAA(_) | DWAB(_) | HTJ2K256 | HTJ2K32 => false,
         }
     }
 
+    /// The error for deep data that uses a compression method which is not permitted for deep data.
+    pub(crate) fn deep_data_error(self) -> Error {
+        Error::invalid(format!(
+            "{self} cannot be used for deep data, which only supports \
+            no compression, rle compression, and zip compression"
+        ))
+    }
+
     /// Most compression methods will reconstruct the exact pixel bytes,
     /// but some might throw away unimportant data for specific types of samples.
     pub fn is_lossless_for(self, sample_type: SampleType) -> bool {
@@ -464,7 +475,7 @@ fn reverse_block_endianness(
                 continue;
             }
 
-            let sample_count = rectangle.size.width() / channel.sampling.x();
+            let sample_count = channel.subsampled_section_resolution(rectangle).width();
 
             match channel.sample_type {
                 SampleType::F16 => {
@@ -832,9 +843,11 @@ mod optimize_bytes {
 ///
 /// - `Uncompressed` - No compression, raw LE bytes
 /// - `RLE` - Run-length encoding
-/// - `ZIP1` - zlib compression (single scanline blocks)
+/// - `ZIP1` - zlib compression (single scanline blocks, `ZIPS` in OpenEXR)
+/// - `ZIP16` - zlib compression (blocks of 16 scanlines, `ZIP` in OpenEXR)
 ///
-/// Other compressions (PIZ, PXR24, etc.) are not typically used with deep data.
+/// OpenEXR does not permit any other compression for deep data,
+/// so all other compressions (`PIZ`, `PXR24`, etc.) are rejected with an error.
 pub mod deep {
     use super::optimize_bytes::*;
     use super::*;
@@ -874,13 +887,8 @@ pub mod deep {
                 return convert_sample_table_le_to_native(compressed);
             }
             Compression::RLE => rle::decompress_rle_raw(compressed, expected_bytes, pedantic)?,
-            Compression::ZIP1 => zip::decompress_zip_raw(compressed, expected_bytes)?,
-            _ => {
-                return Err(Error::unsupported(format!(
-                    "compression {} not supported for deep data",
-                    compression
-                )))
-            }
+            Compression::ZIP1 | Compression::ZIP16 => zip::decompress_zip_raw(compressed, expected_bytes)?,
+            _ => return Err(compression.deep_data_error()),
         };
 
         // Apply unpredict and reorder (same as flat data)
@@ -912,13 +920,8 @@ pub mod deep {
         // Compress based on type
         let compressed = match compression {
             Compression::RLE => rle::compress_rle_raw(&data_le),
-            Compression::ZIP1 => zip::compress_zip_raw(&data_le),
-            _ => {
-                return Err(Error::unsupported(format!(
-                    "compression {} not supported for deep data",
-                    compression
-                )))
-            }
+            Compression::ZIP1 | Compression::ZIP16 => zip::compress_zip_raw(&data_le),
+            _ => return Err(compression.deep_data_error()),
         };
 
         // Return compressed only if smaller, otherwise raw LE data
@@ -930,6 +933,48 @@ pub mod deep {
         }
     }
 
+    /// Compress deep sample count table into the output, replacing its contents.
+    /// The scratch buffer holds the intermediate bytes, and can be reused between calls.
+    pub fn compress_sample_table_into(
+        compression: Compression,
+        sample_counts: &[i32],
+        scratch: &mut Vec<u8>,
+        compressed: &mut Vec<u8>,
+    ) -> UnitResult {
+        compressed.clear();
+
+        if compression != Compression::Uncompressed {
+            scratch.clear();
+            for &count in sample_counts {
+                scratch.extend_from_slice(&count.to_le_bytes());
+            }
+
+            // Apply separate and predict (same as flat data)
+            separate_bytes_fragments(scratch);
+            samples_to_differences(scratch);
+
+            match compression {
+                Compression::RLE => rle::compress_rle_raw_into(scratch, compressed),
+                Compression::ZIP1 | Compression::ZIP16 => zip::compress_zip_raw_into(scratch, compressed),
+                _ => return Err(compression.deep_data_error()),
+            }
+
+            // Use compressed only if smaller
+            if compressed.len() < scratch.len() {
+                return Ok(());
+            }
+
+            compressed.clear();
+        }
+
+        // Uncompressed LE data
+        for &count in sample_counts {
+            compressed.extend_from_slice(&count.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
     /// Decompress deep sample data.
     /// Uses the same pipeline as flat data (reorder+predict).
     pub fn decompress_sample_data(
@@ -949,13 +994,8 @@ pub mod deep {
                 return Ok(compressed.to_vec());
             }
             Compression::RLE => rle::decompress_rle_raw(compressed, expected_bytes, pedantic)?,
-            Compression::ZIP1 => zip::decompress_zip_raw(compressed, expected_bytes)?,
-            _ => {
-                return Err(Error::unsupported(format!(
-                    "compression {} not supported for deep data",
-                    compression
-                )))
-            }
+            Compression::ZIP1 | Compression::ZIP16 => zip::decompress_zip_raw(compressed, expected_bytes)?,
+            _ => return Err(compression.deep_data_error()),
         };
 
         // Apply unpredict and reorder (same as flat data)
@@ -965,6 +1005,43 @@ pub mod deep {
         Ok(decompressed)
     }
 
+    /// Compress deep sample data into the output, replacing its contents.
+    /// The scratch buffer holds the reordered bytes, and can be reused between calls.
+    /// Like [`compress_sample_data`], the raw data is used if compression would not make it smaller.
+    pub fn compress_sample_data_into(
+        compression: Compression,
+        data: &[u8],
+        scratch: &mut Vec<u8>,
+        compressed: &mut Vec<u8>,
+    ) -> UnitResult {
+        compressed.clear();
+
+        if compression == Compression::Uncompressed {
+            compressed.extend_from_slice(data);
+            return Ok(());
+        }
+
+        // Apply separate and predict (same as flat data)
+        scratch.clear();
+        scratch.extend_from_slice(data);
+        separate_bytes_fragments(scratch);
+        samples_to_differences(scratch);
+
+        match compression {
+            Compression::RLE => rle::compress_rle_raw_into(scratch, compressed),
+            Compression::ZIP1 | Compression::ZIP16 => zip::compress_zip_raw_into(scratch, compressed),
+            _ => return Err(compression.deep_data_error()),
+        }
+
+        // Use compressed only if smaller
+        if compressed.len() >= data.len() {
+            compressed.clear();
+            compressed.extend_from_slice(data);
+        }
+
+        Ok(())
+    }
+
     /// Compress deep sample data.
     pub fn compress_sample_data(compression: Compression, data: &[u8]) -> Result<Vec<u8>> {
         let raw_size = data.len();
@@ -981,13 +1058,8 @@ pub mod deep {
         // Compress based on type
         let compressed = match compression {
             Compression::RLE => rle::compress_rle_raw(&data_copy),
-            Compression::ZIP1 => zip::compress_zip_raw(&data_copy),
-            _ => {
-                return Err(Error::unsupported(format!(
-                    "compression {} not supported for deep data",
-                    compression
-                )))
-            }
+            Compression::ZIP1 | Compression::ZIP16 => zip::compress_zip_raw(&data_copy),
+            _ => return Err(compression.deep_data_error()),
         };
 
         // Return compressed only if smaller
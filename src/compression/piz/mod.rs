@@ -5,6 +5,7 @@
 mod huffman;
 mod wavelet;
 
+use crate::block::pool::{recycle_buffer, take_buffer};
 use crate::compression::{mod_p, ByteVec, Bytes};
 use crate::error::{usize_to_i32, usize_to_u16};
 use crate::io::Data;
@@ -29,14 +30,11 @@ pub fn decompress(
     channels: &ChannelList,
     compressed_le: ByteVec,
     rectangle: IntegerBounds,
-    expected_byte_size: usize, // TODO remove expected byte size as it can be computed with `rectangle.size.area() * channels.bytes_per_pixel`
+    expected_byte_size: usize, // TODO remove expected byte size as it can be computed with `channels.byte_size_of_section(rectangle)`
     pedantic: bool,
 ) -> Result<ByteVec> {
     let expected_u16_count = expected_byte_size / 2;
-    debug_assert_eq!(
-        expected_byte_size,
-        rectangle.size.area() * channels.bytes_per_pixel
-    );
+    debug_assert_eq!(expected_byte_size, channels.byte_size_of_section(rectangle));
     debug_assert!(!channels.list.is_empty());
 
     if compressed_le.is_empty() {
@@ -73,6 +71,7 @@ pub fn decompress(
     }
 
     let mut tmp_u16_buffer = huffman::decompress(remaining_input_le, expected_u16_count)?;
+    recycle_buffer(compressed_le);
 
     let mut channel_data: SmallVec<[ChannelData; 6]> = {
         let mut tmp_read_index = 0;
@@ -85,7 +84,7 @@ pub fn decompress(
                     tmp_start_index: tmp_read_index,
                     tmp_end_index: tmp_read_index,
                     y_sampling: channel.sampling.y(),
-                    resolution: channel.subsampled_resolution(rectangle.size),
+                    resolution: channel.subsampled_section_resolution(rectangle),
                     samples_per_pixel: channel.sample_type.bytes_per_sample()
                         / SampleType::F16.bytes_per_sample(),
                 };
@@ -122,7 +121,7 @@ pub fn decompress(
     apply_lookup_table(&mut tmp_u16_buffer, &lookup_table);
 
     // let out_buffer_size = (max_scan_line_size * scan_line_count) + 65536 + 8192; // TODO not use expected byte size?
-    let mut out = Vec::with_capacity(expected_byte_size);
+    let mut out = take_buffer(expected_byte_size);
 
     for y in rectangle.position.y()..rectangle.end().y() {
         for channel in &mut channel_data {
@@ -183,7 +182,7 @@ pub fn compress(
             .list
             .iter()
             .map(|channel| {
-                let number_samples = channel.subsampled_resolution(rectangle.size);
+                let number_samples = channel.subsampled_section_resolution(rectangle);
                 let byte_size =
                     channel.sample_type.bytes_per_sample() / SampleType::F16.bytes_per_sample();
                 let byte_count = byte_size * number_samples.area();
@@ -331,7 +330,7 @@ mod test {
             .collect::<Vec<u8>>()
             .into_iter()
             .cycle()
-            .take(channels.bytes_per_pixel * rectangle.size.area())
+            .take(channels.byte_size_of_section(rectangle))
             .collect();
 
         let compressed = piz::compress(&channels, pixel_bytes.clone(), rectangle).unwrap();
@@ -42,17 +42,21 @@ pub fn compress(channels: &ChannelList, bytes_ne: ByteVec, area: IntegerBounds)
 
     let mut remaining_bytes_ne = bytes_ne.as_slice(); // TODO less allocation
 
-    let bytes_per_pixel: usize = channels
+    let encoded_byte_size: usize = channels
         .list
         .iter()
-        .map(|channel| match channel.sample_type {
-            SampleType::F16 => 2,
-            SampleType::F32 => 3,
-            SampleType::U32 => 4,
+        .map(|channel| {
+            let bytes_per_sample = match channel.sample_type {
+                SampleType::F16 => 2,
+                SampleType::F32 => 3,
+                SampleType::U32 => 4,
+            };
+
+            bytes_per_sample * channel.subsampled_section_resolution(area).area()
         })
         .sum();
 
-    let mut encoded_be = vec![0_u8; bytes_per_pixel * area.size.area()];
+    let mut encoded_be = vec![0_u8; encoded_byte_size];
 
     {
         let mut write = encoded_be.as_mut_slice();
@@ -64,7 +68,7 @@ pub fn compress(channels: &ChannelList, bytes_ne: ByteVec, area: IntegerBounds)
                     continue;
                 }
 
-                let sample_count_x = channel.subsampled_resolution(area.size).0;
+                let sample_count_x = channel.subsampled_section_resolution(area).0;
 
                 // this apparently can't be a closure in Rust 1.43 due to borrowing ambiguity
                 macro_rules! split_off_write_slice {
@@ -168,12 +172,14 @@ pub fn decompress(
         .set_size_hint(expected_byte_size);
     let mut decompressor = zune_inflate::DeflateDecoder::new_with_options(&bytes_le, options);
 
-    let encoded_be = decompressor
+    let encoded = decompressor
         .decode_zlib()
         .map_err(|_| Error::invalid("zlib-compressed data malformed"))?; // TODO share code with zip?
 
-    let mut encoded_be = encoded_be.as_slice();
-    let mut out = Vec::with_capacity(expected_byte_size.min(2048 * 4));
+    recycle_buffer(bytes_le);
+
+    let mut encoded_be = encoded.as_slice();
+    let mut out = take_buffer(expected_byte_size.min(2048 * 4));
 
     for y in area.position.1..area.end().1 {
         for channel in &channels.list {
@@ -181,7 +187,7 @@ pub fn decompress(
                 continue;
             }
 
-            let sample_count_x = channel.subsampled_resolution(area.size).0;
+            let sample_count_x = channel.subsampled_section_resolution(area).0;
             let mut read_sample_line = || {
                 if sample_count_x > encoded_be.len() {
                     return Err(Error::invalid("not enough data"));
@@ -240,6 +246,7 @@ pub fn decompress(
         return Err(Error::invalid("too much data"));
     }
 
+    recycle_buffer(encoded);
     Ok(out)
 }
 
@@ -16,7 +16,7 @@ pub fn decompress_rle_raw(
     pedantic: bool,
 ) -> Result<Vec<u8>> {
     let mut remaining = compressed;
-    let mut decompressed = Vec::with_capacity(expected_size.min(8 * 2048));
+    let mut decompressed = take_buffer(expected_size.min(8 * 2048));
 
     while !remaining.is_empty() && decompressed.len() != expected_size {
         let count = take_1(&mut remaining)? as i8 as i32;
@@ -43,6 +43,14 @@ pub fn decompress_rle_raw(
 /// Used for deep data sample count tables.
 pub fn compress_rle_raw(data: &[u8]) -> Vec<u8> {
     let mut compressed = Vec::with_capacity(data.len());
+    compress_rle_raw_into(data, &mut compressed);
+    compressed
+}
+
+/// Raw RLE compression, appending to the existing bytes of the output.
+pub fn compress_rle_raw_into(data: &[u8], compressed: &mut Vec<u8>) {
+    compressed.reserve(data.len());
+
     let mut run_start = 0;
     let mut run_end = 1;
 
@@ -74,8 +82,6 @@ pub fn compress_rle_raw(data: &[u8]) -> Vec<u8> {
             run_end += 1;
         }
     }
-
-    compressed
 }
 
 /// Decompress RLE with full pipeline: RLE decode -> differences_to_samples -> interleave -> endian convert.
@@ -88,6 +94,7 @@ pub fn decompress_bytes(
     pedantic: bool,
 ) -> Result<ByteVec> {
     let mut decompressed_le = decompress_rle_raw(&compressed_le, expected_byte_size, pedantic)?;
+    recycle_buffer(compressed_le);
 
     differences_to_samples(&mut decompressed_le);
     interleave_byte_blocks(&mut decompressed_le);
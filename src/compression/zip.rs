@@ -27,6 +27,25 @@ pub fn compress_zip_raw(data: &[u8]) -> Vec<u8> {
     miniz_oxide::deflate::compress_to_vec_zlib(data, 4)
 }
 
+/// Raw ZIP compression, appending to the existing bytes of the output.
+/// Produces the same bytes as `compress_zip_raw`.
+pub fn compress_zip_raw_into(data: &[u8], compressed: &mut Vec<u8>) {
+    use miniz_oxide::deflate::core::{
+        compress_to_output, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush,
+        TDEFLStatus,
+    };
+
+    // a positive window size adds the zlib header, like `compress_to_vec_zlib`
+    let mut compressor = CompressorOxide::new(create_comp_flags_from_zip_params(4, 1, 0));
+
+    let (status, _) = compress_to_output(&mut compressor, data, TDEFLFlush::Finish, |bytes| {
+        compressed.extend_from_slice(bytes);
+        true
+    });
+
+    debug_assert!(matches!(status, TDEFLStatus::Done), "zip compression bug");
+}
+
 /// Decompress ZIP with full pipeline: ZIP decode -> differences_to_samples -> interleave -> endian convert.
 /// Used for flat image data.
 pub fn decompress_bytes(
@@ -37,6 +56,7 @@ pub fn decompress_bytes(
     _pedantic: bool,
 ) -> Result<ByteVec> {
     let mut decompressed_le = decompress_zip_raw(&data_le, expected_byte_size)?;
+    recycle_buffer(data_le);
 
     differences_to_samples(&mut decompressed_le);
     interleave_byte_blocks(&mut decompressed_le);
@@ -72,6 +92,17 @@ mod test {
         assert_eq!(data, decompressed);
     }
 
+    #[test]
+    fn compress_zip_raw_into_existing_bytes() {
+        let data: Vec<u8> = (0..1000).map(|i| (i % 7) as u8).collect();
+
+        let mut compressed = vec![42];
+        compress_zip_raw_into(&data, &mut compressed);
+
+        assert_eq!(compressed[0], 42);
+        assert_eq!(compressed[1..], compress_zip_raw(&data)[..]);
+    }
+
     #[test]
     fn roundtrip_zip_raw_empty() {
         // ZIP with empty input still produces a valid zlib stream
@@ -22,11 +22,9 @@ pub type UnitResult = Result<()>;
 /// unsupported features, invalid data, and file system errors.
 #[derive(Debug)]
 pub enum Error {
-    /// Reading or Writing the file has been aborted by the caller.
-    /// This error will never be triggered by this crate itself,
-    /// only by users of this library.
-    /// It exists to be returned from a progress callback.
-    Aborted, // FIXME remove?? is not used really?
+    /// Reading or Writing the file has been aborted by the caller,
+    /// for example by cancelling a `CancellationToken`.
+    Aborted,
 
     /// The contents of the file are not supported by
     /// this specific implementation of open exr,
@@ -0,0 +1,518 @@
+//! Tonemap linear samples and write them to PNG, TIFF, or Radiance HDR files,
+//! for example to save review stills of an image.
+//! The transfer functions are always available, writing files requires the `image` feature.
+//!
+//! PNG and TIFF files contain display values: the samples are multiplied by the exposure,
+//! encoded with the transfer function, clamped, and quantized to 8 or 16 bits.
+//! Alpha is clamped, but neither exposed nor encoded.
+//! Radiance HDR files contain linear rgb values, so only the exposure applies to them.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//! use exr::export::{BitDepth, ExportOptions, Transfer};
+//!
+//! let image = read_all_flat_layers_from_file("render.exr").unwrap();
+//!
+//! let options = ExportOptions {
+//!     exposure: 1.5,
+//!     transfer: Transfer::Srgb,
+//!     bit_depth: BitDepth::Sixteen,
+//! };
+//!
+//! exr::export::write_layer_to_file("render.png", &image.layer_data[0], &options).unwrap();
+//! ```
+
+#[cfg(feature = "image")]
+use ::image::{DynamicImage, ImageBuffer, ImageError, ImageOutputFormat, Rgb};
+#[cfg(feature = "image")]
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+#[cfg(feature = "image")]
+use crate::error::UnitResult;
+#[cfg(feature = "image")]
+use crate::interop::FlatLayer;
+#[cfg(feature = "image")]
+use crate::math::Vec2;
+
+/// Converts linear values to the encoded values stored in PNG and TIFF files.
+#[derive(Debug, Clone, Copy)]
+pub enum Transfer {
+    /// Store the linear values.
+    Linear,
+
+    /// The piecewise sRGB transfer function.
+    Srgb,
+
+    /// A power function with the specified gamma, such as `2.2`.
+    Gamma(f32),
+
+    /// Any function mapping linear values to encoded values.
+    /// The result is clamped to `0.0 ..= 1.0`.
+    Custom(fn(f32) -> f32),
+}
+
+/// The number of bits of each sample in PNG and TIFF files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BitDepth {
+    /// Store `u8` samples.
+    Eight,
+
+    /// Store `u16` samples.
+    Sixteen,
+}
+
+/// The file formats that tonemapped images can be written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    /// Portable network graphics, with 8 or 16 bits.
+    Png,
+
+    /// Tagged image file format, with 8 or 16 bits.
+    Tiff,
+
+    /// Radiance HDR, with linear rgb values.
+    Hdr,
+}
+
+/// How linear values are converted to the values in the exported file.
+#[derive(Debug, Clone, Copy)]
+pub struct ExportOptions {
+    /// Multiplies all colors by `2^exposure` before encoding them. Measured in stops.
+    pub exposure: f32,
+
+    /// Encodes the exposed colors. Does not apply to HDR files.
+    pub transfer: Transfer,
+
+    /// The bits of each sample. Does not apply to HDR files.
+    pub bit_depth: BitDepth,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            exposure: 0.0,
+            transfer: Transfer::Srgb,
+            bit_depth: BitDepth::Eight,
+        }
+    }
+}
+
+impl Transfer {
+    /// Convert a linear value to the encoded value, clamped to `0.0 ..= 1.0`.
+    pub fn encode(self, linear: f32) -> f32 {
+        let linear = linear.max(0.0);
+
+        let encoded = match self {
+            Transfer::Linear => linear,
+            Transfer::Gamma(gamma) => linear.powf(1.0 / gamma),
+            Transfer::Srgb => linear_to_srgb(linear),
+            Transfer::Custom(function) => function(linear),
+        };
+
+        encoded.max(0.0).min(1.0)
+    }
+
+    /// Convert an encoded value, such as a sample of a PNG file, to a linear value.
+    /// Custom functions cannot be inverted, so `Custom` decodes like `Linear`.
+    pub fn decode(self, encoded: f32) -> f32 {
+        let encoded = encoded.max(0.0);
+
+        match self {
+            Transfer::Linear | Transfer::Custom(_) => encoded,
+            Transfer::Gamma(gamma) => encoded.powf(gamma),
+            Transfer::Srgb => srgb_to_linear(encoded),
+        }
+    }
+}
+
+/// The piecewise sRGB transfer function, without clamping the result.
+pub fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The inverse of `linear_to_srgb`.
+pub fn srgb_to_linear(encoded: f32) -> f32 {
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl ExportFormat {
+    /// Choose the format from the extension of the path, ignoring the case.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "png" => Ok(ExportFormat::Png),
+            "tif" | "tiff" => Ok(ExportFormat::Tiff),
+            "hdr" => Ok(ExportFormat::Hdr),
+            _ => Err(Error::unsupported(
+                "export file extension (use png, tif, tiff, or hdr)",
+            )),
+        }
+    }
+}
+
+/// Tonemap one to four channels to an 8 or 16 bit image.
+/// The channels are interpreted as gray, gray and alpha, rgb, or rgba.
+/// Each channel contains the linear samples of all pixels, row by row.
+#[cfg(feature = "image")]
+pub fn tonemap(
+    size: Vec2<usize>,
+    channels: &[&[f32]],
+    options: &ExportOptions,
+) -> Result<DynamicImage> {
+    validate_channels(size, channels)?;
+
+    let channel_count = channels.len();
+    let alpha_index = if channel_count % 2 == 0 {
+        Some(channel_count - 1)
+    } else {
+        None
+    };
+
+    let exposure = 2.0_f32.powf(options.exposure);
+
+    let interleaved = (0..size.area()).flat_map(|index| {
+        (0..channel_count).map(move |channel| {
+            let value = channels[channel][index];
+
+            if Some(channel) == alpha_index {
+                value.max(0.0).min(1.0)
+            } else {
+                options.transfer.encode(value * exposure)
+            }
+        })
+    });
+
+    let (width, height) = (size.width() as u32, size.height() as u32);
+    let invalid_size = || Error::invalid("image buffer size");
+
+    let image = match options.bit_depth {
+        BitDepth::Eight => {
+            let samples: Vec<u8> = interleaved
+                .map(|value| (value * 255.0).round() as u8)
+                .collect();
+
+            match channel_count {
+                1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLuma8),
+                2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLumaA8),
+                3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb8),
+                _ => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba8),
+            }
+        }
+
+        BitDepth::Sixteen => {
+            let samples: Vec<u16> = interleaved
+                .map(|value| (value * 65535.0).round() as u16)
+                .collect();
+
+            match channel_count {
+                1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLuma16),
+                2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLumaA16),
+                3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb16),
+                _ => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba16),
+            }
+        }
+    };
+
+    image.ok_or_else(invalid_size)
+}
+
+/// Tonemap one to four channels and write them to a file.
+/// The format is chosen by the extension of the path.
+/// See `write_channels` for details.
+#[cfg(feature = "image")]
+pub fn write_channels_to_file(
+    path: impl AsRef<Path>,
+    size: Vec2<usize>,
+    channels: &[&[f32]],
+    options: &ExportOptions,
+) -> UnitResult {
+    let format = ExportFormat::from_path(&path)?;
+    let file = std::fs::File::create(path)?;
+    write_channels(BufWriter::new(file), format, size, channels, options)
+}
+
+/// Tonemap one to four channels and write them in the specified format.
+/// The channels are interpreted as gray, gray and alpha, rgb, or rgba.
+/// Each channel contains the linear samples of all pixels, row by row.
+///
+/// HDR files have no alpha and store gray as rgb.
+/// TIFF files store gray with alpha as rgba.
+#[cfg(feature = "image")]
+pub fn write_channels(
+    mut write: impl Write + Seek,
+    format: ExportFormat,
+    size: Vec2<usize>,
+    channels: &[&[f32]],
+    options: &ExportOptions,
+) -> UnitResult {
+    validate_channels(size, channels)?;
+
+    match format {
+        ExportFormat::Hdr => {
+            let color_count = if channels.len() % 2 == 0 {
+                channels.len() - 1
+            } else {
+                channels.len()
+            };
+
+            let exposure = 2.0_f32.powf(options.exposure);
+            let pixels: Vec<Rgb<f32>> = (0..size.area())
+                .map(|index| {
+                    let color =
+                        |channel: usize| channels[channel.min(color_count - 1)][index] * exposure;
+
+                    Rgb([color(0), color(1), color(2)])
+                })
+                .collect();
+
+            ::image::codecs::hdr::HdrEncoder::new(write)
+                .encode(&pixels, size.width(), size.height())
+                .map_err(image_error)
+        }
+
+        ExportFormat::Png => tonemap(size, channels, options)?
+            .write_to(&mut write, ImageOutputFormat::Png)
+            .map_err(image_error),
+
+        ExportFormat::Tiff => {
+            let image = tonemap(size, channels, options)?;
+
+            // the tiff encoder does not support gray with alpha
+            let image = match image {
+                DynamicImage::ImageLumaA8(_) => DynamicImage::ImageRgba8(image.to_rgba8()),
+                DynamicImage::ImageLumaA16(_) => DynamicImage::ImageRgba16(image.to_rgba16()),
+                image => image,
+            };
+
+            image
+                .write_to(&mut write, ImageOutputFormat::Tiff)
+                .map_err(image_error)
+        }
+    }
+}
+
+/// Tonemap the `R`, `G`, `B`, and `A` channels, or the `Y` and `A` channels, of the layer,
+/// and write them to a file. The format is chosen by the extension of the path.
+#[cfg(feature = "image")]
+pub fn write_layer_to_file(
+    path: impl AsRef<Path>,
+    layer: &FlatLayer,
+    options: &ExportOptions,
+) -> UnitResult {
+    let find = |name: &str| -> Result<Option<Vec<f32>>> {
+        let channel = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.eq(name));
+
+        match channel {
+            None => Ok(None),
+            Some(channel) if channel.sampling != Vec2(1, 1) => {
+                Err(Error::unsupported("export of subsampled channels"))
+            }
+            Some(channel) => Ok(Some(channel.sample_data.to_f32_vec())),
+        }
+    };
+
+    let mut channels = match (find("R")?, find("G")?, find("B")?) {
+        (Some(red), Some(green), Some(blue)) => vec![red, green, blue],
+        _ => match find("Y")? {
+            Some(luminance) => vec![luminance],
+            None => {
+                return Err(Error::invalid(
+                    "layer has neither rgb channels nor a luminance channel",
+                ))
+            }
+        },
+    };
+
+    channels.extend(find("A")?);
+
+    let channels: Vec<&[f32]> = channels.iter().map(Vec::as_slice).collect();
+    write_channels_to_file(path, layer.size, &channels, options)
+}
+
+/// Check that there are one to four channels, each containing one sample per pixel.
+#[cfg(feature = "image")]
+fn validate_channels(size: Vec2<usize>, channels: &[&[f32]]) -> UnitResult {
+    if channels.is_empty() || channels.len() > 4 {
+        return Err(Error::invalid(
+            "export requires one to four channels: gray, gray and alpha, rgb, or rgba",
+        ));
+    }
+
+    if channels.iter().any(|channel| channel.len() != size.area()) {
+        return Err(Error::invalid("export channel sample count"));
+    }
+
+    Ok(())
+}
+
+/// Keep io errors, and describe all other errors of the `image` crate.
+#[cfg(feature = "image")]
+fn image_error(error: ImageError) -> Error {
+    match error {
+        ImageError::IoError(error) => Error::from(error),
+        error => Error::invalid(format!("image export failed: {}", error)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "image")]
+    use std::io::Cursor;
+
+    #[test]
+    fn encode_transfer_functions() {
+        assert_eq!(Transfer::Linear.encode(0.25), 0.25);
+        assert_eq!(Transfer::Linear.encode(4.0), 1.0);
+        assert_eq!(Transfer::Srgb.encode(-1.0), 0.0);
+        assert!((Transfer::Srgb.encode(0.5) - 0.735_356_6).abs() < 1e-5);
+        assert!((Transfer::Gamma(2.0).encode(0.25) - 0.5).abs() < 1e-6);
+        assert_eq!(Transfer::Custom(|value| value * 2.0).encode(0.25), 0.5);
+    }
+
+    #[test]
+    fn transfer_functions_are_inverse() {
+        for transfer in [Transfer::Srgb, Transfer::Gamma(2.2), Transfer::Linear] {
+            for value in [0.0, 0.001, 0.18, 0.5, 1.0] {
+                let round_trip = transfer.decode(transfer.encode(value));
+                assert!(
+                    (round_trip - value).abs() < 1e-5,
+                    "{:?} {}",
+                    transfer,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn choose_format_from_extension() {
+        assert_eq!(ExportFormat::from_path("a.PNG").unwrap(), ExportFormat::Png);
+        assert_eq!(
+            ExportFormat::from_path("a.tif").unwrap(),
+            ExportFormat::Tiff
+        );
+        assert_eq!(ExportFormat::from_path("a.hdr").unwrap(), ExportFormat::Hdr);
+        assert!(ExportFormat::from_path("a.exr").is_err());
+        assert!(ExportFormat::from_path("a").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn png_contains_tonemapped_samples() {
+        let size = Vec2(2, 1);
+        let gray = [0.25_f32, 4.0];
+        let alpha = [0.5_f32, 2.0];
+
+        let options = ExportOptions {
+            exposure: 1.0,
+            transfer: Transfer::Linear,
+            bit_depth: BitDepth::Sixteen,
+        };
+
+        let mut bytes = Cursor::new(Vec::new());
+        write_channels(
+            &mut bytes,
+            ExportFormat::Png,
+            size,
+            &[&gray, &alpha],
+            &options,
+        )
+        .unwrap();
+
+        let image = ::image::load_from_memory(bytes.get_ref()).unwrap();
+        let image = image.as_luma_alpha16().expect("16 bit gray with alpha");
+
+        // exposure applies to gray, but not to alpha
+        assert_eq!(image.get_pixel(0, 0).0, [32768, 32768]);
+        assert_eq!(image.get_pixel(1, 0).0, [65535, 65535]);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn tiff_stores_gray_with_alpha_as_rgba() {
+        let gray = [0.0_f32, 1.0];
+        let alpha = [1.0_f32, 0.0];
+
+        let mut bytes = Cursor::new(Vec::new());
+        write_channels(
+            &mut bytes,
+            ExportFormat::Tiff,
+            Vec2(1, 2),
+            &[&gray, &alpha],
+            &ExportOptions::default(),
+        )
+        .unwrap();
+
+        let image = ::image::load_from_memory(bytes.get_ref()).unwrap();
+        let image = image.as_rgba8().expect("8 bit rgba");
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 1).0, [255, 255, 255, 0]);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn hdr_contains_exposed_linear_values() {
+        let red = [0.5_f32, 8.0];
+        let green = [0.25_f32, 0.0];
+        let blue = [1.0_f32, 2.0];
+
+        let options = ExportOptions {
+            exposure: -1.0,
+            ..ExportOptions::default()
+        };
+
+        let mut bytes = Cursor::new(Vec::new());
+        write_channels(
+            &mut bytes,
+            ExportFormat::Hdr,
+            Vec2(2, 1),
+            &[&red, &green, &blue],
+            &options,
+        )
+        .unwrap();
+
+        let decoder = ::image::codecs::hdr::HdrDecoder::new(bytes.get_ref().as_slice()).unwrap();
+        let pixels: Vec<[f32; 3]> = decoder
+            .read_image_hdr()
+            .unwrap()
+            .into_iter()
+            .map(|pixel| pixel.0)
+            .collect();
+
+        // the decoder of the `image` crate would tonemap the values to 8 bits
+        assert_eq!(pixels, vec![[0.25, 0.125, 0.5], [4.0, 0.0, 1.0]]);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn reject_invalid_channels() {
+        let samples: &[f32] = &[0.0; 4];
+        let options = ExportOptions::default();
+
+        assert!(tonemap(Vec2(2, 2), &[], &options).is_err());
+        assert!(tonemap(Vec2(2, 2), &[samples; 5], &options).is_err());
+        assert!(tonemap(Vec2(3, 2), &[samples], &options).is_err());
+        assert!(tonemap(Vec2(2, 2), &[samples], &options).is_ok());
+    }
+}
@@ -0,0 +1,357 @@
+//! Combine two flat layers pixel by pixel, for simple comps of render passes.
+//!
+//! Channels are matched by name. A channel that is missing in one of the layers contributes zero,
+//! and the result contains the channels of both layers.
+//! The result covers the union of both data windows, where pixels outside a data window are zero.
+//! Colors are expected to be premultiplied by their alpha channel, which is `diffuse.A` for `diffuse.R`,
+//! falling back to `A`. Layers without alpha are opaque within their data window.
+//!
+//! Integer channels, such as object ids, are never blended.
+//! The result uses the foreground id wherever the foreground is not transparent.
+//!
+//! ```no_run
+//! use exr::image::composite::sum_layers;
+//! use exr::prelude::*;
+//!
+//! // the beauty pass is the sum of all light groups
+//! let image = read_all_flat_layers_from_file("lights.exr").unwrap();
+//! let beauty = sum_layers(&image.layer_data).unwrap();
+//! Image::from_layer(beauty).write().to_file("beauty.exr").unwrap();
+//! ```
+
+use crate::error::{Error, Result};
+use crate::image::premultiply::alpha_channel_index_by_name;
+use crate::image::{AnyChannel, AnyChannels, FlatSamples, Layer};
+use crate::math::Vec2;
+use crate::meta::attribute::{IntegerBounds, Text};
+use half::f16;
+use smallvec::SmallVec;
+
+/// How the foreground and the background layer are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Premultiplied alpha compositing: `foreground + background * (1 - foreground alpha)`.
+    Over,
+
+    /// The sum of both layers, `foreground + background`. Combines light groups.
+    Add,
+
+    /// The product of both layers, `foreground * background`. Applies a shadow or mask pass.
+    Multiply,
+}
+
+impl BlendMode {
+    #[inline]
+    fn blend(self, foreground: f32, background: f32, foreground_alpha: f32) -> f32 {
+        match self {
+            BlendMode::Over => foreground + background * (1.0 - foreground_alpha),
+            BlendMode::Add => foreground + background,
+            BlendMode::Multiply => foreground * background,
+        }
+    }
+}
+
+impl Layer<AnyChannels<FlatSamples>> {
+    /// Combine this layer, as the foreground, with the background layer.
+    /// Keeps the attributes and the encoding of this layer.
+    /// Fails for subsampled channels, and for channels that contain integers in only one of the layers.
+    pub fn composite(&self, background: &Self, mode: BlendMode) -> Result<Self> {
+        let layers = [self, background];
+
+        let is_subsampled = layers.iter().any(|layer| {
+            layer
+                .channel_data
+                .list
+                .iter()
+                .any(|channel| channel.sampling != Vec2(1, 1))
+        });
+
+        if is_subsampled {
+            return Err(Error::unsupported("compositing subsampled channels"));
+        }
+
+        let foreground_bounds = data_window(self);
+        let background_bounds = data_window(background);
+        let bounds = foreground_bounds.union(background_bounds);
+
+        let mut names: Vec<&Text> = Vec::new();
+        for channel in layers.iter().flat_map(|layer| &layer.channel_data.list) {
+            if !names.contains(&&channel.name) {
+                names.push(&channel.name);
+            }
+        }
+
+        let list = names
+            .into_iter()
+            .map(|name| {
+                let foreground_samples = find_channel(self, name);
+                let background_samples = find_channel(background, name);
+
+                // pixels outside of the foreground data window are transparent
+                let foreground_alpha =
+                    match alpha_channel_index_by_name(&self.channel_data, &name.to_string()) {
+                        Some(alpha) => place_f32(
+                            &self.channel_data.list[alpha].sample_data,
+                            foreground_bounds,
+                            bounds,
+                        ),
+                        None => place(&vec![1.0; self.size.area()], foreground_bounds, bounds),
+                    };
+
+                let sample_data = match (foreground_samples, background_samples) {
+                    (
+                        Some(FlatSamples::U32(_)),
+                        Some(FlatSamples::F16(_) | FlatSamples::F32(_)),
+                    )
+                    | (
+                        Some(FlatSamples::F16(_) | FlatSamples::F32(_)),
+                        Some(FlatSamples::U32(_)),
+                    ) => {
+                        return Err(Error::unsupported(
+                            "compositing integer channels with float channels",
+                        ))
+                    }
+
+                    (Some(FlatSamples::U32(_)), _) | (_, Some(FlatSamples::U32(_))) => {
+                        let ids = |samples: Option<&FlatSamples>, own_bounds| match samples {
+                            Some(FlatSamples::U32(samples)) => place(samples, own_bounds, bounds),
+                            _ => vec![0; bounds.size.area()],
+                        };
+
+                        let foreground = ids(foreground_samples, foreground_bounds);
+                        let background = ids(background_samples, background_bounds);
+
+                        let ids = foreground
+                            .into_iter()
+                            .zip(background)
+                            .zip(&foreground_alpha)
+                            .map(
+                                |((foreground, background), &alpha)| {
+                                    if alpha > 0.0 {
+                                        foreground
+                                    } else {
+                                        background
+                                    }
+                                },
+                            )
+                            .collect();
+
+                        FlatSamples::U32(ids)
+                    }
+
+                    _ => {
+                        let values = |samples: Option<&FlatSamples>, own_bounds| match samples {
+                            Some(samples) => place_f32(samples, own_bounds, bounds),
+                            None => vec![0.0; bounds.size.area()],
+                        };
+
+                        let result: Vec<f32> = values(foreground_samples, foreground_bounds)
+                            .into_iter()
+                            .zip(values(background_samples, background_bounds))
+                            .zip(foreground_alpha)
+                            .map(|((foreground, background), alpha)| {
+                                mode.blend(foreground, background, alpha)
+                            })
+                            .collect();
+
+                        let is_f32 = [foreground_samples, background_samples]
+                            .iter()
+                            .any(|samples| matches!(samples, Some(FlatSamples::F32(_))));
+
+                        if is_f32 {
+                            FlatSamples::F32(result)
+                        } else {
+                            FlatSamples::F16(result.into_iter().map(f16::from_f32).collect())
+                        }
+                    }
+                };
+
+                let description = self
+                    .channel_data
+                    .list
+                    .iter()
+                    .chain(&background.channel_data.list)
+                    .find(|channel| &channel.name == name)
+                    .expect("channel name from layer");
+
+                Ok(AnyChannel {
+                    name: name.clone(),
+                    sample_data,
+                    quantize_linearly: description.quantize_linearly,
+                    sampling: Vec2(1, 1),
+                })
+            })
+            .collect::<Result<SmallVec<_>>>()?;
+
+        let mut layer = Layer {
+            channel_data: AnyChannels::sort(list),
+            attributes: self.attributes.clone(),
+            size: bounds.size,
+            encoding: self.encoding,
+        };
+
+        layer.attributes.layer_position = bounds.position;
+        Ok(layer)
+    }
+
+    /// Place this layer over the background layer, see `BlendMode::Over`.
+    pub fn over(&self, background: &Self) -> Result<Self> {
+        self.composite(background, BlendMode::Over)
+    }
+}
+
+/// Add all layers, for example to reconstruct the beauty pass from its light groups.
+/// Keeps the attributes and the encoding of the first layer.
+/// Fails if there are no layers.
+pub fn sum_layers<'l>(
+    layers: impl IntoIterator<Item = &'l Layer<AnyChannels<FlatSamples>>>,
+) -> Result<Layer<AnyChannels<FlatSamples>>> {
+    let mut layers = layers.into_iter();
+    let first = layers
+        .next()
+        .ok_or_else(|| Error::invalid("no layers to sum"))?;
+
+    layers.try_fold(first.clone(), |sum, layer| {
+        sum.composite(layer, BlendMode::Add)
+    })
+}
+
+fn data_window(layer: &Layer<AnyChannels<FlatSamples>>) -> IntegerBounds {
+    IntegerBounds::new(layer.attributes.layer_position, layer.size)
+}
+
+fn find_channel<'l>(
+    layer: &'l Layer<AnyChannels<FlatSamples>>,
+    name: &Text,
+) -> Option<&'l FlatSamples> {
+    layer
+        .channel_data
+        .list
+        .iter()
+        .find(|channel| &channel.name == name)
+        .map(|channel| &channel.sample_data)
+}
+
+fn place_f32(samples: &FlatSamples, own_bounds: IntegerBounds, bounds: IntegerBounds) -> Vec<f32> {
+    place(&samples.to_f32_vec(), own_bounds, bounds)
+}
+
+/// Copy the samples of a smaller rectangle into a larger rectangle, filled with default values.
+fn place<T: Copy + Default>(
+    samples: &[T],
+    own_bounds: IntegerBounds,
+    bounds: IntegerBounds,
+) -> Vec<T> {
+    debug_assert!(bounds.contains(own_bounds), "rectangle outside of union");
+
+    let mut result = vec![T::default(); bounds.size.area()];
+    let offset = own_bounds.position - bounds.position;
+    let (offset_x, offset_y) = (offset.x() as usize, offset.y() as usize);
+    let width = own_bounds.size.width();
+
+    if width == 0 {
+        return result;
+    }
+
+    for (y, row) in samples.chunks_exact(width).enumerate() {
+        let start = (offset_y + y) * bounds.size.width() + offset_x;
+        result[start..start + width].copy_from_slice(row);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Encoding;
+    use crate::meta::header::LayerAttributes;
+
+    fn layer(
+        position: Vec2<i32>,
+        size: Vec2<usize>,
+        channels: SmallVec<[AnyChannel<FlatSamples>; 4]>,
+    ) -> Layer<AnyChannels<FlatSamples>> {
+        let mut layer = Layer::new(
+            size,
+            LayerAttributes::default(),
+            Encoding::default(),
+            AnyChannels::sort(channels),
+        );
+
+        layer.attributes.layer_position = position;
+        layer
+    }
+
+    fn values(layer: &Layer<AnyChannels<FlatSamples>>, name: &str) -> Vec<f32> {
+        let channel = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.eq(name));
+        channel.unwrap().sample_data.to_f32_vec()
+    }
+
+    #[test]
+    fn over_respects_alpha_and_data_windows() {
+        let foreground = layer(
+            Vec2(1, 0),
+            Vec2(2, 1),
+            smallvec![
+                AnyChannel::new("A", FlatSamples::F32(vec![0.5, 1.0])),
+                AnyChannel::new("R", FlatSamples::F32(vec![0.25, 1.0])),
+                AnyChannel::new("id", FlatSamples::U32(vec![7, 8])),
+            ],
+        );
+
+        let background = layer(
+            Vec2(0, 0),
+            Vec2(2, 1),
+            smallvec![
+                AnyChannel::new("R", FlatSamples::F16(vec![f16::ONE; 2])),
+                AnyChannel::new("G", FlatSamples::F16(vec![f16::ONE; 2])),
+                AnyChannel::new("id", FlatSamples::U32(vec![1, 2])),
+            ],
+        );
+
+        let result = foreground.over(&background).unwrap();
+        assert_eq!(result.attributes.layer_position, Vec2(0, 0));
+        assert_eq!(result.size, Vec2(3, 1));
+
+        assert_eq!(values(&result, "A"), vec![0.0, 0.5, 1.0]);
+        assert_eq!(values(&result, "R"), vec![1.0, 0.75, 1.0]);
+        assert_eq!(values(&result, "G"), vec![1.0, 0.5, 0.0]);
+        assert_eq!(values(&result, "id"), vec![1.0, 7.0, 8.0]);
+
+        assert!(matches!(
+            result
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.name.eq("R"))
+                .unwrap()
+                .sample_data,
+            FlatSamples::F32(_)
+        ));
+    }
+
+    #[test]
+    fn sum_light_groups() {
+        let light = |value: f32| {
+            layer(
+                Vec2(0, 0),
+                Vec2(2, 2),
+                smallvec![AnyChannel::new("R", FlatSamples::F32(vec![value; 4]))],
+            )
+        };
+
+        let lights = [light(0.5), light(0.25), light(1.0)];
+        let beauty = sum_layers(&lights).unwrap();
+        assert_eq!(values(&beauty, "R"), vec![1.75; 4]);
+
+        let shadow = beauty.composite(&light(0.5), BlendMode::Multiply).unwrap();
+        assert_eq!(values(&shadow, "R"), vec![0.875; 4]);
+
+        assert!(sum_layers(&[]).is_err());
+    }
+}
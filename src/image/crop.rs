@@ -1,10 +1,12 @@
 //! Crop away unwanted pixels. Includes automatic detection of bounding rectangle.
 //! Currently does not support deep data and resolution levels.
+//! Layers can also be cropped or padded to the display window of their image.
 
 use crate::block::BlockIndex;
+use crate::error::{Error, Result};
 use crate::image::write::channels::{ChannelsWriter, GetPixel, WritableChannels};
 use crate::image::{
-    AnyChannel, AnyChannels, FlatSamples, FlatSamplesPixel, Layer, SpecificChannels,
+    AnyChannel, AnyChannels, FlatSamples, FlatSamplesPixel, Image, Layer, Layers, SpecificChannels,
 };
 use crate::math::{RoundingMode, Vec2};
 use crate::meta::attribute::{ChannelList, IntegerBounds, LevelMode};
@@ -454,6 +456,89 @@ impl<Cropped, Original> CropResult<Cropped, Original> {
     }
 }
 
+impl Layer<AnyChannels<FlatSamples>> {
+    /// Crop or pad the layer, such that its data window is the specified display window.
+    /// Pixels outside of the original data window are zero, including their alpha.
+    /// Fails if a channel is subsampled.
+    pub fn to_display_window(&self, display_window: IntegerBounds) -> Result<Self> {
+        if self
+            .channel_data
+            .list
+            .iter()
+            .any(|channel| channel.sampling != Vec2(1, 1))
+        {
+            return Err(Error::unsupported(
+                "moving subsampled channels to the display window",
+            ));
+        }
+
+        let data_window = self.bounds();
+        let list = self
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| AnyChannel {
+                sample_data: moved_samples(&channel.sample_data, data_window, display_window),
+                ..channel.clone()
+            })
+            .collect();
+
+        let mut layer = Layer {
+            channel_data: AnyChannels { list },
+            attributes: self.attributes.clone(),
+            size: display_window.size,
+            encoding: self.encoding,
+        };
+
+        layer.attributes.layer_position = display_window.position;
+        Ok(layer)
+    }
+}
+
+impl Image<Layers<AnyChannels<FlatSamples>>> {
+    /// Crop or pad all layers to the display window of the image.
+    /// See `Layer::to_display_window`.
+    pub fn to_display_window(&self) -> Result<Self> {
+        let display_window = self.attributes.display_window;
+        let layer_data = self
+            .layer_data
+            .iter()
+            .map(|layer| layer.to_display_window(display_window))
+            .collect::<Result<Layers<_>>>()?;
+
+        Ok(Image {
+            attributes: self.attributes.clone(),
+            layer_data,
+        })
+    }
+}
+
+/// Copy the samples of one rectangle into another rectangle, filling the remaining samples with zero.
+fn moved_samples(samples: &FlatSamples, from: IntegerBounds, to: IntegerBounds) -> FlatSamples {
+    fn moved<T: Copy + Default>(samples: &[T], from: IntegerBounds, to: IntegerBounds) -> Vec<T> {
+        let mut result = vec![T::default(); to.size.area()];
+        let overlap = from.intersection(to);
+
+        for y in overlap.position.y()..overlap.end().y() {
+            let row = |bounds: IntegerBounds| {
+                let local = Vec2(overlap.position.x(), y) - bounds.position;
+                let start = local.y() as usize * bounds.size.width() + local.x() as usize;
+                start..start + overlap.size.width()
+            };
+
+            result[row(to)].copy_from_slice(&samples[row(from)]);
+        }
+
+        result
+    }
+
+    match samples {
+        FlatSamples::F16(samples) => FlatSamples::F16(moved(samples, from, to)),
+        FlatSamples::F32(samples) => FlatSamples::F32(moved(samples, from, to)),
+        FlatSamples::U32(samples) => FlatSamples::U32(moved(samples, from, to)),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -718,4 +803,39 @@ mod test {
 
         assert_eq!(bounds, None)
     }
+
+    #[test]
+    fn crop_and_pad_to_display_window() {
+        let channels = AnyChannels::sort(smallvec::smallvec![AnyChannel::new(
+            "Y",
+            FlatSamples::F32(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+        )]);
+
+        let mut layer = Layer::new(
+            Vec2(3, 2),
+            LayerAttributes::default(),
+            crate::image::Encoding::default(),
+            channels,
+        );
+
+        // overscan on the left, and padding at the bottom
+        layer.attributes.layer_position = Vec2(-1, 0);
+        let display_window = IntegerBounds::new(Vec2(0, 0), Vec2(2, 3));
+        let moved = layer.to_display_window(display_window).unwrap();
+
+        assert_eq!(moved.size, Vec2(2, 3));
+        assert_eq!(moved.attributes.layer_position, Vec2(0, 0));
+        assert_eq!(
+            moved.channel_data.list[0].sample_data,
+            FlatSamples::F32(vec![2.0, 3.0, 5.0, 6.0, 0.0, 0.0])
+        );
+
+        // display windows without overlap are empty
+        let outside = IntegerBounds::new(Vec2(10, 10), Vec2(2, 1));
+        let moved = layer.to_display_window(outside).unwrap();
+        assert_eq!(
+            moved.channel_data.list[0].sample_data,
+            FlatSamples::F32(vec![0.0, 0.0])
+        );
+    }
 }
@@ -0,0 +1,438 @@
+//! Decode Cryptomatte ID mattes, which identify the objects or materials of each pixel by name.
+//!
+//! A cryptomatte named `CryptoObject` is stored as the channels `CryptoObject00.R`, `CryptoObject00.G`, ...,
+//! where each pair of channels contains the id and the coverage of one rank, the most covering object first.
+//! The ids are the `f32` bit patterns of the MurmurHash3 of the object names.
+//! The attributes `cryptomatte/<key>/name` and `cryptomatte/<key>/manifest` describe the cryptomatte,
+//! and the manifest maps each object name to the hexadecimal hash.
+//! Manifests in sidecar files (`cryptomatte/<key>/manif_file`) are not loaded,
+//! but mattes can still be extracted by name, as ids are computed from the names.
+
+use crate::error::{Error, Result};
+use crate::image::{AnyChannels, FlatSamples};
+use crate::meta::attribute::AttributeValue;
+use crate::meta::header::LayerAttributes;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// One cryptomatte of a layer, like `CryptoObject` or `CryptoMaterial`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cryptomatte {
+    /// The key of the attributes, in `cryptomatte/<key>/name`.
+    pub key: String,
+
+    /// The name of the cryptomatte, which is the prefix of its channels.
+    pub name: String,
+
+    /// The name and id of each object, in the order of the manifest.
+    pub manifest: Vec<(String, u32)>,
+
+    /// The index of the id channel and the coverage channel of each rank.
+    ranks: Vec<(usize, usize)>,
+}
+
+impl Cryptomatte {
+    /// Find all cryptomattes described by the attributes of a layer.
+    /// Cryptomattes without any rank channels in the layer are skipped.
+    /// Fails if a manifest is malformed or an id channel does not contain `f32` samples.
+    pub fn find_all(
+        attributes: &LayerAttributes,
+        channels: &AnyChannels<FlatSamples>,
+    ) -> Result<Vec<Self>> {
+        let text = |key: &str, property: &str| {
+            let expected = format!("cryptomatte/{key}/{property}");
+            match attributes
+                .other
+                .iter()
+                .find(|&(name, _)| name.eq(expected.as_str()))
+            {
+                Some((_, AttributeValue::Text(value))) => Some(value.to_string()),
+                _ => None,
+            }
+        };
+
+        let mut cryptomattes = Vec::new();
+
+        for attribute_name in attributes.other.keys() {
+            let attribute_name = attribute_name.to_string();
+            let key = match attribute_name
+                .strip_prefix("cryptomatte/")
+                .and_then(|rest| rest.strip_suffix("/name"))
+            {
+                Some(key) => key,
+                None => continue,
+            };
+
+            let name = match text(key, "name") {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let ranks = rank_channels(&name, channels)?;
+            if ranks.is_empty() {
+                continue;
+            }
+
+            let manifest = match text(key, "manifest") {
+                Some(manifest) => parse_manifest(&manifest)?,
+                None => Vec::new(),
+            };
+
+            cryptomattes.push(Cryptomatte {
+                key: key.to_string(),
+                name,
+                manifest,
+                ranks,
+            });
+        }
+
+        cryptomattes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(cryptomattes)
+    }
+
+    /// The number of ids stored for each pixel.
+    pub fn rank_count(&self) -> usize {
+        self.ranks.len()
+    }
+
+    /// The id of an object. Uses the manifest if it contains the name, and hashes the name otherwise.
+    pub fn id_of(&self, name: &str) -> u32 {
+        self.manifest
+            .iter()
+            .find(|(object, _)| object == name)
+            .map_or_else(|| hash_name(name), |&(_, id)| id)
+    }
+
+    /// The name of an object, if the manifest contains its id.
+    pub fn name_of(&self, id: u32) -> Option<&str> {
+        self.manifest
+            .iter()
+            .find(|&&(_, object_id)| object_id == id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The id and coverage of all objects in a pixel, the most covering object first.
+    /// Ranks without coverage are skipped.
+    pub fn objects_at(
+        &self,
+        channels: &AnyChannels<FlatSamples>,
+        pixel_index: usize,
+    ) -> Vec<(u32, f32)> {
+        self.ranks
+            .iter()
+            .filter_map(|&(id_channel, coverage_channel)| {
+                let id = match &channels.list[id_channel].sample_data {
+                    FlatSamples::F32(ids) => ids.get(pixel_index)?.to_bits(),
+                    _ => return None,
+                };
+
+                let coverage = &channels.list[coverage_channel].sample_data;
+                if pixel_index >= coverage.len() {
+                    return None;
+                }
+
+                let coverage = coverage.value_by_flat_index(pixel_index).to_f32();
+                if coverage > 0.0 {
+                    Some((id, coverage))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The coverage of an object in each pixel, summed over all ranks.
+    pub fn matte(&self, channels: &AnyChannels<FlatSamples>, id: u32) -> Vec<f32> {
+        let pixel_count = channels
+            .list
+            .first()
+            .map_or(0, |channel| channel.sample_data.len());
+
+        let mut matte = vec![0.0; pixel_count];
+
+        for &(id_channel, coverage_channel) in &self.ranks {
+            let ids = match &channels.list[id_channel].sample_data {
+                FlatSamples::F32(ids) => ids,
+                _ => continue,
+            };
+
+            let coverage = channels.list[coverage_channel].sample_data.values_as_f32();
+            for ((sum, pixel_id), coverage) in matte.iter_mut().zip(ids).zip(coverage) {
+                if pixel_id.to_bits() == id {
+                    *sum += coverage;
+                }
+            }
+        }
+
+        matte
+    }
+
+    /// The coverage of the object with this name in each pixel.
+    pub fn matte_of_name(&self, channels: &AnyChannels<FlatSamples>, name: &str) -> Vec<f32> {
+        self.matte(channels, self.id_of(name))
+    }
+}
+
+/// The cryptomatte id of an object name:
+/// the MurmurHash3 of the name, changed to avoid infinite, NaN, and denormal `f32` values.
+pub fn hash_name(name: &str) -> u32 {
+    let hash = murmur3_32(name.as_bytes(), 0);
+    let exponent = (hash >> 23) & 0xff;
+
+    if exponent == 0 || exponent == 0xff {
+        hash ^ (1 << 23)
+    } else {
+        hash
+    }
+}
+
+/// The `(id, coverage)` channel indices of each rank, in the order of the ranks.
+fn rank_channels(name: &str, channels: &AnyChannels<FlatSamples>) -> Result<Vec<(usize, usize)>> {
+    let find = |channel_name: String| {
+        channels
+            .list
+            .iter()
+            .position(|channel| channel.name.eq(channel_name.as_str()))
+    };
+
+    let mut ranks = Vec::new();
+
+    for level in 0.. {
+        let mut found_any = false;
+
+        for &(id, coverage) in &[("R", "G"), ("B", "A")] {
+            let id_channel = find(format!("{name}{level:02}.{id}"));
+            let coverage_channel = find(format!("{name}{level:02}.{coverage}"));
+
+            if let (Some(id_channel), Some(coverage_channel)) = (id_channel, coverage_channel) {
+                if !matches!(channels.list[id_channel].sample_data, FlatSamples::F32(_)) {
+                    return Err(Error::invalid(
+                        "cryptomatte id channel must contain f32 samples",
+                    ));
+                }
+
+                ranks.push((id_channel, coverage_channel));
+                found_any = true;
+            }
+        }
+
+        if !found_any {
+            break;
+        }
+    }
+
+    Ok(ranks)
+}
+
+/// Parse a manifest like `{"bunny":"13851a76","default":"42c9679f"}`.
+fn parse_manifest(json: &str) -> Result<Vec<(String, u32)>> {
+    let mut chars = json.chars().peekable();
+    let mut manifest = Vec::new();
+
+    skip_whitespace(&mut chars);
+    if chars.next() != Some('{') {
+        return Err(Error::invalid("cryptomatte manifest"));
+    }
+
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        return Ok(manifest);
+    }
+
+    loop {
+        skip_whitespace(&mut chars);
+        let name = parse_string(&mut chars)?;
+
+        skip_whitespace(&mut chars);
+        if chars.next() != Some(':') {
+            return Err(Error::invalid("cryptomatte manifest"));
+        }
+
+        skip_whitespace(&mut chars);
+        let hash = parse_string(&mut chars)?;
+        let id = u32::from_str_radix(&hash, 16)
+            .map_err(|_| Error::invalid("cryptomatte manifest hash"))?;
+
+        manifest.push((name, id));
+
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(manifest),
+            _ => return Err(Error::invalid("cryptomatte manifest")),
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars<'_>>) {
+    while chars.peek().map_or(false, |c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Parse a JSON string, starting at the opening quote.
+fn parse_string(chars: &mut impl Iterator<Item = char>) -> Result<String> {
+    if chars.next() != Some('"') {
+        return Err(Error::invalid("cryptomatte manifest"));
+    }
+
+    let mut string = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => {
+                let escaped = match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('u') => {
+                        let digits: String = chars.take(4).collect();
+                        u32::from_str_radix(&digits, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    Some(other) => other,
+                    None => return Err(Error::invalid("cryptomatte manifest")),
+                };
+
+                string.push(escaped);
+            }
+            Some(other) => string.push(other),
+            None => return Err(Error::invalid("cryptomatte manifest")),
+        }
+    }
+}
+
+/// The 32 bit x86 variant of MurmurHash3.
+fn murmur3_32(bytes: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let scramble = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let mut hash = seed;
+    let mut blocks = bytes.chunks_exact(4);
+
+    for block in &mut blocks {
+        hash ^= scramble(u32::from_le_bytes([block[0], block[1], block[2], block[3]]));
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .enumerate()
+            .fold(0, |k, (index, &byte)| k | ((byte as u32) << (8 * index)));
+
+        hash ^= scramble(k);
+    }
+
+    hash ^= bytes.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::AnyChannel;
+    use crate::meta::attribute::Text;
+
+    #[test]
+    fn murmur_hash() {
+        assert_eq!(murmur3_32(b"", 0), 0);
+        assert_eq!(murmur3_32(b"hello", 0), 0x248b_fa47);
+        assert_eq!(
+            murmur3_32(b"The quick brown fox jumps over the lazy dog", 0x9747_b28c),
+            0x2fa8_26cd
+        );
+    }
+
+    #[test]
+    fn hashed_names_are_finite_floats() {
+        for name in ["bunny", "default", "/root/geo/teapot", ""] {
+            let id = f32::from_bits(hash_name(name));
+            assert!(id.is_finite() && (id == 0.0 || id.is_normal()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn parse_manifests() {
+        let manifest = parse_manifest(r#" { "bunny" : "13851a76", "a \"b\"":"42c9679f" } "#);
+        assert_eq!(
+            manifest.unwrap(),
+            vec![
+                ("bunny".to_string(), 0x1385_1a76),
+                ("a \"b\"".to_string(), 0x42c9_679f),
+            ]
+        );
+
+        assert_eq!(parse_manifest("{}").unwrap(), Vec::new());
+        assert!(parse_manifest(r#"{"bunny":"not hex"}"#).is_err());
+        assert!(parse_manifest(r#"{"bunny":"13851a76""#).is_err());
+    }
+
+    #[test]
+    fn extract_mattes() {
+        let bunny = hash_name("bunny");
+        let teapot = hash_name("teapot");
+        let ids = |values: [u32; 2]| {
+            FlatSamples::F32(values.iter().map(|&id| f32::from_bits(id)).collect())
+        };
+
+        // two pixels: mostly bunny, and only teapot
+        let channels = AnyChannels::sort(smallvec![
+            AnyChannel::new("CryptoObject00.R", ids([bunny, teapot])),
+            AnyChannel::new("CryptoObject00.G", FlatSamples::F32(vec![0.75, 1.0])),
+            AnyChannel::new("CryptoObject00.B", ids([teapot, 0])),
+            AnyChannel::new("CryptoObject00.A", FlatSamples::F32(vec![0.25, 0.0])),
+        ]);
+
+        let mut attributes = LayerAttributes::default();
+        let key = "cryptomatte/0a1b2c3";
+        attributes.other.insert(
+            Text::from(format!("{key}/name").as_str()),
+            AttributeValue::Text(Text::from("CryptoObject")),
+        );
+        attributes.other.insert(
+            Text::from(format!("{key}/manifest").as_str()),
+            AttributeValue::Text(Text::from(format!(r#"{{"bunny":"{bunny:08x}"}}"#).as_str())),
+        );
+
+        let cryptomattes = Cryptomatte::find_all(&attributes, &channels).unwrap();
+        assert_eq!(cryptomattes.len(), 1);
+
+        let cryptomatte = &cryptomattes[0];
+        assert_eq!(cryptomatte.key, "0a1b2c3");
+        assert_eq!(cryptomatte.rank_count(), 2);
+        assert_eq!(cryptomatte.name_of(bunny), Some("bunny"));
+        assert_eq!(cryptomatte.name_of(teapot), None);
+
+        assert_eq!(
+            cryptomatte.objects_at(&channels, 0),
+            vec![(bunny, 0.75), (teapot, 0.25)]
+        );
+        assert_eq!(cryptomatte.objects_at(&channels, 1), vec![(teapot, 1.0)]);
+
+        assert_eq!(
+            cryptomatte.matte_of_name(&channels, "bunny"),
+            vec![0.75, 0.0]
+        );
+        assert_eq!(
+            cryptomatte.matte_of_name(&channels, "teapot"),
+            vec![0.25, 1.0]
+        );
+    }
+}
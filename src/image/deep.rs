@@ -49,17 +49,27 @@
 //! Reading deep data:
 //! ```ignore
 //! let image = exr::image::read::deep::read_first_deep_layer_from_file("deep.exr")?;
-//! let samples = &image.layer_data.channel_data.list[0].sample_data;
 //!
-//! for y in 0..samples.height {
-//!     for x in 0..samples.width {
-//!         let count = samples.sample_count(x, y);
-//!         let (start, end) = samples.sample_range(y * samples.width + x);
-//!         // Process samples[start..end] for this pixel
+//! for pixel in image.layer_data.channel_data.pixels() {
+//!     let red: Option<&[f16]> = pixel.channel("R");
+//!
+//!     if let (Some(depth), Some(alpha)) = (pixel.z(), pixel.alpha()) {
+//!         for (depth, alpha) in depth.iter().zip(alpha.iter()) {
+//!             // process the samples of this pixel
+//!         }
 //!     }
 //! }
 //! ```
 //!
+//! Creating deep data, with one value per channel for each sample:
+//! ```ignore
+//! let channels = AnyChannels::from_pixel_fn(
+//!     Vec2(64, 64),
+//!     &[("A", SampleType::F16), ("Z", SampleType::F32)],
+//!     |position, pixel| pixel.push(&[0.5, position.x() as f32]),
+//! )?;
+//! ```
+//!
 //! # See Also
 //!
 //! - [`crate::image::read::deep`] - Reading deep images from files
@@ -67,9 +77,16 @@
 //! - [`crate::block::deep`] - Low-level block compression/decompression
 //! - [OpenEXR Deep Data spec](https://openexr.com/en/latest/TechnicalIntroduction.html#deep-data)
 
-use crate::error::{Error, Result};
-use crate::meta::attribute::{ChannelList, SampleType};
+use crate::error::{Error, Result, UnitResult};
+use crate::image::flatten::partial_alpha_factor;
+use crate::image::premultiply::alpha_channel_index;
+use crate::image::{AnyChannel, AnyChannels};
+use crate::math::Vec2;
+use crate::meta::attribute::{ChannelDescription, ChannelList, SampleType, Text};
 use half::f16;
+use smallvec::SmallVec;
+use std::cmp::Ordering;
+use std::convert::{TryFrom, TryInto};
 
 /// Deep samples storage using Struct-of-Arrays (SoA) layout.
 ///
@@ -168,27 +185,93 @@ pub enum DeepChannelData {
     U32(Vec<u32>),
 }
 
-/// Iterator over pixels in a deep image.
+/// Iterator over the pixels of deep samples, in row-major order.
+/// Created by [`DeepSamples::pixels`] or by `AnyChannels::pixels`.
+#[derive(Debug, Clone)]
+pub struct DeepPixels<'a> {
+    channels: &'a [DeepChannelData],
+    sample_offsets: &'a [u32],
+    width: usize,
+    names: ChannelNames<'a>,
+    index: usize,
+}
+
+/// The samples of a single deep pixel.
+///
+/// Channels are accessed by their index, or by their name
+/// if the pixel was obtained from a list of named channels.
+/// The typed accessors return `None` if the channel contains another sample type,
+/// while `values`, `z`, and `alpha` convert any sample type to `f32`.
+#[derive(Debug, Clone, Copy)]
+pub struct DeepPixelView<'a> {
+    /// The coordinates of this pixel.
+    pub position: Vec2<usize>,
+
+    channels: &'a [DeepChannelData],
+    names: ChannelNames<'a>,
+    start: usize,
+    end: usize,
+}
+
+/// The samples of one channel of a deep pixel, converted to `f32` on access.
+#[derive(Debug, Clone, Copy)]
+pub struct DeepValues<'a> {
+    data: &'a DeepChannelData,
+    start: usize,
+    end: usize,
+}
+
+/// Iterator over the mutable pixels of deep samples, in row-major order.
+/// The number of samples per pixel cannot be changed through this iterator.
+/// Created by [`DeepSamples::pixels_mut`] or by `AnyChannels::pixels_mut`.
 #[derive(Debug)]
-pub struct DeepPixelIter<'a> {
-    samples: &'a DeepSamples,
+pub struct DeepPixelsMut<'a> {
+    remaining: SmallVec<[DeepValuesMut<'a>; 4]>,
+    sample_offsets: &'a [u32],
+    width: usize,
+    names: ChannelNames<'a>,
     index: usize,
 }
 
-/// Reference to samples at a single pixel.
+/// The mutable samples of a single deep pixel. See [`DeepPixelView`].
 #[derive(Debug)]
-pub struct DeepPixelRef<'a> {
-    /// Number of samples at this pixel.
-    pub count: usize,
+pub struct DeepPixelViewMut<'a> {
+    /// The coordinates of this pixel.
+    pub position: Vec2<usize>,
+
+    channels: SmallVec<[DeepValuesMut<'a>; 4]>,
+    names: ChannelNames<'a>,
+}
+
+/// The mutable samples of one channel of a deep pixel.
+#[derive(Debug, PartialEq)]
+pub enum DeepValuesMut<'a> {
+    /// 16-bit float samples.
+    F16(&'a mut [f16]),
+
+    /// 32-bit float samples.
+    F32(&'a mut [f32]),
 
-    /// Starting index in channel data arrays.
-    pub start: usize,
+    /// 32-bit unsigned integer samples.
+    U32(&'a mut [u32]),
+}
 
-    /// Ending index (exclusive) in channel data arrays.
-    pub end: usize,
+/// Collects the samples of a single pixel, see [`DeepSamples::from_pixel_fn`].
+#[derive(Debug)]
+pub struct DeepPixelBuilder<'a> {
+    channels: &'a mut [DeepChannelData],
+}
 
-    /// Reference to channel data.
-    pub channels: &'a [DeepChannelData],
+/// The names of the channels, and the indices of the depth and alpha channels.
+/// Empty if the samples are not part of a list of named channels.
+/// The name of the first channel is stored separately, such that the samples,
+/// which are stored in the first channel, can be borrowed mutably at the same time.
+#[derive(Debug, Clone, Copy, Default)]
+struct ChannelNames<'a> {
+    first: Option<&'a Text>,
+    others: &'a [AnyChannel<DeepSamples>],
+    depth: Option<usize>,
+    alpha: Option<usize>,
 }
 
 impl DeepSamples {
@@ -263,26 +346,98 @@ impl DeepSamples {
         (start, end)
     }
 
-    /// Get reference to pixel samples at (x, y).
-    pub fn pixel(&self, x: usize, y: usize) -> DeepPixelRef<'_> {
-        let idx = y * self.width + x;
-        let (start, end) = self.sample_range(idx);
-        DeepPixelRef {
-            count: end - start,
+    /// The samples of the pixel at (x, y).
+    pub fn pixel(&self, x: usize, y: usize) -> DeepPixelView<'_> {
+        let (start, end) = self.sample_range(y * self.width + x);
+
+        DeepPixelView {
+            position: Vec2(x, y),
+            channels: &self.channels,
+            names: ChannelNames::default(),
             start,
             end,
+        }
+    }
+
+    /// Iterate over all pixels, in row-major order.
+    /// The channels of the pixels can only be accessed by index.
+    pub fn pixels(&self) -> DeepPixels<'_> {
+        DeepPixels {
             channels: &self.channels,
+            sample_offsets: &self.sample_offsets,
+            width: self.width,
+            names: ChannelNames::default(),
+            index: 0,
         }
     }
 
-    /// Iterate over all pixels.
-    pub fn pixels(&self) -> DeepPixelIter<'_> {
-        DeepPixelIter {
-            samples: self,
+    /// Iterate over all pixels, in row-major order, to modify their samples.
+    /// Panics if the length of a channel does not match the sample offsets.
+    pub fn pixels_mut(&mut self) -> DeepPixelsMut<'_> {
+        self.pixels_mut_with_names(ChannelNames::default())
+    }
+
+    fn pixels_mut_with_names<'a>(&'a mut self, names: ChannelNames<'a>) -> DeepPixelsMut<'a> {
+        let total = self.total_samples();
+
+        assert!(
+            self.channels.iter().all(|channel| channel.len() == total),
+            "deep channel length does not match the sample offsets"
+        );
+
+        DeepPixelsMut {
+            remaining: self.channels.iter_mut().map(DeepValuesMut::from).collect(),
+            sample_offsets: &self.sample_offsets,
+            width: self.width,
+            names,
             index: 0,
         }
     }
 
+    /// Create deep samples with channels of the specified sample types,
+    /// by calling the closure for each pixel in row-major order.
+    /// The closure pushes the samples of the pixel into the builder.
+    pub fn from_pixel_fn(
+        size: Vec2<usize>,
+        sample_types: &[SampleType],
+        mut pixel: impl FnMut(Vec2<usize>, &mut DeepPixelBuilder<'_>),
+    ) -> Result<Self> {
+        let mut channels: Vec<DeepChannelData> = sample_types
+            .iter()
+            .map(|sample_type| match sample_type {
+                SampleType::F16 => DeepChannelData::F16(Vec::new()),
+                SampleType::F32 => DeepChannelData::F32(Vec::new()),
+                SampleType::U32 => DeepChannelData::U32(Vec::new()),
+            })
+            .collect();
+
+        let mut sample_offsets = Vec::with_capacity(size.area());
+
+        for y in 0..size.height() {
+            for x in 0..size.width() {
+                pixel(
+                    Vec2(x, y),
+                    &mut DeepPixelBuilder {
+                        channels: &mut channels,
+                    },
+                );
+
+                let total = channels.first().map_or(0, DeepChannelData::len);
+                let offset = u32::try_from(total)
+                    .map_err(|_| Error::unsupported("too many deep samples"))?;
+
+                sample_offsets.push(offset);
+            }
+        }
+
+        Ok(Self {
+            sample_offsets,
+            channels,
+            width: size.width(),
+            height: size.height(),
+        })
+    }
+
     /// Set sample offsets from cumulative counts.
     /// Counts must be monotonically non-decreasing.
     pub fn set_cumulative_counts(&mut self, counts: Vec<u32>) -> Result<()> {
@@ -356,6 +511,350 @@ impl DeepSamples {
 
         Ok(())
     }
+
+    /// Copy the samples of the pixels inside the rectangle, starting at `position`.
+    /// Panics if the rectangle is not inside the image.
+    pub fn crop(&self, position: (usize, usize), size: (usize, usize)) -> Self {
+        let (x, y) = position;
+        let (width, height) = size;
+
+        assert!(
+            x + width <= self.width && y + height <= self.height,
+            "crop rectangle outside of the deep samples"
+        );
+
+        let mut sample_offsets = Vec::with_capacity(width * height);
+        let mut rows = Vec::with_capacity(height);
+        let mut total = 0u32;
+
+        if width > 0 {
+            for row in y..y + height {
+                let first = row * self.width + x;
+                let (start, _) = self.sample_range(first);
+                let (_, end) = self.sample_range(first + width - 1);
+
+                for index in first..first + width {
+                    total += self.sample_count_at_index(index) as u32;
+                    sample_offsets.push(total);
+                }
+
+                rows.push(start..end);
+            }
+        }
+
+        Self {
+            sample_offsets,
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| channel.copy_ranges(&rows))
+                .collect(),
+            width,
+            height,
+        }
+    }
+}
+
+/// The "tidy" operations of the OpenEXR deep data specification.
+/// Sorting, splitting and pruning are independent of each other,
+/// but splitting and pruning expect the samples of each pixel to be sorted by depth.
+impl DeepSamples {
+    /// Sort the samples of each pixel by their depth, front to back.
+    /// Samples with the same front depth are sorted by their back depth, if specified.
+    /// The sort is stable, so samples at the same depth keep their order.
+    pub fn sort_by_depth(&mut self, depth: usize, depth_back: Option<usize>) -> UnitResult {
+        let front = self.depth_values(depth)?;
+        let back = match depth_back {
+            Some(depth_back) => Some(self.depth_values(depth_back)?),
+            None => None,
+        };
+
+        let compare = |a: &usize, b: &usize| {
+            let by_front = front[*a].partial_cmp(&front[*b]).unwrap_or(Ordering::Equal);
+            match &back {
+                Some(back) => {
+                    by_front.then(back[*a].partial_cmp(&back[*b]).unwrap_or(Ordering::Equal))
+                }
+                None => by_front,
+            }
+        };
+
+        let mut order: Vec<usize> = (0..self.total_samples()).collect();
+        let mut is_sorted = true;
+
+        for pixel in 0..self.pixel_count() {
+            let (start, end) = self.sample_range(pixel);
+            let samples = &mut order[start..end];
+
+            if samples
+                .windows(2)
+                .any(|pair| compare(&pair[0], &pair[1]) == Ordering::Greater)
+            {
+                samples.sort_by(compare);
+                is_sorted = false;
+            }
+        }
+
+        if !is_sorted {
+            for channel in &mut self.channels {
+                *channel = channel.copy_indices(&order);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Split each volumetric sample at the front and back depths of all other samples of the pixel
+    /// that lie inside of its depth range, so that no two samples partially overlap.
+    /// The pieces replace the original sample, which means that the samples may need to be sorted again.
+    ///
+    /// `alpha_channels` contains the alpha channel of each channel, or `None` for channels that are copied.
+    /// A piece that covers a fraction of the volume has the alpha `1 - (1 - alpha)^fraction`,
+    /// and the premultiplied values of all channels with an alpha channel are scaled accordingly.
+    /// Volumes with infinite depths, and `u32` channels, are not changed.
+    pub fn split_volumes(
+        &mut self,
+        depth: usize,
+        depth_back: usize,
+        alpha_channels: &[Option<usize>],
+    ) -> UnitResult {
+        if alpha_channels.len() != self.channels.len() {
+            return Err(Error::invalid("alpha channel count"));
+        }
+
+        if alpha_channels
+            .iter()
+            .flatten()
+            .any(|&alpha| alpha >= self.channels.len())
+        {
+            return Err(Error::invalid("alpha channel index"));
+        }
+
+        let front = self.depth_values(depth)?;
+        let back = self.depth_values(depth_back)?;
+
+        let mut pieces: Vec<VolumePiece> = Vec::with_capacity(front.len());
+        let mut sample_offsets = Vec::with_capacity(self.pixel_count());
+        let mut boundaries: Vec<f32> = Vec::new();
+
+        for pixel in 0..self.pixel_count() {
+            let (start, end) = self.sample_range(pixel);
+
+            boundaries.clear();
+            boundaries.extend(front[start..end].iter().chain(&back[start..end]));
+            boundaries.retain(|depth| depth.is_finite());
+            boundaries.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            boundaries.dedup();
+
+            for sample in start..end {
+                let (near, far) = (front[sample], back[sample]);
+                let whole = VolumePiece {
+                    sample,
+                    near,
+                    far,
+                    fraction: 1.0,
+                };
+
+                if !(near.is_finite() && far.is_finite() && far > near) {
+                    pieces.push(whole);
+                    continue;
+                }
+
+                let mut piece_near = near;
+                for &boundary in boundaries
+                    .iter()
+                    .filter(|&&depth| depth > near && depth < far)
+                {
+                    pieces.push(VolumePiece::between(
+                        sample,
+                        piece_near,
+                        boundary,
+                        far - near,
+                    ));
+                    piece_near = boundary;
+                }
+
+                if piece_near == near {
+                    pieces.push(whole);
+                } else {
+                    pieces.push(VolumePiece::between(sample, piece_near, far, far - near));
+                }
+            }
+
+            let offset = u32::try_from(pieces.len())
+                .map_err(|_| Error::unsupported("too many deep samples"))?;
+
+            sample_offsets.push(offset);
+        }
+
+        if pieces.len() == front.len() {
+            return Ok(());
+        }
+
+        let sources: Vec<usize> = pieces.iter().map(|piece| piece.sample).collect();
+        let alpha_values: Vec<Option<Vec<f32>>> = (0..self.channels.len())
+            .map(|channel| {
+                alpha_channels
+                    .contains(&Some(channel))
+                    .then(|| self.channels[channel].to_f32_vec())
+            })
+            .collect();
+
+        let channels = self
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(channel, data)| {
+                let mut split = data.copy_indices(&sources);
+
+                if channel == depth {
+                    split.map_f32(|index, _| pieces[index].near);
+                } else if channel == depth_back {
+                    split.map_f32(|index, _| pieces[index].far);
+                } else if let (Some(alpha), false) = (
+                    alpha_channels[channel],
+                    data.sample_type() == SampleType::U32,
+                ) {
+                    let alpha = alpha_values[alpha].as_ref().expect("alpha values bug");
+
+                    split.map_f32(|index, value| {
+                        let piece = &pieces[index];
+                        value * partial_alpha_factor(alpha[piece.sample], piece.fraction)
+                    });
+                }
+
+                split
+            })
+            .collect();
+
+        self.sample_offsets = sample_offsets;
+        self.channels = channels;
+        Ok(())
+    }
+
+    /// Remove all samples that are hidden behind the samples in front of them.
+    /// The alpha of the samples of each pixel is accumulated front to back,
+    /// and all samples after the sample that reaches the threshold are removed.
+    /// A threshold of `1.0` removes only samples behind fully opaque samples.
+    /// Expects the samples to be sorted by depth, and volumes to be split.
+    pub fn prune_occluded(&mut self, alpha: usize, threshold: f32) -> UnitResult {
+        let alpha = self
+            .channels
+            .get(alpha)
+            .ok_or(Error::invalid("alpha channel index"))?
+            .to_f32_vec();
+
+        let mut ranges = Vec::with_capacity(self.pixel_count());
+        let mut sample_offsets = Vec::with_capacity(self.pixel_count());
+        let mut total = 0_u32;
+
+        for pixel in 0..self.pixel_count() {
+            let (start, end) = self.sample_range(pixel);
+
+            let mut accumulated = 0.0_f32;
+            let mut visible_end = end;
+
+            for sample in start..end {
+                accumulated += (1.0 - accumulated) * alpha[sample];
+
+                if accumulated >= threshold {
+                    visible_end = sample + 1;
+                    break;
+                }
+            }
+
+            total += (visible_end - start) as u32;
+            sample_offsets.push(total);
+            ranges.push(start..visible_end);
+        }
+
+        if total as usize == alpha.len() {
+            return Ok(());
+        }
+
+        self.channels = self
+            .channels
+            .iter()
+            .map(|channel| channel.copy_ranges(&ranges))
+            .collect();
+
+        self.sample_offsets = sample_offsets;
+        Ok(())
+    }
+
+    /// The values of the depth channel, converted to `f32`.
+    fn depth_values(&self, depth: usize) -> Result<Vec<f32>> {
+        self.channels
+            .get(depth)
+            .map(DeepChannelData::to_f32_vec)
+            .ok_or(Error::invalid("depth channel index"))
+    }
+}
+
+/// A part of a sample, between two depths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct VolumePiece {
+    sample: usize,
+    near: f32,
+    far: f32,
+
+    /// The fraction of the original volume.
+    fraction: f32,
+}
+
+impl VolumePiece {
+    fn between(sample: usize, near: f32, far: f32, volume_depth: f32) -> Self {
+        Self {
+            sample,
+            near,
+            far,
+            fraction: (far - near) / volume_depth,
+        }
+    }
+}
+
+impl AnyChannels<DeepSamples> {
+    /// Sort the samples of each pixel by the `Z` and `ZBack` channels,
+    /// split overlapping volumes, and remove the samples that are hidden
+    /// behind an accumulated alpha of the `A` channel that reaches the threshold.
+    /// Splitting requires a `ZBack` channel, and pruning requires an `A` channel.
+    /// Each color channel is split using the alpha channel of its layer, see `flatten`.
+    pub fn tidy(&mut self, opacity_threshold: f32) -> UnitResult {
+        let channel_count = match self.list.first() {
+            Some(channel) => channel.sample_data.channels.len(),
+            None => return Ok(()),
+        };
+
+        if channel_count != self.list.len() {
+            return Err(Error::invalid("deep channel count"));
+        }
+
+        let channel_index = |name: &str| self.list.iter().position(|channel| channel.name.eq(name));
+
+        let depth = channel_index("Z")
+            .ok_or(Error::invalid("tidying deep samples requires a Z channel"))?;
+
+        let depth_back = channel_index("ZBack");
+        let alpha = channel_index("A");
+
+        let alpha_channels: Vec<Option<usize>> = (0..self.list.len())
+            .map(|channel| alpha_channel_index(self, channel))
+            .collect();
+
+        let samples = &mut self.list[0].sample_data;
+        samples.sort_by_depth(depth, depth_back)?;
+
+        if let Some(depth_back) = depth_back {
+            samples.split_volumes(depth, depth_back, &alpha_channels)?;
+            samples.sort_by_depth(depth, Some(depth_back))?;
+        }
+
+        if let Some(alpha) = alpha {
+            samples.prune_occluded(alpha, opacity_threshold)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl DeepChannelData {
@@ -411,6 +910,29 @@ impl DeepChannelData {
         }
     }
 
+    /// Get the value at index converted to f32, for any sample type.
+    #[inline]
+    pub fn get_as_f32(&self, index: usize) -> f32 {
+        match self {
+            DeepChannelData::F16(v) => v[index].to_f32(),
+            DeepChannelData::F32(v) => v[index],
+            DeepChannelData::U32(v) => v[index] as f32,
+        }
+    }
+
+    /// Convert all values to f32, for any sample type.
+    /// Half floats are converted in batches, using the conversion instructions
+    /// of the CPU where available (F16C on x86, NEON on ARM, with `half` 2.3.0 or newer), with a scalar fallback.
+    pub fn to_f32_vec(&self) -> Vec<f32> {
+        use half::slice::HalfFloatSliceExt;
+
+        match self {
+            DeepChannelData::F16(v) => v.to_f32_vec(),
+            DeepChannelData::F32(v) => v.clone(),
+            DeepChannelData::U32(v) => v.iter().map(|&value| value as f32).collect(),
+        }
+    }
+
     /// Get mutable f16 slice.
     pub fn as_f16_mut(&mut self) -> Option<&mut Vec<f16>> {
         match self {
@@ -435,6 +957,56 @@ impl DeepChannelData {
         }
     }
 
+    /// Concatenate the samples of the ranges.
+    fn copy_ranges(&self, ranges: &[std::ops::Range<usize>]) -> Self {
+        fn copy<T: Copy>(values: &[T], ranges: &[std::ops::Range<usize>]) -> Vec<T> {
+            let mut copied = Vec::with_capacity(ranges.iter().map(|range| range.len()).sum());
+
+            for range in ranges {
+                copied.extend_from_slice(&values[range.clone()]);
+            }
+
+            copied
+        }
+
+        match self {
+            DeepChannelData::F16(v) => DeepChannelData::F16(copy(v, ranges)),
+            DeepChannelData::F32(v) => DeepChannelData::F32(copy(v, ranges)),
+            DeepChannelData::U32(v) => DeepChannelData::U32(copy(v, ranges)),
+        }
+    }
+
+    /// Copy the samples at the indices, in the order of the indices.
+    fn copy_indices(&self, indices: &[usize]) -> Self {
+        fn copy<T: Copy>(values: &[T], indices: &[usize]) -> Vec<T> {
+            indices.iter().map(|&index| values[index]).collect()
+        }
+
+        match self {
+            DeepChannelData::F16(v) => DeepChannelData::F16(copy(v, indices)),
+            DeepChannelData::F32(v) => DeepChannelData::F32(copy(v, indices)),
+            DeepChannelData::U32(v) => DeepChannelData::U32(copy(v, indices)),
+        }
+    }
+
+    /// Replace each sample by a value computed from its index and its value, converted to `f32`.
+    fn map_f32(&mut self, mut map: impl FnMut(usize, f32) -> f32) {
+        match self {
+            DeepChannelData::F16(v) => v
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, value)| *value = f16::from_f32(map(index, value.to_f32()))),
+            DeepChannelData::F32(v) => v
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, value)| *value = map(index, *value)),
+            DeepChannelData::U32(v) => v
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, value)| *value = map(index, *value as f32) as u32),
+        }
+    }
+
     /// Bytes per sample element.
     pub fn bytes_per_sample(&self) -> usize {
         match self {
@@ -445,34 +1017,292 @@ impl DeepChannelData {
     }
 }
 
-impl<'a> Iterator for DeepPixelIter<'a> {
-    type Item = DeepPixelRef<'a>;
+/// A value type of deep channels: `f16`, `f32`, or `u32`.
+///
+/// Loops over the samples of a channel are generic over this trait,
+/// so that the compiler generates one specialized loop per sample type,
+/// instead of matching the sample type for each value.
+pub trait DeepSample: Copy + Default + Send + Sync + 'static {
+    /// The sample type of channels that contain this value type.
+    const SAMPLE_TYPE: SampleType;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.samples.pixel_count() {
-            return None;
+    /// Read a value from its little-endian bytes. The slice must have the size of the value.
+    fn from_le_slice(bytes: &[u8]) -> Self;
+
+    /// Write the little-endian bytes of this value. The slice must have the size of the value.
+    fn write_le_slice(self, bytes: &mut [u8]);
+
+    /// The values of the channel, or `None` if the channel contains another value type.
+    fn channel_values(data: &DeepChannelData) -> Option<&[Self]>;
+
+    /// Wrap the values in channel data of the corresponding type.
+    fn into_channel_data(values: Vec<Self>) -> DeepChannelData;
+
+    /// The values of a pixel, or `None` if they are of another value type.
+    fn values<'v>(values: &'v DeepValuesMut<'_>) -> Option<&'v [Self]>;
+
+    /// The mutable values of a pixel, or `None` if they are of another value type.
+    fn values_mut<'v>(values: &'v mut DeepValuesMut<'_>) -> Option<&'v mut [Self]>;
+}
+
+macro_rules! implement_deep_sample {
+    ($type: ty, $variant: ident) => {
+        impl DeepSample for $type {
+            const SAMPLE_TYPE: SampleType = SampleType::$variant;
+
+            #[inline]
+            fn from_le_slice(bytes: &[u8]) -> Self {
+                <$type>::from_le_bytes(bytes.try_into().expect("deep sample value size"))
+            }
+
+            #[inline]
+            fn write_le_slice(self, bytes: &mut [u8]) {
+                bytes.copy_from_slice(&self.to_le_bytes());
+            }
+
+            #[inline]
+            fn channel_values(data: &DeepChannelData) -> Option<&[Self]> {
+                match data {
+                    DeepChannelData::$variant(values) => Some(values),
+                    _ => None,
+                }
+            }
+
+            fn into_channel_data(values: Vec<Self>) -> DeepChannelData {
+                DeepChannelData::$variant(values)
+            }
+
+            #[inline]
+            fn values<'v>(values: &'v DeepValuesMut<'_>) -> Option<&'v [Self]> {
+                match values {
+                    DeepValuesMut::$variant(values) => Some(values),
+                    _ => None,
+                }
+            }
+
+            #[inline]
+            fn values_mut<'v>(values: &'v mut DeepValuesMut<'_>) -> Option<&'v mut [Self]> {
+                match values {
+                    DeepValuesMut::$variant(values) => Some(values),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+implement_deep_sample!(f16, F16);
+implement_deep_sample!(f32, F32);
+implement_deep_sample!(u32, U32);
+
+impl AnyChannels<DeepSamples> {
+    /// Iterate over all pixels, in row-major order.
+    /// The channels of the pixels can be accessed by name, and the `Z` and `A` channels
+    /// are available through `DeepPixelView::z` and `DeepPixelView::alpha`.
+    pub fn pixels(&self) -> DeepPixels<'_> {
+        match self.list.first() {
+            Some(channel) => DeepPixels {
+                names: ChannelNames::new(&channel.name, &self.list[1..]),
+                ..channel.sample_data.pixels()
+            },
+
+            None => DeepPixels {
+                channels: &[],
+                sample_offsets: &[],
+                width: 0,
+                names: ChannelNames::default(),
+                index: 0,
+            },
+        }
+    }
+
+    /// Iterate over all pixels, in row-major order, to modify their samples.
+    /// The channels of the pixels can be accessed by name.
+    /// Panics if the length of a channel does not match the sample offsets.
+    pub fn pixels_mut(&mut self) -> DeepPixelsMut<'_> {
+        match self.list.split_first_mut() {
+            Some((first, others)) => {
+                let names = ChannelNames::new(&first.name, others);
+                first.sample_data.pixels_mut_with_names(names)
+            }
+
+            None => DeepPixelsMut {
+                remaining: SmallVec::new(),
+                sample_offsets: &[],
+                width: 0,
+                names: ChannelNames::default(),
+                index: 0,
+            },
+        }
+    }
+
+    /// Create deep channels with the specified names and sample types,
+    /// by calling the closure for each pixel in row-major order.
+    /// The closure pushes the samples of the pixel into the builder,
+    /// with one value per channel in the order of the specified channels.
+    /// The channels are sorted by name afterwards.
+    pub fn from_pixel_fn(
+        size: Vec2<usize>,
+        channels: &[(&str, SampleType)],
+        pixel: impl FnMut(Vec2<usize>, &mut DeepPixelBuilder<'_>),
+    ) -> Result<Self> {
+        let sample_types: Vec<SampleType> = channels.iter().map(|&(_, kind)| kind).collect();
+        let mut samples = DeepSamples::from_pixel_fn(size, &sample_types, pixel)?;
+
+        let mut order: Vec<usize> = (0..channels.len()).collect();
+        order.sort_by_key(|&index| channels[index].0);
+
+        if order
+            .windows(2)
+            .any(|pair| channels[pair[0]].0 == channels[pair[1]].0)
+        {
+            return Err(Error::invalid("duplicate deep channel name"));
+        }
+
+        let mut data: Vec<Option<DeepChannelData>> = samples.channels.drain(..).map(Some).collect();
+
+        samples.channels = order
+            .iter()
+            .map(|&index| data[index].take().expect("channel order bug"))
+            .collect();
+
+        let mut samples = Some(samples);
+        let list = order
+            .iter()
+            .map(|&index| {
+                let name = Text::from(channels[index].0);
+
+                AnyChannel {
+                    quantize_linearly: ChannelDescription::guess_quantization_linearity(&name),
+                    name,
+                    sample_data: samples.take().unwrap_or_else(|| DeepSamples::new(0, 0)),
+                    sampling: Vec2(1, 1),
+                }
+            })
+            .collect();
+
+        Ok(AnyChannels { list })
+    }
+}
+
+impl<'a> ChannelNames<'a> {
+    fn new(first: &'a Text, others: &'a [AnyChannel<DeepSamples>]) -> Self {
+        let mut names = Self {
+            first: Some(first),
+            others,
+            depth: None,
+            alpha: None,
+        };
+
+        names.depth = names.index("Z");
+        names.alpha = names.index("A");
+        names
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        if self.first?.eq(name) {
+            return Some(0);
         }
 
-        let (start, end) = self.samples.sample_range(self.index);
+        let index = self
+            .others
+            .iter()
+            .position(|channel| channel.name.eq(name))?;
+        Some(index + 1)
+    }
+}
+
+impl<'a> Iterator for DeepPixels<'a> {
+    type Item = DeepPixelView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = *self.sample_offsets.get(self.index)? as usize;
+        let start = match self.index {
+            0 => 0,
+            index => self.sample_offsets[index - 1] as usize,
+        };
+
+        let position = Vec2(self.index % self.width, self.index / self.width);
         self.index += 1;
 
-        Some(DeepPixelRef {
-            count: end - start,
+        Some(DeepPixelView {
+            position,
+            channels: self.channels,
+            names: self.names,
             start,
             end,
-            channels: &self.samples.channels,
         })
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let remaining = self.samples.pixel_count() - self.index;
+        let remaining = self.sample_offsets.len() - self.index;
         (remaining, Some(remaining))
     }
 }
 
-impl<'a> ExactSizeIterator for DeepPixelIter<'a> {}
+impl<'a> ExactSizeIterator for DeepPixels<'a> {}
+
+impl<'a> DeepPixelView<'a> {
+    /// Number of samples at this pixel.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Whether this pixel contains no samples.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The indices of the samples of this pixel in the channel data of all pixels.
+    #[inline]
+    pub fn sample_indices(&self) -> std::ops::Range<usize> {
+        self.start..self.end
+    }
+
+    /// The index of the channel with this name, if the channels have names.
+    pub fn channel_index(&self, name: &str) -> Option<usize> {
+        self.names.index(name)
+    }
+
+    /// The samples of the named channel, or `None` if there is no such channel,
+    /// or if it contains another sample type.
+    pub fn channel<T: DeepSample>(&self, name: &str) -> Option<&'a [T]> {
+        self.channel_at(self.channel_index(name)?)
+    }
+
+    /// The samples of the channel at the index, or `None` if there is no such channel,
+    /// or if it contains another sample type.
+    pub fn channel_at<T: DeepSample>(&self, index: usize) -> Option<&'a [T]> {
+        let values = T::channel_values(self.channels.get(index)?)?;
+        Some(&values[self.start..self.end])
+    }
+
+    /// The samples of the named channel, converted to `f32`.
+    pub fn values(&self, name: &str) -> Option<DeepValues<'a>> {
+        self.values_at(self.channel_index(name)?)
+    }
+
+    /// The samples of the channel at the index, converted to `f32`.
+    pub fn values_at(&self, index: usize) -> Option<DeepValues<'a>> {
+        Some(DeepValues {
+            data: self.channels.get(index)?,
+            start: self.start,
+            end: self.end,
+        })
+    }
+
+    /// The samples of the `Z` channel, converted to `f32`.
+    pub fn z(&self) -> Option<DeepValues<'a>> {
+        self.values_at(self.names.depth?)
+    }
+
+    /// The samples of the `A` channel, converted to `f32`.
+    pub fn alpha(&self) -> Option<DeepValues<'a>> {
+        self.values_at(self.names.alpha?)
+    }
 
-impl<'a> DeepPixelRef<'a> {
     /// Get f16 sample at given sample index within this pixel.
     pub fn get_f16(&self, channel: usize, sample: usize) -> f16 {
         self.channels[channel].get_f16(self.start + sample)
@@ -489,6 +1319,208 @@ impl<'a> DeepPixelRef<'a> {
     }
 }
 
+impl<'a> DeepValues<'a> {
+    /// Number of samples.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Whether there are no samples.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The sample at the index, converted to `f32`. Panics if the index is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> f32 {
+        assert!(index < self.len(), "deep sample index out of bounds");
+        self.data.get_as_f32(self.start + index)
+    }
+
+    /// Iterate over the samples, converted to `f32`.
+    pub fn iter(&self) -> impl Iterator<Item = f32> + 'a {
+        let data = self.data;
+        (self.start..self.end).map(move |index| data.get_as_f32(index))
+    }
+}
+
+impl<'a> Iterator for DeepPixelsMut<'a> {
+    type Item = DeepPixelViewMut<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = *self.sample_offsets.get(self.index)? as usize;
+        let start = match self.index {
+            0 => 0,
+            index => self.sample_offsets[index - 1] as usize,
+        };
+
+        let position = Vec2(self.index % self.width, self.index / self.width);
+        self.index += 1;
+
+        let count = end - start;
+        let channels = self
+            .remaining
+            .iter_mut()
+            .map(|values| values.split_off_front(count))
+            .collect();
+
+        Some(DeepPixelViewMut {
+            position,
+            channels,
+            names: self.names,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.sample_offsets.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for DeepPixelsMut<'a> {}
+
+impl<'a> DeepPixelViewMut<'a> {
+    /// Number of samples at this pixel.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.channels.first().map_or(0, DeepValuesMut::len)
+    }
+
+    /// Whether this pixel contains no samples.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The index of the channel with this name, if the channels have names.
+    pub fn channel_index(&self, name: &str) -> Option<usize> {
+        self.names.index(name)
+    }
+
+    /// The samples of the named channel, or `None` if there is no such channel,
+    /// or if it contains another sample type.
+    pub fn channel<T: DeepSample>(&self, name: &str) -> Option<&[T]> {
+        T::values(self.channels.get(self.channel_index(name)?)?)
+    }
+
+    /// The mutable samples of the named channel, or `None` if there is no such channel,
+    /// or if it contains another sample type.
+    pub fn channel_mut<T: DeepSample>(&mut self, name: &str) -> Option<&mut [T]> {
+        self.channel_at_mut(self.channel_index(name)?)
+    }
+
+    /// The mutable samples of the channel at the index, or `None` if there is no such channel,
+    /// or if it contains another sample type.
+    pub fn channel_at_mut<T: DeepSample>(&mut self, index: usize) -> Option<&mut [T]> {
+        T::values_mut(self.channels.get_mut(index)?)
+    }
+
+    /// The mutable samples of the named channel, of any sample type.
+    pub fn values_mut(&mut self, name: &str) -> Option<&mut DeepValuesMut<'a>> {
+        self.values_at_mut(self.channel_index(name)?)
+    }
+
+    /// The mutable samples of the channel at the index, of any sample type.
+    pub fn values_at_mut(&mut self, index: usize) -> Option<&mut DeepValuesMut<'a>> {
+        self.channels.get_mut(index)
+    }
+
+    /// The mutable samples of the `Z` channel.
+    pub fn z_mut(&mut self) -> Option<&mut DeepValuesMut<'a>> {
+        self.values_at_mut(self.names.depth?)
+    }
+
+    /// The mutable samples of the `A` channel.
+    pub fn alpha_mut(&mut self) -> Option<&mut DeepValuesMut<'a>> {
+        self.values_at_mut(self.names.alpha?)
+    }
+}
+
+impl<'a> From<&'a mut DeepChannelData> for DeepValuesMut<'a> {
+    fn from(data: &'a mut DeepChannelData) -> Self {
+        match data {
+            DeepChannelData::F16(values) => DeepValuesMut::F16(values),
+            DeepChannelData::F32(values) => DeepValuesMut::F32(values),
+            DeepChannelData::U32(values) => DeepValuesMut::U32(values),
+        }
+    }
+}
+
+impl<'a> DeepValuesMut<'a> {
+    /// Number of samples.
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            DeepValuesMut::F16(values) => values.len(),
+            DeepValuesMut::F32(values) => values.len(),
+            DeepValuesMut::U32(values) => values.len(),
+        }
+    }
+
+    /// Whether there are no samples.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The sample at the index, converted to `f32`. Panics if the index is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> f32 {
+        match self {
+            DeepValuesMut::F16(values) => values[index].to_f32(),
+            DeepValuesMut::F32(values) => values[index],
+            DeepValuesMut::U32(values) => values[index] as f32,
+        }
+    }
+
+    /// Replace the sample at the index, converting the value to the sample type.
+    /// Panics if the index is out of bounds.
+    #[inline]
+    pub fn set(&mut self, index: usize, value: f32) {
+        match self {
+            DeepValuesMut::F16(values) => values[index] = f16::from_f32(value),
+            DeepValuesMut::F32(values) => values[index] = value,
+            DeepValuesMut::U32(values) => values[index] = value as u32,
+        }
+    }
+
+    /// Remove the first samples from these values, and return them.
+    fn split_off_front(&mut self, count: usize) -> Self {
+        fn split<'v, T>(values: &mut &'v mut [T], count: usize) -> &'v mut [T] {
+            let (front, back) = std::mem::take(values).split_at_mut(count);
+            *values = back;
+            front
+        }
+
+        match self {
+            DeepValuesMut::F16(values) => DeepValuesMut::F16(split(values, count)),
+            DeepValuesMut::F32(values) => DeepValuesMut::F32(split(values, count)),
+            DeepValuesMut::U32(values) => DeepValuesMut::U32(split(values, count)),
+        }
+    }
+}
+
+impl DeepPixelBuilder<'_> {
+    /// Add a sample to the pixel, with one value per channel, converted to the sample type.
+    /// Panics if the number of values is not the number of channels.
+    pub fn push(&mut self, values: &[f32]) {
+        assert_eq!(
+            values.len(),
+            self.channels.len(),
+            "deep sample value count does not match the channel count"
+        );
+
+        for (channel, &value) in self.channels.iter_mut().zip(values) {
+            match channel {
+                DeepChannelData::F16(samples) => samples.push(f16::from_f32(value)),
+                DeepChannelData::F32(samples) => samples.push(value),
+                DeepChannelData::U32(samples) => samples.push(value as u32),
+            }
+        }
+    }
+}
 #[cfg(test)]
 mod test {
     use super::*;
@@ -514,7 +1546,7 @@ mod test {
         let mut samples = DeepSamples::new(2, 2);
         samples.set_cumulative_counts(vec![1, 3, 3, 5]).unwrap();
 
-        let counts: Vec<_> = samples.pixels().map(|p| p.count).collect();
+        let counts: Vec<_> = samples.pixels().map(|p| p.len()).collect();
         assert_eq!(counts, vec![1, 2, 0, 2]);
     }
 
@@ -531,6 +1563,42 @@ mod test {
         assert_eq!(channel.get_f32(0), 10.0);
     }
 
+    #[test]
+    fn deep_channel_data_to_f32() {
+        // more than one batch of the hardware conversion, with a remainder
+        let halves: Vec<f16> = (0..19).map(|i| f16::from_f32(i as f32 * 0.25)).collect();
+        let expected: Vec<f32> = (0..19).map(|i| i as f32 * 0.25).collect();
+        assert_eq!(DeepChannelData::F16(halves).to_f32_vec(), expected);
+
+        assert_eq!(
+            DeepChannelData::U32(vec![7, 9]).to_f32_vec(),
+            vec![7.0, 9.0]
+        );
+    }
+
+    #[test]
+    fn deep_samples_crop() {
+        let mut samples = DeepSamples::new(3, 2);
+        // counts: [1, 0, 2]
+        //         [1, 3, 0]
+        samples
+            .set_cumulative_counts(vec![1, 1, 3, 4, 7, 7])
+            .unwrap();
+        samples
+            .channels
+            .push(DeepChannelData::U32((0..7).collect()));
+
+        let cropped = samples.crop((1, 0), (2, 2));
+        assert_eq!(cropped.width, 2);
+        assert_eq!(cropped.height, 2);
+        assert_eq!(cropped.sample_offsets, vec![0, 2, 5, 5]);
+        assert_eq!(
+            cropped.channels,
+            vec![DeepChannelData::U32(vec![1, 2, 4, 5, 6])]
+        );
+        assert!(cropped.validate().is_ok());
+    }
+
     #[test]
     fn deep_samples_validation() {
         let mut samples = DeepSamples::new(2, 2);
@@ -574,7 +1642,7 @@ mod test {
         }
 
         // Iteration should work with all zero-count pixels
-        let counts: Vec<_> = samples.pixels().map(|p| p.count).collect();
+        let counts: Vec<_> = samples.pixels().map(|p| p.len()).collect();
         assert_eq!(counts, vec![0; 9]);
     }
 
@@ -594,7 +1662,7 @@ mod test {
 
         // Each pixel should have exactly one sample
         for (i, pixel) in samples.pixels().enumerate() {
-            assert_eq!(pixel.count, 1);
+            assert_eq!(pixel.len(), 1);
             assert_eq!(pixel.get_f32(0, 0), (i + 1) as f32);
         }
     }
@@ -706,4 +1774,264 @@ mod test {
         assert_eq!(samples.sample_count(2, 0), 0);
         assert_eq!(samples.sample_count(3, 0), 1);
     }
+
+    /// A single pixel with the channels `A`, `R`, `Z`, `ZBack` and `id`:
+    /// a half transparent volume from 0 to 4, an opaque point at 2, and a point at 1.
+    fn overlapping_samples() -> DeepSamples {
+        let mut samples = DeepSamples::new(1, 1);
+        samples.set_cumulative_counts(vec![3]).unwrap();
+
+        samples.channels = vec![
+            DeepChannelData::F32(vec![0.75, 1.0, 0.5]),
+            DeepChannelData::F16(vec![
+                f16::from_f32(0.75),
+                f16::from_f32(1.0),
+                f16::from_f32(0.25),
+            ]),
+            DeepChannelData::F32(vec![0.0, 2.0, 1.0]),
+            DeepChannelData::F32(vec![4.0, 2.0, 1.0]),
+            DeepChannelData::U32(vec![10, 20, 30]),
+        ];
+
+        samples
+    }
+
+    const OVERLAPPING_ALPHA: [Option<usize>; 5] = [Some(0), Some(0), None, None, None];
+
+    #[test]
+    fn sort_samples_by_depth() {
+        let mut samples = DeepSamples::new(2, 1);
+        samples.set_cumulative_counts(vec![3, 5]).unwrap();
+        samples.channels = vec![
+            DeepChannelData::F32(vec![3.0, 1.0, 1.0, 5.0, 6.0]),
+            DeepChannelData::F32(vec![3.0, 2.0, 1.0, 5.0, 6.0]),
+            DeepChannelData::U32(vec![0, 1, 2, 3, 4]),
+        ];
+
+        let mut by_front = samples.clone();
+        by_front.sort_by_depth(0, None).unwrap();
+        assert_eq!(
+            by_front.channels[2],
+            DeepChannelData::U32(vec![1, 2, 0, 3, 4])
+        );
+
+        samples.sort_by_depth(0, Some(1)).unwrap();
+        assert_eq!(
+            samples.channels[2],
+            DeepChannelData::U32(vec![2, 1, 0, 3, 4])
+        );
+        assert_eq!(samples.sample_offsets, vec![3, 5]);
+
+        assert!(samples.sort_by_depth(3, None).is_err());
+    }
+
+    #[test]
+    fn split_overlapping_volumes() {
+        let mut samples = overlapping_samples();
+        samples.split_volumes(2, 3, &OVERLAPPING_ALPHA).unwrap();
+
+        // the volume is split at 1 and 2, into quarters, halves and quarters
+        assert_eq!(samples.total_samples(), 5);
+        assert!(samples.validate().is_ok());
+
+        assert_eq!(
+            samples.channels[2],
+            DeepChannelData::F32(vec![0.0, 1.0, 2.0, 2.0, 1.0])
+        );
+        assert_eq!(
+            samples.channels[3],
+            DeepChannelData::F32(vec![1.0, 2.0, 4.0, 2.0, 1.0])
+        );
+        assert_eq!(
+            samples.channels[4],
+            DeepChannelData::U32(vec![10, 10, 10, 20, 30])
+        );
+
+        // the pieces of the volume composite to the original alpha
+        let alpha = samples.channels[0].to_f32_vec();
+        let quarter = 1.0 - 0.25_f32.powf(0.25);
+        let half = 1.0 - 0.25_f32.powf(0.5);
+        assert!((alpha[0] - quarter).abs() < 1e-6);
+        assert!((alpha[1] - quarter).abs() < 1e-6);
+        assert!((alpha[2] - half).abs() < 1e-6);
+        assert_eq!(&alpha[3..], &[1.0, 0.5]);
+
+        let remaining = alpha[..3]
+            .iter()
+            .fold(1.0, |remaining, alpha| remaining * (1.0 - alpha));
+        assert!((remaining - 0.25).abs() < 1e-6);
+
+        // the color keeps its ratio to the alpha
+        let red = samples.channels[1].to_f32_vec();
+        assert!((red[2] - half).abs() < 1e-3);
+
+        let mut unchanged = overlapping_samples();
+        unchanged.channels[3] = unchanged.channels[2].clone();
+        unchanged.split_volumes(2, 3, &OVERLAPPING_ALPHA).unwrap();
+        assert_eq!(unchanged.total_samples(), 3);
+
+        assert!(overlapping_samples().split_volumes(2, 3, &[None]).is_err());
+    }
+
+    #[test]
+    fn prune_occluded_samples() {
+        let mut samples = DeepSamples::new(3, 1);
+        samples.set_cumulative_counts(vec![3, 5, 5]).unwrap();
+        samples.channels = vec![
+            DeepChannelData::F32(vec![0.5, 1.0, 0.3, 0.5, 0.5]),
+            DeepChannelData::U32(vec![0, 1, 2, 3, 4]),
+        ];
+
+        let mut opaque = samples.clone();
+        opaque.prune_occluded(0, 1.0).unwrap();
+        assert_eq!(opaque.sample_offsets, vec![2, 4, 4]);
+        assert_eq!(opaque.channels[1], DeepChannelData::U32(vec![0, 1, 3, 4]));
+
+        samples.prune_occluded(0, 0.7).unwrap();
+        assert_eq!(samples.sample_offsets, vec![2, 4, 4]);
+
+        let mut strict = opaque.clone();
+        strict.prune_occluded(0, 0.5).unwrap();
+        assert_eq!(strict.channels[1], DeepChannelData::U32(vec![0, 3]));
+        assert!(strict.validate().is_ok());
+    }
+
+    #[test]
+    fn tidy_channels() {
+        use crate::image::AnyChannel;
+        use crate::math::Vec2;
+        use crate::meta::attribute::Text;
+
+        let samples = overlapping_samples();
+        let list = ["A", "R", "Z", "ZBack", "id"]
+            .iter()
+            .map(|&name| AnyChannel {
+                name: Text::from(name),
+                sample_data: samples.clone(),
+                quantize_linearly: false,
+                sampling: Vec2(1, 1),
+            })
+            .collect();
+
+        let mut channels = AnyChannels { list };
+        channels.tidy(1.0).unwrap();
+        let tidy = &channels.list[0].sample_data;
+
+        // the point at 1 is inside the volume, and the back of the volume is behind the opaque point
+        assert_eq!(
+            tidy.channels[2],
+            DeepChannelData::F32(vec![0.0, 1.0, 1.0, 2.0])
+        );
+        assert_eq!(
+            tidy.channels[3],
+            DeepChannelData::F32(vec![1.0, 1.0, 2.0, 2.0])
+        );
+        assert_eq!(tidy.channels[4], DeepChannelData::U32(vec![10, 30, 10, 20]));
+
+        let flat = channels.flatten(Default::default()).unwrap();
+        assert_eq!(
+            flat.list[0].sample_data.value_by_flat_index(0).to_f32(),
+            1.0
+        );
+
+        channels.list.retain(|channel| !channel.name.eq("Z"));
+        assert!(channels.tidy(1.0).is_err());
+    }
+
+    #[test]
+    fn pixel_views_by_name() {
+        let channels = AnyChannels::from_pixel_fn(
+            Vec2(2, 2),
+            &[
+                ("Z", SampleType::F32),
+                ("A", SampleType::F16),
+                ("id", SampleType::U32),
+            ],
+            |position, pixel| {
+                for sample in 0..position.x() + position.y() {
+                    pixel.push(&[sample as f32, 0.5, position.y() as f32]);
+                }
+            },
+        )
+        .unwrap();
+
+        let names: Vec<String> = channels
+            .list
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+        assert_eq!(names, vec!["A", "Z", "id"]);
+
+        let samples = &channels.list[0].sample_data;
+        assert_eq!(samples.sample_offsets, vec![0, 1, 2, 4]);
+        assert!(samples.validate().is_ok());
+
+        let pixels: Vec<DeepPixelView<'_>> = channels.pixels().collect();
+        assert_eq!(pixels.len(), 4);
+        assert!(pixels[0].is_empty());
+
+        let last = pixels[3];
+        assert_eq!(last.position, Vec2(1, 1));
+        assert_eq!(last.channel::<f32>("Z"), Some(&[0.0, 1.0][..]));
+        assert_eq!(last.channel::<u32>("id"), Some(&[1, 1][..]));
+        assert_eq!(last.channel::<f32>("A"), None);
+        assert_eq!(last.channel::<f32>("missing"), None);
+
+        assert_eq!(last.z().unwrap().iter().collect::<Vec<_>>(), vec![0.0, 1.0]);
+        assert_eq!(last.alpha().unwrap().get(1), 0.5);
+
+        // without names, channels can only be accessed by index
+        let unnamed = samples.pixel(1, 1);
+        assert!(unnamed.z().is_none());
+        assert_eq!(unnamed.channel_at::<u32>(2), Some(&[1, 1][..]));
+    }
+
+    #[test]
+    fn modify_pixels() {
+        let mut channels = AnyChannels::from_pixel_fn(
+            Vec2(3, 1),
+            &[("A", SampleType::F16), ("Z", SampleType::F32)],
+            |position, pixel| {
+                for _ in 0..position.x() {
+                    pixel.push(&[1.0, 2.0]);
+                }
+            },
+        )
+        .unwrap();
+
+        for mut pixel in channels.pixels_mut() {
+            let x = pixel.position.x() as f32;
+
+            for depth in pixel.channel_mut::<f32>("Z").unwrap() {
+                *depth += x;
+            }
+
+            let alpha = pixel.alpha_mut().unwrap();
+            for index in 0..alpha.len() {
+                alpha.set(index, 0.25);
+            }
+        }
+
+        let samples = &channels.list[0].sample_data;
+        assert_eq!(
+            samples.channels[1],
+            DeepChannelData::F32(vec![3.0, 4.0, 4.0])
+        );
+        assert_eq!(samples.channels[0].to_f32_vec(), vec![0.25; 3]);
+
+        let mut unnamed = samples.clone();
+        let counts: Vec<usize> = unnamed.pixels_mut().map(|pixel| pixel.len()).collect();
+        assert_eq!(counts, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn reject_duplicate_channel_names() {
+        let channels = AnyChannels::from_pixel_fn(
+            Vec2(1, 1),
+            &[("Z", SampleType::F32), ("Z", SampleType::F16)],
+            |_, _| {},
+        );
+
+        assert!(channels.is_err());
+    }
 }
@@ -0,0 +1,191 @@
+//! Statistics of the samples of deep images, for status displays and quality control reports.
+//!
+//! ```no_run
+//! use exr::image::read::deep::read_first_deep_layer_from_file;
+//!
+//! let image = read_first_deep_layer_from_file("deep.exr").unwrap();
+//! let stats = image.layer_data.channel_data.stats();
+//!
+//! println!(
+//!     "{} samples, {:.1} per pixel, {:.1}% empty pixels",
+//!     stats.total_samples,
+//!     stats.mean_samples_per_pixel(),
+//!     stats.empty_percentage()
+//! );
+//! ```
+
+use crate::image::deep::DeepSamples;
+use crate::image::memory::{MemoryUsage, SamplesMemoryUsage};
+use crate::image::AnyChannels;
+use crate::math::Vec2;
+
+/// The distribution of the sample counts of deep samples,
+/// the range of their depth, and the memory they occupy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeepStats {
+    /// The number of pixels.
+    pub pixel_count: usize,
+
+    /// The number of samples of all pixels.
+    pub total_samples: usize,
+
+    /// The smallest number of samples in a pixel.
+    pub min_samples_per_pixel: usize,
+
+    /// The largest number of samples in a pixel.
+    pub max_samples_per_pixel: usize,
+
+    /// The first pixel, in row-major order, that contains the largest number of samples.
+    /// `None` if there are no pixels.
+    pub max_samples_position: Option<Vec2<usize>>,
+
+    /// The number of pixels without any sample.
+    pub empty_pixels: usize,
+
+    /// The smallest and largest finite value of the depth channel.
+    /// `None` if the depth channel is unknown, or contains no finite value.
+    pub depth_range: Option<(f32, f32)>,
+
+    /// The memory occupied by the samples and their offset table.
+    pub memory: MemoryUsage,
+
+    /// The number of pixels for each sample count: `histogram[n]` pixels contain `n` samples.
+    /// Has an entry for each sample count up to the largest number of samples in a pixel.
+    pub histogram: Vec<usize>,
+}
+
+impl DeepStats {
+    /// Compute the statistics of the samples. The depth range is unknown,
+    /// as the samples do not know which channel contains the depth.
+    pub fn compute(samples: &DeepSamples) -> Self {
+        let mut histogram = Vec::new();
+        let mut max_samples_position = None;
+        let mut max_samples_per_pixel = 0;
+
+        for pixel in samples.pixels() {
+            let count = pixel.len();
+
+            if max_samples_position.is_none() || count > max_samples_per_pixel {
+                max_samples_per_pixel = count;
+                max_samples_position = Some(pixel.position);
+            }
+
+            if count >= histogram.len() {
+                histogram.resize(count + 1, 0);
+            }
+
+            histogram[count] += 1;
+        }
+
+        DeepStats {
+            pixel_count: samples.pixel_count(),
+            total_samples: samples.total_samples(),
+            min_samples_per_pixel: histogram.iter().position(|&pixels| pixels > 0).unwrap_or(0),
+            max_samples_per_pixel,
+            max_samples_position,
+            empty_pixels: histogram.first().copied().unwrap_or(0),
+            depth_range: None,
+            memory: samples.memory_usage(),
+            histogram,
+        }
+    }
+
+    /// Compute the statistics of the samples, including the range of the channel at the index.
+    pub fn compute_with_depth(samples: &DeepSamples, depth_channel: usize) -> Self {
+        let depth_range = samples.channels.get(depth_channel).and_then(|depth| {
+            depth
+                .to_f32_vec()
+                .into_iter()
+                .filter(|depth| depth.is_finite())
+                .fold(None, |range, depth| match range {
+                    None => Some((depth, depth)),
+                    Some((min, max)) => Some((depth.min(min), depth.max(max))),
+                })
+        });
+
+        DeepStats {
+            depth_range,
+            ..Self::compute(samples)
+        }
+    }
+
+    /// The average number of samples per pixel.
+    pub fn mean_samples_per_pixel(&self) -> f64 {
+        self.total_samples as f64 / self.pixel_count.max(1) as f64
+    }
+
+    /// The percentage of pixels without any sample, between zero and one hundred.
+    pub fn empty_percentage(&self) -> f64 {
+        self.empty_pixels as f64 * 100.0 / self.pixel_count.max(1) as f64
+    }
+}
+
+impl AnyChannels<DeepSamples> {
+    /// Compute the statistics of the samples, with the depth range of the `Z` channel.
+    pub fn stats(&self) -> DeepStats {
+        let samples = match self.list.first() {
+            Some(channel) => &channel.sample_data,
+            None => return DeepStats::compute(&DeepSamples::new(0, 0)),
+        };
+
+        match self.list.iter().position(|channel| channel.name.eq("Z")) {
+            Some(depth) => DeepStats::compute_with_depth(samples, depth),
+            None => DeepStats::compute(samples),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::attribute::SampleType;
+
+    #[test]
+    fn sample_count_distribution() {
+        let mut samples = DeepSamples::new(2, 2);
+        samples.set_cumulative_counts(vec![0, 3, 3, 4]).unwrap();
+
+        let stats = DeepStats::compute(&samples);
+        assert_eq!(stats.total_samples, 4);
+        assert_eq!(stats.min_samples_per_pixel, 0);
+        assert_eq!(stats.max_samples_per_pixel, 3);
+        assert_eq!(stats.max_samples_position, Some(Vec2(1, 0)));
+        assert_eq!(stats.empty_pixels, 2);
+        assert_eq!(stats.histogram, vec![2, 1, 0, 1]);
+        assert_eq!(stats.mean_samples_per_pixel(), 1.0);
+        assert_eq!(stats.empty_percentage(), 50.0);
+        assert_eq!(stats.memory.offsets, 4 * 4);
+        assert_eq!(stats.depth_range, None);
+    }
+
+    #[test]
+    fn depth_range_of_z_channel() {
+        let channels = AnyChannels::from_pixel_fn(
+            Vec2(3, 1),
+            &[("A", SampleType::F16), ("Z", SampleType::F32)],
+            |position, pixel| {
+                for sample in 0..position.x() + 1 {
+                    pixel.push(&[1.0, (position.x() + sample) as f32]);
+                }
+
+                if position.x() == 0 {
+                    pixel.push(&[1.0, f32::INFINITY]);
+                }
+            },
+        )
+        .unwrap();
+
+        let stats = channels.stats();
+        assert_eq!(stats.depth_range, Some((0.0, 4.0)));
+        assert_eq!(stats.min_samples_per_pixel, 2);
+        assert_eq!(stats.max_samples_position, Some(Vec2(2, 0)));
+        assert_eq!(stats.memory.samples, 7 * (2 + 4));
+
+        let empty = AnyChannels {
+            list: Default::default(),
+        }
+        .stats();
+        assert_eq!(empty.pixel_count, 0);
+        assert_eq!(empty.max_samples_position, None);
+    }
+}
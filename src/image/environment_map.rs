@@ -0,0 +1,295 @@
+//! Resample decoded environment maps into another layout and size,
+//! such as a latitude-longitude map into a cube map.
+//! The geometry of the layouts is described in the `meta::environment_map` module.
+//!
+//! Each target pixel averages lookups spread over its area, with bilinear interpolation.
+//! When the resolution is reduced, enough lookups are used to cover all source pixels
+//! that a target pixel spans. Integer samples, such as object ids, are not interpolated,
+//! but use the nearest sample.
+
+use crate::error::{Error, Result};
+use crate::image::{AnyChannel, AnyChannels, FlatSamples, Image, Layer, Layers};
+use crate::math::Vec2;
+use crate::meta::attribute::{EnvironmentMap, IntegerBounds};
+use half::f16;
+
+impl EnvironmentMap {
+    /// The size of a map of this type with the specified number of pixels around the horizon.
+    /// Latitude-longitude maps are twice as wide as they are high,
+    /// and each face of a cube map is a quarter of the horizontal resolution wide.
+    pub fn size_for_horizontal_resolution(self, resolution: usize) -> Vec2<usize> {
+        match self {
+            EnvironmentMap::LatitudeLongitude => Vec2(resolution.max(2), (resolution / 2).max(1)),
+            EnvironmentMap::Cube => {
+                let face_size = (resolution / 4).max(1);
+                Vec2(face_size, face_size * 6)
+            }
+        }
+    }
+
+    /// The number of lookups per pixel along each axis when resampling a map of this type and size
+    /// into the target layout and size. Enough to cover all source pixels that a target pixel spans.
+    pub fn samples_per_axis(
+        self,
+        size: Vec2<usize>,
+        target: EnvironmentMap,
+        target_size: Vec2<usize>,
+    ) -> usize {
+        let source_resolution = self.horizontal_resolution(size);
+        let target_resolution = target.horizontal_resolution(target_size).max(1);
+        ((source_resolution + target_resolution - 1) / target_resolution).max(1)
+    }
+}
+
+/// Resample the samples of a single channel from one environment map layout and size into another.
+/// Averages `samples_per_axis` by `samples_per_axis` lookups per pixel, see `EnvironmentMap::resample_filtered`.
+/// Keeps the sample type. Integer samples use the nearest sample instead.
+pub fn resample_samples(
+    samples: &FlatSamples,
+    source: EnvironmentMap,
+    size: Vec2<usize>,
+    target: EnvironmentMap,
+    target_size: Vec2<usize>,
+    samples_per_axis: usize,
+) -> Result<FlatSamples> {
+    let filtered = |values: Vec<f32>| {
+        source.resample_filtered(size, &values, target, target_size, samples_per_axis)
+    };
+
+    Ok(match samples {
+        FlatSamples::F16(values) => FlatSamples::F16(
+            filtered(values.iter().map(|value| value.to_f32()).collect())?
+                .into_iter()
+                .map(f16::from_f32)
+                .collect(),
+        ),
+
+        FlatSamples::F32(values) => FlatSamples::F32(filtered(values.clone())?),
+
+        FlatSamples::U32(values) => {
+            source.validate_size(size)?;
+            target.validate_size(target_size)?;
+
+            if values.len() != size.area() {
+                return Err(Error::invalid("environment map sample count"));
+            }
+
+            FlatSamples::U32(nearest_samples(values, source, size, target, target_size))
+        }
+    })
+}
+
+/// Look up the nearest sample of each pixel of the target layout.
+fn nearest_samples(
+    samples: &[u32],
+    source: EnvironmentMap,
+    size: Vec2<usize>,
+    target: EnvironmentMap,
+    target_size: Vec2<usize>,
+) -> Vec<u32> {
+    let max = Vec2(size.width() - 1, size.height() - 1);
+
+    (0..target_size.area())
+        .map(|index| {
+            let pixel = Vec2(
+                (index % target_size.width()) as f32,
+                (index / target_size.width()) as f32,
+            );
+            let direction = target.direction(target_size, pixel);
+            let position = source.pixel_position(size, direction);
+
+            let x = (position.x().round().max(0.0) as usize).min(max.x());
+            let y = (position.y().round().max(0.0) as usize).min(max.y());
+            samples[y * size.width() + x]
+        })
+        .collect()
+}
+
+impl Layer<AnyChannels<FlatSamples>> {
+    /// Resample this environment map into another layout and size, such as a latitude-longitude map
+    /// into a cube map. The layer must have the `envmap` attribute, which is updated.
+    /// Keeps the sample type of each channel. Integer samples, such as object ids,
+    /// are not interpolated, but use the nearest sample.
+    /// Fails if a channel is subsampled, or if the size does not fit the layout.
+    pub fn to_environment_map(
+        &self,
+        target: EnvironmentMap,
+        target_size: Vec2<usize>,
+    ) -> Result<Self> {
+        let source = self
+            .attributes
+            .environment_map
+            .ok_or_else(|| Error::invalid("layer without environment map attribute"))?;
+
+        if self
+            .channel_data
+            .list
+            .iter()
+            .any(|channel| channel.sampling != Vec2(1, 1))
+        {
+            return Err(Error::unsupported("resampling subsampled environment maps"));
+        }
+
+        source.validate_size(self.size)?;
+        target.validate_size(target_size)?;
+
+        let samples_per_axis = source.samples_per_axis(self.size, target, target_size);
+
+        let list = self
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| {
+                Ok(AnyChannel {
+                    sample_data: resample_samples(
+                        &channel.sample_data,
+                        source,
+                        self.size,
+                        target,
+                        target_size,
+                        samples_per_axis,
+                    )?,
+                    ..channel.clone()
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let mut layer = Layer {
+            channel_data: AnyChannels { list },
+            attributes: self.attributes.clone(),
+            size: target_size,
+            encoding: self.encoding,
+        };
+
+        layer.attributes.environment_map = Some(target);
+        layer.attributes.layer_position = Vec2(0, 0);
+        Ok(layer)
+    }
+}
+
+impl Image<Layers<AnyChannels<FlatSamples>>> {
+    /// Resample all layers into another environment map layout and size.
+    /// The display window becomes the new size. See `Layer::to_environment_map`.
+    pub fn to_environment_map(
+        &self,
+        target: EnvironmentMap,
+        target_size: Vec2<usize>,
+    ) -> Result<Self> {
+        let layer_data = self
+            .layer_data
+            .iter()
+            .map(|layer| layer.to_environment_map(target, target_size))
+            .collect::<Result<Layers<_>>>()?;
+
+        let mut attributes = self.attributes.clone();
+        attributes.display_window = IntegerBounds::from_dimensions(target_size);
+
+        Ok(Image {
+            attributes,
+            layer_data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_layers() {
+        use crate::image::Encoding;
+        use crate::meta::header::LayerAttributes;
+
+        let size = Vec2(32, 16);
+        let channels = AnyChannels::sort(smallvec::smallvec![
+            AnyChannel::new(
+                "Y",
+                FlatSamples::F16(vec![f16::from_f32(0.25); size.area()])
+            ),
+            AnyChannel::new("id", FlatSamples::U32(vec![7; size.area()])),
+        ]);
+
+        let mut attributes = LayerAttributes::default();
+        attributes.environment_map = Some(EnvironmentMap::LatitudeLongitude);
+        let layer = Layer::new(size, attributes, Encoding::default(), channels);
+
+        let cube_size = EnvironmentMap::Cube.size_for_horizontal_resolution(32);
+        assert_eq!(cube_size, Vec2(8, 48));
+
+        let cube = layer
+            .to_environment_map(EnvironmentMap::Cube, cube_size)
+            .unwrap();
+        assert_eq!(cube.size, cube_size);
+        assert_eq!(cube.attributes.environment_map, Some(EnvironmentMap::Cube));
+
+        let luminance = cube.channel_data.list[0].sample_data.to_f32_vec();
+        assert!(luminance.iter().all(|&value| (value - 0.25).abs() < 1.0e-3));
+        assert_eq!(
+            cube.channel_data.list[1].sample_data,
+            FlatSamples::U32(vec![7; cube_size.area()])
+        );
+
+        let back = cube
+            .to_environment_map(EnvironmentMap::LatitudeLongitude, size)
+            .unwrap();
+        assert_eq!(back.size, size);
+
+        let mut plain = layer.clone();
+        plain.attributes.environment_map = None;
+        assert!(plain
+            .to_environment_map(EnvironmentMap::Cube, cube_size)
+            .is_err());
+    }
+
+    #[test]
+    fn sizes_keep_resolution() {
+        let lat_long = EnvironmentMap::LatitudeLongitude;
+        let cube = EnvironmentMap::Cube;
+
+        let resolution = lat_long.horizontal_resolution(Vec2(1024, 512));
+        assert_eq!(
+            cube.size_for_horizontal_resolution(resolution),
+            Vec2(256, 1536)
+        );
+
+        let resolution = cube.horizontal_resolution(Vec2(256, 1536));
+        assert_eq!(
+            lat_long.size_for_horizontal_resolution(resolution),
+            Vec2(1024, 512)
+        );
+
+        assert_eq!(
+            lat_long.samples_per_axis(Vec2(1024, 512), cube, Vec2(64, 384)),
+            4
+        );
+        assert_eq!(
+            lat_long.samples_per_axis(Vec2(1024, 512), cube, Vec2(512, 3072)),
+            1
+        );
+    }
+
+    #[test]
+    fn integer_samples_are_not_blended() {
+        let size = Vec2(8, 4);
+        let ids = FlatSamples::U32((0..size.area() as u32).collect());
+
+        let cube = resample_samples(
+            &ids,
+            EnvironmentMap::LatitudeLongitude,
+            size,
+            EnvironmentMap::Cube,
+            Vec2(4, 24),
+            3,
+        )
+        .unwrap();
+
+        match cube {
+            FlatSamples::U32(values) => {
+                assert_eq!(values.len(), 4 * 24);
+                assert!(values.iter().all(|&id| id < size.area() as u32));
+            }
+
+            _ => panic!("sample type changed"),
+        }
+    }
+}
@@ -0,0 +1,473 @@
+//! Composite deep samples into flat pixels, like the `flatten` operation of OpenEXR deep compositing.
+//!
+//! The samples of each pixel are sorted by depth and composited front to back with the "over" operation.
+//! Samples are expected to contain premultiplied colors, as required by the OpenEXR deep data specification.
+//! Volumetric samples, where `ZBack` is greater than `Z`, are split at the boundaries of the depth range.
+//! The sorted samples can also be composited back to front with the "under" operation,
+//! which produces the same pixels apart from rounding.
+
+use crate::error::{Error, Result};
+use crate::image::deep::{DeepChannelData, DeepSample, DeepSamples};
+use crate::image::premultiply::alpha_channel_index;
+use crate::image::{AnyChannel, AnyChannels, FlatSamples, Layer};
+use crate::math::Vec2;
+use half::f16;
+use half::slice::HalfFloatSliceExt;
+use smallvec::SmallVec;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// Controls how deep samples are composited into flat pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FlattenOptions {
+    /// Only composite the samples between these two depths, inclusive.
+    /// Volumetric samples that cross a boundary contribute only the part inside the range.
+    /// Requires a `Z` channel. All samples are used if not specified.
+    pub depth_range: Option<(f32, f32)>,
+
+    /// Add a `Z` channel that contains the depth of the front-most sample of each pixel.
+    /// Pixels without any sample contain infinity.
+    pub keep_depth: bool,
+
+    /// The order in which the sorted samples of each pixel are composited.
+    pub order: CompositingOrder,
+}
+
+/// The order in which the samples of a pixel are composited, after sorting them by depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompositingOrder {
+    /// Start with the nearest sample, and composite each sample behind the previous samples.
+    FrontToBack,
+
+    /// Start with the farthest sample, and composite each sample over the previous samples.
+    BackToFront,
+}
+
+impl Default for CompositingOrder {
+    fn default() -> Self {
+        CompositingOrder::FrontToBack
+    }
+}
+
+/// The role of a deep channel while flattening.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// Composited using the alpha channel at this index, or the front-most value if there is no alpha.
+    Color { alpha: Option<usize> },
+
+    /// `u32` channels such as object ids cannot be blended, so the front-most value is used.
+    FrontMost,
+
+    /// `Z` and `ZBack` are replaced by the optional front-most depth.
+    Depth,
+}
+
+impl AnyChannels<DeepSamples> {
+    /// Composite all samples of each pixel into a single flat sample per channel.
+    /// All channels except `Z` and `ZBack` are kept, with their original sample type.
+    ///
+    /// Each color channel is composited with the alpha channel of its layer,
+    /// for example `diffuse.R` with `diffuse.A`, falling back to the `A` channel.
+    /// Channels without an alpha channel, and `u32` channels, contain the value of the front-most sample.
+    /// Without a `Z` channel, the samples are assumed to be stored front to back.
+    pub fn flatten(&self, options: FlattenOptions) -> Result<AnyChannels<FlatSamples>> {
+        // all channels share the samples of the first channel
+        let samples = match self.list.first() {
+            Some(channel) => &channel.sample_data,
+            None => {
+                return Ok(AnyChannels {
+                    list: SmallVec::new(),
+                })
+            }
+        };
+
+        if samples.channels.len() != self.list.len() {
+            return Err(Error::invalid("deep channel count"));
+        }
+
+        let channel_index = |name: &str| self.list.iter().position(|channel| channel.name.eq(name));
+
+        let depth = channel_index("Z");
+        let depth_back = channel_index("ZBack");
+
+        if depth.is_none() && (options.depth_range.is_some() || options.keep_depth) {
+            return Err(Error::invalid("flattening by depth requires a Z channel"));
+        }
+
+        let roles: Vec<Role> = self
+            .list
+            .iter()
+            .enumerate()
+            .map(|(index, _)| {
+                if Some(index) == depth || Some(index) == depth_back {
+                    return Role::Depth;
+                }
+
+                if let DeepChannelData::U32(_) = samples.channels[index] {
+                    return Role::FrontMost;
+                }
+
+                Role::Color {
+                    alpha: alpha_channel_index(self, index),
+                }
+            })
+            .collect();
+
+        // convert all samples at once, which uses the batched half float conversion
+        let values: Vec<Cow<'_, [f32]>> = samples
+            .channels
+            .iter()
+            .map(|data| match data {
+                DeepChannelData::F32(values) => Cow::Borrowed(values.as_slice()),
+                data => Cow::Owned(data.to_f32_vec()),
+            })
+            .collect();
+
+        let pixel_count = samples.pixel_count();
+        let mut flat: Vec<Vec<f32>> = vec![vec![0.0; pixel_count]; self.list.len()];
+        let mut front_depths = vec![f32::INFINITY; pixel_count];
+        let mut front_samples: Vec<Option<usize>> = vec![None; pixel_count];
+
+        let front_to_back = options.order == CompositingOrder::FrontToBack;
+        let mut order: Vec<usize> = Vec::new();
+        let mut accumulated_alpha = vec![0.0_f32; self.list.len()];
+
+        for (pixel, view) in self.pixels().enumerate() {
+            order.clear();
+            order.extend(view.sample_indices());
+
+            if let Some(depth) = depth {
+                let depth = &values[depth];
+                order.sort_by(|&a, &b| depth[a].partial_cmp(&depth[b]).unwrap_or(Ordering::Equal));
+            }
+
+            if !front_to_back {
+                order.reverse();
+            }
+
+            for alpha in &mut accumulated_alpha {
+                *alpha = 0.0;
+            }
+
+            for &sample in &order {
+                let coverage = match (options.depth_range, depth) {
+                    (Some(range), Some(depth)) => {
+                        let front = values[depth][sample];
+                        let back = depth_back.map(|back| values[back][sample]).unwrap_or(front);
+
+                        match depth_range_coverage(front, back, range) {
+                            Some(coverage) => coverage,
+                            None => continue,
+                        }
+                    }
+
+                    _ => 1.0,
+                };
+
+                if let Some(depth) = depth {
+                    let front = values[depth][sample];
+                    let front = match options.depth_range {
+                        Some((near, _)) => front.max(near),
+                        None => front,
+                    };
+
+                    front_depths[pixel] = front_depths[pixel].min(front);
+                }
+
+                // when compositing back to front, the last sample is the front-most sample
+                if front_samples[pixel].is_none() || !front_to_back {
+                    front_samples[pixel] = Some(sample);
+                }
+
+                if !front_to_back {
+                    // the sample covers the previous samples by its own alpha
+                    for (channel, role) in roles.iter().enumerate() {
+                        if let Role::Color { alpha: Some(alpha) } = *role {
+                            let sample_alpha = values[alpha][sample];
+                            let factor = partial_alpha_factor(sample_alpha, coverage);
+                            let covered = 1.0 - sample_alpha * factor;
+                            let value = values[channel][sample] * factor;
+                            flat[channel][pixel] = value + flat[channel][pixel] * covered;
+                        }
+                    }
+
+                    continue;
+                }
+
+                // all channels use the accumulated alpha of the previous samples
+                for (channel, role) in roles.iter().enumerate() {
+                    if let Role::Color { alpha: Some(alpha) } = *role {
+                        let value = values[channel][sample];
+                        let sample_alpha = values[alpha][sample];
+                        let value = value * partial_alpha_factor(sample_alpha, coverage);
+                        flat[channel][pixel] += (1.0 - accumulated_alpha[alpha]) * value;
+                    }
+                }
+
+                for (channel, alpha) in accumulated_alpha.iter_mut().enumerate() {
+                    if let Role::Color {
+                        alpha: Some(alpha_channel),
+                    } = roles[channel]
+                    {
+                        if alpha_channel == channel {
+                            let sample_alpha = values[channel][sample];
+                            let sample_alpha =
+                                sample_alpha * partial_alpha_factor(sample_alpha, coverage);
+                            *alpha += (1.0 - *alpha) * sample_alpha;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut list: SmallVec<[AnyChannel<FlatSamples>; 4]> = self
+            .list
+            .iter()
+            .zip(&samples.channels)
+            .zip(roles.iter().zip(flat))
+            .filter(|(_, (role, _))| **role != Role::Depth)
+            .map(|((channel, data), (role, values))| {
+                let sample_data = match role {
+                    Role::Color { alpha: Some(_) } => flat_samples_like(data, values),
+                    _ => front_most_samples(data, &front_samples),
+                };
+
+                AnyChannel {
+                    name: channel.name.clone(),
+                    sample_data,
+                    quantize_linearly: channel.quantize_linearly,
+                    sampling: Vec2(1, 1),
+                }
+            })
+            .collect();
+
+        if options.keep_depth {
+            let depth = depth.expect("depth channel existence bug");
+            let channel = &self.list[depth];
+
+            list.push(AnyChannel {
+                name: channel.name.clone(),
+                sample_data: flat_samples_like(&samples.channels[depth], front_depths),
+                quantize_linearly: channel.quantize_linearly,
+                sampling: Vec2(1, 1),
+            });
+        }
+
+        Ok(AnyChannels::sort(list))
+    }
+}
+
+impl Layer<AnyChannels<DeepSamples>> {
+    /// Composite the samples of each pixel into a flat layer, see `AnyChannels::flatten`.
+    /// Keeps the size, attributes and encoding of the layer, except for the deep image state.
+    pub fn flatten(&self, options: FlattenOptions) -> Result<Layer<AnyChannels<FlatSamples>>> {
+        let mut attributes = self.attributes.clone();
+        attributes.deep_image_state = None;
+
+        Ok(Layer {
+            channel_data: self.channel_data.flatten(options)?,
+            attributes,
+            size: self.size,
+            encoding: self.encoding,
+        })
+    }
+}
+
+/// The fraction of a sample inside the depth range, or `None` if the sample is outside.
+/// Point samples are either completely inside or outside.
+fn depth_range_coverage(front: f32, back: f32, (near, far): (f32, f32)) -> Option<f32> {
+    if back <= front {
+        return if front >= near && front <= far {
+            Some(1.0)
+        } else {
+            None
+        };
+    }
+
+    let overlap = back.min(far) - front.max(near);
+    if overlap <= 0.0 {
+        None
+    } else {
+        Some((overlap / (back - front)).min(1.0))
+    }
+}
+
+/// The factor by which the premultiplied values of a volumetric sample with the specified alpha
+/// are scaled, if only the specified fraction of the volume is used.
+/// A fraction of the volume has the alpha `1 - (1 - alpha)^fraction`.
+pub(crate) fn partial_alpha_factor(alpha: f32, coverage: f32) -> f32 {
+    if coverage >= 1.0 {
+        1.0
+    } else if alpha <= 0.0 {
+        coverage
+    } else if alpha >= 1.0 {
+        1.0 / alpha
+    } else {
+        (1.0 - (1.0 - alpha).powf(coverage)) / alpha
+    }
+}
+
+/// The value of the front-most sample of each pixel, or zero for pixels without any sample.
+/// The values are copied without conversion, which keeps `u32` ids exact.
+fn front_most_samples(data: &DeepChannelData, front_samples: &[Option<usize>]) -> FlatSamples {
+    match data {
+        DeepChannelData::F16(values) => FlatSamples::F16(front_most_values(values, front_samples)),
+        DeepChannelData::F32(values) => FlatSamples::F32(front_most_values(values, front_samples)),
+        DeepChannelData::U32(values) => FlatSamples::U32(front_most_values(values, front_samples)),
+    }
+}
+
+/// The value of the front-most sample of each pixel, in a loop specialized for the sample type.
+fn front_most_values<T: DeepSample>(values: &[T], front_samples: &[Option<usize>]) -> Vec<T> {
+    front_samples
+        .iter()
+        .map(|sample| sample.map_or_else(T::default, |sample| values[sample]))
+        .collect()
+}
+
+/// Converts the flattened values back to the sample type of the deep channel.
+fn flat_samples_like(data: &DeepChannelData, values: Vec<f32>) -> FlatSamples {
+    match data {
+        DeepChannelData::F16(_) => {
+            let mut samples = vec![f16::ZERO; values.len()];
+            samples.convert_from_f32_slice(&values);
+            FlatSamples::F16(samples)
+        }
+        DeepChannelData::F32(_) => FlatSamples::F32(values),
+        DeepChannelData::U32(_) => {
+            FlatSamples::U32(values.into_iter().map(|value| value as u32).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::attribute::Text;
+
+    /// A 2x1 image with channels `A`, `R`, `Z`, and `id`, sorted like a channel list.
+    /// The first pixel has a red sample behind a half transparent sample, the second pixel is empty.
+    fn two_samples() -> AnyChannels<DeepSamples> {
+        let mut samples = DeepSamples::new(2, 1);
+        samples.set_cumulative_counts(vec![2, 2]).unwrap();
+
+        samples.channels = vec![
+            DeepChannelData::F32(vec![1.0, 0.5]),
+            DeepChannelData::F32(vec![1.0, 0.25]),
+            DeepChannelData::F32(vec![10.0, 5.0]),
+            DeepChannelData::U32(vec![16_777_217, 7]),
+        ];
+
+        let list = ["A", "R", "Z", "id"]
+            .iter()
+            .map(|&name| AnyChannel {
+                name: Text::from(name),
+                sample_data: samples.clone(),
+                quantize_linearly: false,
+                sampling: Vec2(1, 1),
+            })
+            .collect();
+
+        AnyChannels { list }
+    }
+
+    fn values(channels: &AnyChannels<FlatSamples>, name: &str) -> Vec<f32> {
+        let channel = channels
+            .list
+            .iter()
+            .find(|channel| channel.name.eq(name))
+            .expect("channel missing");
+
+        channel.sample_data.to_f32_vec()
+    }
+
+    /// The ids must stay `u32` and are copied exactly.
+    fn ids(channels: &AnyChannels<FlatSamples>) -> Vec<u32> {
+        let channel = channels
+            .list
+            .iter()
+            .find(|channel| channel.name.eq("id"))
+            .expect("channel missing");
+
+        match &channel.sample_data {
+            FlatSamples::U32(ids) => ids.clone(),
+            _ => panic!("id channel must stay u32"),
+        }
+    }
+
+    #[test]
+    fn composites_samples_front_to_back() {
+        let flat = two_samples().flatten(FlattenOptions::default()).unwrap();
+
+        let names: Vec<String> = flat
+            .list
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+        assert_eq!(names, vec!["A", "R", "id"]);
+
+        assert_eq!(values(&flat, "A"), vec![1.0, 0.0]);
+        assert_eq!(values(&flat, "R"), vec![0.25 + 0.5 * 1.0, 0.0]);
+
+        assert_eq!(ids(&flat), vec![7, 0]);
+    }
+
+    #[test]
+    fn composites_samples_back_to_front() {
+        let options = FlattenOptions {
+            order: CompositingOrder::BackToFront,
+            ..FlattenOptions::default()
+        };
+
+        let channels = two_samples();
+        let flat = channels.flatten(options).unwrap();
+        let front_to_back = channels.flatten(FlattenOptions::default()).unwrap();
+
+        assert_eq!(flat, front_to_back);
+        assert_eq!(ids(&flat), vec![7, 0]);
+    }
+
+    #[test]
+    fn depth_range_and_front_most_depth() {
+        let options = FlattenOptions {
+            depth_range: Some((8.0, 20.0)),
+            keep_depth: true,
+            ..FlattenOptions::default()
+        };
+
+        let flat = two_samples().flatten(options).unwrap();
+        assert_eq!(values(&flat, "R"), vec![1.0, 0.0]);
+        assert_eq!(values(&flat, "Z"), vec![10.0, f32::INFINITY]);
+
+        assert_eq!(ids(&flat), vec![16_777_217, 0]);
+    }
+
+    #[test]
+    fn volumetric_samples_are_split() {
+        assert_eq!(depth_range_coverage(0.0, 4.0, (1.0, 3.0)), Some(0.5));
+        assert_eq!(depth_range_coverage(5.0, 5.0, (1.0, 3.0)), None);
+        assert_eq!(depth_range_coverage(2.0, 2.0, (1.0, 3.0)), Some(1.0));
+
+        // two halves of a volume composite to the alpha of the whole volume
+        let alpha = 0.75;
+        let half = alpha * partial_alpha_factor(alpha, 0.5);
+        assert!((half + (1.0 - half) * half - alpha).abs() < 1e-6);
+    }
+
+    #[test]
+    fn depth_options_require_z() {
+        let mut channels = two_samples();
+        channels.list.retain(|channel| !channel.name.eq("Z"));
+        for channel in &mut channels.list {
+            channel.sample_data.channels.remove(2);
+        }
+
+        assert!(channels.flatten(FlattenOptions::default()).is_ok());
+
+        let options = FlattenOptions {
+            keep_depth: true,
+            ..FlattenOptions::default()
+        };
+        assert!(channels.flatten(options).is_err());
+    }
+}
@@ -0,0 +1,521 @@
+//! Convert between rgb channels and luminance/chroma channels,
+//! as written by the `RgbaYca` interface of OpenEXR, for example by `exrenvmap`.
+//!
+//! Luminance/chroma layers store the luminance in a `Y` channel with full resolution,
+//! and the chroma in the channels `RY = (R - Y) / Y` and `BY = (B - Y) / Y`,
+//! which are usually subsampled by two in both dimensions.
+//! Groups with a layer prefix, such as `diffuse.Y`, `diffuse.RY`, and `diffuse.BY`, are converted together.
+//! Layers with only a `Y` channel are grayscale, and are left unchanged.
+//!
+//! The luminance weights are derived from the chromaticities of the image, which default to Rec. 709.
+//! The values are computed with `f32` precision, and rounded only once when stored as `f16`.
+
+use crate::error::{Error, Result};
+use crate::image::{AnyChannel, AnyChannels, Blocks, FlatSamples, Image, Layer, Layers};
+use crate::math::Vec2;
+use crate::meta::attribute::{Chromaticities, LineOrder};
+use half::f16;
+use smallvec::SmallVec;
+
+/// The weights of the red, green, and blue channels in the luminance of colors with these chromaticities.
+/// The weights add up to one.
+pub fn luminance_weights(chromaticities: &Chromaticities) -> Result<[f32; 3]> {
+    let rgb_to_xyz = chromaticities.rgb_to_xyz()?;
+    let weights = [rgb_to_xyz[3], rgb_to_xyz[4], rgb_to_xyz[5]];
+    let sum: f32 = weights.iter().sum();
+
+    Ok([weights[0] / sum, weights[1] / sum, weights[2] / sum])
+}
+
+impl Layer<AnyChannels<FlatSamples>> {
+    /// Whether this layer contains a group of `Y`, `RY`, and `BY` channels.
+    pub fn has_luminance_chroma(&self) -> bool {
+        !channel_groups(&self.channel_data, ["Y", "RY", "BY"]).is_empty()
+    }
+
+    /// Upsample all subsampled channels to the resolution of the layer.
+    /// Interpolates linearly between the samples. Integer samples, such as object ids,
+    /// are not interpolated, but use the nearest sample.
+    pub fn to_full_resolution(&self) -> Self {
+        let list = self
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| {
+                let sampling = channel.sampling;
+                if sampling == Vec2(1, 1) {
+                    return channel.clone();
+                }
+
+                let width = self.size.width();
+                let nearest = |index: usize| {
+                    let (x, y) = (index % width / sampling.x(), index / width / sampling.y());
+                    y * (width / sampling.x()) + x
+                };
+
+                let sample_data = match &channel.sample_data {
+                    FlatSamples::U32(samples) => FlatSamples::U32(
+                        (0..self.size.area())
+                            .map(|index| samples[nearest(index)])
+                            .collect(),
+                    ),
+
+                    samples => with_sample_type(
+                        samples,
+                        upsample(&samples.to_f32_vec(), sampling, self.size),
+                    ),
+                };
+
+                AnyChannel {
+                    sample_data,
+                    sampling: Vec2(1, 1),
+                    ..channel.clone()
+                }
+            })
+            .collect();
+
+        Layer {
+            channel_data: AnyChannels { list },
+            ..self.clone()
+        }
+    }
+
+    /// Replace each group of `Y`, `RY`, and `BY` channels with `R`, `G`, and `B` channels,
+    /// with the resolution of the layer and the sample type of the `Y` channel.
+    /// Uses the luminance weights of the chromaticities, or of Rec. 709 if none are specified.
+    /// Other channels are left unchanged. Fails for integer samples.
+    pub fn luminance_chroma_to_rgb(&self, chromaticities: Option<Chromaticities>) -> Result<Self> {
+        let weights = luminance_weights(&chromaticities.unwrap_or_default())?;
+        let groups = channel_groups(&self.channel_data, ["Y", "RY", "BY"]);
+        let list = &self.channel_data.list;
+
+        let mut converted = SmallVec::new();
+        for (prefix, [luminance, red_chroma, blue_chroma]) in &groups {
+            let [luminance, red_chroma, blue_chroma] =
+                [&list[*luminance], &list[*red_chroma], &list[*blue_chroma]];
+
+            if [luminance, red_chroma, blue_chroma]
+                .iter()
+                .any(|channel| matches!(channel.sample_data, FlatSamples::U32(_)))
+            {
+                return Err(Error::invalid(
+                    "luminance or chroma channel with integer samples",
+                ));
+            }
+
+            let full_resolution = |channel: &AnyChannel<FlatSamples>| {
+                upsample(
+                    &channel.sample_data.to_f32_vec(),
+                    channel.sampling,
+                    self.size,
+                )
+            };
+
+            let luminance_values = full_resolution(luminance);
+            let (red_chroma, blue_chroma) =
+                (full_resolution(red_chroma), full_resolution(blue_chroma));
+
+            let mut rgb = [Vec::new(), Vec::new(), Vec::new()];
+            for index in 0..luminance_values.len() {
+                let luminance = luminance_values[index];
+                let red = (red_chroma[index] + 1.0) * luminance;
+                let blue = (blue_chroma[index] + 1.0) * luminance;
+                let green = (luminance - red * weights[0] - blue * weights[2]) / weights[1];
+
+                rgb[0].push(red);
+                rgb[1].push(green);
+                rgb[2].push(blue);
+            }
+
+            for (name, values) in ["R", "G", "B"].iter().zip(rgb) {
+                converted.push(AnyChannel::new(
+                    format!("{}{}", prefix, name).as_str(),
+                    with_sample_type(&luminance.sample_data, values),
+                ));
+            }
+        }
+
+        Ok(self.with_channels_replaced(&groups, converted))
+    }
+
+    /// Replace each group of `R`, `G`, and `B` channels with a `Y` channel of full resolution,
+    /// and `RY` and `BY` channels that are subsampled by the chroma sampling rate,
+    /// averaging the chroma of the pixels covered by each sample.
+    /// Uses the luminance weights of the chromaticities, or of Rec. 709 if none are specified.
+    /// Fails for integer samples, or if the layer size is not divisible by the sampling rate.
+    /// As subsampled channels can only be stored in ordered scan lines,
+    /// the encoding of the layer is changed accordingly.
+    pub fn rgb_to_luminance_chroma(
+        &self,
+        chromaticities: Option<Chromaticities>,
+        chroma_sampling: Vec2<usize>,
+    ) -> Result<Self> {
+        if chroma_sampling.x() == 0
+            || chroma_sampling.y() == 0
+            || self.size.x() % chroma_sampling.x() != 0
+            || self.size.y() % chroma_sampling.y() != 0
+        {
+            return Err(Error::invalid(
+                "chroma sampling rate not dividing the layer size",
+            ));
+        }
+
+        let weights = luminance_weights(&chromaticities.unwrap_or_default())?;
+        let groups = channel_groups(&self.channel_data, ["R", "G", "B"]);
+        let list = &self.channel_data.list;
+
+        let mut converted = SmallVec::new();
+        for (prefix, indices) in &groups {
+            let channels = indices.map(|index| &list[index]);
+
+            if channels
+                .iter()
+                .any(|channel| matches!(channel.sample_data, FlatSamples::U32(_)))
+            {
+                return Err(Error::invalid("color channel with integer samples"));
+            }
+
+            let [red, green, blue] = channels.map(|channel| {
+                upsample(
+                    &channel.sample_data.to_f32_vec(),
+                    channel.sampling,
+                    self.size,
+                )
+            });
+
+            let mut luminance = Vec::with_capacity(red.len());
+            let mut red_chroma = Vec::with_capacity(red.len());
+            let mut blue_chroma = Vec::with_capacity(red.len());
+
+            for index in 0..red.len() {
+                let value =
+                    red[index] * weights[0] + green[index] * weights[1] + blue[index] * weights[2];
+                let chroma = |color: f32| {
+                    if value == 0.0 {
+                        0.0
+                    } else {
+                        (color - value) / value
+                    }
+                };
+
+                luminance.push(value);
+                red_chroma.push(chroma(red[index]));
+                blue_chroma.push(chroma(blue[index]));
+            }
+
+            let sample_type = &channels[0].sample_data;
+            converted.push(AnyChannel::new(
+                format!("{}Y", prefix).as_str(),
+                with_sample_type(sample_type, luminance),
+            ));
+
+            for (name, chroma) in [("RY", red_chroma), ("BY", blue_chroma)] {
+                let mut channel = AnyChannel::new(
+                    format!("{}{}", prefix, name).as_str(),
+                    with_sample_type(sample_type, downsample(&chroma, self.size, chroma_sampling)),
+                );
+
+                channel.sampling = chroma_sampling;
+                converted.push(channel);
+            }
+        }
+
+        let mut layer = self.with_channels_replaced(&groups, converted);
+        if chroma_sampling != Vec2(1, 1) {
+            layer.encoding.blocks = Blocks::ScanLines;
+
+            if layer.encoding.line_order == LineOrder::Unspecified {
+                layer.encoding.line_order = LineOrder::Increasing;
+            }
+        }
+
+        Ok(layer)
+    }
+
+    /// Remove the channels of the groups, and add the converted channels.
+    fn with_channels_replaced<const N: usize>(
+        &self,
+        groups: &[(String, [usize; N])],
+        converted: SmallVec<[AnyChannel<FlatSamples>; 4]>,
+    ) -> Self {
+        let mut list: SmallVec<[AnyChannel<FlatSamples>; 4]> = self
+            .channel_data
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !groups.iter().any(|(_, group)| group.contains(index)))
+            .map(|(_, channel)| channel.clone())
+            .collect();
+
+        list.extend(converted);
+
+        Layer {
+            channel_data: AnyChannels::sort(list),
+            ..self.clone()
+        }
+    }
+}
+
+impl Image<Layers<AnyChannels<FlatSamples>>> {
+    /// Convert the luminance and chroma channels of all layers to rgb channels,
+    /// using the chromaticities of the image. See `Layer::luminance_chroma_to_rgb`.
+    pub fn luminance_chroma_to_rgb(&self) -> Result<Self> {
+        self.with_converted_layers(|layer| {
+            layer.luminance_chroma_to_rgb(self.attributes.chromaticities)
+        })
+    }
+
+    /// Convert the rgb channels of all layers to luminance and chroma channels,
+    /// using the chromaticities of the image. See `Layer::rgb_to_luminance_chroma`.
+    pub fn rgb_to_luminance_chroma(&self, chroma_sampling: Vec2<usize>) -> Result<Self> {
+        self.with_converted_layers(|layer| {
+            layer.rgb_to_luminance_chroma(self.attributes.chromaticities, chroma_sampling)
+        })
+    }
+
+    fn with_converted_layers(
+        &self,
+        convert: impl Fn(&Layer<AnyChannels<FlatSamples>>) -> Result<Layer<AnyChannels<FlatSamples>>>,
+    ) -> Result<Self> {
+        Ok(Image {
+            attributes: self.attributes.clone(),
+            layer_data: self.layer_data.iter().map(convert).collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// The layer prefix and the channel indices of each group that contains all of the named channels.
+fn channel_groups<const N: usize>(
+    channels: &AnyChannels<FlatSamples>,
+    names: [&str; N],
+) -> Vec<(String, [usize; N])> {
+    let index_of = |name: &str| {
+        channels
+            .list
+            .iter()
+            .position(|channel| channel.name.eq(name))
+    };
+
+    channels
+        .list
+        .iter()
+        .filter_map(|channel| {
+            let name = channel.name.to_string();
+            let prefix = name.strip_suffix(names[0])?;
+
+            if !(prefix.is_empty() || prefix.ends_with('.')) {
+                return None;
+            }
+
+            let mut indices = [0; N];
+            for (index, name) in indices.iter_mut().zip(names) {
+                *index = index_of(&format!("{}{}", prefix, name))?;
+            }
+
+            Some((prefix.to_string(), indices))
+        })
+        .collect()
+}
+
+/// Interpolate the samples of a subsampled channel linearly at each pixel of the layer.
+/// The samples are located at the pixels with coordinates divisible by the sampling rate.
+fn upsample(samples: &[f32], sampling: Vec2<usize>, size: Vec2<usize>) -> Vec<f32> {
+    if sampling == Vec2(1, 1) {
+        return samples.to_vec();
+    }
+
+    let resolution = size / sampling;
+    if resolution.area() == 0 {
+        return vec![0.0; size.area()];
+    }
+
+    // the two samples around a pixel, and the weight of the second sample
+    let neighbours = |position: usize, sampling: usize, count: usize| {
+        let position = position as f32 / sampling as f32;
+        let first = (position.floor() as usize).min(count - 1);
+        (first, (first + 1).min(count - 1), position - first as f32)
+    };
+
+    (0..size.area())
+        .map(|index| {
+            let (left, right, x) =
+                neighbours(index % size.width(), sampling.x(), resolution.width());
+            let (top, bottom, y) =
+                neighbours(index / size.width(), sampling.y(), resolution.height());
+
+            let sample = |x: usize, y: usize| samples[y * resolution.width() + x];
+            let upper = sample(left, top) + (sample(right, top) - sample(left, top)) * x;
+            let lower = sample(left, bottom) + (sample(right, bottom) - sample(left, bottom)) * x;
+            upper + (lower - upper) * y
+        })
+        .collect()
+}
+
+/// Average the values of the pixels covered by each sample of a subsampled channel.
+fn downsample(values: &[f32], size: Vec2<usize>, sampling: Vec2<usize>) -> Vec<f32> {
+    let resolution = size / sampling;
+
+    (0..resolution.area())
+        .map(|index| {
+            let x = (index % resolution.width()) * sampling.x();
+            let y = (index / resolution.width()) * sampling.y();
+
+            let sum: f32 = (y..y + sampling.y())
+                .flat_map(|y| (x..x + sampling.x()).map(move |x| values[y * size.width() + x]))
+                .sum();
+
+            sum / sampling.area() as f32
+        })
+        .collect()
+}
+
+/// Store the values with the sample type of the samples, which must not be integers.
+fn with_sample_type(samples: &FlatSamples, values: Vec<f32>) -> FlatSamples {
+    match samples {
+        FlatSamples::F16(_) => FlatSamples::F16(values.into_iter().map(f16::from_f32).collect()),
+        FlatSamples::F32(_) => FlatSamples::F32(values),
+        FlatSamples::U32(_) => unreachable!("integer samples are not interpolated"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Encoding;
+    use crate::meta::header::LayerAttributes;
+    use crate::prelude::*;
+    use std::io::Cursor;
+
+    fn rgb_layer(size: Vec2<usize>) -> Layer<AnyChannels<FlatSamples>> {
+        let channel = |name: &str, value: fn(usize, usize) -> f32| {
+            let values = (0..size.area())
+                .map(|index| value(index % size.width(), index / size.width()))
+                .map(f16::from_f32)
+                .collect();
+
+            AnyChannel::new(name, FlatSamples::F16(values))
+        };
+
+        let channels = AnyChannels::sort(smallvec::smallvec![
+            channel("R", |x, _| 0.2 + x as f32 * 0.01),
+            channel("G", |_, y| 0.5 + y as f32 * 0.02),
+            channel("B", |_, _| 0.3),
+            AnyChannel::new("id", FlatSamples::U32(vec![3; size.area()])),
+        ]);
+
+        Layer::new(
+            size,
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            channels,
+        )
+    }
+
+    #[test]
+    fn luminance_of_rec709() {
+        let weights = luminance_weights(&Chromaticities::rec709()).unwrap();
+        let expected = [0.2126, 0.7152, 0.0722];
+
+        for (weight, expected) in weights.iter().zip(expected) {
+            assert!((weight - expected).abs() < 1.0e-3, "{:?}", weights);
+        }
+    }
+
+    #[test]
+    fn convert_rgb_and_back() {
+        let layer = rgb_layer(Vec2(8, 6));
+        let converted = layer.rgb_to_luminance_chroma(None, Vec2(2, 2)).unwrap();
+        assert!(converted.has_luminance_chroma());
+
+        let names: Vec<String> = converted
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+        assert_eq!(names, ["BY", "RY", "Y", "id"]);
+
+        let chroma = &converted.channel_data.list[0];
+        assert_eq!(chroma.sampling, Vec2(2, 2));
+        assert_eq!(chroma.sample_data.len(), 4 * 3);
+
+        let back = converted.luminance_chroma_to_rgb(None).unwrap();
+        assert!(!back.has_luminance_chroma());
+
+        for (original, restored) in layer.channel_data.list.iter().zip(&back.channel_data.list) {
+            assert_eq!(original.name, restored.name);
+            assert_eq!(restored.sampling, Vec2(1, 1));
+
+            let (original, restored) = (
+                original.sample_data.to_f32_vec(),
+                restored.sample_data.to_f32_vec(),
+            );
+
+            for (original, restored) in original.iter().zip(restored) {
+                assert!(
+                    (original - restored).abs() < 0.02,
+                    "{} {}",
+                    original,
+                    restored
+                );
+            }
+        }
+
+        assert!(layer.rgb_to_luminance_chroma(None, Vec2(3, 2)).is_err());
+    }
+
+    #[test]
+    fn upsample_between_samples() {
+        let samples = [0.0, 2.0, 4.0, 6.0];
+        let values = upsample(&samples, Vec2(2, 2), Vec2(4, 4));
+
+        assert_eq!(&values[0..4], &[0.0, 1.0, 2.0, 2.0]);
+        assert_eq!(&values[4..8], &[2.0, 3.0, 4.0, 4.0]);
+        assert_eq!(&values[12..16], &[4.0, 5.0, 6.0, 6.0]);
+
+        assert_eq!(
+            downsample(&values, Vec2(4, 4), Vec2(2, 2)),
+            [1.5, 3.0, 4.5, 6.0]
+        );
+    }
+
+    #[test]
+    fn write_and_read_subsampled_channels() {
+        let layer = rgb_layer(Vec2(18, 40))
+            .rgb_to_luminance_chroma(None, Vec2(2, 2))
+            .unwrap();
+
+        for compression in [
+            Compression::Uncompressed,
+            Compression::RLE,
+            Compression::ZIP1,
+            Compression::ZIP16,
+            Compression::PIZ,
+            Compression::PXR24,
+            Compression::B44,
+            Compression::B44A,
+        ] {
+            let mut layer = layer.clone();
+            layer.encoding.compression = compression;
+
+            let image = Image::from_layer(layer);
+            let mut bytes = Vec::new();
+            image.write().to_buffered(Cursor::new(&mut bytes)).unwrap();
+
+            let read_image = read_all_flat_layers_from_bytes(&bytes).unwrap();
+            let read_layer = &read_image.layer_data[0];
+            let chroma = &read_layer.channel_data.list[0];
+
+            assert_eq!(chroma.sampling, Vec2(2, 2), "{}", compression);
+            assert_eq!(chroma.sample_data.len(), 9 * 20, "{}", compression);
+
+            if compression.is_lossless_for(SampleType::F16) {
+                assert_eq!(read_layer.channel_data, image.layer_data.channel_data);
+            }
+
+            let rgb = read_layer.luminance_chroma_to_rgb(None).unwrap();
+            let red = rgb.channel_data.list[2].sample_data.to_f32_vec();
+            assert!((red[0] - 0.2).abs() < 0.02, "{}: {}", compression, red[0]);
+        }
+    }
+}
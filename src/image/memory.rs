@@ -0,0 +1,414 @@
+//! Measure the memory that the pixels of a decoded image occupy,
+//! or estimate it from the headers before decoding anything.
+//!
+//! Host applications can compare the estimate with their memory budget
+//! to decide whether to decode the whole image or to stream its blocks instead.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//! use exr::image::memory::estimate_image_memory;
+//!
+//! let meta_data = MetaData::read_from_file("image.exr", false).unwrap();
+//! let estimate = estimate_image_memory(&meta_data);
+//!
+//! if estimate.total().total() < 1024 * 1024 * 1024 {
+//!     let image = read_all_flat_layers_from_file("image.exr").unwrap();
+//!     println!("decoded {} bytes", image.memory_usage().total().total());
+//! }
+//! ```
+
+use crate::image::deep::{DeepChannelData, DeepSamples};
+use crate::image::{AnyChannels, FlatSamples, Image, Layer, Layers, Levels};
+use crate::meta::attribute::Text;
+use crate::meta::header::Header;
+use crate::meta::MetaData;
+use std::iter::Sum;
+use std::mem::size_of;
+use std::ops::{Add, AddAssign};
+
+/// The number of bytes occupied by some pixel data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The bytes of the sample values.
+    pub samples: usize,
+
+    /// The bytes of the deep sample offset tables.
+    /// Always zero for flat samples.
+    pub offsets: usize,
+
+    /// The bytes that have been allocated, but are not used by any sample or offset.
+    /// Always zero for estimates.
+    pub unused_capacity: usize,
+}
+
+/// The memory occupied by the samples of a single channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMemoryUsage {
+    /// The name of the channel.
+    pub name: Text,
+
+    /// The memory occupied by the samples of this channel.
+    pub usage: MemoryUsage,
+}
+
+/// The memory occupied by the pixels of a single layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerMemoryUsage {
+    /// The name of the layer, if any.
+    pub name: Option<Text>,
+
+    /// The memory of each channel in this layer.
+    pub channels: Vec<ChannelMemoryUsage>,
+
+    /// The memory shared by all channels, which is the sample offset table of deep layers.
+    pub shared: MemoryUsage,
+}
+
+/// The memory occupied by the pixels of all layers of an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageMemoryUsage {
+    /// The memory of each layer in this image.
+    pub layers: Vec<LayerMemoryUsage>,
+}
+
+/// Samples that can measure the memory they occupy.
+pub trait SamplesMemoryUsage {
+    /// The memory currently occupied by these samples, including unused capacity.
+    fn memory_usage(&self) -> MemoryUsage;
+}
+
+/// Channels that can measure the memory they occupy.
+pub trait ChannelsMemoryUsage {
+    /// The memory currently occupied by each channel, in the order of the channels,
+    /// and the memory shared by all channels.
+    fn channels_memory_usage(&self) -> (Vec<ChannelMemoryUsage>, MemoryUsage);
+}
+
+impl MemoryUsage {
+    /// The sum of all bytes, including overhead.
+    pub fn total(&self) -> usize {
+        self.samples + self.overhead()
+    }
+
+    /// The bytes that are not sample values: offset tables and unused capacity.
+    pub fn overhead(&self) -> usize {
+        self.offsets + self.unused_capacity
+    }
+
+    /// The memory occupied by a vector, including its unused capacity.
+    fn of_samples<T>(samples: &Vec<T>) -> Self {
+        MemoryUsage {
+            samples: samples.len() * size_of::<T>(),
+            offsets: 0,
+            unused_capacity: (samples.capacity() - samples.len()) * size_of::<T>(),
+        }
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        MemoryUsage {
+            samples: self.samples + other.samples,
+            offsets: self.offsets + other.offsets,
+            unused_capacity: self.unused_capacity + other.unused_capacity,
+        }
+    }
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for MemoryUsage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(MemoryUsage::default(), Add::add)
+    }
+}
+
+impl LayerMemoryUsage {
+    /// The memory of all channels in this layer, including the shared memory.
+    pub fn total(&self) -> MemoryUsage {
+        let channels: MemoryUsage = self.channels.iter().map(|channel| channel.usage).sum();
+        self.shared + channels
+    }
+}
+
+impl ImageMemoryUsage {
+    /// The memory of all layers in this image.
+    pub fn total(&self) -> MemoryUsage {
+        self.layers.iter().map(LayerMemoryUsage::total).sum()
+    }
+}
+
+impl SamplesMemoryUsage for FlatSamples {
+    fn memory_usage(&self) -> MemoryUsage {
+        match self {
+            FlatSamples::F16(samples) => MemoryUsage::of_samples(samples),
+            FlatSamples::F32(samples) => MemoryUsage::of_samples(samples),
+            FlatSamples::U32(samples) => MemoryUsage::of_samples(samples),
+        }
+    }
+}
+
+impl SamplesMemoryUsage for DeepChannelData {
+    fn memory_usage(&self) -> MemoryUsage {
+        match self {
+            DeepChannelData::F16(samples) => MemoryUsage::of_samples(samples),
+            DeepChannelData::F32(samples) => MemoryUsage::of_samples(samples),
+            DeepChannelData::U32(samples) => MemoryUsage::of_samples(samples),
+        }
+    }
+}
+
+/// The samples of all channels, and the sample offset table.
+impl SamplesMemoryUsage for DeepSamples {
+    fn memory_usage(&self) -> MemoryUsage {
+        let offsets = MemoryUsage::of_samples(&self.sample_offsets);
+
+        let offsets = MemoryUsage {
+            samples: 0,
+            offsets: offsets.samples,
+            unused_capacity: offsets.unused_capacity,
+        };
+
+        let channels: MemoryUsage = self
+            .channels
+            .iter()
+            .map(SamplesMemoryUsage::memory_usage)
+            .sum();
+        offsets + channels
+    }
+}
+
+impl<Samples: SamplesMemoryUsage> SamplesMemoryUsage for Levels<Samples> {
+    fn memory_usage(&self) -> MemoryUsage {
+        self.levels_as_slice()
+            .iter()
+            .map(SamplesMemoryUsage::memory_usage)
+            .sum()
+    }
+}
+
+impl ChannelsMemoryUsage for AnyChannels<FlatSamples> {
+    fn channels_memory_usage(&self) -> (Vec<ChannelMemoryUsage>, MemoryUsage) {
+        flat_channels_memory_usage(self)
+    }
+}
+
+impl ChannelsMemoryUsage for AnyChannels<Levels<FlatSamples>> {
+    fn channels_memory_usage(&self) -> (Vec<ChannelMemoryUsage>, MemoryUsage) {
+        flat_channels_memory_usage(self)
+    }
+}
+
+/// The samples of each channel are attributed to that channel, and no memory is shared.
+fn flat_channels_memory_usage<Samples: SamplesMemoryUsage>(
+    channels: &AnyChannels<Samples>,
+) -> (Vec<ChannelMemoryUsage>, MemoryUsage) {
+    let usage = channels
+        .list
+        .iter()
+        .map(|channel| ChannelMemoryUsage {
+            name: channel.name.clone(),
+            usage: channel.sample_data.memory_usage(),
+        })
+        .collect();
+
+    (usage, MemoryUsage::default())
+}
+
+/// A deep layer stores all channels in the samples of its first channel.
+/// The `n`-th channel data of these samples is attributed to the `n`-th channel,
+/// and the sample offset table is shared by all channels.
+impl ChannelsMemoryUsage for AnyChannels<DeepSamples> {
+    fn channels_memory_usage(&self) -> (Vec<ChannelMemoryUsage>, MemoryUsage) {
+        let mut usage: Vec<ChannelMemoryUsage> = self
+            .list
+            .iter()
+            .map(|channel| ChannelMemoryUsage {
+                name: channel.name.clone(),
+                usage: MemoryUsage::default(),
+            })
+            .collect();
+
+        let mut shared = MemoryUsage::default();
+
+        for channel in &self.list {
+            let samples = &channel.sample_data;
+            let offsets = MemoryUsage::of_samples(&samples.sample_offsets);
+
+            shared += MemoryUsage {
+                samples: 0,
+                offsets: offsets.samples,
+                unused_capacity: offsets.unused_capacity,
+            };
+
+            for (index, data) in samples.channels.iter().enumerate() {
+                match usage.get_mut(index) {
+                    Some(channel_usage) => channel_usage.usage += data.memory_usage(),
+                    None => shared += data.memory_usage(),
+                }
+            }
+        }
+
+        (usage, shared)
+    }
+}
+
+impl<Channels: ChannelsMemoryUsage> Layer<Channels> {
+    /// The memory currently occupied by the pixels of this layer.
+    pub fn memory_usage(&self) -> LayerMemoryUsage {
+        let (channels, shared) = self.channel_data.channels_memory_usage();
+
+        LayerMemoryUsage {
+            name: self.attributes.layer_name.clone(),
+            channels,
+            shared,
+        }
+    }
+}
+
+impl<Channels: ChannelsMemoryUsage> Image<Layer<Channels>> {
+    /// The memory currently occupied by the pixels of this image.
+    pub fn memory_usage(&self) -> ImageMemoryUsage {
+        ImageMemoryUsage {
+            layers: vec![self.layer_data.memory_usage()],
+        }
+    }
+}
+
+impl<Channels: ChannelsMemoryUsage> Image<Layers<Channels>> {
+    /// The memory currently occupied by the pixels of this image.
+    pub fn memory_usage(&self) -> ImageMemoryUsage {
+        ImageMemoryUsage {
+            layers: self.layer_data.iter().map(Layer::memory_usage).collect(),
+        }
+    }
+}
+
+/// Estimate the memory that the pixels of this layer will occupy when decoded, without reading any pixels.
+/// Respects multi-resolution levels and subsampling of flat layers.
+///
+/// The number of samples in a deep layer is only known after decoding it.
+/// The estimate assumes `max_samples_per_pixel` samples in each pixel if the header specifies it,
+/// which is an upper bound, and one sample per pixel otherwise.
+pub fn estimate_layer_memory(header: &Header) -> LayerMemoryUsage {
+    let pixel_count = header.layer_size.area();
+    let samples_per_pixel = header.max_samples_per_pixel.unwrap_or(1);
+
+    let channels = header
+        .channels
+        .list
+        .iter()
+        .map(|channel| {
+            let samples = if header.deep {
+                pixel_count * samples_per_pixel * channel.sample_type.bytes_per_sample()
+            } else {
+                header.channel_pixel_bytes(channel)
+            };
+
+            ChannelMemoryUsage {
+                name: channel.name.clone(),
+                usage: MemoryUsage {
+                    samples,
+                    ..MemoryUsage::default()
+                },
+            }
+        })
+        .collect();
+
+    let shared = MemoryUsage {
+        offsets: if header.deep {
+            pixel_count * size_of::<u32>()
+        } else {
+            0
+        },
+        ..MemoryUsage::default()
+    };
+
+    LayerMemoryUsage {
+        name: header.own_attributes.layer_name.clone(),
+        channels,
+        shared,
+    }
+}
+
+/// Estimate the memory that the pixels of all layers will occupy when decoded, without reading any pixels.
+/// See `estimate_layer_memory` for details on deep layers.
+pub fn estimate_image_memory(meta_data: &MetaData) -> ImageMemoryUsage {
+    ImageMemoryUsage {
+        layers: meta_data
+            .headers
+            .iter()
+            .map(estimate_layer_memory)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::read::deep::read_first_deep_layer_from_file;
+    use crate::prelude::*;
+
+    #[test]
+    fn flat_estimate_matches_decoded_samples() {
+        let path = "tests/images/valid/custom/compression_methods/f16/zip.exr";
+        let meta_data = MetaData::read_from_file(path, false).unwrap();
+        let image = read_all_flat_layers_from_file(path).unwrap();
+
+        let estimate = estimate_image_memory(&meta_data);
+        let usage = image.memory_usage();
+
+        assert_eq!(estimate.layers.len(), usage.layers.len());
+        assert_eq!(estimate.total().samples, usage.total().samples);
+        assert_eq!(usage.total().offsets, 0);
+
+        for (estimated, decoded) in estimate.layers.iter().zip(&usage.layers) {
+            for (estimated, decoded) in estimated.channels.iter().zip(&decoded.channels) {
+                assert_eq!(estimated.name, decoded.name);
+                assert_eq!(estimated.usage.samples, decoded.usage.samples);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_channels_are_attributed_by_index() {
+        let path = "tests/images/valid/openexr/v2/LowResLeftView/Balls.exr";
+        let image = read_first_deep_layer_from_file(path).unwrap();
+        let samples = &image.layer_data.channel_data.list[0].sample_data;
+
+        let usage = image.layer_data.memory_usage();
+        assert_eq!(usage.channels.len(), samples.channels.len());
+        assert!(usage.shared.offsets >= samples.pixel_count() * size_of::<u32>());
+
+        for (channel, data) in usage.channels.iter().zip(&samples.channels) {
+            assert_eq!(channel.usage.samples, data.memory_usage().samples);
+            assert!(channel.usage.samples > 0);
+        }
+
+        let meta_data = MetaData::read_from_file(path, false).unwrap();
+        let estimate = estimate_layer_memory(&meta_data.headers[0]);
+        assert_eq!(
+            estimate.shared.offsets,
+            samples.pixel_count() * size_of::<u32>()
+        );
+    }
+
+    #[test]
+    fn add_memory_usage() {
+        let usage = MemoryUsage {
+            samples: 8,
+            offsets: 4,
+            unused_capacity: 2,
+        };
+
+        let sum: MemoryUsage = vec![usage, usage].into_iter().sum();
+        assert_eq!(sum.total(), 28);
+        assert_eq!(sum.overhead(), 12);
+    }
+}
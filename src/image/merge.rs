@@ -0,0 +1,433 @@
+//! Merge the samples of two deep images, like the deep merge and holdout operations of deep compositing.
+//!
+//! The samples of each pixel are interleaved by their depth, such that flattening the merged image
+//! composites the objects of both images in the correct order, even where they intersect.
+//! Both images must have the same resolution.
+//!
+//! Channels are matched by name. Channels of the second image can be renamed with a [`ChannelMapping`]
+//! before merging, for example if one renderer writes `depth` instead of `Z`.
+//! A channel that is missing in one of the images contains zero for the samples of that image.
+//!
+//! ```no_run
+//! use exr::image::merge::ChannelMapping;
+//! use exr::image::read::deep::read_first_deep_layer_from_file;
+//!
+//! let smoke = read_first_deep_layer_from_file("smoke.exr").unwrap();
+//! let character = read_first_deep_layer_from_file("character.exr").unwrap();
+//!
+//! let mapping = ChannelMapping::new().rename("depth", "Z");
+//! let merged = smoke.layer_data.merge(&character.layer_data, &mapping).unwrap();
+//! let flat = merged.channel_data.flatten(Default::default()).unwrap();
+//! ```
+
+use crate::error::{Error, Result};
+use crate::image::deep::{DeepChannelData, DeepSample, DeepSamples};
+use crate::image::{AnyChannel, AnyChannels, Layer};
+use crate::meta::attribute::{SampleType, Text};
+use half::f16;
+use smallvec::SmallVec;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/// Renames channels of the second image, before matching the channels of both images by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelMapping {
+    renames: Vec<(Text, Text)>,
+}
+
+impl ChannelMapping {
+    /// Match all channels by their original name.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge the channel of the second image named `from` into the channel named `to`.
+    pub fn rename(mut self, from: impl Into<Text>, to: impl Into<Text>) -> Self {
+        self.renames.push((from.into(), to.into()));
+        self
+    }
+
+    /// The name of the channel in the merged image.
+    fn target<'s>(&'s self, name: &'s Text) -> &'s Text {
+        self.renames
+            .iter()
+            .find(|(from, _)| from == name)
+            .map_or(name, |(_, to)| to)
+    }
+}
+
+/// Which of the two merged images a sample comes from, and its index in that image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    First(usize),
+    Second(usize),
+}
+
+impl DeepSamples {
+    /// Interleave the samples of both images by their depth, per pixel.
+    /// Both images must have the same resolution and the same channels, in the same order,
+    /// and `depth_channel` is the index of the `Z` channel.
+    /// Samples with equal depth keep their order, and samples of this image come first.
+    pub fn merge(&self, other: &DeepSamples, depth_channel: usize) -> Result<DeepSamples> {
+        if self.width != other.width || self.height != other.height {
+            return Err(Error::invalid(
+                "merged deep images must have the same resolution",
+            ));
+        }
+
+        let matching_channels = self.channels.len() == other.channels.len()
+            && self
+                .channels
+                .iter()
+                .zip(&other.channels)
+                .all(|(a, b)| a.sample_type() == b.sample_type());
+
+        if !matching_channels {
+            return Err(Error::invalid(
+                "merged deep images must have the same channels",
+            ));
+        }
+
+        if depth_channel >= self.channels.len() {
+            return Err(Error::invalid("depth channel index"));
+        }
+
+        let depth = self.channels[depth_channel].to_f32_vec();
+        let other_depth = other.channels[depth_channel].to_f32_vec();
+
+        let total = self.total_samples() + other.total_samples();
+        let mut sources = Vec::with_capacity(total);
+        let mut sample_offsets = Vec::with_capacity(self.pixel_count());
+
+        for (pixel, other_pixel) in self.pixels().zip(other.pixels()) {
+            let first_source = sources.len();
+            sources.extend(pixel.sample_indices().map(Source::First));
+            sources.extend(other_pixel.sample_indices().map(Source::Second));
+
+            let depth_of = |source: &Source| match *source {
+                Source::First(index) => depth[index],
+                Source::Second(index) => other_depth[index],
+            };
+
+            // stable, so that samples of equal depth keep their order
+            sources[first_source..].sort_by(|a, b| {
+                depth_of(a)
+                    .partial_cmp(&depth_of(b))
+                    .unwrap_or(Ordering::Equal)
+            });
+
+            let offset = u32::try_from(sources.len())
+                .map_err(|_| Error::unsupported("too many deep samples"))?;
+
+            sample_offsets.push(offset);
+        }
+
+        let channels = self
+            .channels
+            .iter()
+            .zip(&other.channels)
+            .map(|(first, second)| match first.sample_type() {
+                SampleType::F16 => gather::<f16>(first, second, &sources),
+                SampleType::F32 => gather::<f32>(first, second, &sources),
+                SampleType::U32 => gather::<u32>(first, second, &sources),
+            })
+            .collect();
+
+        Ok(DeepSamples {
+            sample_offsets,
+            channels,
+            width: self.width,
+            height: self.height,
+        })
+    }
+}
+
+/// Collect the values of both channels in the order of the sources.
+/// Both channels must contain values of type `T`.
+fn gather<T: DeepSample>(
+    first: &DeepChannelData,
+    second: &DeepChannelData,
+    sources: &[Source],
+) -> DeepChannelData {
+    let first = T::channel_values(first).expect("merged channel sample type");
+    let second = T::channel_values(second).expect("merged channel sample type");
+
+    let values = sources
+        .iter()
+        .map(|source| match *source {
+            Source::First(index) => first[index],
+            Source::Second(index) => second[index],
+        })
+        .collect();
+
+    T::into_channel_data(values)
+}
+
+/// A channel of the merged image, and the index of that channel in each of the two images.
+#[derive(Debug)]
+struct MergedChannel<'c> {
+    name: &'c Text,
+    template: &'c AnyChannel<DeepSamples>,
+    first: Option<usize>,
+    second: Option<usize>,
+}
+
+impl AnyChannels<DeepSamples> {
+    /// Interleave the samples of both images by their `Z` channel, per pixel.
+    /// The channels of the other image are renamed with the mapping, and then matched by name.
+    /// The result contains the channels of both images, with the sample type of this image.
+    /// A channel that is missing in one of the images contains zero for the samples of that image.
+    ///
+    /// Like the layers that are read from files, the first channel contains the samples of all channels.
+    pub fn merge(&self, other: &Self, mapping: &ChannelMapping) -> Result<Self> {
+        let empty = DeepSamples::new(0, 0);
+        let samples = self
+            .list
+            .first()
+            .map_or(&empty, |channel| &channel.sample_data);
+        let other_samples = other
+            .list
+            .first()
+            .map_or(&empty, |channel| &channel.sample_data);
+
+        if samples.channels.len() != self.list.len()
+            || other_samples.channels.len() != other.list.len()
+        {
+            return Err(Error::invalid("deep channel count"));
+        }
+
+        let mut channels: Vec<MergedChannel<'_>> = self
+            .list
+            .iter()
+            .enumerate()
+            .map(|(index, channel)| MergedChannel {
+                name: &channel.name,
+                template: channel,
+                first: Some(index),
+                second: None,
+            })
+            .collect();
+
+        for (index, channel) in other.list.iter().enumerate() {
+            let name = mapping.target(&channel.name);
+
+            match channels.iter_mut().find(|merged| merged.name == name) {
+                Some(merged) if merged.second.is_none() => merged.second = Some(index),
+                Some(_) => {
+                    return Err(Error::invalid(
+                        "channel mapping produces duplicate channel names",
+                    ))
+                }
+                None => channels.push(MergedChannel {
+                    name,
+                    template: channel,
+                    first: None,
+                    second: Some(index),
+                }),
+            }
+        }
+
+        channels.sort_by(|a, b| a.name.cmp(b.name));
+
+        let depth_channel = channels
+            .iter()
+            .position(|channel| channel.name.eq("Z"))
+            .ok_or_else(|| Error::invalid("merging deep images requires a Z channel"))?;
+
+        // align the channels of both images, such that they can be merged index by index
+        let sample_types: Vec<SampleType> = channels
+            .iter()
+            .map(|channel| match (channel.first, channel.second) {
+                (Some(index), _) => samples.channels[index].sample_type(),
+                (None, Some(index)) => other_samples.channels[index].sample_type(),
+                (None, None) => unreachable!("merged channel without source"),
+            })
+            .collect();
+
+        let first = aligned(
+            samples,
+            channels.iter().map(|channel| channel.first),
+            &sample_types,
+        );
+        let second = aligned(
+            other_samples,
+            channels.iter().map(|channel| channel.second),
+            &sample_types,
+        );
+
+        let mut merged = Some(first.merge(&second, depth_channel)?);
+
+        let list = channels
+            .iter()
+            .map(|channel| AnyChannel {
+                name: channel.name.clone(),
+                sample_data: merged.take().unwrap_or_else(|| DeepSamples::new(0, 0)),
+                quantize_linearly: channel.template.quantize_linearly,
+                sampling: channel.template.sampling,
+            })
+            .collect::<SmallVec<_>>();
+
+        Ok(AnyChannels { list })
+    }
+}
+
+impl Layer<AnyChannels<DeepSamples>> {
+    /// Merge the samples of both layers by depth. See [`AnyChannels::merge`].
+    /// Both layers must have the same data window.
+    /// The result has the attributes and the encoding of this layer.
+    pub fn merge(&self, other: &Self, mapping: &ChannelMapping) -> Result<Self> {
+        if self.size != other.size
+            || self.attributes.layer_position != other.attributes.layer_position
+        {
+            return Err(Error::invalid(
+                "merged deep layers must have the same data window",
+            ));
+        }
+
+        Ok(Layer {
+            channel_data: self.channel_data.merge(&other.channel_data, mapping)?,
+            attributes: self.attributes.clone(),
+            size: self.size,
+            encoding: self.encoding,
+        })
+    }
+}
+
+/// The samples with the channels at the indices, converted to the sample types,
+/// or zero for channels without an index.
+fn aligned(
+    samples: &DeepSamples,
+    indices: impl Iterator<Item = Option<usize>>,
+    sample_types: &[SampleType],
+) -> DeepSamples {
+    let channels = indices
+        .zip(sample_types)
+        .map(|(index, &sample_type)| match index {
+            Some(index) => convert(&samples.channels[index], sample_type),
+            None => zeroes(sample_type, samples.total_samples()),
+        })
+        .collect();
+
+    DeepSamples {
+        sample_offsets: samples.sample_offsets.clone(),
+        channels,
+        width: samples.width,
+        height: samples.height,
+    }
+}
+
+/// The values of the channel, converted to the sample type.
+fn convert(data: &DeepChannelData, sample_type: SampleType) -> DeepChannelData {
+    if data.sample_type() == sample_type {
+        return data.clone();
+    }
+
+    match sample_type {
+        SampleType::F16 => {
+            DeepChannelData::F16(data.to_f32_vec().into_iter().map(f16::from_f32).collect())
+        }
+        SampleType::F32 => DeepChannelData::F32(data.to_f32_vec()),
+        SampleType::U32 => DeepChannelData::U32(
+            data.to_f32_vec()
+                .into_iter()
+                .map(|value| value as u32)
+                .collect(),
+        ),
+    }
+}
+
+fn zeroes(sample_type: SampleType, sample_count: usize) -> DeepChannelData {
+    match sample_type {
+        SampleType::F16 => DeepChannelData::F16(vec![f16::ZERO; sample_count]),
+        SampleType::F32 => DeepChannelData::F32(vec![0.0; sample_count]),
+        SampleType::U32 => DeepChannelData::U32(vec![0; sample_count]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::Vec2;
+
+    /// A 2x1 image with a single sample per pixel, at the specified depths.
+    fn channels(names: &[&str], depths: [f32; 2], ids: [u32; 2]) -> AnyChannels<DeepSamples> {
+        let mut samples = DeepSamples::new(2, 1);
+        samples.set_cumulative_counts(vec![1, 2]).unwrap();
+
+        samples.channels = names
+            .iter()
+            .map(|&name| match name {
+                "id" => DeepChannelData::U32(ids.to_vec()),
+                "A" => DeepChannelData::F16(vec![f16::ONE; 2]),
+                _ => DeepChannelData::F32(depths.to_vec()),
+            })
+            .collect();
+
+        let mut samples = Some(samples);
+        let list = names
+            .iter()
+            .map(|&name| AnyChannel {
+                name: Text::from(name),
+                sample_data: samples.take().unwrap_or_else(|| DeepSamples::new(0, 0)),
+                quantize_linearly: false,
+                sampling: Vec2(1, 1),
+            })
+            .collect();
+
+        AnyChannels { list }
+    }
+
+    #[test]
+    fn interleaves_samples_by_depth() {
+        let near = channels(&["A", "Z", "id"], [1.0, 8.0], [1, 2]);
+        let far = channels(&["A", "Z", "id"], [4.0, 5.0], [3, 4]);
+
+        let merged = near.merge(&far, &ChannelMapping::new()).unwrap();
+        let samples = &merged.list[0].sample_data;
+
+        assert_eq!(samples.sample_offsets, vec![2, 4]);
+        assert_eq!(
+            samples.channels[1],
+            DeepChannelData::F32(vec![1.0, 4.0, 5.0, 8.0])
+        );
+        assert_eq!(samples.channels[2], DeepChannelData::U32(vec![1, 3, 4, 2]));
+        samples.validate().unwrap();
+    }
+
+    #[test]
+    fn maps_and_adds_channels() {
+        let near = channels(&["A", "Z"], [1.0, 8.0], [0, 0]);
+        let far = channels(&["depth", "id"], [4.0, 5.0], [3, 4]);
+
+        let mapping = ChannelMapping::new().rename("depth", "Z");
+        let merged = near.merge(&far, &mapping).unwrap();
+
+        let names: Vec<String> = merged
+            .list
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+        assert_eq!(names, vec!["A", "Z", "id"]);
+
+        let samples = &merged.list[0].sample_data;
+        assert_eq!(
+            samples.channels[1],
+            DeepChannelData::F32(vec![1.0, 4.0, 5.0, 8.0])
+        );
+        assert_eq!(samples.channels[2], DeepChannelData::U32(vec![0, 3, 4, 0]));
+
+        // the far image has no alpha, which is zero for its samples
+        assert_eq!(samples.channels[0].to_f32_vec(), vec![1.0, 0.0, 0.0, 1.0]);
+
+        // without the mapping, the depth of the far image is a separate channel
+        let unmapped = near.merge(&far, &ChannelMapping::new()).unwrap();
+        assert_eq!(unmapped.list.len(), 4);
+    }
+
+    #[test]
+    fn rejects_different_resolutions() {
+        let samples = channels(&["Z"], [1.0, 2.0], [0, 0]);
+        let smaller = DeepSamples::new(1, 1);
+        assert!(samples.list[0].sample_data.merge(&smaller, 0).is_err());
+    }
+}
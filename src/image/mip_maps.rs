@@ -0,0 +1,261 @@
+//! Generate the smaller resolution levels of an image, for writing mip maps and rip maps.
+//! Each level is computed from the next larger level using a separable resampling filter,
+//! see the `resize` module.
+
+use crate::error::{Error, Result};
+use crate::image::{AnyChannel, AnyChannels, Blocks, Encoding, FlatSamples, Layer, Levels, RipMaps};
+use crate::math::{RoundingMode, Vec2};
+use crate::meta::attribute::{LevelMode, LineOrder};
+use crate::meta::{mip_map_levels, rip_map_levels};
+
+pub use crate::image::resize::{resize_f32, resize_nearest, ResizeFilter};
+
+impl Levels<FlatSamples> {
+    /// Compute all resolution levels of the specified level mode from the full resolution samples.
+    /// The level sizes are computed with the rounding mode, which must also be used for the tiles when writing.
+    pub fn generate(
+        full_resolution: FlatSamples,
+        size: Vec2<usize>,
+        level_mode: LevelMode,
+        rounding_mode: RoundingMode,
+        filter: ResizeFilter,
+    ) -> Self {
+        match level_mode {
+            LevelMode::Singular => Levels::Singular(full_resolution),
+
+            LevelMode::MipMap => {
+                let mut levels: Vec<FlatSamples> = Vec::new();
+                let mut previous_size = size;
+
+                for (_index, level_size) in mip_map_levels(rounding_mode, size) {
+                    let level = match levels.last() {
+                        None => full_resolution.clone(),
+                        Some(previous) => previous.resized(previous_size, level_size, filter),
+                    };
+
+                    levels.push(level);
+                    previous_size = level_size;
+                }
+
+                Levels::Mip {
+                    rounding_mode,
+                    level_data: levels,
+                }
+            }
+
+            LevelMode::RipMap => {
+                let all_levels: Vec<(Vec2<usize>, Vec2<usize>)> =
+                    rip_map_levels(rounding_mode, size).collect();
+
+                let level_count = Vec2(
+                    all_levels
+                        .iter()
+                        .map(|(index, _)| index.x() + 1)
+                        .max()
+                        .unwrap_or(1),
+                    all_levels
+                        .iter()
+                        .map(|(index, _)| index.y() + 1)
+                        .max()
+                        .unwrap_or(1),
+                );
+
+                let mut levels: Vec<FlatSamples> = Vec::with_capacity(all_levels.len());
+                for &(index, level_size) in &all_levels {
+                    // levels are ordered by rows, so the level above or to the left is always present
+                    let level = if index == Vec2(0, 0) {
+                        full_resolution.clone()
+                    } else {
+                        let source_index = if index.x() == 0 {
+                            Vec2(0, index.y() - 1)
+                        } else {
+                            Vec2(index.x() - 1, index.y())
+                        };
+
+                        let flat_index = source_index.flat_index_for_size(level_count);
+                        let source_size = all_levels[flat_index].1;
+                        levels[flat_index].resized(source_size, level_size, filter)
+                    };
+
+                    levels.push(level);
+                }
+
+                Levels::Rip {
+                    rounding_mode,
+                    level_data: RipMaps {
+                        map_data: levels,
+                        level_count,
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl AnyChannels<FlatSamples> {
+    /// Compute the resolution levels of all channels, see `Levels::generate`.
+    /// All channels must have a sampling rate of `1`, as required for tiled images.
+    pub fn generate_levels(
+        self,
+        size: Vec2<usize>,
+        level_mode: LevelMode,
+        rounding_mode: RoundingMode,
+        filter: ResizeFilter,
+    ) -> AnyChannels<Levels<FlatSamples>> {
+        AnyChannels {
+            list: self
+                .list
+                .into_iter()
+                .map(|channel| AnyChannel {
+                    name: channel.name,
+                    quantize_linearly: channel.quantize_linearly,
+                    sampling: channel.sampling,
+                    sample_data: Levels::generate(
+                        channel.sample_data,
+                        size,
+                        level_mode,
+                        rounding_mode,
+                        filter,
+                    ),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Layer<AnyChannels<FlatSamples>> {
+    /// Compute the resolution levels of all channels, see `Levels::generate`,
+    /// and store the layer in tiles of the specified size, such that it can be written as a mip map or rip map.
+    /// Keeps the compression of the layer.
+    /// Returns an error if any channel is subsampled, as tiled images do not support subsampling.
+    pub fn generate_levels(
+        self,
+        tile_size: Vec2<usize>,
+        level_mode: LevelMode,
+        rounding_mode: RoundingMode,
+        filter: ResizeFilter,
+    ) -> Result<Layer<AnyChannels<Levels<FlatSamples>>>> {
+        if self
+            .channel_data
+            .list
+            .iter()
+            .any(|channel| channel.sampling != Vec2(1, 1))
+        {
+            return Err(Error::unsupported("subsampled channels in tiled images"));
+        }
+
+        Ok(Layer {
+            channel_data: self.channel_data.generate_levels(
+                self.size,
+                level_mode,
+                rounding_mode,
+                filter,
+            ),
+
+            encoding: Encoding {
+                compression: self.encoding.compression,
+                blocks: Blocks::Tiles(tile_size),
+                line_order: LineOrder::Increasing,
+            },
+
+            attributes: self.attributes,
+            size: self.size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use half::f16;
+
+    #[test]
+    fn mip_map_level_sizes() {
+        let samples = FlatSamples::F32(vec![1.0; 10 * 6]);
+        let levels = Levels::generate(
+            samples,
+            Vec2(10, 6),
+            LevelMode::MipMap,
+            RoundingMode::Down,
+            ResizeFilter::Box,
+        );
+
+        let sizes: Vec<usize> = levels
+            .levels_as_slice()
+            .iter()
+            .map(FlatSamples::len)
+            .collect();
+        assert_eq!(sizes, vec![60, 15, 2, 1]);
+    }
+
+    #[test]
+    fn rip_map_level_sizes() {
+        let samples = FlatSamples::F16(vec![f16::ONE; 4 * 2]);
+        let levels = Levels::generate(
+            samples,
+            Vec2(4, 2),
+            LevelMode::RipMap,
+            RoundingMode::Down,
+            ResizeFilter::Triangle,
+        );
+
+        match levels {
+            Levels::Rip { level_data, .. } => {
+                assert_eq!(level_data.level_count, Vec2(3, 2));
+
+                let sizes: Vec<usize> = level_data.map_data.iter().map(FlatSamples::len).collect();
+                assert_eq!(sizes, vec![8, 4, 2, 4, 2, 1]);
+            }
+
+            _ => panic!("expected rip maps"),
+        }
+    }
+
+    #[test]
+    fn write_and_read_single_level() {
+        use crate::image::read::read;
+        use crate::prelude::*;
+        use std::io::Cursor;
+
+        let size = Vec2(12, 8);
+        let samples = FlatSamples::F32((0..size.area()).map(|index| index as f32).collect());
+        let layer = Layer::new(
+            size,
+            LayerAttributes::named("mips"),
+            Encoding::UNCOMPRESSED,
+            AnyChannels::sort(smallvec::smallvec![AnyChannel::new("Y", samples)]),
+        );
+
+        let layer = layer
+            .generate_levels(
+                Vec2(4, 4),
+                LevelMode::MipMap,
+                RoundingMode::Up,
+                ResizeFilter::Gaussian,
+            )
+            .unwrap();
+
+        let expected = match &layer.channel_data.list[0].sample_data {
+            Levels::Mip { level_data, .. } => level_data[1].clone(),
+            _ => panic!("expected mip maps"),
+        };
+
+        let mut bytes = Vec::new();
+        Image::from_layer(layer)
+            .write()
+            .to_buffered(Cursor::new(&mut bytes))
+            .unwrap();
+
+        let level = read()
+            .no_deep_data()
+            .specific_resolution_level(|levels| levels[1].index)
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(Cursor::new(&bytes))
+            .unwrap();
+
+        assert_eq!(level.layer_data.size, Vec2(6, 4));
+        assert_eq!(level.layer_data.channel_data.list[0].sample_data, expected);
+    }
+}
@@ -22,11 +22,23 @@
 //! This is the high-level interface for the pixels of an image.
 //! See `exr::blocks` module for a low-level interface.
 
+pub mod composite;
 pub mod crop;
+pub mod cryptomatte;
 pub mod deep;
+pub mod deep_stats;
+pub mod environment_map;
+pub mod flatten;
+pub mod luminance_chroma;
+pub mod memory;
+pub mod merge;
+pub mod mip_maps;
 pub mod pixel_vec;
+pub mod point_cloud;
+pub mod premultiply;
 pub mod read;
 pub mod recursive;
+pub mod resize;
 pub mod write;
 // pub mod channel_groups;
 
@@ -798,6 +810,20 @@ impl FlatSamples {
         self.values().map(|sample| sample.to_f32())
     }
 
+    /// Converts all samples in this storage to f32.
+    /// Half floats are converted in batches, using the conversion instructions
+    /// of the CPU where available (F16C on x86, NEON on ARM, with `half` 2.3.0 or newer), with a scalar fallback.
+    /// `u32` samples are converted without scaling.
+    pub fn to_f32_vec(&self) -> Vec<f32> {
+        use half::slice::HalfFloatSliceExt;
+
+        match self {
+            FlatSamples::F16(vec) => vec.to_f32_vec(),
+            FlatSamples::F32(vec) => vec.clone(),
+            FlatSamples::U32(vec) => vec.iter().map(|&value| value as f32).collect(),
+        }
+    }
+
     /// All samples in this storage as iterator.
     /// Matches the underlying sample type again for every sample,
     /// match yourself if performance is critical! Does not allocate.
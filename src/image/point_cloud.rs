@@ -0,0 +1,377 @@
+//! Export the samples of deep images as point clouds, to inspect deep renders in 3D applications.
+//!
+//! Each sample with a finite depth becomes a point. If the layer has a camera with a perspective
+//! projection, see `meta::camera::Camera`, the points are reprojected into world space.
+//! Otherwise, a point is placed at the center of its pixel, with the `Z` sample as the third coordinate.
+//!
+//! Point clouds are written as binary PLY files, with the color and the alpha of each sample,
+//! or as OBJ files, which contain the colors as additional vertex coordinates but no alpha.
+//! Both formats store sRGB encoded colors, which are unpremultiplied and clamped.
+//!
+//! ```no_run
+//! use exr::image::read::deep::read_first_deep_layer_from_file;
+//! use exr::image::point_cloud::{PointCloud, Projection};
+//!
+//! let image = read_first_deep_layer_from_file("deep.exr").unwrap();
+//! let projection = Projection::from_attributes(&image.layer_data.attributes).unwrap();
+//!
+//! let points = PointCloud::from_deep_layer(
+//!     &image.layer_data,
+//!     image.attributes.display_window,
+//!     &projection,
+//! )
+//! .unwrap();
+//!
+//! points.write_to_file("deep.ply").unwrap();
+//! ```
+
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result, UnitResult};
+use crate::export::Transfer;
+use crate::image::deep::{DeepChannelData, DeepSamples};
+use crate::image::{AnyChannels, Layer};
+use crate::math::Vec2;
+use crate::meta::attribute::IntegerBounds;
+use crate::meta::camera::{field_of_view_from_projection, Camera};
+use crate::meta::header::LayerAttributes;
+
+/// Where the points of the samples are placed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projection {
+    /// The center of the pixel in absolute pixel coordinates, with y pointing down,
+    /// and the `Z` sample as the third coordinate.
+    Pixels,
+
+    /// Reproject the center of the pixel through the camera into world space,
+    /// at the distance of the `Z` sample along the z axis of the camera.
+    /// The display window spans the normalized device coordinates of the camera.
+    Camera(Camera),
+}
+
+/// A sample of a deep image, positioned in space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    /// The position of the point, see `Projection`.
+    pub position: [f32; 3],
+
+    /// The linear rgb color of the sample, not premultiplied.
+    /// White if the layer has no `R`, `G`, and `B` channels.
+    pub color: [f32; 3],
+
+    /// The alpha of the sample, or `1.0` if the layer has no `A` channel.
+    pub alpha: f32,
+}
+
+/// The points of all samples of a deep layer.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PointCloud {
+    /// The points, in the order of the pixels and their samples.
+    pub points: Vec<Point>,
+}
+
+impl Projection {
+    /// Use the camera of the layer, if its attributes contain a perspective projection,
+    /// such as `worldToCamera` together with `worldToNDC`.
+    /// Returns an error if the matrices of the camera cannot be inverted.
+    pub fn from_attributes(attributes: &LayerAttributes) -> Result<Self> {
+        let camera = Camera::from_attributes(attributes)?;
+
+        let is_perspective = camera
+            .as_ref()
+            .and_then(|camera| camera.projection.as_ref())
+            .map_or(false, |projection| {
+                field_of_view_from_projection(projection).is_some()
+            });
+
+        Ok(match camera {
+            Some(camera) if is_perspective => Projection::Camera(camera),
+            _ => Projection::Pixels,
+        })
+    }
+}
+
+impl PointCloud {
+    /// Create a point for each sample of the layer with a finite depth.
+    /// The display window is required to compute the normalized device coordinates of the pixels.
+    /// Returns an error if the layer has no `Z` channel.
+    pub fn from_deep_layer(
+        layer: &Layer<AnyChannels<DeepSamples>>,
+        display_window: IntegerBounds,
+        projection: &Projection,
+    ) -> Result<Self> {
+        // the samples of all channels are stored in the first channel
+        let samples = match layer.channel_data.list.first() {
+            Some(channel) => &channel.sample_data,
+            None => return Ok(PointCloud::default()),
+        };
+
+        let channel = |name: &str| {
+            layer
+                .channel_data
+                .list
+                .iter()
+                .position(|channel| channel.name.eq(name))
+                .and_then(|index| samples.channels.get(index))
+        };
+
+        let depth = channel("Z").ok_or_else(|| Error::invalid("deep layer has no `Z` channel"))?;
+        let [red, green, blue, alpha] = ["R", "G", "B", "A"].map(channel);
+
+        let origin = layer.attributes.layer_position - display_window.position;
+        let display_size = Vec2(
+            display_window.size.width() as f32,
+            display_window.size.height() as f32,
+        );
+        let mut points = Vec::with_capacity(samples.total_samples());
+
+        for y in 0..samples.height {
+            for x in 0..samples.width {
+                // the center of the pixel, relative to the display window
+                let pixel = Vec2(
+                    (origin.x() as f32) + x as f32 + 0.5,
+                    (origin.y() as f32) + y as f32 + 0.5,
+                );
+
+                let ndc = Vec2(
+                    2.0 * pixel.x() / display_size.x() - 1.0,
+                    1.0 - 2.0 * pixel.y() / display_size.y(),
+                );
+
+                let (start, end) = samples.sample_range(y * samples.width + x);
+                for index in start..end {
+                    let z = depth.get_as_f32(index);
+
+                    let position = match projection {
+                        Projection::Pixels => Some([
+                            pixel.x() + display_window.position.x() as f32,
+                            pixel.y() + display_window.position.y() as f32,
+                            z,
+                        ]),
+                        Projection::Camera(camera) => camera.world_position_from_depth(ndc, z),
+                    };
+
+                    let position = match position {
+                        Some(position) if position.iter().all(|value| value.is_finite()) => {
+                            position
+                        }
+                        _ => continue,
+                    };
+
+                    let value = |channel: Option<&DeepChannelData>, default: f32| {
+                        channel.map_or(default, |channel| channel.get_as_f32(index))
+                    };
+
+                    let alpha = value(alpha, 1.0);
+                    let unpremultiply = if alpha > 0.0 { 1.0 / alpha } else { 1.0 };
+
+                    points.push(Point {
+                        position,
+                        color: [
+                            value(red, alpha) * unpremultiply,
+                            value(green, alpha) * unpremultiply,
+                            value(blue, alpha) * unpremultiply,
+                        ],
+                        alpha,
+                    });
+                }
+            }
+        }
+
+        Ok(PointCloud { points })
+    }
+
+    /// Write the points to a PLY or OBJ file, chosen by the extension of the path.
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> UnitResult {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+
+        let write_points = match extension.as_deref() {
+            Some("ply") => PointCloud::write_ply,
+            Some("obj") => PointCloud::write_obj,
+            _ => return Err(Error::unsupported("point cloud file extension")),
+        };
+
+        let mut file = BufWriter::new(std::fs::File::create(path)?);
+        write_points(self, &mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Write the points as a binary little endian PLY file, with `float` positions
+    /// and `uchar` colors and alpha.
+    pub fn write_ply(&self, write: &mut impl Write) -> UnitResult {
+        let header = format!(
+            "ply\n\
+             format binary_little_endian 1.0\n\
+             comment exported by exrs\n\
+             element vertex {}\n\
+             property float x\n\
+             property float y\n\
+             property float z\n\
+             property uchar red\n\
+             property uchar green\n\
+             property uchar blue\n\
+             property uchar alpha\n\
+             end_header\n",
+            self.points.len()
+        );
+
+        write.write_all(header.as_bytes())?;
+
+        for point in &self.points {
+            for coordinate in point.position {
+                write.write_all(&coordinate.to_le_bytes())?;
+            }
+
+            let [red, green, blue] = point.color.map(linear_to_srgb8);
+            let alpha = (point.alpha.max(0.0).min(1.0) * 255.0).round() as u8;
+            write.write_all(&[red, green, blue, alpha])?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the points as vertices of an OBJ file, each followed by its color from `0` to `1`.
+    /// OBJ files cannot contain the alpha of the points.
+    pub fn write_obj(&self, write: &mut impl Write) -> UnitResult {
+        writeln!(write, "# exported by exrs")?;
+
+        for point in &self.points {
+            let [x, y, z] = point.position;
+            let [red, green, blue] = point
+                .color
+                .map(|value| linear_to_srgb8(value) as f32 / 255.0);
+            writeln!(
+                write,
+                "v {} {} {} {:.4} {:.4} {:.4}",
+                x, y, z, red, green, blue
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn linear_to_srgb8(linear: f32) -> u8 {
+    (Transfer::Srgb.encode(linear) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::{AnyChannel, Encoding};
+    use crate::meta::camera::{multiply_matrices, perspective_projection};
+    use smallvec::smallvec;
+
+    fn deep_layer() -> Layer<AnyChannels<DeepSamples>> {
+        // two pixels, the first with two samples, the second with one
+        let mut samples = DeepSamples::new(2, 1);
+        samples.sample_offsets = vec![2, 3];
+        samples.channels = vec![
+            DeepChannelData::F32(vec![1.0, 0.5, 0.0]),
+            DeepChannelData::F32(vec![0.5, 0.25, 0.0]),
+            DeepChannelData::F32(vec![10.0, f32::INFINITY, 30.0]),
+        ];
+
+        let channel = |name: &str, sample_data: DeepSamples| AnyChannel {
+            name: name.into(),
+            sample_data,
+            quantize_linearly: false,
+            sampling: Vec2(1, 1),
+        };
+
+        // the samples of all channels are stored in the first channel
+        let channels = AnyChannels::sort(smallvec![
+            channel("A", samples),
+            channel("R", DeepSamples::new(0, 0)),
+            channel("Z", DeepSamples::new(0, 0)),
+        ]);
+
+        Layer {
+            channel_data: channels,
+            attributes: LayerAttributes::default(),
+            size: Vec2(2, 1),
+            encoding: Encoding::default(),
+        }
+    }
+
+    #[test]
+    fn points_at_pixels() {
+        let layer = deep_layer();
+        let display_window = IntegerBounds::new((0, 0), (2, 1));
+        let cloud =
+            PointCloud::from_deep_layer(&layer, display_window, &Projection::Pixels).unwrap();
+
+        // the sample with infinite depth is skipped
+        assert_eq!(cloud.points.len(), 2);
+        assert_eq!(cloud.points[0].position, [0.5, 0.5, 10.0]);
+        assert_eq!(cloud.points[0].color, [0.5, 1.0, 1.0]);
+        assert_eq!(cloud.points[1].position, [1.5, 0.5, 30.0]);
+        assert_eq!(cloud.points[1].alpha, 0.0);
+
+        let mut ply = Vec::new();
+        cloud.write_ply(&mut ply).unwrap();
+        let header_end = b"end_header\n";
+        let header_length = ply
+            .windows(header_end.len())
+            .position(|window| window == header_end)
+            .unwrap()
+            + header_end.len();
+
+        assert!(ply.starts_with(b"ply\nformat binary_little_endian 1.0\n"));
+        assert_eq!(ply.len(), header_length + 2 * (3 * 4 + 4));
+
+        let mut obj = Vec::new();
+        cloud.write_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), 2);
+
+        let mut missing_depth = deep_layer();
+        missing_depth.channel_data.list[2].name = "depth".into();
+        assert!(
+            PointCloud::from_deep_layer(&missing_depth, display_window, &Projection::Pixels)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn points_in_world_space() {
+        let mut layer = deep_layer();
+
+        #[rustfmt::skip]
+        let world_to_camera = [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            -1.0, -2.0, -3.0, 1.0,
+        ];
+
+        let projection = perspective_projection(Vec2(90.0, 90.0), 0.1..100.0);
+        layer.attributes.world_to_camera = Some(world_to_camera);
+        layer.attributes.world_to_normalized_device =
+            Some(multiply_matrices(&world_to_camera, &projection));
+
+        let projection = Projection::from_attributes(&layer.attributes).unwrap();
+        assert!(matches!(projection, Projection::Camera(_)));
+
+        // the first pixel is in the left half of the screen, the second in the right half
+        let display_window = IntegerBounds::new((0, 0), (2, 1));
+        let cloud = PointCloud::from_deep_layer(&layer, display_window, &projection).unwrap();
+        let [x, y, z] = cloud.points[0].position;
+        assert!((x - (1.0 - 5.0)).abs() < 1.0e-3, "{}", x);
+        assert!((y - 2.0).abs() < 1.0e-3 && (z - 13.0).abs() < 1.0e-3);
+
+        let [x, _, z] = cloud.points[1].position;
+        assert!((x - (1.0 + 15.0)).abs() < 1.0e-3 && (z - 33.0).abs() < 1.0e-3);
+
+        assert_eq!(
+            Projection::from_attributes(&LayerAttributes::default()).unwrap(),
+            Projection::Pixels
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! Premultiply or unpremultiply the color channels of a layer by their alpha channel.
+//!
+//! OpenEXR expects premultiplied ("associated") colors, but straight colors are still delivered often.
+//! Each color channel uses the alpha channel of its layer, for example `diffuse.R` uses `diffuse.A`,
+//! falling back to the `A` channel. Channels without alpha, alpha channels, depth channels (`Z`, `ZBack`),
+//! and `u32` channels such as object ids are left unchanged.
+//!
+//! The values are computed with `f32` precision, and rounded only once when stored as `f16`.
+//! Unpremultiplying a pixel with zero alpha leaves its colors unchanged, as they can only be emissive.
+//! If unpremultiplying by a tiny alpha exceeds the range of `f16`, the premultiplied value is kept.
+
+use crate::error::{Error, UnitResult};
+use crate::image::deep::{DeepChannelData, DeepSamples};
+use crate::image::{AnyChannels, FlatSamples};
+use half::f16;
+
+/// Whether to multiply or divide the colors by alpha.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Premultiply,
+    Unpremultiply,
+}
+
+impl AnyChannels<FlatSamples> {
+    /// Multiply each color channel by the alpha channel of its layer.
+    /// Fails if a color channel has another resolution than its alpha channel.
+    pub fn premultiply(&mut self) -> UnitResult {
+        self.apply_alpha(Operation::Premultiply)
+    }
+
+    /// Divide each color channel by the alpha channel of its layer.
+    /// Colors of pixels with zero alpha are left unchanged.
+    /// Fails if a color channel has another resolution than its alpha channel.
+    pub fn unpremultiply(&mut self) -> UnitResult {
+        self.apply_alpha(Operation::Unpremultiply)
+    }
+
+    fn apply_alpha(&mut self, operation: Operation) -> UnitResult {
+        let pairs = color_alpha_pairs(self, |index| {
+            matches!(self.list[index].sample_data, FlatSamples::U32(_))
+        });
+
+        for (color, alpha) in pairs {
+            let alpha_values = self.list[alpha].sample_data.to_f32_vec();
+            let color_channel = &mut self.list[color];
+
+            if color_channel.sample_data.len() != alpha_values.len() {
+                return Err(Error::unsupported(
+                    "color channel resolution differs from alpha channel",
+                ));
+            }
+
+            match &mut color_channel.sample_data {
+                FlatSamples::F16(values) => apply_to_f16(values, &alpha_values, operation),
+                FlatSamples::F32(values) => apply_to_f32(values, &alpha_values, operation),
+                FlatSamples::U32(_) => unreachable!("u32 channels are never premultiplied"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AnyChannels<DeepSamples> {
+    /// Multiply each color channel of every sample by the alpha of that sample.
+    pub fn premultiply(&mut self) -> UnitResult {
+        self.apply_alpha(Operation::Premultiply)
+    }
+
+    /// Divide each color channel of every sample by the alpha of that sample.
+    /// Colors of samples with zero alpha are left unchanged.
+    pub fn unpremultiply(&mut self) -> UnitResult {
+        self.apply_alpha(Operation::Unpremultiply)
+    }
+
+    fn apply_alpha(&mut self, operation: Operation) -> UnitResult {
+        // all channels share the samples of the first channel
+        let channel_count = match self.list.first() {
+            Some(channel) => channel.sample_data.channels.len(),
+            None => return Ok(()),
+        };
+
+        if channel_count != self.list.len() {
+            return Err(Error::invalid("deep channel count"));
+        }
+
+        let pairs = color_alpha_pairs(self, |index| {
+            matches!(
+                self.list[0].sample_data.channels[index],
+                DeepChannelData::U32(_)
+            )
+        });
+
+        let channels = &mut self.list[0].sample_data.channels;
+
+        for (color, alpha) in pairs {
+            let alpha_values = channels[alpha].to_f32_vec();
+
+            match &mut channels[color] {
+                DeepChannelData::F16(values) => apply_to_f16(values, &alpha_values, operation),
+                DeepChannelData::F32(values) => apply_to_f32(values, &alpha_values, operation),
+                DeepChannelData::U32(_) => unreachable!("u32 channels are never premultiplied"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The index of the alpha channel that belongs to the channel at the specified index:
+/// `diffuse.A` for `diffuse.R`, falling back to `A`. Alpha channels are their own alpha channel.
+pub(crate) fn alpha_channel_index<Samples>(
+    channels: &AnyChannels<Samples>,
+    channel: usize,
+) -> Option<usize> {
+    let name = channels.list[channel].name.to_string();
+    alpha_channel_index_by_name(channels, &name)
+}
+
+/// The index of the alpha channel that belongs to a channel with the specified name,
+/// which is not required to be in the list of channels.
+pub(crate) fn alpha_channel_index_by_name<Samples>(
+    channels: &AnyChannels<Samples>,
+    name: &str,
+) -> Option<usize> {
+    let channel_index = |name: &str| {
+        channels
+            .list
+            .iter()
+            .position(|channel| channel.name.eq(name))
+    };
+
+    let layer_alpha = match name.rfind('.') {
+        Some(dot) => format!("{}.A", &name[..dot]),
+        None => "A".to_string(),
+    };
+
+    channel_index(&layer_alpha).or_else(|| channel_index("A"))
+}
+
+/// The indices of all color channels that have an alpha channel, and their alpha channel.
+fn color_alpha_pairs<Samples>(
+    channels: &AnyChannels<Samples>,
+    is_integer: impl Fn(usize) -> bool,
+) -> Vec<(usize, usize)> {
+    (0..channels.list.len())
+        .filter(|&index| !is_integer(index))
+        .filter(|&index| {
+            let name = &channels.list[index].name;
+            !(name.eq("Z") || name.eq("ZBack"))
+        })
+        .filter_map(|index| {
+            alpha_channel_index(channels, index)
+                .filter(|&alpha| alpha != index)
+                .map(|alpha| (index, alpha))
+        })
+        .collect()
+}
+
+/// Premultiply or unpremultiply a single value.
+/// Returns `None` for unpremultiplied values with zero alpha, which are left unchanged.
+#[inline]
+fn apply(value: f32, alpha: f32, operation: Operation) -> Option<f32> {
+    match operation {
+        Operation::Premultiply => Some(value * alpha),
+        Operation::Unpremultiply if alpha == 0.0 => None,
+        Operation::Unpremultiply => Some(value / alpha),
+    }
+}
+
+fn apply_to_f32(values: &mut [f32], alpha: &[f32], operation: Operation) {
+    for (value, &alpha) in values.iter_mut().zip(alpha) {
+        if let Some(result) = apply(*value, alpha, operation) {
+            *value = result;
+        }
+    }
+}
+
+fn apply_to_f16(values: &mut [f16], alpha: &[f32], operation: Operation) {
+    for (value, &alpha) in values.iter_mut().zip(alpha) {
+        if let Some(result) = apply(value.to_f32(), alpha, operation) {
+            let result = f16::from_f32(result);
+
+            // keep the value if dividing by a tiny alpha exceeds the range of f16
+            if result.is_finite() || !value.is_finite() {
+                *value = result;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::AnyChannel;
+    use crate::math::Vec2;
+    use smallvec::smallvec;
+
+    fn flat_channel(name: &str, sample_data: FlatSamples) -> AnyChannel<FlatSamples> {
+        AnyChannel {
+            name: name.into(),
+            sample_data,
+            quantize_linearly: false,
+            sampling: Vec2(1, 1),
+        }
+    }
+
+    #[test]
+    fn premultiply_flat_layers_with_their_alpha() {
+        let mut channels = AnyChannels::sort(smallvec![
+            flat_channel("A", FlatSamples::F32(vec![0.5, 0.0, 1.0])),
+            flat_channel("R", FlatSamples::F32(vec![0.8, 0.4, 0.2])),
+            flat_channel("spec.A", FlatSamples::F16(vec![f16::from_f32(0.25); 3])),
+            flat_channel("spec.G", FlatSamples::F16(vec![f16::ONE; 3])),
+            flat_channel("id", FlatSamples::U32(vec![7, 8, 9])),
+        ]);
+
+        let original = channels.clone();
+        channels.premultiply().unwrap();
+
+        let values = |channels: &AnyChannels<FlatSamples>, name: &str| {
+            let channel = channels.list.iter().find(|channel| channel.name.eq(name));
+            channel.unwrap().sample_data.to_f32_vec()
+        };
+
+        assert_eq!(values(&channels, "R"), vec![0.4, 0.0, 0.2]);
+        assert_eq!(values(&channels, "spec.G"), vec![0.25; 3]);
+        assert_eq!(values(&channels, "A"), values(&original, "A"));
+        assert_eq!(values(&channels, "id"), values(&original, "id"));
+
+        channels.unpremultiply().unwrap();
+
+        // the color of the pixel with zero alpha is lost, but remains finite
+        assert_eq!(values(&channels, "R"), vec![0.8, 0.0, 0.2]);
+        assert_eq!(values(&channels, "spec.G"), vec![1.0; 3]);
+    }
+
+    #[test]
+    fn unpremultiply_f16_by_tiny_alpha() {
+        let tiny = f16::from_f32(1.0e-7);
+        assert!(tiny.to_f32() > 0.0);
+
+        let mut channels = AnyChannels::sort(smallvec![
+            flat_channel("A", FlatSamples::F16(vec![tiny, f16::ZERO])),
+            flat_channel("B", FlatSamples::F16(vec![f16::from_f32(0.5); 2])),
+        ]);
+
+        channels.unpremultiply().unwrap();
+        assert_eq!(
+            channels.list[1].sample_data,
+            FlatSamples::F16(vec![f16::from_f32(0.5); 2])
+        );
+    }
+
+    #[test]
+    fn premultiply_deep_samples() {
+        let mut samples = DeepSamples::new(2, 1);
+        samples.set_cumulative_counts(vec![2, 3]).unwrap();
+        samples.channels = vec![
+            DeepChannelData::F32(vec![0.5, 0.25, 0.0]),
+            DeepChannelData::F16(vec![f16::ONE; 3]),
+            DeepChannelData::F32(vec![10.0, 20.0, 30.0]),
+        ];
+
+        let channel = |name: &str, sample_data| AnyChannel {
+            name: name.into(),
+            sample_data,
+            quantize_linearly: false,
+            sampling: Vec2(1, 1),
+        };
+
+        let mut channels = AnyChannels {
+            list: smallvec![
+                channel("A", samples),
+                channel("G", DeepSamples::new(0, 0)),
+                channel("Z", DeepSamples::new(0, 0)),
+            ],
+        };
+
+        channels.premultiply().unwrap();
+
+        let samples = &channels.list[0].sample_data;
+        assert_eq!(samples.channels[1].to_f32_vec(), vec![0.5, 0.25, 0.0]);
+        assert_eq!(samples.channels[2].to_f32_vec(), vec![10.0, 20.0, 30.0]);
+
+        channels.unpremultiply().unwrap();
+
+        let samples = &channels.list[0].sample_data;
+        assert_eq!(samples.channels[1].to_f32_vec(), vec![1.0, 1.0, 0.0]);
+    }
+}
@@ -2,6 +2,7 @@
 
 use crate::block::chunk::TileCoordinates;
 use crate::block::lines::LineRef;
+use crate::block::pool::recycle_buffer;
 use crate::block::UncompressedBlock;
 use crate::error::{Result, UnitResult};
 use crate::image::read::layers::{ChannelsReader, ReadChannels};
@@ -70,6 +71,12 @@ pub trait SamplesReader {
 
     /// Deliver the final accumulated sample storage for the image
     fn into_samples(self) -> Self::Samples;
+
+    /// The resolution of the samples, if it differs from the layer size,
+    /// because only a smaller resolution level is read.
+    fn resolution(&self) -> Option<Vec2<usize>> {
+        None
+    }
 }
 
 impl<'s, S: 's + ReadSamples> ReadChannels<'s> for ReadAnyChannels<S> {
@@ -118,6 +125,7 @@ impl<S: SamplesReader> ChannelsReader for AnyChannelsReader<S> {
                 .read_line(line)?;
         }
 
+        recycle_buffer(decompressed.data);
         Ok(())
     }
 
@@ -137,4 +145,10 @@ impl<S: SamplesReader> ChannelsReader for AnyChannelsReader<S> {
                 .collect(),
         }
     }
+
+    fn resolution(&self) -> Option<Vec2<usize>> {
+        self.sample_channels_reader
+            .first()
+            .and_then(|channel| channel.samples.resolution())
+    }
 }
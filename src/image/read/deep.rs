@@ -26,8 +26,8 @@
 //!  │
 //!  ├── MetaData (header with deep=true)
 //!  │
-//!  ├── DeepScanLine blocks (compressed)
-//!  │    ├── y_coordinate
+//!  ├── DeepScanLine or DeepTile blocks (compressed)
+//!  │    ├── y_coordinate / tile_coordinates
 //!  │    ├── packed_offset_table (cumulative sample counts)
 //!  │    └── sample_data (interleaved channels, little-endian)
 //!  │
@@ -44,12 +44,19 @@
 //! [`merge_deep_blocks()`] combines them into a single [`DeepSamples`]:
 //!
 //! 1. Collect all blocks and sort by y-coordinate
-//! 2. Build combined cumulative offset table (adding prefix sums)
-//! 3. Allocate output channel arrays
-//! 4. Copy sample data from each block at correct offsets
+//! 2. Build combined cumulative offset table, shifting the offsets of each block
+//!    by the samples of all previous blocks
+//! 3. Concatenate the sample data of each channel, one channel per thread
 //!
+//! Blocks are decompressed and unpacked on a thread pool while the file is being read,
+//! so only the concatenation remains after reading.
 //! This is the main complexity vs flat images which can directly use block data.
 //!
+//! Deep tiles are merged pixel by pixel at their position inside the resolution level.
+//! Only the tiles of one level are read, the largest level by default,
+//! or the level specified with [`ReadDeepImage::resolution_level`].
+//! The tile size is available as `layer.encoding.blocks`.
+//!
 //! # Usage Examples
 //!
 //! Simple file reading:
@@ -88,22 +95,29 @@
 //! - [`crate::block::deep`] - Block-level decompression
 //! - [`crate::image::read`] - Flat image reading (non-deep)
 
+use std::convert::TryFrom;
 use std::io::{BufReader, Read, Seek};
 use std::path::Path;
 
-use crate::block::chunk::CompressedBlock;
+use crate::block::cancel::CancellationToken;
+use crate::block::chunk::{CompressedBlock, TileCoordinates};
+#[cfg(feature = "rayon")]
+use crate::block::deep::ParallelDeepBlockDecompressor;
 use crate::block::deep::{
-    decompress_deep_scanline_block, decompress_deep_tile_block,
+    channel_indices, decompress_deep_scanline_block_with_channels,
+    decompress_deep_tile_block_with_channels, deep_tile_size, DeepUncompressedBlock,
     SequentialDeepBlockDecompressor,
 };
-#[cfg(feature = "rayon")]
-use crate::block::deep::ParallelDeepBlockDecompressor;
-use crate::block::reader::Reader;
+use crate::block::limits::{DeepBudget, ReadLimits};
+use crate::block::reader::{ChunksReader, Reader};
 use crate::error::{Error, Result};
-use crate::image::deep::DeepSamples;
-use crate::image::{AnyChannel, AnyChannels, Blocks, Encoding, Image, Layer};
+use crate::image::deep::{DeepChannelData, DeepSamples};
+use crate::image::read::region;
+use crate::image::{ignore_progress, AnyChannel, AnyChannels, Blocks, Encoding, Image, Layer};
+use crate::math::Vec2;
+use crate::meta::attribute::{IntegerBounds, LevelMode, Text};
 use crate::meta::header::Header;
-use crate::meta::BlockDescription;
+use crate::meta::{compute_level_size, mip_map_indices, rip_map_indices, BlockDescription};
 use smallvec::SmallVec;
 
 // ============================================================================
@@ -136,6 +150,17 @@ pub fn read_first_deep_layer_from_file(path: impl AsRef<Path>) -> Result<DeepIma
         .from_file(path)
 }
 
+/// Read the deep layer with the specified index from a file, which is the index of the part in multi-part files.
+/// Only the blocks of this layer are decompressed.
+/// Uses parallel decompression and relaxed error handling.
+pub fn read_deep_layer_from_file(path: impl AsRef<Path>, layer_index: usize) -> Result<DeepImage> {
+    read_deep()
+        .all_channels()
+        .specific_layer(layer_index)
+        .all_attributes()
+        .from_file(path)
+}
+
 /// Read all deep layers from a file.
 /// Uses parallel decompression and relaxed error handling.
 pub fn read_all_deep_layers_from_file(path: impl AsRef<Path>) -> Result<DeepLayersImage> {
@@ -186,6 +211,12 @@ impl ReadDeepAllChannels {
         ReadDeepFirstLayer
     }
 
+    /// Read only the deep layer with the specified index,
+    /// which is the index of the part in multi-part files.
+    pub fn specific_layer(self, layer_index: usize) -> ReadDeepSpecificLayer {
+        ReadDeepSpecificLayer { layer_index }
+    }
+
     /// Read all deep layers.
     pub fn all_layers(self) -> ReadDeepAllLayers {
         ReadDeepAllLayers
@@ -202,7 +233,38 @@ impl ReadDeepFirstLayer {
         ReadDeepImage {
             pedantic: false,
             _parallel: cfg!(feature = "rayon"),
-            _on_progress: None,
+            on_progress: ignore_progress,
+            cancellation: None,
+            limits: ReadLimits::UNLIMITED,
+            channel_names: None,
+            layer_index: None,
+            region: None,
+            level: Vec2(0, 0),
+            _layer_selection: std::marker::PhantomData,
+        }
+    }
+}
+
+/// Read the deep layer with a specific index.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ReadDeepSpecificLayer {
+    /// The index of the layer (the part of the file) to read.
+    pub layer_index: usize,
+}
+
+impl ReadDeepSpecificLayer {
+    /// Include all image attributes.
+    pub fn all_attributes(self) -> ReadDeepImage<FirstLayer> {
+        ReadDeepImage {
+            pedantic: false,
+            _parallel: cfg!(feature = "rayon"),
+            on_progress: ignore_progress,
+            cancellation: None,
+            limits: ReadLimits::UNLIMITED,
+            channel_names: None,
+            layer_index: Some(self.layer_index),
+            region: None,
+            level: Vec2(0, 0),
             _layer_selection: std::marker::PhantomData,
         }
     }
@@ -218,7 +280,13 @@ impl ReadDeepAllLayers {
         ReadDeepImage {
             pedantic: false,
             _parallel: cfg!(feature = "rayon"),
-            _on_progress: None,
+            on_progress: ignore_progress,
+            cancellation: None,
+            limits: ReadLimits::UNLIMITED,
+            channel_names: None,
+            layer_index: None,
+            region: None,
+            level: Vec2(0, 0),
             _layer_selection: std::marker::PhantomData,
         }
     }
@@ -234,14 +302,23 @@ pub struct AllLayers;
 
 /// Final reader configuration for deep images.
 #[derive(Debug, Clone)]
-pub struct ReadDeepImage<LayerSelection> {
+pub struct ReadDeepImage<LayerSelection, OnProgress = fn(f64)> {
     pedantic: bool,
     _parallel: bool,
-    _on_progress: Option<fn(f64)>,
+    on_progress: OnProgress,
+    cancellation: Option<CancellationToken>,
+    limits: ReadLimits,
+    channel_names: Option<Vec<Text>>,
+    /// Read this layer instead of the first deep layer.
+    layer_index: Option<usize>,
+    /// Only read the pixels inside this rectangle.
+    region: Option<IntegerBounds>,
+    /// The index of the resolution level to read.
+    level: Vec2<usize>,
     _layer_selection: std::marker::PhantomData<LayerSelection>,
 }
 
-impl<L> ReadDeepImage<L> {
+impl<L, F> ReadDeepImage<L, F> {
     /// Use pedantic error handling.
     pub fn pedantic(mut self) -> Self {
         self.pedantic = true;
@@ -254,14 +331,70 @@ impl<L> ReadDeepImage<L> {
         self
     }
 
-    /// Set progress callback.
-    pub fn on_progress(mut self, callback: fn(f64)) -> Self {
-        self._on_progress = Some(callback);
+    /// Specify a function to be called for each block that is read from the file.
+    /// Replaces all previously specified progress functions in this reader.
+    pub fn on_progress<OnProgress>(self, on_progress: OnProgress) -> ReadDeepImage<L, OnProgress>
+    where
+        OnProgress: FnMut(f64),
+    {
+        ReadDeepImage {
+            pedantic: self.pedantic,
+            _parallel: self._parallel,
+            on_progress,
+            cancellation: self.cancellation,
+            limits: self.limits,
+            channel_names: self.channel_names,
+            layer_index: self.layer_index,
+            region: self.region,
+            level: self.level,
+            _layer_selection: self._layer_selection,
+        }
+    }
+
+    /// Stop reading the image with `Error::Aborted` as soon as the token is cancelled.
+    /// The token is checked before each block is read from the file.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Refuse to read files that exceed the limits, with `Error::NotSupported`.
+    /// The meta data is checked before any samples are allocated,
+    /// and each block is checked before it is decompressed.
+    /// Use this when reading files from untrusted sources.
+    pub fn with_limits(mut self, limits: ReadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Only read the channels with the specified names.
+    /// The values of all other channels are skipped while unpacking the blocks,
+    /// and no memory is allocated for them.
+    /// Names that do not exist in a layer are ignored.
+    pub fn select_channels(mut self, names: impl IntoIterator<Item = impl Into<Text>>) -> Self {
+        self.channel_names = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only read the pixels inside this rectangle, in absolute coordinates like the data window.
+    /// Each resulting layer has the intersection of its data window and the rectangle
+    /// as its data window. Scan line blocks outside of the rectangle are not decompressed.
+    pub fn region(mut self, region: IntegerBounds) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Read the mip map or rip map level with this index instead of the largest level.
+    /// The size of each resulting layer is the size of that level.
+    /// Tiles of all other levels are skipped without decompressing them.
+    /// Fails if a layer does not contain the level. Cannot be combined with a region.
+    pub fn resolution_level(mut self, level: Vec2<usize>) -> Self {
+        self.level = level;
         self
     }
 }
 
-impl ReadDeepImage<FirstLayer> {
+impl<F: FnMut(f64)> ReadDeepImage<FirstLayer, F> {
     /// Read from file path.
     pub fn from_file(self, path: impl AsRef<Path>) -> Result<DeepImage> {
         self.from_unbuffered(std::fs::File::open(path)?)
@@ -272,20 +405,56 @@ impl ReadDeepImage<FirstLayer> {
         self.from_buffered(BufReader::new(read))
     }
 
+    /// Read from an asynchronous byte source, positioned at the start of the file.
+    /// The bytes are read without blocking the current task,
+    /// and the samples are then decompressed on the blocking thread pool of `tokio`.
+    #[cfg(feature = "tokio")]
+    pub async fn from_async(
+        self,
+        read: impl tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin,
+    ) -> Result<DeepImage>
+    where
+        F: Send + 'static,
+    {
+        use crate::block::asynchronous::{read_file_bytes, spawn_blocking};
+
+        let bytes = read_file_bytes(read, self.pedantic).await?;
+        spawn_blocking(move || self.from_buffered(std::io::Cursor::new(bytes))).await
+    }
+
     /// Read from buffered reader.
     pub fn from_buffered(self, read: impl Read + Seek) -> Result<DeepImage> {
         let reader = Reader::read_from_buffered(read, self.pedantic)?;
+        self.limits.validate_meta_data(reader.meta_data())?;
 
-        // Find first deep layer
-        let (layer_index, _header) = reader
-            .headers()
-            .iter()
-            .enumerate()
-            .find(|(_, h)| h.deep)
-            .ok_or_else(|| Error::invalid("no deep layer found"))?;
+        let layer_index = match self.layer_index {
+            Some(index) => match reader.headers().get(index) {
+                Some(header) if header.deep => index,
+                Some(_) => return Err(Error::invalid("layer does not contain deep data")),
+                None => return Err(Error::invalid("layer index out of bounds")),
+            },
+
+            // Find first deep layer
+            None => reader
+                .headers()
+                .iter()
+                .position(|h| h.deep)
+                .ok_or_else(|| Error::invalid("no deep layer found"))?,
+        };
 
         let image_attrs = reader.headers()[layer_index].shared_attributes.clone();
-        let layer = read_deep_layer_internal(reader, layer_index, self.pedantic, self._parallel)?;
+        let layer = read_deep_layer_internal(
+            reader,
+            layer_index,
+            self.channel_names.as_deref(),
+            self.region,
+            self.level,
+            self.pedantic,
+            self._parallel,
+            self.on_progress,
+            self.cancellation,
+            self.limits,
+        )?;
 
         Ok(Image {
             attributes: image_attrs,
@@ -294,7 +463,7 @@ impl ReadDeepImage<FirstLayer> {
     }
 }
 
-impl ReadDeepImage<AllLayers> {
+impl<F: FnMut(f64)> ReadDeepImage<AllLayers, F> {
     /// Read from file path.
     pub fn from_file(self, path: impl AsRef<Path>) -> Result<DeepLayersImage> {
         self.from_unbuffered(std::fs::File::open(path)?)
@@ -305,9 +474,27 @@ impl ReadDeepImage<AllLayers> {
         self.from_buffered(BufReader::new(read))
     }
 
+    /// Read from an asynchronous byte source, positioned at the start of the file.
+    /// The bytes are read without blocking the current task,
+    /// and the samples are then decompressed on the blocking thread pool of `tokio`.
+    #[cfg(feature = "tokio")]
+    pub async fn from_async(
+        self,
+        read: impl tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin,
+    ) -> Result<DeepLayersImage>
+    where
+        F: Send + 'static,
+    {
+        use crate::block::asynchronous::{read_file_bytes, spawn_blocking};
+
+        let bytes = read_file_bytes(read, self.pedantic).await?;
+        spawn_blocking(move || self.from_buffered(std::io::Cursor::new(bytes))).await
+    }
+
     /// Read from buffered reader.
     pub fn from_buffered(self, read: impl Read + Seek) -> Result<DeepLayersImage> {
         let reader = Reader::read_from_buffered(read, self.pedantic)?;
+        self.limits.validate_meta_data(reader.meta_data())?;
 
         // Collect deep layer indices
         let deep_indices: Vec<usize> = reader
@@ -331,7 +518,18 @@ impl ReadDeepImage<AllLayers> {
         // For now, only support single deep layer in all_layers mode
         // to avoid re-reading the file multiple times
         if deep_indices.len() == 1 {
-            let layer = read_deep_layer_internal(reader, deep_indices[0], self.pedantic, self._parallel)?;
+            let layer = read_deep_layer_internal(
+                reader,
+                deep_indices[0],
+                self.channel_names.as_deref(),
+                self.region,
+                self.level,
+                self.pedantic,
+                self._parallel,
+                self.on_progress,
+                self.cancellation,
+                self.limits,
+            )?;
             let mut layers = SmallVec::new();
             layers.push(layer);
 
@@ -343,10 +541,35 @@ impl ReadDeepImage<AllLayers> {
 
         // Multiple layers - need to collect all blocks first
         let meta = reader.meta_data().clone();
-        let chunks_reader = reader.all_chunks(self.pedantic)?;
+
+        if self.region.is_some() && self.level != Vec2(0, 0) {
+            return Err(region::smaller_level_error());
+        }
+
+        let level_sizes = deep_indices
+            .iter()
+            .map(|&index| level_size(&meta.headers[index], self.level))
+            .collect::<Result<Vec<_>>>()?;
 
         // Group blocks by layer
-        let mut layer_blocks: Vec<Vec<(usize, DeepSamples)>> = vec![Vec::new(); meta.headers.len()];
+        let mut layer_blocks: Vec<Vec<(Vec2<usize>, DeepSamples)>> =
+            vec![Vec::new(); meta.headers.len()];
+
+        let selected_channels: Vec<Option<Vec<usize>>> = meta
+            .headers
+            .iter()
+            .map(|header| {
+                let names = self.channel_names.as_deref()?;
+                Some(channel_indices(&header.channels, names))
+            })
+            .collect();
+
+        let chunks_reader = reader
+            .all_chunks(self.pedantic)?
+            .on_progress(self.on_progress)
+            .cancel_with(self.cancellation);
+
+        let mut budget = DeepBudget::new(self.limits);
 
         for chunk_result in chunks_reader {
             let chunk = chunk_result?;
@@ -357,45 +580,59 @@ impl ReadDeepImage<AllLayers> {
             }
 
             let header = &meta.headers[layer_idx];
+            let block_level = header
+                .get_block_data_indices(&chunk.compressed_block)?
+                .level_index;
+
+            if block_level != self.level {
+                continue;
+            }
+
+            budget.reserve_block(&chunk.compressed_block)?;
+
             let width = header.layer_size.width();
             let height = header.layer_size.height();
 
             match chunk.compressed_block {
                 CompressedBlock::DeepScanLine(ref deep_block) => {
-                    let y = deep_block.y_coordinate as usize;
+                    let position = block_position(header, deep_block.y_coordinate, None)?;
                     let block_height = header
                         .compression
                         .scan_lines_per_block()
-                        .min(height.saturating_sub(y));
+                        .min(height.saturating_sub(position.y()));
 
-                    let samples = decompress_deep_scanline_block(
+                    let samples = decompress_deep_scanline_block_with_channels(
                         deep_block,
                         header.compression,
                         &header.channels,
+                        selected_channels[layer_idx].as_deref(),
                         width,
                         block_height,
                         self.pedantic,
+                        &self.limits,
                     )?;
 
-                    layer_blocks[layer_idx].push((y, samples));
+                    budget.add_samples(samples.total_samples())?;
+                    layer_blocks[layer_idx].push((position, samples));
                 }
                 CompressedBlock::DeepTile(ref deep_block) => {
-                    let tile_size = match header.blocks {
-                        BlockDescription::Tiles(desc) => desc.tile_size,
-                        _ => return Err(Error::invalid("deep tile in scanline image")),
-                    };
+                    let tile_size = deep_tile_size(header, deep_block.coordinates)?;
 
-                    let samples = decompress_deep_tile_block(
+                    let samples = decompress_deep_tile_block_with_channels(
                         deep_block,
                         header.compression,
                         &header.channels,
+                        selected_channels[layer_idx].as_deref(),
                         tile_size.width(),
                         tile_size.height(),
                         self.pedantic,
+                        &self.limits,
                     )?;
 
-                    let y = deep_block.coordinates.tile_index.y() * tile_size.height();
-                    layer_blocks[layer_idx].push((y, samples));
+                    budget.add_samples(samples.total_samples())?;
+
+                    let position = block_position(header, 0, Some(deep_block.coordinates))?;
+                    layer_blocks[layer_idx].push((position, samples));
                 }
                 _ => {}
             }
@@ -404,18 +641,21 @@ impl ReadDeepImage<AllLayers> {
         // Build layers
         let mut layers = SmallVec::new();
 
-        for layer_idx in deep_indices {
+        for (layer_idx, size) in deep_indices.into_iter().zip(level_sizes) {
             let header = &meta.headers[layer_idx];
             let mut blocks = std::mem::take(&mut layer_blocks[layer_idx]);
-            blocks.sort_by_key(|(y, _)| *y);
+            blocks.sort_by_key(|(position, _)| (position.y(), position.x()));
 
-            let merged = merge_deep_blocks(
-                blocks,
-                header.layer_size.width(),
-                header.layer_size.height(),
-            )?;
+            let merged = merge_deep_blocks(blocks, size.width(), size.height(), self._parallel)?;
+
+            let layer = match self.region {
+                None => build_deep_layer(header, selected_channels[layer_idx].as_deref(), merged),
+                Some(region) => {
+                    let (cropped, merged) = crop_deep_samples(header, region, merged)?;
+                    build_deep_layer(&cropped, selected_channels[layer_idx].as_deref(), merged)
+                }
+            };
 
-            let layer = build_deep_layer(header, merged);
             layers.push(layer);
         }
 
@@ -461,7 +701,18 @@ pub fn read_deep_layer_samples<R: Read + Seek>(
     pedantic: bool,
 ) -> Result<AnyChannels<DeepSamples>> {
     let parallel = cfg!(feature = "rayon");
-    let layer = read_deep_layer_internal(reader, layer_index, pedantic, parallel)?;
+    let layer = read_deep_layer_internal(
+        reader,
+        layer_index,
+        None,
+        None,
+        Vec2(0, 0),
+        pedantic,
+        parallel,
+        ignore_progress,
+        None,
+        ReadLimits::UNLIMITED,
+    )?;
     Ok(layer.channel_data)
 }
 
@@ -470,39 +721,190 @@ pub fn read_deep_layer_samples<R: Read + Seek>(
 // ============================================================================
 
 /// Read a single deep layer from the reader.
+///
+/// Blocks are decompressed and unpacked on the thread pool while the file is read.
+/// Afterwards, the blocks are stitched together, one channel per thread.
+#[allow(clippy::too_many_arguments)]
 fn read_deep_layer_internal<R: Read + Seek>(
     reader: Reader<R>,
     layer_index: usize,
+    channel_names: Option<&[Text]>,
+    region: Option<IntegerBounds>,
+    level: Vec2<usize>,
     pedantic: bool,
     parallel: bool,
+    on_progress: impl FnMut(f64),
+    cancellation: Option<CancellationToken>,
+    limits: ReadLimits,
 ) -> Result<Layer<AnyChannels<DeepSamples>>> {
     let meta = reader.meta_data().clone();
     let header = &meta.headers[layer_index];
-    let width = header.layer_size.width();
-    let height = header.layer_size.height();
 
-    let chunks_reader = reader.all_chunks(pedantic)?;
+    if region.is_some() && level != Vec2(0, 0) {
+        return Err(region::smaller_level_error());
+    }
 
-    // Collect blocks using parallel or sequential decompression
-    let blocks = if parallel {
-        #[cfg(feature = "rayon")]
-        {
-            decompress_blocks_parallel(chunks_reader, layer_index, pedantic)?
-        }
-        #[cfg(not(feature = "rayon"))]
-        {
-            decompress_blocks_sequential(chunks_reader, layer_index, pedantic)?
-        }
+    let size = level_size(header, level)?;
+
+    // skip the chunks of other layers, levels, and outside of the region without decompressing them
+    let blocks = if meta.headers.len() > 1 || region.is_some() || header.blocks.has_tiles() {
+        let chunks = reader.filter_chunks(pedantic, |_, _, block| {
+            block.layer == layer_index
+                && block.level == level
+                && region.map_or(true, |region| {
+                    region::block_intersects(header, region, block)
+                })
+        })?;
+
+        let chunks = chunks.on_progress(on_progress).cancel_with(cancellation);
+        decompress_layer_blocks(
+            chunks,
+            layer_index,
+            channel_names,
+            pedantic,
+            parallel,
+            limits,
+        )?
     } else {
-        decompress_blocks_sequential(chunks_reader, layer_index, pedantic)?
+        let chunks = reader.all_chunks(pedantic)?;
+        let chunks = chunks.on_progress(on_progress).cancel_with(cancellation);
+        decompress_layer_blocks(
+            chunks,
+            layer_index,
+            channel_names,
+            pedantic,
+            parallel,
+            limits,
+        )?
     };
 
-    // Sort by y coordinate and merge
-    let mut blocks = blocks;
-    blocks.sort_by_key(|(y, _)| *y);
-    let merged = merge_deep_blocks(blocks, width, height)?;
+    let mut blocks = blocks
+        .into_iter()
+        .map(|block| {
+            let position = block_position(header, block.y_coordinate, block.tile)?;
+            Ok((position, block.samples))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    blocks.sort_by_key(|(position, _)| (position.y(), position.x()));
+    let merged = merge_deep_blocks(blocks, size.width(), size.height(), parallel)?;
+
+    let selected_channels = channel_names.map(|names| channel_indices(&header.channels, names));
+
+    if let Some(region) = region {
+        let (cropped, merged) = crop_deep_samples(header, region, merged)?;
+        return Ok(build_deep_layer(
+            &cropped,
+            selected_channels.as_deref(),
+            merged,
+        ));
+    }
+
+    Ok(build_deep_layer(
+        header,
+        selected_channels.as_deref(),
+        merged,
+    ))
+}
+
+/// The size of the resolution level of the layer, or an error if the layer does not contain it.
+fn level_size(header: &Header, level: Vec2<usize>) -> Result<Vec2<usize>> {
+    let tiles = match header.blocks {
+        BlockDescription::Tiles(tiles) => tiles,
+        BlockDescription::ScanLines if level == Vec2(0, 0) => return Ok(header.layer_size),
+        BlockDescription::ScanLines => return Err(Error::invalid("resolution level index")),
+    };
+
+    let round = tiles.rounding_mode;
+    let contains_level = match tiles.level_mode {
+        LevelMode::Singular => level == Vec2(0, 0),
+        LevelMode::MipMap => {
+            level.x() == level.y()
+                && mip_map_indices(round, header.layer_size).any(|index| index == level.x())
+        }
+        LevelMode::RipMap => rip_map_indices(round, header.layer_size).any(|index| index == level),
+    };
+
+    if !contains_level {
+        return Err(Error::invalid("resolution level index"));
+    }
+
+    Ok(Vec2(
+        compute_level_size(round, header.layer_size.width(), level.x()),
+        compute_level_size(round, header.layer_size.height(), level.y()),
+    ))
+}
+
+/// The position of the top left pixel of a deep block within the data window of its level.
+/// Scan line blocks are stored with the absolute y coordinate.
+fn block_position(
+    header: &Header,
+    y_coordinate: i32,
+    tile: Option<TileCoordinates>,
+) -> Result<Vec2<usize>> {
+    match tile {
+        Some(tile) => header
+            .get_absolute_block_pixel_coordinates(tile)?
+            .position
+            .to_usize("deep tile position"),
+
+        None => {
+            let origin = header.own_attributes.layer_position.y() as i64;
+            let y = usize::try_from(y_coordinate as i64 - origin)
+                .map_err(|_| Error::invalid("deep block outside of the data window"))?;
+
+            Ok(Vec2(0, y))
+        }
+    }
+}
+
+/// Crop the merged samples of the layer to the region.
+/// Returns the header of the cropped layer and its samples.
+fn crop_deep_samples(
+    header: &Header,
+    region: IntegerBounds,
+    samples: DeepSamples,
+) -> Result<(Header, DeepSamples)> {
+    let cropped = region::crop_header(header, region)?;
+    let size = cropped.layer_size;
+
+    if size.area() == 0 {
+        return Ok((cropped, DeepSamples::new(size.width(), size.height())));
+    }
 
-    Ok(build_deep_layer(&meta.headers[layer_index], merged))
+    let offset = (cropped.own_attributes.layer_position - header.own_attributes.layer_position)
+        .to_usize("cropped layer position")?;
+
+    let samples = samples.crop((offset.x(), offset.y()), (size.width(), size.height()));
+    Ok((cropped, samples))
+}
+
+/// Decompress the blocks of the layer, on the thread pool if parallel and possible.
+fn decompress_layer_blocks<R: crate::block::reader::ChunksReader>(
+    chunks: R,
+    layer_index: usize,
+    channel_names: Option<&[Text]>,
+    pedantic: bool,
+    parallel: bool,
+    limits: ReadLimits,
+) -> Result<Vec<DeepUncompressedBlock>> {
+    #[cfg(feature = "rayon")]
+    {
+        if parallel {
+            return decompress_blocks_parallel(
+                chunks,
+                layer_index,
+                channel_names,
+                pedantic,
+                limits,
+            );
+        }
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    let _ = parallel;
+
+    decompress_blocks_sequential(chunks, layer_index, channel_names, pedantic, limits)
 }
 
 /// Decompress blocks using parallel decompression (when rayon feature is enabled).
@@ -510,23 +912,35 @@ fn read_deep_layer_internal<R: Read + Seek>(
 fn decompress_blocks_parallel<R: crate::block::reader::ChunksReader>(
     chunks: R,
     layer_index: usize,
+    channel_names: Option<&[Text]>,
     pedantic: bool,
-) -> Result<Vec<(usize, DeepSamples)>> {
-    let decompressor = match ParallelDeepBlockDecompressor::new(chunks, pedantic) {
-        Ok(d) => d,
+    limits: ReadLimits,
+) -> Result<Vec<DeepUncompressedBlock>> {
+    let mut decompressor = match ParallelDeepBlockDecompressor::new(chunks, pedantic) {
+        Ok(d) => d.with_limits(limits),
         Err(chunks) => {
             // Fall back to sequential if parallel not beneficial (e.g., uncompressed data)
-            return decompress_blocks_sequential(chunks, layer_index, pedantic);
+            return decompress_blocks_sequential(
+                chunks,
+                layer_index,
+                channel_names,
+                pedantic,
+                limits,
+            );
         }
     };
 
-    let mut blocks = Vec::new();
+    if let Some(names) = channel_names {
+        decompressor = decompressor.select_channels(names);
+    }
+
+    let mut blocks = Vec::with_capacity(decompressor.len());
     for block_result in decompressor {
         let block = block_result?;
         if block.layer_index != layer_index {
             continue;
         }
-        blocks.push((block.y_coordinate as usize, block.samples));
+        blocks.push(block);
     }
     Ok(blocks)
 }
@@ -535,35 +949,53 @@ fn decompress_blocks_parallel<R: crate::block::reader::ChunksReader>(
 fn decompress_blocks_sequential<R: crate::block::reader::ChunksReader>(
     chunks: R,
     layer_index: usize,
+    channel_names: Option<&[Text]>,
     pedantic: bool,
-) -> Result<Vec<(usize, DeepSamples)>> {
-    let decompressor = SequentialDeepBlockDecompressor::new(chunks, pedantic);
+    limits: ReadLimits,
+) -> Result<Vec<DeepUncompressedBlock>> {
+    let mut decompressor =
+        SequentialDeepBlockDecompressor::new(chunks, pedantic).with_limits(limits);
+
+    if let Some(names) = channel_names {
+        decompressor = decompressor.select_channels(names);
+    }
 
-    let mut blocks = Vec::new();
+    let mut blocks = Vec::with_capacity(decompressor.len());
     for block_result in decompressor {
         let block = block_result?;
         if block.layer_index != layer_index {
             continue;
         }
-        blocks.push((block.y_coordinate as usize, block.samples));
+        blocks.push(block);
     }
     Ok(blocks)
 }
 
 /// Build a Layer from header and DeepSamples.
-fn build_deep_layer(header: &Header, samples: DeepSamples) -> Layer<AnyChannels<DeepSamples>> {
+/// The samples contain only the selected channels, or all channels if there is no selection.
+/// The size of the layer is the size of the samples, which may be a smaller resolution level.
+fn build_deep_layer(
+    header: &Header,
+    selected_channels: Option<&[usize]>,
+    samples: DeepSamples,
+) -> Layer<AnyChannels<DeepSamples>> {
+    let size = Vec2(samples.width, samples.height);
+
     // Build channel list - first channel gets the samples, rest get empty
+    let mut samples = Some(samples);
     let channels: SmallVec<[AnyChannel<DeepSamples>; 4]> = header
         .channels
         .list
         .iter()
         .enumerate()
+        .filter(|(index, _)| selected_channels.map_or(true, |selected| selected.contains(index)))
+        .map(|(_, ch)| ch)
+        .enumerate()
         .map(|(i, ch)| AnyChannel {
             name: ch.name.clone(),
-            sample_data: if i == 0 {
-                samples.clone()
-            } else {
-                DeepSamples::new(0, 0)
+            sample_data: match samples.take() {
+                Some(samples) if i == 0 => samples,
+                _ => DeepSamples::new(0, 0),
             },
             quantize_linearly: ch.quantize_linearly,
             sampling: ch.sampling,
@@ -573,7 +1005,7 @@ fn build_deep_layer(header: &Header, samples: DeepSamples) -> Layer<AnyChannels<
     Layer {
         channel_data: AnyChannels { list: channels },
         attributes: header.own_attributes.clone(),
-        size: header.layer_size,
+        size,
         encoding: Encoding {
             compression: header.compression,
             line_order: header.line_order,
@@ -585,11 +1017,178 @@ fn build_deep_layer(header: &Header, samples: DeepSamples) -> Layer<AnyChannels<
     }
 }
 
-/// Merge multiple deep scanline blocks into a single full-image [`DeepSamples`].
+/// Merge multiple deep blocks into a single full-image [`DeepSamples`].
+///
+/// Scan line blocks span the full width of the image, so the samples of each block
+/// are a contiguous range of the merged samples. Their offsets only need to be shifted
+/// by the samples of the previous blocks, and the channels are concatenated,
+/// one channel per thread if parallel. Other blocks, such as tiles, are merged pixel by pixel.
+///
+/// # Arguments
+///
+/// * `blocks` - Vec of (position, DeepSamples) pairs, one per block, sorted by y
+/// * `total_width` - Full image width
+/// * `total_height` - Full image height
+/// * `parallel` - Concatenate the channels on the thread pool
+fn merge_deep_blocks(
+    blocks: Vec<(Vec2<usize>, DeepSamples)>,
+    total_width: usize,
+    total_height: usize,
+    parallel: bool,
+) -> Result<DeepSamples> {
+    if blocks.is_empty() {
+        return Ok(DeepSamples::new(total_width, total_height));
+    }
+
+    if let [(Vec2(0, 0), block)] = blocks.as_slice() {
+        if block.width == total_width && block.height == total_height {
+            let (_, samples) = blocks.into_iter().next().unwrap();
+            return Ok(samples);
+        }
+    }
+
+    if blocks
+        .iter()
+        .all(|(position, block)| position.x() == 0 && block.width == total_width)
+    {
+        merge_scan_line_blocks(blocks, total_width, total_height, parallel)
+    } else {
+        merge_deep_blocks_per_pixel(blocks, total_width, total_height)
+    }
+}
+
+/// Merge blocks that span the full width of the image, sorted by y.
+/// Rows without a block contain no samples,
+/// and rows of blocks that overlap previous blocks are ignored.
+fn merge_scan_line_blocks(
+    blocks: Vec<(Vec2<usize>, DeepSamples)>,
+    total_width: usize,
+    total_height: usize,
+    parallel: bool,
+) -> Result<DeepSamples> {
+    let total_pixels = total_width * total_height;
+    let mut sample_offsets = Vec::with_capacity(total_pixels);
+
+    // the index of each used block, and the number of its samples inside the image
+    let mut ranges = Vec::with_capacity(blocks.len());
+    let mut total_samples: u32 = 0;
+
+    for (block_index, (position, block)) in blocks.iter().enumerate() {
+        let y = position.y();
+        let first_pixel = y * total_width;
+        if y >= total_height || first_pixel < sample_offsets.len() {
+            continue;
+        }
+
+        let pixels = block.height.min(total_height - y) * total_width;
+        let offsets = block
+            .sample_offsets
+            .get(..pixels)
+            .ok_or_else(|| Error::invalid("deep block sample count table"))?;
+
+        let block_samples = offsets.last().copied().unwrap_or(0);
+        let next_total = total_samples
+            .checked_add(block_samples)
+            .ok_or_else(|| Error::invalid("too many deep samples"))?;
+
+        sample_offsets.resize(first_pixel, total_samples);
+        sample_offsets.extend(offsets.iter().map(|&offset| total_samples + offset));
+
+        ranges.push((block_index, block_samples as usize));
+        total_samples = next_total;
+    }
+
+    sample_offsets.resize(total_pixels, total_samples);
+
+    let channel_count = ranges
+        .first()
+        .map_or(0, |&(block_index, _)| blocks[block_index].1.channels.len());
+
+    let concatenate =
+        |channel: usize| concatenate_channel(&blocks, &ranges, channel, total_samples as usize);
+
+    #[cfg(feature = "rayon")]
+    let channels: Vec<Result<DeepChannelData>> = if parallel && channel_count > 1 {
+        let mut channels: Vec<Option<Result<DeepChannelData>>> =
+            (0..channel_count).map(|_| None).collect();
+
+        rayon_core::scope(|scope| {
+            for (channel, result) in channels.iter_mut().enumerate() {
+                scope.spawn(move |_| *result = Some(concatenate(channel)));
+            }
+        });
+
+        channels
+            .into_iter()
+            .map(|channel| channel.expect("deep channel was not merged"))
+            .collect()
+    } else {
+        (0..channel_count).map(concatenate).collect()
+    };
+
+    #[cfg(not(feature = "rayon"))]
+    let channels: Vec<Result<DeepChannelData>> = {
+        let _ = parallel;
+        (0..channel_count).map(concatenate).collect()
+    };
+
+    Ok(DeepSamples {
+        sample_offsets,
+        channels: channels.into_iter().collect::<Result<Vec<_>>>()?,
+        width: total_width,
+        height: total_height,
+    })
+}
+
+/// Concatenate the first samples of one channel of the specified blocks.
+fn concatenate_channel(
+    blocks: &[(Vec2<usize>, DeepSamples)],
+    ranges: &[(usize, usize)],
+    channel: usize,
+    total_samples: usize,
+) -> Result<DeepChannelData> {
+    let source = |block_index: usize| {
+        blocks[block_index]
+            .1
+            .channels
+            .get(channel)
+            .ok_or_else(|| Error::invalid("deep blocks with different channels"))
+    };
+
+    let mut data = match ranges.first().map(|&(block_index, _)| source(block_index)) {
+        Some(first) => match first? {
+            DeepChannelData::F16(_) => DeepChannelData::F16(Vec::with_capacity(total_samples)),
+            DeepChannelData::F32(_) => DeepChannelData::F32(Vec::with_capacity(total_samples)),
+            DeepChannelData::U32(_) => DeepChannelData::U32(Vec::with_capacity(total_samples)),
+        },
+        None => return Err(Error::invalid("deep channel without blocks")),
+    };
+
+    let too_few_samples = || Error::invalid("deep block has too few samples");
+
+    for &(block_index, count) in ranges {
+        match (&mut data, source(block_index)?) {
+            (DeepChannelData::F16(data), DeepChannelData::F16(source)) => {
+                data.extend_from_slice(source.get(..count).ok_or_else(too_few_samples)?)
+            }
+            (DeepChannelData::F32(data), DeepChannelData::F32(source)) => {
+                data.extend_from_slice(source.get(..count).ok_or_else(too_few_samples)?)
+            }
+            (DeepChannelData::U32(data), DeepChannelData::U32(source)) => {
+                data.extend_from_slice(source.get(..count).ok_or_else(too_few_samples)?)
+            }
+            _ => return Err(Error::invalid("deep blocks with different sample types")),
+        }
+    }
+
+    Ok(data)
+}
+
+/// Merge multiple deep blocks into a single full-image [`DeepSamples`], pixel by pixel.
 ///
 /// # Algorithm
 ///
-/// Deep scanline files store data in blocks (typically 1-32 lines each).
+/// Deep files store data in blocks, which are scan lines or tiles at any position.
 /// This function combines them:
 ///
 /// 1. **Build offset table**: Create `combined_offsets[total_pixels + 1]` with leading 0
@@ -612,37 +1211,28 @@ fn build_deep_layer(header: &Header, samples: DeepSamples) -> Layer<AnyChannels<
 ///
 /// # Arguments
 ///
-/// * `blocks` - Vec of (y_offset, DeepSamples) pairs, one per block
+/// * `blocks` - Vec of (position, DeepSamples) pairs, one per block
 /// * `total_width` - Full image width
 /// * `total_height` - Full image height
-fn merge_deep_blocks(
-    blocks: Vec<(usize, DeepSamples)>,
+fn merge_deep_blocks_per_pixel(
+    blocks: Vec<(Vec2<usize>, DeepSamples)>,
     total_width: usize,
     total_height: usize,
 ) -> Result<DeepSamples> {
-    if blocks.is_empty() {
-        return Ok(DeepSamples::new(total_width, total_height));
-    }
-
-    if blocks.len() == 1 {
-        let (_, samples) = blocks.into_iter().next().unwrap();
-        return Ok(samples);
-    }
-
     // Multiple blocks - merge sample counts and channel data
     let total_pixels = total_width * total_height;
     let mut combined_offsets = vec![0u32; total_pixels + 1];
 
     // Calculate sample counts from all blocks
-    for (y, block) in &blocks {
+    for (position, block) in &blocks {
         for row in 0..block.height {
-            let image_y = y + row;
+            let image_y = position.y() + row;
             if image_y >= total_height {
                 break;
             }
 
-            for col in 0..block.width.min(total_width) {
-                let pixel_idx = image_y * total_width + col;
+            for col in 0..block.width.min(total_width.saturating_sub(position.x())) {
+                let pixel_idx = image_y * total_width + position.x() + col;
                 combined_offsets[pixel_idx + 1] = block.sample_count(col, row) as u32;
             }
         }
@@ -657,7 +1247,6 @@ fn merge_deep_blocks(
     let num_channels = blocks.first().map(|(_, b)| b.channels.len()).unwrap_or(0);
 
     // Merge channel data
-    use crate::image::deep::DeepChannelData;
     use crate::meta::attribute::SampleType;
 
     let mut combined_channels = Vec::with_capacity(num_channels);
@@ -721,16 +1310,14 @@ fn merge_deep_blocks(
 
 /// Merge F16 channel data.
 fn merge_channel_f16(
-    blocks: &[(usize, DeepSamples)],
+    blocks: &[(Vec2<usize>, DeepSamples)],
     ch_idx: usize,
     total_width: usize,
     total_height: usize,
     combined_offsets: &[u32],
     output: &mut [half::f16],
 ) {
-    use crate::image::deep::DeepChannelData;
-
-    for (y, block) in blocks {
+    for (position, block) in blocks {
         let src = match block.channels.get(ch_idx) {
             Some(DeepChannelData::F16(v)) => v,
             _ => continue,
@@ -738,7 +1325,7 @@ fn merge_channel_f16(
 
         copy_block_samples(
             block,
-            *y,
+            *position,
             src,
             total_width,
             total_height,
@@ -750,16 +1337,14 @@ fn merge_channel_f16(
 
 /// Merge F32 channel data.
 fn merge_channel_f32(
-    blocks: &[(usize, DeepSamples)],
+    blocks: &[(Vec2<usize>, DeepSamples)],
     ch_idx: usize,
     total_width: usize,
     total_height: usize,
     combined_offsets: &[u32],
     output: &mut [f32],
 ) {
-    use crate::image::deep::DeepChannelData;
-
-    for (y, block) in blocks {
+    for (position, block) in blocks {
         let src = match block.channels.get(ch_idx) {
             Some(DeepChannelData::F32(v)) => v,
             _ => continue,
@@ -767,7 +1352,7 @@ fn merge_channel_f32(
 
         copy_block_samples(
             block,
-            *y,
+            *position,
             src,
             total_width,
             total_height,
@@ -779,16 +1364,14 @@ fn merge_channel_f32(
 
 /// Merge U32 channel data.
 fn merge_channel_u32(
-    blocks: &[(usize, DeepSamples)],
+    blocks: &[(Vec2<usize>, DeepSamples)],
     ch_idx: usize,
     total_width: usize,
     total_height: usize,
     combined_offsets: &[u32],
     output: &mut [u32],
 ) {
-    use crate::image::deep::DeepChannelData;
-
-    for (y, block) in blocks {
+    for (position, block) in blocks {
         let src = match block.channels.get(ch_idx) {
             Some(DeepChannelData::U32(v)) => v,
             _ => continue,
@@ -796,7 +1379,7 @@ fn merge_channel_u32(
 
         copy_block_samples(
             block,
-            *y,
+            *position,
             src,
             total_width,
             total_height,
@@ -809,7 +1392,7 @@ fn merge_channel_u32(
 /// Copy samples from a block to the combined output.
 fn copy_block_samples<T: Copy>(
     block: &DeepSamples,
-    y_offset: usize,
+    position: Vec2<usize>,
     src: &[T],
     total_width: usize,
     total_height: usize,
@@ -817,13 +1400,13 @@ fn copy_block_samples<T: Copy>(
     output: &mut [T],
 ) {
     for row in 0..block.height {
-        let image_y = y_offset + row;
+        let image_y = position.y() + row;
         if image_y >= total_height {
             break;
         }
 
-        for col in 0..block.width.min(total_width) {
-            let pixel_idx = image_y * total_width + col;
+        for col in 0..block.width.min(total_width.saturating_sub(position.x())) {
+            let pixel_idx = image_y * total_width + position.x() + col;
             let block_pixel_idx = row * block.width + col;
             let count = block.sample_count(col, row);
 
@@ -848,6 +1431,26 @@ fn copy_block_samples<T: Copy>(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::image::deep::DeepChannelData;
+    use crate::math::Vec2;
+
+    /// The bits of each sample, such that `NaN` samples compare equal.
+    fn sample_bits(data: &DeepChannelData) -> Vec<u32> {
+        match data {
+            DeepChannelData::F16(samples) => samples.iter().map(|s| u32::from(s.to_bits())).collect(),
+            DeepChannelData::F32(samples) => samples.iter().map(|s| s.to_bits()).collect(),
+            DeepChannelData::U32(samples) => samples.clone(),
+        }
+    }
+
+    fn assert_same_samples(a: &DeepSamples, b: &DeepSamples) {
+        assert_eq!(a.sample_offsets, b.sample_offsets);
+        assert_eq!(a.channels.len(), b.channels.len());
+
+        for (a, b) in a.channels.iter().zip(&b.channels) {
+            assert_eq!(sample_bits(a), sample_bits(b));
+        }
+    }
 
     #[test]
     fn test_read_deep_first_layer() {
@@ -876,6 +1479,46 @@ mod test {
         println!("Total samples: {}", samples.total_samples());
     }
 
+    #[test]
+    fn scan_line_blocks_start_at_data_window_origin() {
+        use std::fs::File;
+
+        // the data window of this file starts at (131, 170)
+        let path = "tests/images/valid/openexr/v2/LowResLeftView/Balls.exr";
+        let reader =
+            Reader::read_from_buffered(BufReader::new(File::open(path).unwrap()), false).unwrap();
+        let origin = reader.headers()[0].own_attributes.layer_position;
+        assert_ne!(origin.y(), 0);
+
+        let blocks = SequentialDeepBlockDecompressor::new(reader.all_chunks(false).unwrap(), false)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        let block_samples: usize = blocks
+            .iter()
+            .map(|block| block.samples.total_samples())
+            .sum();
+        assert!(block_samples > 0);
+
+        let check = |samples: &DeepSamples| {
+            assert_eq!(samples.total_samples(), block_samples);
+
+            for block in &blocks {
+                let y = (block.y_coordinate - origin.y()) as usize;
+
+                for x in 0..samples.width {
+                    assert_eq!(samples.sample_count(x, y), block.samples.sample_count(x, 0));
+                }
+            }
+        };
+
+        let image = read_first_deep_layer_from_file(path).unwrap();
+        check(&image.layer_data.channel_data.list[0].sample_data);
+
+        let image = read_all_deep_layers_from_file(path).unwrap();
+        check(&image.layer_data[0].channel_data.list[0].sample_data);
+    }
+
     #[test]
     fn test_read_deep_builder_api() {
         let path = "tests/images/valid/openexr/v2/LowResLeftView/Ground.exr";
@@ -895,6 +1538,119 @@ mod test {
         println!("Ground.exr: {} samples", samples.total_samples());
     }
 
+    #[test]
+    fn read_deep_region() {
+        let path = "tests/images/valid/openexr/v2/LowResLeftView/Balls.exr";
+        if !std::path::Path::new(path).exists() {
+            eprintln!("Skipping: {} not found", path);
+            return;
+        }
+
+        let full = read_first_deep_layer_from_file(path).unwrap();
+        let data_window = full.layer_data.absolute_bounds();
+
+        let region = IntegerBounds::new(
+            data_window.position + Vec2(7, 13),
+            data_window.size / Vec2(2, 2),
+        );
+
+        let cropped = read_deep()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .region(region)
+            .from_file(path)
+            .unwrap();
+
+        assert_eq!(cropped.layer_data.absolute_bounds(), region);
+
+        let expected = full.layer_data.channel_data.list[0]
+            .sample_data
+            .crop((7, 13), (region.size.width(), region.size.height()));
+
+        assert_same_samples(&cropped.layer_data.channel_data.list[0].sample_data, &expected);
+    }
+
+    #[test]
+    fn read_deep_with_limits() {
+        let path = "tests/images/valid/openexr/v2/LowResLeftView/Balls.exr";
+        if !std::path::Path::new(path).exists() {
+            eprintln!("Skipping: {} not found", path);
+            return;
+        }
+
+        let full = read_first_deep_layer_from_file(path).unwrap();
+        let total_samples = full.layer_data.channel_data.list[0]
+            .sample_data
+            .total_samples();
+
+        let read_with = |limits: ReadLimits, parallel: bool| {
+            let reader = read_deep()
+                .all_channels()
+                .first_valid_layer()
+                .all_attributes()
+                .with_limits(limits);
+
+            if parallel { reader } else { reader.non_parallel() }.from_file(path)
+        };
+
+        for parallel in [false, true] {
+            let exact = ReadLimits {
+                max_sample_count: total_samples,
+                ..ReadLimits::UNLIMITED
+            };
+            assert!(read_with(exact, parallel).is_ok());
+
+            let samples = ReadLimits {
+                max_sample_count: total_samples - 1,
+                ..ReadLimits::UNLIMITED
+            };
+            assert!(matches!(read_with(samples, parallel), Err(Error::NotSupported(_))));
+
+            let bytes = ReadLimits {
+                max_decompressed_bytes: 1024,
+                ..ReadLimits::UNLIMITED
+            };
+            assert!(matches!(read_with(bytes, parallel), Err(Error::NotSupported(_))));
+        }
+    }
+
+    #[test]
+    fn read_selected_channels() {
+        let path = "tests/images/valid/openexr/v2/LowResLeftView/Balls.exr";
+        if !std::path::Path::new(path).exists() {
+            eprintln!("Skipping: {} not found", path);
+            return;
+        }
+
+        let full = read_first_deep_layer_from_file(path).unwrap();
+        let selected = read_deep()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .select_channels(vec!["Z", "A", "missing"])
+            .from_file(path)
+            .unwrap();
+
+        let full_channels = &full.layer_data.channel_data.list;
+        let full_samples = &full_channels[0].sample_data;
+        let selected_channels = &selected.layer_data.channel_data.list;
+        let selected_samples = &selected_channels[0].sample_data;
+
+        assert_eq!(selected_channels.len(), 2);
+        assert_eq!(selected_samples.channels.len(), 2);
+        assert_eq!(selected_samples.sample_offsets, full_samples.sample_offsets);
+
+        for (channel, data) in selected_channels.iter().zip(&selected_samples.channels) {
+            let full_index = full_channels
+                .iter()
+                .position(|full_channel| full_channel.name == channel.name)
+                .unwrap();
+
+            assert_eq!(sample_bits(data), sample_bits(&full_samples.channels[full_index]));
+        }
+    }
+
     #[test]
     fn test_read_via_main_api() {
         let path = "tests/images/valid/openexr/v2/LowResLeftView/Leaves.exr";
@@ -916,4 +1672,138 @@ mod test {
         let samples = &image.layer_data.channel_data.list[0].sample_data;
         println!("Leaves.exr via read(): {} samples", samples.total_samples());
     }
+
+    #[test]
+    fn read_specific_deep_layer() {
+        let path = "tests/images/valid/openexr/v2/LowResLeftView/Balls.exr";
+        if !std::path::Path::new(path).exists() {
+            eprintln!("Skipping: {} not found", path);
+            return;
+        }
+
+        let first = read_first_deep_layer_from_file(path).unwrap();
+        let specific = read_deep_layer_from_file(path, 0).unwrap();
+        assert_same_samples(
+            &specific.layer_data.channel_data.list[0].sample_data,
+            &first.layer_data.channel_data.list[0].sample_data,
+        );
+
+        assert!(read_deep_layer_from_file(path, 1000).is_err());
+    }
+
+    #[test]
+    fn read_deep_tiles_of_each_level() {
+        use crate::block::chunk::Chunk;
+        use crate::block::deep::compress_deep_tile_block;
+        use crate::block::writer::{write_chunks_with, ChunksWriter};
+        use crate::compression::Compression;
+        use crate::meta::attribute::{LineOrder, TileDescription};
+        use crate::math::RoundingMode;
+        use crate::meta::MetaData;
+        use std::io::Cursor;
+
+        let path = "tests/images/valid/openexr/v2/LowResLeftView/Balls.exr";
+        if !std::path::Path::new(path).exists() {
+            eprintln!("Skipping: {} not found", path);
+            return;
+        }
+
+        let full = read_first_deep_layer_from_file(path).unwrap();
+        let full = &full.layer_data.channel_data.list[0].sample_data;
+        let channels = MetaData::read_from_file(path, false).unwrap().headers[0]
+            .channels
+            .clone();
+
+        // each level contains the top left pixels of the original image
+        let level_samples = |size: Vec2<usize>| full.crop((0, 0), (size.width(), size.height()));
+
+        let tile_size = Vec2(64, 48);
+        let blocks = BlockDescription::Tiles(TileDescription {
+            tile_size,
+            level_mode: LevelMode::MipMap,
+            rounding_mode: RoundingMode::Up,
+        });
+
+        let mut header = Header::new(
+            Text::from("deep tiles"),
+            Vec2(full.width, full.height),
+            channels.list.clone(),
+        )
+        .with_encoding(Compression::ZIP1, blocks, LineOrder::Increasing);
+
+        header.deep = true;
+        header.deep_data_version = Some(1);
+        header.max_samples_per_pixel = Some(full.max_samples_per_pixel() as usize);
+
+        let mut bytes = Vec::new();
+        write_chunks_with(
+            Cursor::new(&mut bytes),
+            smallvec::smallvec![header.clone()],
+            true,
+            |_, writer| {
+                for (index, tile) in header.blocks_increasing_y_order().enumerate() {
+                    let level = level_size(&header, tile.location.level_index)?;
+                    let bounds = header.get_absolute_block_pixel_coordinates(tile.location)?;
+                    let position = bounds.position.to_usize("tile position")?;
+
+                    let samples = level_samples(level).crop(
+                        (position.x(), position.y()),
+                        (bounds.size.width(), bounds.size.height()),
+                    );
+
+                    let block = compress_deep_tile_block(
+                        &samples,
+                        Compression::ZIP1,
+                        &channels,
+                        tile.location,
+                    )?;
+
+                    writer.write_chunk(
+                        index,
+                        Chunk {
+                            layer_index: 0,
+                            compressed_block: CompressedBlock::DeepTile(block),
+                        },
+                    )?;
+                }
+
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        for parallel in [false, true] {
+            let read_level = |level: Vec2<usize>| {
+                let reader = read_deep()
+                    .all_channels()
+                    .first_valid_layer()
+                    .all_attributes()
+                    .resolution_level(level);
+
+                let reader = if parallel {
+                    reader
+                } else {
+                    reader.non_parallel()
+                };
+                reader.from_buffered(Cursor::new(&bytes))
+            };
+
+            let largest = read_level(Vec2(0, 0)).unwrap().layer_data;
+            assert_eq!(largest.encoding.blocks, Blocks::Tiles(tile_size));
+            assert_eq!(largest.size, Vec2(full.width, full.height));
+            assert_same_samples(&largest.channel_data.list[0].sample_data, full);
+
+            let level = Vec2(2, 2);
+            let smaller = read_level(level).unwrap().layer_data;
+            let size = level_size(&header, level).unwrap();
+            assert_eq!(smaller.size, size);
+            assert_same_samples(
+                &smaller.channel_data.list[0].sample_data,
+                &level_samples(size),
+            );
+
+            assert!(read_level(Vec2(1, 0)).is_err());
+            assert!(read_level(Vec2(20, 20)).is_err());
+        }
+    }
 }
@@ -1,13 +1,19 @@
 //! The last wrapper of image readers, finally containing the [`from_file(path)`] method.
 //! This completes the builder and reads a complete image.
 
+use crate::block::cancel::CancellationToken;
 use crate::block::chunk::TileCoordinates;
+use crate::block::limits::ReadLimits;
 use crate::block::reader::ChunksReader;
 use crate::block::{BlockIndex, UncompressedBlock};
-use crate::error::{Result, UnitResult};
+use crate::error::{Error, Result, UnitResult};
+use crate::image::read::region;
 use crate::image::*;
+use crate::math::Vec2;
+use crate::meta::attribute::{IntegerBounds, SampleType};
 use crate::meta::header::{Header, ImageAttributes};
 use crate::meta::MetaData;
+use std::collections::HashSet;
 use std::io::Seek;
 use std::io::{BufReader, Read};
 use std::path::Path;
@@ -21,6 +27,11 @@ pub struct ReadImage<OnProgress, ReadLayers> {
     read_layers: ReadLayers,
     pedantic: bool,
     parallel: bool,
+    prefetch: bool,
+    region: Option<IntegerBounds>,
+    cancellation: Option<CancellationToken>,
+    limits: ReadLimits,
+    salvage: Option<f32>,
 }
 
 impl<F, L> ReadImage<F, L>
@@ -37,6 +48,11 @@ where
             parallel: false,
             #[cfg(feature = "rayon")]
             parallel: true,
+            prefetch: false,
+            region: None,
+            cancellation: None,
+            limits: ReadLimits::UNLIMITED,
+            salvage: None,
         }
     }
 
@@ -68,6 +84,61 @@ where
         }
     }
 
+    /// Specify that files should be read on a dedicated thread,
+    /// reading the next chunks while the current chunks are decompressed.
+    /// This hides the latency of files on network file systems.
+    /// Only affects `from_file`. For other byte sources, use `from_chunks_prefetched`.
+    pub fn prefetch_chunks(self) -> Self {
+        Self {
+            prefetch: true,
+            ..self
+        }
+    }
+
+    /// Specify that only the pixels inside this rectangle should be read.
+    /// The rectangle uses absolute coordinates, like the data window of the layers.
+    /// Blocks outside of the rectangle are not decompressed, and each resulting layer
+    /// has the intersection of its data window and the rectangle as its data window.
+    /// Layers that do not overlap the rectangle contain no pixels.
+    /// Only the largest resolution level can be read, and subsampled channels are not supported.
+    pub fn region(self, region: IntegerBounds) -> Self {
+        Self {
+            region: Some(region),
+            ..self
+        }
+    }
+
+    /// Stop reading the image with `Error::Aborted` as soon as the token is cancelled.
+    /// The token is checked before each block is read from the file.
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        Self {
+            cancellation: Some(cancellation),
+            ..self
+        }
+    }
+
+    /// Refuse to read files whose meta data exceeds the limits, with `Error::NotSupported`.
+    /// The limits are checked before any pixels are allocated.
+    /// Use this when reading files from untrusted sources.
+    pub fn with_limits(self, limits: ReadLimits) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// Skip blocks that cannot be read or decompressed, instead of failing the whole image,
+    /// and fill their pixels with the specified value.
+    /// This recovers the intact parts of truncated or otherwise damaged files,
+    /// as long as the headers and the offset tables can still be read.
+    /// Use `exr::block::verify::verify_file` to find out which blocks are damaged.
+    ///
+    /// Blocks are then decompressed on the current thread, and are not prefetched.
+    /// Missing deep blocks are not filled, and pedantic reading still fails on invalid offset tables.
+    pub fn salvage_corrupt_blocks(self, fill: f32) -> Self {
+        Self {
+            salvage: Some(fill),
+            ..self
+        }
+    }
+
     /// Specify a function to be called regularly throughout the loading process.
     /// Replaces all previously specified progress functions in this reader.
     pub fn on_progress<OnProgress>(self, on_progress: OnProgress) -> ReadImage<OnProgress, L>
@@ -79,6 +150,11 @@ where
             read_layers: self.read_layers,
             pedantic: self.pedantic,
             parallel: self.parallel,
+            prefetch: self.prefetch,
+            region: self.region,
+            cancellation: self.cancellation,
+            limits: self.limits,
+            salvage: self.salvage,
         }
     }
 
@@ -90,7 +166,14 @@ where
     where
         for<'s> L: ReadLayers<'s, Layers = Layers>,
     {
-        self.from_unbuffered(std::fs::File::open(path)?)
+        let file = std::fs::File::open(path)?;
+
+        if self.prefetch && self.salvage.is_none() {
+            let chunks = crate::block::read(BufReader::new(file), self.pedantic)?;
+            self.from_chunks_prefetched(chunks)
+        } else {
+            self.from_unbuffered(file)
+        }
     }
 
     /// Buffer the reader and then read the exr image from it.
@@ -105,6 +188,21 @@ where
         self.from_buffered(BufReader::new(unbuffered))
     }
 
+    /// Read the exr image from a source that fetches byte ranges on request,
+    /// such as an object store or an asset cache.
+    /// The chunks of layers and resolution levels that are not read are not fetched.
+    /// See [`crate::block::source`] for how to implement a source.
+    #[must_use]
+    pub fn from_source<Layers>(
+        self,
+        source: impl crate::block::source::ChunkSource,
+    ) -> Result<Image<Layers>>
+    where
+        for<'s> L: ReadLayers<'s, Layers = Layers>,
+    {
+        self.from_buffered(crate::block::source::SourceRead::new(source)?)
+    }
+
     /// Read the exr image from a buffered reader.
     /// Use [`ReadImage::read_from_file`] instead, if you have a file path.
     /// Use [`ReadImage::read_from_unbuffered`] instead, if this is not an in-memory reader.
@@ -125,50 +223,206 @@ where
     // TODO Use Parallel<> Wrapper to only require sendable byte source where parallel decompression is required
     #[must_use]
     pub fn from_chunks<Layers>(
-        mut self,
+        self,
         chunks_reader: crate::block::reader::Reader<impl Read + Seek>,
     ) -> Result<Image<Layers>>
     where
         for<'s> L: ReadLayers<'s, Layers = Layers>,
+    {
+        self.read_chunks_with(chunks_reader, Ok)
+    }
+
+    /// Read the exr image from an initialized chunks reader,
+    /// reading the chunks on a dedicated thread while the previous chunks are decompressed.
+    /// See `prefetch_chunks`.
+    #[must_use]
+    pub fn from_chunks_prefetched<Layers>(
+        self,
+        chunks_reader: crate::block::reader::Reader<impl Read + Seek + Send + 'static>,
+    ) -> Result<Image<Layers>>
+    where
+        for<'s> L: ReadLayers<'s, Layers = Layers>,
+    {
+        self.read_chunks_with(chunks_reader, ChunksReader::prefetch)
+    }
+
+    /// Read the filtered chunks, after preparing them with the specified function.
+    fn read_chunks_with<Layers, R, C>(
+        mut self,
+        chunks_reader: crate::block::reader::Reader<R>,
+        prepare_chunks: impl FnOnce(crate::block::reader::FilteredChunksReader<R>) -> Result<C>,
+    ) -> Result<Image<Layers>>
+    where
+        for<'s> L: ReadLayers<'s, Layers = Layers>,
+        R: Read + Seek,
+        C: ChunksReader,
     {
         let Self {
             pedantic,
             parallel,
+            region,
             ref mut on_progress,
             ref mut read_layers,
+            ref cancellation,
+            limits,
+            salvage,
+            ..
         } = self;
 
-        let layers_reader = read_layers.create_layers_reader(chunks_reader.headers())?;
-        let mut image_collector =
-            ImageWithAttributesReader::new(chunks_reader.headers(), layers_reader)?;
+        limits.validate_meta_data(chunks_reader.meta_data())?;
+
+        // the layers are created with the cropped headers, and receive cropped blocks
+        let cropped_headers = match region {
+            None => None,
+            Some(region) => Some(
+                chunks_reader
+                    .headers()
+                    .iter()
+                    .map(|header| region::crop_header(header, region))
+                    .collect::<Result<Vec<Header>>>()?,
+            ),
+        };
+
+        let headers = cropped_headers
+            .as_deref()
+            .unwrap_or_else(|| chunks_reader.headers());
+
+        let layers_reader = read_layers.create_layers_reader(headers)?;
+        let mut image_collector = ImageWithAttributesReader::new(headers, layers_reader)?;
+
+        // remember the blocks to be read, so that the missing ones can be filled when salvaging
+        let mut expected_blocks = Vec::new();
+
+        // only the largest resolution level can be read with a region
+        let mut requires_smaller_levels = false;
+
+        let filtered_chunks = chunks_reader.filter_chunks(pedantic, |meta, tile, block| {
+            let mut is_required = image_collector.filter_block(meta, tile, block);
+
+            if let Some(region) = region {
+                requires_smaller_levels |= is_required && block.level != Vec2(0, 0);
+                is_required = is_required
+                    && region::block_intersects(&meta.headers[block.layer], region, block);
+            }
+
+            if is_required && salvage.is_some() {
+                expected_blocks.push(block);
+            }
+
+            is_required
+        })?;
+
+        if requires_smaller_levels {
+            return Err(region::smaller_level_error());
+        }
+
+        let block_reader = prepare_chunks(filtered_chunks)?
+            .on_progress(on_progress)
+            .cancel_with(cancellation.clone());
+
+        let mut insert_block =
+            |meta_data: &MetaData, block: UncompressedBlock| match &cropped_headers {
+                None => image_collector.read_block(&meta_data.headers, block),
+                Some(cropped_headers) => {
+                    let layer = block.index.layer;
+                    let block = region::crop_block(
+                        &meta_data.headers[layer],
+                        &cropped_headers[layer],
+                        block,
+                    )?;
+
+                    image_collector.read_block(cropped_headers, block)
+                }
+            };
+
+        if let Some(fill) = salvage {
+            let mut decompressor = block_reader.sequential_decompressor(pedantic);
+            let expected: HashSet<BlockIndex> = expected_blocks.iter().copied().collect();
+            let mut complete = HashSet::with_capacity(expected.len());
 
-        let block_reader = chunks_reader
-            .filter_chunks(pedantic, |meta, tile, block| {
-                image_collector.filter_block(meta, tile, block)
-            })?
-            .on_progress(on_progress);
+            while let Some(block) = decompressor.next() {
+                match block {
+                    Err(Error::Aborted) => return Err(Error::Aborted),
 
+                    // a damaged chunk may claim to contain any block, so accept each block only once
+                    Ok(block)
+                        if expected.contains(&block.index) && !complete.contains(&block.index) =>
+                    {
+                        let index = block.index;
+                        if insert_block(decompressor.meta_data(), block).is_ok() {
+                            complete.insert(index);
+                        }
+                    }
+
+                    // the pixels of damaged chunks are filled below
+                    _ => {}
+                }
+            }
+
+            let meta_data = decompressor.meta_data();
+            for &index in &expected_blocks {
+                let header = &meta_data.headers[index.layer];
+                if !complete.contains(&index) && !header.deep {
+                    insert_block(meta_data, filled_block(header, index, fill))?;
+                }
+            }
+        }
         // TODO propagate send requirement further upwards
-        if parallel {
+        else if parallel {
             #[cfg(not(feature = "rayon"))]
             return Err(crate::error::Error::unsupported(
                 "parallel decompression requires the rayon feature",
             ));
 
             #[cfg(feature = "rayon")]
-            block_reader.decompress_parallel(pedantic, |meta_data, block| {
-                image_collector.read_block(&meta_data.headers, block)
-            })?;
+            block_reader.decompress_parallel(pedantic, &mut insert_block)?;
         } else {
-            block_reader.decompress_sequential(pedantic, |meta_data, block| {
-                image_collector.read_block(&meta_data.headers, block)
-            })?;
+            block_reader.decompress_sequential(pedantic, &mut insert_block)?;
         }
 
         Ok(image_collector.into_image())
     }
 }
 
+/// A block with the same value in all samples, replacing a damaged block.
+fn filled_block(header: &Header, index: BlockIndex, fill: f32) -> UncompressedBlock {
+    UncompressedBlock::from_lines(&header.channels, index, |line| {
+        let written = match header.channels.list[line.location.channel].sample_type {
+            SampleType::F16 => line.write_samples(|_| f16::from_f32(fill)),
+            SampleType::F32 => line.write_samples(|_| fill),
+            SampleType::U32 => line.write_samples(|_| fill as u32),
+        };
+
+        written.expect("writing samples to a block of the correct size failed");
+    })
+}
+
+#[cfg(feature = "tokio")]
+impl<F, L> ReadImage<F, L>
+where
+    F: FnMut(f64) + Send + 'static,
+    L: Send + 'static,
+{
+    /// Read the exr image from an asynchronous byte source.
+    /// The bytes are read without blocking the current task,
+    /// and the image is then decompressed on the blocking thread pool of `tokio`.
+    /// The byte source must be positioned at the start of the file.
+    #[must_use]
+    pub async fn from_async<Layers>(
+        self,
+        read: impl tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin,
+    ) -> Result<Image<Layers>>
+    where
+        for<'s> L: ReadLayers<'s, Layers = Layers>,
+        Layers: Send + 'static,
+    {
+        use crate::block::asynchronous::{read_file_bytes, spawn_blocking};
+
+        let bytes = read_file_bytes(read, self.pedantic).await?;
+        spawn_blocking(move || self.from_buffered(std::io::Cursor::new(bytes))).await
+    }
+}
+
 /// Processes blocks from a file and collects them into a complete `Image`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImageWithAttributesReader<L> {
@@ -247,3 +501,216 @@ pub trait LayersReader {
     /// Deliver the final accumulated layers for the image
     fn into_layers(self) -> Self::Layers;
 }
+
+#[cfg(test)]
+mod test {
+    use crate::image::validate_results::ValidateResult;
+    use crate::prelude::*;
+
+    #[test]
+    fn read_prefetched_chunks() {
+        let path = "tests/images/valid/custom/compression_methods/f16/zip.exr";
+
+        let read_image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .all_layers()
+            .all_attributes();
+
+        let expected = read_image.clone().from_file(path).unwrap();
+        let prefetched = read_image
+            .clone()
+            .prefetch_chunks()
+            .from_file(path)
+            .unwrap();
+        expected.assert_equals_result(&prefetched);
+
+        let sequential = read_image
+            .non_parallel()
+            .prefetch_chunks()
+            .from_file(path)
+            .unwrap();
+
+        expected.assert_equals_result(&sequential);
+    }
+
+    #[test]
+    fn read_region() {
+        use std::io::Cursor;
+
+        // each value is the absolute position of its pixel
+        let samples: Vec<f32> = (0..5)
+            .flat_map(|y| (0..6).map(move |x| (10 + x + 100 * (20 + y)) as f32))
+            .collect();
+
+        for &blocks in &[Blocks::ScanLines, Blocks::Tiles(Vec2(2, 2))] {
+            let mut attributes = LayerAttributes::named("layer");
+            attributes.layer_position = Vec2(10, 20);
+
+            let encoding = Encoding {
+                compression: Compression::ZIP1,
+                blocks,
+                line_order: LineOrder::Increasing,
+            };
+
+            let layer = Layer::new(
+                (6, 5),
+                attributes,
+                encoding,
+                AnyChannels::sort(smallvec![AnyChannel::new(
+                    "Y",
+                    FlatSamples::F32(samples.clone())
+                )]),
+            );
+
+            let image = Image::from_layer(layer);
+            let mut bytes = Vec::new();
+            image.write().to_buffered(Cursor::new(&mut bytes)).unwrap();
+
+            let cropped = read()
+                .no_deep_data()
+                .largest_resolution_level()
+                .all_channels()
+                .first_valid_layer()
+                .all_attributes()
+                .region(IntegerBounds::new((12, 21), (3, 10)))
+                .from_buffered(Cursor::new(&bytes))
+                .unwrap();
+
+            assert_eq!(
+                cropped.layer_data.absolute_bounds(),
+                IntegerBounds::new((12, 21), (3, 4))
+            );
+
+            let expected = (21..25)
+                .flat_map(|y| (12..15).map(move |x| (x + 100 * y) as f32))
+                .collect();
+
+            assert_eq!(
+                cropped.layer_data.channel_data.list[0].sample_data,
+                FlatSamples::F32(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn read_region_of_mip_mapped_file() {
+        let path = "tests/images/valid/openexr/MultiResolution/ColorCodedLevels.exr";
+        let region = IntegerBounds::new((100, 70), (90, 40));
+
+        let full = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_file(path)
+            .unwrap();
+
+        let cropped = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .region(region)
+            .from_file(path)
+            .unwrap();
+
+        assert_eq!(cropped.layer_data.absolute_bounds(), region);
+
+        let full_width = full.layer_data.size.width();
+        let channels = full.layer_data.channel_data.list.iter();
+
+        for (full, cropped) in channels.zip(&cropped.layer_data.channel_data.list) {
+            let (full, cropped) = match (&full.sample_data, &cropped.sample_data) {
+                (FlatSamples::F16(full), FlatSamples::F16(cropped)) => (full, cropped),
+                _ => panic!("the file contains only f16 channels"),
+            };
+
+            let expected: Vec<u16> = (70..110)
+                .flat_map(|y| (100..190).map(move |x| full[y * full_width + x].to_bits()))
+                .collect();
+
+            let cropped: Vec<u16> = cropped.iter().map(|sample| sample.to_bits()).collect();
+            assert_eq!(cropped, expected);
+        }
+
+        let all_levels = read()
+            .no_deep_data()
+            .all_resolution_levels()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .region(region)
+            .from_file(path);
+
+        assert!(matches!(all_levels, Err(Error::NotSupported(_))));
+    }
+
+    #[test]
+    fn salvage_truncated_file() {
+        use crate::block::verify::verify;
+        use std::io::Cursor;
+
+        let samples: Vec<f32> = (0..8 * 8).map(|index| index as f32).collect();
+
+        let layer = Layer::new(
+            (8, 8),
+            LayerAttributes::named("layer"),
+            Encoding {
+                compression: Compression::ZIP1,
+                blocks: Blocks::ScanLines,
+                line_order: LineOrder::Increasing,
+            },
+            AnyChannels::sort(smallvec![AnyChannel::new(
+                "Y",
+                FlatSamples::F32(samples.clone())
+            )]),
+        );
+
+        let mut bytes = Vec::new();
+        Image::from_layer(layer)
+            .write()
+            .to_buffered(Cursor::new(&mut bytes))
+            .unwrap();
+
+        // cut the file in the middle of the sixth scan line
+        let verification = verify(Cursor::new(&bytes)).unwrap();
+        assert!(verification.is_valid());
+        bytes.truncate(verification.chunks[5].byte_offset as usize + 10);
+
+        let truncated = verify(Cursor::new(&bytes)).unwrap();
+        let damaged: Vec<usize> = truncated.errors().map(|chunk| chunk.chunk_index).collect();
+        assert_eq!(damaged, vec![5, 6, 7]);
+
+        let read_image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes();
+
+        assert!(read_image
+            .clone()
+            .from_buffered(Cursor::new(&bytes))
+            .is_err());
+
+        let salvaged = read_image
+            .salvage_corrupt_blocks(-1.0)
+            .from_buffered(Cursor::new(&bytes))
+            .unwrap();
+
+        let expected: Vec<f32> = samples[..5 * 8]
+            .iter()
+            .copied()
+            .chain(std::iter::repeat(-1.0).take(3 * 8))
+            .collect();
+
+        assert_eq!(
+            salvaged.layer_data.channel_data.list[0].sample_data,
+            FlatSamples::F32(expected)
+        );
+    }
+}
@@ -5,6 +5,7 @@
 //! - [`ReadAllLayers`]: Read all layers, fail if any layer is invalid.
 //! - [`ReadFirstValidLayer`]: Read only the first layer that matches requirements.
 //! - [`ReadAllValidLayers`]: Read all valid layers, silently skipping invalid ones.
+//! - [`ReadSpecificLayer`]: Read only the layer with a specific index, like a part of a multi-part file.
 //!
 //! # Example: Reading All Valid Layers
 //!
@@ -48,6 +49,17 @@ pub struct ReadFirstValidLayer<ReadChannels> {
     pub read_channels: ReadChannels,
 }
 
+/// Specify to read only the layer with the specified index, aborting if it is invalid.
+/// The blocks of all other layers are skipped without decompressing them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReadSpecificLayer<ReadChannels> {
+    /// The channel reading specification
+    pub read_channels: ReadChannels,
+
+    /// The index of the layer (the part of the file) to read
+    pub layer_index: usize,
+}
+
 /// Specify to read all layers that match the requirements, silently skipping invalid ones.
 ///
 /// Unlike [`ReadAllLayers`] which fails if any layer is invalid, this strategy
@@ -129,6 +141,19 @@ pub trait ReadChannels<'s> {
         }
     }
 
+    /// Read only the layer with the specified index, which is the index of the part in multi-part files.
+    /// Only the blocks of this layer are decompressed.
+    /// Aborts if the index is out of bounds or the layer does not meet the previously specified requirements.
+    fn specific_layer(self, layer_index: usize) -> ReadSpecificLayer<Self>
+    where
+        Self: Sized,
+    {
+        ReadSpecificLayer {
+            read_channels: self,
+            layer_index,
+        }
+    }
+
     /// Read all layers that match the channel requirements, silently skipping invalid ones.
     ///
     /// Unlike [`all_layers`](Self::all_layers) which fails if any layer is invalid,
@@ -229,14 +254,20 @@ pub trait ChannelsReader {
 
     /// Deliver the final accumulated channel collection for the image
     fn into_channels(self) -> Self::Channels;
+
+    /// The resolution of the channels, if it differs from the layer size,
+    /// because only a smaller resolution level is read.
+    fn resolution(&self) -> Option<Vec2<usize>> {
+        None
+    }
 }
 
-impl<C> LayerReader<C> {
+impl<C: ChannelsReader> LayerReader<C> {
     fn new(header: &Header, channels_reader: C) -> Result<Self> {
         Ok(LayerReader {
+            size: channels_reader.resolution().unwrap_or(header.layer_size),
             channels_reader,
             attributes: header.own_attributes.clone(),
-            size: header.layer_size,
             encoding: Encoding {
                 compression: header.compression,
                 line_order: header.line_order,
@@ -371,6 +402,28 @@ where
     }
 }
 
+impl<'s, C> ReadLayers<'s> for ReadSpecificLayer<C>
+where
+    C: ReadChannels<'s>,
+{
+    type Layers = Layer<<C::Reader as ChannelsReader>::Channels>;
+    type Reader = FirstValidLayerReader<C::Reader>;
+
+    fn create_layers_reader(&'s self, headers: &[Header]) -> Result<Self::Reader> {
+        let header = headers
+            .get(self.layer_index)
+            .ok_or(Error::invalid("layer index out of bounds"))?;
+
+        Ok(FirstValidLayerReader {
+            layer_reader: LayerReader::new(
+                header,
+                self.read_channels.create_channels_reader(header)?,
+            )?,
+            layer_index: self.layer_index,
+        })
+    }
+}
+
 impl<'s, C> ReadLayers<'s> for ReadAllValidLayers<C>
 where
     C: ReadChannels<'s>,
@@ -518,4 +571,57 @@ mod tests {
         assert_eq!(valid_indices.iter().position(|&idx| idx == 1), Some(1));
         assert_eq!(valid_indices.iter().position(|&idx| idx == 2), Some(2));
     }
+
+    /// Reading a specific layer returns only that layer of a multi-layer image.
+    #[test]
+    fn read_specific_layer() {
+        use crate::prelude::*;
+        use std::io::Cursor;
+
+        let layer = |name: &str, value: f32| {
+            let samples = FlatSamples::F32(vec![value; 4 * 3]);
+            Layer::new(
+                (4, 3),
+                LayerAttributes::named(name),
+                Encoding::default(),
+                AnyChannels::sort(smallvec![AnyChannel::new("Y", samples)]),
+            )
+        };
+
+        let image = Image::from_layers(
+            ImageAttributes::new(IntegerBounds::from_dimensions((4, 3))),
+            smallvec![layer("first", 1.0), layer("second", 2.0)],
+        );
+
+        let mut bytes = Vec::new();
+        image.write().to_buffered(Cursor::new(&mut bytes)).unwrap();
+
+        let second = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .specific_layer(1)
+            .all_attributes()
+            .from_buffered(Cursor::new(&bytes))
+            .unwrap();
+
+        assert_eq!(
+            second.layer_data.attributes.layer_name,
+            Some(Text::from("second"))
+        );
+        assert_eq!(
+            second.layer_data.channel_data.list[0].sample_data,
+            FlatSamples::F32(vec![2.0; 4 * 3])
+        );
+
+        let out_of_bounds = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .specific_layer(2)
+            .all_attributes()
+            .from_buffered(Cursor::new(&bytes));
+
+        assert!(out_of_bounds.is_err());
+    }
 }
@@ -353,6 +353,9 @@ pub struct SpecificLevelReader<SamplesReader> {
 
     /// The selected level index for filtering blocks.
     selected_level: Vec2<usize>,
+
+    /// The pixel resolution of the selected level.
+    resolution: Vec2<usize>,
 }
 
 /// Helper function to collect level information from a header.
@@ -438,6 +441,7 @@ where
         Ok(SpecificLevelReader {
             reader,
             selected_level,
+            resolution: level_info.resolution,
         })
     }
 }
@@ -461,6 +465,10 @@ impl<S: SamplesReader> SamplesReader for SpecificLevelReader<S> {
     fn into_samples(self) -> Self::Samples {
         self.reader.into_samples()
     }
+
+    fn resolution(&self) -> Option<Vec2<usize>> {
+        Some(self.resolution)
+    }
 }
 
 /// Processes pixel blocks from a file and accumulates them into multiple levels per channel.
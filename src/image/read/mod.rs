@@ -30,6 +30,10 @@
 //!     Note: Currently does not support deep data, and currently fails
 //!     if any layer in the image contains deep data.
 //!
+//! Where no file system is available, for example in the browser,
+//! `read_all_flat_layers_from_bytes` and `read_first_rgba_layer_from_bytes`
+//! decode a file that is already in memory.
+//!
 
 // The following three stages are internally used to read an image.
 // 1. `ReadImage` - The specification. Contains everything the user wants to tell us about loading an image.
@@ -47,6 +51,7 @@ pub mod deep;
 pub mod image;
 pub mod layers;
 pub mod levels;
+mod region;
 pub mod samples;
 pub mod specific_channels;
 
@@ -60,6 +65,7 @@ use crate::image::{
 };
 use crate::math::Vec2;
 use crate::prelude::PixelImage;
+use std::io::Cursor;
 use std::path::Path;
 
 /// All resolution levels, all channels, all layers.
@@ -184,6 +190,54 @@ where
         .from_file(path)
 }
 
+/// No deep data, no resolution levels, all channels, all layers, from the bytes of a file in memory.
+/// Does not require a file system, and can be used on `wasm32-unknown-unknown`.
+/// Uses parallel decompression and relaxed error handling, falling back to one thread where threads are not available.
+/// Inspect the source code of this function if you need customization.
+pub fn read_all_flat_layers_from_bytes(bytes: &[u8]) -> Result<FlatImage> {
+    read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .from_buffered(Cursor::new(bytes))
+}
+
+/// No deep data, no resolution levels, rgba channels, choosing the first layer with rgba channels,
+/// from the bytes of a file in memory. Does not require a file system, and can be used on `wasm32-unknown-unknown`.
+/// See `read_first_rgba_layer_from_file` for the meaning of the closures.
+// FIXME Set and Create should not need to be static
+pub fn read_first_rgba_layer_from_bytes<
+    R,
+    G,
+    B,
+    A,
+    Set: 'static,
+    Create: 'static,
+    Pixels: 'static,
+>(
+    bytes: &[u8],
+    create: Create,
+    set_pixel: Set,
+) -> Result<PixelImage<Pixels, RgbaChannels>>
+where
+    R: FromNativeSample,
+    G: FromNativeSample,
+    B: FromNativeSample,
+    A: FromNativeSample,
+    Create: Fn(Vec2<usize>, &RgbaChannels) -> Pixels,
+    Set: Fn(&mut Pixels, Vec2<usize>, (R, G, B, A)),
+{
+    read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .rgba_channels(create, set_pixel)
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(Cursor::new(bytes))
+}
+
 /// Utilizes the builder pattern to configure an image reader. This is the initial struct.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct ReadBuilder;
@@ -210,6 +264,8 @@ pub struct ReadBuilder;
 /// 1. `read_all_rgba_layers_from_file`
 /// 1. `read_all_flat_layers_from_file`
 /// 1. `read_all_data_from_file`
+/// 1. `read_all_flat_layers_from_bytes`
+/// 1. `read_first_rgba_layer_from_bytes`
 ///
 /// Note: Use `read().deep_data()` for reading deep EXR files.
 pub fn read() -> ReadBuilder {
@@ -0,0 +1,171 @@
+//! Read only a rectangle of the pixels of each layer, see `ReadImage::region`.
+//! Blocks outside of the rectangle are skipped without decompressing them,
+//! and the pixels of the intersecting blocks are cropped before they are inserted into the layer.
+
+use crate::block::{BlockIndex, UncompressedBlock};
+use crate::error::{Error, Result};
+use crate::math::Vec2;
+use crate::meta::attribute::IntegerBounds;
+use crate::meta::header::Header;
+
+/// The header of a layer that only contains the pixels inside the region.
+/// The region is in absolute coordinates, like the data window.
+/// If the region does not overlap the layer, the layer will have no pixels.
+pub(crate) fn crop_header(header: &Header, region: IntegerBounds) -> Result<Header> {
+    if header
+        .channels
+        .list
+        .iter()
+        .any(|channel| channel.sampling != Vec2(1, 1))
+    {
+        return Err(Error::unsupported(
+            "reading a region of subsampled channels",
+        ));
+    }
+
+    let bounds = header.data_window().intersection(region);
+
+    let mut cropped = header.clone();
+    cropped.layer_size = bounds.size;
+    cropped.own_attributes.layer_position = bounds.position;
+    Ok(cropped)
+}
+
+/// Whether any pixel of the block is inside the region.
+/// Blocks of smaller resolution levels are never inside the region,
+/// because only the largest level can be read with a region, see `smaller_level_error`.
+pub(crate) fn block_intersects(header: &Header, region: IntegerBounds, block: BlockIndex) -> bool {
+    if block.level != Vec2(0, 0) {
+        return false;
+    }
+
+    let block_bounds = IntegerBounds::new(block.pixel_position.to_i32(), block.pixel_size)
+        .with_origin(header.own_attributes.layer_position);
+
+    block_bounds.intersection(region).size.area() > 0
+}
+
+/// Copy the pixels of the block that are inside the cropped layer.
+/// The position of the returned block is relative to the cropped layer.
+pub(crate) fn crop_block(
+    header: &Header,
+    cropped: &Header,
+    block: UncompressedBlock,
+) -> Result<UncompressedBlock> {
+    if block.index.level != Vec2(0, 0) {
+        return Err(smaller_level_error());
+    }
+
+    // the cropped layer, relative to the original layer
+    let layer_offset = (cropped.own_attributes.layer_position
+        - header.own_attributes.layer_position)
+        .to_usize("cropped layer position")?;
+
+    let block_start = block.index.pixel_position;
+    let block_size = block.index.pixel_size;
+
+    let start = block_start.max(layer_offset);
+    let end = (block_start + block_size).min(layer_offset + cropped.layer_size);
+
+    if end.x() <= start.x() || end.y() <= start.y() {
+        return Err(Error::invalid("block outside of the region"));
+    }
+
+    let size = end - start;
+    let bytes_per_sample: Vec<usize> = header
+        .channels
+        .list
+        .iter()
+        .map(|channel| channel.sample_type.bytes_per_sample())
+        .collect();
+
+    let line_bytes = block_size.width() * bytes_per_sample.iter().sum::<usize>();
+    let mut data = Vec::with_capacity(size.area() * bytes_per_sample.iter().sum::<usize>());
+
+    for y in start.y()..end.y() {
+        let mut channel_start = (y - block_start.y()) * line_bytes;
+
+        // each line contains all samples of the first channel, then of the second channel, and so on
+        for &bytes in &bytes_per_sample {
+            let first = channel_start + (start.x() - block_start.x()) * bytes;
+            let samples = data_range(&block.data, first, size.width() * bytes)?;
+            data.extend_from_slice(samples);
+            channel_start += block_size.width() * bytes;
+        }
+    }
+
+    Ok(UncompressedBlock {
+        index: BlockIndex {
+            layer: block.index.layer,
+            pixel_position: start - layer_offset,
+            pixel_size: size,
+            level: block.index.level,
+        },
+        data,
+    })
+}
+
+/// The error for reading a region of a resolution level other than the largest.
+pub(crate) fn smaller_level_error() -> Error {
+    Error::unsupported("reading a region of resolution levels other than the largest")
+}
+
+fn data_range(data: &[u8], start: usize, len: usize) -> Result<&[u8]> {
+    data.get(start..start + len)
+        .ok_or_else(|| Error::invalid("block data size"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::attribute::{ChannelDescription, SampleType};
+
+    #[test]
+    fn crop_block_lines_of_each_channel() {
+        let channels = smallvec![
+            ChannelDescription::named("A", SampleType::F16),
+            ChannelDescription::named("B", SampleType::F32),
+        ];
+
+        let header = Header::new("layer".into(), (4, 2), channels).with_position(Vec2(10, 20));
+        let cropped = crop_header(&header, IntegerBounds::new((11, 21), (2, 5))).unwrap();
+        assert_eq!(cropped.layer_size, Vec2(2, 1));
+        assert_eq!(cropped.own_attributes.layer_position, Vec2(11, 21));
+
+        // each byte is the index of the pixel in its line, plus 10 for the second line
+        let line = |offset: u8| -> Vec<u8> {
+            let a = (0..4).flat_map(|x| vec![x + offset; 2]);
+            let b = (0..4).flat_map(|x| vec![x + offset; 4]);
+            a.chain(b).collect()
+        };
+
+        let block = UncompressedBlock {
+            index: BlockIndex {
+                layer: 0,
+                pixel_position: Vec2(0, 0),
+                pixel_size: Vec2(4, 2),
+                level: Vec2(0, 0),
+            },
+            data: [line(0), line(10)].concat(),
+        };
+
+        assert!(block_intersects(
+            &header,
+            IntegerBounds::new((11, 21), (2, 5)),
+            block.index
+        ));
+        assert!(!block_intersects(
+            &header,
+            IntegerBounds::new((0, 0), (10, 10)),
+            block.index
+        ));
+
+        let block = crop_block(&header, &cropped, block).unwrap();
+        assert_eq!(block.index.pixel_position, Vec2(0, 0));
+        assert_eq!(block.index.pixel_size, Vec2(2, 1));
+        assert_eq!(
+            block.data,
+            vec![11, 11, 12, 12, 11, 11, 11, 11, 12, 12, 12, 12]
+        );
+    }
+}
@@ -119,7 +119,8 @@ impl ReadSamples for ReadFlatSamples {
         header: &Header,
         channel: &ChannelDescription,
     ) -> Result<Self::Reader> {
-        self.create_samples_level_reader(header, channel, Vec2(0, 0), header.layer_size)
+        let resolution = channel.subsampled_resolution(header.layer_size);
+        self.create_samples_level_reader(header, channel, Vec2(0, 0), resolution)
     }
 }
 
@@ -135,7 +136,7 @@ impl ReadSamplesLevel for ReadFlatSamples {
     ) -> Result<Self::Reader> {
         Ok(FlatSamplesReader {
             level,
-            resolution, // TODO sampling
+            resolution,
             samples: match channel.sample_type {
                 SampleType::F16 => FlatSamples::F16(vec![f16::ZERO; resolution.area()]),
                 SampleType::F32 => FlatSamples::F32(vec![0.0; resolution.area()]),
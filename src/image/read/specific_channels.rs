@@ -2,6 +2,7 @@
 //! This is not a zero-cost abstraction.
 
 use crate::block::chunk::TileCoordinates;
+use crate::block::pool::recycle_buffer;
 use crate::block::samples::*;
 use crate::block::UncompressedBlock;
 use crate::error::*;
@@ -172,6 +173,10 @@ ReadChannels<'s> for CollectPixels<InnerChannels, Pixel, PixelStorage, CreatePix
     fn create_channels_reader(&'s self, header: &Header) -> Result<Self::Reader> {
         if header.deep { return Err(Error::invalid("`SpecificChannels` does not support deep data yet")) }
 
+        if header.channels.list.iter().any(|channel| channel.sampling != Vec2(1, 1)) {
+            return Err(Error::unsupported("`SpecificChannels` with subsampled channels, use `all_channels` instead"));
+        }
+
         let pixel_reader = self.read_channels.create_recursive_reader(&header.channels)?;
         let channel_descriptions = pixel_reader.get_descriptions().into_non_recursive();// TODO not call this twice
 
@@ -241,6 +246,7 @@ where
             }
         }
 
+        recycle_buffer(block.data);
         Ok(())
     }
 
@@ -0,0 +1,549 @@
+//! Resize images with a separable resampling filter.
+//! Used to generate mip map levels, thumbnails, and scaled copies of layers.
+//!
+//! All samples are filtered as `f32`, and stored with their original sample type.
+//! As exr pixels are usually stored with premultiplied alpha, all channels are filtered independently.
+//! Filters with negative lobes may produce alpha values outside of `[0, 1]`
+//! when scaling layers, which are clamped.
+//! Integer samples, such as object ids, are never blended, but use the nearest sample.
+//!
+//! Scaling a layer also scales its data window, which is positioned on the scaled display window.
+//! The scaled data window covers every pixel that is partially covered by the original data window.
+//! Pixels beyond the data window are extended from its edge.
+
+use crate::error::{Error, Result};
+use crate::image::premultiply::alpha_channel_index;
+use crate::image::{AnyChannel, AnyChannels, FlatSamples, Image, Layer, Layers};
+use crate::math::Vec2;
+use crate::meta::attribute::IntegerBounds;
+use half::f16;
+
+/// The filter used to compute the pixels of a resized image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResizeFilter {
+    /// Averages all pixels covered by the smaller pixel.
+    /// Fast, and equivalent to the levels generated by `exrmaketiled`.
+    Box,
+
+    /// Weights pixels by their distance. Smoother than `Box`.
+    Triangle,
+
+    /// A windowed sinc filter with three lobes.
+    /// Keeps the image sharp, but may produce ringing at hard edges.
+    Lanczos3,
+
+    /// The cubic filter by Mitchell and Netravali, with `B = C = 1/3`.
+    /// A compromise between sharpness and ringing, suited to enlarging images.
+    Mitchell,
+
+    /// A gaussian bell with a standard deviation of half a pixel, cut off at two pixels.
+    /// Smoother than `Triangle`, without ringing, which reduces aliasing in small mip map levels.
+    Gaussian,
+}
+
+impl Default for ResizeFilter {
+    fn default() -> Self {
+        ResizeFilter::Box
+    }
+}
+
+impl ResizeFilter {
+    /// The distance from the center, in pixels of the smaller image, at which the filter weight becomes zero.
+    pub fn radius(self) -> f32 {
+        match self {
+            ResizeFilter::Box => 0.5,
+            ResizeFilter::Triangle => 1.0,
+            ResizeFilter::Lanczos3 => 3.0,
+            ResizeFilter::Mitchell => 2.0,
+            ResizeFilter::Gaussian => 2.0,
+        }
+    }
+
+    /// The weight of a sample at the specified distance from the center.
+    pub fn weight(self, distance: f32) -> f32 {
+        let distance = distance.abs();
+
+        match self {
+            ResizeFilter::Box => {
+                if distance <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+
+            ResizeFilter::Triangle => (1.0 - distance).max(0.0),
+
+            ResizeFilter::Lanczos3 => {
+                if distance >= 3.0 {
+                    0.0
+                } else {
+                    sinc(distance) * sinc(distance / 3.0)
+                }
+            }
+
+            ResizeFilter::Mitchell => {
+                let (x, x2) = (distance, distance * distance);
+                let x3 = x2 * x;
+
+                if x < 1.0 {
+                    (7.0 * x3 - 12.0 * x2 + 16.0 / 3.0) / 6.0
+                } else if x < 2.0 {
+                    (-7.0 / 3.0 * x3 + 12.0 * x2 - 20.0 * x + 32.0 / 3.0) / 6.0
+                } else {
+                    0.0
+                }
+            }
+
+            ResizeFilter::Gaussian => {
+                if distance >= 2.0 {
+                    0.0
+                } else {
+                    // standard deviation of 0.5
+                    (-2.0 * distance * distance).exp()
+                }
+            }
+        }
+    }
+
+    /// Whether some weights of this filter are negative,
+    /// such that the results may be outside of the range of the samples.
+    pub fn has_negative_lobes(self) -> bool {
+        matches!(self, ResizeFilter::Lanczos3 | ResizeFilter::Mitchell)
+    }
+
+    /// Parse a filter name, ignoring case: `box`, `triangle`, `lanczos3`, `mitchell`, or `gaussian`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "box" => Some(ResizeFilter::Box),
+            "triangle" | "bilinear" => Some(ResizeFilter::Triangle),
+            "lanczos3" | "lanczos" => Some(ResizeFilter::Lanczos3),
+            "mitchell" => Some(ResizeFilter::Mitchell),
+            "gaussian" | "gauss" => Some(ResizeFilter::Gaussian),
+            _ => None,
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * std::f32::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// Maps the pixels of a resized line to the pixels of the source line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LineMapping {
+    source_length: usize,
+    target_length: usize,
+
+    /// The number of source pixels per target pixel.
+    scale: f32,
+
+    /// The position of the first target pixel, in source pixels.
+    offset: f32,
+}
+
+impl LineMapping {
+    /// Stretch the source line to exactly cover the target line.
+    fn fit(source_length: usize, target_length: usize) -> Self {
+        LineMapping {
+            source_length,
+            target_length,
+            scale: source_length as f32 / target_length as f32,
+            offset: 0.0,
+        }
+    }
+
+    /// The center of the target pixel, as an index into the source line.
+    fn center(self, target: usize) -> f32 {
+        (target as f32 + 0.5) * self.scale - 0.5 + self.offset
+    }
+
+    /// The source pixel closest to the center of each target pixel.
+    fn nearest(self) -> Vec<usize> {
+        (0..self.target_length)
+            .map(|target| {
+                let nearest = self.center(target).round().max(0.0) as usize;
+                nearest.min(self.source_length - 1)
+            })
+            .collect()
+    }
+
+    /// The source pixels and their normalized weights, for each target pixel.
+    fn weights(self, filter: ResizeFilter) -> Vec<Vec<(usize, f32)>> {
+        let filter_scale = self.scale.max(1.0);
+        let radius = filter.radius() * filter_scale;
+        let last_source = self.source_length as i64 - 1;
+
+        (0..self.target_length)
+            .map(|target| {
+                let center = self.center(target);
+                let first = (center - radius).ceil() as i64;
+                let last = (center + radius).floor() as i64;
+
+                let mut weights: Vec<(usize, f32)> = Vec::new();
+                for source in first..=last {
+                    let weight = filter.weight((source as f32 - center) / filter_scale);
+                    if weight == 0.0 {
+                        continue;
+                    }
+
+                    // extend the edge pixels beyond the image
+                    let source = source.max(0).min(last_source) as usize;
+                    match weights.iter_mut().find(|(index, _)| *index == source) {
+                        Some((_, existing)) => *existing += weight,
+                        None => weights.push((source, weight)),
+                    }
+                }
+
+                let sum: f32 = weights.iter().map(|&(_, weight)| weight).sum();
+                if sum.abs() < f32::EPSILON {
+                    let nearest = (center.round().max(0.0) as usize).min(self.source_length - 1);
+                    vec![(nearest, 1.0)]
+                } else {
+                    weights
+                        .into_iter()
+                        .map(|(index, weight)| (index, weight / sum))
+                        .collect()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Resize a grid of samples, stored in rows from top to bottom.
+/// Resizes horizontally first, then vertically.
+pub fn resize_f32(
+    samples: &[f32],
+    size: Vec2<usize>,
+    new_size: Vec2<usize>,
+    filter: ResizeFilter,
+) -> Vec<f32> {
+    if size == new_size {
+        assert_eq!(
+            samples.len(),
+            size.area(),
+            "sample count does not match size"
+        );
+        return samples.to_vec();
+    }
+
+    let mapping = Vec2(
+        LineMapping::fit(size.width(), new_size.width()),
+        LineMapping::fit(size.height(), new_size.height()),
+    );
+
+    resample_f32(samples, mapping, filter)
+}
+
+/// Resize a grid of samples by picking the nearest sample, without blending any values.
+/// Used for integer samples, which often contain ids that must not be averaged.
+pub fn resize_nearest<T: Copy>(samples: &[T], size: Vec2<usize>, new_size: Vec2<usize>) -> Vec<T> {
+    let mapping = Vec2(
+        LineMapping::fit(size.width(), new_size.width()),
+        LineMapping::fit(size.height(), new_size.height()),
+    );
+
+    resample_nearest(samples, mapping)
+}
+
+fn resample_f32(samples: &[f32], mapping: Vec2<LineMapping>, filter: ResizeFilter) -> Vec<f32> {
+    let size = Vec2(mapping.x().source_length, mapping.y().source_length);
+    let new_width = mapping.x().target_length;
+    assert_eq!(
+        samples.len(),
+        size.area(),
+        "sample count does not match size"
+    );
+
+    let horizontal = mapping.x().weights(filter);
+    let mut rows = Vec::with_capacity(new_width * size.height());
+    for row in samples.chunks_exact(size.width()) {
+        rows.extend(horizontal.iter().map(|weights| {
+            weights
+                .iter()
+                .map(|&(x, weight)| row[x] * weight)
+                .sum::<f32>()
+        }));
+    }
+
+    let vertical = mapping.y().weights(filter);
+    let mut result = Vec::with_capacity(new_width * vertical.len());
+    for weights in &vertical {
+        result.extend((0..new_width).map(|x| {
+            weights
+                .iter()
+                .map(|&(y, weight)| rows[y * new_width + x] * weight)
+                .sum::<f32>()
+        }));
+    }
+
+    result
+}
+
+fn resample_nearest<T: Copy>(samples: &[T], mapping: Vec2<LineMapping>) -> Vec<T> {
+    let width = mapping.x().source_length;
+    assert_eq!(
+        samples.len(),
+        width * mapping.y().source_length,
+        "sample count does not match size"
+    );
+
+    let columns = mapping.x().nearest();
+    let rows = mapping.y().nearest();
+
+    let mut result = Vec::with_capacity(columns.len() * rows.len());
+    for source_y in rows {
+        let row = &samples[source_y * width..(source_y + 1) * width];
+        result.extend(columns.iter().map(|&source_x| row[source_x]));
+    }
+
+    result
+}
+
+/// Scale a rectangle of pixels, such that the result covers every pixel
+/// that is partially covered by the scaled rectangle. Keeps at least one pixel.
+pub fn scaled_bounds(bounds: IntegerBounds, scale: Vec2<f32>) -> IntegerBounds {
+    let scale_axis = |position: i32, size: usize, scale: f32| {
+        let scale = f64::from(scale);
+        let start = (f64::from(position) * scale).floor();
+        let end = ((f64::from(position) + size as f64) * scale).ceil();
+        (start as i32, ((end - start) as usize).max(1))
+    };
+
+    let (x, width) = scale_axis(bounds.position.x(), bounds.size.width(), scale.x());
+    let (y, height) = scale_axis(bounds.position.y(), bounds.size.height(), scale.y());
+    IntegerBounds::new(Vec2(x, y), Vec2(width, height))
+}
+
+/// Maps the pixels of the scaled rectangle to the pixels of the original rectangle on one axis.
+fn scaled_line_mapping(source: (i32, usize), target: (i32, usize), scale: f32) -> LineMapping {
+    LineMapping {
+        source_length: source.1,
+        target_length: target.1,
+        scale: 1.0 / scale,
+        offset: target.0 as f32 / scale - source.0 as f32,
+    }
+}
+
+impl FlatSamples {
+    /// Resize the samples of a channel with the specified size. Keeps the sample type.
+    /// Floating point samples are filtered, while `u32` samples use the nearest sample.
+    pub fn resized(&self, size: Vec2<usize>, new_size: Vec2<usize>, filter: ResizeFilter) -> Self {
+        let mapping = Vec2(
+            LineMapping::fit(size.width(), new_size.width()),
+            LineMapping::fit(size.height(), new_size.height()),
+        );
+
+        self.resampled(mapping, filter)
+    }
+
+    fn resampled(&self, mapping: Vec2<LineMapping>, filter: ResizeFilter) -> Self {
+        match self {
+            FlatSamples::F16(samples) => {
+                let samples: Vec<f32> = samples.iter().map(|sample| sample.to_f32()).collect();
+                let resized = resample_f32(&samples, mapping, filter);
+                FlatSamples::F16(resized.into_iter().map(f16::from_f32).collect())
+            }
+
+            FlatSamples::F32(samples) => FlatSamples::F32(resample_f32(samples, mapping, filter)),
+            FlatSamples::U32(samples) => FlatSamples::U32(resample_nearest(samples, mapping)),
+        }
+    }
+}
+
+impl Layer<AnyChannels<FlatSamples>> {
+    /// Scale the layer and its data window by the specified factor per axis.
+    /// Fails if the factor is not positive, or if a channel is subsampled.
+    pub fn scaled(&self, scale: Vec2<f32>, filter: ResizeFilter) -> Result<Self> {
+        let is_valid = |scale: f32| scale.is_finite() && scale > 0.0;
+        if !is_valid(scale.x()) || !is_valid(scale.y()) {
+            return Err(Error::invalid("resize scale"));
+        }
+
+        if self
+            .channel_data
+            .list
+            .iter()
+            .any(|channel| channel.sampling != Vec2(1, 1))
+        {
+            return Err(Error::unsupported("resizing subsampled channels"));
+        }
+
+        let bounds = IntegerBounds::new(self.attributes.layer_position, self.size);
+        let new_bounds = scaled_bounds(bounds, scale);
+
+        let mapping = Vec2(
+            scaled_line_mapping(
+                (bounds.position.x(), bounds.size.width()),
+                (new_bounds.position.x(), new_bounds.size.width()),
+                scale.x(),
+            ),
+            scaled_line_mapping(
+                (bounds.position.y(), bounds.size.height()),
+                (new_bounds.position.y(), new_bounds.size.height()),
+                scale.y(),
+            ),
+        );
+
+        let list = (0..self.channel_data.list.len())
+            .map(|index| {
+                let channel = &self.channel_data.list[index];
+                let mut sample_data = channel.sample_data.resampled(mapping, filter);
+
+                let is_alpha = alpha_channel_index(&self.channel_data, index) == Some(index);
+                if is_alpha && filter.has_negative_lobes() {
+                    clamp_alpha(&mut sample_data);
+                }
+
+                AnyChannel {
+                    name: channel.name.clone(),
+                    sample_data,
+                    quantize_linearly: channel.quantize_linearly,
+                    sampling: channel.sampling,
+                }
+            })
+            .collect();
+
+        let mut layer = Layer {
+            channel_data: AnyChannels { list },
+            attributes: self.attributes.clone(),
+            size: new_bounds.size,
+            encoding: self.encoding,
+        };
+
+        layer.attributes.layer_position = new_bounds.position;
+        Ok(layer)
+    }
+}
+
+impl Image<Layers<AnyChannels<FlatSamples>>> {
+    /// Scale the display window and all layers by the specified factor per axis.
+    /// See `Layer::scaled`.
+    pub fn scaled(&self, scale: Vec2<f32>, filter: ResizeFilter) -> Result<Self> {
+        let layer_data = self
+            .layer_data
+            .iter()
+            .map(|layer| layer.scaled(scale, filter))
+            .collect::<Result<Layers<_>>>()?;
+
+        let mut attributes = self.attributes.clone();
+        attributes.display_window = scaled_bounds(attributes.display_window, scale);
+
+        Ok(Image {
+            attributes,
+            layer_data,
+        })
+    }
+}
+
+fn clamp_alpha(samples: &mut FlatSamples) {
+    match samples {
+        FlatSamples::F16(samples) => {
+            for sample in samples {
+                *sample = f16::from_f32(sample.to_f32().max(0.0).min(1.0));
+            }
+        }
+
+        FlatSamples::F32(samples) => {
+            for sample in samples {
+                *sample = sample.max(0.0).min(1.0);
+            }
+        }
+
+        FlatSamples::U32(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Encoding;
+    use crate::meta::header::LayerAttributes;
+    use smallvec::smallvec;
+
+    #[test]
+    fn box_filter_averages_pixel_pairs() {
+        let samples = [1.0, 3.0, 5.0, 7.0, 1.0, 3.0, 5.0, 7.0];
+        let resized = resize_f32(&samples, Vec2(4, 2), Vec2(2, 1), ResizeFilter::Box);
+        assert_eq!(resized, vec![2.0, 6.0]);
+    }
+
+    #[test]
+    fn filters_preserve_constant_images() {
+        let samples = vec![0.25; 7 * 5];
+
+        for &filter in &[
+            ResizeFilter::Box,
+            ResizeFilter::Triangle,
+            ResizeFilter::Lanczos3,
+            ResizeFilter::Mitchell,
+            ResizeFilter::Gaussian,
+        ] {
+            for &new_size in &[Vec2(3, 2), Vec2(15, 11)] {
+                let resized = resize_f32(&samples, Vec2(7, 5), new_size, filter);
+                assert_eq!(resized.len(), new_size.area());
+                assert!(resized.iter().all(|&value| (value - 0.25).abs() < 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_keeps_integer_values() {
+        let samples = [1_u32, 2, 3, 4];
+        assert_eq!(resize_nearest(&samples, Vec2(4, 1), Vec2(2, 1)), vec![2, 4]);
+    }
+
+    #[test]
+    fn scale_data_window() {
+        let bounds = IntegerBounds::new(Vec2(3, -4), Vec2(5, 8));
+        assert_eq!(
+            scaled_bounds(bounds, Vec2(0.5, 0.5)),
+            IntegerBounds::new(Vec2(1, -2), Vec2(3, 4))
+        );
+
+        assert_eq!(
+            scaled_bounds(bounds, Vec2(2.0, 0.01)),
+            IntegerBounds::new(Vec2(6, -1), Vec2(10, 2))
+        );
+    }
+
+    #[test]
+    fn scale_layer_with_alpha() {
+        let channels = AnyChannels::sort(smallvec![
+            AnyChannel::new("A", FlatSamples::F32(vec![0.0, 1.0, 1.0, 0.0])),
+            AnyChannel::new(
+                "R",
+                FlatSamples::F16(vec![f16::ZERO, f16::ONE, f16::ONE, f16::ZERO])
+            ),
+            AnyChannel::new("id", FlatSamples::U32(vec![1, 2, 3, 4])),
+        ]);
+
+        let mut layer = Layer::new(
+            Vec2(4, 1),
+            LayerAttributes::default(),
+            Encoding::default(),
+            channels,
+        );
+
+        layer.attributes.layer_position = Vec2(2, 0);
+        let scaled = layer
+            .scaled(Vec2(2.0, 1.0), ResizeFilter::Lanczos3)
+            .unwrap();
+
+        assert_eq!(scaled.size, Vec2(8, 1));
+        assert_eq!(scaled.attributes.layer_position, Vec2(4, 0));
+
+        let alpha = scaled.channel_data.list[0].sample_data.to_f32_vec();
+        assert!(alpha.iter().all(|&alpha| (0.0..=1.0).contains(&alpha)));
+
+        let ids = &scaled.channel_data.list[2].sample_data;
+        assert_eq!(ids, &FlatSamples::U32(vec![1, 1, 2, 2, 3, 3, 4, 4]));
+
+        assert!(layer.scaled(Vec2(0.0, 1.0), ResizeFilter::Box).is_err());
+    }
+}
@@ -13,9 +13,9 @@
 //!         ├── Build Header (deep=true, BlockType=DeepScanLine)
 //!         │
 //!         ├── For each scanline block:
-//!         │    ├── extract_single_line() → line DeepSamples
-//!         │    ├── compress_deep_scanline_block()
-//!         │    └── write CompressedBlock
+//!         │    ├── extract_block_samples() → block DeepSamples
+//!         │    ├── compress_deep_scanline_block_with_buffers() (on the thread pool)
+//!         │    └── write CompressedBlock (in increasing y order)
 //!         │
 //!         └── Write offset table
 //! ```
@@ -53,6 +53,16 @@
 //! # Ok::<(), exr::error::Error>(())
 //! ```
 //!
+//! Write all deep layers of a file, with another compression method:
+//! ```no_run
+//! use exr::image::read::deep::read_all_deep_layers_from_file;
+//! use exr::compression::Compression;
+//!
+//! let image = read_all_deep_layers_from_file("input.exr")?;
+//! image.write_deep().with_compression(Compression::ZIP1).to_file("output.exr")?;
+//! # Ok::<(), exr::error::Error>(())
+//! ```
+//!
 //! Low-level writing with custom samples:
 //! ```no_run
 //! use exr::image::deep::DeepSamples;
@@ -68,11 +78,13 @@
 //!
 //! # Compression Support
 //!
-//! All standard compressions work with deep data:
+//! Deep data supports the same compressions as in OpenEXR:
 //! - `Uncompressed` - Fastest, largest files
-//! - `ZIP1` - Good balance (recommended)
+//! - `ZIP1` - Good balance (recommended), called `ZIPS` in OpenEXR
+//! - `ZIP16` - Like `ZIP1`, but compresses 16 lines at once, called `ZIP` in OpenEXR
 //! - `RLE` - Fast, moderate compression
-//! - `ZIPS` - Best compression, slower
+//!
+//! Other compressions are rejected with an error before anything is written.
 //!
 //! # See Also
 //!
@@ -83,17 +95,21 @@
 use std::io::{BufWriter, Seek, Write};
 use std::path::Path;
 
+use crate::block::cancel::CancellationToken;
 use crate::block::chunk::{Chunk, CompressedBlock};
-use crate::block::deep::compress_deep_scanline_block;
-use crate::block::writer::{ChunkWriter, ChunksWriter};
+use crate::block::deep::{compress_deep_scanline_block_with_buffers, DeepBlockBuffers};
+use crate::block::writer::ChunksWriter;
 use crate::compression::Compression;
-use crate::error::UnitResult;
+use crate::error::{Error, UnitResult};
 use crate::image::deep::DeepSamples;
-use crate::image::{AnyChannels, Image, ImageAttributes, Layer, LayerAttributes};
+use crate::image::{
+    ignore_progress, AnyChannels, Image, ImageAttributes, Layer, LayerAttributes, Layers,
+};
 use crate::math::Vec2;
 use crate::meta::attribute::{ChannelDescription, ChannelList, IntegerBounds, LineOrder};
 use crate::meta::header::{Header, ImageAttributes as HeaderImageAttributes};
 use crate::meta::{BlockDescription, Headers, MetaData};
+use smallvec::SmallVec;
 
 /// Type alias for a deep image with any channels.
 pub type DeepImage = Image<Layer<AnyChannels<DeepSamples>>>;
@@ -123,14 +139,169 @@ pub fn write_deep_image_to_buffered<W: Write + Seek>(
     image: &DeepImage,
     compression: Compression,
 ) -> UnitResult {
-    let layer = &image.layer_data;
+    image
+        .write_deep()
+        .with_compression(compression)
+        .to_buffered(write)
+}
+
+/// Write the deep layers of an image, see `Image::write_deep`.
+/// Each layer is written as a separate part of a multi-part file,
+/// in deep scan line blocks of the size required by its compression method.
+/// All layers must have a unique name if there is more than one layer.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct WriteDeepImage<'i, OnProgress = fn(f64)> {
+    attributes: &'i ImageAttributes,
+    layers: SmallVec<[&'i Layer<AnyChannels<DeepSamples>>; 2]>,
+    compression: Option<Compression>,
+    parallel: bool,
+    max_blocks_in_flight: Option<usize>,
+    on_progress: OnProgress,
+    cancellation: Option<CancellationToken>,
+}
+
+impl Image<Layer<AnyChannels<DeepSamples>>> {
+    /// Write this deep image to a file or stream.
+    /// Uses the compression method of the layer encoding, unless specified otherwise.
+    pub fn write_deep(&self) -> WriteDeepImage<'_> {
+        WriteDeepImage::new(&self.attributes, smallvec::smallvec![&self.layer_data])
+    }
+}
+
+impl Image<Layers<AnyChannels<DeepSamples>>> {
+    /// Write all layers of this deep image to a multi-part file or stream.
+    /// Uses the compression method of each layer encoding, unless specified otherwise.
+    pub fn write_deep(&self) -> WriteDeepImage<'_> {
+        WriteDeepImage::new(&self.attributes, self.layer_data.iter().collect())
+    }
+}
+
+impl<'i> WriteDeepImage<'i> {
+    fn new(
+        attributes: &'i ImageAttributes,
+        layers: SmallVec<[&'i Layer<AnyChannels<DeepSamples>>; 2]>,
+    ) -> Self {
+        WriteDeepImage {
+            attributes,
+            layers,
+            compression: None,
+            parallel: true,
+            max_blocks_in_flight: None,
+            on_progress: ignore_progress,
+            cancellation: None,
+        }
+    }
+}
+
+impl<'i, F: FnMut(f64)> WriteDeepImage<'i, F> {
+    /// Specify a function to be called for each block that is written to the file.
+    /// Replaces all previously specified progress functions in this writer.
+    pub fn on_progress<OnProgress>(self, on_progress: OnProgress) -> WriteDeepImage<'i, OnProgress>
+    where
+        OnProgress: FnMut(f64),
+    {
+        WriteDeepImage {
+            attributes: self.attributes,
+            layers: self.layers,
+            compression: self.compression,
+            parallel: self.parallel,
+            max_blocks_in_flight: self.max_blocks_in_flight,
+            on_progress,
+            cancellation: self.cancellation,
+        }
+    }
+
+    /// Stop writing the image with `Error::Aborted` as soon as the token is cancelled.
+    /// The token is checked before each block is written to the file.
+    /// Writing to a file deletes the incomplete file.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Compress all layers with this method, instead of the compression of each layer encoding.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Compress all blocks on the current thread.
+    pub fn non_parallel(mut self) -> Self {
+        self.parallel = false;
+        self
+    }
+
+    /// Compress at most this many blocks at the same time, on as many threads,
+    /// and keep at most this many blocks in memory while writing.
+    /// Without a limit, the number of blocks depends on the number of processors.
+    /// Has no effect if the blocks are compressed on the current thread.
+    pub fn with_max_blocks_in_flight(mut self, max_blocks_in_flight: usize) -> Self {
+        self.max_blocks_in_flight = Some(max_blocks_in_flight);
+        self
+    }
+
+    /// Write the image to a new file, deleting the file if writing fails.
+    pub fn to_file(self, path: impl AsRef<Path>) -> UnitResult {
+        crate::io::attempt_delete_file_on_write_error(path.as_ref(), move |write| {
+            self.to_buffered(BufWriter::new(write))
+        })
+    }
+
+    /// Write the image to a buffered stream.
+    pub fn to_buffered<W: Write + Seek>(self, write: W) -> UnitResult {
+        if self.layers.is_empty() {
+            return Err(Error::invalid("deep image without layers"));
+        }
+
+        let mut headers = Headers::new();
+        let mut layer_samples: SmallVec<[&DeepSamples; 2]> = SmallVec::new();
+
+        for layer in &self.layers {
+            // all channels are stored in the samples of the first channel
+            let samples = &layer
+                .channel_data
+                .list
+                .first()
+                .ok_or_else(|| Error::invalid("deep layer without channels"))?
+                .sample_data;
+
+            let compression = self.compression.unwrap_or(layer.encoding.compression);
+            if !compression.supports_deep_data() {
+                return Err(compression.deep_data_error());
+            }
+
+            headers.push(deep_header(
+                samples,
+                &deep_channel_list(layer),
+                compression,
+                Some(self.attributes),
+                Some(&layer.attributes),
+            ));
+
+            layer_samples.push(samples);
+        }
 
-    // The read API stores ALL channels' data in the FIRST channel's DeepSamples
-    let first_ch = &layer.channel_data.list[0];
-    let samples = &first_ch.sample_data;
+        write_deep_layers(
+            write,
+            headers,
+            &layer_samples,
+            Parallelism {
+                parallel: self.parallel,
+                max_blocks_in_flight: self.max_blocks_in_flight,
+            },
+            self.on_progress,
+            self.cancellation,
+        )
+    }
+}
+
+/// The channel descriptions of a deep layer, with the sample types of the stored samples.
+fn deep_channel_list(layer: &Layer<AnyChannels<DeepSamples>>) -> ChannelList {
+    // the channel data of the first channel contains all channels, in the same order as the list
+    let samples = &layer.channel_data.list[0].sample_data;
 
-    // Build channel list - channel data order matches list order
-    let channel_list = ChannelList::new(
+    ChannelList::new(
         layer
             .channel_data
             .list
@@ -147,15 +318,6 @@ pub fn write_deep_image_to_buffered<W: Write + Seek>(
                 sampling: ch.sampling,
             })
             .collect(),
-    );
-
-    write_deep_scanlines_to_buffered(
-        write,
-        samples,
-        &channel_list,
-        compression,
-        Some(&image.attributes),
-        Some(&layer.attributes),
     )
 }
 
@@ -176,19 +338,43 @@ pub fn write_deep_scanlines_to_file(
             compression,
             None,
             None,
+            true,
         )
     })
 }
 
 /// Write deep scanline data to a buffered writer.
-fn write_deep_scanlines_to_buffered<W: Write + Seek>(
+pub(crate) fn write_deep_scanlines_to_buffered<W: Write + Seek>(
     write: W,
     samples: &DeepSamples,
     channels: &ChannelList,
     compression: Compression,
     image_attrs: Option<&ImageAttributes>,
     layer_attrs: Option<&LayerAttributes>,
+    parallel: bool,
 ) -> UnitResult {
+    let header = deep_header(samples, channels, compression, image_attrs, layer_attrs);
+    write_deep_layers(
+        write,
+        smallvec::smallvec![header],
+        &[samples],
+        Parallelism {
+            parallel,
+            max_blocks_in_flight: None,
+        },
+        ignore_progress,
+        None,
+    )
+}
+
+/// Build the header of a deep scan line layer.
+fn deep_header(
+    samples: &DeepSamples,
+    channels: &ChannelList,
+    compression: Compression,
+    image_attrs: Option<&ImageAttributes>,
+    layer_attrs: Option<&LayerAttributes>,
+) -> Header {
     let width = samples.width;
     let height = samples.height;
     let data_size = Vec2(width, height);
@@ -196,8 +382,7 @@ fn write_deep_scanlines_to_buffered<W: Write + Seek>(
     // Calculate max samples per pixel for header
     let max_samples = samples.max_samples_per_pixel();
 
-    // Build header for deep scanline data
-    let header = Header {
+    Header {
         channels: channels.clone(),
         compression,
         blocks: BlockDescription::ScanLines,
@@ -234,13 +419,31 @@ fn write_deep_scanlines_to_buffered<W: Write + Seek>(
 
         // Multi-part
         chunk_count: calculate_chunk_count(height, compression),
-    };
+    }
+}
 
-    let headers: Headers = smallvec::smallvec![header];
+/// Whether the blocks are compressed with multiple threads, and how many blocks are kept in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Parallelism {
+    parallel: bool,
+    max_blocks_in_flight: Option<usize>,
+}
 
-    // Write the file
+/// Write the samples of each header to the file.
+fn write_deep_layers<W: Write + Seek>(
+    write: W,
+    headers: Headers,
+    layers: &[&DeepSamples],
+    parallelism: Parallelism,
+    on_progress: impl FnMut(f64),
+    cancellation: Option<CancellationToken>,
+) -> UnitResult {
     crate::block::writer::write_chunks_with(write, headers, true, |meta, chunk_writer| {
-        write_deep_chunks(chunk_writer, &meta, samples, channels, compression)
+        let mut chunk_writer = chunk_writer
+            .on_progress(on_progress)
+            .cancel_with(cancellation);
+
+        write_deep_chunks(&mut chunk_writer, &meta, layers, parallelism)
     })
 }
 
@@ -250,45 +453,104 @@ fn calculate_chunk_count(height: usize, compression: Compression) -> usize {
     (height + lines_per_block - 1) / lines_per_block
 }
 
-/// Write deep scanline chunks to the writer.
-fn write_deep_chunks<W: Write + Seek>(
-    writer: &mut ChunkWriter<W>,
+/// Write the deep scanline chunks of all layers to the writer, one layer after another.
+/// Packs and compresses the blocks with multiple threads if possible.
+fn write_deep_chunks<W: ChunksWriter>(
+    writer: &mut W,
     meta: &MetaData,
-    samples: &DeepSamples,
-    channels: &ChannelList,
-    compression: Compression,
+    layers: &[&DeepSamples],
+    parallelism: Parallelism,
 ) -> UnitResult {
-    let header = &meta.headers[0];
-    let height = header.layer_size.height();
-    let lines_per_block = compression.scan_lines_per_block();
+    #[cfg(feature = "rayon")]
+    {
+        use crate::block::deep::{DeepUncompressedBlock, ParallelDeepBlocksCompressor};
+
+        if parallelism.parallel {
+            let compressor = match parallelism.max_blocks_in_flight {
+                Some(max_blocks_in_flight) => {
+                    ParallelDeepBlocksCompressor::new_with_max_blocks_in_flight(
+                        meta,
+                        &mut *writer,
+                        max_blocks_in_flight,
+                    )
+                }
+
+                None => ParallelDeepBlocksCompressor::new(meta, &mut *writer),
+            };
+
+            if let Some(mut compressor) = compressor {
+                for (layer_index, (header, samples)) in meta.headers.iter().zip(layers).enumerate()
+                {
+                    for (block_idx, y, block_height) in scan_line_blocks(header) {
+                        let block = DeepUncompressedBlock {
+                            layer_index,
+                            y_coordinate: absolute_y(header, y),
+                            tile: None,
+                            samples: extract_block_samples(
+                                samples,
+                                y,
+                                block_height,
+                                &header.channels,
+                            ),
+                        };
+
+                        compressor.add_block_to_compression_queue(block_idx, block)?;
+                    }
+                }
 
-    let mut block_idx = 0;
-    let mut y = 0;
+                return compressor.write_all_queued_chunks();
+            }
+        }
+    }
 
-    while y < height {
-        let block_height = lines_per_block.min(height - y);
+    #[cfg(not(feature = "rayon"))]
+    let _ = parallelism;
 
-        // Extract samples for this block
-        let block_samples = extract_block_samples(samples, y, block_height, channels);
+    // the packed bytes of each block are only needed while compressing the block
+    let mut buffers = DeepBlockBuffers::new();
 
-        // Compress to deep scanline block
-        let compressed =
-            compress_deep_scanline_block(&block_samples, compression, channels, y as i32)?;
+    for (layer_index, (header, samples)) in meta.headers.iter().zip(layers).enumerate() {
+        for (block_idx, y, block_height) in scan_line_blocks(header) {
+            // Extract samples for this block
+            let block_samples = extract_block_samples(samples, y, block_height, &header.channels);
 
-        let chunk = Chunk {
-            layer_index: 0,
-            compressed_block: CompressedBlock::DeepScanLine(compressed),
-        };
+            // Compress to deep scanline block
+            let compressed = compress_deep_scanline_block_with_buffers(
+                &block_samples,
+                header.compression,
+                &header.channels,
+                absolute_y(header, y),
+                &mut buffers,
+            )?;
 
-        writer.write_chunk(block_idx, chunk)?;
+            let chunk = Chunk {
+                layer_index,
+                compressed_block: CompressedBlock::DeepScanLine(compressed),
+            };
 
-        block_idx += 1;
-        y += block_height;
+            writer.write_chunk(block_idx, chunk)?;
+        }
     }
 
     Ok(())
 }
 
+/// Scan line blocks store the y coordinate of their first line in absolute coordinates.
+fn absolute_y(header: &Header, y: usize) -> i32 {
+    header.own_attributes.layer_position.y() + y as i32
+}
+
+/// The index, first line, and height of each scan line block, in increasing y order.
+fn scan_line_blocks(header: &Header) -> impl Iterator<Item = (usize, usize, usize)> {
+    let height = header.layer_size.height();
+    let lines_per_block = header.compression.scan_lines_per_block();
+
+    (0..height)
+        .step_by(lines_per_block)
+        .enumerate()
+        .map(move |(block_idx, y)| (block_idx, y, lines_per_block.min(height - y)))
+}
+
 /// Extract a subset of samples for a specific block (y range).
 fn extract_block_samples(
     samples: &DeepSamples,
@@ -507,6 +769,7 @@ mod test {
             Compression::Uncompressed,
             None,
             None,
+            true,
         )
         .expect("write should succeed");
 
@@ -514,6 +777,70 @@ mod test {
         assert!(buffer.get_ref().len() > 0, "output should have data");
     }
 
+    #[test]
+    fn parallel_compression_keeps_chunk_order() {
+        let (width, height) = (7, 53);
+        let mut samples = DeepSamples::new(width, height);
+
+        let mut total = 0;
+        let cumulative = (0..width * height)
+            .map(|pixel| {
+                total += (pixel % 4) as u32;
+                total
+            })
+            .collect();
+
+        samples.set_cumulative_counts(cumulative).unwrap();
+
+        let channels = ChannelList::new(smallvec::smallvec![
+            ChannelDescription::named("A", SampleType::F16),
+            ChannelDescription::named("Z", SampleType::F32),
+        ]);
+
+        samples.allocate_channels(&channels);
+
+        for channel in &mut samples.channels {
+            match channel {
+                crate::image::deep::DeepChannelData::F16(values) => {
+                    for (i, value) in values.iter_mut().enumerate() {
+                        *value = half::f16::from_f32((i % 100) as f32 * 0.01);
+                    }
+                }
+                crate::image::deep::DeepChannelData::F32(values) => {
+                    for (i, value) in values.iter_mut().enumerate() {
+                        *value = i as f32 * 0.5;
+                    }
+                }
+                crate::image::deep::DeepChannelData::U32(_) => unreachable!(),
+            }
+        }
+
+        for &compression in &[
+            Compression::Uncompressed,
+            Compression::RLE,
+            Compression::ZIP1,
+            Compression::ZIP16,
+        ] {
+            let write = |parallel| {
+                let mut buffer = std::io::Cursor::new(Vec::new());
+                write_deep_scanlines_to_buffered(
+                    &mut buffer,
+                    &samples,
+                    &channels,
+                    compression,
+                    None,
+                    None,
+                    parallel,
+                )
+                .expect("write should succeed");
+
+                buffer.into_inner()
+            };
+
+            assert_eq!(write(true), write(false), "{:?}", compression);
+        }
+    }
+
     #[test]
     fn test_extract_single_line() {
         let mut samples = DeepSamples::new(3, 2);
@@ -633,4 +960,100 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn write_and_read_multiple_deep_layers() {
+        use crate::image::deep::DeepChannelData;
+        use crate::image::{AnyChannel, Encoding};
+
+        let layer = |name: &str, width: usize, height: usize, compression: Compression| {
+            let mut samples = DeepSamples::new(width, height);
+            let counts = (0..width * height)
+                .scan(0, |total, pixel| {
+                    *total += (pixel % 3) as u32;
+                    Some(*total)
+                })
+                .collect();
+
+            samples.set_cumulative_counts(counts).unwrap();
+            samples.channels = vec![
+                DeepChannelData::F16(vec![half::f16::ONE; samples.total_samples()]),
+                DeepChannelData::F32((0..samples.total_samples()).map(|i| i as f32).collect()),
+            ];
+
+            let channel = |name: &str, sample_data| AnyChannel {
+                name: name.into(),
+                sample_data,
+                quantize_linearly: false,
+                sampling: Vec2(1, 1),
+            };
+
+            Layer {
+                channel_data: AnyChannels {
+                    list: smallvec::smallvec![
+                        channel("A", samples),
+                        channel("Z", DeepSamples::new(0, 0)),
+                    ],
+                },
+                attributes: LayerAttributes::named(name),
+                size: Vec2(width, height),
+                encoding: Encoding {
+                    compression,
+                    ..Encoding::default()
+                },
+            }
+        };
+
+        // the data window of the second layer does not start at the origin
+        let mut back = layer("back", 5, 17, Compression::RLE);
+        back.attributes.layer_position = Vec2(-4, 7);
+
+        let image = Image {
+            attributes: ImageAttributes::new(IntegerBounds::from_dimensions((9, 40))),
+            layer_data: smallvec::smallvec![layer("front", 9, 40, Compression::ZIP1), back],
+        };
+
+        let write = |writer: WriteDeepImage<'_>| {
+            let mut buffer = std::io::Cursor::new(Vec::new());
+            writer
+                .to_buffered(&mut buffer)
+                .expect("write should succeed");
+            buffer.into_inner()
+        };
+
+        let bytes = write(image.write_deep());
+        assert_eq!(bytes, write(image.write_deep().non_parallel()));
+        assert_eq!(
+            bytes,
+            write(image.write_deep().with_max_blocks_in_flight(1))
+        );
+        assert_eq!(
+            bytes,
+            write(image.write_deep().with_max_blocks_in_flight(3))
+        );
+
+        let read = crate::image::read::deep::read_deep()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .from_buffered(std::io::Cursor::new(bytes))
+            .expect("read should succeed");
+
+        assert_eq!(read.layer_data.len(), 2);
+
+        for (original, read) in image.layer_data.iter().zip(&read.layer_data) {
+            assert_eq!(original.attributes.layer_name, read.attributes.layer_name);
+            assert_eq!(original.attributes.layer_position, read.attributes.layer_position);
+
+            let original = &original.channel_data.list[0].sample_data;
+            let read = &read.channel_data.list[0].sample_data;
+            assert_eq!(original.sample_offsets, read.sample_offsets);
+            assert_eq!(original.channels, read.channels);
+        }
+
+        let no_deep_compression = image.write_deep().with_compression(Compression::PIZ);
+        assert!(no_deep_compression
+            .to_buffered(std::io::Cursor::new(Vec::new()))
+            .is_err());
+    }
 }
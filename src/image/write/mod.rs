@@ -16,6 +16,7 @@ pub mod deep;
 pub mod layers;
 pub mod samples;
 
+use crate::block::cancel::CancellationToken;
 use crate::block::writer::ChunksWriter;
 use crate::error::UnitResult;
 use crate::image::write::layers::{LayersWriter, WritableLayers};
@@ -90,7 +91,9 @@ impl<'img, WritableLayers> WritableImage<'img, WritableLayers> for &'img Image<W
             #[cfg(feature = "rayon")]
             parallel: true,
 
+            max_blocks_in_flight: None,
             on_progress: ignore_progress,
+            cancellation: None,
         }
     }
 }
@@ -103,6 +106,8 @@ pub struct WriteImageWithOptions<'img, Layers, OnProgress> {
     on_progress: OnProgress,
     check_compatibility: bool,
     parallel: bool,
+    max_blocks_in_flight: Option<usize>,
+    cancellation: Option<CancellationToken>,
 }
 
 impl<'img, L, F> WriteImageWithOptions<'img, L, F>
@@ -125,6 +130,18 @@ where
         }
     }
 
+    /// Compress at most this many blocks at the same time, on as many threads,
+    /// and keep at most this many blocks in memory while writing.
+    /// Compressed blocks that wait for a previous block before they can be written are included.
+    /// Without a limit, the number of blocks depends on the number of processors.
+    /// Has no effect if the image is written without multiple threads.
+    pub fn with_max_blocks_in_flight(self, max_blocks_in_flight: usize) -> Self {
+        Self {
+            max_blocks_in_flight: Some(max_blocks_in_flight),
+            ..self
+        }
+    }
+
     /// Skip some checks that ensure a file can be opened by other exr software.
     /// For example, it is no longer checked that no two headers or two attributes have the same name,
     /// which might be an expensive check for images with an exorbitant number of headers.
@@ -140,6 +157,16 @@ where
         }
     }
 
+    /// Stop writing the image with `Error::Aborted` as soon as the token is cancelled.
+    /// The token is checked before each block is written to the file.
+    /// Writing to a file deletes the incomplete file.
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        Self {
+            cancellation: Some(cancellation),
+            ..self
+        }
+    }
+
     /// Specify a function to be called regularly throughout the writing process.
     /// Replaces all previously specified progress functions in this reader.
     pub fn on_progress<OnProgress>(
@@ -154,6 +181,8 @@ where
             image: self.image,
             check_compatibility: self.check_compatibility,
             parallel: self.parallel,
+            max_blocks_in_flight: self.max_blocks_in_flight,
+            cancellation: self.cancellation,
         }
     }
 
@@ -196,7 +225,9 @@ where
                     layers.extract_uncompressed_block(&meta.headers, block_index)
                 });
 
-                let chunk_writer = chunk_writer.on_progress(self.on_progress);
+                let chunk_writer = chunk_writer
+                    .on_progress(self.on_progress)
+                    .cancel_with(self.cancellation);
                 if self.parallel {
                     #[cfg(not(feature = "rayon"))]
                     return Err(crate::error::Error::unsupported(
@@ -204,7 +235,16 @@ where
                     ));
 
                     #[cfg(feature = "rayon")]
-                    chunk_writer.compress_all_blocks_parallel(&meta, blocks)?;
+                    match self.max_blocks_in_flight {
+                        Some(max_blocks_in_flight) => chunk_writer
+                            .compress_all_blocks_parallel_with_max_blocks_in_flight(
+                                &meta,
+                                blocks,
+                                max_blocks_in_flight,
+                            )?,
+
+                        None => chunk_writer.compress_all_blocks_parallel(&meta, blocks)?,
+                    }
                 } else {
                     chunk_writer.compress_all_blocks_sequential(&meta, blocks)?;
                 }
@@ -0,0 +1,421 @@
+//! Read Radiance HDR, PFM, and floating point TIFF files into layers,
+//! and convert them to EXR files.
+//! Enable with the `image` feature.
+//!
+//! All of these formats contain linear values, which are copied without any color conversion.
+//! Integer TIFF samples are mapped to the range `0.0 ..= 1.0`.
+//! Gray images contain a `Y` channel, colored images contain `R`, `G`, and `B` channels,
+//! and an `A` channel is added if the image has alpha. All samples are stored as `f32`.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//!
+//! exr::import::convert_file_to_exr("sky.hdr", "sky.exr", Compression::PIZ).unwrap();
+//!
+//! let layer = exr::import::read_layer_from_file("normals.pfm").unwrap();
+//! Image::from_layer(layer).write().to_file("normals.exr").unwrap();
+//! ```
+
+use ::image::codecs::hdr::HdrDecoder;
+use ::image::ImageError;
+use smallvec::SmallVec;
+use std::io::{BufRead, BufReader, Read, Seek};
+use std::path::Path;
+use tiff::decoder::{Decoder as TiffDecoder, DecodingResult};
+use tiff::{ColorType as TiffColorType, TiffError};
+
+use crate::compression::Compression;
+use crate::error::{Error, Result, UnitResult};
+use crate::image::write::WritableImage;
+use crate::image::{AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer};
+use crate::interop::FlatLayer;
+use crate::math::Vec2;
+use crate::meta::header::LayerAttributes;
+
+/// The file formats that can be imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImportFormat {
+    /// Radiance HDR, with linear rgb values.
+    Hdr,
+
+    /// Portable float map, with gray or rgb `f32` values.
+    Pfm,
+
+    /// Tagged image file format, preferably with `f32` samples.
+    Tiff,
+}
+
+impl ImportFormat {
+    /// Choose the format from the extension of the path, ignoring the case.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "hdr" => Ok(ImportFormat::Hdr),
+            "pfm" => Ok(ImportFormat::Pfm),
+            "tif" | "tiff" => Ok(ImportFormat::Tiff),
+            _ => Err(Error::unsupported(
+                "import file extension (use hdr, pfm, tif, or tiff)",
+            )),
+        }
+    }
+}
+
+/// Read an HDR, PFM, or TIFF file into a layer with default attributes.
+/// The format is chosen by the extension of the path.
+pub fn read_layer_from_file(path: impl AsRef<Path>) -> Result<FlatLayer> {
+    let format = ImportFormat::from_path(&path)?;
+    let file = std::fs::File::open(path)?;
+    read_layer(BufReader::new(file), format)
+}
+
+/// Read an image in the specified format into a layer with default attributes.
+pub fn read_layer(read: impl BufRead + Seek, format: ImportFormat) -> Result<FlatLayer> {
+    match format {
+        ImportFormat::Hdr => read_hdr(read),
+        ImportFormat::Pfm => read_pfm(read),
+        ImportFormat::Tiff => read_tiff(read),
+    }
+}
+
+/// Read an HDR, PFM, or TIFF file and convert it to an EXR file with the specified compression.
+/// The input format is chosen by the extension of the input path.
+/// The EXR file contains scan lines in increasing order.
+pub fn convert_file_to_exr(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    compression: Compression,
+) -> UnitResult {
+    let mut layer = read_layer_from_file(input)?;
+    layer.encoding = Encoding {
+        compression,
+        ..Encoding::SMALL_LOSSLESS
+    };
+    Image::from_layer(layer).write().to_file(output)
+}
+
+/// Read a Radiance HDR image into a layer with `R`, `G`, and `B` channels.
+pub fn read_hdr(read: impl BufRead) -> Result<FlatLayer> {
+    let decoder = HdrDecoder::new(read).map_err(image_error)?;
+    let meta = decoder.metadata();
+    let size = Vec2(meta.width as usize, meta.height as usize);
+
+    let pixels = decoder.read_image_hdr().map_err(image_error)?;
+    let samples = pixels.into_iter().flat_map(|pixel| pixel.0).collect();
+
+    layer_from_interleaved(size, 3, samples)
+}
+
+/// Read a portable float map into a layer with a `Y` channel or `R`, `G`, and `B` channels.
+/// The scale factor of the header only defines the byte order, its magnitude is ignored.
+pub fn read_pfm(mut read: impl Read) -> Result<FlatLayer> {
+    let channel_count = match pfm_token(&mut read)?.as_str() {
+        "Pf" => 1,
+        "PF" => 3,
+        _ => return Err(Error::invalid("pfm file signature")),
+    };
+
+    let mut number = |name: &'static str| -> Result<String> {
+        let token = pfm_token(&mut read)?;
+        if token.is_empty() {
+            Err(Error::invalid(name))
+        } else {
+            Ok(token)
+        }
+    };
+
+    let width: usize = number("pfm width")?
+        .parse()
+        .map_err(|_| Error::invalid("pfm width"))?;
+
+    let height: usize = number("pfm height")?
+        .parse()
+        .map_err(|_| Error::invalid("pfm height"))?;
+
+    let scale: f32 = number("pfm scale")?
+        .parse()
+        .map_err(|_| Error::invalid("pfm scale"))?;
+
+    let byte_count = width
+        .checked_mul(height)
+        .and_then(|area| area.checked_mul(channel_count * 4))
+        .filter(|&byte_count| byte_count > 0)
+        .ok_or_else(|| Error::invalid("pfm size"))?;
+
+    // do not allocate the whole size announced by the header before reading the bytes
+    let mut bytes = Vec::new();
+    read.take(byte_count as u64).read_to_end(&mut bytes)?;
+
+    if bytes.len() != byte_count {
+        return Err(Error::invalid("reference to missing bytes"));
+    }
+
+    let little_endian = scale < 0.0;
+    let row_length = width * channel_count;
+    let mut samples = Vec::with_capacity(row_length * height);
+
+    // the rows are stored from bottom to top
+    for row in bytes.chunks_exact(row_length * 4).rev() {
+        samples.extend(row.chunks_exact(4).map(|value| {
+            let value = [value[0], value[1], value[2], value[3]];
+
+            if little_endian {
+                f32::from_le_bytes(value)
+            } else {
+                f32::from_be_bytes(value)
+            }
+        }));
+    }
+
+    layer_from_interleaved(Vec2(width, height), channel_count, samples)
+}
+
+/// Read the first image of a TIFF file into a layer.
+/// Supports gray, gray with alpha, rgb, and rgba images with `f32`, `f64`, or unsigned samples.
+pub fn read_tiff(read: impl Read + Seek) -> Result<FlatLayer> {
+    let mut decoder = TiffDecoder::new(read).map_err(tiff_error)?;
+    let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+
+    let channel_count = match decoder.colortype().map_err(tiff_error)? {
+        TiffColorType::Gray(_) => 1,
+        TiffColorType::GrayA(_) => 2,
+        TiffColorType::RGB(_) => 3,
+        TiffColorType::RGBA(_) => 4,
+        _ => return Err(Error::unsupported("tiff color type")),
+    };
+
+    let samples = match decoder.read_image().map_err(tiff_error)? {
+        DecodingResult::F32(samples) => samples,
+        DecodingResult::F64(samples) => samples.into_iter().map(|value| value as f32).collect(),
+        DecodingResult::U8(samples) => normalize(samples, u8::MAX),
+        DecodingResult::U16(samples) => normalize(samples, u16::MAX),
+        DecodingResult::U32(samples) => normalize(samples, u32::MAX),
+        _ => return Err(Error::unsupported("tiff sample type")),
+    };
+
+    layer_from_interleaved(
+        Vec2(width as usize, height as usize),
+        channel_count,
+        samples,
+    )
+}
+
+/// Map unsigned integers to the range `0.0 ..= 1.0`.
+fn normalize<T: Into<f64>>(samples: Vec<T>, max: T) -> Vec<f32> {
+    let max = max.into();
+
+    samples
+        .into_iter()
+        .map(|value| (value.into() / max) as f32)
+        .collect()
+}
+
+/// Create a layer from the interleaved samples of gray, gray and alpha, rgb, or rgba pixels.
+fn layer_from_interleaved(
+    size: Vec2<usize>,
+    channel_count: usize,
+    samples: Vec<f32>,
+) -> Result<FlatLayer> {
+    if samples.len() != size.area() * channel_count {
+        return Err(Error::invalid("image sample count"));
+    }
+
+    let names: &[&str] = match channel_count {
+        1 => &["Y"],
+        2 => &["Y", "A"],
+        3 => &["R", "G", "B"],
+        _ => &["R", "G", "B", "A"],
+    };
+
+    let channels: SmallVec<_> = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let channel_samples = samples
+                .iter()
+                .skip(index)
+                .step_by(channel_count)
+                .copied()
+                .collect();
+
+            AnyChannel::new(*name, FlatSamples::F32(channel_samples))
+        })
+        .collect();
+
+    Ok(Layer::new(
+        size,
+        LayerAttributes::default(),
+        Encoding::default(),
+        AnyChannels::sort(channels),
+    ))
+}
+
+/// Read a whitespace separated token of a pfm header, and the single whitespace after it.
+fn pfm_token(read: &mut impl Read) -> Result<String> {
+    let mut token = String::new();
+
+    for byte in read.bytes() {
+        let byte = byte?;
+
+        if byte.is_ascii_whitespace() {
+            if token.is_empty() {
+                continue;
+            } else {
+                break;
+            }
+        }
+
+        if !byte.is_ascii_graphic() || token.len() >= 32 {
+            return Err(Error::invalid("pfm header"));
+        }
+
+        token.push(byte as char);
+    }
+
+    Ok(token)
+}
+
+/// Keep io errors, and describe all other errors of the `image` crate.
+fn image_error(error: ImageError) -> Error {
+    match error {
+        ImageError::IoError(error) => Error::from(error),
+        ImageError::Unsupported(error) => Error::unsupported(error.to_string()),
+        error => Error::invalid(error.to_string()),
+    }
+}
+
+/// Keep io errors, and describe all other errors of the `tiff` crate.
+fn tiff_error(error: TiffError) -> Error {
+    match error {
+        TiffError::IoError(error) => Error::from(error),
+        TiffError::UnsupportedError(error) => Error::unsupported(error.to_string()),
+        error => Error::invalid(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::{write_channels, ExportFormat, ExportOptions};
+    use std::io::Cursor;
+
+    fn channel<'l>(layer: &'l FlatLayer, name: &str) -> &'l [f32] {
+        let channel = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.eq(name))
+            .expect("missing channel");
+
+        match &channel.sample_data {
+            FlatSamples::F32(samples) => samples,
+            _ => panic!("expected f32 samples"),
+        }
+    }
+
+    #[test]
+    fn read_gray_little_endian_pfm() {
+        let mut bytes = b"Pf\n2 2\n-1.0\n".to_vec();
+        for value in [3.0_f32, 4.0, 1.0, 2.0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let layer = read_pfm(bytes.as_slice()).unwrap();
+        assert_eq!(layer.size, Vec2(2, 2));
+
+        // the bottom row is stored first
+        assert_eq!(channel(&layer, "Y"), &[1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn read_rgb_big_endian_pfm() {
+        let mut bytes = b"PF 1 1 1.0 ".to_vec();
+        for value in [0.25_f32, 8.0, -1.0] {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+
+        let layer = read_pfm(bytes.as_slice()).unwrap();
+        assert_eq!(channel(&layer, "R"), &[0.25]);
+        assert_eq!(channel(&layer, "G"), &[8.0]);
+        assert_eq!(channel(&layer, "B"), &[-1.0]);
+    }
+
+    #[test]
+    fn reject_invalid_pfm() {
+        assert!(read_pfm(b"P6\n1 1\n255\n".as_slice()).is_err());
+        assert!(read_pfm(b"PF\n1 x\n-1.0\n".as_slice()).is_err());
+
+        // too few samples
+        assert!(read_pfm(b"Pf\n2 2\n-1.0\n\0\0\0\0".as_slice()).is_err());
+    }
+
+    #[test]
+    fn read_hdr_written_by_export() {
+        let red = [0.5_f32, 16.0];
+        let green = [0.25_f32, 0.0];
+        let blue = [1.0_f32, 2.0];
+
+        let mut bytes = Cursor::new(Vec::new());
+        write_channels(
+            &mut bytes,
+            ExportFormat::Hdr,
+            Vec2(1, 2),
+            &[&red, &green, &blue],
+            &ExportOptions::default(),
+        )
+        .unwrap();
+
+        bytes.set_position(0);
+        let layer = read_layer(bytes, ImportFormat::Hdr).unwrap();
+
+        assert_eq!(layer.size, Vec2(1, 2));
+        assert_eq!(channel(&layer, "R"), &red);
+        assert_eq!(channel(&layer, "G"), &green);
+        assert_eq!(channel(&layer, "B"), &blue);
+    }
+
+    #[test]
+    fn read_float_tiff() {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        let samples = [0.5_f32, 1.5, -2.0, 0.25, 100.0, 0.0, 3.0, 4.0];
+
+        let mut bytes = Cursor::new(Vec::new());
+        TiffEncoder::new(&mut bytes)
+            .unwrap()
+            .write_image::<colortype::RGBA32Float>(2, 1, &samples)
+            .unwrap();
+
+        bytes.set_position(0);
+        let layer = read_layer(bytes, ImportFormat::Tiff).unwrap();
+
+        assert_eq!(layer.size, Vec2(2, 1));
+        assert_eq!(channel(&layer, "R"), &[0.5, 100.0]);
+        assert_eq!(channel(&layer, "G"), &[1.5, 0.0]);
+        assert_eq!(channel(&layer, "B"), &[-2.0, 3.0]);
+        assert_eq!(channel(&layer, "A"), &[0.25, 4.0]);
+    }
+
+    #[test]
+    fn normalize_integer_tiff() {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        let mut bytes = Cursor::new(Vec::new());
+        TiffEncoder::new(&mut bytes)
+            .unwrap()
+            .write_image::<colortype::Gray16>(3, 1, &[0, 65535, 32768])
+            .unwrap();
+
+        bytes.set_position(0);
+        let layer = read_layer(bytes, ImportFormat::Tiff).unwrap();
+
+        let gray = channel(&layer, "Y");
+        assert_eq!(&gray[..2], &[0.0, 1.0]);
+        assert!((gray[2] - 0.5).abs() < 1e-4);
+    }
+}
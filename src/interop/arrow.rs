@@ -0,0 +1,230 @@
+//! Export the samples of deep layers as Apache Arrow record batches.
+//! Enable with the `arrow` feature.
+//!
+//! Each row contains one sample: the pixel position `x` and `y`, the index of the sample
+//! within its pixel, and one column per channel, with the original sample type.
+//! Pixel positions include the position of the layer, so that they match the data window.
+//! The record batches can be handed to Polars, DuckDB, or DataFusion,
+//! for example to compute sample count distributions or depth statistics.
+//!
+//! ```no_run
+//! use exr::image::read::deep::read_first_deep_layer_from_file;
+//! use exr::interop::arrow::deep_record_batch;
+//!
+//! let image = read_first_deep_layer_from_file("deep.exr").unwrap();
+//! let batch = deep_record_batch(&image.layer_data).unwrap();
+//! println!("{} samples in {} columns", batch.num_rows(), batch.num_columns());
+//! ```
+
+use ::arrow::array::{ArrayRef, Float16Array, Float32Array, Int32Array, UInt32Array};
+use ::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use ::arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+use super::DeepLayer;
+use crate::error::{Error, Result};
+use crate::image::deep::{DeepChannelData, DeepSamples};
+use crate::meta::attribute::SampleType;
+
+/// The name of the column that contains the horizontal pixel position.
+pub const X_COLUMN: &str = "x";
+
+/// The name of the column that contains the vertical pixel position.
+pub const Y_COLUMN: &str = "y";
+
+/// The name of the column that contains the index of the sample within its pixel.
+pub const SAMPLE_COLUMN: &str = "sample";
+
+/// The schema of the record batches of a deep layer:
+/// the columns `x`, `y`, and `sample`, followed by one column per channel in alphabetical order.
+/// Returns an error if a channel is named like one of the position columns,
+/// or if the samples do not contain data for every channel.
+pub fn deep_schema(layer: &DeepLayer) -> Result<SchemaRef> {
+    let mut fields = vec![
+        Field::new(X_COLUMN, DataType::Int32, false),
+        Field::new(Y_COLUMN, DataType::Int32, false),
+        Field::new(SAMPLE_COLUMN, DataType::UInt32, false),
+    ];
+
+    let channel_data = deep_samples(layer)?.map_or(&[][..], |samples| &samples.channels);
+
+    for (channel, data) in layer.channel_data.list.iter().zip(channel_data) {
+        let name = channel.name.to_string();
+
+        if [X_COLUMN, Y_COLUMN, SAMPLE_COLUMN].contains(&name.as_str()) {
+            return Err(Error::invalid(format!(
+                "channel `{}` has the name of a position column",
+                name
+            )));
+        }
+
+        let data_type = match data.sample_type() {
+            SampleType::F16 => DataType::Float16,
+            SampleType::F32 => DataType::Float32,
+            SampleType::U32 => DataType::UInt32,
+        };
+
+        fields.push(Field::new(name, data_type, false));
+    }
+
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+/// All samples of a deep layer as a single record batch, with one row per sample.
+/// The channel columns share no memory with the layer, as arrow requires its own buffers.
+pub fn deep_record_batch(layer: &DeepLayer) -> Result<RecordBatch> {
+    let schema = deep_schema(layer)?;
+
+    let samples = match deep_samples(layer)? {
+        Some(samples) => samples,
+        None => return Ok(RecordBatch::new_empty(schema)),
+    };
+
+    let position = layer.attributes.layer_position;
+    let (xs, ys, indices) = sample_positions(samples, position.x(), position.y());
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(xs)),
+        Arc::new(Int32Array::from(ys)),
+        Arc::new(UInt32Array::from(indices)),
+    ];
+
+    for data in &samples.channels {
+        columns.push(match data {
+            DeepChannelData::F16(values) => Arc::new(Float16Array::from(values.clone())),
+            DeepChannelData::F32(values) => Arc::new(Float32Array::from(values.clone())),
+            DeepChannelData::U32(values) => Arc::new(UInt32Array::from(values.clone())),
+        });
+    }
+
+    RecordBatch::try_new(schema, columns).map_err(|error| Error::invalid(error.to_string()))
+}
+
+/// All samples of a deep layer, split into record batches of at most `max_rows` rows.
+/// The batches share the memory of one large batch.
+pub fn deep_record_batches(layer: &DeepLayer, max_rows: usize) -> Result<Vec<RecordBatch>> {
+    if max_rows == 0 {
+        return Err(Error::invalid("record batches need at least one row"));
+    }
+
+    let batch = deep_record_batch(layer)?;
+
+    Ok((0..batch.num_rows())
+        .step_by(max_rows)
+        .map(|start| batch.slice(start, max_rows.min(batch.num_rows() - start)))
+        .collect())
+}
+
+/// The samples of all channels, which are stored in the first channel.
+/// Returns `None` if the layer has no channels.
+fn deep_samples(layer: &DeepLayer) -> Result<Option<&DeepSamples>> {
+    let samples = match layer.channel_data.list.first() {
+        Some(first) => &first.sample_data,
+        None => return Ok(None),
+    };
+
+    if samples.channels.len() != layer.channel_data.list.len() {
+        return Err(Error::invalid(
+            "deep samples do not contain data for every channel",
+        ));
+    }
+
+    Ok(Some(samples))
+}
+
+/// The pixel position and the index within the pixel of each sample.
+fn sample_positions(samples: &DeepSamples, left: i32, top: i32) -> (Vec<i32>, Vec<i32>, Vec<u32>) {
+    let total = samples.total_samples();
+    let mut xs = Vec::with_capacity(total);
+    let mut ys = Vec::with_capacity(total);
+    let mut indices = Vec::with_capacity(total);
+
+    for y in 0..samples.height {
+        for x in 0..samples.width {
+            let count = samples.sample_count_at_index(y * samples.width + x);
+
+            for index in 0..count {
+                xs.push(left + x as i32);
+                ys.push(top + y as i32);
+                indices.push(index as u32);
+            }
+        }
+    }
+
+    (xs, ys, indices)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::{AnyChannel, AnyChannels, Encoding, Layer};
+    use crate::math::Vec2;
+    use crate::meta::header::LayerAttributes;
+    use ::arrow::array::Array;
+    use half::f16;
+    use smallvec::smallvec;
+
+    fn deep_layer() -> DeepLayer {
+        // three pixels with two, zero, and one samples
+        let mut samples = DeepSamples::new(3, 1);
+        samples.sample_offsets = vec![2, 2, 3];
+        samples.channels = vec![
+            DeepChannelData::F16(vec![f16::ONE, f16::from_f32(0.5), f16::ZERO]),
+            DeepChannelData::F32(vec![1.0, 2.0, 3.0]),
+        ];
+
+        // deep samples cannot be written as flat samples, so `AnyChannel::new` is not available
+        let channel = |name: &str, sample_data| AnyChannel {
+            name: name.into(),
+            sample_data,
+            quantize_linearly: false,
+            sampling: Vec2(1, 1),
+        };
+
+        let channels = AnyChannels::sort(smallvec![
+            channel("A", samples),
+            channel("Z", DeepSamples::new(0, 0)),
+        ]);
+
+        let mut attributes = LayerAttributes::default();
+        attributes.layer_position = Vec2(10, 20);
+
+        Layer {
+            channel_data: channels,
+            attributes,
+            size: Vec2(3, 1),
+            encoding: Encoding::default(),
+        }
+    }
+
+    #[test]
+    fn one_row_per_sample() {
+        let batch = deep_record_batch(&deep_layer()).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.num_columns(), 5);
+
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+        let xs = column("x");
+        let xs = xs.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(&xs.values()[..], &[10, 10, 12]);
+
+        let samples = column("sample");
+        let samples = samples.as_any().downcast_ref::<UInt32Array>().unwrap();
+        assert_eq!(&samples.values()[..], &[0, 1, 0]);
+
+        let depth = column("Z");
+        let depth = depth.as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(&depth.values()[..], &[1.0, 2.0, 3.0]);
+
+        assert_eq!(column("A").data_type(), &DataType::Float16);
+    }
+
+    #[test]
+    fn split_into_batches() {
+        let batches = deep_record_batches(&deep_layer(), 2).unwrap();
+        let rows: Vec<usize> = batches.iter().map(RecordBatch::num_rows).collect();
+        assert_eq!(rows, vec![2, 1]);
+
+        assert!(deep_record_batches(&deep_layer(), 0).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Reinterpret sample and pixel buffers as bytes with `bytemuck`, without copying and without unsafe code.
+//! Enable with the `bytemuck` feature, which also implements `Pod` for `f16`.
+//!
+//! Pixels are best stored as arrays, such as `[f32; 4]`, because arrays are `Pod`, but tuples are not.
+//! Reading from bytes copies the samples, as a byte buffer may not be aligned for the sample type.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//! use exr::prelude::pixel_vec::PixelVec;
+//! use exr::interop::bytemuck::pixels_as_bytes;
+//!
+//! let image = read_first_rgba_layer_from_file(
+//!     "image.exr",
+//!     PixelVec::<[f32; 4]>::constructor,
+//!     |pixels, position, (r, g, b, a): (f32, f32, f32, f32)| pixels.set_pixel(position, [r, g, b, a]),
+//! ).unwrap();
+//!
+//! let bytes: &[u8] = pixels_as_bytes(&image.layer_data.channel_data.pixels);
+//! ```
+
+use ::bytemuck::Pod;
+
+use crate::error::{Error, Result};
+use crate::image::deep::DeepChannelData;
+use crate::image::pixel_vec::PixelVec;
+use crate::image::FlatSamples;
+use crate::meta::attribute::SampleType;
+use half::f16;
+
+/// The samples of a flat channel as bytes, in native byte order.
+pub fn samples_as_bytes(samples: &FlatSamples) -> &[u8] {
+    match samples {
+        FlatSamples::F16(samples) => ::bytemuck::cast_slice(samples),
+        FlatSamples::F32(samples) => ::bytemuck::cast_slice(samples),
+        FlatSamples::U32(samples) => ::bytemuck::cast_slice(samples),
+    }
+}
+
+/// The samples of a flat channel as mutable bytes, in native byte order.
+pub fn samples_as_bytes_mut(samples: &mut FlatSamples) -> &mut [u8] {
+    match samples {
+        FlatSamples::F16(samples) => ::bytemuck::cast_slice_mut(samples),
+        FlatSamples::F32(samples) => ::bytemuck::cast_slice_mut(samples),
+        FlatSamples::U32(samples) => ::bytemuck::cast_slice_mut(samples),
+    }
+}
+
+/// The samples of a deep channel as bytes, in native byte order.
+pub fn deep_samples_as_bytes(samples: &DeepChannelData) -> &[u8] {
+    match samples {
+        DeepChannelData::F16(samples) => ::bytemuck::cast_slice(samples),
+        DeepChannelData::F32(samples) => ::bytemuck::cast_slice(samples),
+        DeepChannelData::U32(samples) => ::bytemuck::cast_slice(samples),
+    }
+}
+
+/// Copy samples of the specified type from bytes in native byte order.
+/// Returns an error if the number of bytes is not a multiple of the sample size.
+pub fn samples_from_bytes(sample_type: SampleType, bytes: &[u8]) -> Result<FlatSamples> {
+    if bytes.len() % sample_type.bytes_per_sample() != 0 {
+        return Err(Error::invalid(format!(
+            "{} bytes cannot contain whole samples of {} bytes",
+            bytes.len(),
+            sample_type.bytes_per_sample()
+        )));
+    }
+
+    Ok(match sample_type {
+        SampleType::F16 => FlatSamples::F16(::bytemuck::pod_collect_to_vec::<u8, f16>(bytes)),
+        SampleType::F32 => FlatSamples::F32(::bytemuck::pod_collect_to_vec::<u8, f32>(bytes)),
+        SampleType::U32 => FlatSamples::U32(::bytemuck::pod_collect_to_vec::<u8, u32>(bytes)),
+    })
+}
+
+/// All pixels as bytes, row after row, in native byte order.
+pub fn pixels_as_bytes<Pixel: Pod>(pixels: &PixelVec<Pixel>) -> &[u8] {
+    ::bytemuck::cast_slice(&pixels.pixels)
+}
+
+/// All pixels as mutable bytes, row after row, in native byte order.
+pub fn pixels_as_bytes_mut<Pixel: Pod>(pixels: &mut PixelVec<Pixel>) -> &mut [u8] {
+    ::bytemuck::cast_slice_mut(&mut pixels.pixels)
+}
+
+/// All samples of all pixels, pixel after pixel, for example `r, g, b, a, r, g, b, a, ...`.
+pub fn pixels_as_samples<Sample: Pod, const CHANNELS: usize>(
+    pixels: &PixelVec<[Sample; CHANNELS]>,
+) -> &[Sample] {
+    ::bytemuck::cast_slice(&pixels.pixels)
+}
+
+/// All samples of all pixels as a mutable slice, pixel after pixel.
+pub fn pixels_as_samples_mut<Sample: Pod, const CHANNELS: usize>(
+    pixels: &mut PixelVec<[Sample; CHANNELS]>,
+) -> &mut [Sample] {
+    ::bytemuck::cast_slice_mut(&mut pixels.pixels)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::Vec2;
+
+    #[test]
+    fn flat_samples_round_trip() {
+        let samples = FlatSamples::F16(vec![f16::from_f32(0.5), f16::from_f32(-2.0), f16::ONE]);
+        let bytes = samples_as_bytes(&samples);
+        assert_eq!(bytes.len(), 6);
+        assert_eq!(samples_from_bytes(SampleType::F16, bytes).unwrap(), samples);
+
+        let mut samples = FlatSamples::U32(vec![1, 2]);
+        samples_as_bytes_mut(&mut samples)[4..].copy_from_slice(&7_u32.to_ne_bytes());
+        assert_eq!(samples, FlatSamples::U32(vec![1, 7]));
+
+        assert!(samples_from_bytes(SampleType::F32, &[0; 6]).is_err());
+    }
+
+    #[test]
+    fn array_pixels() {
+        let mut pixels = PixelVec::new(Vec2(2, 1), vec![[0.0_f32, 1.0, 2.0], [3.0, 4.0, 5.0]]);
+        assert_eq!(pixels_as_bytes(&pixels).len(), 24);
+        assert_eq!(pixels_as_samples(&pixels), &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        pixels_as_samples_mut(&mut pixels)[3] = 9.0;
+        assert_eq!(pixels.pixels[1], [9.0, 4.0, 5.0]);
+
+        let deep = DeepChannelData::F32(vec![1.0, 2.0]);
+        assert_eq!(
+            deep_samples_as_bytes(&deep),
+            ::bytemuck::cast_slice::<f32, u8>(&[1.0, 2.0])
+        );
+    }
+}
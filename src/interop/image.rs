@@ -0,0 +1,251 @@
+//! Convert between exr layers and the buffers of the `image` crate.
+//! Enable with the `image` feature.
+//!
+//! Sample values are copied without any color conversion.
+//! Integer samples of the `image` crate are mapped to the range `0.0 ..= 1.0`,
+//! as done by `DynamicImage::to_rgba32f`. Apply a transfer function yourself
+//! if the integer image contains sRGB encoded colors.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//!
+//! let texture = image::open("texture.png").unwrap();
+//! let layer = Layer::<AnyChannels<FlatSamples>>::from(&texture);
+//! Image::from_layer(layer).write().to_file("texture.exr").unwrap();
+//!
+//! let image = read_first_rgba_layer_from_file(
+//!     "texture.exr",
+//!     exr::image::pixel_vec::PixelVec::<(f32, f32, f32, f32)>::constructor,
+//!     exr::image::pixel_vec::PixelVec::set_pixel,
+//! ).unwrap();
+//!
+//! let buffer = image::Rgba32FImage::from(&image.layer_data);
+//! ```
+
+use ::image::{DynamicImage, Rgb32FImage, Rgba32FImage};
+use smallvec::SmallVec;
+use std::convert::TryFrom;
+
+use crate::error::{Error, Result};
+use crate::image::pixel_vec::PixelVec;
+use crate::image::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Layer, RgbaChannels, SpecificChannels,
+};
+use crate::math::Vec2;
+use crate::meta::attribute::{ChannelDescription, SampleType};
+use crate::meta::header::LayerAttributes;
+
+/// Rgba pixels with `f32` samples, in the same layout as `Rgba32FImage`.
+pub type RgbaPixels = PixelVec<(f32, f32, f32, f32)>;
+
+/// A layer with red, green, blue, and alpha channels, as read by `read_first_rgba_layer_from_file`
+/// with the `RgbaPixels` storage.
+pub type RgbaLayer = Layer<SpecificChannels<RgbaPixels, RgbaChannels>>;
+
+impl From<&RgbaPixels> for Rgba32FImage {
+    fn from(pixels: &RgbaPixels) -> Self {
+        let samples = pixels
+            .pixels
+            .iter()
+            .flat_map(|&(r, g, b, a)| [r, g, b, a])
+            .collect();
+
+        Rgba32FImage::from_raw(
+            pixels.resolution.width() as u32,
+            pixels.resolution.height() as u32,
+            samples,
+        )
+        .expect("pixel vector length does not match its resolution")
+    }
+}
+
+impl From<&Rgba32FImage> for RgbaPixels {
+    fn from(image: &Rgba32FImage) -> Self {
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b, a] = pixel.0;
+                (r, g, b, a)
+            })
+            .collect();
+
+        PixelVec::new(
+            Vec2(image.width() as usize, image.height() as usize),
+            pixels,
+        )
+    }
+}
+
+impl From<&RgbaLayer> for Rgba32FImage {
+    fn from(layer: &RgbaLayer) -> Self {
+        Rgba32FImage::from(&layer.channel_data.pixels)
+    }
+}
+
+/// Creates a layer with `f32` samples and default attributes.
+impl From<&Rgba32FImage> for RgbaLayer {
+    fn from(image: &Rgba32FImage) -> Self {
+        let pixels = RgbaPixels::from(image);
+        let channel = |name: &str| ChannelDescription::named(name, SampleType::F32);
+
+        Layer {
+            size: pixels.resolution,
+            channel_data: SpecificChannels {
+                channels: (channel("R"), channel("G"), channel("B"), Some(channel("A"))),
+                pixels,
+            },
+            attributes: LayerAttributes::default(),
+            encoding: Encoding::default(),
+        }
+    }
+}
+
+impl From<Rgba32FImage> for RgbaLayer {
+    fn from(image: Rgba32FImage) -> Self {
+        RgbaLayer::from(&image)
+    }
+}
+
+/// Creates a layer with `f32` samples and default attributes.
+/// Gray images contain a `Y` channel, colored images contain `R`, `G`, and `B` channels,
+/// and an `A` channel is added if the image has alpha.
+impl From<&DynamicImage> for Layer<AnyChannels<FlatSamples>> {
+    fn from(image: &DynamicImage) -> Self {
+        let rgba = image.to_rgba32f();
+        let size = Vec2(rgba.width() as usize, rgba.height() as usize);
+
+        let channel = |name: &str, index: usize| {
+            let samples = rgba.pixels().map(|pixel| pixel.0[index]).collect();
+            AnyChannel::new(name, FlatSamples::F32(samples))
+        };
+
+        let mut channels = SmallVec::new();
+
+        if image.color().has_color() {
+            channels.push(channel("R", 0));
+            channels.push(channel("G", 1));
+            channels.push(channel("B", 2));
+        } else {
+            channels.push(channel("Y", 0));
+        }
+
+        if image.color().has_alpha() {
+            channels.push(channel("A", 3));
+        }
+
+        Layer::new(
+            size,
+            LayerAttributes::default(),
+            Encoding::default(),
+            AnyChannels::sort(channels),
+        )
+    }
+}
+
+/// Uses the `R`, `G`, `B`, and `A` channels, or the `Y` and `A` channels, of the layer.
+/// The result is an `Rgba32F` image if the layer has alpha, and an `Rgb32F` image otherwise.
+/// Gray layers are converted to rgb, because the `image` crate has no gray `f32` images.
+impl TryFrom<&Layer<AnyChannels<FlatSamples>>> for DynamicImage {
+    type Error = Error;
+
+    fn try_from(layer: &Layer<AnyChannels<FlatSamples>>) -> Result<Self> {
+        let find = |name: &str| -> Result<Option<Vec<f32>>> {
+            let channel = layer
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.name.eq(name));
+
+            match channel {
+                None => Ok(None),
+                Some(channel) if channel.sampling != Vec2(1, 1) => Err(Error::unsupported(
+                    "conversion of subsampled channels to an image buffer",
+                )),
+                Some(channel) => Ok(Some(channel.sample_data.to_f32_vec())),
+            }
+        };
+
+        let colors = match (find("R")?, find("G")?, find("B")?) {
+            (Some(red), Some(green), Some(blue)) => [red, green, blue],
+            _ => match find("Y")? {
+                Some(luminance) => [luminance.clone(), luminance.clone(), luminance],
+                None => {
+                    return Err(Error::invalid(
+                        "layer has neither rgb channels nor a luminance channel",
+                    ))
+                }
+            },
+        };
+
+        let (width, height) = (layer.size.width() as u32, layer.size.height() as u32);
+        let invalid_size = || Error::invalid("image buffer size");
+
+        match find("A")? {
+            Some(alpha) => {
+                let samples = (0..layer.size.area())
+                    .flat_map(|index| {
+                        [
+                            colors[0][index],
+                            colors[1][index],
+                            colors[2][index],
+                            alpha[index],
+                        ]
+                    })
+                    .collect();
+
+                Rgba32FImage::from_raw(width, height, samples)
+                    .map(DynamicImage::ImageRgba32F)
+                    .ok_or_else(invalid_size)
+            }
+
+            None => {
+                let samples = (0..layer.size.area())
+                    .flat_map(|index| [colors[0][index], colors[1][index], colors[2][index]])
+                    .collect();
+
+                Rgb32FImage::from_raw(width, height, samples)
+                    .map(DynamicImage::ImageRgb32F)
+                    .ok_or_else(invalid_size)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::image::{GrayAlphaImage, LumaA, Rgba};
+
+    #[test]
+    fn rgba_buffer_round_trip() {
+        let buffer = Rgba32FImage::from_fn(3, 2, |x, y| Rgba([x as f32, y as f32, 0.5, 1.0]));
+
+        let layer = RgbaLayer::from(&buffer);
+        assert_eq!(layer.size, Vec2(3, 2));
+        assert_eq!(
+            *layer.channel_data.pixels.get_pixel(Vec2(2, 1)),
+            (2.0, 1.0, 0.5, 1.0)
+        );
+
+        assert_eq!(Rgba32FImage::from(&layer), buffer);
+    }
+
+    #[test]
+    fn dynamic_image_round_trip() {
+        let gray = GrayAlphaImage::from_fn(4, 4, |x, _| LumaA([x as u8 * 85, 255]));
+        let gray = DynamicImage::ImageLumaA8(gray);
+
+        let layer = Layer::<AnyChannels<FlatSamples>>::from(&gray);
+        let names: Vec<String> = layer
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+        assert_eq!(names, vec!["A", "Y"]);
+
+        let rgba = DynamicImage::try_from(&layer).unwrap().to_rgba32f();
+        assert_eq!(rgba.get_pixel(3, 0).0, [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(rgba.get_pixel(0, 2).0, [0.0, 0.0, 0.0, 1.0]);
+    }
+}
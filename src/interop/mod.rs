@@ -0,0 +1,45 @@
+//! Conversions between the pixel buffers of this crate and the types of other crates.
+//! Each conversion is enabled by a feature with the name of the other crate.
+
+use crate::image::deep::DeepSamples;
+use crate::image::{AnyChannels, FlatSamples, Layer, Levels};
+
+/// A layer with any flat channels, as read by `read_all_flat_layers_from_file`.
+pub type FlatLayer = Layer<AnyChannels<FlatSamples>>;
+
+/// A layer with any flat channels and all resolution levels, as read by `read_all_data_from_file`.
+pub type LeveledLayer = Layer<AnyChannels<Levels<FlatSamples>>>;
+
+/// A layer with any deep channels, as read by `read_all_deep_layers_from_file`.
+/// The samples of all channels are stored in the first channel.
+pub type DeepLayer = Layer<AnyChannels<DeepSamples>>;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+
+#[cfg(feature = "bytemuck")]
+pub mod bytemuck;
+
+#[cfg(feature = "image")]
+pub mod image;
+
+#[cfg(feature = "ndarray")]
+pub mod ndarray;
+
+#[cfg(feature = "numpy")]
+pub mod numpy;
+
+#[cfg(feature = "ocio")]
+pub mod ocio;
+
+#[cfg(feature = "rerun")]
+pub mod rerun;
+
+#[cfg(feature = "tev")]
+pub mod tev;
+
+#[cfg(feature = "texture")]
+pub mod texture;
+
+#[cfg(feature = "wgpu")]
+pub mod wgpu;
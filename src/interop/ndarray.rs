@@ -0,0 +1,206 @@
+//! Access the channels of exr layers as `ndarray` arrays, and create layers from arrays.
+//! Enable with the `ndarray` feature.
+//!
+//! Arrays are indexed by `[y, x]`, or by `[y, x, channel]`, like images in numpy.
+//! Channels that contain `f32` samples can be viewed without copying.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//! use exr::interop::ndarray::{channel_view, layer_from_arrays};
+//!
+//! let image = read_all_flat_layers_from_file("render.exr").unwrap();
+//! let depth = channel_view(&image.layer_data[0], "Z").unwrap();
+//! let normalized = depth.mapv(|z| z / 100.0);
+//!
+//! let layer = layer_from_arrays(vec![("Z", normalized.view())]).unwrap();
+//! Image::from_layer(layer).write().to_file("depth.exr").unwrap();
+//! ```
+
+use ::ndarray::{Array2, Array3, ArrayView2, ArrayView3, ArrayViewMut2};
+use smallvec::SmallVec;
+
+use super::FlatLayer;
+use crate::error::{Error, Result};
+use crate::image::{AnyChannel, AnyChannels, Encoding, FlatSamples, Layer};
+use crate::math::Vec2;
+use crate::meta::attribute::Text;
+use crate::meta::header::LayerAttributes;
+
+fn find_channel<'l>(layer: &'l FlatLayer, name: &str) -> Result<&'l AnyChannel<FlatSamples>> {
+    layer
+        .channel_data
+        .list
+        .iter()
+        .find(|channel| channel.name.eq(name))
+        .ok_or_else(|| Error::invalid(format!("layer has no channel named `{}`", name)))
+}
+
+/// The array shape `(height, width)` of the channel, considering subsampling.
+fn channel_shape(layer_size: Vec2<usize>, channel: &AnyChannel<FlatSamples>) -> (usize, usize) {
+    let resolution = layer_size / channel.sampling;
+    (resolution.height(), resolution.width())
+}
+
+/// View the `f32` samples of a channel without copying them.
+/// Returns an error for `f16` and `u32` channels, use `channel_array` for those.
+pub fn channel_view<'l>(layer: &'l FlatLayer, name: &str) -> Result<ArrayView2<'l, f32>> {
+    let channel = find_channel(layer, name)?;
+
+    match &channel.sample_data {
+        FlatSamples::F32(samples) => {
+            ArrayView2::from_shape(channel_shape(layer.size, channel), samples)
+                .map_err(|_| Error::invalid("channel sample count"))
+        }
+
+        _ => Err(Error::unsupported(
+            "viewing channels other than f32 without copying",
+        )),
+    }
+}
+
+/// Modify the `f32` samples of a channel in place.
+/// Returns an error for `f16` and `u32` channels.
+pub fn channel_view_mut<'l>(
+    layer: &'l mut FlatLayer,
+    name: &str,
+) -> Result<ArrayViewMut2<'l, f32>> {
+    let layer_size = layer.size;
+    let channel = layer
+        .channel_data
+        .list
+        .iter_mut()
+        .find(|channel| channel.name.eq(name))
+        .ok_or_else(|| Error::invalid(format!("layer has no channel named `{}`", name)))?;
+
+    let shape = channel_shape(layer_size, channel);
+
+    match &mut channel.sample_data {
+        FlatSamples::F32(samples) => ArrayViewMut2::from_shape(shape, samples)
+            .map_err(|_| Error::invalid("channel sample count")),
+
+        _ => Err(Error::unsupported(
+            "viewing channels other than f32 without copying",
+        )),
+    }
+}
+
+/// Copy the samples of a channel of any sample type, converted to `f32`.
+pub fn channel_array(layer: &FlatLayer, name: &str) -> Result<Array2<f32>> {
+    let channel = find_channel(layer, name)?;
+    let samples = channel.sample_data.to_f32_vec();
+
+    Array2::from_shape_vec(channel_shape(layer.size, channel), samples)
+        .map_err(|_| Error::invalid("channel sample count"))
+}
+
+/// Copy the specified channels into a single array with the shape `(height, width, channels)`.
+/// Subsampled channels are not supported.
+pub fn layer_array(layer: &FlatLayer, names: &[&str]) -> Result<Array3<f32>> {
+    let channels = names
+        .iter()
+        .map(|name| {
+            let channel = find_channel(layer, name)?;
+
+            if channel.sampling != Vec2(1, 1) {
+                return Err(Error::unsupported("combining subsampled channels"));
+            }
+
+            Ok(channel)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let size = layer.size;
+    Ok(Array3::from_shape_fn(
+        (size.height(), size.width(), channels.len()),
+        |(y, x, channel)| {
+            channels[channel]
+                .sample_data
+                .value_by_flat_index(Vec2(x, y).flat_index_for_size(size))
+                .to_f32()
+        },
+    ))
+}
+
+/// Create a layer with `f32` channels from arrays that all have the same shape `(height, width)`.
+/// Uses default attributes and encoding.
+pub fn layer_from_arrays<'a, 'n>(
+    channels: impl IntoIterator<Item = (&'n str, ArrayView2<'a, f32>)>,
+) -> Result<FlatLayer> {
+    let mut shape = None;
+    let mut list = SmallVec::new();
+
+    for (name, array) in channels {
+        if *shape.get_or_insert(array.dim()) != array.dim() {
+            return Err(Error::invalid("all arrays must have the same shape"));
+        }
+
+        let name = Text::new_or_none(name).ok_or_else(|| Error::invalid("channel name"))?;
+        let samples = array.iter().copied().collect();
+        list.push(AnyChannel::new(name, FlatSamples::F32(samples)));
+    }
+
+    let (height, width) =
+        shape.ok_or_else(|| Error::invalid("at least one channel is required"))?;
+
+    Ok(Layer::new(
+        Vec2(width, height),
+        LayerAttributes::default(),
+        Encoding::default(),
+        AnyChannels::sort(list),
+    ))
+}
+
+/// Create a layer with `f32` channels from an array with the shape `(height, width, channels)`,
+/// such as an rgb image in numpy layout. There must be one name for each channel.
+pub fn layer_from_array3(names: &[&str], array: ArrayView3<'_, f32>) -> Result<FlatLayer> {
+    let (_, _, channel_count) = array.dim();
+
+    if names.len() != channel_count {
+        return Err(Error::invalid("one name is required for each channel"));
+    }
+
+    layer_from_arrays(
+        names
+            .iter()
+            .enumerate()
+            .map(|(index, &name)| (name, array.index_axis(::ndarray::Axis(2), index))),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::ndarray::arr2;
+
+    #[test]
+    fn arrays_round_trip() {
+        let red = arr2(&[[0.0, 0.25, 0.5], [0.75, 1.0, 1.25]]);
+        let green = red.mapv(|value| value * 2.0);
+
+        let mut layer = layer_from_arrays(vec![("R", red.view()), ("G", green.view())]).unwrap();
+        assert_eq!(layer.size, Vec2(3, 2));
+        assert_eq!(channel_view(&layer, "R").unwrap(), red);
+        assert_eq!(channel_array(&layer, "G").unwrap(), green);
+        assert!(channel_view(&layer, "B").is_err());
+
+        channel_view_mut(&mut layer, "R").unwrap()[[1, 2]] = 7.0;
+        assert_eq!(channel_view(&layer, "R").unwrap()[[1, 2]], 7.0);
+
+        let combined = layer_array(&layer, &["R", "G"]).unwrap();
+        assert_eq!(combined.dim(), (2, 3, 2));
+        assert_eq!(combined[[0, 1, 1]], 0.5);
+        assert_eq!(combined[[1, 2, 0]], 7.0);
+    }
+
+    #[test]
+    fn three_dimensional_arrays() {
+        let pixels = Array3::from_shape_fn((4, 5, 3), |(y, x, channel)| {
+            (y * 100 + x * 10 + channel) as f32
+        });
+        let layer = layer_from_array3(&["R", "G", "B"], pixels.view()).unwrap();
+
+        assert_eq!(layer.size, Vec2(5, 4));
+        assert_eq!(layer_array(&layer, &["R", "G", "B"]).unwrap(), pixels);
+        assert!(layer_from_array3(&["R", "G"], pixels.view()).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+//! Export channels as NumPy `.npy` arrays and `.npz` archives, keeping the sample type.
+//! Enable with the `numpy` feature, which adds no dependencies.
+//!
+//! `f16`, `f32`, and `u32` channels are stored with the NumPy types `float16`, `float32`, and `uint32`.
+//! A single channel is stored as an array with the shape `(height, width)`,
+//! and multiple channels as an array with the shape `(height, width, channels)`.
+//! An `.npz` archive contains one array per channel, named like the channel,
+//! so that `numpy.load("render.npz")["diffuse.R"]` returns the `diffuse.R` channel.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//! use exr::interop::numpy::{write_channels_npy, write_npz};
+//!
+//! let image = read_all_flat_layers_from_file("render.exr").unwrap();
+//! let layer = &image.layer_data[0];
+//!
+//! let file = std::fs::File::create("rgb.npy").unwrap();
+//! write_channels_npy(std::io::BufWriter::new(file), layer, &["R", "G", "B"]).unwrap();
+//!
+//! let file = std::fs::File::create("render.npz").unwrap();
+//! write_npz(std::io::BufWriter::new(file), layer, &[], false).unwrap();
+//! ```
+
+use std::convert::TryFrom;
+use std::io::Write;
+
+use super::FlatLayer;
+use crate::error::{Error, Result, UnitResult};
+use crate::image::write::samples::WritableSamples;
+use crate::image::{AnyChannel, FlatSamples};
+use crate::math::Vec2;
+use crate::meta::attribute::SampleType;
+
+/// Write one channel as an array with the shape `(height, width)`.
+/// The shape of subsampled channels is divided by their sampling rate.
+pub fn write_channel_npy(write: impl Write, layer: &FlatLayer, channel: &str) -> UnitResult {
+    write_channels_npy(write, layer, &[channel])
+}
+
+/// Write the specified channels as one array with the shape `(height, width, channels)`,
+/// or all channels of the layer if no channels are specified. A single specified channel
+/// is written with the shape `(height, width)`. Returns an error if the channels
+/// do not have the same sample type or the same sampling rate.
+pub fn write_channels_npy(
+    mut write: impl Write,
+    layer: &FlatLayer,
+    channels: &[&str],
+) -> UnitResult {
+    let channels = find_channels(layer, channels)?;
+    let single = channels.len() == 1;
+
+    write.write_all(&npy_bytes(layer.size, &channels, single)?)?;
+    write.flush()?;
+    Ok(())
+}
+
+/// Write an `.npz` archive with one `(height, width)` array per channel,
+/// or with all channels of the layer if no channels are specified.
+/// The arrays are compressed with deflate if `compress` is true,
+/// like `numpy.savez_compressed`, and stored like `numpy.savez` otherwise.
+pub fn write_npz(
+    mut write: impl Write,
+    layer: &FlatLayer,
+    channels: &[&str],
+    compress: bool,
+) -> UnitResult {
+    let channels = find_channels(layer, channels)?;
+    let mut archive = ZipWriter::default();
+
+    for channel in channels {
+        let array = npy_bytes(layer.size, &[channel], true)?;
+        let file_name = format!("{}.npy", channel.name);
+        archive.add_file(&mut write, &file_name, &array, compress)?;
+    }
+
+    archive.finish(&mut write)?;
+    write.flush()?;
+    Ok(())
+}
+
+/// The NumPy type description of a sample type, always little endian.
+pub fn numpy_dtype(sample_type: SampleType) -> &'static str {
+    match sample_type {
+        SampleType::F16 => "<f2",
+        SampleType::F32 => "<f4",
+        SampleType::U32 => "<u4",
+    }
+}
+
+/// The channels with the specified names, or all channels if no names are specified.
+fn find_channels<'l>(
+    layer: &'l FlatLayer,
+    names: &[&str],
+) -> Result<Vec<&'l AnyChannel<FlatSamples>>> {
+    if names.is_empty() {
+        return Ok(layer.channel_data.list.iter().collect());
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            layer
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.name.eq(name))
+                .ok_or_else(|| Error::invalid(format!("layer has no channel named `{}`", name)))
+        })
+        .collect()
+}
+
+/// A complete `.npy` file with the samples of all channels interleaved, pixel after pixel.
+/// The last dimension is omitted if `single` is true.
+fn npy_bytes(
+    layer_size: Vec2<usize>,
+    channels: &[&AnyChannel<FlatSamples>],
+    single: bool,
+) -> Result<Vec<u8>> {
+    let first = channels
+        .first()
+        .ok_or_else(|| Error::invalid("no channels to export"))?;
+
+    let sample_type = first.sample_data.sample_type();
+    let sampling = first.sampling;
+
+    for channel in channels {
+        if channel.sample_data.sample_type() != sample_type {
+            return Err(Error::invalid(
+                "channels with different sample types cannot be stored in one array",
+            ));
+        }
+
+        if channel.sampling != sampling {
+            return Err(Error::invalid(
+                "channels with different sampling rates cannot be stored in one array",
+            ));
+        }
+    }
+
+    let resolution = layer_size / sampling;
+    let shape = if single {
+        format!("({}, {})", resolution.height(), resolution.width())
+    } else {
+        format!(
+            "({}, {}, {})",
+            resolution.height(),
+            resolution.width(),
+            channels.len()
+        )
+    };
+
+    let mut bytes = npy_header(numpy_dtype(sample_type), &shape)?;
+    let sample_count = resolution.area();
+    bytes.reserve(sample_count * channels.len() * sample_type.bytes_per_sample());
+
+    for index in 0..sample_count {
+        for channel in channels {
+            match &channel.sample_data {
+                FlatSamples::F16(samples) => bytes.extend(samples[index].to_le_bytes()),
+                FlatSamples::F32(samples) => bytes.extend(samples[index].to_le_bytes()),
+                FlatSamples::U32(samples) => bytes.extend(samples[index].to_le_bytes()),
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// The magic bytes, version 1.0, and the header dictionary, padded to a multiple of 64 bytes.
+fn npy_header(dtype: &str, shape: &str) -> Result<Vec<u8>> {
+    const PREAMBLE_SIZE: usize = 10;
+
+    let mut dictionary = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        dtype, shape
+    );
+
+    // the header ends with a newline, which is included in the padding
+    let unpadded_size = PREAMBLE_SIZE + dictionary.len() + 1;
+    let padding = (64 - unpadded_size % 64) % 64;
+    dictionary.extend(std::iter::repeat(' ').take(padding));
+    dictionary.push('\n');
+
+    let header_size = u16::try_from(dictionary.len())
+        .map_err(|_| Error::unsupported("numpy header larger than 64 kilobytes"))?;
+
+    let mut bytes = Vec::with_capacity(PREAMBLE_SIZE + dictionary.len());
+    bytes.extend_from_slice(b"\x93NUMPY");
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&header_size.to_le_bytes());
+    bytes.extend_from_slice(dictionary.as_bytes());
+    Ok(bytes)
+}
+
+/// Writes a zip archive without seeking, as all file sizes are known in advance.
+/// Archives larger than 4 gigabytes, which would require zip64, are not supported.
+#[derive(Default)]
+struct ZipWriter {
+    /// The central directory entries, written after all files.
+    directory: Vec<u8>,
+    file_count: u16,
+    offset: u32,
+}
+
+impl ZipWriter {
+    /// The date of all files, 1980-01-01, so that archives do not depend on the current time.
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    fn add_file(
+        &mut self,
+        write: &mut impl Write,
+        name: &str,
+        data: &[u8],
+        compress: bool,
+    ) -> UnitResult {
+        let too_large = || Error::unsupported("npz archives larger than 4 gigabytes");
+
+        let compressed;
+        let (method, stored): (u16, &[u8]) = if compress {
+            compressed = miniz_oxide::deflate::compress_to_vec(data, 6);
+            (8, &compressed)
+        } else {
+            (0, data)
+        };
+
+        let checksum = crc32(data);
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let stored_size = u32::try_from(stored.len()).map_err(|_| too_large())?;
+        let name_size = u16::try_from(name.len()).map_err(|_| Error::invalid("file name"))?;
+
+        let mut local = Vec::with_capacity(30 + name.len());
+        local.extend_from_slice(&0x0403_4b50_u32.to_le_bytes());
+        local.extend_from_slice(&20_u16.to_le_bytes()); // version needed to extract
+        local.extend_from_slice(&0_u16.to_le_bytes()); // flags
+        local.extend_from_slice(&method.to_le_bytes());
+        local.extend_from_slice(&0_u16.to_le_bytes()); // time
+        local.extend_from_slice(&Self::DOS_DATE.to_le_bytes());
+        local.extend_from_slice(&checksum.to_le_bytes());
+        local.extend_from_slice(&stored_size.to_le_bytes());
+        local.extend_from_slice(&size.to_le_bytes());
+        local.extend_from_slice(&name_size.to_le_bytes());
+        local.extend_from_slice(&0_u16.to_le_bytes()); // extra field size
+        local.extend_from_slice(name.as_bytes());
+
+        let directory = &mut self.directory;
+        directory.extend_from_slice(&0x0201_4b50_u32.to_le_bytes());
+        directory.extend_from_slice(&20_u16.to_le_bytes()); // version made by
+        directory.extend_from_slice(&local[4..30]); // same as the local header
+        directory.extend_from_slice(&0_u16.to_le_bytes()); // comment size
+        directory.extend_from_slice(&0_u16.to_le_bytes()); // disk number
+        directory.extend_from_slice(&0_u16.to_le_bytes()); // internal attributes
+        directory.extend_from_slice(&0_u32.to_le_bytes()); // external attributes
+        directory.extend_from_slice(&self.offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+
+        write.write_all(&local)?;
+        write.write_all(stored)?;
+
+        self.file_count = self.file_count.checked_add(1).ok_or_else(too_large)?;
+        self.offset = u32::try_from(local.len() + stored.len())
+            .ok()
+            .and_then(|size| self.offset.checked_add(size))
+            .ok_or_else(too_large)?;
+
+        Ok(())
+    }
+
+    fn finish(self, write: &mut impl Write) -> UnitResult {
+        let directory_size = u32::try_from(self.directory.len())
+            .map_err(|_| Error::unsupported("npz archives larger than 4 gigabytes"))?;
+
+        write.write_all(&self.directory)?;
+
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4b50_u32.to_le_bytes());
+        end.extend_from_slice(&0_u16.to_le_bytes()); // disk number
+        end.extend_from_slice(&0_u16.to_le_bytes()); // disk with the directory
+        end.extend_from_slice(&self.file_count.to_le_bytes());
+        end.extend_from_slice(&self.file_count.to_le_bytes());
+        end.extend_from_slice(&directory_size.to_le_bytes());
+        end.extend_from_slice(&self.offset.to_le_bytes());
+        end.extend_from_slice(&0_u16.to_le_bytes()); // comment size
+        write.write_all(&end)?;
+        Ok(())
+    }
+}
+
+/// The checksum used by zip archives.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::{AnyChannels, Encoding, Layer};
+    use crate::meta::header::LayerAttributes;
+    use half::f16;
+    use smallvec::smallvec;
+
+    fn layer() -> FlatLayer {
+        let channels = AnyChannels::sort(smallvec![
+            AnyChannel::new("B", FlatSamples::F16(vec![f16::ONE; 6])),
+            AnyChannel::new("G", FlatSamples::F16(vec![f16::ZERO; 6])),
+            AnyChannel::new("R", FlatSamples::F16(vec![f16::from_f32(0.5); 6])),
+            AnyChannel::new("id", FlatSamples::U32(vec![7; 6])),
+        ]);
+
+        Layer {
+            channel_data: channels,
+            attributes: LayerAttributes::default(),
+            size: Vec2(3, 2),
+            encoding: Encoding::default(),
+        }
+    }
+
+    #[test]
+    fn header_is_aligned() {
+        let mut bytes = Vec::new();
+        write_channel_npy(&mut bytes, &layer(), "id").unwrap();
+
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_size = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_size) % 64, 0);
+
+        let header = std::str::from_utf8(&bytes[10..10 + header_size]).unwrap();
+        assert!(header.starts_with("{'descr': '<u4', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with('\n'));
+
+        assert_eq!(bytes.len(), 10 + header_size + 6 * 4);
+        assert_eq!(&bytes[bytes.len() - 4..], &7_u32.to_le_bytes());
+    }
+
+    #[test]
+    fn interleave_channels() {
+        let mut bytes = Vec::new();
+        write_channels_npy(&mut bytes, &layer(), &["R", "G", "B"]).unwrap();
+
+        let header_size = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = std::str::from_utf8(&bytes[10..10 + header_size]).unwrap();
+        assert!(header.contains("'descr': '<f2'"));
+        assert!(header.contains("'shape': (2, 3, 3)"));
+
+        let data = &bytes[10 + header_size..];
+        assert_eq!(data.len(), 6 * 3 * 2);
+        assert_eq!(&data[..2], &f16::from_f32(0.5).to_le_bytes());
+        assert_eq!(&data[2..4], &f16::ZERO.to_le_bytes());
+        assert_eq!(&data[4..6], &f16::ONE.to_le_bytes());
+
+        // all channels include the u32 channel
+        assert!(write_channels_npy(Vec::new(), &layer(), &[]).is_err());
+        assert!(write_channels_npy(Vec::new(), &layer(), &["Z"]).is_err());
+    }
+
+    #[test]
+    fn archive_contains_all_channels() {
+        for compress in [false, true] {
+            let mut bytes = Vec::new();
+            write_npz(&mut bytes, &layer(), &[], compress).unwrap();
+
+            assert_eq!(&bytes[..4], &0x0403_4b50_u32.to_le_bytes());
+
+            let end = &bytes[bytes.len() - 22..];
+            assert_eq!(&end[..4], &0x0605_4b50_u32.to_le_bytes());
+            assert_eq!(u16::from_le_bytes([end[10], end[11]]), 4);
+
+            let names = ["B.npy", "G.npy", "R.npy", "id.npy"];
+            for name in names {
+                assert!(bytes
+                    .windows(name.len())
+                    .any(|window| window == name.as_bytes()));
+            }
+        }
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,1728 @@
+//! Color transforms defined by OpenColorIO configs, so that pixels are displayed
+//! like in the compositing tools that use the same studio config.
+//! Enable with the `ocio` feature, which adds `serde_yaml` to read the configs.
+//!
+//! Configs of version 1 and 2 are supported, with color spaces, roles, aliases, displays,
+//! views, shared views, looks, and view transforms. The transforms are evaluated on the CPU:
+//! matrices, exponents, logarithms, CDLs, ranges, LUT files (`.cube`, `.spi1d`, `.spi3d`),
+//! and a few built-in transforms of the ACES configs. Other transforms return an error,
+//! notably the built-in ACES output transforms, and inverse 3D LUTs.
+//!
+//! ```no_run
+//! use exr::interop::ocio::OcioConfig;
+//!
+//! let config = OcioConfig::from_file("studio/config.ocio").unwrap();
+//! let display = config.default_display().unwrap();
+//! let view = config.default_view(display).unwrap();
+//!
+//! let processor = config.display_processor("scene_linear", display, view).unwrap();
+//! let display_color = processor.apply_rgb([0.18, 0.18, 0.18]);
+//! ```
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use serde_yaml::Value;
+
+use crate::error::{Error, Result, UnitResult};
+
+/// Convert linear pixels from a color space to the specified display and view,
+/// using the config of the `OCIO` environment variable.
+/// This reads the config each time, use `OcioConfig` to convert many images.
+pub fn apply_ocio_transform(
+    pixels: &mut [[f32; 3]],
+    from: &str,
+    display: &str,
+    view: &str,
+) -> UnitResult {
+    OcioConfig::from_env()?.apply_transform(pixels, from, display, view)
+}
+
+/// The color spaces, displays, and views of an OpenColorIO config.
+#[derive(Debug, Clone)]
+pub struct OcioConfig {
+    /// LUT files are searched relative to this directory.
+    directory: PathBuf,
+    search_paths: Vec<PathBuf>,
+
+    roles: Vec<(String, String)>,
+    color_spaces: Vec<ColorSpace>,
+    displays: Vec<Display>,
+    looks: Vec<Look>,
+    view_transforms: Vec<ViewTransform>,
+    default_view_transform: Option<String>,
+
+    active_displays: Vec<String>,
+    active_views: Vec<String>,
+}
+
+/// Converts colors, created by an `OcioConfig`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Processor {
+    ops: Vec<Op>,
+}
+
+#[derive(Debug, Clone)]
+struct ColorSpace {
+    name: String,
+    aliases: Vec<String>,
+
+    /// Data like normals or depth is never converted.
+    is_data: bool,
+
+    /// Whether the transforms convert from and to the display reference,
+    /// instead of the scene reference.
+    display_referred: bool,
+
+    to_reference: Option<Value>,
+    from_reference: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+struct Display {
+    name: String,
+    views: Vec<View>,
+}
+
+#[derive(Debug, Clone)]
+struct View {
+    name: String,
+    color_space: Option<String>,
+    view_transform: Option<String>,
+    display_color_space: Option<String>,
+    looks: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Look {
+    name: String,
+    process_space: String,
+    transform: Option<Value>,
+    inverse_transform: Option<Value>,
+}
+
+/// Converts from the scene reference to the display reference.
+#[derive(Debug, Clone)]
+struct ViewTransform {
+    name: String,
+    from_reference: Option<Value>,
+    to_reference: Option<Value>,
+}
+
+/// A step of a processor, already in the direction of the processor.
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    /// Multiplies colors with the matrix and adds the offset.
+    Matrix {
+        matrix: [[f32; 3]; 3],
+        offset: [f32; 3],
+    },
+
+    /// Raises colors to the exponent. Negative colors become zero.
+    Power([f32; 3]),
+
+    /// A power curve with a linear segment near zero, like sRGB.
+    /// Converts encoded to linear values, or linear to encoded values if inverse.
+    MonCurve {
+        gamma: [f32; 3],
+        offset: [f32; 3],
+        inverse: bool,
+    },
+
+    Log(LogCurve),
+    Cdl(Cdl),
+    Range(Range),
+
+    Lut1d {
+        lut: Lut1d,
+        inverse: bool,
+    },
+
+    Lut3d(Lut3d),
+
+    /// Converts ACEScct to linear values, or linear values to ACEScct if inverse.
+    AcesCct {
+        inverse: bool,
+    },
+}
+
+/// Converts linear to logarithmic values, or logarithmic to linear values if inverse.
+#[derive(Debug, Clone, PartialEq)]
+struct LogCurve {
+    base: f32,
+    log_slope: [f32; 3],
+    log_offset: [f32; 3],
+    lin_slope: [f32; 3],
+    lin_offset: [f32; 3],
+
+    /// Linear values up to the break are mapped with the slope and offset instead.
+    linear_segment: Option<LinearSegment>,
+    inverse: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LinearSegment {
+    lin_break: [f32; 3],
+    slope: [f32; 3],
+    offset: [f32; 3],
+}
+
+/// An ASC color decision list, clamped to the range from zero to one.
+#[derive(Debug, Clone, PartialEq)]
+struct Cdl {
+    slope: [f32; 3],
+    offset: [f32; 3],
+    power: [f32; 3],
+    saturation: f32,
+    inverse: bool,
+}
+
+/// Maps the input range to the output range, and clamps to the output range.
+#[derive(Debug, Clone, PartialEq)]
+struct Range {
+    min_in: Option<f32>,
+    max_in: Option<f32>,
+    min_out: Option<f32>,
+    max_out: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Lut1d {
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+
+    /// At least two entries. The inverse expects increasing values.
+    values: Vec<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Lut3d {
+    /// The number of entries per axis, at least two.
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+
+    /// The red index changes fastest.
+    values: Vec<[f32; 3]>,
+}
+
+impl OcioConfig {
+    /// Read the config of the `OCIO` environment variable.
+    pub fn from_env() -> Result<Self> {
+        let path = std::env::var_os("OCIO")
+            .ok_or_else(|| Error::invalid("the OCIO environment variable is not set"))?;
+
+        Self::from_file(path)
+    }
+
+    /// Read a config file. LUT files are searched relative to the directory of the file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let yaml = std::fs::read_to_string(path)?;
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Self::parse(&yaml, directory)
+    }
+
+    /// Parse the contents of a config file. LUT files are searched relative to the directory.
+    pub fn parse(yaml: &str, directory: impl Into<PathBuf>) -> Result<Self> {
+        let root: Value = serde_yaml::from_str(&local_tags(yaml))
+            .map_err(|error| Error::invalid(format!("OCIO config: {}", error)))?;
+
+        let mut config = OcioConfig {
+            directory: directory.into(),
+            search_paths: strings(&root, "search_path", ':')
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+
+            roles: Vec::new(),
+            color_spaces: Vec::new(),
+            displays: Vec::new(),
+            looks: Vec::new(),
+            view_transforms: Vec::new(),
+            default_view_transform: string(&root, "default_view_transform"),
+
+            active_displays: strings(&root, "active_displays", ','),
+            active_views: strings(&root, "active_views", ','),
+        };
+
+        if let Some(roles) = field(&root, "roles").and_then(Value::as_mapping) {
+            for (role, color_space) in roles {
+                if let (Some(role), Some(color_space)) = (text(role), text(color_space)) {
+                    config.roles.push((role, color_space));
+                }
+            }
+        }
+
+        for (key, display_referred) in [("colorspaces", false), ("display_colorspaces", true)] {
+            for color_space in sequence(&root, key) {
+                let color_space = ColorSpace::parse(color_space, display_referred)?;
+                config.color_spaces.push(color_space);
+            }
+        }
+
+        let shared_views = sequence(&root, "shared_views")
+            .iter()
+            .map(View::parse)
+            .collect::<Result<Vec<View>>>()?;
+
+        if let Some(displays) = field(&root, "displays").and_then(Value::as_mapping) {
+            for (name, entries) in displays {
+                let name = text(name).ok_or_else(|| Error::invalid("OCIO display name"))?;
+                let mut views = Vec::new();
+
+                for entry in untagged(entries)
+                    .as_sequence()
+                    .map_or(&[][..], Vec::as_slice)
+                {
+                    if tag(entry) != "Views" {
+                        views.push(View::parse(entry)?);
+                        continue;
+                    }
+
+                    // references to shared views
+                    for view_name in untagged(entry).as_sequence().map_or(&[][..], Vec::as_slice) {
+                        let view_name = text(view_name).unwrap_or_default();
+                        let view = shared_views
+                            .iter()
+                            .find(|view| view.name == view_name)
+                            .ok_or_else(|| {
+                                Error::invalid(format!("unknown OCIO shared view `{}`", view_name))
+                            })?;
+
+                        views.push(view.clone());
+                    }
+                }
+
+                config.displays.push(Display { name, views });
+            }
+        }
+
+        for look in sequence(&root, "looks") {
+            config.looks.push(Look {
+                name: required_string(look, "name")?,
+                process_space: required_string(look, "process_space")?,
+                transform: field(look, "transform").cloned(),
+                inverse_transform: field(look, "inverse_transform").cloned(),
+            });
+        }
+
+        for view_transform in sequence(&root, "view_transforms") {
+            config.view_transforms.push(ViewTransform {
+                name: required_string(view_transform, "name")?,
+                from_reference: field(view_transform, "from_scene_reference").cloned(),
+                to_reference: field(view_transform, "to_scene_reference").cloned(),
+            });
+        }
+
+        Ok(config)
+    }
+
+    /// The names of all color spaces, including display color spaces.
+    pub fn color_spaces(&self) -> impl Iterator<Item = &str> + '_ {
+        self.color_spaces
+            .iter()
+            .map(|color_space| color_space.name.as_str())
+    }
+
+    /// The names of all displays.
+    pub fn displays(&self) -> impl Iterator<Item = &str> + '_ {
+        self.displays.iter().map(|display| display.name.as_str())
+    }
+
+    /// The names of the views of the display, or none if the display does not exist.
+    pub fn views(&self, display: &str) -> Vec<&str> {
+        self.display(display).map_or_else(Vec::new, |display| {
+            display
+                .views
+                .iter()
+                .map(|view| view.name.as_str())
+                .collect()
+        })
+    }
+
+    /// The first active display, or the first display if none are active.
+    pub fn default_display(&self) -> Option<&str> {
+        self.active_displays
+            .iter()
+            .find_map(|name| self.display(name))
+            .or_else(|| self.displays.first())
+            .map(|display| display.name.as_str())
+    }
+
+    /// The first active view of the display, or its first view if none are active.
+    pub fn default_view(&self, display: &str) -> Option<&str> {
+        let display = self.display(display)?;
+
+        self.active_views
+            .iter()
+            .find_map(|name| display.view(name))
+            .or_else(|| display.views.first())
+            .map(|view| view.name.as_str())
+    }
+
+    /// Convert linear pixels from a color space to the specified display and view.
+    pub fn apply_transform(
+        &self,
+        pixels: &mut [[f32; 3]],
+        from: &str,
+        display: &str,
+        view: &str,
+    ) -> UnitResult {
+        self.display_processor(from, display, view)?.apply(pixels);
+        Ok(())
+    }
+
+    /// Converts from a color space or role to another color space or role.
+    pub fn color_space_processor(&self, from: &str, to: &str) -> Result<Processor> {
+        let mut ops = Vec::new();
+        self.push_conversion(self.color_space(from)?, self.color_space(to)?, &mut ops)?;
+        Ok(Processor { ops })
+    }
+
+    /// Converts from a color space or role to a view of a display, applying the looks of the view.
+    pub fn display_processor(&self, from: &str, display: &str, view: &str) -> Result<Processor> {
+        let display = self
+            .display(display)
+            .ok_or_else(|| Error::invalid(format!("unknown OCIO display `{}`", display)))?;
+
+        let view = display.view(view).ok_or_else(|| {
+            Error::invalid(format!(
+                "unknown OCIO view `{}` of display `{}`",
+                view, display.name
+            ))
+        })?;
+
+        let resolve = |name: &str| {
+            if name == "<USE_DISPLAY_NAME>" {
+                self.color_space(&display.name)
+            } else {
+                self.color_space(name)
+            }
+        };
+
+        let mut ops = Vec::new();
+        let mut current = self.color_space(from)?;
+
+        if let Some(looks) = &view.looks {
+            self.push_looks(looks, &mut current, &mut ops)?;
+        }
+
+        match &view.view_transform {
+            Some(name) => {
+                let view_transform = self
+                    .view_transforms
+                    .iter()
+                    .find(|view_transform| view_transform.name == *name)
+                    .ok_or_else(|| {
+                        Error::invalid(format!("unknown OCIO view transform `{}`", name))
+                    })?;
+
+                let target = view.display_color_space.as_deref().ok_or_else(|| {
+                    Error::invalid(format!(
+                        "OCIO view `{}` has no display color space",
+                        view.name
+                    ))
+                })?;
+
+                let target = resolve(target)?;
+
+                if !current.is_data && !target.is_data {
+                    if current.display_referred {
+                        return Err(Error::unsupported(
+                            "OCIO view transforms of display-referred color spaces",
+                        ));
+                    }
+
+                    self.push_to_reference(current, &mut ops)?;
+                    self.push_view_transform(view_transform, false, &mut ops)?;
+                    self.push_from_reference(target, &mut ops)?;
+                }
+            }
+
+            None => {
+                let target = view.color_space.as_deref().ok_or_else(|| {
+                    Error::invalid(format!("OCIO view `{}` has no color space", view.name))
+                })?;
+
+                self.push_conversion(current, resolve(target)?, &mut ops)?;
+            }
+        }
+
+        Ok(Processor { ops })
+    }
+
+    fn display(&self, name: &str) -> Option<&Display> {
+        self.displays
+            .iter()
+            .find(|display| display.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Find a color space by name, alias, or role.
+    fn color_space(&self, name: &str) -> Result<&ColorSpace> {
+        let find = |name: &str| {
+            self.color_spaces.iter().find(|color_space| {
+                color_space.name.eq_ignore_ascii_case(name)
+                    || color_space
+                        .aliases
+                        .iter()
+                        .any(|alias| alias.eq_ignore_ascii_case(name))
+            })
+        };
+
+        find(name)
+            .or_else(|| {
+                let (_, color_space) = self
+                    .roles
+                    .iter()
+                    .find(|(role, _)| role.eq_ignore_ascii_case(name))?;
+
+                find(color_space)
+            })
+            .ok_or_else(|| Error::invalid(format!("unknown OCIO color space `{}`", name)))
+    }
+
+    fn default_view_transform(&self) -> Result<&ViewTransform> {
+        let view_transform = match &self.default_view_transform {
+            Some(name) => self
+                .view_transforms
+                .iter()
+                .find(|view_transform| view_transform.name == *name),
+            None => self.view_transforms.first(),
+        };
+
+        view_transform.ok_or_else(|| {
+            Error::invalid(
+                "OCIO config has no view transform between scene and display color spaces",
+            )
+        })
+    }
+
+    fn push_conversion(&self, from: &ColorSpace, to: &ColorSpace, ops: &mut Vec<Op>) -> UnitResult {
+        if from.is_data || to.is_data || std::ptr::eq(from, to) {
+            return Ok(());
+        }
+
+        self.push_to_reference(from, ops)?;
+
+        if from.display_referred != to.display_referred {
+            let view_transform = self.default_view_transform()?;
+            self.push_view_transform(view_transform, from.display_referred, ops)?;
+        }
+
+        self.push_from_reference(to, ops)
+    }
+
+    fn push_to_reference(&self, color_space: &ColorSpace, ops: &mut Vec<Op>) -> UnitResult {
+        match (&color_space.to_reference, &color_space.from_reference) {
+            (Some(transform), _) => self.push_transform(transform, false, ops),
+            (None, Some(transform)) => self.push_transform(transform, true, ops),
+            (None, None) => Ok(()),
+        }
+    }
+
+    fn push_from_reference(&self, color_space: &ColorSpace, ops: &mut Vec<Op>) -> UnitResult {
+        match (&color_space.from_reference, &color_space.to_reference) {
+            (Some(transform), _) => self.push_transform(transform, false, ops),
+            (None, Some(transform)) => self.push_transform(transform, true, ops),
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Converts from the scene reference to the display reference, or back if inverse.
+    fn push_view_transform(
+        &self,
+        view_transform: &ViewTransform,
+        inverse: bool,
+        ops: &mut Vec<Op>,
+    ) -> UnitResult {
+        let (forward, backward) = if inverse {
+            (&view_transform.to_reference, &view_transform.from_reference)
+        } else {
+            (&view_transform.from_reference, &view_transform.to_reference)
+        };
+
+        match (forward, backward) {
+            (Some(transform), _) => self.push_transform(transform, false, ops),
+            (None, Some(transform)) => self.push_transform(transform, true, ops),
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Apply the comma separated looks, each in its process space,
+    /// and update the current color space to the last process space.
+    /// Looks prefixed with a minus are applied inversely.
+    fn push_looks<'s>(
+        &'s self,
+        looks: &str,
+        current: &mut &'s ColorSpace,
+        ops: &mut Vec<Op>,
+    ) -> UnitResult {
+        let names = looks.split(|c| c == ',' || c == ':').map(str::trim);
+
+        for name in names.filter(|name| !name.is_empty()) {
+            let inverse = name.starts_with('-');
+            let name = name.trim_start_matches(|c| c == '+' || c == '-');
+
+            let look = self
+                .looks
+                .iter()
+                .find(|look| look.name == name)
+                .ok_or_else(|| Error::invalid(format!("unknown OCIO look `{}`", name)))?;
+
+            let process_space = self.color_space(&look.process_space)?;
+            self.push_conversion(current, process_space, ops)?;
+            *current = process_space;
+
+            let (forward, backward) = if inverse {
+                (&look.inverse_transform, &look.transform)
+            } else {
+                (&look.transform, &look.inverse_transform)
+            };
+
+            match (forward, backward) {
+                (Some(transform), _) => self.push_transform(transform, false, ops)?,
+                (None, Some(transform)) => self.push_transform(transform, true, ops)?,
+                (None, None) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add the steps of a transform of the config, in the specified direction.
+    fn push_transform(&self, transform: &Value, inverse: bool, ops: &mut Vec<Op>) -> UnitResult {
+        let kind = tag(transform);
+        let inverse = inverse != (string(transform, "direction").as_deref() == Some("inverse"));
+
+        let op = match kind.as_str() {
+            "GroupTransform" => {
+                let children = sequence(transform, "children");
+
+                if inverse {
+                    for child in children.iter().rev() {
+                        self.push_transform(child, true, ops)?;
+                    }
+                } else {
+                    for child in children {
+                        self.push_transform(child, false, ops)?;
+                    }
+                }
+
+                return Ok(());
+            }
+
+            "ColorSpaceTransform" => {
+                let source = self.color_space(&required_string(transform, "src")?)?;
+                let destination = self.color_space(&required_string(transform, "dst")?)?;
+
+                return if inverse {
+                    self.push_conversion(destination, source, ops)
+                } else {
+                    self.push_conversion(source, destination, ops)
+                };
+            }
+
+            "DisplayViewTransform" if !inverse => {
+                let processor = self.display_processor(
+                    &required_string(transform, "src")?,
+                    &required_string(transform, "display")?,
+                    &required_string(transform, "view")?,
+                )?;
+
+                ops.extend(processor.ops);
+                return Ok(());
+            }
+
+            "LookTransform" if !inverse => {
+                let mut current = self.color_space(&required_string(transform, "src")?)?;
+                let destination = self.color_space(&required_string(transform, "dst")?)?;
+
+                let looks = string(transform, "looks").unwrap_or_default();
+                self.push_looks(&looks, &mut current, ops)?;
+                return self.push_conversion(current, destination, ops);
+            }
+
+            "BuiltinTransform" => {
+                let style = required_string(transform, "style")?;
+                let mut builtin = builtin_ops(&style)?;
+
+                if inverse {
+                    builtin.reverse();
+                    for op in builtin {
+                        ops.push(op.inverse()?);
+                    }
+                } else {
+                    ops.extend(builtin);
+                }
+
+                return Ok(());
+            }
+
+            "MatrixTransform" => {
+                let matrix = list(transform, "matrix")?.unwrap_or_else(|| {
+                    vec![
+                        1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.,
+                    ]
+                });
+
+                let offset = list(transform, "offset")?.unwrap_or_else(|| vec![0.0; 4]);
+
+                if matrix.len() != 16 || offset.len() != 4 {
+                    return Err(Error::invalid("OCIO matrix transform size"));
+                }
+
+                Op::Matrix {
+                    matrix: [
+                        [matrix[0], matrix[1], matrix[2]],
+                        [matrix[4], matrix[5], matrix[6]],
+                        [matrix[8], matrix[9], matrix[10]],
+                    ],
+                    offset: [offset[0], offset[1], offset[2]],
+                }
+            }
+
+            "ExponentTransform" => Op::Power(rgb(transform, "value", 1.0)?),
+
+            "ExponentWithLinearTransform" => Op::MonCurve {
+                gamma: rgb(transform, "gamma", 1.0)?,
+                offset: rgb(transform, "offset", 0.0)?,
+                inverse: false,
+            },
+
+            "LogTransform" | "LogAffineTransform" | "LogCameraTransform" => {
+                let base = number(transform, "base", 2.0)?;
+                let log_slope = rgb(transform, "log_side_slope", 1.0)?;
+                let log_offset = rgb(transform, "log_side_offset", 0.0)?;
+                let lin_slope = rgb(transform, "lin_side_slope", 1.0)?;
+                let lin_offset = rgb(transform, "lin_side_offset", 0.0)?;
+
+                let linear_segment = if kind == "LogCameraTransform" {
+                    let lin_break = list(transform, "lin_side_break")?
+                        .ok_or_else(|| Error::invalid("OCIO log camera transform without break"))?;
+
+                    let lin_break = expand(&lin_break, "lin_side_break")?;
+                    let linear_slope = list(transform, "linear_slope")?
+                        .map(|slope| expand(&slope, "linear_slope"))
+                        .transpose()?;
+
+                    Some(LinearSegment::new(
+                        base,
+                        [log_slope, log_offset, lin_slope, lin_offset],
+                        lin_break,
+                        linear_slope,
+                    ))
+                } else {
+                    None
+                };
+
+                Op::Log(LogCurve {
+                    base,
+                    log_slope,
+                    log_offset,
+                    lin_slope,
+                    lin_offset,
+                    linear_segment,
+                    inverse: false,
+                })
+            }
+
+            "CDLTransform" => Op::Cdl(Cdl {
+                slope: rgb(transform, "slope", 1.0)?,
+                offset: rgb(transform, "offset", 0.0)?,
+                power: rgb(transform, "power", 1.0)?,
+                saturation: number(transform, "sat", 1.0)?,
+                inverse: false,
+            }),
+
+            "RangeTransform" => {
+                let value = |key: &str| -> Result<Option<f32>> {
+                    Ok(list(transform, key)?.and_then(|values| values.first().copied()))
+                };
+
+                Op::Range(Range {
+                    min_in: value("min_in_value")?,
+                    max_in: value("max_in_value")?,
+                    min_out: value("min_out_value")?,
+                    max_out: value("max_out_value")?,
+                })
+            }
+
+            "FileTransform" => self.load_lut(&required_string(transform, "src")?)?,
+
+            other => return Err(Error::unsupported(format!("OCIO transform `{}`", other))),
+        };
+
+        ops.push(if inverse { op.inverse()? } else { op });
+        Ok(())
+    }
+
+    /// Read a LUT file, searched in the search paths of the config and next to the config.
+    fn load_lut(&self, name: &str) -> Result<Op> {
+        let path = Path::new(name);
+
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.search_paths
+                .iter()
+                .map(|directory| self.directory.join(directory).join(name))
+                .chain(std::iter::once(self.directory.join(name)))
+                .find(|path| path.is_file())
+                .ok_or_else(|| Error::invalid(format!("cannot find OCIO LUT file `{}`", name)))?
+        };
+
+        read_lut_file(&path)
+    }
+}
+
+/// Read a `.cube`, `.spi1d`, or `.spi3d` LUT file, depending on the extension.
+fn read_lut_file(path: &Path) -> Result<Op> {
+    let text = std::fs::read_to_string(path)?;
+    let extension = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+
+    match extension.to_ascii_lowercase().as_str() {
+        "cube" => parse_cube(&text),
+        "spi1d" => parse_spi1d(&text),
+        "spi3d" => parse_spi3d(&text),
+        _ => Err(Error::unsupported(format!(
+            "LUT files of type `{}`",
+            extension
+        ))),
+    }
+}
+
+impl Display {
+    fn view(&self, name: &str) -> Option<&View> {
+        self.views
+            .iter()
+            .find(|view| view.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl ColorSpace {
+    fn parse(value: &Value, display_referred: bool) -> Result<Self> {
+        let (to_key, from_key) = if display_referred {
+            ("to_display_reference", "from_display_reference")
+        } else {
+            ("to_scene_reference", "from_scene_reference")
+        };
+
+        // version 1 configs only have a scene reference
+        let transform = |key: &str, legacy_key: &str| {
+            field(value, key)
+                .or_else(|| field(value, legacy_key))
+                .cloned()
+        };
+
+        Ok(ColorSpace {
+            name: required_string(value, "name")?,
+            aliases: strings(value, "aliases", ','),
+            is_data: field(value, "isdata")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            display_referred,
+            to_reference: transform(to_key, "to_reference"),
+            from_reference: transform(from_key, "from_reference"),
+        })
+    }
+}
+
+impl View {
+    fn parse(value: &Value) -> Result<Self> {
+        Ok(View {
+            name: required_string(value, "name")?,
+            color_space: string(value, "colorspace"),
+            view_transform: string(value, "view_transform"),
+            display_color_space: string(value, "display_colorspace"),
+            looks: string(value, "looks"),
+        })
+    }
+}
+
+impl Processor {
+    /// A processor that applies a single `.cube`, `.spi1d`, or `.spi3d` LUT file,
+    /// like a baked show look. The format is chosen by the extension of the path.
+    pub fn from_lut_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Processor {
+            ops: vec![read_lut_file(path.as_ref())?],
+        })
+    }
+
+    /// Whether the processor does not change any colors.
+    pub fn is_identity(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Convert one color.
+    pub fn apply_rgb(&self, rgb: [f32; 3]) -> [f32; 3] {
+        self.ops.iter().fold(rgb, |rgb, op| op.apply(rgb))
+    }
+
+    /// Convert all pixels in place.
+    pub fn apply(&self, pixels: &mut [[f32; 3]]) {
+        for pixel in pixels {
+            *pixel = self.apply_rgb(*pixel);
+        }
+    }
+}
+
+impl Op {
+    fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            Op::Matrix { matrix, offset } => map3(|row| {
+                matrix[row][0] * rgb[0]
+                    + matrix[row][1] * rgb[1]
+                    + matrix[row][2] * rgb[2]
+                    + offset[row]
+            }),
+
+            Op::Power(exponent) => map3(|c| rgb[c].max(0.0).powf(exponent[c])),
+
+            Op::MonCurve {
+                gamma,
+                offset,
+                inverse,
+            } => map3(|c| mon_curve(rgb[c], gamma[c], offset[c], *inverse)),
+
+            Op::Log(curve) => map3(|c| curve.apply(rgb[c], c)),
+            Op::Cdl(cdl) => cdl.apply(rgb),
+            Op::Range(range) => map3(|c| range.apply(rgb[c])),
+            Op::Lut1d {
+                lut,
+                inverse: false,
+            } => lut.apply(rgb),
+            Op::Lut1d { lut, inverse: true } => lut.apply_inverse(rgb),
+            Op::Lut3d(lut) => lut.apply(rgb),
+            Op::AcesCct { inverse } => map3(|c| aces_cct(rgb[c], *inverse)),
+        }
+    }
+
+    fn inverse(&self) -> Result<Op> {
+        Ok(match self.clone() {
+            Op::Matrix { matrix, offset } => {
+                let matrix = invert_matrix(matrix)
+                    .ok_or_else(|| Error::invalid("OCIO matrix cannot be inverted"))?;
+
+                let offset = map3(|row| {
+                    -(matrix[row][0] * offset[0]
+                        + matrix[row][1] * offset[1]
+                        + matrix[row][2] * offset[2])
+                });
+
+                Op::Matrix { matrix, offset }
+            }
+
+            Op::Power(exponent) => {
+                if exponent.contains(&0.0) {
+                    return Err(Error::invalid("OCIO exponent of zero cannot be inverted"));
+                }
+
+                Op::Power(map3(|c| 1.0 / exponent[c]))
+            }
+
+            Op::MonCurve {
+                gamma,
+                offset,
+                inverse,
+            } => Op::MonCurve {
+                gamma,
+                offset,
+                inverse: !inverse,
+            },
+
+            Op::Log(curve) => Op::Log(LogCurve {
+                inverse: !curve.inverse,
+                ..curve
+            }),
+
+            Op::Cdl(cdl) => {
+                if cdl.saturation == 0.0 || cdl.slope.contains(&0.0) || cdl.power.contains(&0.0) {
+                    return Err(Error::invalid("OCIO CDL cannot be inverted"));
+                }
+
+                Op::Cdl(Cdl {
+                    inverse: !cdl.inverse,
+                    ..cdl
+                })
+            }
+
+            Op::Range(range) => Op::Range(Range {
+                min_in: range.min_out,
+                max_in: range.max_out,
+                min_out: range.min_in,
+                max_out: range.max_in,
+            }),
+
+            Op::Lut1d { lut, inverse } => Op::Lut1d {
+                lut,
+                inverse: !inverse,
+            },
+
+            Op::Lut3d(_) => return Err(Error::unsupported("inverse 3D LUTs")),
+            Op::AcesCct { inverse } => Op::AcesCct { inverse: !inverse },
+        })
+    }
+}
+
+/// The steps of the supported built-in transforms of OCIO.
+fn builtin_ops(style: &str) -> Result<Vec<Op>> {
+    const AP1_TO_AP0: [[f32; 3]; 3] = [
+        [0.695_452_24, 0.140_678_7, 0.163_869_06],
+        [0.044_794_563, 0.859_671_1, 0.095_534_32],
+        [-0.005_525_883, 0.004_025_21, 1.001_500_7],
+    ];
+
+    const XYZ_D65_TO_REC709: [[f32; 3]; 3] = [
+        [3.240_97, -1.537_383_2, -0.498_610_76],
+        [-0.969_243_6, 1.875_967_5, 0.041_555_06],
+        [0.055_630_08, -0.203_976_96, 1.056_971_5],
+    ];
+
+    let matrix = |matrix| Op::Matrix {
+        matrix,
+        offset: [0.0; 3],
+    };
+
+    Ok(match style {
+        "IDENTITY" => Vec::new(),
+        "ACEScg_to_ACES2065-1" => vec![matrix(AP1_TO_AP0)],
+        "ACEScct_to_ACES2065-1" => vec![Op::AcesCct { inverse: false }, matrix(AP1_TO_AP0)],
+
+        "DISPLAY - CIE-XYZ-D65_to_sRGB" => vec![
+            matrix(XYZ_D65_TO_REC709),
+            Op::MonCurve {
+                gamma: [2.4; 3],
+                offset: [0.055; 3],
+                inverse: true,
+            },
+        ],
+
+        "DISPLAY - CIE-XYZ-D65_to_REC.1886-REC.709" => {
+            vec![matrix(XYZ_D65_TO_REC709), Op::Power([1.0 / 2.4; 3])]
+        }
+
+        _ => {
+            return Err(Error::unsupported(format!(
+                "OCIO built-in transform `{}`",
+                style
+            )))
+        }
+    })
+}
+
+/// Converts encoded to linear values, or linear to encoded values if inverse.
+/// The linear segment meets the power curve with the same slope.
+fn mon_curve(value: f32, gamma: f32, offset: f32, inverse: bool) -> f32 {
+    if offset <= 0.0 || gamma <= 1.0 {
+        let exponent = if inverse { 1.0 / gamma } else { gamma };
+        return value.max(0.0).powf(exponent);
+    }
+
+    let encoded_break = offset / (gamma - 1.0);
+    let slope = ((offset * gamma) / ((gamma - 1.0) * (1.0 + offset))).powf(gamma) / encoded_break;
+
+    if inverse {
+        if value >= encoded_break * slope {
+            (1.0 + offset) * value.powf(1.0 / gamma) - offset
+        } else {
+            value / slope
+        }
+    } else if value >= encoded_break {
+        ((value + offset) / (1.0 + offset)).powf(gamma)
+    } else {
+        value * slope
+    }
+}
+
+/// Converts ACEScct to linear values, or linear values to ACEScct if inverse.
+fn aces_cct(value: f32, inverse: bool) -> f32 {
+    const LINEAR_BREAK: f32 = 0.007_812_5;
+    const SLOPE: f32 = 10.540_237;
+    const OFFSET: f32 = 0.072_905_534;
+
+    if inverse {
+        if value <= LINEAR_BREAK {
+            SLOPE * value + OFFSET
+        } else {
+            (value.log2() + 9.72) / 17.52
+        }
+    } else if value <= SLOPE * LINEAR_BREAK + OFFSET {
+        (value - OFFSET) / SLOPE
+    } else {
+        (value * 17.52 - 9.72).exp2().min(65504.0)
+    }
+}
+
+impl LinearSegment {
+    /// The linear segment continues the log curve at the break,
+    /// with the slope of the curve unless a slope is specified.
+    fn new(
+        base: f32,
+        [log_slope, log_offset, lin_slope, lin_offset]: [[f32; 3]; 4],
+        lin_break: [f32; 3],
+        linear_slope: Option<[f32; 3]>,
+    ) -> Self {
+        let inner = map3(|c| lin_slope[c] * lin_break[c] + lin_offset[c]);
+        let log_break = map3(|c| log_slope[c] * inner[c].log(base) + log_offset[c]);
+
+        let slope = linear_slope
+            .unwrap_or_else(|| map3(|c| log_slope[c] * lin_slope[c] / (inner[c] * base.ln())));
+
+        LinearSegment {
+            lin_break,
+            slope,
+            offset: map3(|c| log_break[c] - slope[c] * lin_break[c]),
+        }
+    }
+}
+
+impl LogCurve {
+    fn apply(&self, value: f32, c: usize) -> f32 {
+        let segment = self.linear_segment.as_ref();
+
+        if self.inverse {
+            match segment {
+                Some(segment)
+                    if value <= segment.slope[c] * segment.lin_break[c] + segment.offset[c] =>
+                {
+                    (value - segment.offset[c]) / segment.slope[c]
+                }
+
+                _ => {
+                    let inner = self
+                        .base
+                        .powf((value - self.log_offset[c]) / self.log_slope[c]);
+                    (inner - self.lin_offset[c]) / self.lin_slope[c]
+                }
+            }
+        } else {
+            match segment {
+                Some(segment) if value <= segment.lin_break[c] => {
+                    segment.slope[c] * value + segment.offset[c]
+                }
+
+                _ => {
+                    let inner = self.lin_slope[c] * value + self.lin_offset[c];
+                    self.log_slope[c] * inner.max(f32::MIN_POSITIVE).log(self.base)
+                        + self.log_offset[c]
+                }
+            }
+        }
+    }
+}
+
+impl Cdl {
+    fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let saturate = |rgb: [f32; 3], saturation: f32| {
+            let luma = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            map3(|c| clamp01(luma + saturation * (rgb[c] - luma)))
+        };
+
+        if self.inverse {
+            let rgb = saturate(map3(|c| clamp01(rgb[c])), 1.0 / self.saturation);
+            map3(|c| (rgb[c].powf(1.0 / self.power[c]) - self.offset[c]) / self.slope[c])
+        } else {
+            let rgb =
+                map3(|c| clamp01(rgb[c] * self.slope[c] + self.offset[c]).powf(self.power[c]));
+            saturate(rgb, self.saturation)
+        }
+    }
+}
+
+impl Range {
+    fn apply(&self, value: f32) -> f32 {
+        let (scale, offset) = match (self.min_in, self.max_in, self.min_out, self.max_out) {
+            (Some(min_in), Some(max_in), Some(min_out), Some(max_out)) if max_in != min_in => {
+                let scale = (max_out - min_out) / (max_in - min_in);
+                (scale, min_out - min_in * scale)
+            }
+
+            (Some(min_in), _, Some(min_out), _) => (1.0, min_out - min_in),
+            (_, Some(max_in), _, Some(max_out)) => (1.0, max_out - max_in),
+            _ => (1.0, 0.0),
+        };
+
+        let value = value * scale + offset;
+        let value = self.min_out.map_or(value, |min| value.max(min));
+        self.max_out.map_or(value, |max| value.min(max))
+    }
+}
+
+impl Lut1d {
+    fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let last = (self.values.len() - 1) as f32;
+
+        map3(|c| {
+            let range = self.domain_max[c] - self.domain_min[c];
+            let position = ((rgb[c] - self.domain_min[c]) / range * last)
+                .max(0.0)
+                .min(last);
+
+            let index = (position as usize).min(self.values.len() - 2);
+            let fraction = position - index as f32;
+            lerp(self.values[index][c], self.values[index + 1][c], fraction)
+        })
+    }
+
+    /// Searches the value in the increasing entries of the LUT.
+    fn apply_inverse(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let last = self.values.len() - 1;
+
+        map3(|c| {
+            let value = rgb[c].max(self.values[0][c]).min(self.values[last][c]);
+            let upper = self
+                .values
+                .partition_point(|entry| entry[c] < value)
+                .max(1)
+                .min(last);
+
+            let (lower_value, upper_value) = (self.values[upper - 1][c], self.values[upper][c]);
+            let fraction = if upper_value > lower_value {
+                (value - lower_value) / (upper_value - lower_value)
+            } else {
+                0.0
+            };
+
+            let position = ((upper - 1) as f32 + fraction) / last as f32;
+            self.domain_min[c] + position * (self.domain_max[c] - self.domain_min[c])
+        })
+    }
+}
+
+impl Lut3d {
+    /// Interpolates trilinearly.
+    fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let size = self.size;
+        let last = (size - 1) as f32;
+
+        let [(red, red_fraction), (green, green_fraction), (blue, blue_fraction)] = map3(|c| {
+            let range = self.domain_max[c] - self.domain_min[c];
+            let position = ((rgb[c] - self.domain_min[c]) / range * last)
+                .max(0.0)
+                .min(last);
+            let index = (position as usize).min(size - 2);
+            (index, position - index as f32)
+        });
+
+        let entry = |r: usize, g: usize, b: usize| {
+            self.values[(red + r) + (green + g) * size + (blue + b) * size * size]
+        };
+
+        let lerp3 =
+            |from: [f32; 3], to: [f32; 3], fraction: f32| map3(|c| lerp(from[c], to[c], fraction));
+
+        let along_red = |g: usize, b: usize| lerp3(entry(0, g, b), entry(1, g, b), red_fraction);
+        let near_blue = lerp3(along_red(0, 0), along_red(1, 0), green_fraction);
+        let far_blue = lerp3(along_red(0, 1), along_red(1, 1), green_fraction);
+        lerp3(near_blue, far_blue, blue_fraction)
+    }
+}
+
+/// Parse a Resolve `.cube` file with a 1D or a 3D LUT.
+fn parse_cube(text: &str) -> Result<Op> {
+    let mut size_1d = None;
+    let mut size_3d = None;
+    let mut domain_min = [0.0; 3];
+    let mut domain_max = [1.0; 3];
+    let mut values = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace();
+        let keyword = tokens.clone().next().unwrap_or_default();
+
+        match keyword {
+            "TITLE" => {}
+
+            "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                let size = tokens.nth(1).and_then(|size| size.parse::<usize>().ok());
+                let size = size.ok_or_else(|| Error::invalid("cube LUT size"))?;
+
+                if keyword == "LUT_1D_SIZE" {
+                    size_1d = Some(size);
+                } else {
+                    size_3d = Some(size);
+                }
+            }
+
+            "DOMAIN_MIN" => domain_min = expand(&floats(tokens.skip(1))?, "DOMAIN_MIN")?,
+            "DOMAIN_MAX" => domain_max = expand(&floats(tokens.skip(1))?, "DOMAIN_MAX")?,
+
+            "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => {
+                let range = floats(tokens.skip(1))?;
+                if range.len() != 2 {
+                    return Err(Error::invalid("cube LUT input range"));
+                }
+
+                domain_min = [range[0]; 3];
+                domain_max = [range[1]; 3];
+            }
+
+            _ => values.push(expand(&floats(tokens)?, "cube LUT entry")?),
+        }
+    }
+
+    match (size_1d, size_3d) {
+        (Some(size), None) if size >= 2 && values.len() == size => Ok(Op::Lut1d {
+            lut: Lut1d {
+                domain_min,
+                domain_max,
+                values,
+            },
+            inverse: false,
+        }),
+
+        (None, Some(size)) if size >= 2 && values.len() == size * size * size => {
+            Ok(Op::Lut3d(Lut3d {
+                size,
+                domain_min,
+                domain_max,
+                values,
+            }))
+        }
+
+        _ => Err(Error::invalid("cube LUT size")),
+    }
+}
+
+/// Parse a Sony Imageworks `.spi1d` file.
+fn parse_spi1d(text: &str) -> Result<Op> {
+    let mut domain = (0.0, 1.0);
+    let mut components = 1;
+    let mut values = Vec::new();
+    let mut in_values = false;
+
+    for line in text.lines().map(str::trim) {
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            None => {}
+            Some("{") => in_values = true,
+            Some("}") => in_values = false,
+
+            Some(_) if in_values => {
+                let entry = floats(line.split_whitespace())?;
+                if entry.len() != components {
+                    return Err(Error::invalid("spi1d LUT entry"));
+                }
+
+                values.push(expand(&entry, "spi1d LUT entry")?);
+            }
+
+            Some("From") => {
+                let range = floats(tokens)?;
+                if range.len() != 2 {
+                    return Err(Error::invalid("spi1d LUT range"));
+                }
+
+                domain = (range[0], range[1]);
+            }
+
+            Some("Components") => {
+                components = tokens
+                    .next()
+                    .and_then(|count| count.parse().ok())
+                    .filter(|&count| count == 1 || count == 3)
+                    .ok_or_else(|| Error::unsupported("spi1d LUT components"))?;
+            }
+
+            Some(_) => {} // version and length
+        }
+    }
+
+    if values.len() < 2 {
+        return Err(Error::invalid("spi1d LUT size"));
+    }
+
+    Ok(Op::Lut1d {
+        lut: Lut1d {
+            domain_min: [domain.0; 3],
+            domain_max: [domain.1; 3],
+            values,
+        },
+        inverse: false,
+    })
+}
+
+/// Parse a Sony Imageworks `.spi3d` file.
+fn parse_spi3d(text: &str) -> Result<Op> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let invalid = || Error::invalid("spi3d LUT");
+
+    // the header and the number of input and output components
+    lines
+        .next()
+        .filter(|line| line.starts_with("SPILUT"))
+        .ok_or_else(invalid)?;
+    lines.next().ok_or_else(invalid)?;
+
+    let sizes = floats(lines.next().ok_or_else(invalid)?.split_whitespace())?;
+    let size = sizes.first().map_or(0, |&size| size as usize);
+
+    if size < 2 || sizes.iter().any(|&other| other as usize != size) {
+        return Err(Error::unsupported("spi3d LUT size"));
+    }
+
+    let mut values = vec![[0.0; 3]; size * size * size];
+    for line in lines {
+        let entry = floats(line.split_whitespace())?;
+        if entry.len() != 6 {
+            return Err(invalid());
+        }
+
+        let [red, green, blue] = map3(|c| entry[c] as usize);
+        if red >= size || green >= size || blue >= size {
+            return Err(invalid());
+        }
+
+        values[red + green * size + blue * size * size] = [entry[3], entry[4], entry[5]];
+    }
+
+    Ok(Op::Lut3d(Lut3d {
+        size,
+        domain_min: [0.0; 3],
+        domain_max: [1.0; 3],
+        values,
+    }))
+}
+
+fn invert_matrix(matrix: [[f32; 3]; 3]) -> Option<[[f32; 3]; 3]> {
+    let m = matrix.map(|row| row.map(f64::from));
+
+    // with cyclic indices, the minors already have the sign of the cofactors
+    let cofactor = |row: usize, column: usize| {
+        let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+        let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+
+    let determinant = (0..3)
+        .map(|column| m[0][column] * cofactor(0, column))
+        .sum::<f64>();
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+
+    Some(map3(|row| {
+        map3(|column| (cofactor(column, row) / determinant) as f32)
+    }))
+}
+
+fn map3<T>(function: impl Fn(usize) -> T) -> [T; 3] {
+    [function(0), function(1), function(2)]
+}
+
+fn lerp(from: f32, to: f32, fraction: f32) -> f32 {
+    from + (to - from) * fraction
+}
+
+fn clamp01(value: f32) -> f32 {
+    value.max(0.0).min(1.0)
+}
+
+fn floats<'t>(tokens: impl Iterator<Item = &'t str>) -> Result<Vec<f32>> {
+    tokens
+        .map(|token| {
+            token
+                .parse()
+                .map_err(|_| Error::invalid(format!("LUT number `{}`", token)))
+        })
+        .collect()
+}
+
+/// One value for all channels, or three values, ignoring a fourth alpha value.
+fn expand(values: &[f32], name: &str) -> Result<[f32; 3]> {
+    match *values {
+        [value] => Ok([value; 3]),
+        [red, green, blue] | [red, green, blue, _] => Ok([red, green, blue]),
+        _ => Err(Error::invalid(format!("number of values of `{}`", name))),
+    }
+}
+
+/// Replace verbatim tags like `!<ColorSpace>` with local tags like `!ColorSpace`,
+/// because the yaml parser discards verbatim tags.
+fn local_tags(yaml: &str) -> String {
+    let mut result = String::with_capacity(yaml.len());
+    let mut rest = yaml;
+
+    while let Some(start) = rest.find("!<") {
+        let name_start = start + 2;
+        let name_length = rest[name_start..]
+            .find(|c: char| c == '>' || c.is_whitespace())
+            .unwrap_or(rest.len() - name_start);
+
+        let name_end = name_start + name_length;
+        result.push_str(&rest[..start]);
+
+        if rest[name_end..].starts_with('>') {
+            result.push('!');
+            result.push_str(&rest[name_start..name_end]);
+            rest = &rest[name_end + 1..];
+        } else {
+            result.push_str(&rest[start..name_end]);
+            rest = &rest[name_end..];
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// The name of the tag, like `ColorSpace` for `!<ColorSpace>`.
+fn tag(value: &Value) -> String {
+    match value {
+        Value::Tagged(tagged) => tagged
+            .tag
+            .to_string()
+            .trim_start_matches('!')
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_string(),
+
+        _ => String::new(),
+    }
+}
+
+fn untagged(value: &Value) -> &Value {
+    match value {
+        Value::Tagged(tagged) => &tagged.value,
+        value => value,
+    }
+}
+
+fn field<'v>(value: &'v Value, key: &str) -> Option<&'v Value> {
+    untagged(value).as_mapping()?.get(key)
+}
+
+fn text(value: &Value) -> Option<String> {
+    match untagged(value) {
+        Value::String(string) => Some(string.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(boolean) => Some(boolean.to_string()),
+        _ => None,
+    }
+}
+
+fn string(value: &Value, key: &str) -> Option<String> {
+    field(value, key).and_then(text)
+}
+
+fn required_string(value: &Value, key: &str) -> Result<String> {
+    string(value, key).ok_or_else(|| Error::invalid(format!("OCIO config is missing `{}`", key)))
+}
+
+/// A list of strings, or a single string with separated values.
+fn strings(value: &Value, key: &str, separator: char) -> Vec<String> {
+    match field(value, key).map(untagged) {
+        Some(Value::Sequence(items)) => items.iter().filter_map(text).collect(),
+
+        Some(other) => text(other)
+            .unwrap_or_default()
+            .split(separator)
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+
+        None => Vec::new(),
+    }
+}
+
+fn sequence<'v>(value: &'v Value, key: &str) -> &'v [Value] {
+    field(value, key)
+        .and_then(|value| untagged(value).as_sequence())
+        .map_or(&[], Vec::as_slice)
+}
+
+/// A single number or a list of numbers.
+fn list(value: &Value, key: &str) -> Result<Option<Vec<f32>>> {
+    let invalid = || Error::invalid(format!("OCIO transform value `{}`", key));
+
+    match field(value, key).map(untagged) {
+        None => Ok(None),
+
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .map(|item| {
+                item.as_f64()
+                    .map(|number| number as f32)
+                    .ok_or_else(invalid)
+            })
+            .collect::<Result<Vec<f32>>>()
+            .map(Some),
+
+        Some(other) => other
+            .as_f64()
+            .map(|number| Some(vec![number as f32]))
+            .ok_or_else(invalid),
+    }
+}
+
+fn number(value: &Value, key: &str, default: f32) -> Result<f32> {
+    match list(value, key)? {
+        None => Ok(default),
+        Some(values) if values.len() == 1 => Ok(values[0]),
+        Some(_) => Err(Error::invalid(format!("OCIO transform value `{}`", key))),
+    }
+}
+
+fn rgb(value: &Value, key: &str, default: f32) -> Result<[f32; 3]> {
+    match list(value, key)? {
+        None => Ok([default; 3]),
+        Some(values) => expand(&values, key),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r#"
+ocio_profile_version: 2
+
+roles:
+  scene_linear: Linear
+
+displays:
+  sRGB:
+    - !<View> {name: Standard, colorspace: sRGB Texture}
+    - !<View> {name: Log, view_transform: Log, display_colorspace: <USE_DISPLAY_NAME>}
+    - !<View> {name: Raw, colorspace: Raw}
+
+active_views: [Standard, Log]
+
+view_transforms:
+  - !<ViewTransform>
+    name: Log
+    from_scene_reference: !<LogTransform> {base: 2}
+
+colorspaces:
+  - !<ColorSpace>
+    name: Linear
+    aliases: [lin]
+
+  - !<ColorSpace>
+    name: sRGB Texture
+    from_scene_reference: !<ExponentWithLinearTransform>
+      gamma: 2.4
+      offset: 0.055
+      direction: inverse
+
+  - !<ColorSpace>
+    name: Raw
+    isdata: true
+
+  - !<ColorSpace>
+    name: ACEScct
+    to_scene_reference: !<BuiltinTransform> {style: ACEScct_to_ACES2065-1}
+
+display_colorspaces:
+  - !<ColorSpace>
+    name: sRGB
+    from_display_reference: !<GroupTransform>
+      children:
+        - !<RangeTransform> {min_in_value: 0, max_in_value: 2, min_out_value: 0, max_out_value: 1}
+"#;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (actual, expected) in actual.iter().zip(expected.iter()) {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn displays_and_views() {
+        let config = OcioConfig::parse(CONFIG, "").unwrap();
+
+        assert_eq!(config.default_display(), Some("sRGB"));
+        assert_eq!(config.default_view("sRGB"), Some("Standard"));
+        assert_eq!(config.views("sRGB"), ["Standard", "Log", "Raw"]);
+        assert_eq!(config.color_spaces().count(), 5);
+
+        let standard = config
+            .display_processor("scene_linear", "sRGB", "Standard")
+            .unwrap();
+        assert_close(standard.apply_rgb([0.18, 0.0, 1.0]), [0.461_356, 0.0, 1.0]);
+
+        // the log view transform maps 1 to 0 and 4 to 2, which the display maps to 1
+        let log = config.display_processor("lin", "sRGB", "Log").unwrap();
+        assert_close(log.apply_rgb([1.0, 4.0, 16.0]), [0.0, 1.0, 1.0]);
+
+        let raw = config.display_processor("Linear", "sRGB", "Raw").unwrap();
+        assert!(raw.is_identity());
+
+        assert!(config
+            .display_processor("Linear", "sRGB", "Missing")
+            .is_err());
+        assert!(config
+            .display_processor("Missing", "sRGB", "Standard")
+            .is_err());
+    }
+
+    #[test]
+    fn inverse_conversions() {
+        let config = OcioConfig::parse(CONFIG, "").unwrap();
+        let to_srgb = config
+            .color_space_processor("Linear", "sRGB Texture")
+            .unwrap();
+        let from_srgb = config
+            .color_space_processor("sRGB Texture", "Linear")
+            .unwrap();
+
+        let color = [0.001, 0.18, 0.9];
+        assert_close(from_srgb.apply_rgb(to_srgb.apply_rgb(color)), color);
+
+        let from_cct = config.color_space_processor("ACEScct", "Linear").unwrap();
+        let to_cct = config.color_space_processor("Linear", "ACEScct").unwrap();
+        assert_close(
+            to_cct.apply_rgb(from_cct.apply_rgb([0.1, 0.4, 0.6])),
+            [0.1, 0.4, 0.6],
+        );
+    }
+
+    #[test]
+    fn cube_luts() {
+        let lut_1d = parse_cube("LUT_1D_SIZE 3\n0 0 0\n1 1 1\n4 4 4\n").unwrap();
+        assert_close(lut_1d.apply([0.25, 0.5, 0.75]), [0.5, 1.0, 2.5]);
+        assert_close(
+            lut_1d.inverse().unwrap().apply([0.5, 1.0, 2.5]),
+            [0.25, 0.5, 0.75],
+        );
+
+        let mut identity = String::from("# identity\nLUT_3D_SIZE 2\n");
+        for index in 0..8 {
+            let [red, green, blue] = map3(|c| (index >> c) & 1);
+            identity.push_str(&format!("{} {} {}\n", red, green, blue));
+        }
+
+        let lut_3d = parse_cube(&identity).unwrap();
+        assert_close(lut_3d.apply([0.2, 0.5, 0.9]), [0.2, 0.5, 0.9]);
+        assert!(lut_3d.inverse().is_err());
+
+        assert!(parse_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    }
+
+    #[test]
+    fn invert_matrices() {
+        let matrix = [[2.0, 0.0, 1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 1.0]];
+        let op = Op::Matrix {
+            matrix,
+            offset: [0.5, 0.0, 0.0],
+        };
+
+        let color = [0.3, 0.6, 0.9];
+        assert_close(op.inverse().unwrap().apply(op.apply(color)), color);
+
+        let singular = [[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 0.0, 1.0]];
+        assert_eq!(invert_matrix(singular), None);
+    }
+}
@@ -0,0 +1,299 @@
+//! Log exr layers and deep samples to the [rerun](https://rerun.io) viewer.
+//! Enable with the `rerun` feature.
+//!
+//! Flat layers are logged as images. Deep layers are logged as point clouds,
+//! with one point per sample at the pixel position and the depth of the sample,
+//! together with an image of the number of samples per pixel.
+//!
+//! ```no_run
+//! use exr::image::read::deep::read_all_deep_layers_from_file;
+//! use exr::interop::rerun::{log_deep_layer, DeepPointOptions};
+//!
+//! let recording = rerun::RecordingStreamBuilder::new("exrs").spawn().unwrap();
+//! let image = read_all_deep_layers_from_file("deep.exr").unwrap();
+//!
+//! for layer in &image.layer_data {
+//!     log_deep_layer(&recording, "deep", layer, &DeepPointOptions::default()).unwrap();
+//! }
+//! ```
+
+use ::rerun::{Color, ColorModel, Image, Points3D, RecordingStream};
+use std::convert::TryFrom;
+
+use super::{DeepLayer, FlatLayer};
+use crate::error::{Error, Result};
+use crate::export::Transfer;
+use crate::image::deep::{DeepChannelData, DeepSamples};
+use crate::image::FlatSamples;
+use crate::math::Vec2;
+
+/// How the samples of a deep layer are converted to points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeepPointOptions {
+    /// The depth of a sample is multiplied by this factor,
+    /// so that it can be compared with the pixel positions.
+    pub depth_scale: f32,
+
+    /// The radius of each point, in the same units as the pixel positions.
+    pub radius: f32,
+
+    /// Multiply the colors by `2 ^ exposure` before converting them to sRGB.
+    pub exposure: f32,
+}
+
+impl Default for DeepPointOptions {
+    fn default() -> Self {
+        DeepPointOptions {
+            depth_scale: 1.0,
+            radius: 0.5,
+            exposure: 0.0,
+        }
+    }
+}
+
+/// The positions and colors of all samples of a deep layer, one point per sample.
+/// The position of a point is the center of its pixel, with the `Z` sample as the third coordinate.
+/// Colors are taken from the `R`, `G`, `B`, and `A` channels, and are white where those channels are missing.
+/// Returns an error if the layer has no `Z` channel.
+pub fn deep_points(layer: &DeepLayer, options: &DeepPointOptions) -> Result<Points3D> {
+    let samples = deep_samples(layer)?;
+    let depth = deep_channel(layer, samples, "Z")
+        .ok_or_else(|| Error::invalid("deep layer has no `Z` channel"))?;
+
+    let [red, green, blue, alpha] =
+        ["R", "G", "B", "A"].map(|name| deep_channel(layer, samples, name));
+    let scale = options.exposure.exp2();
+
+    let mut positions = Vec::with_capacity(samples.total_samples());
+    let mut colors = Vec::with_capacity(samples.total_samples());
+
+    for y in 0..samples.height {
+        for x in 0..samples.width {
+            let (start, end) = samples.sample_range(y * samples.width + x);
+
+            for index in start..end {
+                positions.push([
+                    x as f32 + 0.5,
+                    y as f32 + 0.5,
+                    depth.get_as_f32(index) * options.depth_scale,
+                ]);
+
+                let value = |channel: Option<&DeepChannelData>, default: f32| {
+                    channel.map_or(default, |channel| channel.get_as_f32(index))
+                };
+
+                let alpha = value(alpha, 1.0).max(0.0).min(1.0);
+                let unpremultiply = if alpha > 0.0 { scale / alpha } else { scale };
+
+                colors.push(Color::from_unmultiplied_rgba(
+                    linear_to_srgb8(value(red, alpha) * unpremultiply),
+                    linear_to_srgb8(value(green, alpha) * unpremultiply),
+                    linear_to_srgb8(value(blue, alpha) * unpremultiply),
+                    (alpha * 255.0).round() as u8,
+                ));
+            }
+        }
+    }
+
+    Ok(Points3D::new(positions)
+        .with_colors(colors)
+        .with_radii([options.radius]))
+}
+
+/// Log the samples of a deep layer as a point cloud to `entity_path/points`,
+/// and the number of samples per pixel as an image to `entity_path/sample_count`.
+pub fn log_deep_layer(
+    recording: &RecordingStream,
+    entity_path: &str,
+    layer: &DeepLayer,
+    options: &DeepPointOptions,
+) -> Result<()> {
+    let samples = deep_samples(layer)?;
+    let points = deep_points(layer, options)?;
+
+    let counts: Vec<u32> = (0..samples.pixel_count())
+        .map(|index| samples.sample_count_at_index(index) as u32)
+        .collect();
+
+    let size = image_size(layer.size)?;
+
+    recording
+        .log(format!("{}/points", entity_path), &points)
+        .map_err(logging_error)?;
+
+    recording
+        .log(
+            format!("{}/sample_count", entity_path),
+            &Image::from_elements(&counts, size, ColorModel::L),
+        )
+        .map_err(logging_error)
+}
+
+/// Log a flat layer as images. The `R`, `G`, `B`, and `A` channels are logged together
+/// as a linear color image to `entity_path/rgba`, and all other channels are logged as
+/// single-channel images to `entity_path/<channel name>`. Subsampled channels are skipped.
+pub fn log_flat_layer(
+    recording: &RecordingStream,
+    entity_path: &str,
+    layer: &FlatLayer,
+) -> Result<()> {
+    let size = image_size(layer.size)?;
+    let pixel_count = layer.size.area();
+
+    let find = |name: &str| {
+        layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.eq(name) && channel.sampling == Vec2(1, 1))
+    };
+
+    let is_color = |name: &str| ["R", "G", "B", "A"].iter().any(|color| *color == name);
+
+    let rgb = (find("R"), find("G"), find("B"));
+    let has_rgb = rgb.0.is_some() && rgb.1.is_some() && rgb.2.is_some();
+
+    if let (Some(red), Some(green), Some(blue)) = rgb {
+        let alpha = find("A");
+        let mut rgba = Vec::with_capacity(pixel_count * 4);
+
+        for index in 0..pixel_count {
+            rgba.push(red.sample_data.value_by_flat_index(index).to_f32());
+            rgba.push(green.sample_data.value_by_flat_index(index).to_f32());
+            rgba.push(blue.sample_data.value_by_flat_index(index).to_f32());
+            rgba.push(alpha.map_or(1.0, |alpha| {
+                alpha.sample_data.value_by_flat_index(index).to_f32()
+            }));
+        }
+
+        recording
+            .log(
+                format!("{}/rgba", entity_path),
+                &Image::from_elements(&rgba, size, ColorModel::RGBA),
+            )
+            .map_err(logging_error)?;
+    }
+
+    for channel in &layer.channel_data.list {
+        let name = channel.name.to_string();
+
+        if (has_rgb && is_color(&name)) || channel.sampling != Vec2(1, 1) {
+            continue;
+        }
+
+        let path = format!("{}/{}", entity_path, name);
+        let image = match &channel.sample_data {
+            FlatSamples::F16(samples) => Image::from_elements(samples, size, ColorModel::L),
+            FlatSamples::F32(samples) => Image::from_elements(samples, size, ColorModel::L),
+            FlatSamples::U32(samples) => Image::from_elements(samples, size, ColorModel::L),
+        };
+
+        recording.log(path, &image).map_err(logging_error)?;
+    }
+
+    Ok(())
+}
+
+fn deep_samples(layer: &DeepLayer) -> Result<&DeepSamples> {
+    // the samples of all channels are stored in the first channel
+    layer
+        .channel_data
+        .list
+        .first()
+        .map(|channel| &channel.sample_data)
+        .ok_or_else(|| Error::invalid("deep layer has no channels"))
+}
+
+fn deep_channel<'l>(
+    layer: &DeepLayer,
+    samples: &'l DeepSamples,
+    name: &str,
+) -> Option<&'l DeepChannelData> {
+    layer
+        .channel_data
+        .list
+        .iter()
+        .position(|channel| channel.name.eq(name))
+        .and_then(|index| samples.channels.get(index))
+}
+
+fn image_size(size: Vec2<usize>) -> Result<[u32; 2]> {
+    let width = u32::try_from(size.width()).map_err(|_| Error::invalid("image width"))?;
+    let height = u32::try_from(size.height()).map_err(|_| Error::invalid("image height"))?;
+    Ok([width, height])
+}
+
+fn logging_error(error: ::rerun::RecordingStreamError) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        error.to_string(),
+    ))
+}
+
+fn linear_to_srgb8(linear: f32) -> u8 {
+    // also maps nan to zero
+    (Transfer::Srgb.encode(linear) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::{AnyChannel, AnyChannels, Encoding, Layer};
+    use crate::math::Vec2;
+    use crate::meta::header::LayerAttributes;
+    use smallvec::smallvec;
+
+    fn deep_layer() -> DeepLayer {
+        // two pixels, the first with two samples, the second with one
+        let mut samples = DeepSamples::new(2, 1);
+        samples.sample_offsets = vec![2, 3];
+        samples.channels = vec![
+            DeepChannelData::F32(vec![1.0, 0.5, 0.5]),
+            DeepChannelData::F32(vec![0.5, 1.0, 0.0]),
+            DeepChannelData::F32(vec![10.0, 20.0, 30.0]),
+        ];
+
+        let channels = AnyChannels::sort(smallvec![
+            AnyChannel::new("A", samples),
+            AnyChannel::new("R", DeepSamples::new(0, 0)),
+            AnyChannel::new("Z", DeepSamples::new(0, 0)),
+        ]);
+
+        Layer {
+            channel_data: channels,
+            attributes: LayerAttributes::default(),
+            size: Vec2(2, 1),
+            encoding: Encoding::default(),
+        }
+    }
+
+    #[test]
+    fn deep_channels_by_name() {
+        let layer = deep_layer();
+        let samples = deep_samples(&layer).unwrap();
+
+        assert_eq!(
+            deep_channel(&layer, samples, "Z"),
+            Some(&DeepChannelData::F32(vec![10.0, 20.0, 30.0]))
+        );
+        assert_eq!(deep_channel(&layer, samples, "G"), None);
+
+        let points = deep_points(&layer, &DeepPointOptions::default());
+        assert!(points.is_ok());
+    }
+
+    #[test]
+    fn missing_depth() {
+        let mut layer = deep_layer();
+        layer.channel_data.list[2].name = "depth".into();
+        assert!(deep_points(&layer, &DeepPointOptions::default()).is_err());
+    }
+
+    #[test]
+    fn srgb_curve() {
+        assert_eq!(linear_to_srgb8(0.0), 0);
+        assert_eq!(linear_to_srgb8(f32::NAN), 0);
+        assert_eq!(linear_to_srgb8(1.0), 255);
+        assert_eq!(linear_to_srgb8(0.5), 188);
+    }
+}
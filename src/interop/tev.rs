@@ -0,0 +1,753 @@
+//! Send images to a running [tev](https://github.com/Tom94/tev) viewer over its TCP protocol.
+//! Enable with the `tev` feature, which adds no dependencies.
+//!
+//! tev listens on `127.0.0.1:14158` by default. Images are created in tev once,
+//! and can then be updated region by region, for example while a renderer makes progress.
+//! Viewers that accept the same commands can decode them with `read_command`.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//! use exr::interop::tev::{send_to_tev, DEFAULT_ADDRESS};
+//!
+//! let image = read_all_flat_layers_from_file("render.exr").unwrap();
+//! send_to_tev(DEFAULT_ADDRESS, &image).unwrap();
+//! ```
+
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use super::FlatLayer;
+use crate::error::{Error, Result, UnitResult};
+use crate::image::FlatImage;
+use crate::math::Vec2;
+
+/// The address that tev listens on, unless configured otherwise.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:14158";
+
+/// Images are sent in tiles of this size, like the official python client does,
+/// so that tev can display parts of large images while they are still being received.
+pub const TILE_SIZE: usize = 128;
+
+/// The packet types of the tev protocol.
+mod packet {
+    pub const OPEN_IMAGE: u8 = 0;
+    pub const RELOAD_IMAGE: u8 = 1;
+    pub const CLOSE_IMAGE: u8 = 2;
+    pub const UPDATE_IMAGE: u8 = 3;
+    pub const CREATE_IMAGE: u8 = 4;
+    pub const UPDATE_IMAGE_V2: u8 = 5;
+    pub const UPDATE_IMAGE_V3: u8 = 6;
+    pub const OPEN_IMAGE_V2: u8 = 7;
+}
+
+/// A command of the tev protocol, as sent by `TevClient`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TevCommand {
+    /// Open an image file from the file system of the viewer.
+    OpenImage {
+        /// The path of the file.
+        path: String,
+
+        /// Selects the channels to show, such as `diffuse.*`, or is empty.
+        channel_selector: String,
+
+        /// Whether the viewer should show this image.
+        grab_focus: bool,
+    },
+
+    /// Read an image file again.
+    ReloadImage {
+        /// The name of the image, which is its path for opened files.
+        name: String,
+
+        /// Whether the viewer should show this image.
+        grab_focus: bool,
+    },
+
+    /// Close an image.
+    CloseImage {
+        /// The name of the image, which is its path for opened files.
+        name: String,
+    },
+
+    /// Create an empty image, replacing any image with the same name.
+    CreateImage {
+        /// The name of the new image.
+        name: String,
+
+        /// The width and height of the image.
+        size: Vec2<usize>,
+
+        /// The names of all channels of the image.
+        channel_names: Vec<String>,
+
+        /// Whether the viewer should show this image.
+        grab_focus: bool,
+    },
+
+    /// Replace a region of some channels of an image.
+    UpdateImage {
+        /// The name of the image that was created before.
+        name: String,
+
+        /// The top left corner of the region.
+        position: Vec2<usize>,
+
+        /// The width and height of the region.
+        size: Vec2<usize>,
+
+        /// The names of the updated channels.
+        channel_names: Vec<String>,
+
+        /// The samples of each updated channel, row after row, `size.area()` per channel.
+        channel_samples: Vec<Vec<f32>>,
+
+        /// Whether the viewer should show this image.
+        grab_focus: bool,
+    },
+
+    /// A packet type that tev does not define, which viewers may use to extend the protocol.
+    Other {
+        /// The type of the packet.
+        packet_type: u8,
+
+        /// The contents of the packet after its type.
+        payload: Vec<u8>,
+    },
+}
+
+/// Send all layers of an image to tev, each as a separate image named like the layer.
+/// Unnamed layers are named `image` followed by their index. Subsampled channels are skipped.
+pub fn send_to_tev(address: impl ToSocketAddrs, image: &FlatImage) -> UnitResult {
+    let mut client = TevClient::connect(address)?;
+
+    for (index, layer) in image.layer_data.iter().enumerate() {
+        let name = layer
+            .attributes
+            .layer_name
+            .as_ref()
+            .map_or_else(|| format!("image{}", index), |name| name.to_string());
+
+        client.send_layer(&name, layer, index == 0)?;
+    }
+
+    Ok(())
+}
+
+/// A connection to tev. Each method sends one command, tev does not answer.
+#[derive(Debug)]
+pub struct TevClient<W: Write = TcpStream> {
+    write: W,
+}
+
+impl TevClient {
+    /// Connect to a running tev instance, for example at `DEFAULT_ADDRESS`.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        Ok(TevClient::new(stream))
+    }
+}
+
+impl<W: Write> TevClient<W> {
+    /// Send commands to any byte stream, such as a connection that is already open.
+    pub fn new(write: W) -> Self {
+        TevClient { write }
+    }
+
+    /// Ask tev to open an image file from its own file system.
+    /// The channel selector can be empty, or select channels like `diffuse.*`.
+    pub fn open_image(
+        &mut self,
+        path: &str,
+        channel_selector: &str,
+        grab_focus: bool,
+    ) -> UnitResult {
+        let mut packet = Packet::new(packet::OPEN_IMAGE_V2);
+        packet.push_bool(grab_focus);
+        packet.push_string(path)?;
+        packet.push_string(channel_selector)?;
+        self.send(packet)
+    }
+
+    /// Ask tev to read an image file again.
+    pub fn reload_image(&mut self, name: &str, grab_focus: bool) -> UnitResult {
+        let mut packet = Packet::new(packet::RELOAD_IMAGE);
+        packet.push_bool(grab_focus);
+        packet.push_string(name)?;
+        self.send(packet)
+    }
+
+    /// Close an image in tev.
+    pub fn close_image(&mut self, name: &str) -> UnitResult {
+        let mut packet = Packet::new(packet::CLOSE_IMAGE);
+        packet.push_string(name)?;
+        self.send(packet)
+    }
+
+    /// Create an empty image in tev, replacing any image with the same name.
+    pub fn create_image(
+        &mut self,
+        name: &str,
+        size: Vec2<usize>,
+        channel_names: &[&str],
+        grab_focus: bool,
+    ) -> UnitResult {
+        let mut packet = Packet::new(packet::CREATE_IMAGE);
+        packet.push_bool(grab_focus);
+        packet.push_string(name)?;
+        packet.push_i32(size.width())?;
+        packet.push_i32(size.height())?;
+        packet.push_i32(channel_names.len())?;
+
+        for channel_name in channel_names {
+            packet.push_string(channel_name)?;
+        }
+
+        self.send(packet)
+    }
+
+    /// Update a region of an image that was created before.
+    /// The samples of all channels are interleaved, pixel after pixel and row after row,
+    /// so there are `size.area() * channel_names.len()` samples.
+    pub fn update_image(
+        &mut self,
+        name: &str,
+        position: Vec2<usize>,
+        size: Vec2<usize>,
+        channel_names: &[&str],
+        samples: &[f32],
+        grab_focus: bool,
+    ) -> UnitResult {
+        let channel_count = channel_names.len();
+
+        if samples.len() != size.area() * channel_count {
+            return Err(Error::invalid(
+                "sample count does not match the region size and channel count",
+            ));
+        }
+
+        let mut packet = Packet::new(packet::UPDATE_IMAGE_V3);
+        packet.push_bool(grab_focus);
+        packet.push_string(name)?;
+        packet.push_i32(channel_count)?;
+
+        for channel_name in channel_names {
+            packet.push_string(channel_name)?;
+        }
+
+        // the offset and stride of each channel within the interleaved samples
+        for offset in 0..channel_count {
+            packet
+                .bytes
+                .extend_from_slice(&(offset as i64).to_le_bytes());
+        }
+
+        for _ in 0..channel_count {
+            packet
+                .bytes
+                .extend_from_slice(&(channel_count as i64).to_le_bytes());
+        }
+
+        packet.push_i32(position.x())?;
+        packet.push_i32(position.y())?;
+        packet.push_i32(size.width())?;
+        packet.push_i32(size.height())?;
+
+        packet.bytes.reserve(samples.len() * 4);
+        for sample in samples {
+            packet.bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        self.send(packet)
+    }
+
+    /// Create an image with all channels of a layer, and send its samples in tiles.
+    /// Subsampled channels are skipped.
+    pub fn send_layer(&mut self, name: &str, layer: &FlatLayer, grab_focus: bool) -> UnitResult {
+        let channels: Vec<_> = layer
+            .channel_data
+            .list
+            .iter()
+            .filter(|channel| channel.sampling == Vec2(1, 1))
+            .collect();
+
+        let names: Vec<String> = channels
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        self.create_image(name, layer.size, &names, grab_focus)?;
+
+        for tile_y in (0..layer.size.height()).step_by(TILE_SIZE) {
+            for tile_x in (0..layer.size.width()).step_by(TILE_SIZE) {
+                let position = Vec2(tile_x, tile_y);
+                let size = Vec2(
+                    TILE_SIZE.min(layer.size.width() - tile_x),
+                    TILE_SIZE.min(layer.size.height() - tile_y),
+                );
+
+                let mut samples = Vec::with_capacity(size.area() * channels.len());
+                for y in tile_y..tile_y + size.height() {
+                    for x in tile_x..tile_x + size.width() {
+                        let index = Vec2(x, y).flat_index_for_size(layer.size);
+
+                        for channel in &channels {
+                            samples.push(channel.sample_data.value_by_flat_index(index).to_f32());
+                        }
+                    }
+                }
+
+                self.update_image(name, position, size, &names, &samples, false)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send a packet with a type that tev does not define, to viewers that extend the protocol.
+    pub fn send_custom(&mut self, packet_type: u8, payload: &[u8]) -> UnitResult {
+        let mut packet = Packet::new(packet_type);
+        packet.bytes.extend_from_slice(payload);
+        self.send(packet)
+    }
+
+    /// The byte stream that commands are sent to.
+    pub fn into_inner(self) -> W {
+        self.write
+    }
+
+    fn send(&mut self, packet: Packet) -> UnitResult {
+        let bytes = packet.finish()?;
+        self.write.write_all(&bytes)?;
+        self.write.flush()?;
+        Ok(())
+    }
+}
+
+/// A packet starts with its length, including the length itself, followed by its type.
+struct Packet {
+    bytes: Vec<u8>,
+}
+
+impl Packet {
+    fn new(packet_type: u8) -> Self {
+        let mut bytes = vec![0; 4];
+        bytes.push(packet_type);
+        Packet { bytes }
+    }
+
+    fn push_bool(&mut self, value: bool) {
+        self.bytes.push(value as u8);
+    }
+
+    fn push_i32(&mut self, value: usize) -> UnitResult {
+        let value = i32::try_from(value).map_err(|_| Error::invalid("image size"))?;
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+
+    /// Strings are terminated by a zero byte, so they cannot contain one.
+    fn push_string(&mut self, value: &str) -> UnitResult {
+        if value.contains('\0') {
+            return Err(Error::invalid("tev names cannot contain zero bytes"));
+        }
+
+        self.bytes.extend_from_slice(value.as_bytes());
+        self.bytes.push(0);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        let length = u32::try_from(self.bytes.len())
+            .map_err(|_| Error::unsupported("tev packets larger than 4 gigabytes"))?;
+
+        self.bytes[..4].copy_from_slice(&length.to_le_bytes());
+        Ok(self.bytes)
+    }
+}
+
+/// Read the next command that a client sent, all packet versions are supported.
+/// Returns `None` if the stream ended before the next packet.
+pub fn read_command(mut read: impl Read) -> Result<Option<TevCommand>> {
+    let mut length = [0; 4];
+
+    match read.read_exact(&mut length) {
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+
+    let length = u32::from_le_bytes(length) as usize;
+    if length < 5 {
+        return Err(Error::invalid("tev packet length"));
+    }
+
+    // grows while reading, so an invalid length does not allocate too much memory
+    let mut bytes = Vec::new();
+    read.take(length as u64 - 4).read_to_end(&mut bytes)?;
+
+    if bytes.len() != length - 4 {
+        return Err(Error::invalid("tev packet ended early"));
+    }
+
+    let mut payload = PacketReader { bytes: &bytes[1..] };
+
+    let command = match bytes[0] {
+        packet::OPEN_IMAGE => {
+            let grab_focus = payload.bool()?;
+            let image = payload.string()?;
+
+            // the channel selector follows the last colon, which is not the colon of a drive letter
+            let (path, channel_selector) = match image.rfind(':') {
+                Some(index) if index != 1 => (&image[..index], &image[index + 1..]),
+                _ => (image.as_str(), ""),
+            };
+
+            TevCommand::OpenImage {
+                path: path.to_string(),
+                channel_selector: channel_selector.to_string(),
+                grab_focus,
+            }
+        }
+
+        packet::OPEN_IMAGE_V2 => TevCommand::OpenImage {
+            grab_focus: payload.bool()?,
+            path: payload.string()?,
+            channel_selector: payload.string()?,
+        },
+
+        packet::RELOAD_IMAGE => TevCommand::ReloadImage {
+            grab_focus: payload.bool()?,
+            name: payload.string()?,
+        },
+
+        packet::CLOSE_IMAGE => TevCommand::CloseImage {
+            name: payload.string()?,
+        },
+
+        packet::CREATE_IMAGE => {
+            let grab_focus = payload.bool()?;
+            let name = payload.string()?;
+            let size = Vec2(payload.size()?, payload.size()?);
+            let channel_count = payload.size()?;
+
+            TevCommand::CreateImage {
+                name,
+                size,
+                channel_names: payload.strings(channel_count)?,
+                grab_focus,
+            }
+        }
+
+        packet_type
+        @ (packet::UPDATE_IMAGE | packet::UPDATE_IMAGE_V2 | packet::UPDATE_IMAGE_V3) => {
+            let grab_focus = payload.bool()?;
+            let name = payload.string()?;
+
+            let channel_names = if packet_type == packet::UPDATE_IMAGE {
+                vec![payload.string()?]
+            } else {
+                let channel_count = payload.size()?;
+                payload.strings(channel_count)?
+            };
+
+            let channel_count = channel_names.len();
+
+            // the samples of each channel start at an offset and are a stride apart
+            let (offsets, strides) = if packet_type == packet::UPDATE_IMAGE_V3 {
+                let offsets = (0..channel_count)
+                    .map(|_| payload.offset())
+                    .collect::<Result<Vec<_>>>()?;
+
+                let strides = (0..channel_count)
+                    .map(|_| payload.offset())
+                    .collect::<Result<Vec<_>>>()?;
+
+                (offsets, strides)
+            } else {
+                (Vec::new(), Vec::new())
+            };
+
+            let position = Vec2(payload.size()?, payload.size()?);
+            let size = Vec2(payload.size()?, payload.size()?);
+            let samples = payload.samples()?;
+
+            let (offsets, strides) = if packet_type == packet::UPDATE_IMAGE_V3 {
+                (offsets, strides)
+            } else {
+                // older versions store one channel after another
+                let offsets = (0..channel_count)
+                    .map(|channel| channel * size.area())
+                    .collect();
+                (offsets, vec![1; channel_count])
+            };
+
+            let channel_samples = offsets
+                .iter()
+                .zip(&strides)
+                .map(|(&offset, &stride)| {
+                    (0..size.area())
+                        .map(|index| {
+                            samples
+                                .get(offset + index * stride)
+                                .copied()
+                                .ok_or_else(|| Error::invalid("tev packet sample count"))
+                        })
+                        .collect::<Result<Vec<f32>>>()
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            TevCommand::UpdateImage {
+                name,
+                position,
+                size,
+                channel_names,
+                channel_samples,
+                grab_focus,
+            }
+        }
+
+        packet_type => TevCommand::Other {
+            packet_type,
+            payload: bytes[1..].to_vec(),
+        },
+    };
+
+    Ok(Some(command))
+}
+
+/// Reads the values of a packet, in the same order as they were written.
+struct PacketReader<'b> {
+    bytes: &'b [u8],
+}
+
+impl PacketReader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8]> {
+        if self.bytes.len() < count {
+            return Err(Error::invalid("tev packet ended early"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        Ok(self.take(1)?[0] != 0)
+    }
+
+    /// A non-negative `i32`.
+    fn size(&mut self) -> Result<usize> {
+        let bytes = self.take(4)?;
+        let value = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        usize::try_from(value).map_err(|_| Error::invalid("negative size in tev packet"))
+    }
+
+    /// A non-negative `i64`.
+    fn offset(&mut self) -> Result<usize> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        usize::try_from(i64::from_le_bytes(value))
+            .map_err(|_| Error::invalid("negative offset in tev packet"))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let length = self
+            .bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| Error::invalid("unterminated string in tev packet"))?;
+
+        let string = String::from_utf8_lossy(&self.bytes[..length]).into_owned();
+        self.bytes = &self.bytes[length + 1..];
+        Ok(string)
+    }
+
+    fn strings(&mut self, count: usize) -> Result<Vec<String>> {
+        (0..count).map(|_| self.string()).collect()
+    }
+
+    /// All remaining bytes as `f32` samples.
+    fn samples(&mut self) -> Result<Vec<f32>> {
+        if self.bytes.len() % 4 != 0 {
+            return Err(Error::invalid("tev packet sample count"));
+        }
+
+        let samples = self
+            .bytes
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+
+        self.bytes = &[];
+        Ok(samples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::{AnyChannel, AnyChannels, Encoding, FlatSamples, Layer};
+    use crate::meta::header::LayerAttributes;
+    use smallvec::smallvec;
+
+    #[test]
+    fn create_image_packet() {
+        let mut client = TevClient::new(Vec::new());
+        client
+            .create_image("beauty", Vec2(3, 2), &["R", "G"], true)
+            .unwrap();
+
+        let bytes = client.into_inner();
+        let mut expected = vec![0, 0, 0, 0, packet::CREATE_IMAGE, 1];
+        expected.extend_from_slice(b"beauty\0");
+        expected.extend_from_slice(&3_i32.to_le_bytes());
+        expected.extend_from_slice(&2_i32.to_le_bytes());
+        expected.extend_from_slice(&2_i32.to_le_bytes());
+        expected.extend_from_slice(b"R\0G\0");
+        let length = expected.len() as u32;
+        expected[..4].copy_from_slice(&length.to_le_bytes());
+
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn send_layer_in_tiles() {
+        let size = Vec2(TILE_SIZE + 1, 2);
+        let channels = AnyChannels::sort(smallvec![
+            AnyChannel::new("Y", FlatSamples::F32(vec![0.5; size.area()])),
+            AnyChannel::new("id", FlatSamples::U32(vec![3; size.area()])),
+        ]);
+
+        let layer = Layer {
+            channel_data: channels,
+            attributes: LayerAttributes::default(),
+            size,
+            encoding: Encoding::default(),
+        };
+
+        let mut client = TevClient::new(Vec::new());
+        client.send_layer("layer", &layer, false).unwrap();
+        let bytes = client.into_inner();
+
+        // one create packet and two update packets, one for each tile
+        let mut packet_types = Vec::new();
+        let mut position = 0;
+        while position < bytes.len() {
+            let length = u32::from_le_bytes([
+                bytes[position],
+                bytes[position + 1],
+                bytes[position + 2],
+                bytes[position + 3],
+            ]);
+
+            packet_types.push(bytes[position + 4]);
+            position += length as usize;
+        }
+
+        assert_eq!(position, bytes.len());
+        assert_eq!(
+            packet_types,
+            vec![
+                packet::CREATE_IMAGE,
+                packet::UPDATE_IMAGE_V3,
+                packet::UPDATE_IMAGE_V3
+            ]
+        );
+
+        // the last packet ends with the samples of a 1x2 tile
+        let last_samples: Vec<u8> = [0.5_f32, 3.0, 0.5, 3.0]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+
+        assert!(bytes.ends_with(&last_samples));
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let mut client = TevClient::new(Vec::new());
+        client.open_image("shot.exr", "diffuse.*", true).unwrap();
+        client
+            .create_image("live", Vec2(2, 1), &["R", "G"], false)
+            .unwrap();
+        client
+            .update_image(
+                "live",
+                Vec2(0, 0),
+                Vec2(2, 1),
+                &["R", "G"],
+                &[1.0, 2.0, 3.0, 4.0],
+                true,
+            )
+            .unwrap();
+        client.send_custom(200, &[7, 8]).unwrap();
+
+        let bytes = client.into_inner();
+        let mut read = bytes.as_slice();
+        let mut commands = Vec::new();
+        while let Some(command) = read_command(&mut read).unwrap() {
+            commands.push(command);
+        }
+
+        assert_eq!(
+            commands,
+            vec![
+                TevCommand::OpenImage {
+                    path: "shot.exr".to_string(),
+                    channel_selector: "diffuse.*".to_string(),
+                    grab_focus: true,
+                },
+                TevCommand::CreateImage {
+                    name: "live".to_string(),
+                    size: Vec2(2, 1),
+                    channel_names: vec!["R".to_string(), "G".to_string()],
+                    grab_focus: false,
+                },
+                TevCommand::UpdateImage {
+                    name: "live".to_string(),
+                    position: Vec2(0, 0),
+                    size: Vec2(2, 1),
+                    channel_names: vec!["R".to_string(), "G".to_string()],
+                    channel_samples: vec![vec![1.0, 3.0], vec![2.0, 4.0]],
+                    grab_focus: true,
+                },
+                TevCommand::Other {
+                    packet_type: 200,
+                    payload: vec![7, 8],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_open_image() {
+        let mut packet = Packet::new(packet::OPEN_IMAGE);
+        packet.push_bool(false);
+        packet.push_string("C:/renders/shot.exr:Z").unwrap();
+        let bytes = packet.finish().unwrap();
+
+        assert_eq!(
+            read_command(bytes.as_slice()).unwrap(),
+            Some(TevCommand::OpenImage {
+                path: "C:/renders/shot.exr".to_string(),
+                channel_selector: "Z".to_string(),
+                grab_focus: false,
+            })
+        );
+
+        // a truncated packet
+        assert!(read_command(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn reject_invalid_input() {
+        let mut client = TevClient::new(Vec::new());
+        assert!(client.close_image("a\0b").is_err());
+        assert!(client
+            .update_image("a", Vec2(0, 0), Vec2(2, 2), &["R"], &[0.0; 3], false)
+            .is_err());
+    }
+}
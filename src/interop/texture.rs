@@ -0,0 +1,663 @@
+//! Export layers as KTX2 or DDS textures, for game engines and other real-time renderers.
+//! Enable with the `texture` feature, which adds no dependencies.
+//!
+//! Textures are stored either as uncompressed `RGBA16F`, or compressed as `BC6H`,
+//! which keeps the high dynamic range of lightmaps and environment maps at 8 bits per pixel.
+//! The `BC6H` encoder chooses the endpoints from the bounding box of each block,
+//! which is fast, but not as accurate as the encoders of dedicated texture tools.
+//! Mip maps of the exr file are stored as the mip chain of the texture.
+//!
+//! ```no_run
+//! use exr::prelude::*;
+//! use exr::interop::texture::{rgba_levels, write_ktx2, TextureFormat};
+//!
+//! let image = read_all_data_from_file("lightmap.exr").unwrap();
+//! let levels = rgba_levels(&image.layer_data[0], &["R", "G", "B"]).unwrap();
+//!
+//! let file = std::fs::File::create("lightmap.ktx2").unwrap();
+//! write_ktx2(std::io::BufWriter::new(file), &levels, TextureFormat::Bc6h).unwrap();
+//! ```
+
+use std::convert::TryFrom;
+use std::io::Write;
+
+use super::LeveledLayer;
+use crate::error::{Error, Result, UnitResult};
+use crate::image::Levels;
+use crate::math::Vec2;
+use half::f16;
+
+/// How the pixels of a texture are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    /// Four 16-bit floats per pixel, without compression.
+    Rgba16Float,
+
+    /// Unsigned 16-bit floats without alpha, compressed to blocks of 4 by 4 pixels.
+    /// Negative values are stored as zero.
+    Bc6h,
+}
+
+/// One resolution level of a texture. The first level has the largest resolution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureLevel {
+    /// The width and height of this level.
+    pub size: Vec2<usize>,
+
+    /// The linear rgba pixels of this level, row after row.
+    pub pixels: Vec<[f32; 4]>,
+}
+
+/// The pixels of all resolution levels of a layer.
+/// Specify one channel for gray, two for gray and alpha, three for rgb, or four for rgba.
+/// Alpha is one where no alpha channel is specified. Returns an error for rip maps,
+/// for subsampled channels, and for channels that do not exist.
+pub fn rgba_levels(layer: &LeveledLayer, channels: &[&str]) -> Result<Vec<TextureLevel>> {
+    if channels.is_empty() || channels.len() > 4 {
+        return Err(Error::invalid(
+            "select one to four channels: gray, gray and alpha, rgb, or rgba",
+        ));
+    }
+
+    let channels = channels
+        .iter()
+        .map(|name| {
+            let channel = layer
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.name.eq(name))
+                .ok_or_else(|| Error::invalid(format!("layer has no channel named `{}`", name)))?;
+
+            if channel.sampling != Vec2(1, 1) {
+                return Err(Error::unsupported("textures with subsampled channels"));
+            }
+
+            if let Levels::Rip { .. } = channel.sample_data {
+                return Err(Error::unsupported("textures with rip maps"));
+            }
+
+            Ok(&channel.sample_data)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let sizes = layer
+        .levels_with_resolution(channels[0])
+        .map(|(_, size)| size);
+
+    sizes
+        .enumerate()
+        .map(|(level, size)| {
+            let samples: Vec<_> = channels
+                .iter()
+                .map(|levels| levels.levels_as_slice().get(level))
+                .collect::<Option<_>>()
+                .ok_or_else(|| Error::invalid("channels have different resolution levels"))?;
+
+            let value = |channel: usize, index: usize| -> f32 {
+                samples[channel].value_by_flat_index(index).to_f32()
+            };
+
+            let pixels = (0..size.area())
+                .map(|index| match samples.len() {
+                    1 => [value(0, index), value(0, index), value(0, index), 1.0],
+                    2 => [
+                        value(0, index),
+                        value(0, index),
+                        value(0, index),
+                        value(1, index),
+                    ],
+                    3 => [value(0, index), value(1, index), value(2, index), 1.0],
+                    _ => [
+                        value(0, index),
+                        value(1, index),
+                        value(2, index),
+                        value(3, index),
+                    ],
+                })
+                .collect();
+
+            Ok(TextureLevel { size, pixels })
+        })
+        .collect()
+}
+
+/// Write the levels as a KTX2 texture. Each level must be half as large as the previous level,
+/// rounded down, like the mip levels of exr files with the rounding mode `Down`.
+pub fn write_ktx2(
+    mut write: impl Write,
+    levels: &[TextureLevel],
+    format: TextureFormat,
+) -> UnitResult {
+    validate_levels(levels)?;
+
+    let level_data: Vec<Vec<u8>> = levels
+        .iter()
+        .map(|level| level_bytes(level, format))
+        .collect();
+
+    const IDENTIFIER: [u8; 12] = [
+        0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+    ];
+
+    // the vulkan format, the size of its components, and the alignment of its texel blocks
+    let (vulkan_format, type_size, alignment) = match format {
+        TextureFormat::Rgba16Float => (97_u32, 2_u32, 8),
+        TextureFormat::Bc6h => (143, 1, 16),
+    };
+
+    let descriptor = data_format_descriptor(format);
+    let descriptor_offset = IDENTIFIER.len() + 9 * 4 + 4 * 4 + 2 * 8 + levels.len() * 3 * 8;
+
+    // the level index starts with the largest level, but the data starts with the smallest level
+    let mut level_offsets = vec![0; levels.len()];
+    let mut end = descriptor_offset + descriptor.len();
+    for (offset, data) in level_offsets.iter_mut().zip(&level_data).rev() {
+        *offset = end + (alignment - end % alignment) % alignment;
+        end = *offset + data.len();
+    }
+
+    let base_size = levels[0].size;
+    let mut header = Vec::with_capacity(descriptor_offset + descriptor.len());
+    header.extend_from_slice(&IDENTIFIER);
+
+    for value in [
+        vulkan_format,
+        type_size,
+        to_u32(base_size.width())?,
+        to_u32(base_size.height())?,
+        0, // depth
+        0, // array layers
+        1, // faces
+        to_u32(levels.len())?,
+        0, // supercompression
+        to_u32(descriptor_offset)?,
+        to_u32(descriptor.len())?,
+        0, // key value data offset
+        0, // key value data length
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+
+    header.extend_from_slice(&0_u64.to_le_bytes()); // supercompression data offset
+    header.extend_from_slice(&0_u64.to_le_bytes()); // supercompression data length
+
+    for (offset, data) in level_offsets.iter().zip(&level_data) {
+        header.extend_from_slice(&(*offset as u64).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u64).to_le_bytes()); // uncompressed length
+    }
+
+    header.extend_from_slice(&descriptor);
+    write.write_all(&header)?;
+
+    let mut position = header.len();
+    for (offset, data) in level_offsets.iter().zip(&level_data).rev() {
+        write.write_all(&vec![0; offset - position])?;
+        write.write_all(data)?;
+        position = offset + data.len();
+    }
+
+    write.flush()?;
+    Ok(())
+}
+
+/// Write the levels as a DDS texture with a DX10 header.
+/// Each level must be half as large as the previous level, rounded down.
+pub fn write_dds(
+    mut write: impl Write,
+    levels: &[TextureLevel],
+    format: TextureFormat,
+) -> UnitResult {
+    validate_levels(levels)?;
+
+    const CAPS: u32 = 0x1;
+    const HEIGHT: u32 = 0x2;
+    const WIDTH: u32 = 0x4;
+    const PITCH: u32 = 0x8;
+    const PIXEL_FORMAT: u32 = 0x1000;
+    const MIP_MAP_COUNT: u32 = 0x2_0000;
+    const LINEAR_SIZE: u32 = 0x8_0000;
+    const FOUR_CC: u32 = 0x4;
+    const COMPLEX: u32 = 0x8;
+    const TEXTURE: u32 = 0x1000;
+    const MIP_MAP: u32 = 0x40_0000;
+
+    let level_data: Vec<Vec<u8>> = levels
+        .iter()
+        .map(|level| level_bytes(level, format))
+        .collect();
+
+    let base_size = levels[0].size;
+    let has_mip_maps = levels.len() > 1;
+
+    let (dxgi_format, pitch_flag, pitch) = match format {
+        TextureFormat::Rgba16Float => (10_u32, PITCH, base_size.width() * 8),
+        TextureFormat::Bc6h => (95, LINEAR_SIZE, level_data[0].len()),
+    };
+
+    let mut flags = CAPS | HEIGHT | WIDTH | PIXEL_FORMAT | pitch_flag;
+    let mut caps = TEXTURE;
+
+    if has_mip_maps {
+        flags |= MIP_MAP_COUNT;
+        caps |= COMPLEX | MIP_MAP;
+    }
+
+    let mut header = Vec::with_capacity(4 + 124 + 20);
+    header.extend_from_slice(b"DDS ");
+
+    let mut fields = vec![
+        124,
+        flags,
+        to_u32(base_size.height())?,
+        to_u32(base_size.width())?,
+        to_u32(pitch)?,
+        0, // depth
+        to_u32(levels.len())?,
+    ];
+
+    fields.extend_from_slice(&[0; 11]); // reserved
+    fields.extend_from_slice(&[32, FOUR_CC, u32::from_le_bytes(*b"DX10"), 0, 0, 0, 0, 0]);
+    fields.extend_from_slice(&[caps, 0, 0, 0, 0]);
+
+    // the dx10 header: format, two dimensional texture, no flags, one array element, unknown alpha
+    fields.extend_from_slice(&[dxgi_format, 3, 0, 1, 0]);
+
+    for field in fields {
+        header.extend_from_slice(&field.to_le_bytes());
+    }
+
+    write.write_all(&header)?;
+
+    for data in &level_data {
+        write.write_all(data)?;
+    }
+
+    write.flush()?;
+    Ok(())
+}
+
+fn validate_levels(levels: &[TextureLevel]) -> UnitResult {
+    let base_size = levels
+        .first()
+        .ok_or_else(|| Error::invalid("texture without levels"))?
+        .size;
+
+    if base_size.area() == 0 {
+        return Err(Error::invalid("texture without pixels"));
+    }
+
+    for (index, level) in levels.iter().enumerate() {
+        let expected_size = Vec2(
+            (base_size.width() >> index).max(1),
+            (base_size.height() >> index).max(1),
+        );
+
+        if level.size != expected_size {
+            return Err(Error::unsupported(
+                "texture mip levels that are not rounded down",
+            ));
+        }
+
+        if level.pixels.len() != level.size.area() {
+            return Err(Error::invalid("texture level pixel count"));
+        }
+    }
+
+    Ok(())
+}
+
+fn to_u32(value: usize) -> Result<u32> {
+    u32::try_from(value).map_err(|_| Error::unsupported("texture larger than 4 gigabytes"))
+}
+
+fn level_bytes(level: &TextureLevel, format: TextureFormat) -> Vec<u8> {
+    match format {
+        TextureFormat::Rgba16Float => level
+            .pixels
+            .iter()
+            .flatten()
+            .flat_map(|&sample| f16::from_f32(sample).to_le_bytes())
+            .collect(),
+
+        TextureFormat::Bc6h => bc6h::encode_level(level),
+    }
+}
+
+/// The Khronos data format descriptor of a format, which KTX2 files require.
+fn data_format_descriptor(format: TextureFormat) -> Vec<u8> {
+    const FLOAT: u32 = 0x80;
+    const SIGNED: u32 = 0x40;
+    const BT709: u32 = 1;
+    const LINEAR: u32 = 1;
+    const ONE: u32 = 0x3F80_0000;
+    const MINUS_ONE: u32 = 0xBF80_0000;
+
+    // the color model, the block size minus one, the bytes per block, and the samples
+    let (model, block_size, block_bytes, samples): (u32, u32, u32, Vec<[u32; 4]>) = match format {
+        TextureFormat::Rgba16Float => {
+            // red, green, blue, and alpha channel ids, each 16 bits
+            let samples = [0, 1, 2, 15]
+                .iter()
+                .enumerate()
+                .map(|(index, channel)| {
+                    let offset = index as u32 * 16;
+                    let channel_type = channel | FLOAT | SIGNED;
+                    [
+                        offset | (15 << 16) | (channel_type << 24),
+                        0,
+                        MINUS_ONE,
+                        ONE,
+                    ]
+                })
+                .collect();
+
+            (1, 0, 8, samples)
+        }
+
+        TextureFormat::Bc6h => (
+            131,
+            3 | (3 << 8),
+            16,
+            vec![[(127 << 16) | (FLOAT << 24), 0, 0, ONE]],
+        ),
+    };
+
+    let block_length = 24 + 16 * samples.len() as u32;
+    let mut words = vec![
+        4 + block_length,
+        0, // vendor and descriptor type
+        2 | (block_length << 16),
+        model | (BT709 << 8) | (LINEAR << 16),
+        block_size,
+        block_bytes,
+        0,
+    ];
+
+    for sample in samples {
+        words.extend_from_slice(&sample);
+    }
+
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// A simple encoder for unsigned `BC6H` blocks, using the single-region mode
+/// with 10-bit endpoints and 4-bit indices.
+mod bc6h {
+    use super::TextureLevel;
+    use half::f16;
+
+    /// The interpolation weights of 4-bit indices, out of 64.
+    const WEIGHTS: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+    /// The largest finite half float.
+    const MAX_HALF: u32 = 0x7BFF;
+
+    pub fn encode_level(level: &TextureLevel) -> Vec<u8> {
+        let (width, height) = (level.size.width(), level.size.height());
+        let mut bytes = Vec::with_capacity(((width + 3) / 4) * ((height + 3) / 4) * 16);
+
+        for block_y in (0..height).step_by(4) {
+            for block_x in (0..width).step_by(4) {
+                let mut block = [[0; 3]; 16];
+
+                // blocks at the edge repeat the last row and column
+                for (index, texel) in block.iter_mut().enumerate() {
+                    let x = (block_x + index % 4).min(width - 1);
+                    let y = (block_y + index / 4).min(height - 1);
+                    let pixel = level.pixels[y * width + x];
+                    *texel = [
+                        half_bits(pixel[0]),
+                        half_bits(pixel[1]),
+                        half_bits(pixel[2]),
+                    ];
+                }
+
+                bytes.extend_from_slice(&encode_block(&block));
+            }
+        }
+
+        bytes
+    }
+
+    /// The bits of an unsigned half float, with negative and invalid values clamped.
+    pub fn half_bits(value: f32) -> u32 {
+        if value > 0.0 {
+            u32::from(f16::from_f32(value).to_bits()).min(MAX_HALF)
+        } else {
+            0 // also nan
+        }
+    }
+
+    /// Encode the half float bits of 16 texels, row after row.
+    pub fn encode_block(texels: &[[u32; 3]; 16]) -> [u8; 16] {
+        let mut endpoints = [[0; 3]; 2];
+
+        for channel in 0..3 {
+            let values = texels.iter().map(|texel| texel[channel]);
+            endpoints[0][channel] = quantize(values.clone().min().unwrap_or(0));
+            endpoints[1][channel] = quantize(values.max().unwrap_or(0));
+        }
+
+        let palette = palette(&endpoints);
+        let mut indices = [0_u32; 16];
+
+        for (index, texel) in indices.iter_mut().zip(texels) {
+            let error = |color: &[u32; 3]| -> u64 {
+                (0..3)
+                    .map(|channel| {
+                        let difference = i64::from(color[channel]) - i64::from(texel[channel]);
+                        (difference * difference) as u64
+                    })
+                    .sum()
+            };
+
+            *index = (0..16)
+                .min_by_key(|&candidate| error(&palette[candidate]))
+                .unwrap_or(0) as u32;
+        }
+
+        // the first index is stored without its highest bit, which must be zero,
+        // so the endpoints are swapped if necessary, which reverses the palette
+        if indices[0] >= 8 {
+            endpoints.swap(0, 1);
+
+            for index in &mut indices {
+                *index = 15 - *index;
+            }
+        }
+
+        let mut bits = BitWriter::default();
+        bits.write(0b00011, 5); // the mode with one region and 10-bit endpoints
+
+        for endpoint in &endpoints {
+            for &component in endpoint {
+                bits.write(component, 10);
+            }
+        }
+
+        bits.write(indices[0], 3);
+        for &index in &indices[1..] {
+            bits.write(index, 4);
+        }
+
+        bits.value.to_le_bytes()
+    }
+
+    /// The decoded half float bits of all 16 indices, as computed by the decoder.
+    fn palette(endpoints: &[[u32; 3]; 2]) -> [[u32; 3]; 16] {
+        let mut palette = [[0; 3]; 16];
+
+        for (color, weight) in palette.iter_mut().zip(WEIGHTS) {
+            for channel in 0..3 {
+                let first = unquantize(endpoints[0][channel]);
+                let second = unquantize(endpoints[1][channel]);
+                let interpolated = ((64 - weight) * first + weight * second + 32) >> 6;
+                color[channel] = finish_unquantize(interpolated);
+            }
+        }
+
+        palette
+    }
+
+    /// The 10-bit endpoint that decodes to the closest half float.
+    fn quantize(half: u32) -> u32 {
+        let estimate = (half * 64 / 31) >> 6;
+        let candidates = estimate.saturating_sub(1)..=(estimate + 1).min(0x3FF);
+
+        candidates
+            .min_by_key(|&candidate| {
+                (i64::from(finish_unquantize(unquantize(candidate))) - i64::from(half)).abs()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Expand a 10-bit endpoint to 16 bits.
+    fn unquantize(quantized: u32) -> u32 {
+        match quantized {
+            0 => 0,
+            0x3FF => 0xFFFF,
+            _ => ((quantized << 16) + 0x8000) >> 10,
+        }
+    }
+
+    /// Scale an expanded value to the bits of a finite half float.
+    fn finish_unquantize(value: u32) -> u32 {
+        (value * 31) >> 6
+    }
+
+    /// Writes values into a block, starting at the lowest bit.
+    #[derive(Default)]
+    struct BitWriter {
+        value: u128,
+        position: u32,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u32, bit_count: u32) {
+            debug_assert!(value < (1 << bit_count));
+            self.value |= u128::from(value) << self.position;
+            self.position += bit_count;
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn endpoints_round_trip() {
+            assert_eq!(finish_unquantize(unquantize(0x3FF)), MAX_HALF);
+            assert_eq!(quantize(0), 0);
+            assert_eq!(quantize(MAX_HALF), 0x3FF);
+
+            for value in [0.001_f32, 0.18, 1.0, 3.5, 100.0, 60000.0] {
+                let half = half_bits(value);
+                let decoded = f16::from_bits(finish_unquantize(unquantize(quantize(half))) as u16);
+                assert!((decoded.to_f32() - value).abs() / value < 0.02, "{}", value);
+            }
+        }
+
+        #[test]
+        fn uniform_block() {
+            let texels = [[half_bits(1.0), half_bits(0.5), 0]; 16];
+            let block = encode_block(&texels);
+
+            // mode 11, followed by the red endpoint
+            assert_eq!(block[0] & 0b11111, 0b00011);
+            let red = (u16::from_le_bytes([block[0], block[1]]) >> 5) & 0x3FF;
+            assert_eq!(u32::from(red), quantize(half_bits(1.0)));
+
+            // all indices select the first endpoint
+            assert_eq!(u128::from_le_bytes(block) >> 65, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn levels(size: Vec2<usize>) -> Vec<TextureLevel> {
+        let mut levels = Vec::new();
+
+        for index in 0.. {
+            let level_size = Vec2(
+                (size.width() >> index).max(1),
+                (size.height() >> index).max(1),
+            );
+
+            levels.push(TextureLevel {
+                size: level_size,
+                pixels: vec![[0.5, 1.0, 2.0, 1.0]; level_size.area()],
+            });
+
+            if level_size == Vec2(1, 1) {
+                return levels;
+            }
+        }
+
+        unreachable!()
+    }
+
+    #[test]
+    fn ktx2_layout() {
+        let levels = levels(Vec2(6, 4));
+        assert_eq!(levels.len(), 3);
+
+        let mut bytes = Vec::new();
+        write_ktx2(&mut bytes, &levels, TextureFormat::Rgba16Float).unwrap();
+
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        assert_eq!(&bytes[1..4], b"KTX");
+        assert_eq!(word(12), 97);
+        assert_eq!(word(20), 6);
+        assert_eq!(word(24), 4);
+        assert_eq!(word(40), 3);
+
+        // the largest level is stored last
+        let base_offset = word(80) as usize;
+        let base_length = word(88) as usize;
+        assert_eq!(base_length, 6 * 4 * 8);
+        assert_eq!(base_offset + base_length, bytes.len());
+        assert_eq!(base_offset % 8, 0);
+
+        let descriptor_offset = word(48) as usize;
+        assert_eq!(descriptor_offset, 80 + 3 * 24);
+        assert_eq!(word(descriptor_offset) as usize, word(52) as usize);
+    }
+
+    #[test]
+    fn dds_layout() {
+        let levels = levels(Vec2(8, 8));
+        let mut bytes = Vec::new();
+        write_dds(&mut bytes, &levels, TextureFormat::Bc6h).unwrap();
+
+        assert_eq!(&bytes[..4], b"DDS ");
+        assert_eq!(&bytes[84..88], b"DX10");
+
+        // blocks of 8x8, 4x4, 2x2, and 1x1 pixels are 4, 1, 1, and 1 blocks
+        assert_eq!(bytes.len(), 4 + 124 + 20 + (4 + 1 + 1 + 1) * 16);
+    }
+
+    #[test]
+    fn reject_invalid_levels() {
+        let mut levels = levels(Vec2(5, 5));
+        assert!(write_dds(Vec::new(), &levels, TextureFormat::Rgba16Float).is_ok());
+
+        // rounded up instead of down
+        levels[1].size = Vec2(3, 3);
+        levels[1].pixels = vec![[0.0; 4]; 9];
+        assert!(write_dds(Vec::new(), &levels, TextureFormat::Rgba16Float).is_err());
+        assert!(write_ktx2(Vec::new(), &[], TextureFormat::Bc6h).is_err());
+    }
+}
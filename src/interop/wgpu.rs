@@ -0,0 +1,271 @@
+//! Create `wgpu` textures from exr layers.
+//! Enable with the `wgpu` feature.
+//!
+//! The red, green, blue, and alpha channels of a layer are uploaded to an `Rgba16Float`
+//! or `Rgba32Float` texture. Layers without color use the luminance channel `Y` for all colors,
+//! and layers without alpha are opaque. Several layers, or several channel groups of one layer
+//! like `diffuse.R` and `specular.R`, can be uploaded to the slices of an array texture.
+//!
+//! Rows are padded to `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT` bytes,
+//! such that the texels can also be copied through a buffer.
+
+use ::wgpu::{
+    Device, Extent3d, Origin3d, Queue, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+use half::f16;
+use std::convert::TryFrom;
+
+use super::FlatLayer;
+use crate::error::{Error, Result};
+use crate::image::FlatSamples;
+use crate::math::Vec2;
+
+/// The channels of a layer that are uploaded to a texture.
+/// The prefix selects channels like `diffuse.R`, and is empty for the unprefixed channels `R`, `G`, and `B`.
+#[derive(Debug, Clone, Copy)]
+pub struct RgbaSource<'l> {
+    /// The layer that contains the channels.
+    pub layer: &'l FlatLayer,
+
+    /// The channel name prefix, without the dot. Empty for unprefixed channels.
+    pub prefix: &'l str,
+}
+
+impl<'l> RgbaSource<'l> {
+    /// Use the unprefixed channels of the layer.
+    pub fn new(layer: &'l FlatLayer) -> Self {
+        RgbaSource { layer, prefix: "" }
+    }
+
+    /// Use the channels of the layer that start with `prefix.`.
+    pub fn prefixed(layer: &'l FlatLayer, prefix: &'l str) -> Self {
+        RgbaSource { layer, prefix }
+    }
+}
+
+/// The number of bytes of a row in the texel data, padded to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+pub fn padded_bytes_per_row(width: usize, bytes_per_texel: usize) -> usize {
+    let alignment = COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+    let unpadded = width * bytes_per_texel;
+    (unpadded + alignment - 1) / alignment * alignment
+}
+
+fn bytes_per_texel(format: TextureFormat) -> Result<usize> {
+    match format {
+        TextureFormat::Rgba16Float => Ok(8),
+        TextureFormat::Rgba32Float => Ok(16),
+        _ => Err(Error::unsupported(
+            "texture formats other than Rgba16Float and Rgba32Float",
+        )),
+    }
+}
+
+/// Convert the channels to texel data in the specified format, with padded rows.
+/// Returns an error if the color channels are missing or subsampled,
+/// or if the format is neither `Rgba16Float` nor `Rgba32Float`.
+pub fn rgba_texels(source: RgbaSource<'_>, format: TextureFormat) -> Result<Vec<u8>> {
+    let texel_bytes = bytes_per_texel(format)?;
+    let sample_bytes = texel_bytes / 4;
+    let layer = source.layer;
+
+    let find = |name: &str| -> Result<Option<&FlatSamples>> {
+        let full_name = if source.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", source.prefix, name)
+        };
+
+        let channel = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.eq(full_name.as_str()));
+
+        match channel {
+            Some(channel) if channel.sampling != Vec2(1, 1) => {
+                Err(Error::unsupported("uploading subsampled channels"))
+            }
+            channel => Ok(channel.map(|channel| &channel.sample_data)),
+        }
+    };
+
+    let colors = match (find("R")?, find("G")?, find("B")?) {
+        (Some(red), Some(green), Some(blue)) => [red, green, blue],
+        _ => match find("Y")? {
+            Some(luminance) => [luminance, luminance, luminance],
+            None => {
+                return Err(Error::invalid(
+                    "layer has neither rgb channels nor a luminance channel",
+                ))
+            }
+        },
+    };
+
+    let alpha = find("A")?;
+
+    let size = layer.size;
+    let row_bytes = padded_bytes_per_row(size.width(), texel_bytes);
+    let mut bytes = vec![0_u8; row_bytes * size.height()];
+
+    for y in 0..size.height() {
+        for x in 0..size.width() {
+            let index = Vec2(x, y).flat_index_for_size(size);
+            let alpha = alpha.map_or(1.0, |alpha| alpha.value_by_flat_index(index).to_f32());
+
+            let texel = [
+                colors[0].value_by_flat_index(index).to_f32(),
+                colors[1].value_by_flat_index(index).to_f32(),
+                colors[2].value_by_flat_index(index).to_f32(),
+                alpha,
+            ];
+
+            for (channel, value) in texel.iter().enumerate() {
+                let start = y * row_bytes + x * texel_bytes + channel * sample_bytes;
+                let target = &mut bytes[start..start + sample_bytes];
+
+                if sample_bytes == 2 {
+                    target.copy_from_slice(&f16::from_f32(*value).to_le_bytes());
+                } else {
+                    target.copy_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Create a 2D texture with the colors of the layer, and upload the texels.
+/// The texture can be sampled and be copied to.
+pub fn create_texture(
+    device: &Device,
+    queue: &Queue,
+    source: RgbaSource<'_>,
+    format: TextureFormat,
+) -> Result<Texture> {
+    create_array_texture(device, queue, &[source], format)
+}
+
+/// Create a 2D array texture with one slice per source, and upload the texels.
+/// All sources must have the same size. Useful to bind all AOVs of a render at once.
+pub fn create_array_texture(
+    device: &Device,
+    queue: &Queue,
+    sources: &[RgbaSource<'_>],
+    format: TextureFormat,
+) -> Result<Texture> {
+    let size = sources
+        .first()
+        .ok_or_else(|| Error::invalid("at least one layer is required"))?
+        .layer
+        .size;
+
+    if sources.iter().any(|source| source.layer.size != size) {
+        return Err(Error::invalid("all layers must have the same size"));
+    }
+
+    let mut texels = Vec::new();
+    for &source in sources {
+        texels.extend(rgba_texels(source, format)?);
+    }
+
+    let extent = Extent3d {
+        width: u32::try_from(size.width())?,
+        height: u32::try_from(size.height())?,
+        depth_or_array_layers: u32::try_from(sources.len())?,
+    };
+
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("exr layer"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    let bytes_per_row = padded_bytes_per_row(size.width(), bytes_per_texel(format)?);
+
+    queue.write_texture(
+        TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        &texels,
+        TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(u32::try_from(bytes_per_row)?),
+            rows_per_image: Some(extent.height),
+        },
+        extent,
+    );
+
+    Ok(texture)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::{AnyChannel, AnyChannels, Layer};
+    use crate::prelude::{Encoding, LayerAttributes};
+    use smallvec::smallvec;
+
+    fn layer() -> FlatLayer {
+        let channel =
+            |name: &str, value: f32| AnyChannel::new(name, FlatSamples::F32(vec![value; 3 * 2]));
+
+        Layer::new(
+            Vec2(3, 2),
+            LayerAttributes::default(),
+            Encoding::default(),
+            AnyChannels::sort(smallvec![
+                channel("Y", 0.5),
+                channel("diffuse.R", 1.0),
+                channel("diffuse.G", 2.0),
+                channel("diffuse.B", 3.0),
+            ]),
+        )
+    }
+
+    #[test]
+    fn row_padding() {
+        assert_eq!(padded_bytes_per_row(3, 16), 256);
+        assert_eq!(padded_bytes_per_row(32, 8), 256);
+        assert_eq!(padded_bytes_per_row(33, 8), 512);
+    }
+
+    #[test]
+    fn texels() {
+        let layer = layer();
+
+        let gray = rgba_texels(RgbaSource::new(&layer), TextureFormat::Rgba32Float).unwrap();
+        assert_eq!(gray.len(), 2 * 256);
+
+        let second_row = &gray[256 + 16..256 + 32];
+        let values: Vec<f32> = second_row
+            .chunks(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        assert_eq!(values, vec![0.5, 0.5, 0.5, 1.0]);
+
+        let diffuse = rgba_texels(
+            RgbaSource::prefixed(&layer, "diffuse"),
+            TextureFormat::Rgba16Float,
+        )
+        .unwrap();
+        assert_eq!(&diffuse[2..4], &f16::from_f32(2.0).to_le_bytes());
+
+        assert!(rgba_texels(
+            RgbaSource::prefixed(&layer, "specular"),
+            TextureFormat::Rgba16Float
+        )
+        .is_err());
+        assert!(rgba_texels(RgbaSource::new(&layer), TextureFormat::Rgba8Unorm).is_err());
+    }
+}
@@ -32,6 +32,7 @@
 
 pub mod io; // public to allow for custom attribute byte parsing
 
+pub mod color;
 pub mod compression;
 pub mod image;
 pub mod math;
@@ -45,6 +46,17 @@ pub mod error;
 #[cfg(feature = "gen")]
 pub mod gen;
 
+pub mod interop;
+
+/// Transfer functions, and tonemapping of images to PNG, TIFF, or Radiance HDR files.
+/// Writing files requires the `image` feature.
+pub mod export;
+
+/// Read Radiance HDR, PFM, and floating point TIFF files, and convert them to EXR files.
+/// Enable with `image` feature.
+#[cfg(feature = "image")]
+pub mod import;
+
 /// EXR image viewer with 2D/3D visualization.
 /// Enable with `view` feature.
 #[cfg(feature = "view")]
@@ -73,14 +85,16 @@ pub mod prelude {
     pub use traits::*;
 
     pub use crate::image::read::{
-        levels::LevelInfo,
-        read_all_data_from_file, read_all_flat_layers_from_file,
-        read_all_rgba_layers_from_file, read_first_any_layer_from_file,
-        read_first_flat_layer_from_file, read_first_rgba_layer_from_file,
+        levels::LevelInfo, read_all_data_from_file, read_all_flat_layers_from_bytes,
+        read_all_flat_layers_from_file, read_all_rgba_layers_from_file,
+        read_first_any_layer_from_file, read_first_flat_layer_from_file,
+        read_first_rgba_layer_from_bytes, read_first_rgba_layer_from_file,
     };
     pub use crate::image::write::{write_rgb_file, write_rgba_file};
 
     // image data structures
+    pub use crate::block::cancel::CancellationToken;
+    pub use crate::block::limits::ReadLimits;
     pub use crate::block::samples::Sample;
     pub use crate::image::*;
     pub use crate::meta::attribute::{
@@ -13,6 +13,7 @@ use std::ops::{Add, Div, Mul, Sub};
 /// Supports only few mathematical operations
 /// as this is used mainly as data struct.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec2<T>(pub T, pub T);
 
 impl<T> Vec2<T> {
@@ -202,6 +203,7 @@ pub(crate) fn ceil_log_2(mut number: u32) -> u32 {
 
 /// Round up or down in specific calculations.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RoundingMode {
     /// Round down.
     Down,
@@ -6,6 +6,7 @@ use smallvec::SmallVec;
 /// Contains one of all possible attributes.
 /// Includes a variant for custom attributes.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AttributeValue {
     /// Channel meta data.
     ChannelList(ChannelList),
@@ -119,6 +120,7 @@ pub struct Text {
 /// Satisfies the [SMPTE standard 12M-1999](https://en.wikipedia.org/wiki/SMPTE_timecode).
 /// For more in-depth information, see [philrees.co.uk/timecode](http://www.philrees.co.uk/articles/timecode.htm).
 #[derive(Copy, Debug, Clone, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimeCode {
     /// Hours 0 - 23 are valid.
     pub hours: u8,
@@ -152,6 +154,7 @@ pub struct TimeCode {
 
 /// layer type, specifies block type and deepness.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlockType {
     /// Corresponds to the string value `scanlineimage`.
     ScanLine,
@@ -203,6 +206,7 @@ pub type Matrix3x3 = [f32; 3 * 3];
 /// Valid from minimum coordinate (including) `-1,073,741,822`
 /// to maximum coordinate (including) `1,073,741,822`, the value of (`i32::MAX/2 -1`).
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Default, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntegerBounds {
     /// The top left corner of this rectangle.
     /// The `Box2I32` includes this pixel if the size is not zero.
@@ -216,6 +220,7 @@ pub struct IntegerBounds {
 
 /// A rectangular section anywhere in 2D float space.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FloatRect {
     /// The top left corner location of the rectangle (inclusive)
     pub min: Vec2<f32>,
@@ -226,12 +231,13 @@ pub struct FloatRect {
 
 /// A List of channels. Channels must be sorted alphabetically.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelList {
     /// The channels in this list.
     pub list: SmallVec<[ChannelDescription; 5]>,
 
-    /// The number of bytes that one pixel in this image needs.
-    // FIXME this needs to account for subsampling anywhere?
+    /// The number of bytes that one pixel in this image needs, ignoring subsampling.
+    /// See `byte_size_of_section` for the size of subsampled pixels.
     pub bytes_per_pixel: usize, // FIXME only makes sense for flat images!
 
     /// The sample type of all channels, if all channels have the same type.
@@ -242,6 +248,7 @@ pub struct ChannelList {
 /// Does not contain the actual pixel data,
 /// but instead merely describes it.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelDescription {
     /// One of "R", "G", or "B" most of the time.
     pub name: Text,
@@ -266,6 +273,7 @@ pub struct ChannelDescription {
 
 /// The type of samples in this channel.
 #[derive(Clone, Debug, Eq, PartialEq, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SampleType {
     /// This channel contains 32-bit unsigned int values.
     U32,
@@ -282,6 +290,7 @@ pub enum SampleType {
 /// If a file doesn't have a chromaticities attribute, display software
 /// should assume that the file's primaries and the white point match `Rec. ITU-R BT.709-3`.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chromaticities {
     /// "Red" location on the CIE XY chromaticity diagram.
     pub red: Vec2<f32>,
@@ -299,6 +308,7 @@ pub struct Chromaticities {
 /// If this attribute is present, it describes
 /// how this texture should be projected onto an environment.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EnvironmentMap {
     /// This image is an environment map projected like a world map.
     LatitudeLongitude,
@@ -309,6 +319,7 @@ pub enum EnvironmentMap {
 
 /// Uniquely identifies a motion picture film frame.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyCode {
     /// Identifies a film manufacturer.
     pub film_manufacturer_code: i32,
@@ -334,6 +345,7 @@ pub struct KeyCode {
 
 /// In what order the `Block`s of pixel data appear in a file.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LineOrder {
     /// The blocks in the file are ordered in descending rows from left to right.
     /// When compressing in parallel, this option requires potentially large amounts of memory.
@@ -353,6 +365,7 @@ pub enum LineOrder {
 /// A small `rgba` image of `i8` values that approximates the real exr image.
 // TODO is this linear?
 #[derive(Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Preview {
     /// The dimensions of the preview image.
     pub size: Vec2<usize>,
@@ -367,6 +380,7 @@ pub struct Preview {
 /// Specifies the size of each tile in the image
 /// and whether this image contains multiple resolution levels.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileDescription {
     /// The size of each tile.
     /// Stays the same number of pixels across all levels.
@@ -381,6 +395,7 @@ pub struct TileDescription {
 
 /// Whether to also store increasingly smaller versions of the original image.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LevelMode {
     /// Only a single level.
     Singular,
@@ -717,6 +732,30 @@ impl ::std::fmt::Display for Text {
     }
 }
 
+/// Texts are serialized as strings.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Text {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Returns an error if the string contains unsupported chars.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Text {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let string = <String as serde::Deserialize>::deserialize(deserializer)?;
+
+        Text::new_or_none(&string)
+            .ok_or_else(|| serde::de::Error::custom("text contains unsupported characters"))
+    }
+}
+
 impl ChannelList {
     /// Does not validate channel order.
     pub fn new(channels: SmallVec<[ChannelDescription; 5]>) -> Self {
@@ -747,6 +786,18 @@ impl ChannelList {
         }
     }
 
+    /// The number of bytes of the uncompressed samples of all channels in a section of the layer,
+    /// respecting subsampling.
+    pub fn byte_size_of_section(&self, section: IntegerBounds) -> usize {
+        self.list
+            .iter()
+            .map(|channel| {
+                channel.subsampled_section_resolution(section).area()
+                    * channel.sample_type.bytes_per_sample()
+            })
+            .sum()
+    }
+
     /// Iterate over the channels, and adds to each channel the byte offset of the channels sample type.
     /// Assumes the internal channel list is properly sorted.
     pub fn channels_with_byte_offset(&self) -> impl Iterator<Item = (usize, &ChannelDescription)> {
@@ -995,6 +1046,28 @@ impl IntegerBounds {
             && subset.end().x() <= self.end().x()
             && subset.end().y() <= self.end().y()
     }
+
+    /// The rectangle of all positions that are inside both rectangles.
+    /// Has a size of zero if the rectangles do not overlap.
+    pub fn intersection(self, other: Self) -> Self {
+        let position = self.position.max(other.position);
+        let end = self.end().min(other.end());
+        let size = end - position;
+
+        IntegerBounds::new(
+            position,
+            Vec2(size.x().max(0) as usize, size.y().max(0) as usize),
+        )
+    }
+
+    /// The smallest rectangle that contains both rectangles.
+    pub fn union(self, other: Self) -> Self {
+        let position = self.position.min(other.position);
+        let end = self.end().max(other.end());
+        let size = end - position;
+
+        IntegerBounds::new(position, Vec2(size.x() as usize, size.y() as usize))
+    }
 }
 
 impl FloatRect {
@@ -1111,6 +1184,23 @@ impl ChannelDescription {
         dimensions / self.sampling
     }
 
+    /// The number of samples of this channel in a section of the layer, respecting subsampling.
+    /// Only pixels with coordinates divisible by the sampling rate contain a sample.
+    pub fn subsampled_section_resolution(&self, section: IntegerBounds) -> Vec2<usize> {
+        // the number of multiples of the sampling rate from the start to the end of the section
+        let count = |start: i32, size: usize, sampling: usize| {
+            let sampling = sampling.max(1) as i64;
+            let first_sample = |position: i64| -(-position).div_euclid(sampling);
+            let start = i64::from(start);
+            (first_sample(start + size as i64) - first_sample(start)) as usize
+        };
+
+        Vec2(
+            count(section.position.x(), section.size.width(), self.sampling.x()),
+            count(section.position.y(), section.size.height(), self.sampling.y()),
+        )
+    }
+
     /// Number of bytes this would consume in an exr file.
     pub fn byte_size(&self) -> usize {
         self.name.null_terminated_byte_size()
@@ -1197,13 +1287,6 @@ impl ChannelDescription {
             ));
         }
 
-        if self.sampling != Vec2(1, 1) {
-            // TODO this must only be implemented in the crate::image module and child modules,
-            //      should not be too difficult
-
-            return Err(Error::unsupported("channel subsampling not supported yet"));
-        }
-
         Ok(())
     }
 }
@@ -1282,11 +1365,71 @@ fn u8_from_decimal32(coded: u32) -> u8 {
     ((coded & 0x0f) + 10 * ((coded >> 4) & 0x0f)) as u8
 }
 
+impl ::std::fmt::Display for TimeCode {
+    /// Formats the time code as `hh:mm:ss:ff`, using `;` before the frame for drop frame time codes.
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let frame_separator = if self.drop_frame { ';' } else { ':' };
+
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours, self.minutes, self.seconds, frame_separator, self.frame
+        )
+    }
+}
+
 // https://github.com/AcademySoftwareFoundation/openexr/blob/master/src/lib/OpenEXR/ImfTimeCode.cpp
 impl TimeCode {
     /// Number of bytes this would consume in an exr file.
     pub const BYTE_SIZE: usize = 2 * u32::BYTE_SIZE;
 
+    /// Create a time code from hours, minutes, seconds, and frame, with all flags and user data cleared.
+    /// Returns an error if any of the values is out of range.
+    pub fn new(hours: u8, minutes: u8, seconds: u8, frame: u8) -> Result<Self> {
+        let time_code = TimeCode {
+            hours,
+            minutes,
+            seconds,
+            frame,
+            ..TimeCode::default()
+        };
+
+        time_code.validate(true)?;
+        Ok(time_code)
+    }
+
+    /// Create a time code from the number of frames since midnight, at a whole-number frame rate.
+    /// Does not apply drop-frame counting. Returns an error if the frame rate is zero
+    /// or larger than 30, or if the frame number exceeds 24 hours.
+    pub fn from_frame_number(frame_number: u32, frames_per_second: u32) -> Result<Self> {
+        if frames_per_second == 0 || frames_per_second > 30 {
+            return Err(Error::invalid("time code frame rate"));
+        }
+
+        let total_seconds = frame_number / frames_per_second;
+        let hours = total_seconds / 3600;
+
+        if hours > 23 {
+            return Err(Error::invalid("time code frame number exceeds 24 hours"));
+        }
+
+        Self::new(
+            hours as u8,
+            ((total_seconds / 60) % 60) as u8,
+            (total_seconds % 60) as u8,
+            (frame_number % frames_per_second) as u8,
+        )
+    }
+
+    /// The number of frames since midnight, at a whole-number frame rate.
+    /// Does not apply drop-frame counting.
+    pub fn to_frame_number(&self, frames_per_second: u32) -> u32 {
+        let total_seconds =
+            self.hours as u32 * 3600 + self.minutes as u32 * 60 + self.seconds as u32;
+
+        total_seconds * frames_per_second + self.frame as u32
+    }
+
     /// Returns an error if this time code is considered invalid.
     pub fn validate(&self, strict: bool) -> UnitResult {
         if strict {
@@ -1452,7 +1595,144 @@ impl TimeCode {
     }
 }
 
+impl Default for Chromaticities {
+    /// The primaries and white point that should be assumed
+    /// if a file does not contain a chromaticities attribute.
+    fn default() -> Self {
+        Self::rec709()
+    }
+}
+
 impl Chromaticities {
+    /// The primaries and white point of `Rec. ITU-R BT.709-3`, with a D65 white point.
+    /// This is the default color space of exr files.
+    pub fn rec709() -> Self {
+        Chromaticities {
+            red: Vec2(0.6400, 0.3300),
+            green: Vec2(0.3000, 0.6000),
+            blue: Vec2(0.1500, 0.0600),
+            white: Vec2(0.3127, 0.3290),
+        }
+    }
+
+    /// The primaries and white point of sRGB, which are equal to `Rec. 709`.
+    pub fn srgb() -> Self {
+        Self::rec709()
+    }
+
+    /// The primaries and white point of `ACES 2065-1` (AP0), as used for interchange files.
+    pub fn aces_ap0() -> Self {
+        Chromaticities {
+            red: Vec2(0.7347, 0.2653),
+            green: Vec2(0.0000, 1.0000),
+            blue: Vec2(0.0001, -0.0770),
+            white: Vec2(0.32168, 0.33767),
+        }
+    }
+
+    /// The primaries and white point of `ACEScg` (AP1), the ACES working space for rendering.
+    pub fn aces_ap1() -> Self {
+        Chromaticities {
+            red: Vec2(0.713, 0.293),
+            green: Vec2(0.165, 0.830),
+            blue: Vec2(0.128, 0.044),
+            white: Vec2(0.32168, 0.33767),
+        }
+    }
+
+    /// Compute the matrix that converts linear rgb values in this color space to CIE XYZ.
+    /// The matrix is row-major and multiplies column vectors, see `Chromaticities::transform_rgb`.
+    /// The `Y` component of the white point is normalized to `1`.
+    /// Returns an error if the primaries are degenerate, for example if two of them are equal.
+    // see `RGBtoXYZ` in https://github.com/AcademySoftwareFoundation/openexr/blob/main/src/lib/OpenEXR/ImfChromaticities.cpp
+    pub fn rgb_to_xyz(&self) -> Result<Matrix3x3> {
+        Ok(to_f32_matrix(self.rgb_to_xyz_f64()?))
+    }
+
+    /// Compute the matrix that converts CIE XYZ values to linear rgb values in this color space.
+    /// The matrix is row-major and multiplies column vectors, see `Chromaticities::transform_rgb`.
+    /// Returns an error if the primaries are degenerate, for example if two of them are equal.
+    pub fn xyz_to_rgb(&self) -> Result<Matrix3x3> {
+        let inverse = invert_matrix(self.rgb_to_xyz_f64()?)
+            .ok_or(Error::invalid("chromaticities cannot be inverted"))?;
+
+        Ok(to_f32_matrix(inverse))
+    }
+
+    /// Compute the matrix that converts linear rgb values in this color space
+    /// to linear rgb values in the `target` color space.
+    /// If the white points differ, a Bradford chromatic adaptation is included.
+    /// Returns an error if either of the primaries are degenerate.
+    pub fn conversion_to(&self, target: &Chromaticities) -> Result<Matrix3x3> {
+        let source_to_xyz = self.rgb_to_xyz_f64()?;
+        let xyz_to_target = invert_matrix(target.rgb_to_xyz_f64()?)
+            .ok_or(Error::invalid("chromaticities cannot be inverted"))?;
+
+        let adaptation = bradford_adaptation(self.white, target.white)?;
+        let matrix = multiply_matrices(xyz_to_target, multiply_matrices(adaptation, source_to_xyz));
+        Ok(to_f32_matrix(matrix))
+    }
+
+    /// Multiply a linear rgb value with one of the matrices computed by this type.
+    #[inline]
+    pub fn transform_rgb(matrix: &Matrix3x3, (r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
+        (
+            matrix[0] * r + matrix[1] * g + matrix[2] * b,
+            matrix[3] * r + matrix[4] * g + matrix[5] * b,
+            matrix[6] * r + matrix[7] * g + matrix[8] * b,
+        )
+    }
+
+    /// Convert the linear rgb samples of a decoded image from this color space to the `target` color space, in place.
+    /// All three slices must have the same length.
+    pub fn convert_rgb_samples(
+        &self,
+        target: &Chromaticities,
+        red: &mut [f32],
+        green: &mut [f32],
+        blue: &mut [f32],
+    ) -> UnitResult {
+        if red.len() != green.len() || red.len() != blue.len() {
+            return Err(Error::invalid("rgb sample count mismatch"));
+        }
+
+        let matrix = self.conversion_to(target)?;
+        for ((r, g), b) in red.iter_mut().zip(green.iter_mut()).zip(blue.iter_mut()) {
+            let (new_r, new_g, new_b) = Self::transform_rgb(&matrix, (*r, *g, *b));
+            *r = new_r;
+            *g = new_g;
+            *b = new_b;
+        }
+
+        Ok(())
+    }
+
+    fn rgb_to_xyz_f64(&self) -> Result<[f64; 9]> {
+        let primaries = [self.red, self.green, self.blue];
+
+        // the columns of this matrix are the xyz coordinates of the unscaled primaries
+        let mut matrix = [0.0_f64; 9];
+        for (column, primary) in primaries.iter().enumerate() {
+            let [x, y, z] = xy_to_xyz(*primary)?;
+            matrix[column] = x;
+            matrix[3 + column] = y;
+            matrix[6 + column] = z;
+        }
+
+        // scale the primaries such that rgb (1,1,1) results in the white point
+        let white = xy_to_xyz(self.white)?;
+        let inverse = invert_matrix(matrix).ok_or(Error::invalid("degenerate chromaticities"))?;
+        let scale = multiply_matrix_vector(inverse, white);
+
+        for row in 0..3 {
+            for column in 0..3 {
+                matrix[row * 3 + column] *= scale[column];
+            }
+        }
+
+        Ok(matrix)
+    }
+
     /// Number of bytes this would consume in an exr file.
     pub fn byte_size() -> usize {
         8 * f32::BYTE_SIZE
@@ -1485,6 +1765,101 @@ impl Chromaticities {
     }
 }
 
+/// Convert a CIE xy chromaticity coordinate to XYZ, with `Y` normalized to `1`.
+fn xy_to_xyz(xy: Vec2<f32>) -> Result<[f64; 3]> {
+    let (x, y) = (xy.x() as f64, xy.y() as f64);
+
+    if y == 0.0 {
+        return Err(Error::invalid("chromaticity y coordinate is zero"));
+    }
+
+    Ok([x / y, 1.0, (1.0 - x - y) / y])
+}
+
+/// Compute the Bradford chromatic adaptation matrix from one white point to another.
+fn bradford_adaptation(source_white: Vec2<f32>, target_white: Vec2<f32>) -> Result<[f64; 9]> {
+    const BRADFORD: [f64; 9] = [
+        0.8951, 0.2664, -0.1614, -0.7502, 1.7135, 0.0367, 0.0389, -0.0685, 1.0296,
+    ];
+
+    if source_white == target_white {
+        return Ok([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    }
+
+    let source_cone = multiply_matrix_vector(BRADFORD, xy_to_xyz(source_white)?);
+    let target_cone = multiply_matrix_vector(BRADFORD, xy_to_xyz(target_white)?);
+
+    let scale = [
+        target_cone[0] / source_cone[0],
+        0.0,
+        0.0,
+        0.0,
+        target_cone[1] / source_cone[1],
+        0.0,
+        0.0,
+        0.0,
+        target_cone[2] / source_cone[2],
+    ];
+
+    let inverse_bradford = invert_matrix(BRADFORD).expect("bradford matrix inversion bug");
+    Ok(multiply_matrices(inverse_bradford, multiply_matrices(scale, BRADFORD)))
+}
+
+fn multiply_matrices(a: [f64; 9], b: [f64; 9]) -> [f64; 9] {
+    let mut result = [0.0; 9];
+
+    for row in 0..3 {
+        for column in 0..3 {
+            result[row * 3 + column] = (0..3)
+                .map(|index| a[row * 3 + index] * b[index * 3 + column])
+                .sum();
+        }
+    }
+
+    result
+}
+
+fn multiply_matrix_vector(matrix: [f64; 9], vector: [f64; 3]) -> [f64; 3] {
+    [
+        matrix[0] * vector[0] + matrix[1] * vector[1] + matrix[2] * vector[2],
+        matrix[3] * vector[0] + matrix[4] * vector[1] + matrix[5] * vector[2],
+        matrix[6] * vector[0] + matrix[7] * vector[1] + matrix[8] * vector[2],
+    ]
+}
+
+/// Returns `None` if the matrix is singular.
+fn invert_matrix(m: [f64; 9]) -> Option<[f64; 9]> {
+    let determinant = m[0] * (m[4] * m[8] - m[5] * m[7]) - m[1] * (m[3] * m[8] - m[5] * m[6])
+        + m[2] * (m[3] * m[7] - m[4] * m[6]);
+
+    if determinant.abs() < 1.0e-12 {
+        return None;
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+
+    Some([
+        (m[4] * m[8] - m[5] * m[7]) * inverse_determinant,
+        (m[2] * m[7] - m[1] * m[8]) * inverse_determinant,
+        (m[1] * m[5] - m[2] * m[4]) * inverse_determinant,
+        (m[5] * m[6] - m[3] * m[8]) * inverse_determinant,
+        (m[0] * m[8] - m[2] * m[6]) * inverse_determinant,
+        (m[2] * m[3] - m[0] * m[5]) * inverse_determinant,
+        (m[3] * m[7] - m[4] * m[6]) * inverse_determinant,
+        (m[1] * m[6] - m[0] * m[7]) * inverse_determinant,
+        (m[0] * m[4] - m[1] * m[3]) * inverse_determinant,
+    ])
+}
+
+fn to_f32_matrix(matrix: [f64; 9]) -> Matrix3x3 {
+    let mut result = [0.0_f32; 9];
+    for (target, &value) in result.iter_mut().zip(matrix.iter()) {
+        *target = value as f32;
+    }
+
+    result
+}
+
 impl Compression {
     /// Number of bytes this would consume in an exr file.
     pub fn byte_size() -> usize {
@@ -1565,7 +1940,34 @@ impl EnvironmentMap {
 impl KeyCode {
     /// Number of bytes this would consume in an exr file.
     pub fn byte_size() -> usize {
-        6 * i32::BYTE_SIZE
+        7 * i32::BYTE_SIZE
+    }
+
+    /// Returns an error if this key code is considered invalid.
+    /// The valid ranges are defined by the SMPTE 254 key number standard.
+    // https://github.com/AcademySoftwareFoundation/openexr/blob/main/src/lib/OpenEXR/ImfKeyCode.cpp
+    pub fn validate(&self, strict: bool) -> UnitResult {
+        if strict {
+            if !(0..=99).contains(&self.film_manufacturer_code) {
+                Err(Error::invalid("key code film manufacturer code not in 0 - 99"))
+            } else if !(0..=99).contains(&self.film_type) {
+                Err(Error::invalid("key code film type not in 0 - 99"))
+            } else if !(0..=999_999).contains(&self.film_roll_prefix) {
+                Err(Error::invalid("key code film roll prefix not in 0 - 999999"))
+            } else if !(0..=9999).contains(&self.count) {
+                Err(Error::invalid("key code count not in 0 - 9999"))
+            } else if !(0..=119).contains(&self.perforation_offset) {
+                Err(Error::invalid("key code perforation offset not in 0 - 119"))
+            } else if !(1..=15).contains(&self.perforations_per_frame) {
+                Err(Error::invalid("key code perforations per frame not in 1 - 15"))
+            } else if !(20..=120).contains(&self.perforations_per_count) {
+                Err(Error::invalid("key code perforations per count not in 20 - 120"))
+            } else {
+                Ok(())
+            }
+        } else {
+            Ok(())
+        }
     }
 
     /// Without validation, write this instance to the byte stream.
@@ -1575,6 +1977,7 @@ impl KeyCode {
         self.film_roll_prefix.write_le(write)?;
         self.count.write_le(write)?;
         self.perforation_offset.write_le(write)?;
+        self.perforations_per_frame.write_le(write)?;
         self.perforations_per_count.write_le(write)?;
         Ok(())
     }
@@ -1593,6 +1996,37 @@ impl KeyCode {
     }
 }
 
+impl Default for KeyCode {
+    /// A key code at the start of a roll of 35mm film with four perforations per frame.
+    fn default() -> Self {
+        KeyCode {
+            film_manufacturer_code: 0,
+            film_type: 0,
+            film_roll_prefix: 0,
+            count: 0,
+            perforation_offset: 0,
+            perforations_per_frame: 4,
+            perforations_per_count: 64,
+        }
+    }
+}
+
+impl ::std::fmt::Display for KeyCode {
+    /// Formats the key code like the human-readable key number printed on film,
+    /// for example `KU 22 123456 7890+12`.
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        write!(
+            f,
+            "{:02} {:02} {:06} {:04}+{:02}",
+            self.film_manufacturer_code,
+            self.film_type,
+            self.film_roll_prefix,
+            self.count,
+            self.perforation_offset
+        )
+    }
+}
+
 impl LineOrder {
     /// Number of bytes this would consume in an exr file.
     pub fn byte_size() -> usize {
@@ -1798,6 +2232,24 @@ pub fn read(
     Ok((name, value))
 }
 
+/// Read the attribute without validating, like `read`, but also return the original value.
+/// The original value is an `AttributeValue::Custom` containing the unmodified type name and bytes,
+/// which can be used to write the attribute again even if it could not be parsed.
+pub fn read_with_original(
+    read: &mut PeekRead<impl Read>,
+    max_size: usize,
+) -> Result<(Text, Result<AttributeValue>, AttributeValue)> {
+    let name = Text::read_null_terminated(read, max_size)?;
+    let kind = Text::read_null_terminated(read, max_size)?;
+    let size = i32_to_usize(i32::read_le(read)?, "attribute size")?;
+
+    let mut bytes = SmallVec::<[u8; 16]>::new();
+    u8::read_into_vec_le(read, &mut bytes, size, 64, None, "attribute value size")?;
+
+    let value = AttributeValue::read(&mut PeekRead::new(bytes.as_slice()), kind.clone(), size)?;
+    Ok((name, value, AttributeValue::Custom { kind, bytes }))
+}
+
 /// Validate this attribute.
 pub fn validate(
     name: &Text,
@@ -2087,6 +2539,18 @@ impl AttributeValue {
         Ok(parse_attribute())
     }
 
+    /// Whether writing this value produces exactly the specified type name and bytes.
+    /// This is not the case if the bytes were malformed, or if they contained
+    /// additional data which was ignored when parsing the value.
+    pub fn writes_exactly(&self, kind: &TextSlice, bytes: &[u8]) -> bool {
+        if self.kind_name() != kind || self.byte_size() != bytes.len() {
+            return false;
+        }
+
+        let mut written = Vec::with_capacity(bytes.len());
+        self.write(&mut written).is_ok() && written.as_slice() == bytes
+    }
+
     /// Validate this instance.
     pub fn validate(
         &self,
@@ -2101,6 +2565,7 @@ impl AttributeValue {
             TileDescription(ref value) => value.validate()?,
             Preview(ref value) => value.validate(strict)?,
             TimeCode(ref time_code) => time_code.validate(strict)?,
+            KeyCode(ref key_code) => key_code.validate(strict)?,
 
             TextVector(ref vec) => {
                 if strict && vec.is_empty() {
@@ -2122,6 +2587,14 @@ impl AttributeValue {
         }
     }
 
+    /// Return `Ok(f64)` if this attribute is an f64.
+    pub fn to_f64(&self) -> Result<f64> {
+        match *self {
+            AttributeValue::F64(value) => Ok(value),
+            _ => Err(invalid_type()),
+        }
+    }
+
     /// Return `Ok(f32)` if this attribute is an f32.
     pub fn to_f32(&self) -> Result<f32> {
         match *self {
@@ -2161,12 +2634,43 @@ impl AttributeValue {
             _ => Err(invalid_type()),
         }
     }
+
+    /// Return `Ok(KeyCode)` if this attribute is a key code.
+    pub fn to_key_code(&self) -> Result<KeyCode> {
+        match *self {
+            AttributeValue::KeyCode(value) => Ok(value),
+            _ => Err(invalid_type()),
+        }
+    }
+}
+
+// allow inserting custom attributes without spelling out the variant,
+// for example `attributes.other.insert(Text::from("renderMemory"), 1024.0_f32.into())`
+macro_rules! impl_from_for_attribute_value {
+    ( $( $variant: ident ( $type: ty ) ),* ) => { $(
+        impl From<$type> for AttributeValue {
+            fn from(value: $type) -> Self {
+                AttributeValue::$variant(value)
+            }
+        }
+    )* };
+}
+
+impl_from_for_attribute_value! {
+    I32(i32), F32(f32), F64(f64), Text(Text), TimeCode(TimeCode), KeyCode(KeyCode),
+    Chromaticities(Chromaticities), Matrix3x3(Matrix3x3), Matrix4x4(Matrix4x4)
 }
 
 /// Contains string literals identifying the type of an attribute.
 pub mod type_names {
     macro_rules! define_attribute_type_names {
         ( $($name: ident : $value: expr),* ) => {
+
+            /// A list containing all standard attribute type names.
+            pub const ALL: &'static [&'static [u8]] = &[
+                $( $value ),*
+            ];
+
             $(
                 /// The byte-string name of this attribute type as it appears in an exr file.
                 pub const $name: &'static [u8] = $value;
@@ -2496,6 +3000,110 @@ mod test {
         }
     }
 
+    #[test]
+    fn key_code_write_read_roundtrip() {
+        let key_code = KeyCode {
+            film_manufacturer_code: 12,
+            film_type: 34,
+            film_roll_prefix: 567890,
+            count: 1234,
+            perforation_offset: 56,
+            perforations_per_frame: 3,
+            perforations_per_count: 64,
+        };
+
+        key_code.validate(true).unwrap();
+
+        let value = AttributeValue::KeyCode(key_code);
+        let mut bytes = Vec::new();
+        value.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), value.byte_size());
+
+        let decoded = KeyCode::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, key_code);
+        assert_eq!(decoded.to_string(), "12 34 567890 1234+56");
+
+        let invalid = KeyCode {
+            perforations_per_frame: 0,
+            ..key_code
+        };
+        assert!(invalid.validate(true).is_err());
+        assert!(invalid.validate(false).is_ok());
+    }
+
+    #[test]
+    fn time_code_frame_numbers() {
+        let code = TimeCode::new(1, 2, 3, 4).unwrap();
+        assert_eq!(code.to_string(), "01:02:03:04");
+        assert_eq!(code.to_frame_number(24), (3600 + 2 * 60 + 3) * 24 + 4);
+        assert_eq!(TimeCode::from_frame_number(code.to_frame_number(24), 24).unwrap(), code);
+
+        assert!(TimeCode::new(24, 0, 0, 0).is_err());
+        assert!(TimeCode::from_frame_number(24 * 3600 * 25, 25).is_err());
+    }
+
+    fn assert_matrix_eq(actual: Matrix3x3, expected: Matrix3x3, tolerance: f32) {
+        for (index, (&actual, &expected)) in actual.iter().zip(expected.iter()).enumerate() {
+            assert!(
+                (actual - expected).abs() < tolerance,
+                "matrix entry {}: {} vs {}",
+                index,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn chromaticities_rec709_to_xyz() {
+        let matrix = Chromaticities::rec709().rgb_to_xyz().unwrap();
+
+        assert_matrix_eq(
+            matrix,
+            [
+                0.4124, 0.3576, 0.1805, 0.2126, 0.7152, 0.0722, 0.0193, 0.1192, 0.9505,
+            ],
+            1.0e-3,
+        );
+    }
+
+    #[test]
+    fn chromaticities_conversion_roundtrip() {
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let aces = Chromaticities::aces_ap1();
+        let srgb = Chromaticities::srgb();
+
+        assert_matrix_eq(aces.conversion_to(&aces).unwrap(), identity, 1.0e-5);
+
+        let there = aces.conversion_to(&srgb).unwrap();
+        let back = srgb.conversion_to(&aces).unwrap();
+        let color = (0.18, 0.5, 0.9);
+        let roundtrip =
+            Chromaticities::transform_rgb(&back, Chromaticities::transform_rgb(&there, color));
+
+        assert!((roundtrip.0 - color.0).abs() < 1.0e-4);
+        assert!((roundtrip.1 - color.1).abs() < 1.0e-4);
+        assert!((roundtrip.2 - color.2).abs() < 1.0e-4);
+
+        // white stays white, because of chromatic adaptation
+        let white = Chromaticities::transform_rgb(&there, (1.0, 1.0, 1.0));
+        assert!((white.0 - 1.0).abs() < 1.0e-3);
+        assert!((white.1 - 1.0).abs() < 1.0e-3);
+        assert!((white.2 - 1.0).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn chromaticities_degenerate() {
+        let degenerate = Chromaticities {
+            red: Vec2(0.3, 0.3),
+            green: Vec2(0.3, 0.3),
+            blue: Vec2(0.15, 0.06),
+            white: Vec2(0.3127, 0.3290),
+        };
+
+        assert!(degenerate.rgb_to_xyz().is_err());
+    }
+
     // Tests for pixel_section_indices() - see DEAD_CODE_ANALYSIS.md item #7
     mod pixel_section_indices_tests {
         use super::*;
@@ -0,0 +1,435 @@
+//! Reconstruct the virtual camera of a rendered image from its attributes.
+//! Combines `worldToCamera`, `worldToNDC`, the field of view, the clip planes,
+//! and the lens attributes into view and projection matrices.
+//!
+//! All matrices use the OpenEXR convention: they are stored row by row
+//! and transform row vectors, such that `point * world_to_ndc = point * world_to_camera * projection`.
+//! Normalized device coordinates are assumed to span from `-1` to `1` across the screen window.
+
+use crate::error::*;
+use crate::math::Vec2;
+use crate::meta::attribute::{AttributeValue, Matrix4x4};
+use crate::meta::header::LayerAttributes;
+use std::ops::Range;
+
+/// The name of the custom attribute containing the distance
+/// from the pinhole of the lens to the image plane, in millimeters.
+pub const PINHOLE_FOCAL_LENGTH: &'static [u8] = b"pinholeFocalLength";
+
+/// The name of the custom attribute containing the effective focal length of the lens, in millimeters.
+pub const EFFECTIVE_FOCAL_LENGTH: &'static [u8] = b"effectiveFocalLength";
+
+/// The name of the custom attribute containing the nominal focal length of the lens, in millimeters.
+pub const NOMINAL_FOCAL_LENGTH: &'static [u8] = b"nominalFocalLength";
+
+/// The name of the custom attribute containing the size of the sensor, in millimeters.
+pub const SENSOR_DIMENSIONS: &'static [u8] = b"sensorOverallDimensions";
+
+/// The virtual camera that rendered an image, reconstructed from the layer attributes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    /// Transforms points from world space to camera space.
+    pub world_to_camera: Matrix4x4,
+
+    /// Transforms points from camera space to world space.
+    /// The inverse of `world_to_camera`.
+    pub camera_to_world: Matrix4x4,
+
+    /// Transforms points from camera space to normalized device coordinates.
+    /// Reconstructed from `worldToNDC`, or from the field of view and clip planes.
+    pub projection: Option<Matrix4x4>,
+
+    /// The horizontal and vertical field of view, in degrees.
+    /// Taken from the attributes, or reconstructed from the projection,
+    /// or computed from the focal length and sensor size.
+    pub field_of_view: Option<Vec2<f32>>,
+
+    /// The distance of the near and far clip planes.
+    pub clip_range: Option<Range<f32>>,
+
+    /// The focal length of the lens, in millimeters.
+    pub focal_length: Option<f32>,
+
+    /// The size of the sensor, in millimeters.
+    pub sensor_size: Option<Vec2<f32>>,
+
+    /// The lens aperture, in f-stops.
+    pub aperture: Option<f32>,
+
+    /// The distance from the camera to the plane in focus, in meters.
+    pub focus_distance: Option<f32>,
+}
+
+impl Camera {
+    /// Reconstruct the camera from the attributes of a layer.
+    /// Returns `None` if the layer has no `worldToCamera` attribute.
+    /// Returns an error if any of the matrices cannot be inverted.
+    pub fn from_attributes(attributes: &LayerAttributes) -> Result<Option<Self>> {
+        let world_to_camera = match attributes.world_to_camera {
+            Some(matrix) => matrix,
+            None => return Ok(None),
+        };
+
+        let camera_to_world = invert_matrix(&world_to_camera)
+            .ok_or_else(|| Error::invalid("world to camera matrix"))?;
+
+        let clip_range = match (attributes.near_clip_plane, attributes.far_clip_plane) {
+            (Some(near), Some(far)) => Some(near..far),
+            _ => None,
+        };
+
+        let focal_length = [
+            PINHOLE_FOCAL_LENGTH,
+            EFFECTIVE_FOCAL_LENGTH,
+            NOMINAL_FOCAL_LENGTH,
+        ]
+        .iter()
+        .find_map(|&name| custom_f32(attributes, name));
+
+        let sensor_size = match attributes.other.get(SENSOR_DIMENSIONS) {
+            Some(AttributeValue::FloatVec2(size)) => Some(*size),
+            _ => None,
+        };
+
+        let attribute_field_of_view = match (
+            attributes.horizontal_field_of_view,
+            attributes.vertical_field_of_view,
+        ) {
+            (Some(horizontal), Some(vertical)) => Some(Vec2(horizontal, vertical)),
+            _ => None,
+        };
+
+        let projection = attributes
+            .world_to_normalized_device
+            .map(|world_to_ndc| multiply_matrices(&camera_to_world, &world_to_ndc))
+            .or_else(|| {
+                let fov = attribute_field_of_view?;
+                let clip = clip_range.clone()?;
+                Some(perspective_projection(fov, clip))
+            });
+
+        let field_of_view = attribute_field_of_view
+            .or_else(|| projection.as_ref().and_then(field_of_view_from_projection))
+            .or_else(|| {
+                let focal_length = focal_length?;
+                let sensor = sensor_size?;
+                Some(Vec2(
+                    field_of_view_from_focal_length(focal_length, sensor.x()),
+                    field_of_view_from_focal_length(focal_length, sensor.y()),
+                ))
+            });
+
+        Ok(Some(Camera {
+            world_to_camera,
+            camera_to_world,
+            projection,
+            field_of_view,
+            clip_range,
+            focal_length,
+            sensor_size,
+            aperture: attributes.aperture,
+            focus_distance: attributes.focus,
+        }))
+    }
+
+    /// The position of the camera in world space.
+    pub fn position(&self) -> [f32; 3] {
+        let m = &self.camera_to_world;
+        [m[12], m[13], m[14]]
+    }
+
+    /// The direction the camera looks at, in world space.
+    /// Points along the positive z axis of the camera space.
+    pub fn forward(&self) -> [f32; 3] {
+        let m = &self.camera_to_world;
+        normalize([m[8], m[9], m[10]])
+    }
+
+    /// The up direction of the camera, in world space.
+    pub fn up(&self) -> [f32; 3] {
+        let m = &self.camera_to_world;
+        normalize([m[4], m[5], m[6]])
+    }
+
+    /// Transforms points from world space directly to normalized device coordinates.
+    pub fn world_to_normalized_device(&self) -> Option<Matrix4x4> {
+        self.projection
+            .map(|projection| multiply_matrices(&self.world_to_camera, &projection))
+    }
+
+    /// Transform a world space point into normalized device coordinates.
+    /// Returns `None` if there is no projection or the point lies in the plane of the camera.
+    pub fn project(&self, world_point: [f32; 3]) -> Option<[f32; 3]> {
+        let world_to_ndc = self.world_to_normalized_device()?;
+        transform_point(&world_to_ndc, world_point)
+    }
+
+    /// Transform a point in normalized device coordinates, including depth, back into world space.
+    /// Returns `None` if there is no projection or it cannot be inverted.
+    pub fn unproject(&self, ndc_point: [f32; 3]) -> Option<[f32; 3]> {
+        let ndc_to_world = invert_matrix(&self.world_to_normalized_device()?)?;
+        transform_point(&ndc_to_world, ndc_point)
+    }
+
+    /// Compute the world space position of a pixel, given its distance along the camera z axis,
+    /// as stored in a typical `Z` channel. The pixel position is relative to the screen window,
+    /// from `-1` to `1` on both axes, with y pointing up.
+    /// Returns `None` if there is no perspective projection.
+    pub fn world_position_from_depth(&self, ndc_xy: Vec2<f32>, depth: f32) -> Option<[f32; 3]> {
+        let projection = self.projection?;
+
+        // for a perspective projection, ndc = (x * p00 + z * p20) / (z * p23)
+        let (scale_x, scale_y) = (projection[0], projection[5]);
+        let (offset_x, offset_y) = (projection[8], projection[9]);
+        let perspective = projection[11];
+
+        if scale_x == 0.0 || scale_y == 0.0 || perspective.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let camera_point = [
+            (ndc_xy.x() * perspective - offset_x) * depth / scale_x,
+            (ndc_xy.y() * perspective - offset_y) * depth / scale_y,
+            depth,
+        ];
+
+        transform_point(&self.camera_to_world, camera_point)
+    }
+}
+
+/// Create a perspective projection matrix from the field of view in degrees and the clip planes.
+/// Maps the near plane to a depth of `0` and the far plane to a depth of `1`.
+pub fn perspective_projection(field_of_view: Vec2<f32>, clip_range: Range<f32>) -> Matrix4x4 {
+    let scale_x = 1.0 / (field_of_view.x().to_radians() * 0.5).tan();
+    let scale_y = 1.0 / (field_of_view.y().to_radians() * 0.5).tan();
+    let (near, far) = (clip_range.start, clip_range.end);
+    let depth_scale = far / (far - near);
+
+    #[rustfmt::skip]
+    let matrix = [
+        scale_x, 0.0, 0.0, 0.0,
+        0.0, scale_y, 0.0, 0.0,
+        0.0, 0.0, depth_scale, 1.0,
+        0.0, 0.0, -near * depth_scale, 0.0,
+    ];
+
+    matrix
+}
+
+/// Reconstruct the horizontal and vertical field of view, in degrees, from a perspective projection.
+/// Returns `None` if the projection is orthographic.
+pub fn field_of_view_from_projection(projection: &Matrix4x4) -> Option<Vec2<f32>> {
+    let is_perspective = projection[11].abs() > f32::EPSILON && projection[15].abs() < f32::EPSILON;
+    if !is_perspective || projection[0] == 0.0 || projection[5] == 0.0 {
+        return None;
+    }
+
+    let angle = |scale: f32| 2.0 * (projection[11] / scale).abs().atan().to_degrees();
+    Some(Vec2(angle(projection[0]), angle(projection[5])))
+}
+
+/// Compute the field of view in degrees from the focal length and the sensor size along one axis,
+/// both in the same unit, typically millimeters.
+pub fn field_of_view_from_focal_length(focal_length: f32, sensor_size: f32) -> f32 {
+    2.0 * (sensor_size / (2.0 * focal_length)).atan().to_degrees()
+}
+
+/// Compute the focal length from the field of view in degrees and the sensor size along one axis.
+/// The result has the same unit as the sensor size, typically millimeters.
+pub fn focal_length_from_field_of_view(field_of_view: f32, sensor_size: f32) -> f32 {
+    sensor_size / (2.0 * (field_of_view.to_radians() * 0.5).tan())
+}
+
+/// Transform a point by a matrix, including the perspective division.
+/// Returns `None` if the resulting homogeneous coordinate is zero.
+pub fn transform_point(matrix: &Matrix4x4, [x, y, z]: [f32; 3]) -> Option<[f32; 3]> {
+    let m = matrix;
+    let column = |index: usize| x * m[index] + y * m[4 + index] + z * m[8 + index] + m[12 + index];
+    let w = column(3);
+
+    if w.abs() < f32::EPSILON {
+        None
+    } else {
+        Some([column(0) / w, column(1) / w, column(2) / w])
+    }
+}
+
+/// Multiply two matrices, such that the result first applies `first` and then `second` to a row vector.
+pub fn multiply_matrices(first: &Matrix4x4, second: &Matrix4x4) -> Matrix4x4 {
+    let mut result = [0.0; 16];
+
+    for row in 0..4 {
+        for column in 0..4 {
+            result[row * 4 + column] = (0..4)
+                .map(|index| first[row * 4 + index] * second[index * 4 + column])
+                .sum();
+        }
+    }
+
+    result
+}
+
+/// Invert a matrix, returning `None` if it is singular.
+pub fn invert_matrix(matrix: &Matrix4x4) -> Option<Matrix4x4> {
+    let mut left: [f64; 16] = [0.0; 16];
+    let mut right: [f64; 16] = [0.0; 16];
+
+    for index in 0..16 {
+        left[index] = matrix[index] as f64;
+        right[index] = if index % 5 == 0 { 1.0 } else { 0.0 };
+    }
+
+    // gauss-jordan elimination with partial pivoting
+    for column in 0..4 {
+        let pivot = (column..4)
+            .max_by(|&a, &b| {
+                left[a * 4 + column]
+                    .abs()
+                    .partial_cmp(&left[b * 4 + column].abs())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .expect("matrix row bug");
+
+        if left[pivot * 4 + column].abs() < 1.0e-12 {
+            return None;
+        }
+
+        for index in 0..4 {
+            left.swap(column * 4 + index, pivot * 4 + index);
+            right.swap(column * 4 + index, pivot * 4 + index);
+        }
+
+        let divisor = left[column * 4 + column];
+        for index in 0..4 {
+            left[column * 4 + index] /= divisor;
+            right[column * 4 + index] /= divisor;
+        }
+
+        for row in (0..4).filter(|&row| row != column) {
+            let factor = left[row * 4 + column];
+
+            for index in 0..4 {
+                left[row * 4 + index] -= factor * left[column * 4 + index];
+                right[row * 4 + index] -= factor * right[column * 4 + index];
+            }
+        }
+    }
+
+    let mut result = [0.0; 16];
+    for index in 0..16 {
+        result[index] = right[index] as f32;
+    }
+
+    Some(result)
+}
+
+fn custom_f32(attributes: &LayerAttributes, name: &[u8]) -> Option<f32> {
+    attributes
+        .other
+        .get(name)
+        .and_then(|value| value.to_f32().ok())
+}
+
+fn normalize([x, y, z]: [f32; 3]) -> [f32; 3] {
+    let length = (x * x + y * y + z * z).sqrt();
+
+    if length == 0.0 {
+        [x, y, z]
+    } else {
+        [x / length, y / length, z / length]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::attribute::Text;
+
+    #[rustfmt::skip]
+    const WORLD_TO_CAMERA: Matrix4x4 = [
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        -1.0, -2.0, -3.0, 1.0,
+    ];
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1.0e-3,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn reconstruct_projection_from_world_to_ndc() {
+        let projection = perspective_projection(Vec2(90.0, 60.0), 0.1..100.0);
+
+        let attributes = LayerAttributes {
+            world_to_camera: Some(WORLD_TO_CAMERA),
+            world_to_normalized_device: Some(multiply_matrices(&WORLD_TO_CAMERA, &projection)),
+            ..LayerAttributes::default()
+        };
+
+        let camera = Camera::from_attributes(&attributes).unwrap().unwrap();
+        assert_close(&camera.projection.unwrap(), &projection);
+        assert_close(&camera.position(), &[1.0, 2.0, 3.0]);
+        assert_close(&camera.forward(), &[0.0, 0.0, 1.0]);
+
+        let fov = camera.field_of_view.unwrap();
+        assert_close(&[fov.x(), fov.y()], &[90.0, 60.0]);
+
+        // a point straight ahead of the camera lands in the center of the screen
+        let projected = camera.project([1.0, 2.0, 13.0]).unwrap();
+        assert_close(&projected[..2], &[0.0, 0.0]);
+
+        let unprojected = camera.unproject(projected).unwrap();
+        assert_close(&unprojected, &[1.0, 2.0, 13.0]);
+
+        let from_depth = camera
+            .world_position_from_depth(Vec2(1.0, 0.0), 10.0)
+            .unwrap();
+        assert_close(&from_depth, &[11.0, 2.0, 13.0]);
+    }
+
+    #[test]
+    fn field_of_view_from_lens_attributes() {
+        let mut attributes = LayerAttributes {
+            world_to_camera: Some(WORLD_TO_CAMERA),
+            ..LayerAttributes::default()
+        };
+
+        attributes
+            .other
+            .insert(Text::from("pinholeFocalLength"), AttributeValue::F32(18.0));
+        attributes.other.insert(
+            Text::from("sensorOverallDimensions"),
+            AttributeValue::FloatVec2(Vec2(36.0, 24.0)),
+        );
+
+        let camera = Camera::from_attributes(&attributes).unwrap().unwrap();
+        assert_eq!(camera.focal_length, Some(18.0));
+        assert_close(&[camera.field_of_view.unwrap().x()], &[90.0]);
+        assert_eq!(camera.projection, None);
+
+        assert_close(&[focal_length_from_field_of_view(90.0, 36.0)], &[18.0]);
+    }
+
+    #[test]
+    fn missing_and_singular_cameras() {
+        assert_eq!(
+            Camera::from_attributes(&LayerAttributes::default()).unwrap(),
+            None
+        );
+
+        let attributes = LayerAttributes {
+            world_to_camera: Some([0.0; 16]),
+            ..LayerAttributes::default()
+        };
+
+        assert!(Camera::from_attributes(&attributes).is_err());
+    }
+}
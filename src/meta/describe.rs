@@ -0,0 +1,729 @@
+//! Describe the meta data of a file as a JSON document.
+//! Intended for validation scripts and indexing tools that should not need to parse exr files.
+//!
+//! The document has the following stable schema, which is identified by `schema_version`.
+//! Fields will only be added in future versions, never removed or changed.
+//!
+//! ```json
+//! {
+//!   "schema_version": 1,
+//!   "requirements": {
+//!     "file_format_version": 2, "is_single_layer_and_tiled": false, "has_long_names": false,
+//!     "has_deep_data": false, "has_multiple_layers": false
+//!   },
+//!   "layers": [{
+//!     "index": 0,
+//!     "name": "diffuse" or null,
+//!     "block_type": "scanlineimage" | "tiledimage" | "deepscanline" | "deeptile",
+//!     "compression": "none" | "rle" | "zip1" | "zip16" | "piz" | "pxr24" | "b44" | "b44a" | "dwaa" | "dwab" | "htj2k32" | "htj2k256",
+//!     "line_order": "increasing" | "decreasing" | "unspecified",
+//!     "data_window": { "x": 0, "y": 0, "width": 1920, "height": 1080 },
+//!     "display_window": { "x": 0, "y": 0, "width": 1920, "height": 1080 },
+//!     "tiles": { "width": 64, "height": 64, "level_mode": "singular" | "mipmap" | "ripmap", "rounding_mode": "down" | "up" } or null,
+//!     "chunk_count": 68,
+//!     "header_byte_size": 412,
+//!     "channels": [{ "name": "R", "sample_type": "f16" | "f32" | "u32", "quantize_linearly": false, "sampling": [1, 1] }],
+//!     "attributes": [{ "name": "owner", "type": "string", "byte_size": 21, "value": "..." }]
+//!   }]
+//! }
+//! ```
+//!
+//! The `byte_size` of an attribute includes its name, type name, and size fields, as stored in the file.
+//! Attribute values are converted to JSON as follows:
+//! numbers and texts are plain JSON values, non-finite floats are the strings `"NaN"`, `"inf"`, and `"-inf"`,
+//! vectors are arrays, matrices are arrays of rows, boxes are objects with `x`, `y`, `width`, `height`
+//! or with `min` and `max`, previews only contain their `width` and `height`,
+//! and custom values are objects with the `type` and a lowercase `hex` string of their bytes.
+//! The attributes are sorted by name.
+//! Texts in the file are byte strings, each byte is written as the unicode character with the same number.
+//!
+//! The document is built without `serde`, such that it is available without the optional `serde` feature,
+//! and such that the schema does not change when the rust types of the meta data change.
+
+use crate::math::{RoundingMode, Vec2};
+use crate::meta::attribute::{
+    self, AttributeValue, BlockType, ChannelDescription, Compression, IntegerBounds, LevelMode,
+    LineOrder, SampleType, Text, TileDescription,
+};
+use crate::meta::header::Header;
+use crate::meta::{sequence_end, BlockDescription, MetaData};
+use std::fmt::Write;
+
+/// The version of the JSON schema produced by `MetaData::to_json`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A JSON document, which can be written as text using `JsonValue::to_string`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    /// The `null` value.
+    Null,
+
+    /// A boolean.
+    Bool(bool),
+
+    /// A number, already formatted as valid JSON.
+    Number(String),
+
+    /// A string, which will be escaped when written.
+    String(String),
+
+    /// An ordered list of values.
+    Array(Vec<JsonValue>),
+
+    /// Named values, in the order they will be written.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl MetaData {
+    /// Describe all layers and attributes of this file as a pretty-printed JSON document.
+    /// See the `exr::meta::describe` module for the schema.
+    pub fn to_json(&self) -> String {
+        self.describe().to_string()
+    }
+
+    /// Describe all layers and attributes of this file as a JSON document.
+    /// See the `exr::meta::describe` module for the schema.
+    pub fn describe(&self) -> JsonValue {
+        let requirements = &self.requirements;
+
+        JsonValue::object(vec![
+            ("schema_version", JsonValue::from(SCHEMA_VERSION)),
+            (
+                "requirements",
+                JsonValue::object(vec![
+                    (
+                        "file_format_version",
+                        JsonValue::from(requirements.file_format_version as u32),
+                    ),
+                    (
+                        "is_single_layer_and_tiled",
+                        JsonValue::Bool(requirements.is_single_layer_and_tiled),
+                    ),
+                    (
+                        "has_long_names",
+                        JsonValue::Bool(requirements.has_long_names),
+                    ),
+                    ("has_deep_data", JsonValue::Bool(requirements.has_deep_data)),
+                    (
+                        "has_multiple_layers",
+                        JsonValue::Bool(requirements.has_multiple_layers),
+                    ),
+                ]),
+            ),
+            (
+                "layers",
+                JsonValue::Array(
+                    self.headers
+                        .iter()
+                        .enumerate()
+                        .map(|(index, header)| header.describe(index))
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
+
+impl Header {
+    /// Describe this layer and all of its attributes as a JSON object.
+    /// See the `exr::meta::describe` module for the schema.
+    pub fn describe(&self, index: usize) -> JsonValue {
+        let mut attributes: Vec<(&[u8], AttributeValue)> = self.all_named_attributes().collect();
+        attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let header_byte_size = attributes
+            .iter()
+            .map(|(name, value)| attribute::byte_size(&Text::from_slice_unchecked(name), value))
+            .sum::<usize>()
+            + sequence_end::byte_size();
+
+        let block_type = match (self.blocks, self.deep) {
+            (BlockDescription::ScanLines, false) => BlockType::ScanLine,
+            (BlockDescription::ScanLines, true) => BlockType::DeepScanLine,
+            (BlockDescription::Tiles(_), false) => BlockType::Tile,
+            (BlockDescription::Tiles(_), true) => BlockType::DeepTile,
+        };
+
+        let tiles = match self.blocks {
+            BlockDescription::Tiles(tiles) => describe_tiles(tiles),
+            BlockDescription::ScanLines => JsonValue::Null,
+        };
+
+        JsonValue::object(vec![
+            ("index", JsonValue::from(index)),
+            (
+                "name",
+                self.own_attributes
+                    .layer_name
+                    .as_ref()
+                    .map_or(JsonValue::Null, JsonValue::from),
+            ),
+            (
+                "block_type",
+                JsonValue::from(&Text::from_slice_unchecked(block_type.to_text_bytes())),
+            ),
+            (
+                "compression",
+                JsonValue::from(compression_name(self.compression)),
+            ),
+            (
+                "line_order",
+                JsonValue::from(line_order_name(self.line_order)),
+            ),
+            ("data_window", describe_bounds(self.data_window())),
+            (
+                "display_window",
+                describe_bounds(self.shared_attributes.display_window),
+            ),
+            ("tiles", tiles),
+            ("chunk_count", JsonValue::from(self.chunk_count)),
+            ("header_byte_size", JsonValue::from(header_byte_size)),
+            (
+                "channels",
+                JsonValue::Array(self.channels.list.iter().map(describe_channel).collect()),
+            ),
+            (
+                "attributes",
+                JsonValue::Array(
+                    attributes
+                        .iter()
+                        .map(|(name, value)| {
+                            let name = Text::from_slice_unchecked(name);
+
+                            JsonValue::object(vec![
+                                ("name", JsonValue::from(&name)),
+                                (
+                                    "type",
+                                    JsonValue::from(&Text::from_slice_unchecked(value.kind_name())),
+                                ),
+                                (
+                                    "byte_size",
+                                    JsonValue::from(attribute::byte_size(&name, value)),
+                                ),
+                                ("value", JsonValue::from(value)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+}
+
+impl JsonValue {
+    /// Create an object from a list of names and values.
+    pub fn object(fields: Vec<(&str, JsonValue)>) -> Self {
+        JsonValue::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Write this value as JSON on a single line, without any whitespace.
+    pub fn to_compact_string(&self) -> String {
+        let mut output = String::new();
+        self.write_compact(&mut output);
+        output
+    }
+
+    fn write_compact(&self, output: &mut String) {
+        match self {
+            JsonValue::Array(values) => {
+                output.push('[');
+
+                for (index, value) in values.iter().enumerate() {
+                    if index != 0 {
+                        output.push(',');
+                    }
+
+                    value.write_compact(output);
+                }
+
+                output.push(']');
+            }
+
+            JsonValue::Object(fields) => {
+                output.push('{');
+
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index != 0 {
+                        output.push(',');
+                    }
+
+                    write_escaped(output, name);
+                    output.push(':');
+                    value.write_compact(output);
+                }
+
+                output.push('}');
+            }
+
+            scalar => scalar.write_pretty(output, 0),
+        }
+    }
+
+    /// Write this value as pretty-printed JSON, indenting nested values by two spaces.
+    fn write_pretty(&self, output: &mut String, indentation: usize) {
+        let indent = |output: &mut String, depth: usize| {
+            output.push('\n');
+            output.extend(std::iter::repeat(' ').take(depth * 2));
+        };
+
+        match self {
+            JsonValue::Null => output.push_str("null"),
+            JsonValue::Bool(value) => output.push_str(if *value { "true" } else { "false" }),
+            JsonValue::Number(number) => output.push_str(number),
+            JsonValue::String(string) => write_escaped(output, string),
+
+            JsonValue::Array(values) if values.is_empty() => output.push_str("[]"),
+            JsonValue::Array(values) => {
+                // short lists of numbers are written on a single line
+                if values.len() <= 16
+                    && values
+                        .iter()
+                        .all(|value| matches!(value, JsonValue::Number(_)))
+                {
+                    output.push('[');
+
+                    for (index, value) in values.iter().enumerate() {
+                        if index != 0 {
+                            output.push_str(", ");
+                        }
+
+                        value.write_pretty(output, indentation);
+                    }
+
+                    output.push(']');
+                    return;
+                }
+
+                output.push('[');
+
+                for (index, value) in values.iter().enumerate() {
+                    if index != 0 {
+                        output.push(',');
+                    }
+
+                    indent(output, indentation + 1);
+                    value.write_pretty(output, indentation + 1);
+                }
+
+                indent(output, indentation);
+                output.push(']');
+            }
+
+            JsonValue::Object(fields) if fields.is_empty() => output.push_str("{}"),
+            JsonValue::Object(fields) => {
+                output.push('{');
+
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index != 0 {
+                        output.push(',');
+                    }
+
+                    indent(output, indentation + 1);
+                    write_escaped(output, name);
+                    output.push_str(": ");
+                    value.write_pretty(output, indentation + 1);
+                }
+
+                indent(output, indentation);
+                output.push('}');
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for JsonValue {
+    /// Writes pretty-printed JSON.
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut output = String::new();
+        self.write_pretty(&mut output, 0);
+        formatter.write_str(&output)
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(value: &str) -> Self {
+        JsonValue::String(value.to_string())
+    }
+}
+
+impl From<&Text> for JsonValue {
+    fn from(value: &Text) -> Self {
+        JsonValue::String(value.to_string())
+    }
+}
+
+impl From<u32> for JsonValue {
+    fn from(value: u32) -> Self {
+        JsonValue::Number(value.to_string())
+    }
+}
+
+impl From<i32> for JsonValue {
+    fn from(value: i32) -> Self {
+        JsonValue::Number(value.to_string())
+    }
+}
+
+impl From<usize> for JsonValue {
+    fn from(value: usize) -> Self {
+        JsonValue::Number(value.to_string())
+    }
+}
+
+impl From<u64> for JsonValue {
+    fn from(value: u64) -> Self {
+        JsonValue::Number(value.to_string())
+    }
+}
+
+impl From<f32> for JsonValue {
+    /// Non-finite numbers are represented as the strings `"NaN"`, `"inf"`, and `"-inf"`.
+    fn from(value: f32) -> Self {
+        if value.is_finite() {
+            JsonValue::Number(format!("{:?}", value))
+        } else {
+            JsonValue::from(value as f64)
+        }
+    }
+}
+
+impl From<f64> for JsonValue {
+    /// Non-finite numbers are represented as the strings `"NaN"`, `"inf"`, and `"-inf"`.
+    fn from(value: f64) -> Self {
+        if value.is_nan() {
+            JsonValue::from("NaN")
+        } else if value.is_infinite() {
+            JsonValue::from(if value > 0.0 { "inf" } else { "-inf" })
+        } else {
+            JsonValue::Number(format!("{:?}", value))
+        }
+    }
+}
+
+impl<T: Copy + Into<JsonValue>> From<Vec2<T>> for JsonValue {
+    fn from(value: Vec2<T>) -> Self {
+        JsonValue::Array(vec![value.0.into(), value.1.into()])
+    }
+}
+
+impl From<&AttributeValue> for JsonValue {
+    fn from(value: &AttributeValue) -> Self {
+        use AttributeValue::*;
+
+        let matrix = |values: &[f32], size: usize| {
+            JsonValue::Array(
+                values
+                    .chunks(size)
+                    .map(|row| JsonValue::Array(row.iter().map(|&value| value.into()).collect()))
+                    .collect(),
+            )
+        };
+
+        let custom = |kind: &attribute::Text, bytes: &[u8]| {
+            let mut hex = String::with_capacity(bytes.len() * 2);
+            for byte in bytes {
+                write!(hex, "{:02x}", byte).expect("string formatting bug");
+            }
+
+            JsonValue::object(vec![
+                ("type", JsonValue::from(kind)),
+                ("hex", JsonValue::String(hex)),
+            ])
+        };
+
+        match value {
+            ChannelList(channels) => {
+                JsonValue::Array(channels.list.iter().map(describe_channel).collect())
+            }
+
+            Chromaticities(value) => JsonValue::object(vec![
+                ("red", value.red.into()),
+                ("green", value.green.into()),
+                ("blue", value.blue.into()),
+                ("white", value.white.into()),
+            ]),
+
+            Compression(value) => JsonValue::from(compression_name(*value)),
+            EnvironmentMap(attribute::EnvironmentMap::LatitudeLongitude) => {
+                JsonValue::from("latlong")
+            }
+            EnvironmentMap(attribute::EnvironmentMap::Cube) => JsonValue::from("cube"),
+
+            KeyCode(value) => JsonValue::object(vec![
+                (
+                    "film_manufacturer_code",
+                    value.film_manufacturer_code.into(),
+                ),
+                ("film_type", value.film_type.into()),
+                ("film_roll_prefix", value.film_roll_prefix.into()),
+                ("count", value.count.into()),
+                ("perforation_offset", value.perforation_offset.into()),
+                (
+                    "perforations_per_frame",
+                    value.perforations_per_frame.into(),
+                ),
+                (
+                    "perforations_per_count",
+                    value.perforations_per_count.into(),
+                ),
+            ]),
+
+            LineOrder(value) => JsonValue::from(line_order_name(*value)),
+            Matrix3x3(value) => matrix(value, 3),
+            Matrix4x4(value) => matrix(value, 4),
+
+            Preview(value) => JsonValue::object(vec![
+                ("width", value.size.width().into()),
+                ("height", value.size.height().into()),
+            ]),
+
+            Rational((numerator, denominator)) => {
+                JsonValue::Array(vec![(*numerator).into(), (*denominator).into()])
+            }
+
+            BlockType(value) => JsonValue::from(&attribute::Text::from_slice_unchecked(value.to_text_bytes())),
+            TextVector(values) => JsonValue::Array(values.iter().map(JsonValue::from).collect()),
+            TileDescription(value) => describe_tiles(*value),
+            TimeCode(value) => JsonValue::String(value.to_string()),
+            Text(value) => JsonValue::from(value),
+            F64(value) => JsonValue::from(*value),
+            F32(value) => JsonValue::from(*value),
+            I32(value) => JsonValue::from(*value),
+            IntegerBounds(value) => describe_bounds(*value),
+
+            FloatRect(value) => {
+                JsonValue::object(vec![("min", value.min.into()), ("max", value.max.into())])
+            }
+
+            IntVec2(value) => JsonValue::from(*value),
+            FloatVec2(value) => JsonValue::from(*value),
+            IntVec3((x, y, z)) => JsonValue::Array(vec![(*x).into(), (*y).into(), (*z).into()]),
+            FloatVec3((x, y, z)) => JsonValue::Array(vec![(*x).into(), (*y).into(), (*z).into()]),
+            Bytes { type_hint, bytes } => custom(type_hint, bytes),
+            Custom { kind, bytes } => custom(kind, bytes),
+        }
+    }
+}
+
+fn describe_bounds(bounds: IntegerBounds) -> JsonValue {
+    JsonValue::object(vec![
+        ("x", bounds.position.x().into()),
+        ("y", bounds.position.y().into()),
+        ("width", bounds.size.width().into()),
+        ("height", bounds.size.height().into()),
+    ])
+}
+
+fn describe_tiles(tiles: TileDescription) -> JsonValue {
+    let level_mode = match tiles.level_mode {
+        LevelMode::Singular => "singular",
+        LevelMode::MipMap => "mipmap",
+        LevelMode::RipMap => "ripmap",
+    };
+
+    let rounding_mode = match tiles.rounding_mode {
+        RoundingMode::Down => "down",
+        RoundingMode::Up => "up",
+    };
+
+    JsonValue::object(vec![
+        ("width", tiles.tile_size.width().into()),
+        ("height", tiles.tile_size.height().into()),
+        ("level_mode", level_mode.into()),
+        ("rounding_mode", rounding_mode.into()),
+    ])
+}
+
+fn describe_channel(channel: &ChannelDescription) -> JsonValue {
+    let sample_type = match channel.sample_type {
+        SampleType::F16 => "f16",
+        SampleType::F32 => "f32",
+        SampleType::U32 => "u32",
+    };
+
+    JsonValue::object(vec![
+        ("name", JsonValue::from(&channel.name)),
+        ("sample_type", sample_type.into()),
+        (
+            "quantize_linearly",
+            JsonValue::Bool(channel.quantize_linearly),
+        ),
+        ("sampling", channel.sampling.into()),
+    ])
+}
+
+/// The stable name of a compression method, as used in the JSON description.
+pub fn compression_name(compression: Compression) -> &'static str {
+    match compression {
+        Compression::Uncompressed => "none",
+        Compression::RLE => "rle",
+        Compression::ZIP1 => "zip1",
+        Compression::ZIP16 => "zip16",
+        Compression::PIZ => "piz",
+        Compression::PXR24 => "pxr24",
+        Compression::B44 => "b44",
+        Compression::B44A => "b44a",
+        Compression::DWAA(_) => "dwaa",
+        Compression::DWAB(_) => "dwab",
+        Compression::HTJ2K32 => "htj2k32",
+        Compression::HTJ2K256 => "htj2k256",
+    }
+}
+
+/// Find a compression method by its name, as returned by `compression_name`.
+/// Also accepts the names used by the OpenEXR command line tools, such as `zips` and `zip`.
+/// Lossy DWA compression uses the default compression level.
+pub fn parse_compression(name: &str) -> Option<Compression> {
+    let compression = match name.to_ascii_lowercase().as_str() {
+        "none" | "uncompressed" => Compression::Uncompressed,
+        "rle" => Compression::RLE,
+        "zip1" | "zips" => Compression::ZIP1,
+        "zip16" | "zip" => Compression::ZIP16,
+        "piz" => Compression::PIZ,
+        "pxr24" => Compression::PXR24,
+        "b44" => Compression::B44,
+        "b44a" => Compression::B44A,
+        "dwaa" => Compression::DWAA(None),
+        "dwab" => Compression::DWAB(None),
+        "htj2k32" => Compression::HTJ2K32,
+        "htj2k256" => Compression::HTJ2K256,
+        _ => return None,
+    };
+
+    Some(compression)
+}
+
+/// The stable name of a line order, as used in the JSON description.
+pub fn line_order_name(line_order: LineOrder) -> &'static str {
+    match line_order {
+        LineOrder::Increasing => "increasing",
+        LineOrder::Decreasing => "decreasing",
+        LineOrder::Unspecified => "unspecified",
+    }
+}
+
+fn write_escaped(output: &mut String, string: &str) {
+    output.push('"');
+
+    for character in string.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            control if (control as u32) < 0x20 || control as u32 == 0x7f => {
+                write!(output, "\\u{:04x}", control as u32).expect("string formatting bug")
+            }
+            other => output.push(other),
+        }
+    }
+
+    output.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compact_json() {
+        let value = JsonValue::object(vec![
+            ("size", JsonValue::from(Vec2(1_i32, 2_i32))),
+            ("name", JsonValue::from("a b")),
+        ]);
+
+        assert_eq!(value.to_compact_string(), r#"{"size":[1,2],"name":"a b"}"#);
+    }
+
+    #[test]
+    fn escape_strings() {
+        let mut output = String::new();
+        write_escaped(&mut output, "a \"quoted\"\n\\ \u{1}");
+        assert_eq!(output, r#""a \"quoted\"\n\\ \u0001""#);
+    }
+
+    #[test]
+    fn escape_attribute_texts() {
+        let bytes = b"a\x01\"\\\x7f\xe9\n\xff\x1f";
+        let expected = "a\u{1}\"\\\u{7f}\u{e9}\n\u{ff}\u{1f}";
+
+        let mut header = Header::new("layer".into(), (4, 4), smallvec::smallvec![]);
+        header.own_attributes.owner = Some(Text::from_slice_unchecked(bytes));
+
+        for json in &[
+            header.describe(0).to_string(),
+            header.describe(0).to_compact_string(),
+        ] {
+            assert!(json.contains(r#""a\u0001\"\\\u007fé\nÿ\u001f""#));
+            assert!(!json
+                .chars()
+                .any(|character| character < ' ' && character != '\n'));
+
+            let parsed: serde_json::Value = serde_json::from_str(json).unwrap();
+            let owner = parsed["attributes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|attribute| attribute["name"] == "owner")
+                .unwrap();
+
+            assert_eq!(owner["value"], expected);
+        }
+    }
+
+    #[test]
+    fn describe_file() {
+        let path = "tests/images/valid/openexr/MultiView/Balls.exr";
+        let meta = MetaData::read_from_file(path, false).unwrap();
+        let json = meta.to_json();
+
+        assert!(json.starts_with("{\n  \"schema_version\": 1,"));
+        assert!(json.contains("\"chunk_count\": "));
+        assert!(json.contains("\"name\": \"channels\""));
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+
+        let header = &meta.headers[0];
+        match header.describe(0) {
+            JsonValue::Object(fields) => {
+                let (_, channels) = fields.iter().find(|(name, _)| name == "channels").unwrap();
+
+                match channels {
+                    JsonValue::Array(channels) => {
+                        assert_eq!(channels.len(), header.channels.list.len())
+                    }
+                    other => panic!("channels should be an array, not {:?}", other),
+                }
+            }
+
+            other => panic!("layer should be an object, not {:?}", other),
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(JsonValue::from(1.0_f32).to_string(), "1.0");
+        assert_eq!(JsonValue::from(f32::NAN).to_string(), "\"NaN\"");
+        assert_eq!(JsonValue::from(Vec2(1, -2)).to_string(), "[1, -2]");
+    }
+
+    #[test]
+    fn compression_names_round_trip() {
+        for &compression in &[
+            Compression::Uncompressed,
+            Compression::ZIP1,
+            Compression::PIZ,
+            Compression::DWAB(None),
+        ] {
+            assert_eq!(parse_compression(compression_name(compression)), Some(compression));
+        }
+
+        assert_eq!(parse_compression("ZIP"), Some(Compression::ZIP16));
+        assert_eq!(parse_compression("lzw"), None);
+    }
+}
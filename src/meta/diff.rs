@@ -0,0 +1,383 @@
+//! Compare the meta data of two files.
+//! Explains which layers, channels, and attributes were added, removed, or changed.
+//! Pixel data is not compared.
+
+use crate::error::*;
+use crate::meta::attribute::{AttributeValue, ChannelDescription, Text};
+use crate::meta::header::{standard_names, Header};
+use crate::meta::MetaData;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// The differences between the meta data of two files.
+/// The first file is called `old`, the second file is called `new`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MetaDataDiff {
+    /// Layers that only exist in the new file, identified by their name or index.
+    pub added_layers: Vec<LayerIdentifier>,
+
+    /// Layers that only exist in the old file, identified by their name or index.
+    pub removed_layers: Vec<LayerIdentifier>,
+
+    /// Differences in layers that exist in both files.
+    /// Only contains layers with at least one difference.
+    pub changed_layers: Vec<LayerDiff>,
+}
+
+/// Identifies a layer in a file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LayerIdentifier {
+    /// The layer has a name, which is used to match layers of the two files.
+    Name(Text),
+
+    /// The layers do not all have names, so layers are matched by their index.
+    Index(usize),
+}
+
+/// The differences between two versions of a layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerDiff {
+    /// The name or index of the layer.
+    pub layer: LayerIdentifier,
+
+    /// Attributes that only exist in the new layer.
+    pub added_attributes: Vec<(Text, AttributeValue)>,
+
+    /// Attributes that only exist in the old layer.
+    pub removed_attributes: Vec<(Text, AttributeValue)>,
+
+    /// Attributes whose value differs, containing the old and the new value.
+    /// Values are compared by their binary representation, so that `NaN` values are equal.
+    pub changed_attributes: Vec<(Text, AttributeValue, AttributeValue)>,
+
+    /// Channels that only exist in the new layer.
+    pub added_channels: Vec<ChannelDescription>,
+
+    /// Channels that only exist in the old layer.
+    pub removed_channels: Vec<ChannelDescription>,
+
+    /// Channels whose sample type, sampling, or quantization differs, containing the old and the new channel.
+    pub changed_channels: Vec<(ChannelDescription, ChannelDescription)>,
+}
+
+/// Compare the meta data of two files, without reading any pixel data.
+pub fn diff_files(old: impl AsRef<Path>, new: impl AsRef<Path>) -> Result<MetaDataDiff> {
+    let old = MetaData::read_from_file(old, false)?;
+    let new = MetaData::read_from_file(new, false)?;
+    Ok(old.diff(&new))
+}
+
+impl MetaData {
+    /// Compare this meta data, the old version, with another one, the new version.
+    /// Layers are matched by name if all layers in both files have unique names, and by index otherwise.
+    pub fn diff(&self, new: &MetaData) -> MetaDataDiff {
+        let old_layers = identify_layers(&self.headers);
+        let new_layers = identify_layers(&new.headers);
+
+        let (old_layers, new_layers) = match (old_layers, new_layers) {
+            (Some(old_layers), Some(new_layers)) => (old_layers, new_layers),
+            _ => (index_layers(&self.headers), index_layers(&new.headers)),
+        };
+
+        let mut diff = MetaDataDiff::default();
+
+        for (identifier, old_header) in &old_layers {
+            match new_layers.get(identifier) {
+                None => diff.removed_layers.push(identifier.clone()),
+                Some(new_header) => {
+                    let layer = diff_layer(identifier.clone(), old_header, new_header);
+                    if !layer.is_empty() {
+                        diff.changed_layers.push(layer);
+                    }
+                }
+            }
+        }
+
+        diff.added_layers = new_layers
+            .keys()
+            .filter(|identifier| !old_layers.contains_key(identifier))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+impl MetaDataDiff {
+    /// Whether the meta data of both files is equal.
+    pub fn is_empty(&self) -> bool {
+        self.added_layers.is_empty()
+            && self.removed_layers.is_empty()
+            && self.changed_layers.is_empty()
+    }
+}
+
+impl LayerDiff {
+    /// Whether both versions of the layer are equal.
+    pub fn is_empty(&self) -> bool {
+        self.added_attributes.is_empty()
+            && self.removed_attributes.is_empty()
+            && self.changed_attributes.is_empty()
+            && self.added_channels.is_empty()
+            && self.removed_channels.is_empty()
+            && self.changed_channels.is_empty()
+    }
+}
+
+/// Returns `None` if any layer has no name or if any name appears twice.
+fn identify_layers(headers: &[Header]) -> Option<BTreeMap<LayerIdentifier, &Header>> {
+    let mut layers = BTreeMap::new();
+
+    for header in headers {
+        let name = header.own_attributes.layer_name.clone()?;
+
+        if layers.insert(LayerIdentifier::Name(name), header).is_some() {
+            return None;
+        }
+    }
+
+    Some(layers)
+}
+
+fn index_layers(headers: &[Header]) -> BTreeMap<LayerIdentifier, &Header> {
+    headers
+        .iter()
+        .enumerate()
+        .map(|(index, header)| (LayerIdentifier::Index(index), header))
+        .collect()
+}
+
+fn diff_layer(layer: LayerIdentifier, old: &Header, new: &Header) -> LayerDiff {
+    // channels are compared individually below
+    let attributes = |header: &Header| -> BTreeMap<Text, AttributeValue> {
+        header
+            .all_named_attributes()
+            .filter(|(name, _)| *name != standard_names::CHANNELS)
+            .map(|(name, value)| (Text::from_slice_unchecked(name), value))
+            .collect()
+    };
+
+    let old_attributes = attributes(old);
+    let new_attributes = attributes(new);
+
+    let mut diff = LayerDiff {
+        layer,
+        added_attributes: Vec::new(),
+        removed_attributes: Vec::new(),
+        changed_attributes: Vec::new(),
+        added_channels: Vec::new(),
+        removed_channels: Vec::new(),
+        changed_channels: Vec::new(),
+    };
+
+    for (name, old_value) in &old_attributes {
+        match new_attributes.get(name) {
+            None => diff
+                .removed_attributes
+                .push((name.clone(), old_value.clone())),
+            Some(new_value) if !same_bytes(old_value, new_value) => {
+                diff.changed_attributes
+                    .push((name.clone(), old_value.clone(), new_value.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (name, new_value) in &new_attributes {
+        if !old_attributes.contains_key(name) {
+            diff.added_attributes
+                .push((name.clone(), new_value.clone()));
+        }
+    }
+
+    for old_channel in &old.channels.list {
+        match new
+            .channels
+            .list
+            .iter()
+            .find(|channel| channel.name == old_channel.name)
+        {
+            None => diff.removed_channels.push(old_channel.clone()),
+            Some(new_channel) if new_channel != old_channel => diff
+                .changed_channels
+                .push((old_channel.clone(), new_channel.clone())),
+            Some(_) => {}
+        }
+    }
+
+    for new_channel in &new.channels.list {
+        if !old
+            .channels
+            .list
+            .iter()
+            .any(|channel| channel.name == new_channel.name)
+        {
+            diff.added_channels.push(new_channel.clone());
+        }
+    }
+
+    diff
+}
+
+/// Compare the binary representation, which also treats `NaN` values as equal.
+fn same_bytes(old: &AttributeValue, new: &AttributeValue) -> bool {
+    if old.kind_name() != new.kind_name() {
+        return false;
+    }
+
+    let (mut old_bytes, mut new_bytes) = (Vec::new(), Vec::new());
+    let written = old
+        .write(&mut old_bytes)
+        .and_then(|()| new.write(&mut new_bytes));
+    written.is_ok() && old_bytes == new_bytes
+}
+
+impl Display for LayerIdentifier {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayerIdentifier::Name(name) => write!(formatter, "layer `{}`", name),
+            LayerIdentifier::Index(index) => write!(formatter, "layer #{}", index),
+        }
+    }
+}
+
+impl Display for MetaDataDiff {
+    /// Lists all differences, one per line.
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        for layer in &self.added_layers {
+            writeln!(formatter, "added {}", layer)?;
+        }
+
+        for layer in &self.removed_layers {
+            writeln!(formatter, "removed {}", layer)?;
+        }
+
+        for layer in &self.changed_layers {
+            write!(formatter, "{}", layer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for LayerDiff {
+    /// Lists all differences, one per line.
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        let layer = &self.layer;
+
+        for channel in &self.added_channels {
+            writeln!(
+                formatter,
+                "{}: added channel `{}` ({:?})",
+                layer, channel.name, channel.sample_type
+            )?;
+        }
+
+        for channel in &self.removed_channels {
+            writeln!(formatter, "{}: removed channel `{}`", layer, channel.name)?;
+        }
+
+        for (old, new) in &self.changed_channels {
+            writeln!(
+                formatter,
+                "{}: changed channel `{}`: {:?} -> {:?}",
+                layer, old.name, old, new
+            )?;
+        }
+
+        for (name, value) in &self.added_attributes {
+            writeln!(
+                formatter,
+                "{}: added attribute `{}`: {:?}",
+                layer, name, value
+            )?;
+        }
+
+        for (name, value) in &self.removed_attributes {
+            writeln!(
+                formatter,
+                "{}: removed attribute `{}`: {:?}",
+                layer, name, value
+            )?;
+        }
+
+        for (name, old, new) in &self.changed_attributes {
+            writeln!(
+                formatter,
+                "{}: changed attribute `{}`: {:?} -> {:?}",
+                layer, name, old, new
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::attribute::SampleType;
+
+    fn meta_data() -> MetaData {
+        let path = "tests/images/valid/openexr/MultiView/Balls.exr";
+        MetaData::read_from_file(path, false).unwrap()
+    }
+
+    #[test]
+    fn equal_meta_data() {
+        let meta = meta_data();
+        assert!(meta.diff(&meta.clone()).is_empty());
+    }
+
+    #[test]
+    fn attribute_and_channel_changes() {
+        let old = meta_data();
+        let mut new = old.clone();
+
+        let header = &mut new.headers[0];
+        header.own_attributes.owner = Some(Text::from("studio"));
+        header
+            .own_attributes
+            .other
+            .insert(Text::from("custom"), AttributeValue::I32(1));
+        header.shared_attributes.pixel_aspect += 1.0;
+
+        let first_channel = header.channels.list[0].clone();
+        header.channels.list[0].sample_type = match first_channel.sample_type {
+            SampleType::F16 => SampleType::F32,
+            _ => SampleType::F16,
+        };
+
+        let diff = old.diff(&new);
+        assert!(diff.added_layers.is_empty() && diff.removed_layers.is_empty());
+        assert_eq!(diff.changed_layers.len(), 1);
+
+        let layer = &diff.changed_layers[0];
+        let added: Vec<String> = layer
+            .added_attributes
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        assert!(added.contains(&"owner".to_string()) && added.contains(&"custom".to_string()));
+
+        assert_eq!(layer.changed_attributes.len(), 1);
+        assert_eq!(
+            layer.changed_attributes[0].0,
+            Text::from("pixelAspectRatio")
+        );
+        assert_eq!(layer.changed_channels.len(), 1);
+        assert_eq!(layer.changed_channels[0].0, first_channel);
+
+        assert!(diff
+            .to_string()
+            .contains("changed attribute `pixelAspectRatio`"));
+    }
+
+    #[test]
+    fn nan_attributes_are_equal() {
+        let mut old = meta_data();
+        old.headers[0].own_attributes.aperture = Some(f32::NAN);
+        assert!(old.diff(&old.clone()).is_empty());
+    }
+}
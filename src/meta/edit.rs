@@ -0,0 +1,652 @@
+//! Edit the attributes of an existing file without decompressing any pixels.
+//! Useful to fix meta data, such as `framesPerSecond` or `owner`, on large numbers of frames.
+//!
+//! If the modified headers fit into the space of the original headers,
+//! the headers are overwritten in place, padding the remaining space with a dummy attribute.
+//! Otherwise, the file is rewritten, copying the compressed pixel chunks without decoding them.
+
+use crate::error::*;
+use crate::io::*;
+use crate::meta::attribute::{AttributeValue, Text, TextSlice};
+use crate::meta::header::Header;
+use crate::meta::{magic_number, MetaData, OffsetTables, Requirements};
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Seek, SeekFrom};
+use std::path::Path;
+
+/// The name of the attribute that fills unused space after the headers were shrunk.
+/// This attribute is removed automatically before the headers are passed to the edit function.
+pub const PADDING_ATTRIBUTE_NAME: &[u8] = b"exrsPadding";
+
+/// The type name of the attribute that fills unused space after the headers were shrunk.
+pub const PADDING_ATTRIBUTE_TYPE: &[u8] = b"exrsPadding";
+
+/// How the modified headers were stored in the file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum HeaderEditResult {
+    /// The modified headers had exactly the size of the original headers and were overwritten in place.
+    InPlace,
+
+    /// The modified headers were smaller than the original headers.
+    /// They were overwritten in place, and a padding attribute occupies the remaining space.
+    Padded,
+
+    /// The modified headers did not fit into the original space.
+    /// The file was rewritten, copying the compressed pixel data without decompressing it.
+    Rewritten,
+}
+
+/// Modify the header attributes of an existing file, without decompressing any pixel data.
+///
+/// The closure receives all headers of the file and may change any attribute that
+/// does not affect the layout of the pixel data, such as the owner, comments,
+/// frames per second, time code, camera matrices, or any custom attributes.
+/// Changing channels, compression, data window, tiles, or line order is an error,
+/// and leaves the file untouched.
+///
+/// Custom attributes that could not be parsed are kept with their original bytes.
+/// A standard attribute that could not be parsed cannot be written again,
+/// so the file is left untouched and an error is returned instead.
+/// If the file needs to be rewritten, a temporary file next to the original file is used,
+/// which then replaces the original file.
+pub fn edit_header_attributes(
+    path: impl AsRef<Path>,
+    edit: impl FnOnce(&mut [Header]) -> UnitResult,
+) -> Result<HeaderEditResult> {
+    let path = path.as_ref();
+
+    let (requirements, original_headers, headers_end, offset_tables) = {
+        let mut read = PeekRead::new(Tracking::new(BufReader::new(File::open(path)?)));
+        magic_number::validate_exr(&mut read)?;
+
+        let requirements = Requirements::read(&mut read)?;
+        requirements.validate()?;
+
+        let headers = Header::read_all_without_loss(&mut read, &requirements)?;
+        let headers_end = read.byte_position();
+        let offset_tables = MetaData::read_offset_tables(&mut read, &headers)?;
+        (requirements, headers, headers_end, offset_tables)
+    };
+
+    let mut headers = original_headers.clone();
+    for header in headers.iter_mut() {
+        remove_padding(header);
+    }
+
+    edit(&mut headers)?;
+
+    if headers.len() != original_headers.len() {
+        return Err(Error::invalid("header count must not be changed"));
+    }
+
+    for (original, edited) in original_headers.iter().zip(headers.iter()) {
+        validate_pixel_layout_unchanged(original, edited)?;
+    }
+
+    let requirements = Requirements {
+        has_long_names: requirements.has_long_names
+            || MetaData::validate(&headers, false)?.has_long_names,
+        ..requirements
+    };
+
+    // the version number and the headers start right after the magic number
+    let headers_start = magic_number::BYTES.len();
+    let available_bytes = headers_end - headers_start;
+
+    let mut header_bytes = serialize(requirements, &headers)?;
+
+    let result = if header_bytes.len() == available_bytes {
+        HeaderEditResult::InPlace
+    } else if header_bytes.len() + padding_overhead() <= available_bytes {
+        let padding = available_bytes - header_bytes.len() - padding_overhead();
+        add_padding(headers.last_mut().expect("header count bug"), padding);
+        header_bytes = serialize(requirements, &headers)?;
+
+        debug_assert_eq!(header_bytes.len(), available_bytes, "padding size bug");
+        HeaderEditResult::Padded
+    } else {
+        HeaderEditResult::Rewritten
+    };
+
+    match result {
+        HeaderEditResult::InPlace | HeaderEditResult::Padded => {
+            let mut file = OpenOptions::new().write(true).open(path)?;
+            file.seek(SeekFrom::Start(headers_start as u64))?;
+            u8::write_slice_le(&mut file, &header_bytes)?;
+            file.sync_all()?;
+        }
+
+        HeaderEditResult::Rewritten => {
+            let new_headers_end = headers_start + header_bytes.len();
+            let offset_delta = new_headers_end as i128 - headers_end as i128;
+
+            let temporary_path = path.with_extension("exrs-edit.tmp");
+
+            let rewrite = || -> UnitResult {
+                let mut original = BufReader::new(File::open(path)?);
+                original.seek(SeekFrom::Start(headers_end as u64))?;
+
+                // skip the original offset tables, they will be replaced
+                let offset_count: usize = offset_tables.iter().map(Vec::len).sum();
+                skip_bytes(&mut original, offset_count * u64::BYTE_SIZE)?;
+
+                let mut write = BufWriter::new(File::create(&temporary_path)?);
+                magic_number::write(&mut write)?;
+                u8::write_slice_le(&mut write, &header_bytes)?;
+                write_shifted_offset_tables(&mut write, &offset_tables, offset_delta)?;
+
+                // the chunks contain no absolute file positions, so they can be copied verbatim
+                std::io::copy(&mut original, &mut write)?;
+
+                let file = write.into_inner().map_err(|error| error.into_error())?;
+                file.sync_all()?;
+                Ok(())
+            };
+
+            if let Err(error) = rewrite() {
+                let _ = std::fs::remove_file(&temporary_path);
+                return Err(error);
+            }
+
+            std::fs::rename(&temporary_path, path)?;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Number of bytes a padding attribute requires in addition to its contents.
+fn padding_overhead() -> usize {
+    let empty_padding = padding_value(0);
+    let name = Text::from_slice_unchecked(PADDING_ATTRIBUTE_NAME);
+    crate::meta::attribute::byte_size(&name, &empty_padding)
+}
+
+fn padding_value(byte_count: usize) -> AttributeValue {
+    AttributeValue::Custom {
+        kind: Text::from_slice_unchecked(PADDING_ATTRIBUTE_TYPE),
+        bytes: smallvec::smallvec![0; byte_count],
+    }
+}
+
+fn add_padding(header: &mut Header, byte_count: usize) {
+    header.own_attributes.other.insert(
+        Text::from_slice_unchecked(PADDING_ATTRIBUTE_NAME),
+        padding_value(byte_count),
+    );
+}
+
+fn remove_padding(header: &mut Header) {
+    header.own_attributes.other.remove(PADDING_ATTRIBUTE_NAME);
+}
+
+/// Serialize the version flags and all headers, excluding the magic number.
+fn serialize(requirements: Requirements, headers: &[Header]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    requirements.write(&mut bytes)?;
+    Header::write_all(headers, &mut bytes, requirements.is_multilayer())?;
+    Ok(bytes)
+}
+
+fn write_shifted_offset_tables(
+    write: &mut impl Write,
+    offset_tables: &OffsetTables,
+    delta: i128,
+) -> UnitResult {
+    for table in offset_tables {
+        for &offset in table {
+            let shifted = u64::try_from(offset as i128 + delta)
+                .map_err(|_| Error::invalid("chunk offset"))?;
+
+            shifted.write_le(write)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns an error if any property that determines the layout of the pixel data has changed.
+fn validate_pixel_layout_unchanged(original: &Header, edited: &Header) -> UnitResult {
+    let unchanged = original.channels == edited.channels
+        && original.compression == edited.compression
+        && original.blocks == edited.blocks
+        && original.line_order == edited.line_order
+        && original.layer_size == edited.layer_size
+        && original.own_attributes.layer_position == edited.own_attributes.layer_position
+        && original.deep == edited.deep
+        && original.deep_data_version == edited.deep_data_version
+        && original.max_samples_per_pixel == edited.max_samples_per_pixel
+        && original.chunk_count == edited.chunk_count;
+
+    if unchanged {
+        Ok(())
+    } else {
+        Err(Error::invalid(
+            "attributes that affect the pixel data cannot be edited without recompressing",
+        ))
+    }
+}
+
+/// The names of the attributes that describe the layout of the pixel data.
+/// These attributes cannot be set or removed by name.
+pub(crate) const PIXEL_LAYOUT_ATTRIBUTES: &[&[u8]] = {
+    use crate::meta::header::standard_names::*;
+    &[
+        BLOCK_TYPE,
+        TILES,
+        CHANNELS,
+        COMPRESSION,
+        DATA_WINDOW,
+        LINE_ORDER,
+        DEEP_DATA_VERSION,
+        MAX_SAMPLES,
+        CHUNKS,
+        DWA_COMPRESSION_LEVEL,
+    ]
+};
+
+/// The type of each standard attribute that is not part of the pixel layout.
+const STANDARD_ATTRIBUTE_TYPES: &[(&[u8], &[u8])] = {
+    use crate::meta::attribute::type_names as ty;
+    use crate::meta::header::standard_names::*;
+    &[
+        (DISPLAY_WINDOW, ty::I32BOX2),
+        (PIXEL_ASPECT, ty::F32),
+        (WINDOW_CENTER, ty::F32VEC2),
+        (WINDOW_WIDTH, ty::F32),
+        (NAME, ty::TEXT),
+        (WHITE_LUMINANCE, ty::F32),
+        (ADOPTED_NEUTRAL, ty::F32VEC2),
+        (RENDERING_TRANSFORM, ty::TEXT),
+        (LOOK_MOD_TRANSFORM, ty::TEXT),
+        (X_DENSITY, ty::F32),
+        (OWNER, ty::TEXT),
+        (COMMENTS, ty::TEXT),
+        (CAPTURE_DATE, ty::TEXT),
+        (UTC_OFFSET, ty::F32),
+        (LONGITUDE, ty::F32),
+        (LATITUDE, ty::F32),
+        (ALTITUDE, ty::F32),
+        (FOCUS, ty::F32),
+        (EXPOSURE_TIME, ty::F32),
+        (APERTURE, ty::F32),
+        (ISO_SPEED, ty::F32),
+        (ENVIRONMENT_MAP, ty::ENVIRONMENT_MAP),
+        (KEY_CODE, ty::KEY_CODE),
+        (TIME_CODE, ty::TIME_CODE),
+        (WRAP_MODES, ty::TEXT),
+        (FRAMES_PER_SECOND, ty::RATIONAL),
+        (MULTI_VIEW, ty::TEXT_VECTOR),
+        (WORLD_TO_CAMERA, ty::F32MATRIX4X4),
+        (WORLD_TO_NDC, ty::F32MATRIX4X4),
+        (DEEP_IMAGE_STATE, ty::RATIONAL),
+        (ORIGINAL_DATA_WINDOW, ty::I32BOX2),
+        (CHROMATICITIES, ty::CHROMATICITIES),
+        (PREVIEW, ty::PREVIEW),
+        (VIEW, ty::TEXT),
+        (NEAR, ty::F32),
+        (FAR, ty::F32),
+        (FOV_X, ty::F32),
+        (FOV_Y, ty::F32),
+        (SOFTWARE, ty::TEXT),
+    ]
+};
+
+/// The type name that a standard attribute must have, such as `float` for `xDensity`.
+/// Returns `None` for custom attributes and for attributes that describe the pixel layout.
+pub fn standard_attribute_type(name: &TextSlice) -> Option<&'static TextSlice> {
+    STANDARD_ATTRIBUTE_TYPES
+        .iter()
+        .find(|(standard_name, _)| *standard_name == name)
+        .map(|&(_, kind)| kind)
+}
+
+impl Header {
+    /// The value of the attribute with the specified name, whether it is a standard or a custom attribute.
+    pub fn named_attribute(&self, name: &TextSlice) -> Option<AttributeValue> {
+        self.all_named_attributes()
+            .find(|(attribute_name, _)| *attribute_name == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Set an attribute by its name, storing standard attributes in their corresponding fields.
+    /// Returns an error if the name is a standard attribute but the value has a different type,
+    /// or if the attribute describes the layout of the pixel data, such as `channels` or `dataWindow`.
+    pub fn set_named_attribute(&mut self, name: Text, value: AttributeValue) -> UnitResult {
+        use crate::meta::header::standard_names::*;
+        use AttributeValue as Value;
+
+        let name_bytes = name.as_slice();
+        if PIXEL_LAYOUT_ATTRIBUTES.contains(&name_bytes) {
+            return Err(layout_attribute_error(&name));
+        }
+
+        if let Some(kind) = standard_attribute_type(name_bytes) {
+            if value.kind_name() != kind {
+                return Err(Error::invalid(format!(
+                    "attribute `{}` must have type `{}`",
+                    name,
+                    Text::from_slice_unchecked(kind)
+                )));
+            }
+        }
+
+        let layer = &mut self.own_attributes;
+        let image = &mut self.shared_attributes;
+
+        macro_rules! set_fields {
+            ( $( $name:ident : $variant:ident => $field:expr ),* ) => {
+                match (name_bytes, value) {
+                    (DISPLAY_WINDOW, Value::IntegerBounds(value)) => image.display_window = value,
+                    (PIXEL_ASPECT, Value::F32(value)) => image.pixel_aspect = value,
+                    (WINDOW_CENTER, Value::FloatVec2(value)) => layer.screen_window_center = value,
+                    (WINDOW_WIDTH, Value::F32(value)) => layer.screen_window_width = value,
+
+                    $( ($name, Value::$variant(value)) => $field = Some(value), )*
+
+                    // like when reading, unknown attributes of these types must be the same for all headers
+                    (_, value @ Value::Chromaticities(_)) | (_, value @ Value::TimeCode(_)) => {
+                        layer.other.remove(name_bytes);
+                        image.other.insert(name.clone(), value);
+                    }
+
+                    (_, value) => {
+                        image.other.remove(name_bytes);
+                        layer.other.insert(name.clone(), value);
+                    }
+                }
+            };
+        }
+
+        set_fields! {
+            NAME: Text => layer.layer_name,
+            WHITE_LUMINANCE: F32 => layer.white_luminance,
+            ADOPTED_NEUTRAL: FloatVec2 => layer.adopted_neutral,
+            RENDERING_TRANSFORM: Text => layer.rendering_transform_name,
+            LOOK_MOD_TRANSFORM: Text => layer.look_modification_transform_name,
+            X_DENSITY: F32 => layer.horizontal_density,
+            OWNER: Text => layer.owner,
+            COMMENTS: Text => layer.comments,
+            CAPTURE_DATE: Text => layer.capture_date,
+            UTC_OFFSET: F32 => layer.utc_offset,
+            LONGITUDE: F32 => layer.longitude,
+            LATITUDE: F32 => layer.latitude,
+            ALTITUDE: F32 => layer.altitude,
+            FOCUS: F32 => layer.focus,
+            EXPOSURE_TIME: F32 => layer.exposure,
+            APERTURE: F32 => layer.aperture,
+            ISO_SPEED: F32 => layer.iso_speed,
+            ENVIRONMENT_MAP: EnvironmentMap => layer.environment_map,
+            KEY_CODE: KeyCode => layer.film_key_code,
+            TIME_CODE: TimeCode => image.time_code,
+            WRAP_MODES: Text => layer.wrap_mode_name,
+            FRAMES_PER_SECOND: Rational => layer.frames_per_second,
+            MULTI_VIEW: TextVector => layer.multi_view_names,
+            WORLD_TO_CAMERA: Matrix4x4 => layer.world_to_camera,
+            WORLD_TO_NDC: Matrix4x4 => layer.world_to_normalized_device,
+            DEEP_IMAGE_STATE: Rational => layer.deep_image_state,
+            ORIGINAL_DATA_WINDOW: IntegerBounds => layer.original_data_window,
+            CHROMATICITIES: Chromaticities => image.chromaticities,
+            PREVIEW: Preview => layer.preview,
+            VIEW: Text => layer.view_name,
+            NEAR: F32 => layer.near_clip_plane,
+            FAR: F32 => layer.far_clip_plane,
+            FOV_X: F32 => layer.horizontal_field_of_view,
+            FOV_Y: F32 => layer.vertical_field_of_view,
+            SOFTWARE: Text => layer.software_name
+        }
+
+        Ok(())
+    }
+
+    /// Remove an attribute by its name. Returns the removed value, if the attribute was present.
+    /// Returns an error for attributes that are required in every file, such as `displayWindow`,
+    /// and for attributes that describe the layout of the pixel data.
+    pub fn remove_named_attribute(&mut self, name: &TextSlice) -> Result<Option<AttributeValue>> {
+        use crate::meta::header::standard_names::*;
+        use AttributeValue as Value;
+
+        let text = Text::from_slice_unchecked(name);
+        if PIXEL_LAYOUT_ATTRIBUTES.contains(&name) {
+            return Err(layout_attribute_error(&text));
+        }
+
+        if [DISPLAY_WINDOW, PIXEL_ASPECT, WINDOW_CENTER, WINDOW_WIDTH].contains(&name) {
+            return Err(Error::invalid(format!(
+                "attribute `{}` is required and cannot be removed",
+                text
+            )));
+        }
+
+        let layer = &mut self.own_attributes;
+        let image = &mut self.shared_attributes;
+
+        macro_rules! remove_fields {
+            ( $( $name:ident : $variant:ident => $field:expr ),* ) => {
+                match name {
+                    $( $name => $field.take().map(Value::$variant), )*
+                    _ => layer.other.remove(name).or_else(|| image.other.remove(name)),
+                }
+            };
+        }
+
+        let removed = remove_fields! {
+            NAME: Text => layer.layer_name,
+            WHITE_LUMINANCE: F32 => layer.white_luminance,
+            ADOPTED_NEUTRAL: FloatVec2 => layer.adopted_neutral,
+            RENDERING_TRANSFORM: Text => layer.rendering_transform_name,
+            LOOK_MOD_TRANSFORM: Text => layer.look_modification_transform_name,
+            X_DENSITY: F32 => layer.horizontal_density,
+            OWNER: Text => layer.owner,
+            COMMENTS: Text => layer.comments,
+            CAPTURE_DATE: Text => layer.capture_date,
+            UTC_OFFSET: F32 => layer.utc_offset,
+            LONGITUDE: F32 => layer.longitude,
+            LATITUDE: F32 => layer.latitude,
+            ALTITUDE: F32 => layer.altitude,
+            FOCUS: F32 => layer.focus,
+            EXPOSURE_TIME: F32 => layer.exposure,
+            APERTURE: F32 => layer.aperture,
+            ISO_SPEED: F32 => layer.iso_speed,
+            ENVIRONMENT_MAP: EnvironmentMap => layer.environment_map,
+            KEY_CODE: KeyCode => layer.film_key_code,
+            TIME_CODE: TimeCode => image.time_code,
+            WRAP_MODES: Text => layer.wrap_mode_name,
+            FRAMES_PER_SECOND: Rational => layer.frames_per_second,
+            MULTI_VIEW: TextVector => layer.multi_view_names,
+            WORLD_TO_CAMERA: Matrix4x4 => layer.world_to_camera,
+            WORLD_TO_NDC: Matrix4x4 => layer.world_to_normalized_device,
+            DEEP_IMAGE_STATE: Rational => layer.deep_image_state,
+            ORIGINAL_DATA_WINDOW: IntegerBounds => layer.original_data_window,
+            CHROMATICITIES: Chromaticities => image.chromaticities,
+            PREVIEW: Preview => layer.preview,
+            VIEW: Text => layer.view_name,
+            NEAR: F32 => layer.near_clip_plane,
+            FAR: F32 => layer.far_clip_plane,
+            FOV_X: F32 => layer.horizontal_field_of_view,
+            FOV_Y: F32 => layer.vertical_field_of_view,
+            SOFTWARE: Text => layer.software_name
+        };
+
+        Ok(removed)
+    }
+}
+
+fn layout_attribute_error(name: &Text) -> Error {
+    Error::invalid(format!(
+        "attribute `{}` describes the pixel data and cannot be edited",
+        name
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    fn write_test_file(path: &Path) {
+        write_rgb_file(path, 8, 4, |x, y| (x as f32, y as f32, 0.5_f32)).unwrap();
+    }
+
+    fn read_pixels(path: &Path) -> AnyChannels<FlatSamples> {
+        let image = read_first_flat_layer_from_file(path).unwrap();
+        image.layer_data.channel_data
+    }
+
+    #[test]
+    fn edit_in_place_and_rewrite() {
+        let directory = std::env::temp_dir();
+        let path = directory.join("exrs_header_edit_test.exr");
+        write_test_file(&path);
+        let original_pixels = read_pixels(&path);
+        let original_size = std::fs::metadata(&path).unwrap().len();
+
+        // grow the header, requires a rewrite
+        let result = edit_header_attributes(&path, |headers| {
+            headers[0].own_attributes.owner = Some(Text::from("a very long owner name to force a rewrite"));
+            headers[0].own_attributes.frames_per_second = Some((24, 1));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(result, HeaderEditResult::Rewritten);
+        assert_eq!(read_pixels(&path), original_pixels);
+
+        // shrink the header, fits with padding
+        let result = edit_header_attributes(&path, |headers| {
+            headers[0].own_attributes.owner = Some(Text::from("short"));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(result, HeaderEditResult::Padded);
+        assert_eq!(read_pixels(&path), original_pixels);
+
+        let meta = MetaData::read_from_file(&path, false).unwrap();
+        let attributes = &meta.headers[0].own_attributes;
+        assert_eq!(attributes.owner, Some(Text::from("short")));
+        assert_eq!(attributes.frames_per_second, Some((24, 1)));
+        assert!(std::fs::metadata(&path).unwrap().len() > original_size);
+
+        // same size again, padding is reused
+        let result = edit_header_attributes(&path, |headers| {
+            headers[0].own_attributes.owner = Some(Text::from("other"));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(result, HeaderEditResult::Padded);
+
+        // pixel layout changes are rejected
+        let error = edit_header_attributes(&path, |headers| {
+            headers[0].compression = Compression::ZIP16;
+            Ok(())
+        });
+
+        assert!(error.is_err());
+        assert_eq!(read_pixels(&path), original_pixels);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn malformed_attributes_are_kept_or_rejected() {
+        let path = std::env::temp_dir().join("exrs_header_edit_malformed_test.exr");
+        write_test_file(&path);
+
+        let insert_raw = |name: &str, kind: &str, bytes: &[u8]| {
+            let (name, kind, bytes) = (Text::from(name), Text::from(kind), bytes.to_vec());
+            edit_header_attributes(&path, move |headers| {
+                let value = AttributeValue::Custom {
+                    kind,
+                    bytes: bytes.into(),
+                };
+                headers[0].own_attributes.other.insert(name, value);
+                Ok(())
+            })
+            .unwrap();
+        };
+
+        let contains = |bytes: &[u8]| {
+            let file = std::fs::read(&path).unwrap();
+            file.windows(bytes.len()).any(|window| window == bytes)
+        };
+
+        // an environment map attribute with an invalid value byte
+        insert_raw("vendorEnvmap", "envmap", &[7]);
+        let envmap = b"vendorEnvmap\0envmap\0\x01\0\0\0\x07";
+        assert!(contains(envmap));
+
+        edit_header_attributes(&path, |headers| {
+            headers[0].own_attributes.owner = Some(Text::from("someone"));
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(
+            contains(envmap),
+            "malformed custom attribute should be kept"
+        );
+
+        // a standard attribute with a truncated value
+        insert_raw("xDensity", "float", &[1]);
+        let file = std::fs::read(&path).unwrap();
+
+        let result = edit_header_attributes(&path, |headers| {
+            headers[0].own_attributes.owner = None;
+            Ok(())
+        });
+
+        assert!(result.is_err());
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            file,
+            "file should be untouched"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn set_and_remove_named_attributes() {
+        let mut header = Header::new("layer".into(), (4, 4), smallvec::smallvec![]);
+
+        header
+            .set_named_attribute("owner".into(), AttributeValue::Text("me".into()))
+            .unwrap();
+        assert_eq!(header.own_attributes.owner, Some(Text::from("me")));
+        assert_eq!(
+            header.named_attribute(b"owner"),
+            Some(AttributeValue::Text("me".into()))
+        );
+
+        header
+            .set_named_attribute("shot".into(), AttributeValue::I32(42))
+            .unwrap();
+        assert_eq!(header.named_attribute(b"shot"), Some(AttributeValue::I32(42)));
+
+        // standard attributes must have their standard type
+        assert!(header
+            .set_named_attribute("xDensity".into(), AttributeValue::I32(72))
+            .is_err());
+
+        // the pixel layout cannot be changed by name
+        assert!(header
+            .set_named_attribute("compression".into(), AttributeValue::Compression(Compression::RLE))
+            .is_err());
+        assert!(header.remove_named_attribute(b"displayWindow").is_err());
+
+        assert_eq!(
+            header.remove_named_attribute(b"owner").unwrap(),
+            Some(AttributeValue::Text("me".into()))
+        );
+        assert_eq!(header.own_attributes.owner, None);
+        assert_eq!(
+            header.remove_named_attribute(b"shot").unwrap(),
+            Some(AttributeValue::I32(42))
+        );
+        assert_eq!(header.remove_named_attribute(b"shot").unwrap(), None);
+        assert_eq!(standard_attribute_type(b"xDensity"), Some(&b"float"[..]));
+    }
+}
@@ -0,0 +1,482 @@
+//! Geometry of environment maps, as described by the `envmap` attribute.
+//! Converts between 3D directions and pixel positions, and resamples single channels between the two layouts.
+//!
+//! Follows the conventions of the OpenEXR reference implementation.
+//! Pixel positions are relative to the origin of the data window.
+//! A latitude-longitude map covers the whole sphere, with `+y` pointing up,
+//! and the center of the image looking along `+z`.
+//! A cube map stacks six square faces vertically, in the order of `CubeFace::ALL`.
+//!
+//! Decoded layers and images can be converted between the two layouts
+//! with `to_environment_map`, see the `image::environment_map` module.
+
+use crate::error::*;
+use crate::math::Vec2;
+use crate::meta::attribute::EnvironmentMap;
+use std::f32::consts::PI;
+
+/// One of the six sides of a cube map.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CubeFace {
+    /// The face looking along the positive x axis.
+    PositiveX,
+
+    /// The face looking along the negative x axis.
+    NegativeX,
+
+    /// The face looking along the positive y axis.
+    PositiveY,
+
+    /// The face looking along the negative y axis.
+    NegativeY,
+
+    /// The face looking along the positive z axis.
+    PositiveZ,
+
+    /// The face looking along the negative z axis.
+    NegativeZ,
+}
+
+impl CubeFace {
+    /// All faces, in the order they appear in the image, from top to bottom.
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// The index of this face in the image, from top to bottom.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl EnvironmentMap {
+    /// Validate that an image of this size can contain this type of environment map.
+    /// Cube maps must be six times as high as they are wide.
+    pub fn validate_size(self, size: Vec2<usize>) -> UnitResult {
+        if size.area() == 0 {
+            return Err(Error::invalid("environment map size"));
+        }
+
+        match self {
+            EnvironmentMap::LatitudeLongitude => Ok(()),
+            EnvironmentMap::Cube if size.height() == size.width() * 6 => Ok(()),
+            EnvironmentMap::Cube => Err(Error::invalid(
+                "cube map height must be six times its width",
+            )),
+        }
+    }
+
+    /// The pixel position, relative to the data window origin, that a direction maps to.
+    /// The direction does not need to be normalized.
+    pub fn pixel_position(self, size: Vec2<usize>, direction: [f32; 3]) -> Vec2<f32> {
+        match self {
+            EnvironmentMap::LatitudeLongitude => {
+                lat_long::pixel_position(size, lat_long::from_direction(direction))
+            }
+
+            EnvironmentMap::Cube => {
+                let (face, position) = cube::face_and_position(size, direction);
+                cube::pixel_position(size, face, position)
+            }
+        }
+    }
+
+    /// The direction, not necessarily normalized,
+    /// that the pixel at this position, relative to the data window origin, represents.
+    pub fn direction(self, size: Vec2<usize>, pixel_position: Vec2<f32>) -> [f32; 3] {
+        match self {
+            EnvironmentMap::LatitudeLongitude => {
+                lat_long::to_direction(lat_long::from_pixel_position(size, pixel_position))
+            }
+
+            EnvironmentMap::Cube => {
+                let (face, position) = cube::face_and_position_of_pixel(size, pixel_position);
+                cube::direction(size, face, position)
+            }
+        }
+    }
+
+    /// Look up the value of a single channel for a direction, using bilinear interpolation.
+    /// The samples are stored row by row, with `size.area()` samples in total.
+    /// Lookups in cube maps do not interpolate across face boundaries.
+    pub fn sample(self, size: Vec2<usize>, samples: &[f32], direction: [f32; 3]) -> f32 {
+        debug_assert_eq!(samples.len(), size.area(), "sample count bug");
+        let sample_at = |x: usize, y: usize| samples[y * size.width() + x];
+
+        match self {
+            EnvironmentMap::LatitudeLongitude => {
+                let position = lat_long::pixel_position(size, lat_long::from_direction(direction));
+                let max_y = size.height().saturating_sub(1) as i64;
+
+                bilinear(position, |x, y| {
+                    // wrap around horizontally, clamp at the poles
+                    let x = x.rem_euclid(size.width() as i64) as usize;
+                    let y = y.max(0).min(max_y) as usize;
+                    sample_at(x, y)
+                })
+            }
+
+            EnvironmentMap::Cube => {
+                let (face, position) = cube::face_and_position(size, direction);
+                let face_max = cube::face_size(size).saturating_sub(1) as i64;
+
+                bilinear(position, |x, y| {
+                    let clamped =
+                        Vec2(x.max(0).min(face_max) as f32, y.max(0).min(face_max) as f32);
+                    let pixel = cube::pixel_position(size, face, clamped);
+                    sample_at(pixel.x().round() as usize, pixel.y().round() as usize)
+                })
+            }
+        }
+    }
+
+    /// Resample a single channel from this layout into another layout and size.
+    /// The samples are stored row by row. Returns the new samples, also stored row by row.
+    pub fn resample(
+        self,
+        size: Vec2<usize>,
+        samples: &[f32],
+        target: EnvironmentMap,
+        target_size: Vec2<usize>,
+    ) -> Result<Vec<f32>> {
+        self.resample_filtered(size, samples, target, target_size, 1)
+    }
+
+    /// Resample a single channel like `resample`, but average a grid of
+    /// `samples_per_axis` by `samples_per_axis` lookups spread over the area of each target pixel.
+    /// Avoids aliasing when the target has a lower resolution than the source.
+    pub fn resample_filtered(
+        self,
+        size: Vec2<usize>,
+        samples: &[f32],
+        target: EnvironmentMap,
+        target_size: Vec2<usize>,
+        samples_per_axis: usize,
+    ) -> Result<Vec<f32>> {
+        self.validate_size(size)?;
+        target.validate_size(target_size)?;
+
+        if samples.len() != size.area() {
+            return Err(Error::invalid("environment map sample count"));
+        }
+
+        let samples_per_axis = samples_per_axis.max(1);
+        let offsets: Vec<f32> = (0..samples_per_axis)
+            .map(|index| (index as f32 + 0.5) / samples_per_axis as f32 - 0.5)
+            .collect();
+
+        let mut result = Vec::with_capacity(target_size.area());
+
+        for y in 0..target_size.height() {
+            for x in 0..target_size.width() {
+                let mut sum = 0.0;
+
+                for &offset_y in &offsets {
+                    for &offset_x in &offsets {
+                        let position = Vec2(x as f32 + offset_x, y as f32 + offset_y);
+                        let direction = target.direction(target_size, position);
+                        sum += self.sample(size, samples, direction);
+                    }
+                }
+
+                result.push(sum / offsets.len().pow(2) as f32);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// The width of a latitude-longitude map with the same number of pixels around the horizon.
+    /// For cube maps, this is four times the face size.
+    pub fn horizontal_resolution(self, size: Vec2<usize>) -> usize {
+        match self {
+            EnvironmentMap::LatitudeLongitude => size.width(),
+            EnvironmentMap::Cube => cube::face_size(size) * 4,
+        }
+    }
+}
+
+/// Interpolate between the four pixels around the position.
+fn bilinear(position: Vec2<f32>, sample: impl Fn(i64, i64) -> f32) -> f32 {
+    let (left, top) = (position.x().floor(), position.y().floor());
+    let (fraction_x, fraction_y) = (position.x() - left, position.y() - top);
+    let (left, top) = (left as i64, top as i64);
+
+    let upper = sample(left, top) * (1.0 - fraction_x) + sample(left + 1, top) * fraction_x;
+    let lower = sample(left, top + 1) * (1.0 - fraction_x) + sample(left + 1, top + 1) * fraction_x;
+    upper * (1.0 - fraction_y) + lower * fraction_y
+}
+
+/// Latitude-longitude environment maps.
+/// Latitude ranges from `-pi/2` at the bottom to `pi/2` at the top,
+/// longitude ranges from `pi` at the left to `-pi` at the right.
+pub mod lat_long {
+    use super::*;
+
+    /// The latitude and longitude, in radians, of a direction.
+    pub fn from_direction([x, y, z]: [f32; 3]) -> Vec2<f32> {
+        let horizontal = (z * z + x * x).sqrt();
+
+        let latitude = y.atan2(horizontal);
+        let longitude = if horizontal == 0.0 { 0.0 } else { x.atan2(z) };
+        Vec2(latitude, longitude)
+    }
+
+    /// The direction, of length one, at this latitude and longitude, in radians.
+    pub fn to_direction(lat_long: Vec2<f32>) -> [f32; 3] {
+        let (latitude, longitude) = (lat_long.0, lat_long.1);
+
+        [
+            longitude.sin() * latitude.cos(),
+            latitude.sin(),
+            longitude.cos() * latitude.cos(),
+        ]
+    }
+
+    /// The latitude and longitude, in radians, of a pixel position.
+    pub fn from_pixel_position(size: Vec2<usize>, position: Vec2<f32>) -> Vec2<f32> {
+        let max = Vec2(size.width() as f32 - 1.0, size.height() as f32 - 1.0);
+
+        let latitude = if max.y() > 0.0 {
+            -PI * ((position.y() - max.y() * 0.5) / max.y())
+        } else {
+            0.0
+        };
+
+        let longitude = if max.x() > 0.0 {
+            -2.0 * PI * ((position.x() - max.x() * 0.5) / max.x())
+        } else {
+            0.0
+        };
+
+        Vec2(latitude, longitude)
+    }
+
+    /// The pixel position of a latitude and longitude, in radians.
+    pub fn pixel_position(size: Vec2<usize>, lat_long: Vec2<f32>) -> Vec2<f32> {
+        let max = Vec2(size.width() as f32 - 1.0, size.height() as f32 - 1.0);
+
+        Vec2(
+            lat_long.1 / (-2.0 * PI) * max.x() + max.x() * 0.5,
+            lat_long.0 / -PI * max.y() + max.y() * 0.5,
+        )
+    }
+}
+
+/// Cube environment maps.
+/// The position inside a face ranges from zero to the face size minus one.
+pub mod cube {
+    use super::*;
+
+    /// The width and height of each face of a cube map with this image size.
+    pub fn face_size(size: Vec2<usize>) -> usize {
+        size.width().min(size.height() / 6)
+    }
+
+    /// The face and the position inside that face that a direction maps to.
+    pub fn face_and_position(size: Vec2<usize>, [x, y, z]: [f32; 3]) -> (CubeFace, Vec2<f32>) {
+        let max = face_size(size).saturating_sub(1) as f32;
+        let (abs_x, abs_y, abs_z) = (x.abs(), y.abs(), z.abs());
+        let to_face = |value: f32, major: f32| (value / major + 1.0) * 0.5 * max;
+
+        if abs_x >= abs_y && abs_x >= abs_z {
+            if abs_x == 0.0 {
+                return (CubeFace::PositiveX, Vec2(0.0, 0.0));
+            }
+
+            let face = if x >= 0.0 {
+                CubeFace::PositiveX
+            } else {
+                CubeFace::NegativeX
+            };
+            (face, Vec2(to_face(y, abs_x), to_face(z, abs_x)))
+        } else if abs_y >= abs_z {
+            let face = if y >= 0.0 {
+                CubeFace::PositiveY
+            } else {
+                CubeFace::NegativeY
+            };
+            (face, Vec2(to_face(x, abs_y), to_face(z, abs_y)))
+        } else {
+            let face = if z >= 0.0 {
+                CubeFace::PositiveZ
+            } else {
+                CubeFace::NegativeZ
+            };
+            (face, Vec2(to_face(x, abs_z), to_face(y, abs_z)))
+        }
+    }
+
+    /// The direction, not normalized, of a position inside a face.
+    pub fn direction(size: Vec2<usize>, face: CubeFace, position: Vec2<f32>) -> [f32; 3] {
+        let max = face_size(size).saturating_sub(1) as f32;
+
+        let Vec2(u, v) = if max > 0.0 {
+            Vec2(
+                position.x() / max * 2.0 - 1.0,
+                position.y() / max * 2.0 - 1.0,
+            )
+        } else {
+            Vec2(0.0, 0.0)
+        };
+
+        match face {
+            CubeFace::PositiveX => [1.0, u, v],
+            CubeFace::NegativeX => [-1.0, u, v],
+            CubeFace::PositiveY => [u, 1.0, v],
+            CubeFace::NegativeY => [u, -1.0, v],
+            CubeFace::PositiveZ => [u, v, 1.0],
+            CubeFace::NegativeZ => [u, v, -1.0],
+        }
+    }
+
+    /// The pixel position in the image of a position inside a face.
+    pub fn pixel_position(size: Vec2<usize>, face: CubeFace, position: Vec2<f32>) -> Vec2<f32> {
+        let face_size = face_size(size);
+        let min = Vec2(0.0, (face.index() * face_size) as f32);
+        let max = Vec2(face_size as f32 - 1.0, min.y() + face_size as f32 - 1.0);
+        let Vec2(x, y) = position;
+
+        match face {
+            CubeFace::PositiveX => Vec2(min.x() + y, max.y() - x),
+            CubeFace::NegativeX => Vec2(max.x() - y, max.y() - x),
+            CubeFace::PositiveY => Vec2(min.x() + x, max.y() - y),
+            CubeFace::NegativeY => Vec2(min.x() + x, min.y() + y),
+            CubeFace::PositiveZ => Vec2(max.x() - x, max.y() - y),
+            CubeFace::NegativeZ => Vec2(min.x() + x, max.y() - y),
+        }
+    }
+
+    /// The face and the position inside that face of a pixel position in the image.
+    /// The inverse of `pixel_position`.
+    pub fn face_and_position_of_pixel(
+        size: Vec2<usize>,
+        pixel_position: Vec2<f32>,
+    ) -> (CubeFace, Vec2<f32>) {
+        let face_size = face_size(size).max(1);
+        let face_index = ((pixel_position.y().max(0.0) as usize) / face_size).min(5);
+        let face = CubeFace::ALL[face_index];
+
+        let min = Vec2(0.0, (face_index * face_size) as f32);
+        let max = Vec2(face_size as f32 - 1.0, min.y() + face_size as f32 - 1.0);
+        let Vec2(x, y) = pixel_position;
+
+        let position = match face {
+            CubeFace::PositiveX => Vec2(max.y() - y, x - min.x()),
+            CubeFace::NegativeX => Vec2(max.y() - y, max.x() - x),
+            CubeFace::PositiveY => Vec2(x - min.x(), max.y() - y),
+            CubeFace::NegativeY => Vec2(x - min.x(), y - min.y()),
+            CubeFace::PositiveZ => Vec2(max.x() - x, max.y() - y),
+            CubeFace::NegativeZ => Vec2(x - min.x(), max.y() - y),
+        };
+
+        (face, position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_same_direction(a: [f32; 3], b: [f32; 3]) {
+        let length = |[x, y, z]: [f32; 3]| (x * x + y * y + z * z).sqrt();
+        let (la, lb) = (length(a), length(b));
+
+        for index in 0..3 {
+            assert!(
+                (a[index] / la - b[index] / lb).abs() < 1.0e-3,
+                "{:?} != {:?}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn direction_roundtrip() {
+        let directions = [
+            [1.0, 0.0, 0.0],
+            [0.3, 0.5, -0.8],
+            [0.0, -1.0, 0.1],
+            [-0.2, 0.1, 0.9],
+        ];
+
+        for &map in &[EnvironmentMap::LatitudeLongitude, EnvironmentMap::Cube] {
+            let size = if map == EnvironmentMap::Cube {
+                Vec2(64, 384)
+            } else {
+                Vec2(128, 64)
+            };
+
+            for &direction in &directions {
+                let pixel = map.pixel_position(size, direction);
+                assert_same_direction(map.direction(size, pixel), direction);
+            }
+        }
+    }
+
+    #[test]
+    fn lat_long_center_looks_forward() {
+        let size = Vec2(101, 51);
+        let pixel = EnvironmentMap::LatitudeLongitude.pixel_position(size, [0.0, 0.0, 1.0]);
+        assert!((pixel.x() - 50.0).abs() < 1.0e-4 && (pixel.y() - 25.0).abs() < 1.0e-4);
+
+        let top = EnvironmentMap::LatitudeLongitude.direction(size, Vec2(50.0, 0.0));
+        assert_same_direction(top, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn resample_constant_and_gradient() {
+        let lat_long_size = Vec2(64, 32);
+        let constant = vec![0.5; lat_long_size.area()];
+
+        let cube = EnvironmentMap::LatitudeLongitude
+            .resample(lat_long_size, &constant, EnvironmentMap::Cube, Vec2(16, 96))
+            .unwrap();
+
+        assert!(cube.iter().all(|&value| (value - 0.5).abs() < 1.0e-5));
+
+        // brightness depends on the height of the direction only
+        let gradient: Vec<f32> = (0..lat_long_size.area())
+            .map(|index| (index / lat_long_size.width()) as f32)
+            .collect();
+
+        let cube = EnvironmentMap::LatitudeLongitude
+            .resample(lat_long_size, &gradient, EnvironmentMap::Cube, Vec2(16, 96))
+            .unwrap();
+
+        let face_of = |face: CubeFace| &cube[face.index() * 16 * 16..(face.index() + 1) * 16 * 16];
+        let average = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+        assert!(average(face_of(CubeFace::PositiveY)) < average(face_of(CubeFace::PositiveX)));
+        assert!(average(face_of(CubeFace::NegativeY)) > average(face_of(CubeFace::PositiveX)));
+
+        let filtered = EnvironmentMap::LatitudeLongitude
+            .resample_filtered(
+                lat_long_size,
+                &gradient,
+                EnvironmentMap::Cube,
+                Vec2(16, 96),
+                4,
+            )
+            .unwrap();
+
+        assert!(filtered
+            .iter()
+            .all(|&value| value >= 0.0 && value <= (lat_long_size.height() - 1) as f32));
+
+        assert!(EnvironmentMap::Cube
+            .resample(
+                Vec2(16, 90),
+                &vec![0.0; 16 * 90],
+                EnvironmentMap::LatitudeLongitude,
+                lat_long_size
+            )
+            .is_err());
+    }
+
+}
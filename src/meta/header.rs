@@ -12,6 +12,7 @@ use std::collections::HashMap;
 /// A file can have any number of layers.
 /// The meta data contains one header per layer.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     /// List of channels in this layer.
     pub channels: ChannelList,
@@ -80,6 +81,7 @@ pub struct Header {
 /// which must be the same for all layers.
 /// For more attributes, see struct `LayerAttributes`.
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImageAttributes {
     /// The rectangle anywhere in the global infinite 2D space
     /// that clips all contents of the file.
@@ -104,6 +106,7 @@ pub struct ImageAttributes {
 /// Excludes standard fields that must be the same for all headers.
 /// For more attributes, see struct `ImageAttributes`.
 #[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LayerAttributes {
     /// The name of this layer.
     /// Required if this file contains deep data or multiple layers.
@@ -256,6 +259,14 @@ impl LayerAttributes {
         }
     }
 
+    /// Set the film key code that identifies the film frame this layer was scanned from.
+    pub fn with_key_code(self, key_code: KeyCode) -> Self {
+        Self {
+            film_key_code: Some(key_code),
+            ..self
+        }
+    }
+
     /// Set all common camera projection attributes at once.
     pub fn with_camera_frustum(
         self,
@@ -294,6 +305,28 @@ impl ImageAttributes {
     pub fn with_size(size: impl Into<Vec2<usize>>) -> Self {
         Self::new(IntegerBounds::from_dimensions(size))
     }
+
+    /// The color space of the pixels. If the file does not specify chromaticities,
+    /// returns the `Rec. 709` primaries, as required by the specification.
+    pub fn chromaticities_or_default(&self) -> Chromaticities {
+        self.chromaticities.unwrap_or_default()
+    }
+
+    /// Set the color space of the pixels.
+    pub fn with_chromaticities(self, chromaticities: Chromaticities) -> Self {
+        Self {
+            chromaticities: Some(chromaticities),
+            ..self
+        }
+    }
+
+    /// Set the time code of this frame.
+    pub fn with_time_code(self, time_code: TimeCode) -> Self {
+        Self {
+            time_code: Some(time_code),
+            ..self
+        }
+    }
 }
 
 impl Header {
@@ -681,6 +714,16 @@ impl Header {
     pub fn total_pixel_bytes(&self) -> usize {
         assert!(!self.deep);
 
+        self.channels
+            .list
+            .iter()
+            .map(|channel: &ChannelDescription| self.channel_pixel_bytes(channel))
+            .sum()
+    }
+
+    /// Returns the number of bytes that the samples of the specified flat channel will require
+    /// when stored without compression. Respects multi-resolution levels and subsampling.
+    pub fn channel_pixel_bytes(&self, channel: &ChannelDescription) -> usize {
         let pixel_count_of_levels = |size: Vec2<usize>| -> usize {
             match self.blocks {
                 BlockDescription::ScanLines => size.area(),
@@ -698,14 +741,8 @@ impl Header {
             }
         };
 
-        self.channels
-            .list
-            .iter()
-            .map(|channel: &ChannelDescription| {
-                pixel_count_of_levels(channel.subsampled_resolution(self.layer_size))
-                    * channel.sample_type.bytes_per_sample()
-            })
-            .sum()
+        pixel_count_of_levels(channel.subsampled_resolution(self.layer_size))
+            * channel.sample_type.bytes_per_sample()
     }
 
     /// Approximates the maximum number of bytes that the pixels of this header will consume in a file.
@@ -830,9 +867,7 @@ impl Header {
             }
 
             if !self.compression.supports_deep_data() {
-                return Err(Error::invalid(
-                    "compression method does not support deep data",
-                ));
+                return Err(self.compression.deep_data_error());
             }
         }
 
@@ -844,14 +879,34 @@ impl Header {
         read: &mut PeekRead<impl Read>,
         version: &Requirements,
         pedantic: bool,
+    ) -> Result<Headers> {
+        Self::read_each(read, version, |read| Header::read(read, version, pedantic))
+    }
+
+    /// Read all headers without validating, like `read_all` when not pedantic,
+    /// but fail instead of dropping any attribute that could not be parsed.
+    /// Writing these headers again reproduces every attribute of the file.
+    pub(crate) fn read_all_without_loss(
+        read: &mut PeekRead<impl Read>,
+        version: &Requirements,
+    ) -> Result<Headers> {
+        Self::read_each(read, version, |read| {
+            Header::read_attributes(read, version, false, true)
+        })
+    }
+
+    fn read_each<R: Read>(
+        read: &mut PeekRead<R>,
+        version: &Requirements,
+        mut read_header: impl FnMut(&mut PeekRead<R>) -> Result<Header>,
     ) -> Result<Headers> {
         if !version.is_multilayer() {
-            Ok(smallvec![Header::read(read, version, pedantic)?])
+            Ok(smallvec![read_header(read)?])
         } else {
             let mut headers = SmallVec::new();
 
             while !sequence_end::has_come(read)? {
-                headers.push(Header::read(read, version, pedantic)?);
+                headers.push(read_header(read)?);
             }
 
             Ok(headers)
@@ -1054,10 +1109,24 @@ impl Header {
     }
 
     /// Read the value without validating.
+    /// If not pedantic, custom attributes that are malformed or contain unused bytes
+    /// are kept as `AttributeValue::Custom` with their original bytes,
+    /// such that writing the header again does not alter them.
     pub fn read(
         read: &mut PeekRead<impl Read>,
         requirements: &Requirements,
         pedantic: bool,
+    ) -> Result<Self> {
+        Self::read_attributes(read, requirements, pedantic, false)
+    }
+
+    /// Read the value without validating. If `reject_dropped` is set, a standard attribute
+    /// that could not be parsed is an error, even if not pedantic, instead of being dropped.
+    fn read_attributes(
+        read: &mut PeekRead<impl Read>,
+        requirements: &Requirements,
+        pedantic: bool,
+        reject_dropped: bool,
     ) -> Result<Self> {
         let max_string_len = if requirements.has_long_names { 256 } else { 32 }; // TODO DRY this information
 
@@ -1079,7 +1148,18 @@ impl Header {
 
         // read each attribute in this header
         while !sequence_end::has_come(read)? {
-            let (attribute_name, value) = attribute::read(read, max_string_len)?;
+            let (attribute_name, value, original) =
+                attribute::read_with_original(read, max_string_len)?;
+
+            // when not pedantic, keep the original bytes of any attribute that would otherwise be lost or altered
+            let preserve = |value: AttributeValue| match &original {
+                AttributeValue::Custom { kind, bytes }
+                    if !pedantic && !value.writes_exactly(kind.as_slice(), bytes) =>
+                {
+                    original.clone()
+                }
+                _ => value,
+            };
 
             // if the attribute value itself is ok, record it
             match value {
@@ -1208,22 +1288,29 @@ impl Header {
                         // insert unknown attributes of these types into image attributes,
                         // as these must be the same for all headers
                         (_, value @ Chromaticities(_)) | (_, value @ TimeCode(_)) => {
-                            image_attributes.other.insert(attribute_name, value);
+                            image_attributes.other.insert(attribute_name, preserve(value));
                         }
 
                         // insert unknown attributes into layer attributes
                         (_, value) => {
-                            layer_attributes.other.insert(attribute_name, value);
+                            layer_attributes.other.insert(attribute_name, preserve(value));
                         }
                     }
                 }
 
                 // in case the attribute value itself is not ok, but the rest of the image is
-                // only abort reading the image if desired
+                // only abort reading the image if desired, and otherwise keep the original bytes,
+                // unless the name is reserved, in which case the attribute cannot be custom
                 Err(error) => {
                     if pedantic {
                         return Err(error);
                     }
+
+                    if !standard_names::ALL.contains(&attribute_name.as_slice()) {
+                        layer_attributes.other.insert(attribute_name, original);
+                    } else if reject_dropped {
+                        return Err(error);
+                    }
                 }
             }
         }
@@ -1614,4 +1701,164 @@ mod tests {
             }
         }
     }
+
+    mod attribute_round_trip_tests {
+        use super::*;
+
+        fn raw_attribute(name: &str, kind: &str, value: &[u8]) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(0);
+            bytes.extend_from_slice(kind.as_bytes());
+            bytes.push(0);
+            bytes.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            bytes.extend_from_slice(value);
+            bytes
+        }
+
+        fn requirements() -> Requirements {
+            Requirements {
+                file_format_version: 2,
+                is_single_layer_and_tiled: false,
+                has_long_names: false,
+                has_deep_data: false,
+                has_multiple_layers: false,
+            }
+        }
+
+        /// Header bytes containing a vendor blob, a malformed environment map, and a float with extra bytes.
+        fn header_bytes(vendor_attributes: &[Vec<u8>]) -> Vec<u8> {
+            let mut header = make_test_header(8, 8, BlockDescription::ScanLines);
+            header.chunk_count =
+                compute_chunk_count(header.compression, header.layer_size, header.blocks);
+
+            let mut bytes = Vec::new();
+            header.write(&mut bytes).unwrap();
+
+            let end = bytes.pop();
+            assert_eq!(end, Some(0), "header should end with a null byte");
+
+            for attribute in vendor_attributes {
+                bytes.extend_from_slice(attribute);
+            }
+
+            bytes.push(0);
+            bytes
+        }
+
+        #[test]
+        fn unrecognized_attributes_are_preserved() {
+            let vendor_attributes = vec![
+                raw_attribute("vendorBlob", "vendorData", &[1, 2, 3, 4, 5]),
+                raw_attribute("vendorEnvmap", "envmap", &[7]),
+                raw_attribute("vendorGain", "float", &[0, 0, 128, 63, 9, 9, 9, 9]),
+            ];
+
+            let bytes = header_bytes(&vendor_attributes);
+            let header = Header::read(&mut PeekRead::new(bytes.as_slice()), &requirements(), false).unwrap();
+            assert_eq!(header.own_attributes.other.len(), 3);
+
+            let mut written = Vec::new();
+            header.write(&mut written).unwrap();
+
+            for attribute in &vendor_attributes {
+                assert!(
+                    written.windows(attribute.len()).any(|window| window == attribute.as_slice()),
+                    "attribute should be written byte-exact"
+                );
+            }
+        }
+
+        #[test]
+        fn recognized_attributes_are_parsed() {
+            let gain = raw_attribute("vendorGain", "float", &[0, 0, 128, 63]);
+            let bytes = header_bytes(&[gain]);
+
+            let header = Header::read(&mut PeekRead::new(bytes.as_slice()), &requirements(), false).unwrap();
+            assert_eq!(
+                header.own_attributes.other.get(&Text::from("vendorGain")),
+                Some(&AttributeValue::F32(1.0))
+            );
+        }
+
+        #[test]
+        fn pedantic_rejects_malformed_attributes() {
+            let envmap = raw_attribute("vendorEnvmap", "envmap", &[7]);
+            let bytes = header_bytes(&[envmap]);
+            assert!(Header::read(&mut PeekRead::new(bytes.as_slice()), &requirements(), true).is_err());
+        }
+
+        #[test]
+        fn lenient_meta_data_preserves_malformed_attributes() {
+            let envmap = raw_attribute("vendorEnvmap", "envmap", &[7]);
+
+            let mut bytes = Vec::new();
+            magic_number::write(&mut bytes).unwrap();
+            requirements().write(&mut bytes).unwrap();
+            bytes.extend_from_slice(&header_bytes(&[envmap.clone()]));
+
+            let read = |pedantic| {
+                MetaData::read_validated_from_buffered_peekable(&mut PeekRead::new(bytes.as_slice()), pedantic)
+            };
+
+            assert!(read(true).is_err());
+
+            let lenient = read(false).unwrap();
+            assert_eq!(lenient.headers[0].own_attributes.other.len(), 1);
+        }
+
+        fn file_bytes(attributes: &[Vec<u8>]) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            magic_number::write(&mut bytes).unwrap();
+            requirements().write(&mut bytes).unwrap();
+            bytes.extend_from_slice(&header_bytes(attributes));
+            bytes
+        }
+
+        #[test]
+        fn unknown_attributes_are_read_pedantic_and_lenient() {
+            let bytes = file_bytes(&[raw_attribute("vendorBlob", "vendorData", &[1, 2, 3])]);
+            let expected = AttributeValue::Custom {
+                kind: Text::from("vendorData"),
+                bytes: smallvec![1, 2, 3],
+            };
+
+            for &pedantic in &[true, false] {
+                let unvalidated = MetaData::read_from_buffered(bytes.as_slice(), pedantic).unwrap();
+                let validated = MetaData::read_validated_from_buffered_peekable(
+                    &mut PeekRead::new(bytes.as_slice()),
+                    pedantic,
+                )
+                .unwrap();
+
+                for meta in &[unvalidated, validated] {
+                    assert_eq!(
+                        meta.headers[0].own_attributes.other.get(&Text::from("vendorBlob")),
+                        Some(&expected)
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn lenient_reads_skip_pedantic_checks() {
+            // a chunk count that does not match the data window is only checked when pedantic
+            let chunk_count = raw_attribute("chunkCount", "int", &999_i32.to_le_bytes());
+            let bytes = file_bytes(&[chunk_count]);
+
+            let read = |pedantic| {
+                MetaData::read_validated_from_buffered_peekable(
+                    &mut PeekRead::new(bytes.as_slice()),
+                    pedantic,
+                )
+            };
+
+            assert!(read(true).is_err());
+            assert!(MetaData::read_from_buffered(bytes.as_slice(), true).is_err());
+
+            let lenient = read(false).unwrap();
+            assert_ne!(lenient.headers[0].chunk_count, 999);
+            assert!(MetaData::read_from_buffered(bytes.as_slice(), false).is_ok());
+        }
+    }
 }
@@ -0,0 +1,293 @@
+//! Access the attributes and channels of a layer inside a header, using dotted layer names.
+//! Single-part files often contain multiple layers, with channel names like `diffuse.R`.
+//! By convention, attributes that only apply to one of these layers are prefixed the same way,
+//! for example `diffuse.blurAmount`.
+//!
+//! Nested layers, like `diffuse.left`, inherit the attributes of their enclosing layers,
+//! and all layers inherit the unprefixed attributes of the header.
+
+use crate::meta::attribute::{AttributeValue, ChannelDescription, Text};
+use crate::meta::header::{Header, LayerAttributes};
+use smallvec::SmallVec;
+
+/// A view of the attributes and channels of one layer inside a header.
+/// Created using `header.layer_scope("diffuse")`.
+#[derive(Debug, Clone, Copy)]
+pub struct LayerScope<'h> {
+    header: &'h Header,
+    layer: &'h str,
+}
+
+impl Header {
+    /// Look at the attributes and channels of a layer inside this header, like `diffuse` or `diffuse.left`.
+    /// An empty layer name refers to the whole header.
+    pub fn layer_scope<'h>(&'h self, layer: &'h str) -> LayerScope<'h> {
+        LayerScope {
+            header: self,
+            layer: layer.trim_end_matches('.'),
+        }
+    }
+
+    /// The names of all layers that contain channels, derived from the channel names.
+    /// For example, the channels `R`, `diffuse.R`, and `diffuse.left.R` result in `diffuse` and `diffuse.left`.
+    /// Sorted alphabetically, without duplicates.
+    pub fn channel_layer_names(&self) -> Vec<Text> {
+        let mut layers: Vec<Text> = self
+            .channels
+            .list
+            .iter()
+            .filter_map(|channel| {
+                let name = channel.name.as_slice();
+                let last_dot = name.iter().rposition(|&byte| byte == b'.')?;
+                Some(Text::from_slice_unchecked(&name[..last_dot]))
+            })
+            .collect();
+
+        layers.sort();
+        layers.dedup();
+        layers
+    }
+}
+
+impl<'h> LayerScope<'h> {
+    /// The name of the layer, without a trailing dot.
+    pub fn layer(&self) -> &'h str {
+        self.layer
+    }
+
+    /// Find an attribute of this layer, such as `blurAmount` for the layer `diffuse`.
+    /// First looks for `diffuse.blurAmount`, then for the same attribute in the enclosing layers,
+    /// and finally for the unprefixed attribute `blurAmount` of the header.
+    pub fn attribute(&self, name: &str) -> Option<AttributeValue> {
+        let mut layer = self.layer;
+
+        loop {
+            if !layer.is_empty() {
+                let full_name = prefixed(layer, name);
+                if let Some(value) = find_attribute(self.header, &full_name) {
+                    return Some(value);
+                }
+            }
+
+            match layer.rfind('.') {
+                Some(dot) => layer = &layer[..dot],
+                None if !layer.is_empty() => layer = "",
+                None => return find_attribute(self.header, name.as_bytes()),
+            }
+        }
+    }
+
+    /// All attributes that are prefixed with the name of this layer, with the prefix removed.
+    /// Does not include attributes of nested or enclosing layers, or the unprefixed attributes of the header.
+    pub fn own_attributes(&self) -> Vec<(Text, AttributeValue)> {
+        if self.layer.is_empty() {
+            return Vec::new();
+        }
+
+        let prefix = prefixed(self.layer, "");
+
+        let mut attributes: Vec<(Text, AttributeValue)> = self
+            .header
+            .all_named_attributes()
+            .filter_map(|(name, value)| {
+                let name = name.strip_prefix(prefix.as_slice())?;
+                if name.is_empty() || name.contains(&b'.') {
+                    return None;
+                }
+
+                Some((Text::from_slice_unchecked(name), value))
+            })
+            .collect();
+
+        attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        attributes
+    }
+
+    /// All channels of this layer, including the channels of nested layers.
+    /// For the layer `diffuse`, this contains `diffuse.R` and `diffuse.left.R`, but not `R`.
+    /// For an empty layer name, this contains all channels.
+    pub fn channels(&self) -> Vec<&'h ChannelDescription> {
+        let prefix = prefixed(self.layer, "");
+
+        self.header
+            .channels
+            .list
+            .iter()
+            .filter(|channel| self.layer.is_empty() || channel.name.as_slice().starts_with(&prefix))
+            .collect()
+    }
+    /// The attributes of the header as they apply to this layer alone, for writing the layer to its own part or file.
+    /// The prefixed attributes of this layer and its enclosing layers replace the unprefixed attributes,
+    /// and the prefixed attributes of all other layers are removed. The layer name is set to the name of this layer.
+    /// Prefixed attributes that have the name of a standard attribute, but a different type, are dropped.
+    pub fn extracted_attributes(&self) -> LayerAttributes {
+        let mut header = self.header.clone();
+
+        let other_layers: Vec<SmallVec<[u8; 24]>> = self
+            .header
+            .channel_layer_names()
+            .iter()
+            .map(|layer| prefixed(&layer.to_string(), ""))
+            .collect();
+
+        header.own_attributes.other.retain(|name, _| {
+            !other_layers
+                .iter()
+                .any(|prefix| name.as_slice().starts_with(prefix))
+        });
+
+        // apply the attributes of the outermost layer first, such that nested layers override them
+        let enclosing_layers = self
+            .layer
+            .match_indices('.')
+            .map(|(dot, _)| &self.layer[..dot])
+            .chain(std::iter::once(self.layer))
+            .filter(|layer| !layer.is_empty());
+
+        for layer in enclosing_layers {
+            for (name, value) in self.header.layer_scope(layer).own_attributes() {
+                header.set_named_attribute(name, value).ok();
+            }
+        }
+
+        if !self.layer.is_empty() {
+            header.own_attributes.layer_name = Some(Text::from(self.layer));
+        }
+
+        header.own_attributes
+    }
+}
+
+impl LayerAttributes {
+    /// Set an attribute that only applies to the specified layer inside this header,
+    /// by prefixing the attribute name with the layer name, for example `diffuse.blurAmount`.
+    /// Returns the previous value, if any.
+    pub fn insert_layer_attribute(
+        &mut self,
+        layer: &str,
+        name: &str,
+        value: AttributeValue,
+    ) -> Option<AttributeValue> {
+        let full_name = prefixed(layer.trim_end_matches('.'), name);
+        self.other
+            .insert(Text::from_bytes_unchecked(full_name), value)
+    }
+
+    /// Remove an attribute that only applies to the specified layer inside this header.
+    /// Returns the removed value, if any.
+    pub fn remove_layer_attribute(&mut self, layer: &str, name: &str) -> Option<AttributeValue> {
+        let full_name = prefixed(layer.trim_end_matches('.'), name);
+        self.other.remove(&Text::from_bytes_unchecked(full_name))
+    }
+}
+
+/// Joins the layer name and the attribute name with a dot.
+fn prefixed(layer: &str, name: &str) -> SmallVec<[u8; 24]> {
+    let mut bytes = SmallVec::with_capacity(layer.len() + name.len() + 1);
+    bytes.extend_from_slice(layer.as_bytes());
+    bytes.push(b'.');
+    bytes.extend_from_slice(name.as_bytes());
+    bytes
+}
+
+fn find_attribute(header: &Header, name: &[u8]) -> Option<AttributeValue> {
+    header
+        .all_named_attributes()
+        .find(|(attribute_name, _)| *attribute_name == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::attribute::SampleType;
+
+    fn header() -> Header {
+        let channels = ["R", "diffuse.R", "diffuse.left.R", "specular.R"]
+            .iter()
+            .map(|&name| ChannelDescription::named(name, SampleType::F16))
+            .collect();
+
+        let mut header = Header::new(Text::from("beauty"), (8, 8), channels);
+
+        let attributes = &mut header.own_attributes;
+        attributes
+            .other
+            .insert(Text::from("blurAmount"), AttributeValue::F32(1.0));
+        attributes.insert_layer_attribute("diffuse", "blurAmount", AttributeValue::F32(2.0));
+        attributes.insert_layer_attribute("diffuse.left", "gain", AttributeValue::F32(3.0));
+        header
+    }
+
+    #[test]
+    fn layer_names() {
+        let names: Vec<String> = header()
+            .channel_layer_names()
+            .iter()
+            .map(Text::to_string)
+            .collect();
+
+        assert_eq!(names, vec!["diffuse", "diffuse.left", "specular"]);
+    }
+
+    #[test]
+    fn attributes_are_inherited() {
+        let header = header();
+        let blur = |layer| header.layer_scope(layer).attribute("blurAmount");
+
+        assert_eq!(blur("diffuse"), Some(AttributeValue::F32(2.0)));
+        assert_eq!(blur("diffuse.left"), Some(AttributeValue::F32(2.0)));
+        assert_eq!(blur("specular"), Some(AttributeValue::F32(1.0)));
+        assert_eq!(blur(""), Some(AttributeValue::F32(1.0)));
+
+        assert_eq!(
+            header.layer_scope("diffuse.left").attribute("gain"),
+            Some(AttributeValue::F32(3.0))
+        );
+        assert_eq!(header.layer_scope("diffuse").attribute("gain"), None);
+    }
+
+    #[test]
+    fn own_attributes_and_channels() {
+        let header = header();
+        let diffuse = header.layer_scope("diffuse.");
+        assert_eq!(diffuse.layer(), "diffuse");
+
+        assert_eq!(
+            diffuse.own_attributes(),
+            vec![(Text::from("blurAmount"), AttributeValue::F32(2.0))]
+        );
+
+        let channels: Vec<String> = diffuse
+            .channels()
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+
+        assert_eq!(channels, vec!["diffuse.R", "diffuse.left.R"]);
+    }
+
+    #[test]
+    fn extracted_attributes() {
+        let header = header();
+
+        let left = header.layer_scope("diffuse.left").extracted_attributes();
+        assert_eq!(left.layer_name, Some(Text::from("diffuse.left")));
+        assert_eq!(
+            left.other.get(&Text::from("blurAmount")),
+            Some(&AttributeValue::F32(2.0))
+        );
+        assert_eq!(
+            left.other.get(&Text::from("gain")),
+            Some(&AttributeValue::F32(3.0))
+        );
+        assert_eq!(left.other.len(), 2);
+
+        let specular = header.layer_scope("specular").extracted_attributes();
+        assert_eq!(
+            specular.other.get(&Text::from("blurAmount")),
+            Some(&AttributeValue::F32(1.0))
+        );
+        assert_eq!(specular.other.len(), 1);
+    }
+}
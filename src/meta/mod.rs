@@ -3,7 +3,19 @@
 //! Browse the `exr::image` module to get started with the high-level interface.
 
 pub mod attribute;
+pub mod camera;
+pub mod describe;
+pub mod diff;
+pub mod edit;
+pub mod environment_map;
 pub mod header;
+pub mod layer_scope;
+pub mod multi_view;
+pub mod oiio;
+pub mod registry;
+pub mod screen_window;
+pub mod standard;
+pub mod warnings;
 
 use self::attribute::*;
 use crate::block::chunk::{CompressedBlock, TileCoordinates};
@@ -27,6 +39,7 @@ use std::io::BufReader;
 /// and various other attributes.
 /// The usage of custom attributes is encouraged.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MetaData {
     /// Some flags summarizing the features that must be supported to decode the file.
     pub requirements: Requirements,
@@ -59,6 +72,7 @@ pub type OffsetTable = Vec<u64>;
 /// Used to determine whether this file can be read by a given reader.
 /// It includes the OpenEXR version number. This library aims to support version `2.0`.
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Requirements {
     /// This library supports reading version 1 and 2, and writing version 2.
     // TODO write version 1 for simple images
@@ -92,6 +106,7 @@ pub struct TileIndices {
 
 /// How the image pixels are split up into separate blocks.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlockDescription {
     /// The image is divided into scan line blocks.
     /// The number of scan lines in a block depends on the compression method.
@@ -404,7 +419,7 @@ impl MetaData {
         read: &mut PeekRead<impl Read>,
         pedantic: bool,
     ) -> Result<Self> {
-        let meta_data = Self::read_unvalidated_from_buffered_peekable(read, !pedantic)?;
+        let meta_data = Self::read_unvalidated_from_buffered_peekable(read, pedantic)?;
         MetaData::validate(meta_data.headers.as_slice(), pedantic)?;
         Ok(meta_data)
     }
@@ -511,7 +526,8 @@ impl MetaData {
             // start as low as possible, later increasing if required
             has_long_names: false,
 
-            is_single_layer_and_tiled: !is_multilayer && first_header_has_tiles,
+            // deep tiles are declared by the block type attribute instead
+            is_single_layer_and_tiled: !is_multilayer && !deep && first_header_has_tiles,
             has_multiple_layers: is_multilayer,
             has_deep_data: deep,
         };
@@ -763,6 +779,19 @@ mod test {
         assert_eq!(meta, meta2);
     }
 
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let path = "tests/images/valid/openexr/MultiView/Balls.exr";
+        let meta = MetaData::read_from_file(path, false).unwrap();
+
+        let json = serde_json::to_string(&meta).unwrap();
+        assert!(json.contains("\"channels\""));
+
+        let deserialized: MetaData = serde_json::from_str(&json).unwrap();
+        assert_eq!(meta, deserialized);
+    }
+
     #[test]
     fn infer_low_requirements() {
         let header_version_1_short_names = Header {
@@ -0,0 +1,456 @@
+//! Helpers for multi-view images, such as stereo images, stored in a single layer.
+//! The `multiView` attribute lists the names of all views, the first one being the default view.
+//!
+//! Channels belong to a view according to the OpenEXR naming rules:
+//! A channel name without any dot, like `R`, belongs to the default view.
+//! Otherwise, the second to last part of the name is the view name, like `left.R` or `diffuse.right.G`.
+//! If the second to last part is not a view name, the channel does not belong to any view.
+//!
+//! Use `Layer::view` to extract the channels of one view from a decoded layer.
+
+use crate::error::*;
+use crate::image::{AnyChannel, AnyChannels, FlatSamples, Layer};
+use crate::meta::attribute::{ChannelDescription, ChannelList, Text};
+use crate::meta::header::LayerAttributes;
+use smallvec::SmallVec;
+
+/// The list of views in a multi-view layer, as stored in the `multiView` attribute.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MultiView {
+    views: Vec<Text>,
+}
+
+impl MultiView {
+    /// Create a list of views. The first view is the default view.
+    /// Returns an error if there are no views, if any name is empty or contains a dot,
+    /// or if any name appears twice.
+    pub fn new(views: impl IntoIterator<Item = impl Into<Text>>) -> Result<Self> {
+        let views: Vec<Text> = views.into_iter().map(Into::into).collect();
+
+        if views.is_empty() {
+            return Err(Error::invalid("multi view list must not be empty"));
+        }
+
+        for (index, view) in views.iter().enumerate() {
+            if view.as_slice().is_empty() || view.as_slice().contains(&b'.') {
+                return Err(Error::invalid(
+                    "view name must not be empty or contain a dot",
+                ));
+            }
+
+            if views[..index].contains(view) {
+                return Err(Error::invalid("duplicate view name"));
+            }
+        }
+
+        Ok(MultiView { views })
+    }
+
+    /// The common stereo views, `left` being the default view.
+    pub fn stereo() -> Self {
+        MultiView {
+            views: vec![Text::from("left"), Text::from("right")],
+        }
+    }
+
+    /// Parse the `multiView` attribute of a layer.
+    /// Returns `None` if the layer has no such attribute,
+    /// and an error if the attribute contains invalid view names.
+    pub fn from_attributes(attributes: &LayerAttributes) -> Option<Result<Self>> {
+        attributes
+            .multi_view_names
+            .as_ref()
+            .map(|views| Self::new(views.iter().cloned()))
+    }
+
+    /// All view names, starting with the default view.
+    pub fn views(&self) -> &[Text] {
+        &self.views
+    }
+
+    /// The view that channels without a view name belong to.
+    pub fn default_view(&self) -> &Text {
+        &self.views[0]
+    }
+
+    /// Whether this is one of the view names.
+    pub fn contains(&self, view: &Text) -> bool {
+        self.views.contains(view)
+    }
+
+    /// The value to store in the `multiView` attribute.
+    pub fn to_attribute(&self) -> Vec<Text> {
+        self.views.clone()
+    }
+
+    /// The view that a channel belongs to, or `None` if it belongs to no view.
+    pub fn view_of_channel(&self, channel_name: &Text) -> Option<&Text> {
+        let parts: SmallVec<[&[u8]; 4]> = channel_name
+            .as_slice()
+            .split(|&byte| byte == b'.')
+            .collect();
+
+        if parts.len() == 1 {
+            return Some(self.default_view());
+        }
+
+        let view = parts[parts.len() - 2];
+        self.views.iter().find(|name| name.as_slice() == view)
+    }
+
+    /// The views displayed to the left and the right eye.
+    /// Prefers views named `left` and `right`, and otherwise uses the first two views.
+    /// Both are the default view if there is only one view.
+    pub fn stereo_pair(&self) -> (&Text, &Text) {
+        let find = |name: &str| self.views.iter().find(|view| *view == name);
+        let left = find("left").unwrap_or(self.default_view());
+        let right = find("right")
+            .or_else(|| self.views.iter().find(|&view| view != left))
+            .unwrap_or(left);
+
+        (left, right)
+    }
+
+    /// The index of the channel in the view that has the specified name
+    /// after removing the view name, so `R` finds `right.R` in the `right` view.
+    pub fn position_in_view<'n>(
+        &self,
+        view: &Text,
+        name: &Text,
+        channel_names: impl IntoIterator<Item = &'n Text>,
+    ) -> Option<usize> {
+        channel_names.into_iter().position(|channel| {
+            self.view_of_channel(channel) == Some(view) && &remove_view_name(channel, view) == name
+        })
+    }
+
+    /// The channels that belong to the specified view.
+    pub fn channels_in_view<'c>(
+        &self,
+        view: &Text,
+        channels: &'c ChannelList,
+    ) -> Vec<&'c ChannelDescription> {
+        channels
+            .list
+            .iter()
+            .filter(|channel| self.view_of_channel(&channel.name) == Some(view))
+            .collect()
+    }
+
+    /// The channels that do not belong to any view, for example `depth.Z` in a `left`, `right` image.
+    pub fn channels_in_no_view<'c>(
+        &self,
+        channels: &'c ChannelList,
+    ) -> Vec<&'c ChannelDescription> {
+        channels
+            .list
+            .iter()
+            .filter(|channel| self.view_of_channel(&channel.name).is_none())
+            .collect()
+    }
+
+    /// Split the channels of a layer by view.
+    /// Returns the name of each view and its channels,
+    /// with the view name removed from the channel names, for example `right.R` becomes `R`.
+    /// Channels that belong to no view are not included.
+    pub fn split_channels(&self, channels: &ChannelList) -> Vec<(Text, Vec<ChannelDescription>)> {
+        self.views
+            .iter()
+            .map(|view| {
+                let view_channels = self
+                    .channels_in_view(view, channels)
+                    .into_iter()
+                    .map(|channel| ChannelDescription {
+                        name: remove_view_name(&channel.name, view),
+                        ..channel.clone()
+                    })
+                    .collect();
+
+                (view.clone(), view_channels)
+            })
+            .collect()
+    }
+
+    /// The name of a channel with the view name inserted, such that the channel belongs to that view.
+    /// Channels in the default view without a layer prefix keep their name, so `R` stays `R`.
+    /// Otherwise, the view name is inserted before the last part, so `diffuse.R` becomes `diffuse.right.R`.
+    /// Returns an error if the view is not in this list.
+    pub fn insert_view_name(&self, channel_name: &Text, view: &Text) -> Result<Text> {
+        if !self.contains(view) {
+            return Err(Error::invalid("unknown view name"));
+        }
+
+        let name = channel_name.as_slice();
+        let last_dot = name.iter().rposition(|&byte| byte == b'.');
+
+        if last_dot.is_none() && view == self.default_view() {
+            return Ok(channel_name.clone());
+        }
+
+        let (prefix, suffix) = match last_dot {
+            Some(index) => (&name[..=index], &name[index + 1..]),
+            None => (&name[..0], name),
+        };
+
+        let mut bytes = SmallVec::with_capacity(name.len() + view.as_slice().len() + 1);
+        bytes.extend_from_slice(prefix);
+        bytes.extend_from_slice(view.as_slice());
+        bytes.push(b'.');
+        bytes.extend_from_slice(suffix);
+        Ok(Text::from_bytes_unchecked(bytes))
+    }
+}
+
+/// The name of a channel with the specified view name removed, so `diffuse.right.R` becomes `diffuse.R`.
+/// Returns the name unchanged if the second to last part is not the specified view.
+pub fn remove_view_name(channel_name: &Text, view: &Text) -> Text {
+    let name = channel_name.as_slice();
+
+    let last_dot = match name.iter().rposition(|&byte| byte == b'.') {
+        Some(index) => index,
+        None => return channel_name.clone(),
+    };
+
+    let view_start = name[..last_dot]
+        .iter()
+        .rposition(|&byte| byte == b'.')
+        .map_or(0, |index| index + 1);
+
+    if &name[view_start..last_dot] != view.as_slice() {
+        return channel_name.clone();
+    }
+
+    let mut bytes = SmallVec::with_capacity(name.len());
+    bytes.extend_from_slice(&name[..view_start]);
+    bytes.extend_from_slice(&name[last_dot + 1..]);
+    Text::from_bytes_unchecked(bytes)
+}
+
+impl Layer<AnyChannels<FlatSamples>> {
+    /// The views of this layer, if it has a `multiView` attribute.
+    /// See `MultiView::from_attributes`.
+    pub fn multi_view(&self) -> Option<Result<MultiView>> {
+        MultiView::from_attributes(&self.attributes)
+    }
+
+    /// A copy of this layer with only the channels of one view,
+    /// with the view name removed from the channel names, so `right.R` becomes `R`.
+    /// The copy has no `multiView` attribute.
+    /// Returns an error if the layer has no `multiView` attribute, or if the view is not in it.
+    pub fn view(&self, view: &Text) -> Result<Self> {
+        let multi_view = self
+            .multi_view()
+            .ok_or_else(|| Error::invalid("layer without multi view attribute"))??;
+
+        if !multi_view.contains(view) {
+            return Err(Error::invalid("unknown view name"));
+        }
+
+        let list = self
+            .channel_data
+            .list
+            .iter()
+            .filter(|channel| multi_view.view_of_channel(&channel.name) == Some(view))
+            .map(|channel| AnyChannel {
+                name: remove_view_name(&channel.name, view),
+                ..channel.clone()
+            })
+            .collect();
+
+        let mut layer = Layer {
+            channel_data: AnyChannels::sort(list),
+            attributes: self.attributes.clone(),
+            size: self.size,
+            encoding: self.encoding,
+        };
+
+        layer.attributes.multi_view_names = None;
+        layer.attributes.view_name = Some(view.clone());
+        Ok(layer)
+    }
+}
+
+impl LayerAttributes {
+    /// Set the `multiView` attribute.
+    pub fn with_multi_view(self, multi_view: &MultiView) -> Self {
+        Self {
+            multi_view_names: Some(multi_view.to_attribute()),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::attribute::SampleType;
+
+    fn channel_list(names: &[&str]) -> ChannelList {
+        ChannelList::new(
+            names
+                .iter()
+                .map(|&name| ChannelDescription::named(name, SampleType::F16))
+                .collect(),
+        )
+    }
+
+    fn names(channels: &[&ChannelDescription]) -> Vec<String> {
+        channels
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn assign_channels_to_views() {
+        let views = MultiView::stereo();
+        let channels = channel_list(&[
+            "R",
+            "right.R",
+            "diffuse.G",
+            "diffuse.left.G",
+            "diffuse.right.G",
+            "Z",
+        ]);
+
+        let left = views.channels_in_view(&Text::from("left"), &channels);
+        let right = views.channels_in_view(&Text::from("right"), &channels);
+        let none = views.channels_in_no_view(&channels);
+
+        assert_eq!(names(&left), vec!["R", "diffuse.left.G", "Z"]);
+        assert_eq!(names(&right), vec!["right.R", "diffuse.right.G"]);
+        assert_eq!(names(&none), vec!["diffuse.G"]);
+    }
+
+    #[test]
+    fn split_and_merge_channel_names() {
+        let views = MultiView::stereo();
+        let left = Text::from("left");
+        let right = Text::from("right");
+
+        assert_eq!(
+            views.insert_view_name(&Text::from("R"), &left).unwrap(),
+            Text::from("R")
+        );
+        assert_eq!(
+            views.insert_view_name(&Text::from("R"), &right).unwrap(),
+            Text::from("right.R")
+        );
+        assert_eq!(
+            views
+                .insert_view_name(&Text::from("diffuse.R"), &left)
+                .unwrap(),
+            Text::from("diffuse.left.R")
+        );
+
+        assert!(views
+            .insert_view_name(&Text::from("R"), &Text::from("center"))
+            .is_err());
+
+        assert_eq!(
+            remove_view_name(&Text::from("diffuse.right.R"), &right),
+            Text::from("diffuse.R")
+        );
+        assert_eq!(
+            remove_view_name(&Text::from("right.R"), &right),
+            Text::from("R")
+        );
+        assert_eq!(
+            remove_view_name(&Text::from("left.R"), &right),
+            Text::from("left.R")
+        );
+
+        let channels = channel_list(&["R", "right.R", "Z"]);
+        let split = views.split_channels(&channels);
+        assert_eq!(split[1].0, right);
+        assert_eq!(split[1].1[0].name, Text::from("R"));
+    }
+
+    #[test]
+    fn validate_view_names() {
+        assert!(MultiView::new(Vec::<Text>::new()).is_err());
+        assert!(MultiView::new(vec!["left", "left"]).is_err());
+        assert!(MultiView::new(vec!["left.eye"]).is_err());
+        assert_eq!(
+            MultiView::new(vec!["center"]).unwrap().default_view(),
+            &Text::from("center")
+        );
+    }
+
+    #[test]
+    fn stereo_pairs() {
+        let names = |views: &MultiView| {
+            let (left, right) = views.stereo_pair();
+            (left.to_string(), right.to_string())
+        };
+
+        let adjuster = MultiView::new(vec!["center", "left", "right"]).unwrap();
+        assert_eq!(names(&adjuster), ("left".into(), "right".into()));
+
+        let other = MultiView::new(vec!["main", "second"]).unwrap();
+        assert_eq!(names(&other), ("main".into(), "second".into()));
+
+        let single = MultiView::new(vec!["center"]).unwrap();
+        assert_eq!(names(&single), ("center".into(), "center".into()));
+
+        let channels = channel_list(&["R", "right.R", "right.G"]);
+        let names = channels.list.iter().map(|channel| &channel.name);
+        let right = Text::from("right");
+        assert_eq!(
+            MultiView::stereo().position_in_view(&right, &Text::from("G"), names),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn extract_view_layer() {
+        use crate::image::Encoding;
+        use crate::math::Vec2;
+
+        let channels = AnyChannels::sort(smallvec::smallvec![
+            AnyChannel::new("R", FlatSamples::F32(vec![1.0])),
+            AnyChannel::new("right.R", FlatSamples::F32(vec![2.0])),
+            AnyChannel::new("depth.Z", FlatSamples::F32(vec![3.0])),
+        ]);
+
+        let attributes = LayerAttributes::default().with_multi_view(&MultiView::stereo());
+        let layer = Layer::new(Vec2(1, 1), attributes, Encoding::default(), channels);
+
+        let right = layer.view(&Text::from("right")).unwrap();
+        assert_eq!(right.channel_data.list.len(), 1);
+        assert_eq!(right.channel_data.list[0].name, Text::from("R"));
+        assert_eq!(
+            right.channel_data.list[0].sample_data,
+            FlatSamples::F32(vec![2.0])
+        );
+        assert_eq!(right.attributes.view_name, Some(Text::from("right")));
+
+        assert!(layer.view(&Text::from("center")).is_err());
+    }
+
+    #[test]
+    fn views_of_stereo_file() {
+        use crate::prelude::*;
+
+        let image =
+            read_all_flat_layers_from_file("tests/images/valid/openexr/MultiView/Adjuster.exr")
+                .unwrap();
+
+        let layer = &image.layer_data[0];
+        let views = layer.multi_view().unwrap().unwrap();
+        assert_eq!(views.default_view(), &Text::from("center"));
+
+        let (left, right) = views.stereo_pair();
+        for view in [left, right] {
+            let view_layer = layer.view(view).unwrap();
+            let names: Vec<String> = view_layer
+                .channel_data
+                .list
+                .iter()
+                .map(|channel| channel.name.to_string())
+                .collect();
+
+            assert_eq!(names, vec!["B", "G", "R"]);
+        }
+    }
+}
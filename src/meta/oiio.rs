@@ -0,0 +1,198 @@
+//! Translate attribute names between exr files and the metadata conventions of OpenImageIO.
+//! OpenImageIO renames some standard exr attributes, for example `comments` to `ImageDescription`,
+//! and prefixes format-specific attributes with `openexr:`. Using the same names
+//! allows metadata to survive round-trips through OpenImageIO-based tools.
+//!
+//! Attributes without a mapping keep their name in both directions,
+//! so that custom attributes such as `oiio:ColorSpace` or `Exif:LensModel` are preserved verbatim.
+//! Attribute values are not converted, except for numbers that are stored into float attributes.
+
+use crate::error::*;
+use crate::meta::attribute::{type_names, AttributeValue, Text, TextSlice};
+use crate::meta::edit::{standard_attribute_type, PIXEL_LAYOUT_ATTRIBUTES};
+use crate::meta::header::standard_names::DISPLAY_WINDOW;
+use crate::meta::header::Header;
+
+/// The prefix that OpenImageIO uses for attributes that only exist in exr files.
+pub const OPENEXR_PREFIX: &str = "openexr:";
+
+/// Pairs of exr attribute names and their OpenImageIO metadata names.
+/// Used in both directions.
+pub const NAME_MAPPING: &[(&str, &str)] = &[
+    ("name", "oiio:subimagename"),
+    ("comments", "ImageDescription"),
+    ("owner", "Copyright"),
+    ("capDate", "DateTime"),
+    ("software", "Software"),
+    ("pixelAspectRatio", "PixelAspectRatio"),
+    ("xDensity", "XResolution"),
+    ("expTime", "ExposureTime"),
+    ("aperture", "FNumber"),
+    ("isoSpeed", "Exif:ISOSpeedRatings"),
+    ("focus", "Exif:SubjectDistance"),
+    ("worldToCamera", "worldtocamera"),
+    ("worldToNDC", "worldtoscreen"),
+    ("framesPerSecond", "FramesPerSecond"),
+    ("timeCode", "smpte:TimeCode"),
+    ("keyCode", "smpte:KeyCode"),
+];
+
+/// Further OpenImageIO names that are only used when converting to exr names,
+/// because the same exr attribute already has a preferred name in `NAME_MAPPING`.
+pub const OIIO_ALIASES: &[(&str, &str)] = &[
+    ("worldtoNDC", "worldToNDC"),
+    ("Exif:ExposureTime", "expTime"),
+    ("Exif:FNumber", "aperture"),
+    ("Exif:DateTimeOriginal", "capDate"),
+    ("Exif:PhotographicSensitivity", "isoSpeed"),
+];
+
+/// The OpenImageIO metadata name of an exr attribute.
+/// Attributes without a mapping keep their name.
+pub fn oiio_name(exr_name: &TextSlice) -> String {
+    let exr_name = String::from_utf8_lossy(exr_name);
+
+    NAME_MAPPING
+        .iter()
+        .find(|(exr, _)| *exr == exr_name)
+        .map(|&(_, oiio)| oiio.to_string())
+        .unwrap_or_else(|| exr_name.into_owned())
+}
+
+/// The exr attribute name of an OpenImageIO metadata name.
+/// Removes the `openexr:` prefix, and keeps other names without a mapping.
+/// Returns an error if the name contains characters that cannot be stored in an exr file.
+pub fn exr_name(oiio_name: &str) -> Result<Text> {
+    let name = NAME_MAPPING
+        .iter()
+        .find(|(_, oiio)| *oiio == oiio_name)
+        .map(|&(exr, _)| exr)
+        .or_else(|| {
+            OIIO_ALIASES
+                .iter()
+                .find(|(oiio, _)| *oiio == oiio_name)
+                .map(|&(_, exr)| exr)
+        })
+        .unwrap_or_else(|| oiio_name.strip_prefix(OPENEXR_PREFIX).unwrap_or(oiio_name));
+
+    Text::new_or_none(name).ok_or_else(|| {
+        Error::invalid(format!(
+            "metadata name `{}` cannot be stored in an exr file",
+            oiio_name
+        ))
+    })
+}
+
+impl Header {
+    /// All attributes of this header, named like OpenImageIO names its metadata.
+    /// Excludes the attributes that describe the pixel layout, such as `channels` or `compression`,
+    /// and the display window, because OpenImageIO stores those in the image specification instead.
+    pub fn oiio_metadata(&self) -> Vec<(String, AttributeValue)> {
+        let mut metadata: Vec<(String, AttributeValue)> = self
+            .all_named_attributes()
+            .filter(|(name, _)| !PIXEL_LAYOUT_ATTRIBUTES.contains(name) && *name != DISPLAY_WINDOW)
+            .map(|(name, value)| (oiio_name(name), value))
+            .collect();
+
+        metadata.sort_by(|(a, _), (b, _)| a.cmp(b));
+        metadata
+    }
+
+    /// Set an attribute by its OpenImageIO metadata name. Standard attributes are stored in their fields.
+    /// Integers and doubles are converted to floats where the exr attribute requires a float,
+    /// as OpenImageIO stores `Exif:ISOSpeedRatings`, for example, as an integer.
+    /// Returns an error for attributes that describe the pixel layout,
+    /// and for values that do not match the type of a standard attribute.
+    pub fn set_oiio_attribute(&mut self, oiio_name: &str, value: AttributeValue) -> UnitResult {
+        let name = exr_name(oiio_name)?;
+
+        let value = match (standard_attribute_type(name.as_slice()), value) {
+            (Some(type_names::F32), AttributeValue::I32(value)) => {
+                AttributeValue::F32(value as f32)
+            }
+            (Some(type_names::F32), AttributeValue::F64(value)) => {
+                AttributeValue::F32(value as f32)
+            }
+            (_, value) => value,
+        };
+
+        self.set_named_attribute(name, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn map_names_in_both_directions() {
+        for &(exr, oiio) in NAME_MAPPING {
+            assert_eq!(oiio_name(exr.as_bytes()), oiio);
+            assert_eq!(exr_name(oiio).unwrap(), Text::from(exr));
+        }
+
+        assert_eq!(oiio_name(b"shotName"), "shotName");
+        assert_eq!(exr_name("shotName").unwrap(), Text::from("shotName"));
+        assert_eq!(
+            exr_name("openexr:utcOffset").unwrap(),
+            Text::from("utcOffset")
+        );
+        assert_eq!(exr_name("Exif:FNumber").unwrap(), Text::from("aperture"));
+        assert_eq!(
+            exr_name("oiio:ColorSpace").unwrap(),
+            Text::from("oiio:ColorSpace")
+        );
+    }
+
+    #[test]
+    fn round_trip_through_oiio_names() {
+        let mut header = Header::new("beauty".into(), (4, 4), smallvec::smallvec![]);
+        header.own_attributes.comments = Some("a comment".into());
+        header.own_attributes.owner = Some("studio".into());
+        header.own_attributes.iso_speed = Some(400.0);
+        header
+            .set_named_attribute("shotName".into(), AttributeValue::Text("sh010".into()))
+            .unwrap();
+
+        let metadata = header.oiio_metadata();
+        let find = |name: &str| {
+            metadata
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        assert_eq!(
+            find("ImageDescription"),
+            Some(AttributeValue::Text("a comment".into()))
+        );
+        assert_eq!(
+            find("Copyright"),
+            Some(AttributeValue::Text("studio".into()))
+        );
+        assert_eq!(
+            find("oiio:subimagename"),
+            Some(AttributeValue::Text("beauty".into()))
+        );
+        assert_eq!(find("shotName"), Some(AttributeValue::Text("sh010".into())));
+        assert_eq!(find("compression"), None);
+        assert_eq!(find("displayWindow"), None);
+
+        let mut copy = Header::new("other".into(), (4, 4), smallvec::smallvec![]);
+        for (name, value) in metadata {
+            copy.set_oiio_attribute(&name, value).unwrap();
+        }
+
+        assert_eq!(copy.own_attributes, header.own_attributes);
+
+        // integers from exif are converted to float attributes
+        copy.set_oiio_attribute("Exif:ISOSpeedRatings", AttributeValue::I32(800))
+            .unwrap();
+        assert_eq!(copy.own_attributes.iso_speed, Some(800.0));
+
+        assert!(copy
+            .set_oiio_attribute("compression", AttributeValue::Compression(Compression::RLE))
+            .is_err());
+    }
+}
@@ -0,0 +1,352 @@
+//! Decode custom attribute types into typed values, and encode typed values into attributes.
+//! Applications can register a codec for each of their own attribute type names.
+//! The attributes of a header always stay `AttributeValue::Custom` with their original bytes,
+//! such that unknown types are preserved verbatim. The registry looks up the codec by the type name
+//! of such an attribute to decode it on demand.
+
+use crate::error::*;
+use crate::meta::attribute::{type_names, AttributeValue, Text, TextSlice};
+use crate::meta::header::Header;
+use crate::meta::MetaData;
+use smallvec::SmallVec;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+type DecodeFn = dyn Fn(&[u8]) -> Result<Box<dyn Any + Send + Sync>> + Send + Sync;
+type EncodeFn = dyn Fn(&(dyn Any + Send + Sync)) -> Option<Vec<u8>> + Send + Sync;
+
+/// Contains the codecs of custom attribute types, identified by their type name.
+/// Register a codec with `AttributeRegistry::register`,
+/// then use `AttributeRegistry::decode` or similar methods after reading a file,
+/// and `AttributeRegistry::encode` to create attributes before writing a file.
+#[derive(Clone, Default)]
+pub struct AttributeRegistry {
+    codecs: HashMap<Text, Codec>,
+}
+
+/// The decoder and encoder of a single custom attribute type.
+#[derive(Clone)]
+struct Codec {
+    decode: Arc<DecodeFn>,
+    encode: Arc<EncodeFn>,
+}
+
+/// A custom attribute value decoded by an `AttributeRegistry`.
+/// Use `RegisteredValue::downcast_ref` to access the typed value.
+#[derive(Clone)]
+pub struct RegisteredValue {
+    kind: Text,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+impl AttributeRegistry {
+    /// Create a registry without any codecs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a codec for the custom attribute type with the specified name.
+    /// The decoder receives the raw little-endian bytes of the attribute value,
+    /// and the encoder produces these bytes from a typed value.
+    /// Use the `exr::io::Data` trait to read and write binary values.
+    /// Replaces any codec previously registered for this type name.
+    /// Returns an error if the type name is one of the types defined by the OpenEXR standard.
+    pub fn register<T: Any + Send + Sync>(
+        &mut self,
+        type_name: impl Into<Text>,
+        decode: impl Fn(&[u8]) -> Result<T> + Send + Sync + 'static,
+        encode: impl Fn(&T) -> Vec<u8> + Send + Sync + 'static,
+    ) -> UnitResult {
+        let type_name = type_name.into();
+
+        if type_names::ALL.contains(&type_name.as_slice()) {
+            return Err(Error::invalid(format!(
+                "attribute type `{}` is a standard type and cannot be registered",
+                type_name
+            )));
+        }
+
+        let decode: Arc<DecodeFn> = Arc::new(move |bytes: &[u8]| {
+            decode(bytes).map(|value| Box::new(value) as Box<dyn Any + Send + Sync>)
+        });
+
+        let encode: Arc<EncodeFn> =
+            Arc::new(move |value: &(dyn Any + Send + Sync)| value.downcast_ref::<T>().map(&encode));
+
+        self.codecs.insert(type_name, Codec { decode, encode });
+        Ok(())
+    }
+
+    /// Whether a codec has been registered for this type name.
+    pub fn is_registered(&self, type_name: &TextSlice) -> bool {
+        self.codecs.contains_key(type_name)
+    }
+
+    /// Create a custom attribute value from a typed value, using the encoder registered for the type name.
+    /// Returns an error if no codec has been registered for this type name,
+    /// or if the codec was registered for a different rust type.
+    pub fn encode<T: Any + Send + Sync>(
+        &self,
+        type_name: impl Into<Text>,
+        value: &T,
+    ) -> Result<AttributeValue> {
+        let kind = type_name.into();
+
+        let codec = self.codecs.get(&kind).ok_or_else(|| {
+            Error::invalid(format!("attribute type `{}` is not registered", kind))
+        })?;
+
+        let bytes = (codec.encode)(value).ok_or_else(|| {
+            Error::invalid(format!(
+                "value does not match the registered attribute type `{}`",
+                kind
+            ))
+        })?;
+
+        Ok(AttributeValue::Custom {
+            kind,
+            bytes: SmallVec::from_vec(bytes),
+        })
+    }
+
+    /// Decode a single attribute value, if it is a custom attribute whose type has been registered.
+    /// Returns `None` if it is not a custom attribute or if its type is unknown.
+    /// Returns an error if the codec cannot decode the bytes. The attribute itself is never modified.
+    pub fn decode_value(&self, value: &AttributeValue) -> Result<Option<RegisteredValue>> {
+        match value {
+            AttributeValue::Custom { kind, bytes } => match self.codecs.get(kind) {
+                Some(codec) => Ok(Some(RegisteredValue {
+                    kind: kind.clone(),
+                    value: Arc::from((codec.decode)(bytes.as_slice())?),
+                })),
+
+                None => Ok(None),
+            },
+
+            _ => Ok(None),
+        }
+    }
+
+    /// Decode a single attribute value into the specified type.
+    /// Returns `None` if it is not a custom attribute, if its type is unknown,
+    /// or if its type was registered with a different rust type.
+    /// Returns an error if the codec cannot decode the bytes.
+    pub fn decode<T: Any>(&self, value: &AttributeValue) -> Result<Option<T>> {
+        match value {
+            AttributeValue::Custom { kind, bytes } => match self.codecs.get(kind) {
+                Some(codec) => {
+                    let decoded = (codec.decode)(bytes.as_slice())?;
+                    Ok(decoded.downcast::<T>().ok().map(|value| *value))
+                }
+
+                None => Ok(None),
+            },
+
+            _ => Ok(None),
+        }
+    }
+
+    /// Decode all custom attributes in these attributes whose types have been registered.
+    /// If `pedantic`, aborts on the first attribute that cannot be decoded,
+    /// otherwise skips attributes that cannot be decoded.
+    /// The attributes themselves are never modified.
+    pub fn decode_attributes(
+        &self,
+        attributes: &HashMap<Text, AttributeValue>,
+        pedantic: bool,
+    ) -> Result<HashMap<Text, RegisteredValue>> {
+        let mut decoded = HashMap::new();
+
+        for (name, value) in attributes {
+            match self.decode_value(value) {
+                Ok(Some(value)) => {
+                    decoded.insert(name.clone(), value);
+                }
+
+                Ok(None) => {}
+                Err(error) if pedantic => return Err(error),
+                Err(_) => {}
+            }
+        }
+
+        Ok(decoded)
+    }
+
+    /// Decode the custom attributes of a header, both shared and layer attributes,
+    /// see `AttributeRegistry::decode_attributes`.
+    pub fn decode_header(
+        &self,
+        header: &Header,
+        pedantic: bool,
+    ) -> Result<HashMap<Text, RegisteredValue>> {
+        let mut decoded = self.decode_attributes(&header.shared_attributes.other, pedantic)?;
+        decoded.extend(self.decode_attributes(&header.own_attributes.other, pedantic)?);
+        Ok(decoded)
+    }
+
+    /// Decode the custom attributes of all headers, see `AttributeRegistry::decode_header`.
+    pub fn decode_meta_data(
+        &self,
+        meta_data: &MetaData,
+        pedantic: bool,
+    ) -> Result<Vec<HashMap<Text, RegisteredValue>>> {
+        meta_data
+            .headers
+            .iter()
+            .map(|header| self.decode_header(header, pedantic))
+            .collect()
+    }
+}
+
+impl RegisteredValue {
+    /// The name of the custom type.
+    pub fn kind(&self) -> &Text {
+        &self.kind
+    }
+
+    /// Return the typed value, if it has the requested type.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref::<T>()
+    }
+}
+
+impl Debug for RegisteredValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RegisteredValue({})", self.kind)
+    }
+}
+
+impl Debug for AttributeRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.codecs.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::io::Data;
+
+    #[derive(Debug, PartialEq)]
+    struct LensInfo {
+        focal_length: f32,
+        serial: i32,
+    }
+
+    fn registry() -> AttributeRegistry {
+        let mut registry = AttributeRegistry::new();
+
+        registry
+            .register(
+                "studioLens",
+                |mut bytes: &[u8]| {
+                    Ok(LensInfo {
+                        focal_length: f32::read_le(&mut bytes)?,
+                        serial: i32::read_le(&mut bytes)?,
+                    })
+                },
+                encode_lens,
+            )
+            .unwrap();
+
+        registry
+    }
+
+    fn encode_lens(lens: &LensInfo) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        lens.focal_length.write_le(&mut bytes).unwrap();
+        lens.serial.write_le(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decodes_registered_types_and_preserves_unknown_types() {
+        let registry = registry();
+        let lens = LensInfo {
+            focal_length: 35.0,
+            serial: 7,
+        };
+
+        let encoded = registry.encode("studioLens", &lens).unwrap();
+        assert_eq!(
+            encoded,
+            AttributeValue::Custom {
+                kind: Text::from("studioLens"),
+                bytes: SmallVec::from_vec(encode_lens(&lens)),
+            }
+        );
+
+        let unknown = AttributeValue::Custom {
+            kind: Text::from("somethingElse"),
+            bytes: smallvec![1, 2, 3],
+        };
+
+        let mut attributes = HashMap::new();
+        attributes.insert(Text::from("lens"), encoded.clone());
+        attributes.insert(Text::from("other"), unknown.clone());
+        let original = attributes.clone();
+
+        let decoded = registry.decode_attributes(&attributes, true).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(
+            decoded[&Text::from("lens")].kind(),
+            &Text::from("studioLens")
+        );
+        assert_eq!(
+            decoded[&Text::from("lens")].downcast_ref::<LensInfo>(),
+            Some(&lens)
+        );
+
+        // decoding does not change the attributes
+        assert_eq!(attributes, original);
+
+        assert_eq!(registry.decode::<LensInfo>(&encoded).unwrap(), Some(lens));
+        assert_eq!(registry.decode::<u8>(&encoded).unwrap(), None);
+        assert_eq!(registry.decode::<LensInfo>(&unknown).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_bytes_are_skipped_unless_pedantic() {
+        let registry = registry();
+        let broken = AttributeValue::Custom {
+            kind: Text::from("studioLens"),
+            bytes: smallvec![1],
+        };
+
+        assert!(registry.decode::<LensInfo>(&broken).is_err());
+
+        let mut attributes = HashMap::new();
+        attributes.insert(Text::from("lens"), broken);
+        assert!(registry.decode_attributes(&attributes, true).is_err());
+        assert!(registry
+            .decode_attributes(&attributes, false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn standard_types_cannot_be_registered() {
+        let mut registry = AttributeRegistry::new();
+        assert!(registry
+            .register("float", |_| Ok(0_u8), |value| vec![*value])
+            .is_err());
+    }
+
+    #[test]
+    fn encode_requires_registered_type() {
+        let registry = registry();
+        let lens = LensInfo {
+            focal_length: 50.0,
+            serial: 3,
+        };
+
+        let value = registry.encode("studioLens", &lens).unwrap();
+        assert_eq!(value.kind_name(), b"studioLens");
+        assert_eq!(value.byte_size(), 8);
+
+        assert!(registry.encode("otherLens", &lens).is_err());
+        assert!(registry.encode("studioLens", &5_u8).is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//! Relate the screen space of a perspective projection to the pixels of an image.
+//! The `screenWindowCenter` and `screenWindowWidth` attributes define which part of the
+//! screen space is covered by the display window, and `xDensity` defines the size of the image when printed.
+//!
+//! Raster coordinates are continuous pixel coordinates: the display window `x` range `min .. max`
+//! covers the raster interval `min .. max + 1`, such that the center of a pixel is at `pixel + 0.5`.
+//! The screen space `y` axis points up, while the raster `y` axis points down.
+
+use crate::math::Vec2;
+use crate::meta::attribute::{FloatRect, IntegerBounds, Matrix3x3};
+use crate::meta::header::{Header, LayerAttributes};
+
+/// The rectangle in screen space that corresponds to the display window.
+/// Only the width is stored, the height follows from the display window size and the pixel aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenWindow {
+    /// The center of the screen window, stored in the `screenWindowCenter` attribute.
+    pub center: Vec2<f32>,
+
+    /// The width of the screen window, stored in the `screenWindowWidth` attribute.
+    pub width: f32,
+}
+
+impl Default for ScreenWindow {
+    fn default() -> Self {
+        ScreenWindow {
+            center: Vec2(0.0, 0.0),
+            width: 1.0,
+        }
+    }
+}
+
+impl ScreenWindow {
+    /// Read the screen window attributes.
+    pub fn from_attributes(attributes: &LayerAttributes) -> Self {
+        ScreenWindow {
+            center: attributes.screen_window_center,
+            width: attributes.screen_window_width,
+        }
+    }
+
+    /// Write the screen window attributes.
+    pub fn apply_to(&self, attributes: &mut LayerAttributes) {
+        attributes.screen_window_center = self.center;
+        attributes.screen_window_width = self.width;
+    }
+
+    /// Compute the screen window of a camera with the specified film back, where the image plane is at distance `1`.
+    /// The focal length, the horizontal aperture, and the film offset must use the same unit, typically millimeters.
+    /// This is the screen window to use when matching a plate shot with this camera to rendered images.
+    pub fn from_film_back(
+        focal_length: f32,
+        horizontal_aperture: f32,
+        film_offset: Vec2<f32>,
+    ) -> Self {
+        ScreenWindow {
+            center: Vec2(
+                film_offset.x() / focal_length,
+                film_offset.y() / focal_length,
+            ),
+            width: horizontal_aperture / focal_length,
+        }
+    }
+
+    /// The horizontal field of view in degrees, assuming the image plane is at distance `1`.
+    pub fn horizontal_field_of_view(&self) -> f32 {
+        2.0 * (self.width * 0.5).atan().to_degrees()
+    }
+
+    /// The height of the screen window, which depends on the shape of the display window and its pixels.
+    pub fn height(&self, display_size: Vec2<usize>, pixel_aspect: f32) -> f32 {
+        let (width, height) = (display_size.width() as f32, display_size.height() as f32);
+        self.width * height / (width * pixel_aspect)
+    }
+
+    /// The screen space rectangle covered by the display window.
+    pub fn bounds(&self, display_size: Vec2<usize>, pixel_aspect: f32) -> FloatRect {
+        let half_size = Vec2(self.width, self.height(display_size, pixel_aspect)) * Vec2(0.5, 0.5);
+
+        FloatRect {
+            min: self.center - half_size,
+            max: self.center + half_size,
+        }
+    }
+
+    /// The matrix that transforms homogeneous screen space row vectors `[x, y, 1]` to raster coordinates.
+    pub fn screen_to_raster_matrix(
+        &self,
+        display_window: IntegerBounds,
+        pixel_aspect: f32,
+    ) -> Matrix3x3 {
+        let size = display_window.size;
+        let bounds = self.bounds(size, pixel_aspect);
+        let screen_size = bounds.max - bounds.min;
+
+        let scale_x = size.width() as f32 / screen_size.x();
+        let scale_y = -(size.height() as f32) / screen_size.y();
+        let offset_x = display_window.position.x() as f32 - bounds.min.x() * scale_x;
+        let offset_y = display_window.position.y() as f32 - bounds.max.y() * scale_y;
+
+        #[rustfmt::skip]
+        let matrix = [
+            scale_x, 0.0, 0.0,
+            0.0, scale_y, 0.0,
+            offset_x, offset_y, 1.0,
+        ];
+
+        matrix
+    }
+
+    /// Transform a screen space position to raster coordinates.
+    pub fn screen_to_raster(
+        &self,
+        screen: Vec2<f32>,
+        display_window: IntegerBounds,
+        pixel_aspect: f32,
+    ) -> Vec2<f32> {
+        let matrix = self.screen_to_raster_matrix(display_window, pixel_aspect);
+        Vec2(
+            screen.x() * matrix[0] + matrix[6],
+            screen.y() * matrix[4] + matrix[7],
+        )
+    }
+
+    /// Transform raster coordinates to a screen space position.
+    pub fn raster_to_screen(
+        &self,
+        raster: Vec2<f32>,
+        display_window: IntegerBounds,
+        pixel_aspect: f32,
+    ) -> Vec2<f32> {
+        let matrix = self.screen_to_raster_matrix(display_window, pixel_aspect);
+        Vec2(
+            (raster.x() - matrix[6]) / matrix[0],
+            (raster.y() - matrix[7]) / matrix[4],
+        )
+    }
+}
+
+impl Header {
+    /// The screen window of this layer.
+    pub fn screen_window(&self) -> ScreenWindow {
+        ScreenWindow::from_attributes(&self.own_attributes)
+    }
+
+    /// Transform a screen space position, as computed by the `worldToNDC` matrix, to raster coordinates of the display window.
+    pub fn screen_to_raster(&self, screen: Vec2<f32>) -> Vec2<f32> {
+        let shared = &self.shared_attributes;
+        self.screen_window()
+            .screen_to_raster(screen, shared.display_window, shared.pixel_aspect)
+    }
+
+    /// Transform raster coordinates of the display window to a screen space position.
+    pub fn raster_to_screen(&self, raster: Vec2<f32>) -> Vec2<f32> {
+        let shared = &self.shared_attributes;
+        self.screen_window()
+            .raster_to_screen(raster, shared.display_window, shared.pixel_aspect)
+    }
+
+    /// The horizontal and vertical output density, in pixels per inch.
+    /// Returns `None` if the `xDensity` attribute is not present.
+    pub fn pixels_per_inch(&self) -> Option<Vec2<f32>> {
+        let horizontal = self.own_attributes.horizontal_density?;
+        Some(Vec2(
+            horizontal,
+            horizontal * self.shared_attributes.pixel_aspect,
+        ))
+    }
+
+    /// The size of the display window when printed, in inches.
+    /// Returns `None` if the `xDensity` attribute is not present.
+    pub fn print_size_in_inches(&self) -> Option<Vec2<f32>> {
+        let density = self.pixels_per_inch()?;
+        let size = self.shared_attributes.display_window.size;
+        Some(Vec2(
+            size.width() as f32 / density.x(),
+            size.height() as f32 / density.y(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(a: Vec2<f32>, b: Vec2<f32>) {
+        assert!(
+            (a.x() - b.x()).abs() < 1e-4 && (a.y() - b.y()).abs() < 1e-4,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn default_screen_window_covers_display_window() {
+        let display_window = IntegerBounds::new((10, 20), (200, 100));
+        let window = ScreenWindow {
+            center: Vec2(0.0, 0.0),
+            width: 2.0,
+        };
+
+        // the screen window is 2 units wide and 1 unit high
+        assert_eq!(window.height(display_window.size, 1.0), 1.0);
+
+        assert_close(
+            window.screen_to_raster(Vec2(-1.0, 0.5), display_window, 1.0),
+            Vec2(10.0, 20.0),
+        );
+        assert_close(
+            window.screen_to_raster(Vec2(1.0, -0.5), display_window, 1.0),
+            Vec2(210.0, 120.0),
+        );
+        assert_close(
+            window.screen_to_raster(Vec2(0.0, 0.0), display_window, 1.0),
+            Vec2(110.0, 70.0),
+        );
+    }
+
+    #[test]
+    fn raster_to_screen_inverts_screen_to_raster() {
+        let display_window = IntegerBounds::new((0, 0), (64, 48));
+        let window = ScreenWindow {
+            center: Vec2(0.25, -0.5),
+            width: 1.5,
+        };
+
+        let screen = Vec2(0.3, 0.1);
+        let raster = window.screen_to_raster(screen, display_window, 2.0);
+        assert_close(window.raster_to_screen(raster, display_window, 2.0), screen);
+    }
+
+    #[test]
+    fn film_back() {
+        let window = ScreenWindow::from_film_back(35.0, 36.0, Vec2(0.0, 0.0));
+        assert!((window.horizontal_field_of_view() - 54.43).abs() < 0.01);
+    }
+
+    #[test]
+    fn print_size() {
+        let mut header = Header::new("layer".into(), (300, 150), smallvec::smallvec![]);
+
+        assert_eq!(header.print_size_in_inches(), None);
+
+        header.own_attributes.horizontal_density = Some(100.0);
+        header.shared_attributes.pixel_aspect = 0.5;
+        assert_eq!(header.pixels_per_inch(), Some(Vec2(100.0, 50.0)));
+        assert_eq!(header.print_size_in_inches(), Some(Vec2(3.0, 3.0)));
+    }
+}
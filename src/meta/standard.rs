@@ -0,0 +1,440 @@
+//! Typed access to the optional standard attributes of a layer.
+//! Avoids manufacturing raw attribute values with possibly misspelled names.
+
+use crate::error::*;
+use crate::meta::attribute::{KeyCode, Rational, Text};
+use crate::meta::header::LayerAttributes;
+use std::fmt::{Display, Formatter};
+
+/// A view into the optional standard attributes of a layer,
+/// with typed getters and setters that convert from and to plain rust types.
+/// Obtain one using `LayerAttributes::standard_attributes`.
+/// Setters return the view again, so that multiple calls can be chained.
+#[derive(Debug)]
+pub struct StandardAttributes<'l> {
+    attributes: &'l mut LayerAttributes,
+}
+
+/// The date and local time of image creation, as stored in the `capDate` attribute.
+/// The attribute is stored as text in the format `YYYY:MM:DD hh:mm:ss`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CaptureDate {
+    /// The year, for example `2024`.
+    pub year: u16,
+
+    /// The month, from `1` to `12`.
+    pub month: u8,
+
+    /// The day of the month, from `1` to `31`.
+    pub day: u8,
+
+    /// The hour of the day, from `0` to `23`.
+    pub hour: u8,
+
+    /// The minute, from `0` to `59`.
+    pub minute: u8,
+
+    /// The second, from `0` to `59`.
+    pub second: u8,
+}
+
+/// How a texture is extrapolated beyond its edges, as stored in the `wrapmodes` attribute.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum WrapMode {
+    /// Pixels outside the image are black.
+    Black,
+
+    /// Pixels outside the image repeat the nearest edge pixel.
+    Clamp,
+
+    /// The image repeats endlessly.
+    Periodic,
+
+    /// The image repeats endlessly, with every other repetition mirrored.
+    Mirror,
+}
+
+/// The horizontal and vertical wrap mode of a texture.
+/// The `wrapmodes` attribute is stored as text, for example `clamp` or `periodic,clamp`,
+/// where a single mode applies to both directions.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct WrapModes {
+    /// How the texture is extrapolated to the left and right.
+    pub horizontal: WrapMode,
+
+    /// How the texture is extrapolated to the top and bottom.
+    pub vertical: WrapMode,
+}
+
+impl LayerAttributes {
+    /// Access the optional standard attributes of this layer through typed getters and setters.
+    pub fn standard_attributes(&mut self) -> StandardAttributes<'_> {
+        StandardAttributes { attributes: self }
+    }
+}
+
+macro_rules! text_attribute {
+    ( $( $field: ident, $setter: ident, $name: literal; )* ) => { $(
+        #[doc = concat!("The `", $name, "` attribute, if present.")]
+        pub fn $field(&self) -> Option<String> {
+            self.attributes.$field.as_ref().map(Text::to_string)
+        }
+
+        #[doc = concat!("Set or remove the `", $name, "` attribute.")]
+        #[doc = "Returns an error if the text contains characters that cannot be stored in an exr file."]
+        pub fn $setter(&mut self, value: Option<&str>) -> Result<&mut Self> {
+            self.attributes.$field = match value {
+                Some(text) => Some(Text::new_or_none(text).ok_or_else(|| {
+                    Error::invalid(concat!("unsupported characters in `", $name, "` attribute"))
+                })?),
+                None => None,
+            };
+
+            Ok(self)
+        }
+    )* };
+}
+
+macro_rules! float_attribute {
+    ( $( $field: ident, $setter: ident, $name: literal, $unit: literal; )* ) => { $(
+        #[doc = concat!("The `", $name, "` attribute, ", $unit, ", if present.")]
+        pub fn $field(&self) -> Option<f32> {
+            self.attributes.$field
+        }
+
+        #[doc = concat!("Set or remove the `", $name, "` attribute, ", $unit, ".")]
+        pub fn $setter(&mut self, value: Option<f32>) -> &mut Self {
+            self.attributes.$field = value;
+            self
+        }
+    )* };
+}
+
+impl StandardAttributes<'_> {
+    text_attribute! {
+        owner, set_owner, "owner";
+        comments, set_comments, "comments";
+        software_name, set_software_name, "software";
+        view_name, set_view_name, "view";
+        wrap_mode_name, set_wrap_mode_name, "wrapmodes";
+    }
+
+    float_attribute! {
+        utc_offset, set_utc_offset, "utcOffset", "in seconds";
+        longitude, set_longitude, "longitude", "in degrees east of Greenwich";
+        latitude, set_latitude, "latitude", "in degrees north of the equator";
+        altitude, set_altitude, "altitude", "in meters above sea level";
+        focus, set_focus, "focus", "in meters";
+        exposure, set_exposure, "expTime", "in seconds";
+        aperture, set_aperture, "aperture", "in f-stops";
+        iso_speed, set_iso_speed, "isoSpeed", "as ISO speed rating";
+        white_luminance, set_white_luminance, "whiteLuminance", "in candelas per square meter";
+        horizontal_density, set_horizontal_density, "xDensity", "in pixels per inch";
+    }
+
+    /// The `capDate` attribute, if present and valid.
+    /// Returns `Some(Err(_))` if the attribute does not follow the `YYYY:MM:DD hh:mm:ss` format.
+    pub fn capture_date(&self) -> Option<Result<CaptureDate>> {
+        self.attributes
+            .capture_date
+            .as_ref()
+            .map(|text| CaptureDate::parse(&text.to_string()))
+    }
+
+    /// Set or remove the `capDate` attribute.
+    pub fn set_capture_date(&mut self, value: Option<CaptureDate>) -> &mut Self {
+        self.attributes.capture_date = value.map(|date| Text::from(date.to_string().as_str()));
+        self
+    }
+
+    /// The `wrapmodes` attribute, if present and valid.
+    /// Returns `Some(Err(_))` if the attribute contains an unknown wrap mode.
+    pub fn wrap_modes(&self) -> Option<Result<WrapModes>> {
+        self.attributes
+            .wrap_mode_name
+            .as_ref()
+            .map(|text| WrapModes::parse(&text.to_string()))
+    }
+
+    /// Set or remove the `wrapmodes` attribute.
+    pub fn set_wrap_modes(&mut self, value: Option<WrapModes>) -> &mut Self {
+        self.attributes.wrap_mode_name = value.map(|modes| Text::from(modes.to_string().as_str()));
+        self
+    }
+
+    /// The `keyCode` attribute, if present.
+    pub fn film_key_code(&self) -> Option<KeyCode> {
+        self.attributes.film_key_code
+    }
+
+    /// Set or remove the `keyCode` attribute.
+    /// Returns an error if the key code contains a value that is out of range.
+    pub fn set_film_key_code(&mut self, value: Option<KeyCode>) -> Result<&mut Self> {
+        if let Some(key_code) = value {
+            key_code.validate(true)?;
+        }
+
+        self.attributes.film_key_code = value;
+        Ok(self)
+    }
+
+    /// The `framesPerSecond` attribute as a floating point number, if present and valid.
+    pub fn frames_per_second(&self) -> Option<f64> {
+        self.attributes
+            .frames_per_second
+            .filter(|&(_, denominator)| denominator != 0)
+            .map(|(numerator, denominator)| numerator as f64 / denominator as f64)
+    }
+
+    /// The exact `framesPerSecond` attribute, as numerator and denominator, if present.
+    pub fn frames_per_second_rational(&self) -> Option<Rational> {
+        self.attributes.frames_per_second
+    }
+
+    /// Set or remove the exact `framesPerSecond` attribute, for example `(24000, 1001)` for `23.976` fps.
+    pub fn set_frames_per_second_rational(&mut self, value: Option<Rational>) -> &mut Self {
+        self.attributes.frames_per_second = value;
+        self
+    }
+
+    /// Set the `framesPerSecond` attribute from a floating point number.
+    /// Recognizes the common NTSC rates, such as `23.976` or `29.97`,
+    /// and stores them as the exact rational number, for example `24000/1001`.
+    /// Returns an error if the value is not positive or not finite.
+    pub fn set_frames_per_second(&mut self, value: f64) -> Result<&mut Self> {
+        if !value.is_finite() || value <= 0.0 || value > i32::MAX as f64 {
+            return Err(Error::invalid("frames per second"));
+        }
+
+        let ntsc = (value * 1.001).round();
+        let rational =
+            if (value - ntsc / 1.001).abs() < 0.001 && (value.round() - value).abs() > 0.001 {
+                (ntsc as i32 * 1000, 1001)
+            } else if (value.round() - value).abs() < 1.0e-6 {
+                (value.round() as i32, 1)
+            } else {
+                ((value * 1000.0).round() as i32, 1000)
+            };
+
+        self.attributes.frames_per_second = Some(rational);
+        Ok(self)
+    }
+}
+
+impl CaptureDate {
+    /// Parse a date in the `YYYY:MM:DD hh:mm:ss` format.
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = || Error::invalid("capture date format");
+
+        let (date, time) = text
+            .trim()
+            .split_at(text.trim().find(' ').ok_or_else(invalid)?);
+        let mut date = date.split(':').map(str::parse::<u16>);
+        let mut time = time.trim().split(':').map(str::parse::<u8>);
+
+        let mut next_date = || date.next().ok_or_else(invalid)?.map_err(|_| invalid());
+        let (year, month, day) = (next_date()?, next_date()?, next_date()?);
+
+        let mut next_time = || time.next().ok_or_else(invalid)?.map_err(|_| invalid());
+        let (hour, minute, second) = (next_time()?, next_time()?, next_time()?);
+
+        if month > 255 || day > 255 {
+            return Err(invalid());
+        }
+
+        let date = CaptureDate {
+            year,
+            month: month as u8,
+            day: day as u8,
+            hour,
+            minute,
+            second,
+        };
+
+        date.validate()?;
+        Ok(date)
+    }
+
+    /// Returns an error if any of the fields is out of range.
+    pub fn validate(&self) -> UnitResult {
+        if !(1..=12).contains(&self.month)
+            || !(1..=31).contains(&self.day)
+            || self.hour > 23
+            || self.minute > 59
+            || self.second > 59
+        {
+            Err(Error::invalid("capture date value"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Display for CaptureDate {
+    /// Formats the date as `YYYY:MM:DD hh:mm:ss`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+impl WrapMode {
+    /// The name of this mode in the `wrapmodes` attribute.
+    pub fn name(self) -> &'static str {
+        match self {
+            WrapMode::Black => "black",
+            WrapMode::Clamp => "clamp",
+            WrapMode::Periodic => "periodic",
+            WrapMode::Mirror => "mirror",
+        }
+    }
+
+    /// Parse the name of a mode, ignoring case and surrounding whitespace.
+    pub fn parse(text: &str) -> Result<Self> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "black" => Ok(WrapMode::Black),
+            "clamp" => Ok(WrapMode::Clamp),
+            "periodic" => Ok(WrapMode::Periodic),
+            "mirror" => Ok(WrapMode::Mirror),
+            _ => Err(Error::invalid("wrap mode")),
+        }
+    }
+}
+
+impl WrapModes {
+    /// Use the same mode in both directions.
+    pub fn uniform(mode: WrapMode) -> Self {
+        WrapModes {
+            horizontal: mode,
+            vertical: mode,
+        }
+    }
+
+    /// Parse either a single mode, like `clamp`, or a horizontal and vertical mode, like `periodic,clamp`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut modes = text.split(',').map(WrapMode::parse);
+        let horizontal = modes.next().ok_or_else(|| Error::invalid("wrap mode"))??;
+        let vertical = modes.next().transpose()?.unwrap_or(horizontal);
+
+        if modes.next().is_some() {
+            return Err(Error::invalid("wrap mode count"));
+        }
+
+        Ok(WrapModes {
+            horizontal,
+            vertical,
+        })
+    }
+}
+
+impl Display for WrapModes {
+    /// Formats a single mode if both modes are equal, and `horizontal,vertical` otherwise.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.horizontal == self.vertical {
+            write!(f, "{}", self.horizontal.name())
+        } else {
+            write!(f, "{},{}", self.horizontal.name(), self.vertical.name())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn setters_and_getters() {
+        let mut attributes = LayerAttributes::default();
+
+        attributes
+            .standard_attributes()
+            .set_owner(Some("studio"))
+            .unwrap()
+            .set_aperture(Some(2.8))
+            .set_iso_speed(Some(800.0));
+
+        assert_eq!(attributes.owner, Some(Text::from("studio")));
+        assert_eq!(attributes.aperture, Some(2.8));
+
+        let mut standard = attributes.standard_attributes();
+        assert_eq!(standard.owner().as_deref(), Some("studio"));
+        assert_eq!(standard.iso_speed(), Some(800.0));
+
+        standard.set_owner(None).unwrap();
+        assert_eq!(standard.owner(), None);
+        assert!(standard.set_comments(Some("日本")).is_err());
+    }
+
+    #[test]
+    fn frames_per_second() {
+        let mut attributes = LayerAttributes::default();
+        let mut standard = attributes.standard_attributes();
+
+        standard.set_frames_per_second(23.976).unwrap();
+        assert_eq!(standard.frames_per_second_rational(), Some((24000, 1001)));
+
+        standard.set_frames_per_second(25.0).unwrap();
+        assert_eq!(standard.frames_per_second_rational(), Some((25, 1)));
+        assert_eq!(standard.frames_per_second(), Some(25.0));
+
+        standard.set_frames_per_second(12.5).unwrap();
+        assert_eq!(standard.frames_per_second_rational(), Some((12500, 1000)));
+
+        assert!(standard.set_frames_per_second(-1.0).is_err());
+    }
+
+    #[test]
+    fn capture_date_roundtrip() {
+        let date = CaptureDate::parse("2024:02:29 13:05:59").unwrap();
+        assert_eq!(date.year, 2024);
+        assert_eq!(date.minute, 5);
+        assert_eq!(date.to_string(), "2024:02:29 13:05:59");
+
+        assert!(CaptureDate::parse("2024-02-29 13:05:59").is_err());
+        assert!(CaptureDate::parse("2024:13:01 00:00:00").is_err());
+
+        let mut attributes = LayerAttributes::default();
+        attributes
+            .standard_attributes()
+            .set_capture_date(Some(date));
+        assert_eq!(
+            attributes
+                .standard_attributes()
+                .capture_date()
+                .unwrap()
+                .unwrap(),
+            date
+        );
+    }
+
+    #[test]
+    fn wrap_modes_roundtrip() {
+        let modes = WrapModes::parse("periodic, Clamp").unwrap();
+        assert_eq!(modes.horizontal, WrapMode::Periodic);
+        assert_eq!(modes.vertical, WrapMode::Clamp);
+        assert_eq!(modes.to_string(), "periodic,clamp");
+
+        assert_eq!(
+            WrapModes::parse("mirror").unwrap(),
+            WrapModes::uniform(WrapMode::Mirror)
+        );
+        assert!(WrapModes::parse("repeat").is_err());
+        assert!(WrapModes::parse("clamp,clamp,clamp").is_err());
+
+        let mut attributes = LayerAttributes::default();
+        attributes
+            .standard_attributes()
+            .set_wrap_modes(Some(WrapModes::uniform(WrapMode::Black)));
+        assert_eq!(attributes.wrap_mode_name, Some(Text::from("black")));
+
+        attributes.wrap_mode_name = Some(Text::from("sideways"));
+        assert!(attributes
+            .standard_attributes()
+            .wrap_modes()
+            .unwrap()
+            .is_err());
+    }
+}
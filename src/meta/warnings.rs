@@ -0,0 +1,285 @@
+//! Detect attributes that other exr readers may reject or misinterpret.
+//! These problems do not prevent this library from reading or writing the file,
+//! so they are reported as warnings in lenient mode, and as errors in pedantic mode.
+
+use crate::error::*;
+use crate::meta::attribute::Text;
+use crate::meta::header::{standard_names, Header};
+use crate::meta::multi_view::MultiView;
+use crate::meta::{MetaData, Requirements};
+use std::fmt::{Display, Formatter};
+
+/// The maximum length of attribute names, in bytes, if the file has the long names flag.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// A problem in the meta data of one layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationWarning {
+    /// The index of the header containing the problem.
+    pub layer_index: usize,
+
+    /// The name of the attribute containing the problem, if the problem is about a single attribute.
+    pub attribute: Option<Text>,
+
+    /// What exactly is wrong.
+    pub kind: WarningKind,
+}
+
+/// The different problems that validation can detect.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarningKind {
+    /// The attribute name is empty, or contains bytes that are not printable ascii characters.
+    IllegalNameCharacters,
+
+    /// The attribute name is longer than any exr file allows.
+    NameTooLong {
+        /// The number of bytes in the name.
+        length: usize,
+    },
+
+    /// A custom attribute uses the name of a standard attribute,
+    /// but does not have the type of that standard attribute.
+    ReservedName,
+
+    /// The pixel aspect ratio is negative, zero, or not a finite number.
+    PixelAspectRatio(f32),
+
+    /// The screen window width is negative or not a finite number.
+    ScreenWindowWidth(f32),
+
+    /// The layer does not contain any channels.
+    EmptyChannelList,
+
+    /// The `multiView` attribute contains invalid view names.
+    InvalidViews,
+
+    /// A view of the `multiView` attribute does not contain any channels.
+    EmptyView(Text),
+}
+
+impl MetaData {
+    /// Validates the meta data, like `MetaData::validate`, and additionally looks for suspicious attributes.
+    /// If pedantic, any warning is returned as an error.
+    /// Otherwise, the warnings are returned alongside the minimal requirements.
+    pub fn validate_with_warnings(
+        headers: &[Header],
+        pedantic: bool,
+    ) -> Result<(Requirements, Vec<ValidationWarning>)> {
+        let requirements = MetaData::validate(headers, pedantic)?;
+        let warnings = validation_warnings(headers);
+
+        match warnings.first() {
+            Some(warning) if pedantic => Err(Error::invalid(warning.to_string())),
+            _ => Ok((requirements, warnings)),
+        }
+    }
+}
+
+/// Look for suspicious attributes in all headers, without failing.
+pub fn validation_warnings(headers: &[Header]) -> Vec<ValidationWarning> {
+    headers
+        .iter()
+        .enumerate()
+        .flat_map(|(index, header)| header.validation_warnings(index))
+        .collect()
+}
+
+impl Header {
+    /// Look for suspicious attributes in this header, without failing.
+    /// The layer index is only used to identify this header in the returned warnings.
+    pub fn validation_warnings(&self, layer_index: usize) -> Vec<ValidationWarning> {
+        let mut warnings = Vec::new();
+
+        let mut warn = |attribute: Option<&[u8]>, kind: WarningKind| {
+            warnings.push(ValidationWarning {
+                layer_index,
+                attribute: attribute.map(Text::from_slice_unchecked),
+                kind,
+            })
+        };
+
+        let custom = self
+            .shared_attributes
+            .other
+            .iter()
+            .chain(self.own_attributes.other.iter());
+
+        for (name, _) in custom {
+            if let Some(kind) = name_warning(name.as_slice()) {
+                warn(Some(name.as_slice()), kind);
+            }
+
+            // standard attributes with an unexpected type are stored as custom attributes
+            if standard_names::ALL.contains(&name.as_slice()) {
+                warn(Some(name.as_slice()), WarningKind::ReservedName);
+            }
+        }
+
+        let pixel_aspect = self.shared_attributes.pixel_aspect;
+        if !pixel_aspect.is_finite() || pixel_aspect <= 0.0 {
+            warn(
+                Some(standard_names::PIXEL_ASPECT),
+                WarningKind::PixelAspectRatio(pixel_aspect),
+            );
+        }
+
+        let screen_window_width = self.own_attributes.screen_window_width;
+        if !screen_window_width.is_finite() || screen_window_width < 0.0 {
+            warn(
+                Some(standard_names::WINDOW_WIDTH),
+                WarningKind::ScreenWindowWidth(screen_window_width),
+            );
+        }
+
+        if self.channels.list.is_empty() {
+            warn(
+                Some(standard_names::CHANNELS),
+                WarningKind::EmptyChannelList,
+            );
+        }
+
+        match MultiView::from_attributes(&self.own_attributes) {
+            None => {}
+            Some(Err(_)) => warn(Some(standard_names::MULTI_VIEW), WarningKind::InvalidViews),
+            Some(Ok(views)) => {
+                for view in views.views() {
+                    if views.channels_in_view(view, &self.channels).is_empty() {
+                        warn(
+                            Some(standard_names::MULTI_VIEW),
+                            WarningKind::EmptyView(view.clone()),
+                        );
+                    }
+                }
+            }
+        }
+
+        warnings
+    }
+}
+
+/// Checks the characters and the length of an attribute name.
+fn name_warning(name: &[u8]) -> Option<WarningKind> {
+    if name.len() > MAX_NAME_LENGTH {
+        Some(WarningKind::NameTooLong { length: name.len() })
+    } else if name.is_empty() || !name.iter().all(|&byte| byte.is_ascii_graphic()) {
+        Some(WarningKind::IllegalNameCharacters)
+    } else {
+        None
+    }
+}
+
+impl Display for ValidationWarning {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "layer #{}", self.layer_index)?;
+
+        if let Some(attribute) = &self.attribute {
+            write!(formatter, ", attribute `{}`", attribute)?;
+        }
+
+        write!(formatter, ": {}", self.kind)
+    }
+}
+
+impl Display for WarningKind {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WarningKind::IllegalNameCharacters => {
+                write!(
+                    formatter,
+                    "name must only contain printable ascii characters"
+                )
+            }
+            WarningKind::NameTooLong { length } => write!(
+                formatter,
+                "name has {} bytes, but at most {} are allowed",
+                length, MAX_NAME_LENGTH
+            ),
+            WarningKind::ReservedName => write!(
+                formatter,
+                "name is reserved for a standard attribute of a different type"
+            ),
+            WarningKind::PixelAspectRatio(value) => {
+                write!(formatter, "pixel aspect ratio {} is not positive", value)
+            }
+            WarningKind::ScreenWindowWidth(value) => {
+                write!(formatter, "screen window width {} is negative", value)
+            }
+            WarningKind::EmptyChannelList => write!(formatter, "layer contains no channels"),
+            WarningKind::InvalidViews => write!(formatter, "view names are invalid"),
+            WarningKind::EmptyView(view) => {
+                write!(formatter, "view `{}` contains no channels", view)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::attribute::{AttributeValue, ChannelDescription, SampleType};
+    use crate::meta::header::LayerAttributes;
+
+    fn header() -> Header {
+        Header::new(
+            Text::from("layer"),
+            (8, 8),
+            smallvec::smallvec![ChannelDescription::named("R", SampleType::F16)],
+        )
+    }
+
+    fn kinds(header: &Header) -> Vec<WarningKind> {
+        header
+            .validation_warnings(0)
+            .into_iter()
+            .map(|warning| warning.kind)
+            .collect()
+    }
+
+    #[test]
+    fn valid_header_has_no_warnings() {
+        assert!(kinds(&header()).is_empty());
+        assert!(MetaData::validate_with_warnings(&[header()], true).is_ok());
+    }
+
+    #[test]
+    fn suspicious_names() {
+        let mut header = header();
+        let other = &mut header.own_attributes.other;
+        other.insert(Text::from("has space"), AttributeValue::I32(0));
+        other.insert(Text::from("owner"), AttributeValue::I32(0));
+
+        let kinds = kinds(&header);
+        assert!(kinds.contains(&WarningKind::IllegalNameCharacters));
+        assert!(kinds.contains(&WarningKind::ReservedName));
+    }
+
+    #[test]
+    fn suspicious_values() {
+        let mut header = header();
+        header.shared_attributes.pixel_aspect = -1.0;
+        header.own_attributes = LayerAttributes {
+            multi_view_names: Some(MultiView::stereo().to_attribute()),
+            ..header.own_attributes
+        };
+
+        let kinds = kinds(&header);
+        assert!(kinds.contains(&WarningKind::PixelAspectRatio(-1.0)));
+        assert!(kinds.contains(&WarningKind::EmptyView(Text::from("right"))));
+        assert!(!kinds.contains(&WarningKind::EmptyView(Text::from("left"))));
+    }
+
+    #[test]
+    fn warnings_are_errors_when_pedantic() {
+        let mut header = header();
+        header
+            .own_attributes
+            .other
+            .insert(Text::from("has space"), AttributeValue::I32(0));
+
+        let (_, warnings) = MetaData::validate_with_warnings(&[header.clone()], false).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].attribute, Some(Text::from("has space")));
+
+        assert!(MetaData::validate_with_warnings(&[header], true).is_err());
+    }
+}
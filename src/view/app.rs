@@ -1,18 +1,31 @@
 //! Main viewer application with egui.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
 use egui::{Color32, ColorImage, TextureHandle, TextureOptions, Vec2};
 
+use crate::block::cancel::CancellationToken;
+use crate::export::BitDepth;
+use crate::meta::attribute::IntegerBounds;
+use crate::view::colormap::{self, FALSE_COLOR_BANDS};
+use crate::view::display::{DisplayDevice, InputSpace, ViewTransform};
 use crate::view::handler::ViewerHandler;
+use crate::view::ipc;
 use crate::view::messages::{Generation, ViewerEvent, ViewerMsg};
+use crate::view::motion::{self, MotionField};
+use crate::view::panorama::PanoramaCamera;
+use crate::view::scopes::{Histogram, ScopeMode, Waveform, WAVEFORM_BINS};
+use crate::view::settings::{Action, Settings};
 use crate::view::state::{
-    ChannelMode, DeepMode, DepthMode, View3DMode, ViewerState,
+    Background, ChannelMode, CompareMode, DeepMode, DeepSampleInfo, DepthMode, DisplayMode, PixelInfo,
+    StereoMode, View3DMode, ViewerState,
 };
 
+#[cfg(feature = "view-gpu")]
+use crate::view::gpu::{self, DisplayUniforms, GpuImage};
 #[cfg(feature = "view-3d")]
 use crate::view::view3d::View3D;
 
@@ -32,6 +45,12 @@ pub enum DockTab {
 pub struct ViewerConfig {
     /// Verbosity level (0 = quiet).
     pub verbose: u8,
+
+    /// Accept commands from external tools on this address, like `127.0.0.1:14158`.
+    pub listen: Option<String>,
+
+    /// Start with the default settings, and do not save the settings on exit.
+    pub ignore_settings: bool,
 }
 
 /// Main viewer application.
@@ -41,8 +60,27 @@ pub struct ViewerApp {
     _worker: JoinHandle<()>,
 
     texture: Option<TextureHandle>,
+    /// The size of the image displayed by the shader, instead of the texture.
+    float_image_size: Option<Vec2>,
+    /// The size of the image while the texture is only a preview with a lower resolution.
+    preview_size: Option<Vec2>,
+    /// The shader for exposure and channel changes without a new texture.
+    #[cfg(feature = "view-gpu")]
+    gpu: Option<Arc<Mutex<GpuImage>>>,
+    /// The waveform or parade scope, rendered from the counts of the worker.
+    scope_texture: Option<TextureHandle>,
+    /// The vectors of the motion vector display mode, for the arrow overlay.
+    motion_field: Option<MotionField>,
     state: ViewerState,
     generation: Generation,
+
+    /// Cancelled to stop loading the current file, then replaced for the next load.
+    loading: CancellationToken,
+
+    /// The settings that persist between sessions, updated from the state when saved.
+    settings: Settings,
+    /// Whether the settings are saved on exit.
+    save_settings: bool,
     
     #[cfg(feature = "view-3d")]
     view3d: Option<Arc<Mutex<View3D>>>,
@@ -71,6 +109,24 @@ impl ViewerApp {
         let (tx_to_ui, rx_from_worker) = channel();
 
         let verbose = config.verbose;
+
+        let settings = if config.ignore_settings {
+            Settings::default()
+        } else {
+            Settings::load()
+        };
+
+        let mut state = ViewerState::default();
+        state.exposure = settings.exposure;
+        state.color = settings.color;
+        state.alpha_display.background = settings.background;
+
+        if let Some(address) = &config.listen {
+            if let Err(e) = ipc::spawn_server(address, tx_to_worker.clone(), verbose) {
+                state.error = Some(format!("Cannot listen on {address}: {e}"));
+            }
+        }
+
         let worker = thread::spawn(move || {
             let handler = ViewerHandler::new(rx_in_worker, tx_to_ui, verbose);
             handler.run();
@@ -80,6 +136,18 @@ impl ViewerApp {
         #[cfg(feature = "view-3d")]
         let view3d = cc.gl.as_ref().map(|gl| Arc::new(Mutex::new(View3D::new(gl.clone()))));
         
+        // Display float images with a shader, if the context supports it
+        #[cfg(feature = "view-gpu")]
+        let gpu = cc.gl.as_ref().and_then(|gl| match GpuImage::new(gl.clone()) {
+            Ok(gpu) => Some(Arc::new(Mutex::new(gpu))),
+            Err(e) => {
+                if verbose > 0 {
+                    eprintln!("Displaying without shader: {e}");
+                }
+                None
+            }
+        });
+
         // Init dock state - just 2D view by default
         #[cfg(feature = "view-3d")]
         let dock_state = DockState::new(vec![DockTab::View2D]);
@@ -89,14 +157,35 @@ impl ViewerApp {
             rx: rx_from_worker,
             _worker: worker,
             texture: None,
-            state: ViewerState::default(),
+            float_image_size: None,
+            preview_size: None,
+            #[cfg(feature = "view-gpu")]
+            gpu,
+            scope_texture: None,
+            motion_field: None,
+            state,
             generation: 0,
+            loading: CancellationToken::new(),
+            settings,
+            save_settings: !config.ignore_settings,
             #[cfg(feature = "view-3d")]
             view3d,
             #[cfg(feature = "view-3d")]
             dock_state,
         };
 
+        app.send(ViewerMsg::SetLoadCancellation(app.loading.clone()));
+        #[cfg(feature = "view-gpu")]
+        let gpu_display = app.gpu.is_some();
+        #[cfg(not(feature = "view-gpu"))]
+        let gpu_display = false;
+        app.send(ViewerMsg::SetGpuDisplay(gpu_display));
+
+        // restore the display settings of the last session before loading the image
+        app.send(ViewerMsg::SetExposure(app.state.exposure));
+        app.send(ViewerMsg::SetColorSettings(app.state.color));
+        app.send(ViewerMsg::SetBackground(app.state.alpha_display.background));
+
         if let Some(path) = image_path {
             app.send(ViewerMsg::LoadImage(path));
         }
@@ -131,11 +220,23 @@ impl ViewerApp {
             }
             ViewerMsg::SetPointSize(size) => {
                 self.state.point_size = *size;
+                if let Some(view3d_arc) = &self.view3d {
+                    if let Ok(mut view3d) = view3d_arc.lock() {
+                        view3d.set_point_size(*size);
+                    }
+                }
             }
             _ => {}
         }
     }
 
+    /// Stop loading the current file, and keep displaying the previous image.
+    fn cancel_loading(&mut self) {
+        self.loading.cancel();
+        self.loading = CancellationToken::new();
+        self.send(ViewerMsg::SetLoadCancellation(self.loading.clone()));
+    }
+
     fn send_regen(&mut self, msg: ViewerMsg) {
         self.generation += 1;
         self.send(ViewerMsg::SyncGeneration(self.generation));
@@ -143,38 +244,304 @@ impl ViewerApp {
     }
 
     fn open_file_dialog(&mut self) {
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("EXR", &["exr"])
-            .add_filter("All", &["*"])
-            .pick_file()
-        {
+        if let Some(path) = pick_exr_file(self.settings.last_directory.as_deref()) {
             self.send(ViewerMsg::LoadImage(path));
         }
     }
 
+    /// Pick the second image, to compare the image with.
+    fn open_file_dialog_b(&mut self) {
+        if let Some(path) = pick_exr_file(self.settings.last_directory.as_deref()) {
+            self.send(ViewerMsg::LoadImageB(path));
+        }
+    }
+
+    /// Pick the file to save the displayed image to.
+    fn export_file_dialog(&mut self, bit_depth: BitDepth) {
+        let path = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .add_filter("TIFF", &["tif", "tiff"])
+            .add_filter("Radiance HDR", &["hdr"])
+            .save_file();
+
+        if let Some(path) = path {
+            self.send(ViewerMsg::ExportImage { path, bit_depth });
+        }
+    }
+
+    /// Pick the file to save the deep samples to.
+    fn export_points_dialog(&mut self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("PLY", &["ply"])
+            .add_filter("OBJ", &["obj"])
+            .save_file();
+
+        if let Some(path) = path {
+            self.send(ViewerMsg::ExportPointCloud(path));
+        }
+    }
+
+    /// Pick a LUT file to replace the display transform with.
+    fn load_lut_dialog(&mut self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("LUT", &["cube", "spi1d", "spi3d"])
+            .pick_file();
+
+        if let Some(path) = path {
+            self.send_regen(ViewerMsg::LoadLut(path));
+        }
+    }
+
+    /// Pick a 1D LUT for the LUT display mode.
+    fn load_colormap_dialog(&mut self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("1D LUT", &["cube", "txt"])
+            .pick_file();
+
+        if let Some(path) = path {
+            self.send_regen(ViewerMsg::LoadColormap(path));
+        }
+    }
+
+    /// The colors of the display mode, computed like the colors of the image.
+    fn draw_display_legend(&mut self, ui: &mut egui::Ui) {
+        match self.state.display_mode {
+            DisplayMode::Normal => {}
+            DisplayMode::FalseColor => {
+                for band in FALSE_COLOR_BANDS {
+                    let [r, g, b] = band.color;
+                    let range = match (band.min_stops.is_finite(), band.max_stops.is_finite()) {
+                        (false, _) => format!("< {:+} EV", band.max_stops),
+                        (_, false) => format!(">= {:+} EV", band.min_stops),
+                        _ => format!("{:+} to {:+} EV", band.min_stops, band.max_stops),
+                    };
+
+                    ui.colored_label(Color32::from_rgb(r, g, b), "■")
+                        .on_hover_text(range);
+                    ui.label(band.label);
+                }
+                ui.label("(relative to 18% gray, gray between bands)");
+            }
+            DisplayMode::Turbo => draw_gradient(ui, "Turbo", colormap::turbo),
+            DisplayMode::Viridis => draw_gradient(ui, "Viridis", colormap::viridis),
+            DisplayMode::Lut => {
+                if let Some(lut) = &self.state.colormap {
+                    draw_gradient(ui, &lut.name, |t| lut.sample(t));
+                }
+            }
+            DisplayMode::MotionVectors => {
+                let Some(field) = &self.motion_field else {
+                    ui.label("No motion vector channels, like velocity.x and velocity.y");
+                    return;
+                };
+
+                let directions = [
+                    ("→", [1.0, 0.0]),
+                    ("↓", [0.0, 1.0]),
+                    ("←", [-1.0, 0.0]),
+                    ("↑", [0.0, -1.0]),
+                ];
+                for (arrow, vector) in directions {
+                    let color = motion::motion_color(vector, 1.0);
+                    let [r, g, b] = color.map(|value| (value * 255.0) as u8);
+                    ui.colored_label(Color32::from_rgb(r, g, b), arrow);
+                }
+                ui.label(format!("(brightness up to {:.2} pixels)", field.max_magnitude));
+                ui.checkbox(&mut self.state.show_motion_arrows, "Arrows")
+                    .on_hover_text("Draw the direction and relative length of the vectors");
+            }
+        }
+    }
+
+    /// Pick an OpenColorIO config to replace the display transform with.
+    fn load_ocio_dialog(&mut self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("OCIO config", &["ocio"])
+            .pick_file();
+
+        if let Some(path) = path {
+            self.send_regen(ViewerMsg::LoadOcioConfig(path));
+        }
+    }
+
+    /// The display transform: the built-in color spaces and views,
+    /// or the displays and views of a loaded OpenColorIO config.
+    fn color_menu(&mut self, ui: &mut egui::Ui) {
+        let mut color = self.state.color;
+
+        if let Some(name) = &self.state.custom_transform {
+            ui.label(format!("Custom: {name}"));
+        } else {
+            egui::ComboBox::from_label("Input")
+                .selected_text(color.input.label())
+                .show_ui(ui, |ui| {
+                    for &input in InputSpace::all() {
+                        ui.selectable_value(&mut color.input, input, input.label());
+                    }
+                });
+
+            egui::ComboBox::from_label("View")
+                .selected_text(color.view.label())
+                .show_ui(ui, |ui| {
+                    for &view in ViewTransform::all() {
+                        ui.selectable_value(&mut color.view, view, view.label());
+                    }
+                });
+
+            egui::ComboBox::from_label("Display")
+                .selected_text(color.display.label())
+                .show_ui(ui, |ui| {
+                    for &display in DisplayDevice::all() {
+                        ui.selectable_value(&mut color.display, display, display.label());
+                    }
+                });
+        }
+
+        if color != self.state.color {
+            self.state.color = color;
+            self.send_regen(ViewerMsg::SetColorSettings(color));
+        }
+
+        let mut ocio_selection = None;
+        if let Some(ocio) = &self.state.ocio {
+            if let Some(current) = &ocio.selection {
+                let mut selection = current.clone();
+
+                egui::ComboBox::from_label("Color space")
+                    .selected_text(&selection.color_space)
+                    .show_ui(ui, |ui| {
+                        for name in &ocio.color_spaces {
+                            ui.selectable_value(&mut selection.color_space, name.clone(), name);
+                        }
+                    });
+
+                egui::ComboBox::from_label("OCIO display")
+                    .selected_text(&selection.display)
+                    .show_ui(ui, |ui| {
+                        for (name, _) in &ocio.displays {
+                            ui.selectable_value(&mut selection.display, name.clone(), name);
+                        }
+                    });
+
+                let views = ocio.displays.iter().find(|(name, _)| *name == selection.display);
+                let views = views.map_or(&[][..], |(_, views)| views.as_slice());
+
+                // switching the display keeps the view only if the new display has it
+                if !views.contains(&selection.view) {
+                    selection.view = views.first().cloned().unwrap_or_default();
+                }
+
+                egui::ComboBox::from_label("OCIO view")
+                    .selected_text(&selection.view)
+                    .show_ui(ui, |ui| {
+                        for name in views {
+                            ui.selectable_value(&mut selection.view, name.clone(), name);
+                        }
+                    });
+
+                if selection != *current {
+                    ocio_selection = Some(selection);
+                }
+            }
+        }
+
+        if let Some(selection) = ocio_selection {
+            self.send_regen(ViewerMsg::SetOcioTransform(selection));
+        }
+
+        ui.separator();
+        if ui.button("Load OCIO config...").clicked() {
+            ui.close();
+            self.load_ocio_dialog();
+        }
+        if ui
+            .button("Load LUT...")
+            .on_hover_text("Replace the display transform with a .cube, .spi1d, or .spi3d LUT")
+            .clicked()
+        {
+            ui.close();
+            self.load_lut_dialog();
+        }
+        if self.state.custom_transform.is_some() && ui.button("Use built-in transform").clicked() {
+            self.send_regen(ViewerMsg::ClearCustomTransform);
+        }
+    }
+
+    /// How transparent pixels are displayed in the color channel mode.
+    fn alpha_menu(&mut self, ui: &mut egui::Ui) {
+        let mut alpha = self.state.alpha_display;
+        let solid = match alpha.background {
+            Background::Solid(color) => color,
+            _ => [255, 255, 255],
+        };
+
+        ui.label("Background");
+        for background in [Background::None, Background::Checkerboard, Background::Solid(solid)] {
+            let selected = std::mem::discriminant(&alpha.background)
+                == std::mem::discriminant(&background);
+
+            if ui.radio(selected, background.label()).clicked() {
+                alpha.background = background;
+            }
+        }
+
+        if let Background::Solid(color) = &mut alpha.background {
+            ui.color_edit_button_srgb(color);
+        }
+
+        ui.separator();
+        ui.checkbox(&mut alpha.unpremultiply, "Unpremultiply")
+            .on_hover_text("Divide the colors by alpha to reveal semi-transparent colors");
+        ui.checkbox(&mut alpha.overlay, "Overlay")
+            .on_hover_text("Tint pixels without coverage in magenta");
+
+        let current = self.state.alpha_display;
+        self.state.alpha_display = alpha;
+
+        if alpha.background != current.background {
+            self.send_regen(ViewerMsg::SetBackground(alpha.background));
+        }
+        if alpha.unpremultiply != current.unpremultiply {
+            self.send_regen(ViewerMsg::SetUnpremultiply(alpha.unpremultiply));
+        }
+        if alpha.overlay != current.overlay {
+            self.send_regen(ViewerMsg::SetAlphaOverlay(alpha.overlay));
+        }
+    }
+
     fn process_events(&mut self, ctx: &egui::Context) {
         while let Ok(event) = self.rx.try_recv() {
             match event {
                 ViewerEvent::ImageLoaded {
                     path,
                     dims,
+                    parts,
+                    part,
+                    level,
                     layers,
                     channels,
-                    is_deep,
-                    total_samples,
+                    views,
+                    environment_map,
+                    cryptomattes,
+                    deep_stats,
                     depth_range,
                 } => {
+                    self.state.load_progress = None;
+                    self.settings.add_recent_file(&path);
                     self.state.image_path = Some(path.clone());
                     self.state.image_dims = Some(dims);
+                    self.state.parts = parts;
+                    self.state.current_part = part;
+                    self.state.current_level = level;
                     self.state.layers = layers.clone();
                     self.state.channels = channels.clone();
-                    self.state.is_deep = is_deep;
-                    self.state.total_samples = total_samples;
-                    self.state.avg_samples = if dims.0 * dims.1 > 0 {
-                        total_samples as f32 / (dims.0 * dims.1) as f32
-                    } else {
-                        0.0
-                    };
+                    self.state.views = views;
+                    self.state.environment_map = environment_map;
+                    self.state.cryptomattes = cryptomattes;
+                    self.state.cryptomatte_objects.clear();
+                    self.state.isolated_matte = None;
+                    self.state.is_deep = deep_stats.is_some();
+                    self.state.deep_stats = deep_stats;
 
                     if let Some(first) = layers.first() {
                         self.state.current_layer = first.clone();
@@ -197,15 +564,58 @@ impl ViewerApp {
                     );
                     ctx.send_viewport_cmd(egui::ViewportCommand::Title(title));
                     self.state.error = None;
+                    self.state.hovered_pixel = None;
+                    self.state.pixel_info = None;
+                    self.state.deep_pixel = None;
+                    self.state.sequence_frames.clear();
+                    self.state.playing = false;
                     
                     // Auto-fit on load
                     self.send(ViewerMsg::FitToWindow);
                 }
+                ViewerEvent::Metadata(metadata) => {
+                    self.state.metadata = metadata;
+                }
+                ViewerEvent::ImageBLoaded { path, dims } => {
+                    self.state.load_progress = None;
+                    self.state.compare_path = Some(path);
+                    self.state.compare_dims = Some(dims);
+                    self.state.error = None;
+
+                    if self.state.compare_mode == CompareMode::Off {
+                        self.state.compare_mode = CompareMode::Wipe;
+                        self.send_regen(ViewerMsg::SetCompareMode(CompareMode::Wipe));
+                    }
+                }
+                ViewerEvent::SequenceDetected { frames, index } => {
+                    self.state.sequence_frames = frames;
+                    self.state.frame_index = index;
+                }
+                ViewerEvent::WindowsChanged {
+                    data_window,
+                    display_window,
+                } => {
+                    self.state.data_window = Some(data_window);
+                    self.state.display_window = Some(display_window);
+                }
+                ViewerEvent::ImageReloaded { path } => {
+                    // keep the zoom and pan, unlike a newly loaded image
+                    self.state.load_progress = None;
+                    self.state.image_path = Some(path);
+                    self.state.error = None;
+                }
+                ViewerEvent::FrameChanged { index } => {
+                    self.state.frame_index = index;
+                }
+                ViewerEvent::PlaybackStopped => {
+                    self.state.playing = false;
+                }
                 ViewerEvent::TextureReady {
                     generation,
                     width,
                     height,
                     pixels,
+                    preview_of,
                 } => {
                     if generation < self.generation {
                         continue;
@@ -219,12 +629,88 @@ impl ViewerApp {
                         image,
                         TextureOptions::LINEAR,
                     ));
+                    self.float_image_size = None;
+                    self.preview_size =
+                        preview_of.map(|(width, height)| Vec2::new(width as f32, height as f32));
+
+                    // the displayed color of the inspected pixel may have changed
+                    if let Some((x, y)) = self.state.hovered_pixel {
+                        self.send(ViewerMsg::QueryPixel { x, y });
+                    }
+                }
+                #[cfg(feature = "view-gpu")]
+                ViewerEvent::FloatImageReady {
+                    generation,
+                    width,
+                    height,
+                    pixels,
+                    input_matrix,
+                } => {
+                    if generation < self.generation {
+                        continue;
+                    }
+                    let Some(gpu) = &self.gpu else { continue };
+                    if let Ok(mut gpu) = gpu.lock() {
+                        gpu.upload((width, height), &pixels, input_matrix);
+                    }
+                    self.float_image_size = Some(Vec2::new(width as f32, height as f32));
+
+                    if let Some((x, y)) = self.state.hovered_pixel {
+                        self.send(ViewerMsg::QueryPixel { x, y });
+                    }
                 }
                 ViewerEvent::StateSync { zoom, pan } => {
                     self.state.zoom = zoom;
                     self.state.pan = pan;
                 }
+                ViewerEvent::DisplaySettingsChanged {
+                    exposure,
+                    color,
+                    channel_mode,
+                } => {
+                    self.state.exposure = exposure;
+                    self.state.color = color;
+                    self.state.channel_mode = channel_mode;
+                }
+                ViewerEvent::ColormapLoaded(colormap) => {
+                    self.state.colormap = Some(colormap);
+                    self.state.display_mode = DisplayMode::Lut;
+                }
+                ViewerEvent::CustomTransformChanged { name, ocio } => {
+                    self.state.custom_transform = name;
+                    self.state.ocio = ocio;
+                }
+                ViewerEvent::PixelValue { x, y, values, color } => {
+                    if self.state.hovered_pixel == Some((x, y)) {
+                        self.state.pixel_info = Some(PixelInfo { x, y, values, color });
+                    }
+                }
+                ViewerEvent::DeepPixelInfo(info) => {
+                    self.state.deep_pixel = Some(info);
+                }
+                ViewerEvent::CryptomattePicked { objects, .. } => {
+                    self.state.cryptomatte_objects = objects;
+                }
+                ViewerEvent::Histogram(histogram) => {
+                    self.state.histogram = Some(histogram);
+                }
+                ViewerEvent::Waveform(waveform) => {
+                    let parade = self.state.scope_mode == ScopeMode::Parade;
+                    let image = waveform_image(&waveform, parade);
+                    self.scope_texture =
+                        Some(ctx.load_texture("scope", image, TextureOptions::LINEAR));
+                }
+                ViewerEvent::MotionField(field) => {
+                    self.motion_field = field;
+                }
+                ViewerEvent::LoadProgress(progress) => {
+                    self.state.load_progress = Some(progress);
+                }
+                ViewerEvent::LoadCancelled => {
+                    self.state.load_progress = None;
+                }
                 ViewerEvent::Error(msg) => {
+                    self.state.load_progress = None;
                     self.state.error = Some(msg);
                 }
                 #[cfg(feature = "view-3d")]
@@ -247,71 +733,147 @@ impl ViewerApp {
                         }
                     }
                 }
+                #[cfg(feature = "view-3d")]
+                ViewerEvent::DeepPoints3DReady {
+                    width,
+                    height,
+                    points,
+                    colors,
+                } => {
+                    if let Some(view3d_arc) = &self.view3d {
+                        if let Ok(mut view3d) = view3d_arc.lock() {
+                            view3d.set_deep_pointcloud(width, height, &points, &colors);
+                        }
+                    }
+                }
                 #[cfg(not(feature = "view-3d"))]
-                ViewerEvent::Data3DReady { .. } => {}
+                ViewerEvent::Data3DReady { .. } | ViewerEvent::DeepPoints3DReady { .. } => {}
             }
         }
     }
 
     fn handle_input(&mut self, ctx: &egui::Context) -> bool {
+        let shortcuts = &self.settings.shortcuts;
+        let pressed: Vec<Action> = ctx.input(|i| {
+            Action::all()
+                .iter()
+                .copied()
+                .filter(|&action| shortcuts.pressed(i, action))
+                .collect()
+        });
+
         let mut exit = false;
+        for action in pressed {
+            let channel_mode = match action {
+                Action::Quit => {
+                    exit = true;
+                    None
+                }
+                Action::Fit => {
+                    self.send(ViewerMsg::FitToWindow);
+                    None
+                }
+                Action::Home => {
+                    self.send(ViewerMsg::Home);
+                    None
+                }
+                Action::ZoomIn => {
+                    self.send(ViewerMsg::Zoom { factor: 0.2 });
+                    None
+                }
+                Action::ZoomOut => {
+                    self.send(ViewerMsg::Zoom { factor: -0.2 });
+                    None
+                }
 
-        ctx.input(|i| {
-            if i.key_pressed(egui::Key::Escape) {
-                exit = true;
-            }
-            if i.key_pressed(egui::Key::F) {
-                self.send(ViewerMsg::FitToWindow);
-            }
-            if i.key_pressed(egui::Key::H) || i.key_pressed(egui::Key::Num0) {
-                self.send(ViewerMsg::Home);
-            }
-            if i.key_pressed(egui::Key::Plus) || i.key_pressed(egui::Key::Equals) {
-                self.send(ViewerMsg::Zoom { factor: 0.2 });
-            }
-            if i.key_pressed(egui::Key::Minus) {
-                self.send(ViewerMsg::Zoom { factor: -0.2 });
-            }
+                // Channel shortcuts
+                Action::Red => Some(ChannelMode::Red),
+                Action::Green => Some(ChannelMode::Green),
+                Action::Blue => Some(ChannelMode::Blue),
+                Action::Alpha => Some(ChannelMode::Alpha),
+                Action::Color => Some(ChannelMode::Color),
+                Action::Depth => Some(ChannelMode::Depth),
+                Action::Luminance => Some(ChannelMode::Luminance),
+
+                // Sequence playback: space plays or pauses, arrows step
+                Action::PlayPause | Action::NextFrame | Action::PreviousFrame
+                    if self.state.sequence_frames.is_empty() =>
+                {
+                    None
+                }
+                Action::PlayPause => {
+                    self.toggle_playback();
+                    None
+                }
+                Action::NextFrame => {
+                    self.step_frame(1);
+                    None
+                }
+                Action::PreviousFrame => {
+                    self.step_frame(-1);
+                    None
+                }
 
-            // Channel shortcuts
-            if i.key_pressed(egui::Key::R) && !i.modifiers.ctrl {
-                self.state.channel_mode = ChannelMode::Red;
-                self.send_regen(ViewerMsg::SetChannelMode(ChannelMode::Red));
-            }
-            if i.key_pressed(egui::Key::G) && !i.modifiers.ctrl {
-                self.state.channel_mode = ChannelMode::Green;
-                self.send_regen(ViewerMsg::SetChannelMode(ChannelMode::Green));
-            }
-            if i.key_pressed(egui::Key::B) && !i.modifiers.ctrl {
-                self.state.channel_mode = ChannelMode::Blue;
-                self.send_regen(ViewerMsg::SetChannelMode(ChannelMode::Blue));
-            }
-            if i.key_pressed(egui::Key::A) && !i.modifiers.ctrl {
-                self.state.channel_mode = ChannelMode::Alpha;
-                self.send_regen(ViewerMsg::SetChannelMode(ChannelMode::Alpha));
-            }
-            if i.key_pressed(egui::Key::C) && !i.modifiers.ctrl {
-                self.state.channel_mode = ChannelMode::Color;
-                self.send_regen(ViewerMsg::SetChannelMode(ChannelMode::Color));
-            }
-            if i.key_pressed(egui::Key::Z) && !i.modifiers.ctrl {
-                self.state.channel_mode = ChannelMode::Depth;
-                self.send_regen(ViewerMsg::SetChannelMode(ChannelMode::Depth));
-            }
-            if i.key_pressed(egui::Key::L) {
-                self.state.channel_mode = ChannelMode::Luminance;
-                self.send_regen(ViewerMsg::SetChannelMode(ChannelMode::Luminance));
-            }
+                Action::Open => {
+                    self.open_file_dialog();
+                    None
+                }
+            };
 
-            // Ctrl+O open file
-            if i.key_pressed(egui::Key::O) && i.modifiers.ctrl {
-                self.open_file_dialog();
+            if let Some(mode) = channel_mode {
+                self.state.channel_mode = mode;
+                self.send_regen(ViewerMsg::SetChannelMode(mode));
             }
-        });
+        }
 
         exit
     }
 
+    /// Remember the current display settings and write the settings file.
+    fn save_settings(&mut self) {
+        if !self.save_settings {
+            return;
+        }
+
+        self.settings.exposure = self.state.exposure;
+        self.settings.color = self.state.color;
+        self.settings.background = self.state.alpha_display.background;
+
+        if let Err(e) = self.settings.save() {
+            eprintln!("Cannot save the viewer settings: {e}");
+        }
+    }
+
+    /// The recently opened files, the latest first.
+    fn recent_menu(&mut self, ui: &mut egui::Ui) {
+        if self.settings.recent_files.is_empty() {
+            ui.label("No recent files");
+            return;
+        }
+
+        let mut open = None;
+        for path in &self.settings.recent_files {
+            let name = path.file_name().map_or_else(
+                || path.to_string_lossy(),
+                |name| name.to_string_lossy(),
+            );
+
+            if ui.button(name).on_hover_text(path.display().to_string()).clicked() {
+                open = Some(path.clone());
+            }
+        }
+
+        ui.separator();
+        if ui.button("Clear").clicked() {
+            self.settings.recent_files.clear();
+        }
+
+        if let Some(path) = open {
+            ui.close();
+            self.send(ViewerMsg::LoadImage(path));
+        }
+    }
+
     fn draw_controls(&mut self, ctx: &egui::Context) {
         egui::TopBottomPanel::top("controls").show(ctx, |ui| {
             // Row 1: File, Mode, Layer, Channel
@@ -333,8 +895,85 @@ impl ViewerApp {
                     self.handle_ui_msg(&msg);
                     self.send(msg);
                 }
+
+                ui.checkbox(&mut self.state.show_metadata, "Metadata");
+
+                if self.state.is_deep {
+                    ui.checkbox(&mut self.state.show_deep_stats, "Deep stats");
+                }
+
+                // Scopes panel toggle
+                if ui.checkbox(&mut self.state.show_scopes, "Scopes").changed() {
+                    let mode = self.state.show_scopes.then_some(self.state.scope_mode);
+                    self.send(ViewerMsg::SetScopes(mode));
+                }
                 ui.separator();
 
+                // Part selector, for multi-part files
+                if self.state.parts.len() > 1 {
+                    let selected = self
+                        .state
+                        .parts
+                        .get(self.state.current_part)
+                        .map(|part| part.name.clone())
+                        .unwrap_or_default();
+
+                    egui::ComboBox::from_label("Part")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (index, part) in self.state.parts.clone().iter().enumerate() {
+                                if ui
+                                    .selectable_value(
+                                        &mut self.state.current_part,
+                                        index,
+                                        part.label(),
+                                    )
+                                    .changed()
+                                {
+                                    self.send_regen(ViewerMsg::SetPart(index));
+                                }
+                            }
+                        });
+                    ui.separator();
+                }
+
+                // Level selector, for tiled parts with mip or rip maps
+                let levels = self
+                    .state
+                    .parts
+                    .get(self.state.current_part)
+                    .map(|part| part.levels.clone())
+                    .unwrap_or_default();
+
+                if levels.len() > 1 {
+                    let label = |index: usize, (width, height): (usize, usize)| {
+                        format!("{index}: {width}x{height}")
+                    };
+
+                    let selected = levels
+                        .get(self.state.current_level)
+                        .map(|&size| label(self.state.current_level, size))
+                        .unwrap_or_default();
+
+                    egui::ComboBox::from_label("Level")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (index, &size) in levels.iter().enumerate() {
+                                if ui
+                                    .selectable_value(
+                                        &mut self.state.current_level,
+                                        index,
+                                        label(index, size),
+                                    )
+                                    .changed()
+                                {
+                                    self.send_regen(ViewerMsg::SetLevel(index));
+                                }
+                            }
+                        });
+                    ui.separator();
+                }
+
                 // Layer selector
                 if self.state.layers.len() > 1 {
                     egui::ComboBox::from_label("Layer")
@@ -369,18 +1008,70 @@ impl ViewerApp {
                                 self.send_regen(ViewerMsg::SetChannelMode(mode));
                             }
                         }
-                        // Add custom channels
+                        // Add custom channels, grouped by view in multi-view layers
                         ui.separator();
                         let channels: Vec<_> = self.state.channels.clone();
-                        for (i, ch) in channels.iter().enumerate() {
-                            let mode = ChannelMode::Custom(i);
+                        let mut groups = self.state.views.clone();
+                        let other: Vec<usize> = (0..channels.len())
+                            .filter(|i| !groups.iter().any(|(_, group)| group.contains(i)))
+                            .collect();
+
+                        if !other.is_empty() {
+                            let label = if groups.is_empty() { "" } else { "No view" };
+                            groups.push((label.to_string(), other));
+                        }
+
+                        for (view, indices) in groups {
+                            if !view.is_empty() {
+                                ui.label(egui::RichText::new(view).weak());
+                            }
+                            for i in indices {
+                                let (mode, name) = (ChannelMode::Custom(i), &channels[i]);
+                                if ui
+                                    .selectable_value(&mut self.state.channel_mode, mode, name)
+                                    .changed()
+                                {
+                                    self.send_regen(ViewerMsg::SetChannel(name.clone()));
+                                }
+                            }
+                        }
+                    });
+
+                // Views of multi-view images
+                if self.state.views.len() > 1 {
+                    egui::ComboBox::from_label("Stereo")
+                        .selected_text(self.state.stereo_mode.label())
+                        .show_ui(ui, |ui| {
+                            for &mode in StereoMode::all() {
+                                let stereo_mode = &mut self.state.stereo_mode;
+                                if ui.selectable_value(stereo_mode, mode, mode.label()).changed()
+                                {
+                                    self.send_regen(ViewerMsg::SetStereoMode(mode));
+                                }
+                            }
+                        });
+                }
+
+                // Display mode
+                egui::ComboBox::from_label("Mode")
+                    .selected_text(self.state.display_mode.label())
+                    .show_ui(ui, |ui| {
+                        for &mode in DisplayMode::all() {
+                            // the lut mode needs a colormap first
+                            if mode == DisplayMode::Lut && self.state.colormap.is_none() {
+                                continue;
+                            }
                             if ui
-                                .selectable_value(&mut self.state.channel_mode, mode, ch)
+                                .selectable_value(&mut self.state.display_mode, mode, mode.label())
                                 .changed()
                             {
-                                self.send_regen(ViewerMsg::SetChannel(ch.clone()));
+                                self.send_regen(ViewerMsg::SetDisplayMode(mode));
                             }
                         }
+                        ui.separator();
+                        if ui.button("Load colormap...").clicked() {
+                            self.load_colormap_dialog();
+                        }
                     });
 
                 ui.separator();
@@ -400,22 +1091,103 @@ impl ViewerApp {
                     self.send_regen(ViewerMsg::SetExposure(self.state.exposure));
                 }
 
-                // sRGB toggle
                 if ui
-                    .checkbox(&mut self.state.apply_srgb, "sRGB")
+                    .checkbox(&mut self.state.show_clipping, "Clip")
+                    .on_hover_text("Show values above 1 in red and below 0 in blue")
                     .changed()
                 {
-                    self.send_regen(ViewerMsg::SetSrgb(self.state.apply_srgb));
+                    self.send_regen(ViewerMsg::SetClipping(self.state.show_clipping));
                 }
 
-                // Open file button (right side)
+                let alpha_label = if self.state.alpha_display.is_active() {
+                    "Alpha *"
+                } else {
+                    "Alpha"
+                };
+                ui.menu_button(alpha_label, |ui| self.alpha_menu(ui))
+                    .response
+                    .on_hover_text("Background, unpremultiply, and coverage overlay");
+
+                ui.checkbox(&mut self.state.show_windows, "Windows")
+                    .on_hover_text("Show the display window border and shade the overscan");
+
+                if self.state.environment_map.is_some()
+                    && ui
+                        .checkbox(&mut self.state.panorama, "Panorama")
+                        .on_hover_text("Drag to look around the environment map, scroll to zoom")
+                        .changed()
+                {
+                    self.send_regen(ViewerMsg::SetPanorama(self.state.panorama));
+                }
+
+                // Display transform
+                let color_label = match &self.state.custom_transform {
+                    Some(_) => "Color: Custom".to_string(),
+                    None => format!(
+                        "Color: {} / {}",
+                        self.state.color.view.label(),
+                        self.state.color.display.label()
+                    ),
+                };
+                ui.menu_button(color_label, |ui| self.color_menu(ui));
+
+                // File menu (right side)
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    if ui.button("Open...").clicked() {
-                        self.open_file_dialog();
-                    }
+                    ui.menu_button("File", |ui| {
+                        if ui.button("Open...").clicked() {
+                            self.open_file_dialog();
+                        }
+                        if ui
+                            .button("Open B...")
+                            .on_hover_text(
+                                "Open a second image to compare with, or drop it with Shift",
+                            )
+                            .clicked()
+                        {
+                            self.open_file_dialog_b();
+                        }
+                        ui.menu_button("Recent", |ui| self.recent_menu(ui));
+
+                        ui.separator();
+
+                        let export_hint = "Save the displayed channels with the current exposure \
+                            and display transform as PNG or TIFF, or linear as HDR";
+                        if ui.button("Export...").on_hover_text(export_hint).clicked() {
+                            self.export_file_dialog(BitDepth::Eight);
+                        }
+                        if ui.button("Export 16-bit...").on_hover_text(export_hint).clicked() {
+                            self.export_file_dialog(BitDepth::Sixteen);
+                        }
+                        let export_points = egui::Button::new("Export Points...");
+                        if ui
+                            .add_enabled(self.state.is_deep, export_points)
+                            .on_hover_text(
+                                "Save the deep samples as a PLY or OBJ point cloud, \
+                                 in world space if the file contains a camera",
+                            )
+                            .clicked()
+                        {
+                            self.export_points_dialog();
+                        }
+                    });
                     if ui.button("Refresh").clicked() {
                         self.send(ViewerMsg::Regenerate);
                     }
+                    if ui
+                        .checkbox(&mut self.state.watch, "Watch")
+                        .on_hover_text("Reload the image when the file is overwritten")
+                        .changed()
+                    {
+                        self.send(ViewerMsg::SetWatch(self.state.watch));
+                    }
+                    if ui
+                        .button("Send to tev")
+                        .on_hover_text("Send the image to a tev viewer on this machine")
+                        .clicked()
+                    {
+                        let address = crate::interop::tev::DEFAULT_ADDRESS.to_string();
+                        self.send(ViewerMsg::SendToTev(address));
+                    }
                 });
             });
 
@@ -539,6 +1311,76 @@ impl ViewerApp {
                 });
             }
 
+            // Row: A/B comparison (if a second image is loaded)
+            if let Some(path) = self.state.compare_path.clone() {
+                ui.horizontal(|ui| {
+                    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("?");
+                    ui.label(format!("B: {name}"));
+
+                    if let Some((w, h)) = self.state.compare_dims {
+                        if Some((w, h)) != self.state.image_dims {
+                            ui.colored_label(Color32::YELLOW, format!("{w}x{h}"));
+                        }
+                    }
+                    ui.separator();
+
+                    egui::ComboBox::from_label("Compare")
+                        .selected_text(self.state.compare_mode.label())
+                        .show_ui(ui, |ui| {
+                            for &mode in CompareMode::all() {
+                                if ui
+                                    .selectable_value(
+                                        &mut self.state.compare_mode,
+                                        mode,
+                                        mode.label(),
+                                    )
+                                    .changed()
+                                {
+                                    self.send_regen(ViewerMsg::SetCompareMode(mode));
+                                }
+                            }
+                        });
+
+                    match self.state.compare_mode {
+                        CompareMode::Wipe => {
+                            if ui
+                                .add(
+                                    egui::Slider::new(&mut self.state.wipe_position, 0.0..=1.0)
+                                        .text("Wipe"),
+                                )
+                                .changed()
+                            {
+                                self.send_regen(ViewerMsg::SetWipePosition(
+                                    self.state.wipe_position,
+                                ));
+                            }
+                        }
+                        CompareMode::AbsoluteDifference | CompareMode::RelativeDifference => {
+                            ui.label("Threshold:");
+                            if ui
+                                .add(
+                                    egui::DragValue::new(&mut self.state.difference_threshold)
+                                        .speed(0.001)
+                                        .range(0.0..=f32::MAX),
+                                )
+                                .on_hover_text("Pixels with a larger difference are magenta")
+                                .changed()
+                            {
+                                self.send_regen(ViewerMsg::SetDifferenceThreshold(
+                                    self.state.difference_threshold,
+                                ));
+                            }
+                        }
+                        CompareMode::Off | CompareMode::SideBySide => {}
+                    }
+                });
+            }
+
+            // Row: legend of the display mode
+            if self.state.display_mode != DisplayMode::Normal {
+                ui.horizontal(|ui| self.draw_display_legend(ui));
+            }
+
             // Row 3: 3D controls (if 3D panel shown)
             if self.state.show_3d {
                 ui.horizontal(|ui| {
@@ -558,67 +1400,431 @@ impl ViewerApp {
                         self.send(ViewerMsg::Set3DMode(self.state.view_3d_mode));
                     }
 
-                    ui.separator();
-                    ui.label("Point Size:");
-                    let old_size = self.state.point_size;
-                    ui.add(egui::Slider::new(&mut self.state.point_size, 1.0..=10.0));
-                    if (self.state.point_size - old_size).abs() > 0.01 {
-                        let msg = ViewerMsg::SetPointSize(self.state.point_size);
-                        self.handle_ui_msg(&msg);
-                        self.send(msg);
+                    ui.separator();
+                    ui.label("Point Size:");
+                    let old_size = self.state.point_size;
+                    ui.add(egui::Slider::new(&mut self.state.point_size, 1.0..=10.0));
+                    if (self.state.point_size - old_size).abs() > 0.01 {
+                        let msg = ViewerMsg::SetPointSize(self.state.point_size);
+                        self.handle_ui_msg(&msg);
+                        self.send(msg);
+                    }
+
+                    ui.separator();
+                    if ui.button("Reset Camera").clicked() {
+                        let msg = ViewerMsg::Reset3DCamera;
+                        self.handle_ui_msg(&msg);
+                        self.send(msg);
+                    }
+                });
+            }
+        });
+    }
+
+    fn draw_status(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if let Some(progress) = self.state.load_progress {
+                    ui.add(
+                        egui::ProgressBar::new(progress)
+                            .desired_width(160.0)
+                            .show_percentage(),
+                    );
+                    if ui.button("Cancel").clicked() {
+                        self.cancel_loading();
+                    }
+                    ui.separator();
+                }
+
+                if self.state.image_dims.is_some() {
+                    // Show image info when loaded
+                    if let Some((w, h)) = self.state.image_dims {
+                        ui.label(format!("{}x{}", w, h));
+                        ui.separator();
+                    }
+
+                    ui.label(format!("{} ch", self.state.channels.len()));
+                    ui.separator();
+
+                    if let Some(stats) = &self.state.deep_stats {
+                        ui.label(format!(
+                            "Deep: {} ({:.1}/px, max {}, {:.0}% empty)",
+                            stats.total_samples,
+                            stats.mean_samples_per_pixel(),
+                            stats.max_samples_per_pixel,
+                            stats.empty_percentage()
+                        ))
+                        .on_hover_text("Total samples, mean and maximum samples per pixel");
+                        ui.separator();
+                    }
+
+                    let (min, max) = self.state.depth_auto_range;
+                    if max > min {
+                        ui.label(format!("Z: {:.2}..{:.2}", min, max));
+                        ui.separator();
+                    }
+
+                    ui.label(format!("{}%", (self.state.zoom * 100.0) as i32));
+
+                    // Pixel inspector
+                    if let Some(info) = &self.state.pixel_info {
+                        ui.separator();
+                        let (x, y) = self.display_window_position(info.x, info.y);
+                        ui.label(format!("{x}, {y}"))
+                            .on_hover_text("Pixel coordinates relative to the display window");
+
+                        let (swatch, _) = ui.allocate_exact_size(
+                            egui::vec2(12.0, 12.0),
+                            egui::Sense::hover(),
+                        );
+                        ui.painter().rect_filled(swatch, 2.0, info.color);
+
+                        let values: Vec<String> = info
+                            .values
+                            .iter()
+                            .map(|(name, value)| format!("{name}: {value:.4}"))
+                            .collect();
+                        ui.monospace(values.join("  "));
+                    }
+
+                    // Picked cryptomatte objects, with a toggle to display only their matte
+                    for object in self.state.cryptomatte_objects.clone() {
+                        ui.separator();
+                        ui.label(format!(
+                            "{}: {} ({:.0}%)",
+                            object.cryptomatte_name,
+                            object.name,
+                            object.coverage * 100.0
+                        ));
+
+                        let matte = (object.cryptomatte, object.id);
+                        let mut isolated = self.state.isolated_matte == Some(matte);
+                        if ui.toggle_value(&mut isolated, "Isolate").changed() {
+                            self.state.isolated_matte = isolated.then_some(matte);
+                            self.send_regen(ViewerMsg::IsolateMatte(self.state.isolated_matte));
+                        }
+                    }
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label("F:Fit H:1:1 +/-:Zoom R/G/B/A/Z:Ch Hover:Inspect");
+                    });
+                } else {
+                    // No file loaded
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label("Ctrl+O: Open | Drag & drop EXR file");
+                    });
+                }
+            });
+        });
+    }
+
+    fn toggle_playback(&mut self) {
+        self.state.playing = !self.state.playing;
+        if self.state.playing {
+            self.send(ViewerMsg::Play { fps: self.state.fps });
+        } else {
+            self.send(ViewerMsg::Stop);
+        }
+    }
+
+    /// Show the next or previous frame, wrapping around at the ends of the sequence.
+    fn step_frame(&mut self, step: isize) {
+        let frame_count = self.state.sequence_frames.len() as isize;
+        let index = (self.state.frame_index as isize + step).rem_euclid(frame_count) as usize;
+        self.state.frame_index = index;
+        self.send(ViewerMsg::SeekFrame(index));
+    }
+
+    fn draw_timeline(&mut self, ctx: &egui::Context) {
+        if self.state.sequence_frames.is_empty() {
+            return;
+        }
+
+        egui::TopBottomPanel::bottom("timeline").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let label = if self.state.playing { "Pause" } else { "Play" };
+                if ui.button(label).on_hover_text("Space").clicked() {
+                    self.toggle_playback();
+                }
+
+                if ui.button("<").clicked() {
+                    self.step_frame(-1);
+                }
+                if ui.button(">").clicked() {
+                    self.step_frame(1);
+                }
+
+                let frames = &self.state.sequence_frames;
+                let frame = frames.get(self.state.frame_index).copied().unwrap_or_default();
+                ui.monospace(format!("{frame}"));
+
+                let last = frames.len() - 1;
+                ui.spacing_mut().slider_width = (ui.available_width() - 120.0).max(100.0);
+                if ui
+                    .add(egui::Slider::new(&mut self.state.frame_index, 0..=last).show_value(false))
+                    .changed()
+                {
+                    self.send(ViewerMsg::SeekFrame(self.state.frame_index));
+                }
+
+                ui.label("FPS:");
+                if ui
+                    .add(egui::DragValue::new(&mut self.state.fps).speed(0.5).range(1.0..=120.0))
+                    .changed()
+                    && self.state.playing
+                {
+                    self.send(ViewerMsg::Play { fps: self.state.fps });
+                }
+            });
+        });
+    }
+
+    fn draw_scopes(&mut self, ctx: &egui::Context) {
+        if !self.state.show_scopes {
+            return;
+        }
+
+        egui::TopBottomPanel::bottom("scopes")
+            .resizable(true)
+            .default_height(180.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let old_mode = self.state.scope_mode;
+                    egui::ComboBox::from_label("Scope")
+                        .selected_text(self.state.scope_mode.label())
+                        .show_ui(ui, |ui| {
+                            for &mode in ScopeMode::all() {
+                                let label = mode.label();
+                                ui.selectable_value(&mut self.state.scope_mode, mode, label);
+                            }
+                        });
+                    if self.state.scope_mode != old_mode {
+                        self.send(ViewerMsg::SetScopes(Some(self.state.scope_mode)));
+                    }
+
+                    if self.state.scope_mode == ScopeMode::Histogram {
+                        ui.checkbox(&mut self.state.histogram_log, "Log");
                     }
+                });
 
-                    ui.separator();
-                    if ui.button("Reset Camera").clicked() {
-                        let msg = ViewerMsg::Reset3DCamera;
-                        self.handle_ui_msg(&msg);
-                        self.send(msg);
+                let available = ui.available_size();
+                let (rect, _response) = ui.allocate_exact_size(available, egui::Sense::hover());
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, Color32::from_gray(16));
+
+                match self.state.scope_mode {
+                    ScopeMode::Histogram => {
+                        if let Some(histogram) = &self.state.histogram {
+                            draw_histogram(&painter, rect, histogram, self.state.histogram_log);
+                        }
                     }
-                });
-            }
-        });
+                    ScopeMode::Waveform | ScopeMode::Parade => {
+                        if let Some(texture) = &self.scope_texture {
+                            let uv = egui::Rect::from_min_max(
+                                egui::pos2(0.0, 0.0),
+                                egui::pos2(1.0, 1.0),
+                            );
+                            painter.image(texture.id(), rect, uv, Color32::WHITE);
+                        }
+                    }
+                }
+            });
     }
 
-    fn draw_status(&self, ctx: &egui::Context) {
-        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                if self.state.image_dims.is_some() {
-                    // Show image info when loaded
-                    if let Some((w, h)) = self.state.image_dims {
-                        ui.label(format!("{}x{}", w, h));
-                        ui.separator();
-                    }
+    fn draw_deep_inspector(&mut self, ctx: &egui::Context) {
+        let Some(info) = &self.state.deep_pixel else { return };
+        let mut open = true;
 
-                    ui.label(format!("{} ch", self.state.channels.len()));
-                    ui.separator();
+        egui::SidePanel::right("deep_inspector")
+            .resizable(true)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong(format!("Deep pixel {}, {}", info.x, info.y));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("Close").clicked() {
+                            open = false;
+                        }
+                    });
+                });
 
-                    if self.state.is_deep {
-                        ui.label(format!(
-                            "Deep: {} ({:.1}/px)",
-                            self.state.total_samples, self.state.avg_samples
-                        ));
-                        ui.separator();
-                    }
+                ui.label(format!("{} samples", info.samples.len()));
+                ui.separator();
 
-                    let (min, max) = self.state.depth_auto_range;
-                    if max > min {
-                        ui.label(format!("Z: {:.2}..{:.2}", min, max));
-                        ui.separator();
-                    }
+                // Depth vs alpha plot
+                draw_depth_alpha_plot(ui, &info.samples);
+                ui.separator();
 
-                    ui.label(format!("{}%", (self.state.zoom * 100.0) as i32));
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("deep_samples")
+                        .striped(true)
+                        .num_columns(7)
+                        .show(ui, |ui| {
+                            for header in ["#", "Z", "ZBack", "R", "G", "B", "A"] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+
+                            for (index, sample) in info.samples.iter().enumerate() {
+                                ui.label(index.to_string());
+                                ui.monospace(format!("{:.4}", sample.z));
+                                ui.monospace(format!("{:.4}", sample.z_back));
+                                for value in sample.rgba {
+                                    ui.monospace(format!("{value:.4}"));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
 
+        if !open {
+            self.state.deep_pixel = None;
+        }
+    }
+
+    /// The sample count distribution, depth range, and memory of the displayed deep image.
+    fn draw_deep_stats(&mut self, ctx: &egui::Context) {
+        if !self.state.show_deep_stats {
+            return;
+        }
+
+        let Some(stats) = &self.state.deep_stats else { return };
+        let mut open = true;
+
+        egui::SidePanel::right("deep_stats")
+            .resizable(true)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Deep statistics");
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.label("F:Fit H:1:1 +/-:Zoom R/G/B/A/Z:Ch");
+                        if ui.small_button("Close").clicked() {
+                            open = false;
+                        }
                     });
-                } else {
-                    // No file loaded
+                });
+                ui.separator();
+
+                let max_position = stats
+                    .max_samples_position
+                    .map(|position| format!(" at {}, {}", position.x(), position.y()))
+                    .unwrap_or_default();
+
+                let depth_range = stats
+                    .depth_range
+                    .map(|(min, max)| format!("{min:.4} .. {max:.4}"))
+                    .unwrap_or_else(|| "-".to_string());
+
+                let rows = [
+                    ("Pixels", stats.pixel_count.to_string()),
+                    ("Samples", stats.total_samples.to_string()),
+                    ("Mean", format!("{:.2} per pixel", stats.mean_samples_per_pixel())),
+                    ("Min", format!("{} per pixel", stats.min_samples_per_pixel)),
+                    (
+                        "Max",
+                        format!("{} per pixel{}", stats.max_samples_per_pixel, max_position),
+                    ),
+                    (
+                        "Empty",
+                        format!("{} ({:.1}%)", stats.empty_pixels, stats.empty_percentage()),
+                    ),
+                    ("Z range", depth_range),
+                    ("Memory", format_bytes(stats.memory.total())),
+                ];
+
+                egui::Grid::new("deep_stats_grid")
+                    .striped(true)
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (name, value) in rows {
+                            ui.label(name);
+                            ui.monospace(value);
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                ui.label("Samples per pixel");
+                draw_sample_count_histogram(ui, &stats.histogram);
+            });
+
+        if !open {
+            self.state.show_deep_stats = false;
+        }
+    }
+
+    /// All header attributes of all parts, filtered by name or value.
+    fn draw_metadata(&mut self, ctx: &egui::Context) {
+        if !self.state.show_metadata {
+            return;
+        }
+
+        egui::SidePanel::left("metadata")
+            .resizable(true)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Metadata");
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.label("Ctrl+O: Open | Drag & drop EXR file");
+                        if ui.small_button("Close").clicked() {
+                            self.state.show_metadata = false;
+                        }
                     });
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.state.metadata_filter);
+                });
+                ui.separator();
+
+                if self.state.metadata.is_empty() {
+                    ui.label("No file loaded");
+                    return;
                 }
+
+                let filter = self.state.metadata_filter.to_lowercase();
+                let multi_part = self.state.metadata.len() > 1;
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (index, part) in self.state.metadata.iter().enumerate() {
+                        let attributes = part.attributes.iter().filter(|attribute| {
+                            filter.is_empty()
+                                || attribute.name.to_lowercase().contains(&filter)
+                                || attribute.value.to_lowercase().contains(&filter)
+                        });
+
+                        let grid = |ui: &mut egui::Ui| {
+                            egui::Grid::new(("metadata", index))
+                                .striped(true)
+                                .num_columns(3)
+                                .show(ui, |ui| {
+                                    for attribute in attributes {
+                                        ui.label(&attribute.name);
+                                        ui.weak(&attribute.kind);
+                                        ui.add(
+                                            egui::Label::new(
+                                                egui::RichText::new(&attribute.value).monospace(),
+                                            )
+                                            .wrap(),
+                                        );
+                                        ui.end_row();
+                                    }
+                                });
+                        };
+
+                        if multi_part {
+                            egui::CollapsingHeader::new(&part.name)
+                                .id_salt(("metadata_part", index))
+                                .default_open(index == self.state.current_part)
+                                .show(ui, grid);
+                        } else {
+                            grid(ui);
+                        }
+                    }
+                });
             });
-        });
     }
 
     #[cfg(feature = "view-3d")]
@@ -684,6 +1890,75 @@ impl ViewerApp {
         }
     }
 
+    /// Draw the perspective view of an environment map, rendered with the size of the canvas.
+    fn draw_panorama(&mut self, ui: &mut egui::Ui, available: Vec2) {
+        let (rect, response) = ui.allocate_exact_size(available, egui::Sense::click_and_drag());
+        let camera = self.state.panorama_camera;
+
+        // the view has no pixel positions of the image
+        self.state.hovered_pixel = None;
+        self.state.pixel_info = None;
+
+        if response.dragged() {
+            let delta = response.drag_delta();
+            self.state.panorama_camera.drag([delta.x, delta.y], rect.height());
+        }
+        if response.hovered() {
+            let scroll = ui.input(|i| i.raw_scroll_delta.y);
+            if scroll != 0.0 {
+                self.state.panorama_camera.zoom((-scroll * 0.002).exp());
+            }
+        }
+        if response.double_clicked() {
+            self.state.panorama_camera = PanoramaCamera::default();
+        }
+
+        if self.state.panorama_camera != camera {
+            self.send(ViewerMsg::SetPanoramaCamera(self.state.panorama_camera));
+        }
+
+        let painter = ui.painter_at(rect);
+        if let Some(texture) = &self.texture {
+            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            painter.image(texture.id(), rect, uv, Color32::WHITE);
+        }
+    }
+
+    /// Convert a pixel index of the data window to coordinates relative to the display window.
+    fn display_window_position(&self, x: usize, y: usize) -> (i64, i64) {
+        let offset = match (self.state.data_window, self.state.display_window) {
+            (Some(data), Some(display)) => data.position - display.position,
+            _ => crate::math::Vec2(0, 0),
+        };
+
+        (x as i64 + i64::from(offset.x()), y as i64 + i64::from(offset.y()))
+    }
+
+    /// Paint the image with the shader, or the texture of the worker.
+    fn paint_image(&self, painter: &egui::Painter, image_rect: egui::Rect) {
+        #[cfg(feature = "view-gpu")]
+        if let (Some(gpu), Some(_)) = (&self.gpu, self.float_image_size) {
+            let uniforms = DisplayUniforms {
+                exposure: self.state.exposure,
+                channel_mode: self.state.channel_mode,
+                display: self.state.color.display,
+                clipping: self.state.show_clipping,
+            };
+
+            painter.add(gpu::paint_callback(gpu.clone(), image_rect, uniforms));
+            return;
+        }
+
+        if let Some(texture) = &self.texture {
+            painter.image(
+                texture.id(),
+                image_rect,
+                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
+    }
+
     fn draw_2d_canvas(&mut self, ui: &mut egui::Ui, available: Vec2) {
         // Track viewport size for fit-to-window calculation
         if (self.state.viewport_size[0] - available.x).abs() > 1.0
@@ -693,8 +1968,17 @@ impl ViewerApp {
             self.send(ViewerMsg::SetViewport(self.state.viewport_size));
         }
         
-        if let Some(ref texture) = self.texture {
-            let tex_size = texture.size_vec2();
+        if self.state.panorama && self.state.environment_map.is_some() {
+            self.draw_panorama(ui, available);
+            return;
+        }
+
+        let displayed_size = self
+            .float_image_size
+            .or(self.preview_size)
+            .or_else(|| self.texture.as_ref().map(TextureHandle::size_vec2));
+
+        if let Some(tex_size) = displayed_size {
             let scaled_size = tex_size * self.state.zoom;
 
             let center = available / 2.0;
@@ -707,6 +1991,24 @@ impl ViewerApp {
             let (rect, response) =
                 ui.allocate_exact_size(available, egui::Sense::click_and_drag());
 
+            let image_rect =
+                egui::Rect::from_min_size(rect.min + top_left.to_pos2().to_vec2(), scaled_size);
+
+            // Pixel inspector: ask the worker for the values under the cursor
+            let hovered_pixel = response
+                .hover_pos()
+                .map(|pos| (pos - image_rect.min) / self.state.zoom)
+                .filter(|p| p.x >= 0.0 && p.y >= 0.0 && p.x < tex_size.x && p.y < tex_size.y)
+                .map(|p| (p.x as usize, p.y as usize));
+
+            if hovered_pixel != self.state.hovered_pixel {
+                self.state.hovered_pixel = hovered_pixel;
+                match hovered_pixel {
+                    Some((x, y)) => self.send(ViewerMsg::QueryPixel { x, y }),
+                    None => self.state.pixel_info = None,
+                }
+            }
+
             if response.dragged() {
                 let delta = response.drag_delta();
                 self.send(ViewerMsg::Pan { delta: [delta.x, delta.y] });
@@ -714,6 +2016,20 @@ impl ViewerApp {
             if response.double_clicked() {
                 self.send(ViewerMsg::FitToWindow);
             }
+
+            // Deep sample inspector: list the samples of the clicked pixel
+            if response.clicked() && self.state.is_deep {
+                if let Some((x, y)) = self.state.hovered_pixel {
+                    self.send(ViewerMsg::InspectDeepPixel { x, y });
+                }
+            }
+
+            // Cryptomatte: name the objects of the clicked pixel
+            if response.clicked() && !self.state.cryptomattes.is_empty() {
+                if let Some((x, y)) = self.state.hovered_pixel {
+                    self.send(ViewerMsg::PickCryptomatte { x, y });
+                }
+            }
             
             // Scroll zoom only when hovered over 2D canvas
             if response.hovered() {
@@ -724,14 +2040,30 @@ impl ViewerApp {
             }
 
             let painter = ui.painter_at(rect);
-            let image_rect =
-                egui::Rect::from_min_size(rect.min + top_left.to_pos2().to_vec2(), scaled_size);
-            painter.image(
-                texture.id(),
-                image_rect,
-                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-                Color32::WHITE,
-            );
+            self.paint_image(&painter, image_rect);
+
+            if let (true, Some(field)) = (self.state.show_motion_arrows, &self.motion_field) {
+                if tex_size == Vec2::new(field.width as f32, field.height as f32) {
+                    draw_motion_arrows(&painter, image_rect, self.state.zoom, field);
+                }
+            }
+
+            if let (true, Some(data_window), Some(display_window)) = (
+                self.state.show_windows,
+                self.state.data_window,
+                self.state.display_window,
+            ) {
+                if data_window != display_window {
+                    let zoom = self.state.zoom;
+                    draw_windows(&painter, image_rect, zoom, data_window, display_window);
+                }
+            }
+
+            // Wipe line between image A and image B
+            if self.state.compare_mode == CompareMode::Wipe && self.state.compare_path.is_some() {
+                let x = image_rect.left() + self.state.wipe_position * image_rect.width();
+                painter.vline(x, image_rect.y_range(), egui::Stroke::new(1.0, Color32::WHITE));
+            }
         } else {
             // Empty canvas - clickable area for file opening
             let (rect, response) = ui.allocate_exact_size(available, egui::Sense::click());
@@ -909,7 +2241,12 @@ impl ViewerApp {
         ctx.input(|i| {
             if !i.raw.dropped_files.is_empty() {
                 if let Some(path) = i.raw.dropped_files.first().and_then(|f| f.path.clone()) {
-                    self.send(ViewerMsg::LoadImage(path));
+                    // Shift+drop loads the image to compare with
+                    if i.modifiers.shift {
+                        self.send(ViewerMsg::LoadImageB(path));
+                    } else {
+                        self.send(ViewerMsg::LoadImage(path));
+                    }
                 }
             }
         });
@@ -929,10 +2266,378 @@ impl eframe::App for ViewerApp {
 
         self.draw_controls(ctx);
         self.draw_status(ctx);
+        self.draw_timeline(ctx);
+        self.draw_scopes(ctx);
+        self.draw_deep_inspector(ctx);
+        self.draw_deep_stats(ctx);
+        self.draw_metadata(ctx);
         self.draw_canvas(ctx);
 
+        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+            self.settings.window_size = Some([rect.width(), rect.height()]);
+        }
+
         ctx.request_repaint();
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_settings();
+    }
+}
+
+/// Show a file dialog for EXR files, and the HDR, PFM, and TIFF files that can be imported.
+/// Starts in the directory, if any.
+fn pick_exr_file(directory: Option<&Path>) -> Option<PathBuf> {
+    let dialog = rfd::FileDialog::new();
+    let dialog = match directory {
+        Some(directory) => dialog.set_directory(directory),
+        None => dialog,
+    };
+
+    dialog
+        .add_filter("EXR", &["exr"])
+        .add_filter("HDR, PFM, TIFF", &["hdr", "pfm", "tif", "tiff"])
+        .add_filter("All", &["*"])
+        .pick_file()
+}
+
+/// Shade the overscan outside of the display window, and draw the border of both windows.
+/// The image rectangle covers the data window.
+/// Draw an arrow for the vector at the center of each cell of a grid in screen space.
+/// The longest vector of the image fills a cell.
+fn draw_motion_arrows(
+    painter: &egui::Painter,
+    image_rect: egui::Rect,
+    zoom: f32,
+    field: &MotionField,
+) {
+    const CELL_SIZE: f32 = 24.0;
+
+    if field.max_magnitude <= 0.0 {
+        return;
+    }
+
+    // only the cells of the visible part of the image
+    let visible = image_rect.intersect(painter.clip_rect());
+    if !visible.is_positive() {
+        return;
+    }
+
+    let first = ((visible.min - image_rect.min) / CELL_SIZE).floor();
+    let last = ((visible.max - image_rect.min) / CELL_SIZE).ceil();
+    let stroke = egui::Stroke::new(1.5, Color32::WHITE);
+    let shadow = egui::Stroke::new(3.0, Color32::from_black_alpha(160));
+
+    for row in first.y as usize..last.y as usize {
+        for column in first.x as usize..last.x as usize {
+            let cell = egui::vec2(column as f32, row as f32) + Vec2::splat(0.5);
+            let center = image_rect.min + cell * CELL_SIZE;
+            let pixel = (center - image_rect.min) / zoom;
+            let Some(vector) = field.get(pixel.x as usize, pixel.y as usize) else { continue };
+            if !motion::magnitude(vector).is_finite() {
+                continue;
+            }
+
+            let arrow = Vec2::from(vector) / field.max_magnitude * CELL_SIZE * 0.9;
+            if arrow.length() < 2.0 {
+                continue;
+            }
+
+            let origin = center - arrow / 2.0;
+            painter.arrow(origin, arrow, shadow);
+            painter.arrow(origin, arrow, stroke);
+        }
+    }
+}
+
+fn draw_windows(
+    painter: &egui::Painter,
+    image_rect: egui::Rect,
+    zoom: f32,
+    data_window: IntegerBounds,
+    display_window: IntegerBounds,
+) {
+    let offset = display_window.position - data_window.position;
+    let display_rect = egui::Rect::from_min_size(
+        image_rect.min + egui::vec2(offset.x() as f32, offset.y() as f32) * zoom,
+        egui::vec2(display_window.size.width() as f32, display_window.size.height() as f32) * zoom,
+    );
+
+    // the parts of the data window above, below, left, and right of the display window
+    let overscan = Color32::from_black_alpha(128);
+    let inner = display_rect.intersect(image_rect);
+    if inner.is_positive() {
+        let strips = [
+            egui::Rect::from_x_y_ranges(image_rect.x_range(), image_rect.top()..=inner.top()),
+            egui::Rect::from_x_y_ranges(image_rect.x_range(), inner.bottom()..=image_rect.bottom()),
+            egui::Rect::from_x_y_ranges(image_rect.left()..=inner.left(), inner.y_range()),
+            egui::Rect::from_x_y_ranges(inner.right()..=image_rect.right(), inner.y_range()),
+        ];
+
+        for strip in strips {
+            if strip.is_positive() {
+                painter.rect_filled(strip, 0.0, overscan);
+            }
+        }
+    } else {
+        painter.rect_filled(image_rect, 0.0, overscan);
+    }
+
+    let outside = egui::StrokeKind::Outside;
+    let data_stroke = egui::Stroke::new(1.0, Color32::from_gray(128));
+    painter.rect_stroke(image_rect, 0.0, data_stroke, outside);
+    painter.rect_stroke(display_rect, 0.0, egui::Stroke::new(1.0, Color32::YELLOW), outside);
+}
+
+/// Draw a colormap from 0 to 1 as a horizontal bar.
+fn draw_gradient(ui: &mut egui::Ui, name: &str, sample: impl Fn(f32) -> [f32; 3]) {
+    const STEPS: usize = 64;
+
+    ui.label(format!("{name}: 0"));
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(256.0, 12.0), egui::Sense::hover());
+    let step_width = rect.width() / STEPS as f32;
+
+    for step in 0..STEPS {
+        let [r, g, b] = sample((step as f32 + 0.5) / STEPS as f32);
+        let quantize = |value: f32| (value.clamp(0.0, 1.0) * 255.0) as u8;
+        let min = rect.min + egui::vec2(step as f32 * step_width, 0.0);
+        let step_rect = egui::Rect::from_min_size(min, egui::vec2(step_width + 0.5, rect.height()));
+        let color = Color32::from_rgb(quantize(r), quantize(g), quantize(b));
+        ui.painter().rect_filled(step_rect, 0.0, color);
+    }
+
+    ui.label("1 (luminance after exposure)");
+}
+
+/// Draw the histogram of each channel as a line, scaled to the largest count.
+fn draw_histogram(
+    painter: &egui::Painter,
+    rect: egui::Rect,
+    histogram: &Histogram,
+    log_scale: bool,
+) {
+    let scale = |count: u32| {
+        if log_scale {
+            (count as f32).ln_1p()
+        } else {
+            count as f32
+        }
+    };
+
+    let curves = [
+        (&histogram.red, Color32::RED),
+        (&histogram.green, Color32::GREEN),
+        (&histogram.blue, Color32::from_rgb(64, 128, 255)),
+        (&histogram.luma, Color32::LIGHT_GRAY),
+    ];
+
+    let max = curves
+        .iter()
+        .flat_map(|(bins, _)| bins.iter())
+        .map(|&count| scale(count))
+        .fold(0.0, f32::max);
+
+    if max <= 0.0 {
+        return;
+    }
+
+    let plot = rect.shrink(4.0);
+    for (bins, color) in curves {
+        let points = bins
+            .iter()
+            .enumerate()
+            .map(|(index, &count)| {
+                egui::pos2(
+                    plot.left() + (index as f32 + 0.5) / bins.len() as f32 * plot.width(),
+                    plot.bottom() - scale(count) / max * plot.height(),
+                )
+            })
+            .collect();
+
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+    }
+
+    let (min_value, max_value) = histogram.range;
+    let font = egui::FontId::monospace(10.0);
+    let text_color = Color32::from_gray(160);
+    let (min_text, max_text) = (format!("{min_value:.3}"), format!("{max_value:.3}"));
+    painter.text(plot.left_top(), egui::Align2::LEFT_TOP, min_text, font.clone(), text_color);
+    painter.text(plot.right_top(), egui::Align2::RIGHT_TOP, max_text, font, text_color);
+}
+
+/// Render the waveform with one pixel per column and value bin, brighter where more samples are.
+/// The parade shows red, green, and blue side by side instead of overlaid.
+fn waveform_image(waveform: &Waveform, parade: bool) -> ColorImage {
+    let columns = waveform.columns;
+    let channels = [&waveform.red, &waveform.green, &waveform.blue];
+
+    let max = channels.iter().flat_map(|counts| counts.iter()).copied().max().unwrap_or(0);
+    let max = (max.max(1) as f32).ln_1p();
+    let intensity = |count: u32| ((count as f32).ln_1p() / max * 255.0) as u8;
+
+    let width = if parade { 3 * columns } else { columns };
+    let mut pixels = vec![[0_u8, 0, 0, 255]; width * WAVEFORM_BINS];
+
+    for (channel_index, counts) in channels.iter().enumerate() {
+        for column in 0..columns {
+            let x = if parade { channel_index * columns + column } else { column };
+
+            for (bin, &count) in Waveform::column(counts, column).iter().enumerate() {
+                // high values at the top
+                let y = WAVEFORM_BINS - 1 - bin;
+                pixels[y * width + x][channel_index] = intensity(count);
+            }
+        }
+    }
+
+    ColorImage::from_rgba_premultiplied([width, WAVEFORM_BINS], &pixels.concat())
+}
+
+/// Plot the number of pixels for each sample count, on a logarithmic scale,
+/// as a single bar per count, or as the maximum of the counts that share a column.
+fn draw_sample_count_histogram(ui: &mut egui::Ui, histogram: &[usize]) {
+    let size = egui::vec2(ui.available_width(), 120.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, Color32::from_gray(16));
+
+    let max = histogram.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return;
+    }
+
+    let plot = rect.shrink(4.0);
+    let columns = histogram.len().min(plot.width().max(1.0) as usize);
+    let scale = (max as f32).ln_1p();
+
+    // the sample counts of a column, and the largest number of pixels with one of these counts
+    let column_counts = |column: usize| {
+        let first = column * histogram.len() / columns;
+        let last = ((column + 1) * histogram.len() / columns).max(first + 1);
+        let pixels = histogram[first..last].iter().copied().max().unwrap_or(0);
+        (first..last, pixels)
+    };
+
+    for column in 0..columns {
+        let (_, pixels) = column_counts(column);
+        if pixels == 0 {
+            continue;
+        }
+
+        let left = plot.left() + column as f32 / columns as f32 * plot.width();
+        let right = plot.left() + (column + 1) as f32 / columns as f32 * plot.width();
+        let top = plot.bottom() - (pixels as f32).ln_1p() / scale * plot.height();
+
+        painter.rect_filled(
+            egui::Rect::from_min_max(egui::pos2(left, top), egui::pos2(right, plot.bottom())),
+            0.0,
+            Color32::from_gray(180),
+        );
+    }
+
+    if let Some(position) = response.hover_pos() {
+        let column = ((position.x - plot.left()) / plot.width() * columns as f32).max(0.0);
+        let (counts, pixels) = column_counts((column as usize).min(columns - 1));
+
+        let text = if counts.len() == 1 {
+            format!("{} pixels with {} samples", pixels, counts.start)
+        } else {
+            format!("up to {} pixels with {} to {} samples", pixels, counts.start, counts.end - 1)
+        };
+
+        response.on_hover_text(text);
+    }
+
+    let font = egui::FontId::monospace(10.0);
+    let text_color = Color32::from_gray(160);
+    let max_text = format!("{}", histogram.len() - 1);
+    painter.text(plot.left_top(), egui::Align2::LEFT_TOP, "0", font.clone(), text_color);
+    painter.text(plot.right_top(), egui::Align2::RIGHT_TOP, max_text, font, text_color);
+}
+
+/// A number of bytes in the largest binary unit that keeps the value at least one.
+fn format_bytes(count: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = count as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{count} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Plot the alpha of each deep sample over its depth range,
+/// and the accumulated alpha of the pixel from front to back.
+fn draw_depth_alpha_plot(ui: &mut egui::Ui, samples: &[DeepSampleInfo]) {
+    let size = egui::vec2(ui.available_width(), 120.0);
+    let (rect, _response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, Color32::from_gray(24));
+
+    let near = samples.iter().map(|s| s.z.min(s.z_back)).fold(f32::INFINITY, f32::min);
+    let far = samples.iter().map(|s| s.z.max(s.z_back)).fold(f32::NEG_INFINITY, f32::max);
+
+    if !near.is_finite() || !far.is_finite() {
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "No samples",
+            egui::FontId::default(),
+            Color32::from_gray(120),
+        );
+        return;
+    }
+
+    let plot = rect.shrink(6.0);
+    let range = (far - near).max(f32::EPSILON);
+    let to_screen = |z: f32, alpha: f32| {
+        egui::pos2(
+            plot.left() + (z - near) / range * plot.width(),
+            plot.bottom() - alpha.clamp(0.0, 1.0) * plot.height(),
+        )
+    };
+
+    // each sample as a segment from Z to ZBack at its alpha
+    for sample in samples {
+        let [r, g, b, a] = sample.rgba;
+        let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0) as u8;
+        let color = Color32::from_rgb(to_byte(r), to_byte(g), to_byte(b));
+
+        let front = to_screen(sample.z, a);
+        let back = to_screen(sample.z_back, a);
+        painter.line_segment([front, back], egui::Stroke::new(2.0, Color32::LIGHT_GRAY));
+        painter.circle_filled(front, 3.0, color);
+    }
+
+    // accumulated alpha, compositing the samples from front to back
+    let mut sorted: Vec<&DeepSampleInfo> = samples.iter().collect();
+    sorted.sort_by(|a, b| a.z.total_cmp(&b.z));
+
+    let mut accumulated = 0.0;
+    let mut points = vec![to_screen(near, 0.0)];
+    for sample in sorted {
+        points.push(to_screen(sample.z, accumulated));
+        accumulated += sample.rgba[3] * (1.0 - accumulated);
+        points.push(to_screen(sample.z, accumulated));
+    }
+    points.push(to_screen(far, accumulated));
+
+    painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, Color32::YELLOW)));
+
+    painter.text(
+        plot.left_top(),
+        egui::Align2::LEFT_TOP,
+        format!("alpha over Z {near:.3}..{far:.3}"),
+        egui::FontId::monospace(10.0),
+        Color32::from_gray(160),
+    );
 }
 
 // === DockTabs wrapper for egui_dock ===
@@ -0,0 +1,119 @@
+//! Cache of decoded channels, reused when the texture is regenerated.
+
+use std::rc::Rc;
+
+use crate::view::state::ImageSlot;
+
+/// Default memory budget of the channel cache, in bytes.
+pub const DEFAULT_CACHE_BUDGET: usize = 512 * 1024 * 1024;
+
+/// Identifies a decoded channel of the loaded image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelKey {
+    /// Whether the channel belongs to the main image or the image it is compared with.
+    pub image: ImageSlot,
+
+    /// The index of the layer (part) in the image.
+    pub layer: usize,
+
+    /// The index of the channel in the layer.
+    pub channel: usize,
+}
+
+/// Least-recently-used cache of channels that have been converted to `f32`.
+///
+/// Display-only changes, like exposure or the channel mode, regenerate the texture.
+/// They reuse the decoded channels instead of converting all samples of the image again.
+/// When the cached channels exceed the memory budget, the least recently used channels are dropped.
+#[derive(Debug)]
+pub struct ChannelCache {
+    /// The least recently used channel comes first.
+    entries: Vec<(ChannelKey, Rc<[f32]>)>,
+    size_in_bytes: usize,
+    budget_in_bytes: usize,
+}
+
+impl ChannelCache {
+    /// An empty cache that keeps at most the specified number of bytes.
+    /// The most recently used channel is always kept, even if it exceeds the budget.
+    pub fn new(budget_in_bytes: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            size_in_bytes: 0,
+            budget_in_bytes,
+        }
+    }
+
+    /// The cached channel, or decode and insert the channel if it is not cached yet.
+    pub fn get_or_insert_with(
+        &mut self,
+        key: ChannelKey,
+        decode: impl FnOnce() -> Vec<f32>,
+    ) -> Rc<[f32]> {
+        if let Some(index) = self.entries.iter().position(|(cached, _)| *cached == key) {
+            let entry = self.entries.remove(index);
+            let values = entry.1.clone();
+            self.entries.push(entry);
+            return values;
+        }
+
+        let values: Rc<[f32]> = decode().into();
+        self.size_in_bytes += byte_size(&values);
+        self.entries.push((key, values.clone()));
+
+        while self.size_in_bytes > self.budget_in_bytes && self.entries.len() > 1 {
+            let (_, evicted) = self.entries.remove(0);
+            self.size_in_bytes -= byte_size(&evicted);
+        }
+
+        values
+    }
+
+    /// Forget all channels, for example because the image has changed.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size_in_bytes = 0;
+    }
+
+    /// The number of bytes of all cached channels.
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+}
+
+fn byte_size(values: &[f32]) -> usize {
+    values.len() * std::mem::size_of::<f32>()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn evict_least_recently_used() {
+        let key = |channel| ChannelKey {
+            image: ImageSlot::A,
+            layer: 0,
+            channel,
+        };
+        let mut cache = ChannelCache::new(2 * 4 * 4);
+
+        cache.get_or_insert_with(key(0), || vec![0.0; 4]);
+        cache.get_or_insert_with(key(1), || vec![1.0; 4]);
+
+        // uses channel 0, so that channel 1 is evicted next
+        let cached = cache.get_or_insert_with(key(0), || unreachable!());
+        assert_eq!(&*cached, &[0.0; 4]);
+
+        cache.get_or_insert_with(key(2), || vec![2.0; 4]);
+        assert_eq!(cache.size_in_bytes(), 2 * 4 * 4);
+
+        let decoded = cache.get_or_insert_with(key(1), || vec![3.0; 4]);
+        assert_eq!(&*decoded, &[3.0; 4]);
+
+        cache.get_or_insert_with(key(2), || unreachable!());
+
+        cache.clear();
+        assert_eq!(cache.size_in_bytes(), 0);
+    }
+}
@@ -0,0 +1,285 @@
+//! Colormaps for analysis display modes: false color exposure bands,
+//! heatmaps for single channel data, and 1D LUTs loaded from files.
+//!
+//! All colors are display colors from zero to one, without any further display transform.
+
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+/// The middle gray the false color bands are relative to.
+pub const MIDDLE_GRAY: f32 = 0.18;
+
+/// A range of scene luminance highlighted by the false color mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FalseColorBand {
+    /// The lower bound in stops relative to middle gray, inclusive.
+    pub min_stops: f32,
+    /// The upper bound in stops relative to middle gray, exclusive.
+    pub max_stops: f32,
+    pub color: [u8; 3],
+    pub label: &'static str,
+}
+
+/// The highlighted bands, in ascending order, similar to the false color of ARRI cameras.
+/// Luminance between the bands is displayed in gray.
+pub const FALSE_COLOR_BANDS: &[FalseColorBand] = &[
+    FalseColorBand {
+        min_stops: f32::NEG_INFINITY,
+        max_stops: -6.0,
+        color: [128, 0, 192],
+        label: "Black clip",
+    },
+    FalseColorBand {
+        min_stops: -6.0,
+        max_stops: -5.0,
+        color: [0, 96, 255],
+        label: "Near black",
+    },
+    FalseColorBand {
+        min_stops: -0.25,
+        max_stops: 0.25,
+        color: [0, 200, 0],
+        label: "18% gray",
+    },
+    FalseColorBand {
+        min_stops: 0.75,
+        max_stops: 1.25,
+        color: [255, 128, 192],
+        label: "Skin (+1)",
+    },
+    FalseColorBand {
+        min_stops: 4.5,
+        max_stops: 5.5,
+        color: [255, 230, 0],
+        label: "Near white",
+    },
+    FalseColorBand {
+        min_stops: 5.5,
+        max_stops: f32::INFINITY,
+        color: [255, 0, 0],
+        label: "White clip",
+    },
+];
+
+/// The false color band of a linear scene luminance, or `None` for luminance between the bands.
+pub fn false_color(luminance: f32) -> Option<&'static FalseColorBand> {
+    if luminance <= 0.0 {
+        return FALSE_COLOR_BANDS.first();
+    }
+
+    let stops = (luminance / MIDDLE_GRAY).log2();
+    FALSE_COLOR_BANDS
+        .iter()
+        .find(|band| stops >= band.min_stops && stops < band.max_stops)
+}
+
+/// The turbo colormap, for values from zero to one.
+/// Uses the polynomial approximation by Ruofei Du.
+pub fn turbo(t: f32) -> [f32; 3] {
+    const RED: [f32; 6] = [
+        0.135_721_4,
+        4.615_392_6,
+        -42.660_32,
+        132.131_08,
+        -152.942_4,
+        59.286_38,
+    ];
+    const GREEN: [f32; 6] = [
+        0.091_402_6,
+        2.194_188_4,
+        4.842_966_6,
+        -14.185_033,
+        4.277_298_6,
+        2.829_566,
+    ];
+    const BLUE: [f32; 6] = [
+        0.106_673_3,
+        12.641_946,
+        -60.582_05,
+        110.362_77,
+        -89.903_11,
+        27.348_25,
+    ];
+
+    let t = t.clamp(0.0, 1.0);
+    let channel = |coefficients: &[f32; 6]| polynomial(coefficients, t).clamp(0.0, 1.0);
+    [channel(&RED), channel(&GREEN), channel(&BLUE)]
+}
+
+/// The viridis colormap, for values from zero to one.
+/// Uses a polynomial fit of the original colormap.
+pub fn viridis(t: f32) -> [f32; 3] {
+    const RED: [f32; 7] = [
+        0.277_727_3,
+        0.105_093,
+        -0.330_861_8,
+        -4.634_230_6,
+        6.228_27,
+        4.776_385,
+        -5.435_456,
+    ];
+    const GREEN: [f32; 7] = [
+        0.005_407_3,
+        1.404_613_5,
+        0.214_847_6,
+        -5.799_101,
+        14.179_933,
+        -13.745_145,
+        4.645_852_6,
+    ];
+    const BLUE: [f32; 7] = [
+        0.334_099_8,
+        1.384_590_2,
+        0.095_095_2,
+        -19.332_441,
+        56.690_55,
+        -65.353_03,
+        26.312_435,
+    ];
+
+    let t = t.clamp(0.0, 1.0);
+    let channel = |coefficients: &[f32; 7]| polynomial(coefficients, t).clamp(0.0, 1.0);
+    [channel(&RED), channel(&GREEN), channel(&BLUE)]
+}
+
+/// Evaluate a polynomial with the coefficient of the constant term first.
+fn polynomial(coefficients: &[f32], t: f32) -> f32 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |sum, &coefficient| sum * t + coefficient)
+}
+
+/// A 1D LUT of display colors, mapping values from zero to one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Colormap {
+    /// The file name, for the UI.
+    pub name: String,
+
+    /// At least two colors, evenly spaced from zero to one.
+    colors: Vec<[f32; 3]>,
+}
+
+impl Colormap {
+    /// Read a `.cube` file with a 1D LUT, or a text file with one `r g b` color per line.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+
+        Self::parse(&text, name)
+    }
+
+    /// Parse the colors of a `.cube` file with a 1D LUT, or of one `r g b` color per line.
+    /// Ignores comments and keywords, like `TITLE` and `LUT_1D_SIZE`.
+    pub fn parse(text: &str, name: impl Into<String>) -> Result<Self> {
+        let mut colors = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            let starts_with_number = line.chars().next().map_or(false, |c| {
+                c.is_ascii_digit() || c == '-' || c == '+' || c == '.'
+            });
+
+            if !starts_with_number {
+                continue;
+            }
+
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|_| Error::invalid(format!("colormap entry `{}`", line)))?;
+
+            match values.as_slice() {
+                &[r, g, b] => colors.push([r, g, b]),
+                _ => return Err(Error::invalid(format!("colormap entry `{}`", line))),
+            }
+        }
+
+        if colors.len() < 2 {
+            return Err(Error::invalid("colormap with less than two colors"));
+        }
+
+        Ok(Colormap {
+            name: name.into(),
+            colors,
+        })
+    }
+
+    /// The interpolated color of a value from zero to one.
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let position = t.clamp(0.0, 1.0) * (self.colors.len() - 1) as f32;
+        let index = (position as usize).min(self.colors.len() - 2);
+        let fraction = position - index as f32;
+
+        let (low, high) = (self.colors[index], self.colors[index + 1]);
+        [0, 1, 2].map(|channel| low[channel] + (high[channel] - low[channel]) * fraction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn false_color_bands() {
+        let label = |luminance: f32| false_color(luminance).map(|band| band.label);
+
+        assert_eq!(label(0.0), Some("Black clip"));
+        assert_eq!(label(-1.0), Some("Black clip"));
+        assert_eq!(label(0.001), Some("Black clip"));
+        assert_eq!(label(0.18 / 40.0), Some("Near black"));
+        assert_eq!(label(0.18), Some("18% gray"));
+        assert_eq!(label(0.36), Some("Skin (+1)"));
+        assert_eq!(label(0.18 * 32.0), Some("Near white"));
+        assert_eq!(label(100.0), Some("White clip"));
+        assert_eq!(label(0.09), None);
+        assert_eq!(label(1.0), None);
+    }
+
+    #[test]
+    fn heatmaps() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.02);
+
+        // turbo goes from blue over green to red
+        let (low, middle, high) = (turbo(0.1), turbo(0.5), turbo(0.9));
+        assert!(low[2] > low[0] && low[2] > low[1], "{:?}", low);
+        assert!(
+            middle[1] > middle[0] && middle[1] > middle[2],
+            "{:?}",
+            middle
+        );
+        assert!(high[0] > high[1] && high[0] > high[2], "{:?}", high);
+
+        // the first and last colors of the original colormap
+        assert!(
+            close(viridis(0.0), [0.267, 0.005, 0.329]),
+            "{:?}",
+            viridis(0.0)
+        );
+        assert!(
+            close(viridis(1.0), [0.993, 0.906, 0.144]),
+            "{:?}",
+            viridis(1.0)
+        );
+        assert_eq!(turbo(-1.0), turbo(0.0));
+    }
+
+    #[test]
+    fn parse_and_sample() {
+        let cube = "# gray ramp\nTITLE \"ramp\"\nLUT_1D_SIZE 3\n0 0 0\n0.5 0.25 0\n1 1 1\n";
+        let colormap = Colormap::parse(cube, "ramp.cube").unwrap();
+
+        assert_eq!(colormap.sample(0.0), [0.0, 0.0, 0.0]);
+        assert_eq!(colormap.sample(0.25), [0.25, 0.125, 0.0]);
+        assert_eq!(colormap.sample(1.0), [1.0, 1.0, 1.0]);
+        assert_eq!(colormap.sample(2.0), [1.0, 1.0, 1.0]);
+
+        assert!(Colormap::parse("0 0 0\n", "single").is_err());
+        assert!(Colormap::parse("0 0\n1 1 1\n", "pairs").is_err());
+    }
+}
@@ -0,0 +1,394 @@
+//! Display transforms: how the linear colors of the file become the colors on the monitor.
+//!
+//! The built-in pipeline interprets the file colors in the input color space,
+//! converts them to linear Rec. 709, applies the view transform, converts them
+//! to the primaries of the display, and encodes them with the transfer function of the display.
+//! A custom transform, from an OpenColorIO config or a baked LUT file, replaces the whole pipeline
+//! and receives the exposed file colors unchanged.
+
+use crate::color::ColorSpace;
+use crate::error::Result;
+use crate::export::linear_to_srgb;
+use crate::interop::ocio::{OcioConfig, Processor};
+use crate::math::Vec2;
+use crate::meta::attribute::{Chromaticities, Matrix3x3};
+
+/// How the colors of the file are interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputSpace {
+    /// Use the chromaticities attribute of the file, or linear Rec. 709 without the attribute.
+    #[default]
+    FromFile,
+    /// Linear colors with the primaries of sRGB.
+    LinearRec709,
+    /// Linear colors with the ACES AP1 primaries.
+    AcesCg,
+}
+
+impl InputSpace {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::FromFile => "From File",
+            Self::LinearRec709 => "Linear Rec.709",
+            Self::AcesCg => "ACEScg",
+        }
+    }
+
+    pub const fn all() -> &'static [Self] {
+        &[Self::FromFile, Self::LinearRec709, Self::AcesCg]
+    }
+}
+
+/// Maps scene colors to display colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViewTransform {
+    /// Clip the colors to the range of the display.
+    #[default]
+    Raw,
+    /// A fit of the ACES reference rendering and output transforms.
+    AcesRrt,
+    /// A filmic curve with a soft shoulder.
+    Filmic,
+}
+
+impl ViewTransform {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Raw => "Raw",
+            Self::AcesRrt => "ACES RRT",
+            Self::Filmic => "Filmic",
+        }
+    }
+
+    pub const fn all() -> &'static [Self] {
+        &[Self::Raw, Self::AcesRrt, Self::Filmic]
+    }
+}
+
+/// The primaries and the transfer function of the monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayDevice {
+    /// Rec. 709 primaries with the sRGB transfer function.
+    #[default]
+    Srgb,
+    /// Rec. 709 primaries with a gamma of 2.4.
+    Rec1886,
+    /// P3 primaries and a D65 white point, with the sRGB transfer function.
+    DisplayP3,
+    /// Rec. 709 primaries without any transfer function, to inspect the values.
+    Linear,
+}
+
+impl DisplayDevice {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Srgb => "sRGB",
+            Self::Rec1886 => "Rec.1886",
+            Self::DisplayP3 => "Display P3",
+            Self::Linear => "Linear",
+        }
+    }
+
+    pub const fn all() -> &'static [Self] {
+        &[Self::Srgb, Self::Rec1886, Self::DisplayP3, Self::Linear]
+    }
+
+    /// The linear color space of the display.
+    fn color_space(self) -> ColorSpace {
+        match self {
+            Self::DisplayP3 => ColorSpace::Custom(Chromaticities {
+                red: Vec2(0.680, 0.320),
+                green: Vec2(0.265, 0.690),
+                blue: Vec2(0.150, 0.060),
+                white: Vec2(0.3127, 0.3290),
+            }),
+            _ => ColorSpace::LinearRec709,
+        }
+    }
+
+    /// Convert a linear value to the value sent to the display.
+    fn encode(self, linear: f32) -> f32 {
+        match self {
+            Self::Srgb | Self::DisplayP3 => linear_to_srgb(linear),
+            Self::Rec1886 => linear.max(0.0).powf(1.0 / 2.4),
+            Self::Linear => linear,
+        }
+    }
+}
+
+/// The selection of the built-in display pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ColorSettings {
+    pub input: InputSpace,
+    pub view: ViewTransform,
+    pub display: DisplayDevice,
+}
+
+/// Selects a display and view of an OpenColorIO config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OcioSelection {
+    /// The color space of the file, a name or role of the config, like `scene_linear`.
+    pub color_space: String,
+    pub display: String,
+    pub view: String,
+}
+
+/// The color spaces, displays, and views of a loaded OpenColorIO config, for the UI.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OcioInfo {
+    pub color_spaces: Vec<String>,
+    /// Each display with its views.
+    pub displays: Vec<(String, Vec<String>)>,
+    pub selection: Option<OcioSelection>,
+}
+
+impl OcioInfo {
+    /// The choices of the config, and its default display and view.
+    pub fn of_config(config: &OcioConfig) -> Self {
+        let displays: Vec<(String, Vec<String>)> = config
+            .displays()
+            .map(|display| {
+                let views = config
+                    .views(display)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect();
+                (display.to_string(), views)
+            })
+            .collect();
+
+        let selection = config.default_display().and_then(|display| {
+            Some(OcioSelection {
+                color_space: "scene_linear".to_string(),
+                display: display.to_string(),
+                view: config.default_view(display)?.to_string(),
+            })
+        });
+
+        OcioInfo {
+            color_spaces: config.color_spaces().map(str::to_string).collect(),
+            displays,
+            selection,
+        }
+    }
+}
+
+/// Converts the exposed linear colors of the file to display colors.
+#[derive(Debug, Clone, Default)]
+pub struct ColorPipeline {
+    settings: ColorSettings,
+
+    /// Converts the file primaries to Rec. 709, if they differ.
+    input_matrix: Option<Matrix3x3>,
+
+    /// Converts Rec. 709 to the display primaries, if they differ.
+    display_matrix: Option<Matrix3x3>,
+
+    /// Replaces the built-in pipeline.
+    custom: Option<Processor>,
+}
+
+impl ColorPipeline {
+    /// Create the built-in pipeline for a file with these chromaticities.
+    /// Fails if the chromaticities cannot be converted.
+    pub fn new(settings: ColorSettings, file: Option<Chromaticities>) -> Result<Self> {
+        let working = ColorSpace::LinearRec709;
+        let conversion = |from: ColorSpace, to: ColorSpace| -> Result<Option<Matrix3x3>> {
+            if from == to {
+                Ok(None)
+            } else {
+                from.conversion_to(&to).map(Some)
+            }
+        };
+
+        let input = match settings.input {
+            InputSpace::FromFile => file.map_or(working, ColorSpace::from_chromaticities),
+            InputSpace::LinearRec709 => working,
+            InputSpace::AcesCg => ColorSpace::AcesCg,
+        };
+
+        let input_matrix = conversion(input, working)?;
+        let display_matrix = conversion(working, settings.display.color_space())?;
+
+        Ok(ColorPipeline {
+            settings,
+            input_matrix,
+            display_matrix,
+            custom: None,
+        })
+    }
+
+    /// Replace the built-in pipeline with a processor of an OCIO config or a LUT file.
+    pub fn with_custom(self, custom: Option<Processor>) -> Self {
+        ColorPipeline { custom, ..self }
+    }
+
+    pub fn settings(&self) -> ColorSettings {
+        self.settings
+    }
+
+    pub fn custom(&self) -> Option<&Processor> {
+        self.custom.as_ref()
+    }
+
+    /// The matrix of `to_working`, or `None` if the colors are not converted.
+    #[cfg(feature = "view-gpu")]
+    pub fn input_matrix(&self) -> Option<Matrix3x3> {
+        self.input_matrix.filter(|_| self.custom.is_none())
+    }
+
+    /// Convert a linear color from the input color space to linear Rec. 709,
+    /// unless a custom transform expects the colors of the file.
+    pub fn to_working(&self, rgb: (f32, f32, f32)) -> (f32, f32, f32) {
+        match &self.input_matrix {
+            Some(matrix) if self.custom.is_none() => Chromaticities::transform_rgb(matrix, rgb),
+            _ => rgb,
+        }
+    }
+
+    /// Convert an exposed linear color to the encoded display color.
+    /// The result is not clamped.
+    pub fn display(&self, (r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
+        if let Some(processor) = &self.custom {
+            let [r, g, b] = processor.apply_rgb([r, g, b]);
+            return (r, g, b);
+        }
+
+        let rgb = match self.settings.view {
+            ViewTransform::Raw => (r, g, b),
+            ViewTransform::AcesRrt => aces_fitted((r, g, b)),
+            ViewTransform::Filmic => (filmic(r), filmic(g), filmic(b)),
+        };
+
+        let (r, g, b) = match &self.display_matrix {
+            Some(matrix) => Chromaticities::transform_rgb(matrix, rgb),
+            None => rgb,
+        };
+
+        let display = self.settings.display;
+        (display.encode(r), display.encode(g), display.encode(b))
+    }
+}
+
+/// Stephen Hill's fit of the ACES RRT and the sRGB ODT, for linear Rec. 709 colors.
+/// Returns linear Rec. 709 colors in the range from zero to one.
+fn aces_fitted(rgb: (f32, f32, f32)) -> (f32, f32, f32) {
+    // converts to AP1 and applies the saturation of the RRT
+    const INPUT: Matrix3x3 = [
+        0.59719, 0.35458, 0.04823, //
+        0.07600, 0.90834, 0.01566, //
+        0.02840, 0.13383, 0.83777,
+    ];
+
+    const OUTPUT: Matrix3x3 = [
+        1.60475, -0.53108, -0.07367, //
+        -0.10208, 1.10813, -0.00605, //
+        -0.00327, -0.07276, 1.07602,
+    ];
+
+    let curve = |v: f32| {
+        let a = v * (v + 0.024_578_6) - 0.000_090_537;
+        let b = v * (0.983_729 * v + 0.432_951) + 0.238_081;
+        a / b
+    };
+
+    let (r, g, b) = Chromaticities::transform_rgb(&INPUT, rgb);
+    let rgb = (curve(r), curve(g), curve(b));
+    let (r, g, b) = Chromaticities::transform_rgb(&OUTPUT, rgb);
+    (r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0))
+}
+
+/// John Hable's filmic curve, with a white point of 11.2 and an exposure bias of two.
+/// Returns values from zero to one.
+fn filmic(value: f32) -> f32 {
+    let curve = |x: f32| {
+        let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+        (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+    };
+
+    (curve(value.max(0.0) * 2.0) / curve(11.2)).min(1.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+        let difference = (actual.0 - expected.0)
+            .abs()
+            .max((actual.1 - expected.1).abs())
+            .max((actual.2 - expected.2).abs());
+
+        assert!(difference < 1e-3, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn default_pipeline_encodes_srgb() {
+        let pipeline = ColorPipeline::new(ColorSettings::default(), None).unwrap();
+        assert_close(pipeline.to_working((0.2, 0.4, 0.6)), (0.2, 0.4, 0.6));
+        assert_close(pipeline.display((0.0, 1.0, 0.5)), (0.0, 1.0, 0.735_357));
+    }
+
+    #[test]
+    fn view_transforms_compress_highlights() {
+        for view in [ViewTransform::AcesRrt, ViewTransform::Filmic] {
+            let settings = ColorSettings {
+                view,
+                display: DisplayDevice::Linear,
+                ..ColorSettings::default()
+            };
+
+            let pipeline = ColorPipeline::new(settings, None).unwrap();
+            let (black, _, _) = pipeline.display((0.0, 0.0, 0.0));
+            let (mid, _, _) = pipeline.display((0.18, 0.18, 0.18));
+            let (bright, _, _) = pipeline.display((16.0, 16.0, 16.0));
+
+            assert!(black.abs() < 0.01, "{:?}", view);
+            assert!(mid > 0.05 && mid < 0.3, "{:?}", view);
+            assert!(bright > 0.9 && bright <= 1.0, "{:?}", view);
+        }
+    }
+
+    #[test]
+    fn displays_keep_white() {
+        for &display in DisplayDevice::all() {
+            let settings = ColorSettings {
+                display,
+                ..ColorSettings::default()
+            };
+
+            let pipeline = ColorPipeline::new(settings, None).unwrap();
+            assert_close(pipeline.display((1.0, 1.0, 1.0)), (1.0, 1.0, 1.0));
+        }
+
+        let p3 = ColorSettings {
+            display: DisplayDevice::DisplayP3,
+            ..ColorSettings::default()
+        };
+
+        // saturated rec709 red is inside of the p3 gamut
+        let (r, g, b) = ColorPipeline::new(p3, None)
+            .unwrap()
+            .display((1.0, 0.0, 0.0));
+        assert!(r < 1.0 && g > 0.0 && b > 0.0);
+    }
+
+    #[test]
+    fn acescg_input_is_converted() {
+        let settings = ColorSettings {
+            input: InputSpace::AcesCg,
+            ..ColorSettings::default()
+        };
+
+        let pipeline = ColorPipeline::new(settings, None).unwrap();
+        assert_close(pipeline.to_working((0.5, 0.5, 0.5)), (0.5, 0.5, 0.5));
+
+        let (r, g, b) = pipeline.to_working((1.0, 0.0, 0.0));
+        assert!(r > 1.0 && g < 0.0 && b < 0.01);
+
+        // custom transforms receive the colors of the file
+        let custom = pipeline.with_custom(Some(Processor::default()));
+        assert_close(custom.to_working((1.0, 0.0, 0.0)), (1.0, 0.0, 0.0));
+        assert_close(custom.display((2.0, 0.5, 0.0)), (2.0, 0.5, 0.0));
+    }
+}
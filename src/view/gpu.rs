@@ -0,0 +1,235 @@
+//! Displays the image from a half float texture, applying exposure, channel isolation,
+//! clipping overlays, and the display encoding in a fragment shader.
+//!
+//! The worker uploads the image only when its pixels change,
+//! so that dragging the exposure slider does not regenerate the texture.
+
+use std::sync::{Arc, Mutex};
+
+use half::f16;
+use three_d::*;
+
+use crate::meta::attribute::Matrix3x3;
+use crate::view::display::DisplayDevice;
+use crate::view::state::ChannelMode;
+
+const VERTEX_SHADER: &str = "
+in vec2 position;
+out vec2 uvs;
+
+void main() {
+    uvs = vec2(position.x, 1.0 - position.y);
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+}
+";
+
+// mirrors the `flat_pixels` and `display_value` functions of the handler
+const FRAGMENT_SHADER: &str = "
+uniform sampler2D image;
+uniform mat3 input_matrix;
+uniform float exposure;
+uniform float channel;
+uniform float encoding;
+uniform float clipping;
+
+in vec2 uvs;
+layout (location = 0) out vec4 color;
+
+float srgb(float x) {
+    return x <= 0.0031308 ? x * 12.92 : 1.055 * pow(x, 1.0 / 2.4) - 0.055;
+}
+
+void main() {
+    vec4 rgba = texture(image, uvs);
+    vec3 rgb;
+
+    if (channel < 0.5) { rgb = input_matrix * rgba.rgb; }
+    else if (channel < 1.5) { rgb = vec3(rgba.r); }
+    else if (channel < 2.5) { rgb = vec3(rgba.g); }
+    else if (channel < 3.5) { rgb = vec3(rgba.b); }
+    else if (channel < 4.5) { rgb = vec3(rgba.a); }
+    else { rgb = vec3(dot(rgba.rgb, vec3(0.2126, 0.7152, 0.0722))); }
+
+    rgb *= exposure;
+
+    if (clipping > 0.5 && max(rgb.r, max(rgb.g, rgb.b)) > 1.0) {
+        color = vec4(1.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    if (clipping > 0.5 && min(rgb.r, min(rgb.g, rgb.b)) < 0.0) {
+        color = vec4(0.0, 0.0, 1.0, 1.0);
+        return;
+    }
+
+    if (encoding > 1.5) { rgb = pow(max(rgb, 0.0), vec3(1.0 / 2.4)); }
+    else if (encoding > 0.5) { rgb = vec3(srgb(rgb.r), srgb(rgb.g), srgb(rgb.b)); }
+
+    color = vec4(clamp(rgb, 0.0, 1.0), 1.0);
+}
+";
+
+/// The `channel` uniform of the shader.
+fn channel_index(channel_mode: ChannelMode) -> Option<f32> {
+    match channel_mode {
+        ChannelMode::Color => Some(0.0),
+        ChannelMode::Red => Some(1.0),
+        ChannelMode::Green => Some(2.0),
+        ChannelMode::Blue => Some(3.0),
+        ChannelMode::Alpha => Some(4.0),
+        ChannelMode::Luminance => Some(5.0),
+        ChannelMode::Depth | ChannelMode::Custom(_) => None,
+    }
+}
+
+/// The `encoding` uniform of the shader. Displays with other primaries are not supported.
+fn encoding_index(display: DisplayDevice) -> Option<f32> {
+    match display {
+        DisplayDevice::Linear => Some(0.0),
+        DisplayDevice::Srgb => Some(1.0),
+        DisplayDevice::Rec1886 => Some(2.0),
+        DisplayDevice::DisplayP3 => None,
+    }
+}
+
+/// The display settings applied by the shader.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayUniforms {
+    pub exposure: f32,
+    pub channel_mode: ChannelMode,
+    pub display: DisplayDevice,
+    pub clipping: bool,
+}
+
+/// The uploaded image and the shader that displays it.
+pub struct GpuImage {
+    context: Context,
+    program: Program,
+    quad: VertexBuffer<Vec2>,
+    texture: Option<Texture2D>,
+    input_matrix: Mat3,
+}
+
+impl std::fmt::Debug for GpuImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuImage")
+            .field("has_texture", &self.texture.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl GpuImage {
+    /// Compile the shader. Fails if the context does not support it.
+    pub fn new(gl: Arc<eframe::glow::Context>) -> std::result::Result<Self, String> {
+        let context = Context::from_gl_context(gl).map_err(|e| e.to_string())?;
+        let program = Program::from_source(&context, VERTEX_SHADER, FRAGMENT_SHADER)
+            .map_err(|e| e.to_string())?;
+
+        let corners = [
+            vec2(0.0, 0.0),
+            vec2(1.0, 0.0),
+            vec2(1.0, 1.0),
+            vec2(0.0, 0.0),
+            vec2(1.0, 1.0),
+            vec2(0.0, 1.0),
+        ];
+
+        Ok(Self {
+            quad: VertexBuffer::new_with_data(&context, &corners),
+            context,
+            program,
+            texture: None,
+            input_matrix: Mat3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0),
+        })
+    }
+
+    /// Replace the displayed pixels. The input matrix converts the file primaries to
+    /// the display primaries, and is applied in the color channel mode only.
+    pub fn upload(
+        &mut self,
+        (width, height): (usize, usize),
+        pixels: &[[f16; 4]],
+        input_matrix: Matrix3x3,
+    ) {
+        let (width, height) = (width as u32, height as u32);
+        let same_size = self.texture.as_ref().map_or(false, |texture| {
+            texture.width() == width && texture.height() == height
+        });
+
+        if !same_size {
+            self.texture = Some(Texture2D::new_empty::<[f16; 4]>(
+                &self.context,
+                width,
+                height,
+                Interpolation::Linear,
+                Interpolation::Linear,
+                None,
+                Wrapping::ClampToEdge,
+                Wrapping::ClampToEdge,
+            ));
+        }
+
+        if let Some(texture) = &mut self.texture {
+            texture.fill(pixels);
+        }
+
+        // three-d matrices are column major
+        let m = input_matrix;
+        self.input_matrix = Mat3::new(m[0], m[3], m[6], m[1], m[4], m[7], m[2], m[5], m[8]);
+    }
+
+    /// Draw the image into the viewport with the display settings.
+    fn paint(&self, viewport: Viewport, uniforms: DisplayUniforms) {
+        let Some(texture) = &self.texture else { return };
+        let (Some(channel), Some(encoding)) = (
+            channel_index(uniforms.channel_mode),
+            encoding_index(uniforms.display),
+        ) else {
+            return;
+        };
+
+        self.program.use_texture("image", texture);
+        self.program.use_uniform("input_matrix", self.input_matrix);
+        self.program
+            .use_uniform("exposure", 2.0_f32.powf(uniforms.exposure));
+        self.program.use_uniform("channel", channel);
+        self.program.use_uniform("encoding", encoding);
+        self.program
+            .use_uniform("clipping", if uniforms.clipping { 1.0_f32 } else { 0.0 });
+        self.program.use_vertex_attribute("position", &self.quad);
+
+        let render_states = RenderStates {
+            depth_test: DepthTest::Always,
+            write_mask: WriteMask::COLOR,
+            ..Default::default()
+        };
+
+        self.program.draw_arrays(render_states, viewport, 6);
+    }
+}
+
+/// Paint the image into the rectangle, within the clip rectangle of the painter.
+pub fn paint_callback(
+    image: Arc<Mutex<GpuImage>>,
+    rect: egui::Rect,
+    uniforms: DisplayUniforms,
+) -> egui::PaintCallback {
+    let callback = eframe::egui_glow::CallbackFn::new(move |info, _painter| {
+        let pixels = info.viewport_in_pixels();
+        let viewport = Viewport {
+            x: pixels.left_px,
+            y: pixels.from_bottom_px,
+            width: pixels.width_px as u32,
+            height: pixels.height_px as u32,
+        };
+
+        if let Ok(image) = image.lock() {
+            image.paint(viewport, uniforms);
+        }
+    });
+
+    egui::PaintCallback {
+        rect,
+        callback: Arc::new(callback),
+    }
+}
@@ -1,15 +1,54 @@
 //! Worker thread handler for image processing.
 
-use std::path::PathBuf;
-use std::sync::mpsc::{Receiver, Sender};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::{Duration, Instant};
 
 use egui::Color32;
 
-use crate::image::read::deep::read_first_deep_layer_from_file;
-use crate::image::Layers;
+use crate::block::cancel::CancellationToken;
+use crate::block::lines::LineRef;
+use crate::block::reader::ChunksReader;
+use crate::export::{BitDepth, ExportFormat, ExportOptions, Transfer};
+use crate::image::cryptomatte::Cryptomatte;
+use crate::import::ImportFormat;
+use crate::image::read::deep::read_deep;
+use crate::image::point_cloud::{PointCloud, Projection};
+use crate::image::resize::resize_nearest;
+use crate::image::{FlatImage, Layers};
+use crate::interop::ocio::{OcioConfig, Processor};
+use crate::meta::describe::JsonValue;
+use crate::meta::attribute::{EnvironmentMap, IntegerBounds, LevelMode, SampleType};
+use crate::meta::header::Header;
+use crate::meta::multi_view::MultiView;
+use crate::meta::{mip_map_levels, rip_map_levels, BlockDescription, MetaData};
 use crate::prelude::*;
+use crate::view::cache::{ChannelCache, ChannelKey, DEFAULT_CACHE_BUDGET};
+use crate::view::colormap::{self, Colormap};
+use crate::view::display::{
+    ColorPipeline, ColorSettings, DisplayDevice, OcioInfo, OcioSelection, ViewTransform,
+};
+use crate::view::ipc::DisplaySettings;
 use crate::view::messages::{Generation, ViewerEvent, ViewerMsg};
-use crate::view::state::{ChannelMode, DeepMode, DepthMode, View3DMode};
+use crate::view::motion::{self, MotionField};
+use crate::view::panorama::{self, PanoramaCamera, MAX_VIEW_SIZE};
+use crate::view::scopes::{Histogram, ScopeMode, Waveform};
+use crate::view::sequence::{FrameCache, Sequence, PREFETCH_FRAMES};
+use crate::view::watch::{Change, FileWatcher, DEBOUNCE, WATCH_INTERVAL};
+use crate::view::state::{
+    AlphaDisplay, AttributeInfo, ChannelMode, CompareMode, CryptomatteObject, DeepMode, DeepPixelInfo,
+    DeepSampleInfo, DepthMode, DisplayMode, ImageSlot, PartInfo, PartMetadata, StereoMode,
+    View3DMode,
+};
+
+/// One view of the stereo pair of a multi-view image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Eye {
+    Left,
+    Right,
+}
 
 /// Loaded image data.
 enum LoadedImage {
@@ -26,14 +65,62 @@ pub struct ViewerHandler {
     image: Option<LoadedImage>,
     image_path: Option<PathBuf>,
 
+    /// All parts of the loaded file. Only the current part is decoded.
+    parts: Vec<PartInfo>,
+    current_part: usize,
+    /// The displayed resolution level of the current part.
+    current_level: usize,
+
+    /// The cryptomattes of the displayed layer.
+    cryptomattes: Vec<Cryptomatte>,
+    /// The cryptomatte index and object id of the matte displayed instead of the image.
+    isolated_matte: Option<(usize, u32)>,
+
+    /// Which views of a multi-view image are displayed.
+    stereo_mode: StereoMode,
+    /// The view that channels are looked up in, while rendering a multi-view image.
+    eye: Cell<Eye>,
+
+    /// The second image, compared with the first image.
+    image_b: Option<LoadedImage>,
+    compare_mode: CompareMode,
+    wipe_position: f32,
+    difference_threshold: f32,
+
+    // Sequence playback
+    sequence: Option<Sequence>,
+    frame_index: usize,
+    /// Upcoming frames, decoded ahead during playback.
+    frame_cache: FrameCache<LoadedImage>,
+    /// Polls the displayed file for changes, if watching is enabled.
+    watcher: Option<FileWatcher>,
+    watch: bool,
+
+    /// When to show the next frame, while playing.
+    next_frame_time: Option<Instant>,
+    frame_duration: Duration,
+
+    /// Channels of the image converted to `f32`, reused for display-only changes.
+    channel_cache: RefCell<ChannelCache>,
+
     // Settings
     current_layer: String,
     current_channel: String,
     channel_mode: ChannelMode,
+    display_mode: DisplayMode,
+    /// The 1D LUT of the LUT display mode.
+    colormap: Option<Colormap>,
+    show_clipping: bool,
+    alpha_display: AlphaDisplay,
     deep_mode: DeepMode,
     depth_mode: DepthMode,
     exposure: f32,
-    apply_srgb: bool,
+    /// Converts the linear colors of the file to display colors.
+    color: ColorPipeline,
+    /// The primaries of the displayed file, to rebuild the color pipeline.
+    file_chromaticities: Option<crate::meta::attribute::Chromaticities>,
+    /// The loaded OpenColorIO config, to switch between its displays and views.
+    ocio: Option<(String, OcioConfig, OcioInfo)>,
     depth_near: f32,
     depth_far: f32,
     depth_invert: bool,
@@ -44,6 +131,25 @@ pub struct ViewerHandler {
     zoom: f32,
     pan: [f32; 2],
     viewport: [f32; 2],
+
+    /// Look around environment maps with a perspective view.
+    panorama: bool,
+    panorama_camera: PanoramaCamera,
+    /// The layout, size, and displayed pixels of the environment map,
+    /// projected again when the camera moves.
+    environment: Option<(EnvironmentMap, (usize, usize), Vec<Color32>)>,
+
+    /// The scope to compute on every regeneration, if the scopes panel is open.
+    scope_mode: Option<ScopeMode>,
+
+    /// Cancelled by the UI to stop loading the current file.
+    load_cancellation: CancellationToken,
+
+    /// Whether the UI can apply the display settings with a shader.
+    gpu_display: bool,
+    /// Whether the UI displays the current pixels with its shader,
+    /// so that changing the exposure does not need a new texture.
+    gpu_image_current: bool,
     
     // 3D settings
     view_3d_mode: View3DMode,
@@ -59,13 +165,38 @@ impl ViewerHandler {
             generation: 0,
             image: None,
             image_path: None,
+            parts: Vec::new(),
+            current_part: 0,
+            current_level: 0,
+            cryptomattes: Vec::new(),
+            isolated_matte: None,
+            stereo_mode: StereoMode::Left,
+            eye: Cell::new(Eye::Left),
+            image_b: None,
+            compare_mode: CompareMode::Off,
+            wipe_position: 0.5,
+            difference_threshold: 0.01,
+            sequence: None,
+            frame_index: 0,
+            frame_cache: FrameCache::new(PREFETCH_FRAMES),
+            watcher: None,
+            watch: false,
+            next_frame_time: None,
+            frame_duration: Duration::from_secs_f32(1.0 / 24.0),
+            channel_cache: RefCell::new(ChannelCache::new(DEFAULT_CACHE_BUDGET)),
             current_layer: String::new(),
             current_channel: String::new(),
             channel_mode: ChannelMode::Color,
+            display_mode: DisplayMode::Normal,
+            colormap: None,
+            show_clipping: false,
+            alpha_display: AlphaDisplay::default(),
             deep_mode: DeepMode::Flattened,
             depth_mode: DepthMode::AutoNormalize,
             exposure: 0.0,
-            apply_srgb: true,
+            color: ColorPipeline::default(),
+            file_chromaticities: None,
+            ocio: None,
             depth_near: 0.0,
             depth_far: 1.0,
             depth_invert: false,
@@ -74,17 +205,53 @@ impl ViewerHandler {
             zoom: 1.0,
             pan: [0.0, 0.0],
             viewport: [1280.0, 720.0],
+            panorama: false,
+            panorama_camera: PanoramaCamera::default(),
+            environment: None,
+            scope_mode: None,
+            load_cancellation: CancellationToken::new(),
+            gpu_display: false,
+            gpu_image_current: false,
             view_3d_mode: View3DMode::Heightfield,
             verbose,
         }
     }
 
     pub fn run(mut self) {
-        while let Ok(msg) = self.rx.recv() {
+        while let Some(msg) = self.next_message() {
             match msg {
                 ViewerMsg::Close => break,
                 ViewerMsg::SyncGeneration(g) => self.generation = g,
                 ViewerMsg::LoadImage(path) => self.load_image(path),
+                ViewerMsg::SetLoadCancellation(token) => self.load_cancellation = token,
+                ViewerMsg::LoadImageB(path) => self.load_image_b(path),
+                ViewerMsg::SetWatch(watch) => {
+                    self.watch = watch;
+                    self.watcher = None;
+                    self.watch_current_file();
+                }
+                ViewerMsg::SeekFrame(index) => self.show_frame(index),
+                ViewerMsg::Play { fps } => self.play(fps),
+                ViewerMsg::Stop => self.next_frame_time = None,
+                ViewerMsg::SetCompareMode(mode) => {
+                    self.compare_mode = mode;
+                    self.regenerate();
+                }
+                ViewerMsg::SetStereoMode(mode) => {
+                    self.stereo_mode = mode;
+                    self.eye.set(self.displayed_eye());
+                    self.regenerate();
+                }
+                ViewerMsg::SetWipePosition(position) => {
+                    self.wipe_position = position;
+                    self.regenerate();
+                }
+                ViewerMsg::SetDifferenceThreshold(threshold) => {
+                    self.difference_threshold = threshold;
+                    self.regenerate();
+                }
+                ViewerMsg::SetPart(index) => self.show_part(index),
+                ViewerMsg::SetLevel(index) => self.show_level(index),
                 ViewerMsg::SetLayer(layer) => {
                     self.current_layer = layer;
                     self.regenerate();
@@ -95,8 +262,13 @@ impl ViewerHandler {
                 }
                 ViewerMsg::SetChannelMode(mode) => {
                     self.channel_mode = mode;
+                    self.redisplay();
+                }
+                ViewerMsg::SetDisplayMode(mode) => {
+                    self.display_mode = mode;
                     self.regenerate();
                 }
+                ViewerMsg::LoadColormap(path) => self.load_colormap(&path),
                 ViewerMsg::SetDeepMode(mode) => {
                     self.deep_mode = mode;
                     self.regenerate();
@@ -117,12 +289,39 @@ impl ViewerHandler {
                 }
                 ViewerMsg::SetExposure(ev) => {
                     self.exposure = ev;
+                    self.redisplay();
+                }
+                ViewerMsg::SetClipping(clipping) => {
+                    self.show_clipping = clipping;
+                    self.redisplay();
+                }
+                ViewerMsg::SetBackground(background) => {
+                    self.alpha_display.background = background;
+                    self.regenerate();
+                }
+                ViewerMsg::SetUnpremultiply(unpremultiply) => {
+                    self.alpha_display.unpremultiply = unpremultiply;
+                    self.regenerate();
+                }
+                ViewerMsg::SetAlphaOverlay(overlay) => {
+                    self.alpha_display.overlay = overlay;
+                    self.regenerate();
+                }
+                ViewerMsg::SetGpuDisplay(enabled) => {
+                    self.gpu_display = enabled;
                     self.regenerate();
                 }
-                ViewerMsg::SetSrgb(v) => {
-                    self.apply_srgb = v;
+                ViewerMsg::SetColorSettings(settings) => {
+                    self.update_color_pipeline(settings);
                     self.regenerate();
                 }
+                ViewerMsg::LoadLut(path) => self.load_lut(&path),
+                ViewerMsg::LoadOcioConfig(path) => self.load_ocio_config(&path),
+                ViewerMsg::SetOcioTransform(selection) => self.set_ocio_transform(selection),
+                ViewerMsg::ClearCustomTransform => {
+                    self.ocio = None;
+                    self.set_custom_transform(None, None);
+                }
                 ViewerMsg::SetInvertDepth(v) => {
                     self.depth_invert = v;
                     self.regenerate();
@@ -132,8 +331,44 @@ impl ViewerHandler {
                 ViewerMsg::Pan { delta } => self.pan(delta),
                 ViewerMsg::FitToWindow => self.fit_to_window(),
                 ViewerMsg::Home => self.home(),
-                ViewerMsg::SetViewport(size) => self.viewport = size,
+                ViewerMsg::SetViewport(size) => {
+                    self.viewport = size;
+                    self.send_panorama();
+                }
+                ViewerMsg::SetPanorama(panorama) => {
+                    self.panorama = panorama;
+                    self.regenerate();
+                }
+                ViewerMsg::SetPanoramaCamera(camera) => {
+                    self.panorama_camera = camera;
+                    self.send_panorama();
+                }
+                ViewerMsg::QueryPixel { x, y } => self.query_pixel(x, y),
+                ViewerMsg::InspectDeepPixel { x, y } => self.inspect_deep_pixel(x, y),
+                ViewerMsg::PickCryptomatte { x, y } => self.pick_cryptomatte(x, y),
+                ViewerMsg::IsolateMatte(matte) => {
+                    self.isolated_matte = matte;
+                    self.regenerate();
+                }
+                ViewerMsg::SetScopes(mode) => {
+                    self.scope_mode = mode;
+                    self.send_scopes();
+                }
                 ViewerMsg::Request3DData => self.send_3d_data(),
+                ViewerMsg::SendToTev(address) => self.send_to_tev(&address),
+                ViewerMsg::ExportImage { path, bit_depth } => self.export_image(&path, bit_depth),
+                ViewerMsg::ExportPointCloud(path) => self.export_point_cloud(&path),
+                ViewerMsg::CreateImage { name, dims, channels } => {
+                    self.create_image(name, dims, channels)
+                }
+                ViewerMsg::UpdateRegion {
+                    name,
+                    position,
+                    dims,
+                    channels,
+                    samples,
+                } => self.update_region(&name, position, dims, &channels, &samples),
+                ViewerMsg::ApplyDisplaySettings(settings) => self.apply_display_settings(settings),
                 ViewerMsg::Set3DMode(mode) => {
                     self.view_3d_mode = mode;
                     self.send_3d_data();
@@ -148,6 +383,52 @@ impl ViewerHandler {
         }
     }
 
+    /// Wait for the next message.
+    /// While playing a sequence, shows the next frame on time and decodes upcoming frames meanwhile.
+    fn next_message(&mut self) -> Option<ViewerMsg> {
+        loop {
+            self.poll_watcher();
+
+            let Some(deadline) = self.next_frame_time else {
+                if self.watcher.is_none() {
+                    return self.rx.recv().ok();
+                }
+
+                match self.rx.recv_timeout(WATCH_INTERVAL) {
+                    Ok(msg) => return Some(msg),
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                }
+            };
+
+            match self.rx.try_recv() {
+                Ok(msg) => return Some(msg),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {}
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                // skip ahead instead of catching up if decoding is too slow
+                let next = deadline + self.frame_duration;
+                let next = if next < now { now + self.frame_duration } else { next };
+                self.next_frame_time = Some(next);
+
+                let frame_count = self.sequence.as_ref().map_or(1, |s| s.frames.len());
+                self.show_frame((self.frame_index + 1) % frame_count);
+                continue;
+            }
+
+            if !self.prefetch_frame() {
+                match self.rx.recv_timeout(deadline - now) {
+                    Ok(msg) => return Some(msg),
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                    Err(RecvTimeoutError::Timeout) => {}
+                }
+            }
+        }
+    }
+
     fn send(&self, event: ViewerEvent) {
         let _ = self.tx.send(event);
     }
@@ -161,232 +442,1440 @@ impl ViewerHandler {
     fn load_image(&mut self, path: PathBuf) {
         self.log(&format!("Loading: {}", path.display()));
 
-        // Try deep first, then flat
-        let result = read_first_deep_layer_from_file(&path)
-            .map(LoadedImage::Deep)
-            .or_else(|_| {
-                read()
-                    .no_deep_data()
-                    .largest_resolution_level()
-                    .all_channels()
-                    .all_layers()
-                    .all_attributes()
-                    .from_file(&path)
-                    .map(LoadedImage::Flat)
-            });
+        if ImportFormat::from_path(&path).is_ok() {
+            self.load_imported_image(path);
+            return;
+        }
 
-        match result {
+        let meta = match MetaData::read_from_file(&path, false) {
+            Ok(meta) => meta,
+            Err(e) => {
+                self.send(ViewerEvent::Error(format!("Failed to load: {e}")));
+                return;
+            }
+        };
+
+        let parts = meta.headers.iter().enumerate().map(part_info).collect();
+        let previous_parts = std::mem::replace(&mut self.parts, parts);
+
+        // prefer deep data, like single-part files
+        let deep_part = self.parts.iter().position(|part| part.deep).unwrap_or(0);
+        let previous_part = std::mem::replace(&mut self.current_part, deep_part);
+        let previous_level = std::mem::replace(&mut self.current_level, 0);
+
+        match self.load_current_part(&path) {
             Ok(img) => {
-                let (dims, layers, channels, is_deep, total_samples, depth_range) = match &img {
-                    LoadedImage::Flat(flat) => {
-                        let layer = flat.layer_data.first();
-                        let dims = layer.map(|l| (l.size.x(), l.size.y())).unwrap_or((0, 0));
-                        let layers: Vec<String> = flat
-                            .layer_data
-                            .iter()
-                            .map(|l| {
-                                l.attributes
-                                    .layer_name
-                                    .as_ref()
-                                    .map(|t| t.to_string())
-                                    .unwrap_or_else(|| "default".into())
-                            })
-                            .collect();
-                        let channels: Vec<String> = layer
-                            .map(|l| {
-                                l.channel_data
-                                    .list
-                                    .iter()
-                                    .map(|c| c.name.to_string())
-                                    .collect()
-                            })
-                            .unwrap_or_default();
-                        
-                        // Find Z range
-                        let depth_range = self.find_depth_range_flat(layer);
-                        
-                        (dims, layers, channels, false, 0, depth_range)
-                    }
-                    LoadedImage::Deep(deep) => {
-                        let layer = &deep.layer_data;
-                        let dims = (layer.size.x(), layer.size.y());
-                        let layers = vec![layer
-                            .attributes
-                            .layer_name
-                            .as_ref()
-                            .map(|t| t.to_string())
-                            .unwrap_or_else(|| "deep".into())];
-                        let channels: Vec<String> = layer
-                            .channel_data
-                            .list
-                            .iter()
-                            .map(|c| c.name.to_string())
-                            .collect();
-                        
-                        // Get sample info from first channel's DeepSamples
-                        let total = layer
-                            .channel_data
-                            .list
-                            .first()
-                            .map(|c| c.sample_data.total_samples())
-                            .unwrap_or(0);
-                        
-                        // Find Z range in deep data
-                        let depth_range = self.find_depth_range_deep(&layer.channel_data);
-                        
-                        (dims, layers, channels, true, total, depth_range)
-                    }
-                };
+                self.show_image(img, path.clone());
+                self.send(ViewerEvent::Metadata(
+                    meta.headers.iter().enumerate().map(part_metadata).collect(),
+                ));
+                self.detect_sequence(&path);
+            }
+            Err(Error::Aborted) => {
+                // keep displaying the previous image
+                self.parts = previous_parts;
+                self.current_part = previous_part;
+                self.current_level = previous_level;
+                self.send(ViewerEvent::LoadCancelled);
+            }
+            Err(e) => {
+                self.send(ViewerEvent::Error(format!("Failed to load: {e}")));
+            }
+        }
+    }
 
-                self.image = Some(img);
-                self.image_path = Some(path.clone());
+    /// Load an HDR, PFM, or TIFF file, and display it like an exr file with a single part.
+    fn load_imported_image(&mut self, path: PathBuf) {
+        let image = match read_image(&path, 0, self.load_progress()) {
+            Ok(LoadedImage::Flat(image)) => image,
+            Ok(LoadedImage::Deep(_)) => unreachable!("imported images are flat"),
+            Err(e) => {
+                self.send(ViewerEvent::Error(format!("Failed to load: {e}")));
+                return;
+            }
+        };
 
-                if let Some(first) = layers.first() {
-                    self.current_layer = first.clone();
-                }
+        let headers = image.write().infer_meta_data();
+        self.parts = headers.iter().enumerate().map(part_info).collect();
+        self.current_part = 0;
+        self.current_level = 0;
 
-                if let Some((min, max)) = depth_range {
-                    self.depth_near = min;
-                    self.depth_far = max;
-                    self.slice_near = min;
-                    self.slice_far = max;
-                }
+        self.show_image(LoadedImage::Flat(image), path.clone());
+        self.send(ViewerEvent::Metadata(
+            headers.iter().enumerate().map(part_metadata).collect(),
+        ));
+        self.detect_sequence(&path);
+    }
 
-                self.send(ViewerEvent::ImageLoaded {
-                    path,
-                    dims,
-                    layers,
-                    channels,
-                    is_deep,
-                    total_samples,
-                    depth_range,
-                });
+    /// Decode another part of the loaded file, and display it instead of the current part.
+    fn show_part(&mut self, index: usize) {
+        if index == self.current_part || index >= self.parts.len() {
+            return;
+        }
 
-                self.regenerate();
+        let Some(path) = self.image_path.clone() else { return };
+        self.log(&format!("Loading part {index} of {}", path.display()));
+
+        let previous = std::mem::replace(&mut self.current_part, index);
+        let previous_level = std::mem::replace(&mut self.current_level, 0);
+        match self.load_current_part(&path) {
+            Ok(img) => {
+                self.show_image(img, path.clone());
+                self.detect_sequence(&path);
             }
             Err(e) => {
-                self.send(ViewerEvent::Error(format!("Failed to load: {e}")));
+                self.current_part = previous;
+                self.current_level = previous_level;
+                self.send_load_error(format!("Failed to load part {index}: {e}"), e);
             }
         }
     }
 
-    fn find_depth_range_flat(
-        &self,
-        layer: Option<&Layer<AnyChannels<FlatSamples>>>,
-    ) -> Option<(f32, f32)> {
-        let layer = layer?;
-        let z_channel = layer
-            .channel_data
-            .list
-            .iter()
-            .find(|c| c.name.to_string() == "Z" || c.name.to_string() == "depth")?;
+    /// Decode another resolution level of the current part, and display it instead of the current level.
+    fn show_level(&mut self, index: usize) {
+        let level_count = self.parts.get(self.current_part).map_or(1, |part| part.levels.len());
+        if index == self.current_level || index >= level_count {
+            return;
+        }
 
-        let mut min = f32::MAX;
-        let mut max = f32::MIN;
+        let Some(path) = self.image_path.clone() else { return };
+        self.log(&format!("Loading level {index} of {}", path.display()));
 
-        match &z_channel.sample_data {
-            FlatSamples::F32(data) => {
-                for &v in data.iter() {
-                    if v.is_finite() {
-                        min = min.min(v);
-                        max = max.max(v);
-                    }
-                }
+        let previous = std::mem::replace(&mut self.current_level, index);
+        match self.load_current_part(&path) {
+            Ok(img) => {
+                self.show_image(img, path.clone());
+                self.detect_sequence(&path);
             }
-            FlatSamples::F16(data) => {
-                for &v in data.iter() {
-                    let f = v.to_f32();
-                    if f.is_finite() {
-                        min = min.min(f);
-                        max = max.max(f);
-                    }
-                }
+            Err(e) => {
+                self.current_level = previous;
+                self.send_load_error(format!("Failed to load level {index}: {e}"), e);
             }
-            _ => return None,
         }
+    }
 
-        if min < max {
-            Some((min, max))
-        } else {
-            None
+    /// Report a failed load, or only that it has been cancelled.
+    fn send_load_error(&self, message: String, error: Error) {
+        match error {
+            Error::Aborted => self.send(ViewerEvent::LoadCancelled),
+            _ => self.send(ViewerEvent::Error(message)),
         }
     }
 
-    fn find_depth_range_deep(
-        &self,
-        channels: &AnyChannels<crate::image::deep::DeepSamples>,
-    ) -> Option<(f32, f32)> {
-        // Find Z channel in deep data
-        let first_ch = channels.list.first()?;
-        let samples = &first_ch.sample_data;
+    /// Decode only the current part of a multi-part file, or the whole file otherwise.
+    /// Reports the progress to the UI, and stops when the UI cancels the load.
+    fn read_current_part(&self, path: &Path) -> Result<LoadedImage> {
+        let progress = self.load_progress();
 
-        // Look for Z in channel names
-        let z_idx = channels
-            .list
-            .iter()
-            .position(|c| c.name.to_string() == "Z")?;
+        match self.parts.get(self.current_part) {
+            Some(part) if self.parts.len() > 1 => {
+                read_part(path, self.current_part, part.deep, self.current_level, progress)
+            }
+            _ => read_image(path, self.current_level, progress),
+        }
+    }
 
-        if z_idx >= samples.channels.len() {
-            return None;
+    /// Decode the current part like `read_current_part`, displaying a preview meanwhile.
+    fn load_current_part(&mut self, path: &Path) -> Result<LoadedImage> {
+        let previewed = self.send_preview(path);
+        let image = self.read_current_part(path);
+
+        // display the previous image again instead of the preview
+        if previewed && image.is_err() {
+            self.regenerate();
         }
 
-        let mut min = f32::MAX;
-        let mut max = f32::MIN;
+        image
+    }
 
-        match &samples.channels[z_idx] {
-            crate::image::deep::DeepChannelData::F32(data) => {
-                for &v in data.iter() {
-                    if v.is_finite() {
-                        min = min.min(v);
-                        max = max.max(v);
-                    }
-                }
+    /// Display a preview of a large flat part before all of its pixels are decoded:
+    /// the smallest resolution level that is at least `PREVIEW_SIZE` pixels large,
+    /// or every few pixels of every few lines if the part has no smaller levels.
+    /// If the same file is displayed, the visible pixels are then decoded in full resolution,
+    /// so that the preview is refined where the view is zoomed in.
+    /// Returns whether a preview has been displayed.
+    fn send_preview(&self, path: &Path) -> bool {
+        let Some(part) = self.parts.get(self.current_part) else { return false };
+        let Some(&(width, height)) = part.levels.first() else { return false };
+
+        // while playing, each frame is displayed only when it is complete
+        let large = width.max(height) > 2 * PREVIEW_SIZE;
+        let playing = self.next_frame_time.is_some();
+        if part.deep || !large || playing || self.current_level != 0 {
+            return false;
+        }
+
+        if ImportFormat::from_path(path).is_ok() {
+            return false;
+        }
+
+        let cancellation = self.load_cancellation.clone();
+        let preview = match part.levels.iter().rposition(|&(w, h)| w.max(h) >= PREVIEW_SIZE) {
+            Some(level) if level > 0 => {
+                read_level_preview(path, self.current_part, level, cancellation)
             }
-            crate::image::deep::DeepChannelData::F16(data) => {
-                for &v in data.iter() {
-                    let f = v.to_f32();
-                    if f.is_finite() {
-                        min = min.min(f);
-                        max = max.max(f);
-                    }
-                }
+            _ => {
+                let stride = (width.max(height) + PREVIEW_SIZE - 1) / PREVIEW_SIZE;
+                read_strided_preview(path, self.current_part, stride, cancellation)
             }
-            _ => return None,
-        }
+        };
 
-        if min < max {
-            Some((min, max))
-        } else {
-            None
+        let preview = match preview {
+            Ok(preview) => preview,
+            Err(error) => {
+                self.log(&format!("No preview of {}: {error}", path.display()));
+                return false;
+            }
+        };
+
+        let Some(preview_size) = preview.layer_data.first().map(|layer| layer.size) else {
+            return false;
+        };
+
+        // the cached channels belong to the displayed image
+        self.channel_cache.borrow_mut().clear();
+        let pixels = self.render_flat(&preview, ImageSlot::A);
+        self.channel_cache.borrow_mut().clear();
+
+        self.send(ViewerEvent::TextureReady {
+            generation: self.generation,
+            width: preview_size.x(),
+            height: preview_size.y(),
+            pixels: pixels.clone(),
+            preview_of: Some((width, height)),
+        });
+
+        let same_image = self.image_path.as_deref() == Some(path)
+            && self.image.as_ref().map(image_size) == Some((width, height));
+
+        if same_image {
+            self.send_visible_pixels(path, (preview_size, pixels), (width, height));
         }
+
+        true
     }
 
-    fn regenerate(&mut self) {
+    /// Decode the pixels inside the viewport in full resolution,
+    /// and display them on top of the preview, which is scaled to the size of the image.
+    /// Only if the view shows a small part of the image.
+    fn send_visible_pixels(
+        &self,
+        path: &Path,
+        (preview_size, preview): (Vec2<usize>, Vec<Color32>),
+        (width, height): (usize, usize),
+    ) {
         let Some(image) = &self.image else { return };
+        let visible = self.visible_pixels((width, height));
+        if visible.size.area() == 0 || visible.size.area() * 4 > width * height {
+            return;
+        }
 
-        let pixels = match image {
-            LoadedImage::Flat(flat) => self.render_flat(flat),
-            LoadedImage::Deep(deep) => self.render_deep(deep),
+        let (data_window, _) = windows(image);
+        let region = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .specific_layer(self.current_part)
+            .all_attributes()
+            .region(visible.with_origin(data_window.position))
+            .with_cancellation(self.load_cancellation.clone())
+            .from_file(path);
+
+        let region = match region {
+            Ok(region) => {
+                let layers = smallvec::smallvec![region.layer_data];
+                Image::from_layers(region.attributes, layers)
+            }
+            Err(error) => {
+                self.log(&format!("No preview of the visible pixels: {error}"));
+                return;
+            }
+        };
+
+        let Some(layer) = region.layer_data.first() else { return };
+        let Ok(offset) = (layer.attributes.layer_position - data_window.position).to_usize("region")
+        else {
+            return;
+        };
+
+        self.channel_cache.borrow_mut().clear();
+        let visible_pixels = self.render_flat(&region, ImageSlot::A);
+        self.channel_cache.borrow_mut().clear();
+
+        let mut pixels = resize_nearest(&preview, preview_size, Vec2(width, height));
+        let region_width = layer.size.x();
+
+        for (y, row) in visible_pixels.chunks_exact(region_width.max(1)).enumerate() {
+            let start = (offset.y() + y) * width + offset.x();
+            pixels[start..start + region_width].copy_from_slice(row);
+        }
+
+        self.send(ViewerEvent::TextureReady {
+            generation: self.generation,
+            width,
+            height,
+            pixels,
+            preview_of: None,
+        });
+    }
+
+    /// The pixels of an image with this size that are inside the viewport,
+    /// relative to the data window.
+    fn visible_pixels(&self, (width, height): (usize, usize)) -> IntegerBounds {
+        let range = |size: usize, pan: f32, viewport: f32| {
+            let center = size as f32 / 2.0 - pan;
+            let radius = viewport / (2.0 * self.zoom);
+            let start = (center - radius).floor().clamp(0.0, size as f32) as usize;
+            let end = (center + radius).ceil().clamp(0.0, size as f32) as usize;
+            (start, end.max(start) - start)
+        };
+
+        let (x, width) = range(width, self.pan[0], self.viewport[0]);
+        let (y, height) = range(height, self.pan[1], self.viewport[1]);
+        IntegerBounds::new(Vec2(x, y).to_i32(), Vec2(width, height))
+    }
+
+    fn load_progress(&self) -> LoadProgress {
+        LoadProgress {
+            tx: self.tx.clone(),
+            cancellation: self.load_cancellation.clone(),
+        }
+    }
+
+    fn load_image_b(&mut self, path: PathBuf) {
+        self.log(&format!("Loading for comparison: {}", path.display()));
+
+        match read_image(&path, 0, self.load_progress()) {
+            Ok(img) => {
+                let dims = image_size(&img);
+                self.channel_cache.get_mut().clear();
+                self.image_b = Some(img);
+                self.send(ViewerEvent::ImageBLoaded { path, dims });
+                self.regenerate();
+            }
+            Err(e) => {
+                self.send_load_error(format!("Failed to load: {e}"), e);
+            }
+        }
+    }
+
+    /// Replace the displayed image, and send its layers and channels to the UI.
+    fn show_image(&mut self, img: LoadedImage, path: PathBuf) {
+        let (dims, layers, channels, deep_stats, depth_range) = match &img {
+            LoadedImage::Flat(flat) => {
+                let layer = flat.layer_data.first();
+                let dims = layer.map(|l| (l.size.x(), l.size.y())).unwrap_or((0, 0));
+                let layers: Vec<String> = flat
+                    .layer_data
+                    .iter()
+                    .map(|l| {
+                        l.attributes
+                            .layer_name
+                            .as_ref()
+                            .map(|t| t.to_string())
+                            .unwrap_or_else(|| "default".into())
+                    })
+                    .collect();
+                let channels: Vec<String> = layer
+                    .map(|l| {
+                        l.channel_data
+                            .list
+                            .iter()
+                            .map(|c| c.name.to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                
+                // Find Z range
+                let depth_range = self.find_depth_range_flat(layer);
+                
+                (dims, layers, channels, None, depth_range)
+            }
+            LoadedImage::Deep(deep) => {
+                let layer = &deep.layer_data;
+                let dims = (layer.size.x(), layer.size.y());
+                let layers = vec![layer
+                    .attributes
+                    .layer_name
+                    .as_ref()
+                    .map(|t| t.to_string())
+                    .unwrap_or_else(|| "deep".into())];
+                let channels: Vec<String> = layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .map(|c| c.name.to_string())
+                    .collect();
+                
+                let stats = layer.channel_data.stats();
+                let depth_range = stats.depth_range.filter(|(min, max)| min < max);
+
+                (dims, layers, channels, Some(stats), depth_range)
+            }
+        };
+
+        self.file_chromaticities = chromaticities(&img);
+        self.update_color_pipeline(self.color.settings());
+        self.channel_cache.get_mut().clear();
+        self.image = Some(img);
+        self.image_path = Some(path.clone());
+        self.cryptomattes = self.find_cryptomattes();
+        self.isolated_matte = None;
+
+        self.sequence = None;
+        self.next_frame_time = None;
+        self.frame_cache.clear();
+
+        self.watcher = None;
+        self.watch_current_file();
+
+        if let Some(first) = layers.first() {
+            self.current_layer = first.clone();
+        }
+
+        if let Some((min, max)) = depth_range {
+            self.depth_near = min;
+            self.depth_far = max;
+            self.slice_near = min;
+            self.slice_far = max;
+        }
+
+        let views = match &self.image {
+            Some(LoadedImage::Flat(flat)) => flat.layer_data.first().map(view_channels),
+            _ => None,
         };
 
-        let (width, height) = match image {
-            LoadedImage::Flat(f) => f
-                .layer_data
-                .first()
-                .map(|l| (l.size.x(), l.size.y()))
-                .unwrap_or((0, 0)),
-            LoadedImage::Deep(d) => (d.layer_data.size.x(), d.layer_data.size.y()),
-        };
+        let environment_map = self.image.as_ref().and_then(environment_map);
+
+        self.send(ViewerEvent::ImageLoaded {
+            path,
+            dims,
+            parts: self.parts.clone(),
+            part: self.current_part,
+            level: self.current_level,
+            layers,
+            channels,
+            views: views.unwrap_or_default(),
+            environment_map,
+            cryptomattes: self.cryptomattes.iter().map(|c| c.name.clone()).collect(),
+            deep_stats,
+            depth_range,
+        });
+
+        self.send_windows();
+        self.regenerate();
+    }
+
+    fn send_windows(&self) {
+        if let Some(image) = &self.image {
+            let (data_window, display_window) = windows(image);
+            self.send(ViewerEvent::WindowsChanged {
+                data_window,
+                display_window,
+            });
+        }
+    }
+
+    /// Start watching the displayed file and its directory, if watching is enabled.
+    fn watch_current_file(&mut self) {
+        if let (true, None, Some(path)) = (self.watch, &self.watcher, &self.image_path) {
+            self.watcher = Some(FileWatcher::new(path, true, DEBOUNCE));
+        }
+    }
+
+    /// Reload the displayed file or update the sequence, if they changed.
+    fn poll_watcher(&mut self) {
+        let Some(watcher) = &mut self.watcher else { return };
+
+        match watcher.poll(Instant::now()) {
+            Some(Change::File) => self.reload_image(),
+            Some(Change::Directory) => self.update_sequence(),
+            None => {}
+        }
+    }
+
+    /// Read the displayed file again, keeping the part, level, view, and display settings.
+    fn reload_image(&mut self) {
+        let Some(path) = self.image_path.clone() else { return };
+
+        match self.load_current_part(&path) {
+            Ok(image) => {
+                self.log(&format!("Reloaded {}", path.display()));
+                self.file_chromaticities = chromaticities(&image);
+                self.update_color_pipeline(self.color.settings());
+                self.channel_cache.get_mut().clear();
+                self.frame_cache.clear();
+                self.image = Some(image);
+                self.cryptomattes = self.find_cryptomattes();
+
+                self.send(ViewerEvent::ImageReloaded { path });
+                self.send_windows();
+                self.regenerate();
+            }
+            Err(Error::Aborted) => self.send(ViewerEvent::LoadCancelled),
+            // the file may still be incomplete, try again after the next change
+            Err(e) => self.log(&format!("Reloading {} failed: {e}", path.display())),
+        }
+    }
+
+    /// Detect the frames of the sequence again, after files were added or removed.
+    fn update_sequence(&mut self) {
+        let Some(path) = self.image_path.clone() else { return };
+        let frames =
+            |sequence: &Sequence| sequence.frames.iter().map(|(frame, _)| *frame).collect();
+
+        let previous: Option<Vec<i64>> = self.sequence.as_ref().map(frames);
+        let detected = Sequence::detect(&path);
+        if detected.as_ref().map(frames) == previous {
+            return;
+        }
+
+        // the cached frames may have moved to other indices
+        self.frame_cache.clear();
+
+        match detected {
+            Some(sequence) => {
+                self.frame_index = sequence.index_of(&path).unwrap_or(0);
+                self.send(ViewerEvent::SequenceDetected {
+                    frames: frames(&sequence),
+                    index: self.frame_index,
+                });
+
+                self.log(&format!("The sequence now has {} frames", sequence.frames.len()));
+                self.sequence = Some(sequence);
+            }
+            None => {
+                self.next_frame_time = None;
+                self.sequence = None;
+            }
+        }
+    }
+
+    /// Find the other frames of the loaded file, and send them to the timeline.
+    fn detect_sequence(&mut self, path: &Path) {
+        let Some(sequence) = Sequence::detect(path) else { return };
+
+        self.frame_index = sequence.index_of(path).unwrap_or(0);
+        self.send(ViewerEvent::SequenceDetected {
+            frames: sequence.frames.iter().map(|(frame, _)| *frame).collect(),
+            index: self.frame_index,
+        });
+
+        self.log(&format!("Found a sequence of {} frames", sequence.frames.len()));
+        self.sequence = Some(sequence);
+    }
+
+    /// Display a frame of the sequence, keeping the view and the display settings.
+    fn show_frame(&mut self, index: usize) {
+        let Some(sequence) = &self.sequence else { return };
+        let Some((_, path)) = sequence.frames.get(index) else { return };
+        let path = path.clone();
+
+        let image = match self.frame_cache.take(index) {
+            Some(image) => Ok(image),
+            None => self.load_current_part(&path),
+        };
+
+        match image {
+            Ok(image) => {
+                self.file_chromaticities = chromaticities(&image);
+                self.update_color_pipeline(self.color.settings());
+                self.channel_cache.get_mut().clear();
+                self.image = Some(image);
+                if let Some(watcher) = &mut self.watcher {
+                    watcher.set_file(&path);
+                }
+
+                self.image_path = Some(path);
+                self.cryptomattes = self.find_cryptomattes();
+                self.frame_index = index;
+
+                self.send(ViewerEvent::FrameChanged { index });
+                self.send_windows();
+                self.regenerate();
+            }
+            Err(e) => {
+                self.next_frame_time = None;
+                self.send(ViewerEvent::PlaybackStopped);
+                self.send(ViewerEvent::Error(format!("Failed to load {}: {e}", path.display())));
+            }
+        }
+    }
+
+    fn play(&mut self, fps: f32) {
+        if self.sequence.is_none() {
+            self.send(ViewerEvent::PlaybackStopped);
+            return;
+        }
+
+        self.frame_duration = Duration::from_secs_f32(1.0 / fps.clamp(0.1, 240.0));
+        self.next_frame_time = Some(Instant::now() + self.frame_duration);
+    }
+
+    /// Decode one of the upcoming frames that is not cached yet.
+    /// Returns false if all upcoming frames are cached, or decoding failed.
+    fn prefetch_frame(&mut self) -> bool {
+        let Some(sequence) = &self.sequence else { return false };
+        let frame_count = sequence.frames.len();
+
+        let missing = (1..=PREFETCH_FRAMES.min(frame_count - 1))
+            .map(|offset| (self.frame_index + offset) % frame_count)
+            .find(|&index| !self.frame_cache.contains(index));
+
+        let Some(index) = missing else { return false };
+        match self.read_current_part(&sequence.frames[index].1) {
+            Ok(image) => {
+                self.frame_cache.insert(index, image);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn create_image(&mut self, name: String, dims: (usize, usize), channels: Vec<String>) {
+        let text = |name: &str| {
+            Text::new_or_none(name).ok_or_else(|| format!("Invalid name '{name}'"))
+        };
+
+        let size = Vec2(dims.0, dims.1);
+        let result = channels
+            .iter()
+            .map(|channel| {
+                let samples = FlatSamples::F32(vec![0.0; size.area()]);
+                Ok(AnyChannel::new(text(channel)?, samples))
+            })
+            .collect::<std::result::Result<SmallVec<_>, String>>()
+            .and_then(|list| {
+                let attributes = LayerAttributes::named(text(&name)?);
+                let channels = AnyChannels::sort(list);
+                Ok(Layer::new(size, attributes, Encoding::default(), channels))
+            });
+
+        match result {
+            Ok(layer) => {
+                self.parts.clear();
+                self.current_part = 0;
+                self.current_level = 0;
+                self.send(ViewerEvent::Metadata(Vec::new()));
+
+                let attributes = ImageAttributes::new(IntegerBounds::from_dimensions(size));
+                let image = Image::from_layers(attributes, smallvec::smallvec![layer]);
+                self.show_image(LoadedImage::Flat(image), PathBuf::from(name));
+            }
+            Err(message) => self.send(ViewerEvent::Error(message)),
+        }
+    }
+
+    fn update_region(
+        &mut self,
+        name: &str,
+        position: (usize, usize),
+        dims: (usize, usize),
+        channels: &[String],
+        samples: &[Vec<f32>],
+    ) {
+        let layer = match &mut self.image {
+            Some(LoadedImage::Flat(image)) => image.layer_data.iter_mut().find(|layer| {
+                layer
+                    .attributes
+                    .layer_name
+                    .as_ref()
+                    .map_or(false, |layer_name| layer_name.eq(name))
+            }),
+            _ => None,
+        };
+
+        let Some(layer) = layer else {
+            self.send(ViewerEvent::Error(format!("No image named '{name}' was created")));
+            return;
+        };
+
+        let (layer_width, layer_height) = (layer.size.x(), layer.size.y());
+        self.channel_cache.get_mut().clear();
+
+        for (channel_name, values) in channels.iter().zip(samples) {
+            let channel = layer
+                .channel_data
+                .list
+                .iter_mut()
+                .find(|channel| channel.name.eq(channel_name.as_str()));
+
+            let Some(channel) = channel else { continue };
+
+            // the region is clipped to the image
+            for y in 0..dims.1.min(layer_height.saturating_sub(position.1)) {
+                for x in 0..dims.0.min(layer_width.saturating_sub(position.0)) {
+                    let value = values[y * dims.0 + x];
+                    let index = (position.1 + y) * layer_width + position.0 + x;
+
+                    match &mut channel.sample_data {
+                        FlatSamples::F16(data) => data[index] = f16::from_f32(value),
+                        FlatSamples::F32(data) => data[index] = value,
+                        FlatSamples::U32(data) => data[index] = value as u32,
+                    }
+                }
+            }
+        }
+
+        self.regenerate();
+    }
+
+    fn apply_display_settings(&mut self, settings: DisplaySettings) {
+        if let Some(exposure) = settings.exposure {
+            self.exposure = exposure;
+        }
+
+        if let Some(srgb) = settings.srgb {
+            let display = if srgb { DisplayDevice::Srgb } else { DisplayDevice::Linear };
+            self.update_color_pipeline(ColorSettings { display, ..self.color.settings() });
+        }
+
+        if let Some(channel) = settings.channel {
+            let mode = match channel.to_ascii_lowercase().as_str() {
+                "color" => Some(ChannelMode::Color),
+                "red" => Some(ChannelMode::Red),
+                "green" => Some(ChannelMode::Green),
+                "blue" => Some(ChannelMode::Blue),
+                "alpha" => Some(ChannelMode::Alpha),
+                "depth" => Some(ChannelMode::Depth),
+                "luminance" => Some(ChannelMode::Luminance),
+                _ => self
+                    .channel_names()
+                    .iter()
+                    .position(|name| *name == channel)
+                    .map(ChannelMode::Custom),
+            };
+
+            match mode {
+                Some(mode) => {
+                    if let ChannelMode::Custom(_) = mode {
+                        self.current_channel = channel;
+                    }
+                    self.channel_mode = mode;
+                }
+                None => self.send(ViewerEvent::Error(format!("No channel named '{channel}'"))),
+            }
+        }
+
+        self.send(ViewerEvent::DisplaySettingsChanged {
+            exposure: self.exposure,
+            color: self.color.settings(),
+            channel_mode: self.channel_mode,
+        });
+
+        self.regenerate();
+    }
+
+    /// The channels of the first layer, like the channel list of the UI.
+    fn channel_names(&self) -> Vec<String> {
+        let names: Vec<&Text> = match &self.image {
+            Some(LoadedImage::Flat(image)) => image
+                .layer_data
+                .first()
+                .map(|layer| layer.channel_data.list.iter().map(|c| &c.name).collect())
+                .unwrap_or_default(),
+            Some(LoadedImage::Deep(image)) => {
+                let list = &image.layer_data.channel_data.list;
+                list.iter().map(|c| &c.name).collect()
+            }
+            None => Vec::new(),
+        };
+
+        names.into_iter().map(Text::to_string).collect()
+    }
+
+    fn send_to_tev(&self, address: &str) {
+        let result = match &self.image {
+            Some(LoadedImage::Flat(image)) => crate::interop::tev::send_to_tev(address, image)
+                .map_err(|error| format!("Sending to tev at {address} failed: {error}")),
+            Some(LoadedImage::Deep(_)) => Err("Deep images cannot be sent to tev".to_string()),
+            None => return,
+        };
+
+        match result {
+            Ok(()) => self.log(&format!("Sent image to tev at {address}")),
+            Err(message) => self.send(ViewerEvent::Error(message)),
+        }
+    }
+
+    /// Save the displayed image as PNG, TIFF, or HDR. PNG and TIFF contain the display colors,
+    /// while HDR contains the linear colors with the current exposure.
+    /// Exports the first image only, ignoring the compare mode.
+    fn export_image(&self, path: &Path, bit_depth: BitDepth) {
+        let Some(image) = &self.image else { return };
+        let (width, height) = image_size(image);
+
+        let linear = matches!(ExportFormat::from_path(path), Ok(ExportFormat::Hdr));
+        let exposure_multiplier = 2.0_f32.powf(self.exposure);
+        let display = |rgb: (f32, f32, f32), multiplier: f32| {
+            let (r, g, b) = rgb;
+            let exposed = (r * multiplier, g * multiplier, b * multiplier);
+            if linear { exposed } else { self.display_value(exposed) }
+        };
+
+        // isolated mattes and sample count heatmaps are displayed without exposure
+        let colors = match image {
+            LoadedImage::Flat(flat) => self
+                .isolated_matte_pixels(flat, ImageSlot::A, |rgb| display(rgb, 1.0))
+                .unwrap_or_else(|| {
+                    self.flat_pixels(flat, ImageSlot::A, |rgb| display(rgb, exposure_multiplier))
+                }),
+            LoadedImage::Deep(deep) if self.deep_mode == DeepMode::SampleCount => {
+                self.deep_pixels(deep, |rgb| rgb)
+            }
+            LoadedImage::Deep(deep) => {
+                self.deep_pixels(deep, |rgb| display(rgb, exposure_multiplier))
+            }
+        };
+
+        let mut channels = [
+            Vec::with_capacity(colors.len()),
+            Vec::with_capacity(colors.len()),
+            Vec::with_capacity(colors.len()),
+        ];
+
+        for (r, g, b) in colors {
+            channels[0].push(r);
+            channels[1].push(g);
+            channels[2].push(b);
+        }
+
+        let options = ExportOptions { exposure: 0.0, transfer: Transfer::Linear, bit_depth };
+        let [red, green, blue] = &channels;
+        let result = crate::export::write_channels_to_file(
+            path,
+            Vec2(width, height),
+            &[red, green, blue],
+            &options,
+        );
+
+        match result {
+            Ok(()) => self.log(&format!("Exported image to {}", path.display())),
+            Err(error) => self.send(ViewerEvent::Error(format!(
+                "Exporting to {} failed: {error}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Rebuild the built-in display transform for the displayed file, keeping any custom transform.
+    /// Save the samples of the deep image as a point cloud, see `PointCloud`.
+    fn export_point_cloud(&self, path: &Path) {
+        let Some(LoadedImage::Deep(deep)) = &self.image else {
+            let message = "Only deep images can be exported as point clouds";
+            self.send(ViewerEvent::Error(message.into()));
+            return;
+        };
+
+        let layer = &deep.layer_data;
+        let result = Projection::from_attributes(&layer.attributes)
+            .and_then(|projection| {
+                PointCloud::from_deep_layer(layer, deep.attributes.display_window, &projection)
+            })
+            .and_then(|points| points.write_to_file(path));
+
+        match result {
+            Ok(()) => self.log(&format!("Exported point cloud to {}", path.display())),
+            Err(error) => self.send(ViewerEvent::Error(format!(
+                "Exporting to {} failed: {error}",
+                path.display()
+            ))),
+        }
+    }
+
+    fn update_color_pipeline(&mut self, settings: ColorSettings) {
+        let pipeline = ColorPipeline::new(settings, self.file_chromaticities).unwrap_or_else(|e| {
+            self.log(&format!("Ignoring chromaticities: {e}"));
+            ColorPipeline::new(settings, None).unwrap_or_default()
+        });
+
+        self.color = pipeline.with_custom(self.color.custom().cloned());
+    }
+
+    /// Replace the display transform, tell the UI, and redisplay the image.
+    fn set_custom_transform(&mut self, name: Option<String>, processor: Option<Processor>) {
+        self.color = self.color.clone().with_custom(processor);
+        self.send(ViewerEvent::CustomTransformChanged {
+            name,
+            ocio: self.ocio.as_ref().map(|(_, _, info)| info.clone()),
+        });
+
+        self.regenerate();
+    }
+
+    fn load_lut(&mut self, path: &Path) {
+        match Processor::from_lut_file(path) {
+            Ok(processor) => {
+                self.ocio = None;
+                self.log(&format!("Loaded LUT {}", path.display()));
+                self.set_custom_transform(Some(file_name(path)), Some(processor));
+            }
+            Err(e) => self.send(ViewerEvent::Error(format!(
+                "Failed to load LUT {}: {e}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Load an OpenColorIO config and display its default view.
+    fn load_ocio_config(&mut self, path: &Path) {
+        let config = match OcioConfig::from_file(path) {
+            Ok(config) => config,
+            Err(e) => {
+                let message = format!("Failed to load OCIO config {}: {e}", path.display());
+                return self.send(ViewerEvent::Error(message));
+            }
+        };
+
+        let info = OcioInfo::of_config(&config);
+        let Some(selection) = info.selection.clone() else {
+            let message = format!("The OCIO config {} has no displays", path.display());
+            return self.send(ViewerEvent::Error(message));
+        };
+
+        self.log(&format!("Loaded OCIO config {}", path.display()));
+        self.ocio = Some((file_name(path), config, info));
+        self.set_ocio_transform(selection);
+    }
+
+    /// Display the selected view of the loaded OpenColorIO config.
+    fn set_ocio_transform(&mut self, selection: OcioSelection) {
+        let Some((name, config, info)) = &mut self.ocio else { return };
+
+        let processor = config.display_processor(
+            &selection.color_space,
+            &selection.display,
+            &selection.view,
+        );
+
+        match processor {
+            Ok(processor) => {
+                let name = format!("{name}: {} / {}", selection.display, selection.view);
+                info.selection = Some(selection);
+                self.set_custom_transform(Some(name), Some(processor));
+            }
+            Err(e) => self.send(ViewerEvent::Error(format!("OCIO: {e}"))),
+        }
+    }
+
+    fn find_depth_range_flat(
+        &self,
+        layer: Option<&Layer<AnyChannels<FlatSamples>>>,
+    ) -> Option<(f32, f32)> {
+        let layer = layer?;
+        let z_channel = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|c| c.name.to_string() == "Z" || c.name.to_string() == "depth")?;
+
+        let mut min = f32::MAX;
+        let mut max = f32::MIN;
+
+        match &z_channel.sample_data {
+            FlatSamples::F32(data) => {
+                for &v in data.iter() {
+                    if v.is_finite() {
+                        min = min.min(v);
+                        max = max.max(v);
+                    }
+                }
+            }
+            FlatSamples::F16(data) => {
+                for &v in data.iter() {
+                    let f = v.to_f32();
+                    if f.is_finite() {
+                        min = min.min(f);
+                        max = max.max(f);
+                    }
+                }
+            }
+            _ => return None,
+        }
+
+        if min < max {
+            Some((min, max))
+        } else {
+            None
+        }
+    }
+
+    fn regenerate(&mut self) {
+        let Some(image) = &self.image else { return };
+        self.environment = None;
+        self.send_motion_field(image);
+
+        #[cfg(feature = "view-gpu")]
+        if let (true, LoadedImage::Flat(flat)) = (self.gpu_supported(), image) {
+            self.send_float_image(flat);
+            self.gpu_image_current = true;
+            self.send_scopes();
+            return;
+        }
+
+        self.gpu_image_current = false;
+
+        let ((width, height), pixels) = match &self.image_b {
+            Some(image_b) if self.compare_mode != CompareMode::Off => {
+                let pixels = self.render(image, ImageSlot::A);
+                self.compare(image, pixels, image_b)
+            }
+            _ => self.render_views(image),
+        };
+
+        if let Some(map) = self.panorama_map(image) {
+            self.environment = Some((map, (width, height), pixels));
+            self.send_panorama();
+            self.send_scopes();
+            return;
+        }
+
+        self.send(ViewerEvent::TextureReady {
+            generation: self.generation,
+            width,
+            height,
+            pixels,
+            preview_of: None,
+        });
+
+        self.send_scopes();
+    }
+
+    /// The layout of the environment map to look around in, if the panorama view is enabled.
+    fn panorama_map(&self, image: &LoadedImage) -> Option<EnvironmentMap> {
+        let compared = self.image_b.is_some() && self.compare_mode != CompareMode::Off;
+        let map = environment_map(image).filter(|_| self.panorama && !compared)?;
+        let (width, height) = image_size(image);
+        map.validate_size(Vec2(width, height)).ok().map(|_| map)
+    }
+
+    /// Render the view of the panorama camera, with the size of the viewport.
+    fn send_panorama(&self) {
+        let Some((map, map_size, pixels)) = &self.environment else { return };
+        let view_size = |size: f32| (size as usize).clamp(1, MAX_VIEW_SIZE);
+        let size = (view_size(self.viewport[0]), view_size(self.viewport[1]));
+
+        self.send(ViewerEvent::TextureReady {
+            generation: self.generation,
+            width: size.0,
+            height: size.1,
+            pixels: panorama::project(*map, *map_size, pixels, self.panorama_camera, size),
+            preview_of: None,
+        });
+    }
+
+    /// Send the vectors for the arrow overlay, if the texture shows the vectors of the image.
+    fn send_motion_field(&self, image: &LoadedImage) {
+        let compared = self.image_b.is_some() && self.compare_mode != CompareMode::Off;
+        let plain = !compared
+            && self.isolated_matte.is_none()
+            && !self.combines_views(image)
+            && self.panorama_map(image).is_none();
+
+        let field = match image {
+            LoadedImage::Flat(flat) if plain => flat
+                .layer_data
+                .first()
+                .and_then(|layer| self.motion_field(layer, ImageSlot::A)),
+            _ => None,
+        };
+
+        self.send(ViewerEvent::MotionField(field));
+    }
+
+    /// The vectors of the first layer in the motion vector display mode,
+    /// or `None` in other modes and for layers without vector channels.
+    /// The layer of a selected custom channel is preferred.
+    fn motion_field(
+        &self,
+        layer: &Layer<AnyChannels<FlatSamples>>,
+        slot: ImageSlot,
+    ) -> Option<MotionField> {
+        if self.display_mode != DisplayMode::MotionVectors {
+            return None;
+        }
+
+        let names: Vec<String> =
+            layer.channel_data.list.iter().map(|channel| channel.name.to_string()).collect();
+        let selected = match self.channel_mode {
+            ChannelMode::Custom(index) => Some(index),
+            _ => None,
+        };
+
+        let (x, y) = motion::vector_channels(&names, selected)?;
+        let x = self.cached_channel(layer, slot, Some(x));
+        let y = self.cached_channel(layer, slot, Some(y));
+        Some(MotionField::new(layer.size.x(), layer.size.y(), &x, &y))
+    }
+
+    /// Apply display settings that the shader of the UI may support,
+    /// regenerating the texture only if the UI does not display the pixels with its shader.
+    fn redisplay(&mut self) {
+        if self.gpu_image_current && self.gpu_supported() {
+            self.send_scopes();
+        } else {
+            self.regenerate();
+        }
+    }
+
+    /// Whether the shader of the UI can display the image with the current settings.
+    /// Mirrors `display_value` for the normal display mode of flat images.
+    fn gpu_supported(&self) -> bool {
+        let color = self.color.settings();
+        let basic_channel = matches!(
+            self.channel_mode,
+            ChannelMode::Color
+                | ChannelMode::Red
+                | ChannelMode::Green
+                | ChannelMode::Blue
+                | ChannelMode::Alpha
+                | ChannelMode::Luminance
+        );
+
+        self.gpu_display
+            && matches!(self.image, Some(LoadedImage::Flat(_)))
+            && self.isolated_matte.is_none()
+            && (self.image_b.is_none() || self.compare_mode == CompareMode::Off)
+            && !self.image.as_ref().map_or(false, |image| {
+                self.combines_views(image) || self.panorama_map(image).is_some()
+            })
+            && self.display_mode == DisplayMode::Normal
+            && self.color.custom().is_none()
+            && color.view == ViewTransform::Raw
+            && color.display != DisplayDevice::DisplayP3
+            && basic_channel
+            && (self.channel_mode != ChannelMode::Color || !self.alpha_display.is_active())
+    }
+
+    /// Send the unprocessed pixels of the first layer to the shader of the UI.
+    #[cfg(feature = "view-gpu")]
+    fn send_float_image(&self, image: &Image<Layers<AnyChannels<FlatSamples>>>) {
+        let Some(layer) = image.layer_data.first() else { return };
+        let find = |name: &str| self.find_channel(layer, name);
+
+        let [r, g, b, a] =
+            ["R", "G", "B", "A"].map(|name| self.cached_channel(layer, ImageSlot::A, find(name)));
+
+        let pixels = (0..layer.size.area())
+            .map(|i| [r[i], g[i], b[i], a[i]].map(f16::from_f32))
+            .collect();
+
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        self.send(ViewerEvent::FloatImageReady {
+            generation: self.generation,
+            width: layer.size.x(),
+            height: layer.size.y(),
+            pixels,
+            input_matrix: self.color.input_matrix().unwrap_or(identity),
+        });
+    }
+
+    /// The eye of the view displayed by the stereo mode, or the left eye for combined views.
+    fn displayed_eye(&self) -> Eye {
+        match self.stereo_mode {
+            StereoMode::Right => Eye::Right,
+            StereoMode::Left | StereoMode::Anaglyph | StereoMode::SideBySide => Eye::Left,
+        }
+    }
+
+    /// Whether the stereo mode displays both views of this multi-view image.
+    fn combines_views(&self, image: &LoadedImage) -> bool {
+        let combined = matches!(self.stereo_mode, StereoMode::Anaglyph | StereoMode::SideBySide);
+        combined && multi_view(image).map_or(false, |views| views.views().len() > 1)
+    }
+
+    /// The index of the channel with this name in the displayed view of a multi-view layer,
+    /// such that `R` finds `right.R` in the right view. Other layers need the exact name.
+    fn find_channel(&self, layer: &Layer<AnyChannels<FlatSamples>>, name: &str) -> Option<usize> {
+        let channels = &layer.channel_data.list;
+
+        match layer.multi_view() {
+            Some(Ok(views)) => {
+                let (left, right) = views.stereo_pair();
+                let view = if self.eye.get() == Eye::Left { left } else { right };
+                let names = channels.iter().map(|channel| &channel.name);
+                views.position_in_view(view, &Text::from(name), names)
+            }
+            _ => channels.iter().position(|channel| channel.name.eq(name)),
+        }
+    }
+
+    /// Render the displayed view, or combine both views of a multi-view image.
+    fn render_views(&self, image: &LoadedImage) -> ((usize, usize), Vec<Color32>) {
+        let (w, h) = image_size(image);
+        if !self.combines_views(image) {
+            return ((w, h), self.render(image, ImageSlot::A));
+        }
+
+        self.eye.set(Eye::Left);
+        let left = self.render(image, ImageSlot::A);
+        self.eye.set(Eye::Right);
+        let right = self.render(image, ImageSlot::A);
+        self.eye.set(self.displayed_eye());
+
+        if self.stereo_mode == StereoMode::Anaglyph {
+            let pixels = left
+                .iter()
+                .zip(&right)
+                .map(|(left, right)| Color32::from_rgb(left.r(), right.g(), right.b()))
+                .collect();
+
+            return ((w, h), pixels);
+        }
+
+        let pixels = (0..h)
+            .flat_map(|y| {
+                let row = y * w..(y + 1) * w;
+                left[row.clone()].iter().chain(&right[row]).copied()
+            })
+            .collect();
+
+        ((2 * w, h), pixels)
+    }
+
+    fn render(&self, image: &LoadedImage, slot: ImageSlot) -> Vec<Color32> {
+        match image {
+            LoadedImage::Flat(flat) => self
+                .render_isolated_matte(flat, slot)
+                .unwrap_or_else(|| self.render_flat(flat, slot)),
+            LoadedImage::Deep(deep) => self.render_deep(deep),
+        }
+    }
+
+    /// Combine the rendered first image with the second image, for the compare mode.
+    /// Returns the size and the pixels of the texture.
+    /// Pixels outside of the second image are black.
+    fn compare(
+        &self,
+        image: &LoadedImage,
+        pixels: Vec<Color32>,
+        image_b: &LoadedImage,
+    ) -> ((usize, usize), Vec<Color32>) {
+        let (w, h) = image_size(image);
+        let (w_b, h_b) = image_size(image_b);
+        let index_b = |x: usize, y: usize| (x < w_b && y < h_b).then(|| y * w_b + x);
+
+        match self.compare_mode {
+            CompareMode::Off => ((w, h), pixels),
+            CompareMode::Wipe => {
+                let pixels_b = self.render(image_b, ImageSlot::B);
+                let split = (self.wipe_position.clamp(0.0, 1.0) * w as f32) as usize;
+
+                let pixels = (0..w * h)
+                    .map(|i| {
+                        let (x, y) = (i % w, i / w);
+                        if x < split {
+                            pixels[i]
+                        } else {
+                            index_b(x, y).map_or(Color32::BLACK, |j| pixels_b[j])
+                        }
+                    })
+                    .collect();
+
+                ((w, h), pixels)
+            }
+            CompareMode::SideBySide => {
+                let pixels_b = self.render(image_b, ImageSlot::B);
+                let (width, height) = (w + w_b, h.max(h_b));
+
+                let pixels = (0..width * height)
+                    .map(|i| {
+                        let (x, y) = (i % width, i / width);
+                        if x < w {
+                            if y < h { pixels[y * w + x] } else { Color32::BLACK }
+                        } else {
+                            index_b(x - w, y).map_or(Color32::BLACK, |j| pixels_b[j])
+                        }
+                    })
+                    .collect();
+
+                ((width, height), pixels)
+            }
+            CompareMode::AbsoluteDifference | CompareMode::RelativeDifference => {
+                let relative = self.compare_mode == CompareMode::RelativeDifference;
+                let a = self.display_rgb(image, ImageSlot::A, 1.0);
+                let b = self.display_rgb(image_b, ImageSlot::B, 1.0);
+                let (Some((_, a)), Some((_, b))) = (a, b) else {
+                    return ((w, h), pixels);
+                };
+
+                let exposure_multiplier = 2.0_f32.powf(self.exposure);
+                let pixels = (0..w * h)
+                    .map(|i| {
+                        let (x, y) = (i % w, i / w);
+                        let channel_difference = |channel: usize| {
+                            let value_a = a[channel][i];
+                            let value_b = index_b(x, y).map_or(0.0, |j| b[channel][j]);
+                            difference(value_a, value_b, relative)
+                        };
+
+                        let rgb = (
+                            channel_difference(0),
+                            channel_difference(1),
+                            channel_difference(2),
+                        );
+                        if rgb.0.max(rgb.1).max(rgb.2) > self.difference_threshold {
+                            // highlight failing pixels
+                            Color32::from_rgb(255, 0, 255)
+                        } else {
+                            self.display_color(rgb, exposure_multiplier)
+                        }
+                    })
+                    .collect();
+
+                ((w, h), pixels)
+            }
+        }
+    }
+
+    /// Send the histogram or the waveform of the displayed colors, if the scopes panel is open.
+    fn send_scopes(&self) {
+        let Some(mode) = self.scope_mode else { return };
+        let Some(image) = &self.image else { return };
+
+        let exposure_multiplier = 2.0_f32.powf(self.exposure);
+        let Some((width, [red, green, blue])) =
+            self.display_rgb(image, ImageSlot::A, exposure_multiplier)
+        else {
+            return;
+        };
+
+        let event = match mode {
+            ScopeMode::Histogram => ViewerEvent::Histogram(Histogram::compute(&red, &green, &blue)),
+            ScopeMode::Waveform | ScopeMode::Parade => {
+                ViewerEvent::Waveform(Waveform::compute(width, &red, &green, &blue))
+            }
+        };
+
+        self.send(event);
+    }
+
+    /// The linear colors of an image in display primaries, multiplied by the exposure.
+    /// Returns the width of the image and the red, green, and blue values.
+    fn display_rgb(
+        &self,
+        image: &LoadedImage,
+        slot: ImageSlot,
+        exposure_multiplier: f32,
+    ) -> Option<(usize, [Vec<f32>; 3])> {
+        let (width, colors): (usize, Vec<(f32, f32, f32)>) = match image {
+            LoadedImage::Flat(flat) => {
+                let layer = flat.layer_data.first()?;
+                let find_ch = |name: &str| self.find_channel(layer, name);
+
+                let r = self.cached_channel(layer, slot, find_ch("R"));
+                let g = self.cached_channel(layer, slot, find_ch("G"));
+                let b = self.cached_channel(layer, slot, find_ch("B"));
+
+                let colors = (0..layer.size.area())
+                    .map(|i| self.to_display_primaries((r[i], g[i], b[i])))
+                    .collect();
+
+                (layer.size.x(), colors)
+            }
+            LoadedImage::Deep(deep) => {
+                let layer = &deep.layer_data;
+                let samples = &layer.channel_data.list.first()?.sample_data;
+                let find_idx = |name: &str| {
+                    layer.channel_data.list.iter().position(|c| c.name.eq(name))
+                };
+
+                let (r_idx, g_idx, b_idx, a_idx) =
+                    (find_idx("R"), find_idx("G"), find_idx("B"), find_idx("A"));
+
+                let w = layer.size.x();
+                let colors = (0..layer.size.area())
+                    .map(|i| {
+                        let (x, y) = (i % w, i / w);
+                        let rgb =
+                            self.composite_deep_pixel(samples, x, y, r_idx, g_idx, b_idx, a_idx);
+                        self.to_display_primaries(rgb)
+                    })
+                    .collect();
+
+                (w, colors)
+            }
+        };
+
+        let mut channels = [
+            Vec::with_capacity(colors.len()),
+            Vec::with_capacity(colors.len()),
+            Vec::with_capacity(colors.len()),
+        ];
+
+        for (r, g, b) in colors {
+            channels[0].push(r * exposure_multiplier);
+            channels[1].push(g * exposure_multiplier);
+            channels[2].push(b * exposure_multiplier);
+        }
+
+        Some((width, channels))
+    }
+
+    /// A channel of the first layer converted to `f32`.
+    /// Reuses the channels of previous regenerations. Missing channels are zero.
+    fn cached_channel(
+        &self,
+        layer: &Layer<AnyChannels<FlatSamples>>,
+        slot: ImageSlot,
+        index: Option<usize>,
+    ) -> Rc<[f32]> {
+        let Some(channel) = index else {
+            return vec![0.0; layer.size.area()].into();
+        };
+
+        let samples = &layer.channel_data.list[channel].sample_data;
+        let mut cache = self.channel_cache.borrow_mut();
+        let key = ChannelKey {
+            image: slot,
+            layer: 0,
+            channel,
+        };
+
+        cache.get_or_insert_with(key, || match samples {
+            FlatSamples::F16(_) | FlatSamples::F32(_) => samples.to_f32_vec(),
+            FlatSamples::U32(d) => d.iter().map(|&v| v as f32 / u32::MAX as f32).collect(),
+        })
+    }
+
+    fn render_flat(
+        &self,
+        image: &Image<Layers<AnyChannels<FlatSamples>>>,
+        slot: ImageSlot,
+    ) -> Vec<Color32> {
+        let exp_mult = 2.0_f32.powf(self.exposure);
+        let layer = image.layer_data.first();
+
+        // the exposure brightens slow motion, like dark colors
+        if let Some(field) = layer.and_then(|layer| self.motion_field(layer, slot)) {
+            return field
+                .colors(exp_mult)
+                .map(|[r, g, b]| Color32::from_rgb(quantize(r), quantize(g), quantize(b)))
+                .collect();
+        }
+
+        let alpha_channel = layer.and_then(|layer| self.find_channel(layer, "A"));
 
-        self.send(ViewerEvent::TextureReady {
-            generation: self.generation,
-            width,
-            height,
-            pixels,
-        });
+        match (layer, alpha_channel) {
+            (Some(layer), Some(alpha))
+                if self.channel_mode == ChannelMode::Color && self.alpha_display.is_active() =>
+            {
+                let alpha = self.cached_channel(layer, slot, Some(alpha));
+                let colors = self.flat_pixels(image, slot, |rgb| rgb);
+                self.display_colors_with_alpha(colors, &alpha, layer.size.x(), exp_mult)
+            }
+
+            _ => self.flat_pixels(image, slot, |rgb| self.display_color(rgb, exp_mult)),
+        }
     }
 
-    fn render_flat(&self, image: &Image<Layers<AnyChannels<FlatSamples>>>) -> Vec<Color32> {
+    /// The linear colors of the first layer in the current channel mode, converted by `map`.
+    fn flat_pixels<T>(
+        &self,
+        image: &Image<Layers<AnyChannels<FlatSamples>>>,
+        slot: ImageSlot,
+        map: impl Fn((f32, f32, f32)) -> T,
+    ) -> Vec<T> {
         let layer = match image.layer_data.first() {
             Some(l) => l,
             None => return Vec::new(),
@@ -395,14 +1884,8 @@ impl ViewerHandler {
         let (w, h) = (layer.size.x(), layer.size.y());
         let pixel_count = w * h;
 
-        // Find channels
-        let find_ch = |name: &str| {
-            layer
-                .channel_data
-                .list
-                .iter()
-                .find(|c| c.name.to_string() == name)
-        };
+        // Find channels, in the rendered view of multi-view layers
+        let find_ch = |name: &str| self.find_channel(layer, name);
 
         let r_ch = find_ch("R");
         let g_ch = find_ch("G");
@@ -410,15 +1893,8 @@ impl ViewerHandler {
         let a_ch = find_ch("A");
         let z_ch = find_ch("Z").or_else(|| find_ch("depth"));
 
-        // Extract data as f32
-        let get_f32 = |ch: Option<&AnyChannel<FlatSamples>>| -> Vec<f32> {
-            ch.map(|c| match &c.sample_data {
-                FlatSamples::F32(d) => d.clone(),
-                FlatSamples::F16(d) => d.iter().map(|v| v.to_f32()).collect(),
-                FlatSamples::U32(d) => d.iter().map(|&v| v as f32 / u32::MAX as f32).collect(),
-            })
-            .unwrap_or_else(|| vec![0.0; pixel_count])
-        };
+        // Extract data as f32, reusing the channels of previous regenerations
+        let get_f32 = |index: Option<usize>| self.cached_channel(layer, slot, index);
 
         let r = get_f32(r_ch);
         let g = get_f32(g_ch);
@@ -426,12 +1902,17 @@ impl ViewerHandler {
         let a = get_f32(a_ch);
         let z = get_f32(z_ch);
 
-        let exp_mult = 2.0_f32.powf(self.exposure);
+        let custom = match self.channel_mode {
+            ChannelMode::Custom(idx) if idx < layer.channel_data.list.len() => {
+                Some(get_f32(Some(idx)))
+            }
+            _ => None,
+        };
 
         (0..pixel_count)
             .map(|i| {
-                let (mut rv, mut gv, mut bv) = match self.channel_mode {
-                    ChannelMode::Color => (r[i], g[i], b[i]),
+                let rgb = match self.channel_mode {
+                    ChannelMode::Color => self.to_display_primaries((r[i], g[i], b[i])),
                     ChannelMode::Red => (r[i], r[i], r[i]),
                     ChannelMode::Green => (g[i], g[i], g[i]),
                     ChannelMode::Blue => (b[i], b[i], b[i]),
@@ -444,47 +1925,53 @@ impl ViewerHandler {
                         let l = 0.2126 * r[i] + 0.7152 * g[i] + 0.0722 * b[i];
                         (l, l, l)
                     }
-                    ChannelMode::Custom(idx) => {
-                        if let Some(ch) = layer.channel_data.list.get(idx) {
-                            let v = match &ch.sample_data {
-                                FlatSamples::F32(d) => d.get(i).copied().unwrap_or(0.0),
-                                FlatSamples::F16(d) => {
-                                    d.get(i).map(|v| v.to_f32()).unwrap_or(0.0)
-                                }
-                                FlatSamples::U32(d) => {
-                                    d.get(i).map(|&v| v as f32 / u32::MAX as f32).unwrap_or(0.0)
-                                }
-                            };
-                            (v, v, v)
-                        } else {
-                            (0.0, 0.0, 0.0)
-                        }
+                    ChannelMode::Custom(_) => {
+                        let v = custom.as_ref().and_then(|c| c.get(i).copied()).unwrap_or(0.0);
+                        (v, v, v)
                     }
                 };
 
-                // Apply exposure
-                rv *= exp_mult;
-                gv *= exp_mult;
-                bv *= exp_mult;
-
-                // Apply sRGB gamma
-                if self.apply_srgb {
-                    rv = linear_to_srgb(rv);
-                    gv = linear_to_srgb(gv);
-                    bv = linear_to_srgb(bv);
-                }
-
-                // Clamp and convert
-                let rb = (rv.clamp(0.0, 1.0) * 255.0) as u8;
-                let gb = (gv.clamp(0.0, 1.0) * 255.0) as u8;
-                let bb = (bv.clamp(0.0, 1.0) * 255.0) as u8;
-
-                Color32::from_rgb(rb, gb, bb)
+                map(rgb)
             })
             .collect()
     }
 
     fn render_deep(&self, image: &crate::image::write::deep::DeepImage) -> Vec<Color32> {
+        // Apply exposure and sRGB (except for sample count mode)
+        if self.deep_mode == DeepMode::SampleCount {
+            return self.deep_pixels(image, |(r, g, b)| {
+                Color32::from_rgb(quantize(r), quantize(g), quantize(b))
+            });
+        }
+
+        let exp_mult = 2.0_f32.powf(self.exposure);
+        let layer = &image.layer_data;
+        let alpha_channel = layer.channel_data.list.iter().position(|c| c.name.eq("A"));
+
+        match (layer.channel_data.list.first(), alpha_channel) {
+            (Some(first), Some(alpha))
+                if self.deep_mode == DeepMode::Flattened && self.alpha_display.is_active() =>
+            {
+                let samples = &first.sample_data;
+                let alpha: Vec<f32> = (0..layer.size.area())
+                    .map(|i| self.composite_deep_alpha(samples, i, alpha))
+                    .collect();
+
+                let colors = self.deep_pixels(image, |rgb| rgb);
+                self.display_colors_with_alpha(colors, &alpha, layer.size.x(), exp_mult)
+            }
+
+            _ => self.deep_pixels(image, |rgb| self.display_color(rgb, exp_mult)),
+        }
+    }
+
+    /// The linear colors of the deep image in the current deep mode, converted by `map`.
+    /// Pixels without samples are black.
+    fn deep_pixels<T>(
+        &self,
+        image: &crate::image::write::deep::DeepImage,
+        map: impl Fn((f32, f32, f32)) -> T,
+    ) -> Vec<T> {
         let layer = &image.layer_data;
         let (w, h) = (layer.size.x(), layer.size.y());
         let pixel_count = w * h;
@@ -492,7 +1979,7 @@ impl ViewerHandler {
         // Get first channel's DeepSamples (contains all data)
         let samples = match layer.channel_data.list.first() {
             Some(ch) => &ch.sample_data,
-            None => return vec![Color32::BLACK; pixel_count],
+            None => return (0..pixel_count).map(|_| map((0.0, 0.0, 0.0))).collect(),
         };
 
         // Find channel indices
@@ -510,8 +1997,6 @@ impl ViewerHandler {
         let a_idx = find_idx("A");
         let z_idx = find_idx("Z");
 
-        let exp_mult = 2.0_f32.powf(self.exposure);
-
         (0..pixel_count)
             .map(|pixel_idx| {
                 let x = pixel_idx % w;
@@ -519,10 +2004,10 @@ impl ViewerHandler {
                 let count = samples.sample_count(x, y);
 
                 if count == 0 {
-                    return Color32::BLACK;
+                    return map((0.0, 0.0, 0.0));
                 }
 
-                let (rv, gv, bv) = match self.deep_mode {
+                let rgb = match self.deep_mode {
                     DeepMode::SampleCount => {
                         // Heatmap: 0 = black, max = red
                         let max_count = 64.0; // Arbitrary max for heatmap
@@ -532,7 +2017,8 @@ impl ViewerHandler {
                     }
                     DeepMode::Flattened => {
                         // Over composite all samples
-                        self.composite_deep_pixel(samples, x, y, r_idx, g_idx, b_idx, a_idx)
+                        let rgb = self.composite_deep_pixel(samples, x, y, r_idx, g_idx, b_idx, a_idx);
+                        self.to_display_primaries(rgb)
                     }
                     DeepMode::FirstSample | DeepMode::LastSample => {
                         let sample_idx = if self.deep_mode == DeepMode::FirstSample {
@@ -565,29 +2051,336 @@ impl ViewerHandler {
                     }
                 };
 
-                // Apply exposure (except for sample count mode)
-                let (mut rv, mut gv, mut bv) = if self.deep_mode == DeepMode::SampleCount {
-                    (rv, gv, bv)
+                map(rgb)
+            })
+            .collect()
+    }
+
+    /// Apply exposure and the display mode to a linear color, and quantize it for display.
+    fn display_color(&self, (r, g, b): (f32, f32, f32), exposure_multiplier: f32) -> Color32 {
+        let m = exposure_multiplier;
+        let (r, g, b) = self.display_value((r * m, g * m, b * m));
+        Color32::from_rgb(quantize(r), quantize(g), quantize(b))
+    }
+
+    /// Display premultiplied linear colors with the alpha display settings:
+    /// optionally divide the colors by alpha before the display transform,
+    /// composite them over the background in display space, and tint pixels without coverage.
+    fn display_colors_with_alpha(
+        &self,
+        colors: Vec<(f32, f32, f32)>,
+        alpha: &[f32],
+        width: usize,
+        exposure_multiplier: f32,
+    ) -> Vec<Color32> {
+        let settings = self.alpha_display;
+
+        colors
+            .into_iter()
+            .zip(alpha)
+            .enumerate()
+            .map(|(index, ((r, g, b), &alpha))| {
+                let straight = if settings.unpremultiply && alpha > 0.0 {
+                    (r / alpha, g / alpha, b / alpha)
                 } else {
-                    (rv * exp_mult, gv * exp_mult, bv * exp_mult)
+                    (r, g, b)
                 };
 
-                // Apply sRGB
-                if self.apply_srgb && self.deep_mode != DeepMode::SampleCount {
-                    rv = linear_to_srgb(rv);
-                    gv = linear_to_srgb(gv);
-                    bv = linear_to_srgb(bv);
-                }
+                let color = self.display_color(straight, exposure_multiplier);
+                let coverage = alpha.clamp(0.0, 1.0);
+
+                let mut rgb = match settings.background.color(index % width, index / width) {
+                    None => [color.r(), color.g(), color.b()].map(f32::from),
+                    Some(background) => {
+                        // straight colors are weighted by their coverage, premultiplied colors already are
+                        let weight = if settings.unpremultiply { coverage } else { 1.0 };
+                        let [r, g, b] = [color.r(), color.g(), color.b()];
+
+                        [(r, background[0]), (g, background[1]), (b, background[2])].map(
+                            |(value, background)| {
+                                value as f32 * weight + background as f32 * (1.0 - coverage)
+                            },
+                        )
+                    }
+                };
 
-                let rb = (rv.clamp(0.0, 1.0) * 255.0) as u8;
-                let gb = (gv.clamp(0.0, 1.0) * 255.0) as u8;
-                let bb = (bv.clamp(0.0, 1.0) * 255.0) as u8;
+                if settings.overlay && alpha <= 0.0 {
+                    rgb = [
+                        rgb[0] * 0.5 + 127.5,
+                        rgb[1] * 0.5,
+                        rgb[2] * 0.5 + 127.5,
+                    ];
+                }
 
-                Color32::from_rgb(rb, gb, bb)
+                let [r, g, b] = rgb.map(|value| value.round().clamp(0.0, 255.0) as u8);
+                Color32::from_rgb(r, g, b)
             })
             .collect()
     }
 
+    /// Map an exposed linear color to a display color with the display mode.
+    /// Heatmaps and false color map the luminance, so that their legends are exact.
+    fn display_value(&self, rgb: (f32, f32, f32)) -> (f32, f32, f32) {
+        let luminance = 0.2126 * rgb.0 + 0.7152 * rgb.1 + 0.0722 * rgb.2;
+        let mapped = match (self.display_mode, &self.colormap) {
+            (DisplayMode::Normal, _) if self.show_clipping && rgb.0.max(rgb.1).max(rgb.2) > 1.0 => {
+                [1.0, 0.0, 0.0]
+            }
+            (DisplayMode::Normal, _) if self.show_clipping && rgb.0.min(rgb.1).min(rgb.2) < 0.0 => {
+                [0.0, 0.0, 1.0]
+            }
+            (DisplayMode::Normal, _) => return self.color.display(rgb),
+            (DisplayMode::FalseColor, _) => match colormap::false_color(luminance) {
+                Some(band) => band.color.map(|value| value as f32 / 255.0),
+                None => return self.color.display((luminance, luminance, luminance)),
+            },
+            (DisplayMode::Turbo, _) => colormap::turbo(luminance),
+            (DisplayMode::Viridis, _) => colormap::viridis(luminance),
+            (DisplayMode::Lut, Some(colormap)) => colormap.sample(luminance),
+            (DisplayMode::Lut, None) => return self.color.display(rgb),
+            // flat images with vector channels are rendered by `render_flat`
+            (DisplayMode::MotionVectors, _) => return self.color.display(rgb),
+        };
+
+        (mapped[0], mapped[1], mapped[2])
+    }
+
+    /// Load a 1D LUT and display the image with it.
+    fn load_colormap(&mut self, path: &Path) {
+        match Colormap::from_file(path) {
+            Ok(colormap) => {
+                self.send(ViewerEvent::ColormapLoaded(colormap.clone()));
+                self.colormap = Some(colormap);
+                self.display_mode = DisplayMode::Lut;
+                self.regenerate();
+            }
+            Err(e) => self.send(ViewerEvent::Error(format!(
+                "Failed to load colormap {}: {e}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Send the original float values of a pixel to the pixel inspector.
+    /// Samples the loaded image instead of the 8-bit texture.
+    fn query_pixel(&self, x: usize, y: usize) {
+        let Some(image) = &self.image else { return };
+        let exposure_multiplier = 2.0_f32.powf(self.exposure);
+
+        // the right half of side by side views shows the right view
+        let (width, _) = image_size(image);
+        let right_half =
+            self.stereo_mode == StereoMode::SideBySide && self.combines_views(image) && x >= width;
+
+        let pixel = if right_half {
+            self.eye.set(Eye::Right);
+            let pixel = self.pixel_values(image, x - width, y);
+            self.eye.set(self.displayed_eye());
+            pixel
+        } else {
+            self.pixel_values(image, x, y)
+        };
+
+        let Some((values, rgb)) = pixel else { return };
+        let color = self.display_color(self.to_display_primaries(rgb), exposure_multiplier);
+        self.send(ViewerEvent::PixelValue { x, y, values, color });
+    }
+
+    /// The values of all channels of a pixel, and its linear color in the displayed view.
+    fn pixel_values(
+        &self,
+        image: &LoadedImage,
+        x: usize,
+        y: usize,
+    ) -> Option<(Vec<(String, f32)>, (f32, f32, f32))> {
+        let (values, rgb) = match image {
+            LoadedImage::Flat(flat) => {
+                let layer = flat.layer_data.first()?;
+                if x >= layer.size.x() || y >= layer.size.y() {
+                    return None;
+                }
+
+                let index = y * layer.size.x() + x;
+                let values: Vec<(String, f32)> = layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .map(|c| {
+                        let value = c.sample_data.value_by_flat_index(index).to_f32();
+                        (c.name.to_string(), value)
+                    })
+                    .collect();
+
+                let value_of = |name: &str| {
+                    self.find_channel(layer, name).map_or(0.0, |index| values[index].1)
+                };
+
+                let rgb = (value_of("R"), value_of("G"), value_of("B"));
+                (values, rgb)
+            }
+            LoadedImage::Deep(deep) => {
+                let layer = &deep.layer_data;
+                if x >= layer.size.x() || y >= layer.size.y() {
+                    return None;
+                }
+
+                let first = layer.channel_data.list.first()?;
+                let samples = &first.sample_data;
+
+                let find_idx = |name: &str| {
+                    layer.channel_data.list.iter().position(|c| c.name.to_string() == name)
+                };
+
+                let (r_idx, g_idx, b_idx, a_idx) =
+                    (find_idx("R"), find_idx("G"), find_idx("B"), find_idx("A"));
+
+                let rgb = self.composite_deep_pixel(samples, x, y, r_idx, g_idx, b_idx, a_idx);
+
+                // deep pixels show the flattened color
+                let mut values = vec![("samples".to_string(), samples.sample_count(x, y) as f32)];
+                let flattened = [("R", r_idx, rgb.0), ("G", g_idx, rgb.1), ("B", b_idx, rgb.2)];
+                for (name, index, value) in flattened {
+                    if index.is_some() {
+                        values.push((name.to_string(), value));
+                    }
+                }
+
+                (values, rgb)
+            }
+        };
+
+        Some((values, rgb))
+    }
+
+    /// The cryptomattes of the first layer of a flat image.
+    fn find_cryptomattes(&self) -> Vec<Cryptomatte> {
+        let Some(LoadedImage::Flat(flat)) = &self.image else { return Vec::new() };
+        let Some(layer) = flat.layer_data.first() else { return Vec::new() };
+
+        Cryptomatte::find_all(&layer.attributes, &layer.channel_data).unwrap_or_else(|e| {
+            self.log(&format!("Ignoring cryptomattes: {e}"));
+            Vec::new()
+        })
+    }
+
+    /// Send the most covering object of each cryptomatte in a pixel.
+    fn pick_cryptomatte(&self, x: usize, y: usize) {
+        let Some(LoadedImage::Flat(flat)) = &self.image else { return };
+        let Some(layer) = flat.layer_data.first() else { return };
+
+        if x >= layer.size.x() || y >= layer.size.y() {
+            return;
+        }
+
+        let pixel_index = y * layer.size.x() + x;
+        let objects = self
+            .cryptomattes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, cryptomatte)| {
+                let objects = cryptomatte.objects_at(&layer.channel_data, pixel_index);
+                let &(id, coverage) = objects.first()?;
+
+                Some(CryptomatteObject {
+                    cryptomatte: index,
+                    cryptomatte_name: cryptomatte.name.clone(),
+                    id,
+                    name: cryptomatte
+                        .name_of(id)
+                        .map_or_else(|| format!("{id:08x}"), str::to_string),
+                    coverage,
+                })
+            })
+            .collect();
+
+        self.send(ViewerEvent::CryptomattePicked { x, y, objects });
+    }
+
+    /// The coverage of the isolated cryptomatte object as a grayscale image, if any is isolated.
+    fn render_isolated_matte(
+        &self,
+        image: &Image<Layers<AnyChannels<FlatSamples>>>,
+        slot: ImageSlot,
+    ) -> Option<Vec<Color32>> {
+        self.isolated_matte_pixels(image, slot, |rgb| self.display_color(rgb, 1.0))
+    }
+
+    /// The coverage of the isolated cryptomatte object as gray colors, converted by `map`.
+    fn isolated_matte_pixels<T>(
+        &self,
+        image: &Image<Layers<AnyChannels<FlatSamples>>>,
+        slot: ImageSlot,
+        map: impl Fn((f32, f32, f32)) -> T,
+    ) -> Option<Vec<T>> {
+        let (index, id) = self.isolated_matte?;
+        if slot != ImageSlot::A {
+            return None;
+        }
+
+        let layer = image.layer_data.first()?;
+        let matte = self.cryptomattes.get(index)?.matte(&layer.channel_data, id);
+
+        Some(
+            matte
+                .into_iter()
+                .map(|coverage| map((coverage, coverage, coverage)))
+                .collect(),
+        )
+    }
+
+    /// Send all samples of a pixel of the deep image to the deep sample inspector.
+    fn inspect_deep_pixel(&self, x: usize, y: usize) {
+        let Some(LoadedImage::Deep(deep)) = &self.image else { return };
+        let layer = &deep.layer_data;
+
+        if x >= layer.size.x() || y >= layer.size.y() {
+            return;
+        }
+
+        let Some(first) = layer.channel_data.list.first() else { return };
+        let samples = &first.sample_data;
+
+        let find_idx = |name: &str| {
+            layer.channel_data.list.iter().position(|c| c.name.to_string() == name)
+        };
+
+        let (r_idx, g_idx, b_idx, a_idx) =
+            (find_idx("R"), find_idx("G"), find_idx("B"), find_idx("A"));
+        let (z_idx, z_back_idx) = (find_idx("Z"), find_idx("ZBack"));
+
+        let (start, end) = samples.sample_range(y * samples.width + x);
+        let pixel_samples = (start..end)
+            .map(|i| {
+                let value = |idx: Option<usize>, default: f32| {
+                    self.get_channel_sample(samples, idx, i).unwrap_or(default)
+                };
+
+                let z = value(z_idx, 0.0);
+                DeepSampleInfo {
+                    z,
+                    z_back: value(z_back_idx, z),
+                    rgba: [
+                        value(r_idx, 0.0),
+                        value(g_idx, 0.0),
+                        value(b_idx, 0.0),
+                        value(a_idx, 1.0),
+                    ],
+                }
+            })
+            .collect();
+
+        self.send(ViewerEvent::DeepPixelInfo(DeepPixelInfo {
+            x,
+            y,
+            samples: pixel_samples,
+        }));
+    }
+
+    /// Convert a linear rgb color from the input color space to the working space of the display.
+    fn to_display_primaries(&self, rgb: (f32, f32, f32)) -> (f32, f32, f32) {
+        self.color.to_working(rgb)
+    }
+
     fn normalize_depth(&self, z: f32) -> f32 {
         let v = match self.depth_mode {
             DepthMode::Raw => z,
@@ -661,6 +2454,21 @@ impl ViewerHandler {
         (accum_r, accum_g, accum_b)
     }
 
+    /// The alpha of all samples of the pixel composited over each other.
+    fn composite_deep_alpha(
+        &self,
+        samples: &crate::image::deep::DeepSamples,
+        pixel_idx: usize,
+        a_idx: usize,
+    ) -> f32 {
+        let (start, end) = samples.sample_range(pixel_idx);
+
+        (start..end).fold(0.0, |accum_a, i| {
+            let a = self.get_channel_sample(samples, Some(a_idx), i).unwrap_or(1.0);
+            a + accum_a * (1.0 - a)
+        })
+    }
+
     fn composite_deep_slice(
         &self,
         samples: &crate::image::deep::DeepSamples,
@@ -800,16 +2608,8 @@ impl ViewerHandler {
 
     fn fit_to_window(&mut self) {
         if let Some(image) = &self.image {
-            let (img_w, img_h) = match image {
-                LoadedImage::Flat(f) => f
-                    .layer_data
-                    .first()
-                    .map(|l| (l.size.x() as f32, l.size.y() as f32))
-                    .unwrap_or((1.0, 1.0)),
-                LoadedImage::Deep(d) => {
-                    (d.layer_data.size.x() as f32, d.layer_data.size.y() as f32)
-                }
-            };
+            let (img_w, img_h) = image_size(image);
+            let (img_w, img_h) = (img_w as f32, img_h as f32);
 
             let vp_w = self.viewport[0];
             let vp_h = self.viewport[1];
@@ -834,11 +2634,70 @@ impl ViewerHandler {
         });
     }
     
+    /// Send the deep samples as points with their pixel position, depth, and displayed color.
+    fn send_deep_points(&self, deep: &crate::image::write::deep::DeepImage) {
+        let layer = &deep.layer_data;
+        let (w, h) = (layer.size.x(), layer.size.y());
+        let Some(first) = layer.channel_data.list.first() else { return };
+        let samples = &first.sample_data;
+
+        let find_idx = |name: &str| {
+            layer.channel_data.list.iter().position(|c| c.name.to_string() == name)
+        };
+
+        let (r_idx, g_idx, b_idx, a_idx) =
+            (find_idx("R"), find_idx("G"), find_idx("B"), find_idx("A"));
+        let Some(z_idx) = find_idx("Z") else {
+            self.send(ViewerEvent::Error("Deep image has no Z channel".into()));
+            return;
+        };
+
+        let step = (samples.total_samples() / MAX_DEEP_POINTS).max(1);
+        let exposure_multiplier = 2.0_f32.powf(self.exposure);
+        let mut points = Vec::new();
+        let mut colors = Vec::new();
+
+        for pixel_idx in 0..w * h {
+            let (start, end) = samples.sample_range(pixel_idx);
+            let first_sample = (start + step - 1) / step * step;
+
+            for i in (first_sample..end).step_by(step) {
+                let value = |idx: Option<usize>, default: f32| {
+                    self.get_channel_sample(samples, idx, i).unwrap_or(default)
+                };
+
+                let rgb = (value(r_idx, 0.0), value(g_idx, 0.0), value(b_idx, 0.0));
+                let alpha = value(a_idx, 1.0);
+                let color = self.display_color(self.to_display_primaries(rgb), exposure_multiplier);
+
+                let x = (pixel_idx % w) as f32 + 0.5;
+                let y = (pixel_idx / w) as f32 + 0.5;
+                points.push([x, y, value(Some(z_idx), 0.0)]);
+
+                let [r, g, b, _] = color.to_array();
+                colors.push([r, g, b, (alpha.clamp(0.0, 1.0) * 255.0) as u8]);
+            }
+        }
+
+        self.send(ViewerEvent::DeepPoints3DReady {
+            width: w,
+            height: h,
+            points,
+            colors,
+        });
+    }
+
     /// Send depth data for 3D visualization.
     fn send_3d_data(&self) {
         let Some(image) = &self.image else {
             return;
         };
+
+        // Deep point clouds contain every sample, not only the nearest one
+        if let (LoadedImage::Deep(deep), View3DMode::PointCloud) = (image, self.view_3d_mode) {
+            self.send_deep_points(deep);
+            return;
+        }
         
         // Extract depth channel data
         let (width, height, depth) = match image {
@@ -859,11 +2718,7 @@ impl ViewerHandler {
                     return;
                 };
                 
-                let depth: Vec<f32> = match &ch.sample_data {
-                    FlatSamples::F32(data) => data.clone(),
-                    FlatSamples::F16(data) => data.iter().map(|v| v.to_f32()).collect(),
-                    FlatSamples::U32(data) => data.iter().map(|&v| v as f32).collect(),
-                };
+                let depth: Vec<f32> = ch.sample_data.to_f32_vec();
                 
                 (w, h, depth)
             }
@@ -910,15 +2765,404 @@ impl ViewerHandler {
     }
 }
 
-/// Linear to sRGB gamma.
-fn linear_to_srgb(x: f32) -> f32 {
-    if x <= 0.0031308 {
-        x * 12.92
+/// Maximum number of deep samples sent to the 3D point cloud. Larger images are subsampled.
+const MAX_DEEP_POINTS: usize = 250_000;
+
+/// The approximate resolution of the preview that is displayed while a large image is loading.
+/// Images up to twice this size are displayed without a preview.
+const PREVIEW_SIZE: usize = 1024;
+
+/// Sends the progress of reading a file to the UI, and stops reading when the UI cancels the load.
+struct LoadProgress {
+    tx: Sender<ViewerEvent>,
+    cancellation: CancellationToken,
+}
+
+impl LoadProgress {
+    /// The progress callback for the reading builders.
+    /// Only sends an event when the progress advanced by at least one percent.
+    fn callback(&self) -> impl FnMut(f64) {
+        let tx = self.tx.clone();
+        let mut last_sent = f64::NEG_INFINITY;
+
+        move |progress| {
+            if progress - last_sent >= 0.01 || progress >= 1.0 {
+                last_sent = progress;
+                let _ = tx.send(ViewerEvent::LoadProgress(progress as f32));
+            }
+        }
+    }
+}
+
+/// Read a deep image, or a flat image if the file contains no deep data.
+/// Flat images are read at the specified resolution level, or the smallest level if there are fewer levels.
+fn read_image(path: &Path, level: usize, progress: LoadProgress) -> Result<LoadedImage> {
+    if ImportFormat::from_path(path).is_ok() {
+        let image = Image::from_layer(crate::import::read_layer_from_file(path)?);
+        let layers = smallvec::smallvec![image.layer_data];
+        return Ok(LoadedImage::Flat(Image::from_layers(image.attributes, layers)));
+    }
+
+    let deep = read_deep()
+        .all_channels()
+        .first_valid_layer()
+        .all_attributes()
+        .on_progress(progress.callback())
+        .with_cancellation(progress.cancellation.clone())
+        .from_file(path);
+
+    match deep {
+        Ok(image) => Ok(LoadedImage::Deep(image)),
+        Err(Error::Aborted) => Err(Error::Aborted),
+        Err(_) => read()
+            .no_deep_data()
+            .specific_resolution_level(move |levels| select_level(levels, level))
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .on_progress(progress.callback())
+            .with_cancellation(progress.cancellation)
+            .from_file(path)
+            .map(|image| LoadedImage::Flat(full_resolution(image))),
+    }
+}
+
+/// The mip map level with the specified index, or the rip map level with this index in x and y.
+/// Uses the smallest of these levels if there are fewer levels.
+fn select_level(levels: &[LevelInfo], level: usize) -> Vec2<usize> {
+    levels
+        .iter()
+        .map(|info| info.index)
+        .filter(|index| index.x() == index.y() && index.x() <= level)
+        .max_by_key(|index| index.x())
+        .unwrap_or(Vec2(0, 0))
+}
+
+/// The name, type, compression, and levels of a part, for the part and level selectors.
+fn part_info((index, header): (usize, &Header)) -> PartInfo {
+    let levels = match header.blocks {
+        BlockDescription::Tiles(tiles) if !header.deep => match tiles.level_mode {
+            LevelMode::Singular => vec![header.layer_size],
+            LevelMode::MipMap => mip_map_levels(tiles.rounding_mode, header.layer_size)
+                .map(|(_, size)| size)
+                .collect(),
+            LevelMode::RipMap => rip_map_levels(tiles.rounding_mode, header.layer_size)
+                .filter(|(index, _)| index.x() == index.y())
+                .map(|(_, size)| size)
+                .collect(),
+        },
+        _ => vec![header.layer_size],
+    };
+
+    PartInfo {
+        name: part_name(index, header),
+        deep: header.deep,
+        tiled: header.blocks.has_tiles(),
+        compression: header.compression.to_string(),
+        levels: levels.into_iter().map(|size| (size.x(), size.y())).collect(),
+    }
+}
+
+/// All attributes of a part, formatted for the metadata inspector.
+fn part_metadata((index, header): (usize, &Header)) -> PartMetadata {
+    let mut attributes: Vec<AttributeInfo> = header
+        .all_named_attributes()
+        .map(|(name, value)| AttributeInfo {
+            name: Text::from_slice_unchecked(name).to_string(),
+            kind: Text::from_slice_unchecked(value.kind_name()).to_string(),
+            value: match JsonValue::from(&value) {
+                JsonValue::String(text) => text,
+                json => json.to_compact_string(),
+            },
+        })
+        .collect();
+
+    attributes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    PartMetadata {
+        name: part_name(index, header),
+        attributes,
+    }
+}
+
+fn part_name(index: usize, header: &Header) -> String {
+    header
+        .own_attributes
+        .layer_name
+        .as_ref()
+        .map_or_else(|| format!("part {index}"), |name| name.to_string())
+}
+
+/// Decode only one part of a file, at the specified resolution level, see `read_image`.
+fn read_part(
+    path: &Path,
+    index: usize,
+    deep: bool,
+    level: usize,
+    progress: LoadProgress,
+) -> Result<LoadedImage> {
+    if deep {
+        return read_deep()
+            .all_channels()
+            .specific_layer(index)
+            .all_attributes()
+            .on_progress(progress.callback())
+            .with_cancellation(progress.cancellation)
+            .from_file(path)
+            .map(LoadedImage::Deep);
+    }
+
+    read()
+        .no_deep_data()
+        .specific_resolution_level(move |levels| select_level(levels, level))
+        .all_channels()
+        .specific_layer(index)
+        .all_attributes()
+        .on_progress(progress.callback())
+        .with_cancellation(progress.cancellation)
+        .from_file(path)
+        .map(|image| {
+            let layers = smallvec::smallvec![image.layer_data];
+            LoadedImage::Flat(full_resolution(Image::from_layers(image.attributes, layers)))
+        })
+}
+
+/// Decode a smaller resolution level of a flat part, for the preview of a large image.
+fn read_level_preview(
+    path: &Path,
+    index: usize,
+    level: usize,
+    cancellation: CancellationToken,
+) -> Result<FlatImage> {
+    read()
+        .no_deep_data()
+        .specific_resolution_level(move |levels| select_level(levels, level))
+        .all_channels()
+        .specific_layer(index)
+        .all_attributes()
+        .with_cancellation(cancellation)
+        .from_file(path)
+        .map(|image| {
+            let layers = smallvec::smallvec![image.layer_data];
+            full_resolution(Image::from_layers(image.attributes, layers))
+        })
+}
+
+/// Decode every `stride`-th pixel of every `stride`-th line of a flat part,
+/// for the preview of a large image without smaller resolution levels.
+/// Blocks that contain none of these lines are skipped without decompressing them.
+fn read_strided_preview(
+    path: &Path,
+    index: usize,
+    stride: usize,
+    cancellation: CancellationToken,
+) -> Result<FlatImage> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let reader = crate::block::read(file, false)?;
+    let header = reader
+        .headers()
+        .get(index)
+        .ok_or(Error::invalid("part index"))?
+        .clone();
+
+    if header.channels.list.iter().any(|channel| channel.sampling != Vec2(1, 1)) {
+        return Err(Error::unsupported("previews of subsampled channels"));
+    }
+
+    let size = Vec2(
+        (header.layer_size.x() + stride - 1) / stride,
+        (header.layer_size.y() + stride - 1) / stride,
+    );
+    let mut channels: Vec<FlatSamples> = header
+        .channels
+        .list
+        .iter()
+        .map(|channel| match channel.sample_type {
+            SampleType::F16 => FlatSamples::F16(vec![f16::ZERO; size.area()]),
+            SampleType::F32 => FlatSamples::F32(vec![0.0; size.area()]),
+            SampleType::U32 => FlatSamples::U32(vec![0; size.area()]),
+        })
+        .collect();
+
+    let chunks = reader.filter_chunks(false, |_, tile, block| {
+        let first_line = (block.pixel_position.y() + stride - 1) / stride * stride;
+        let end = block.pixel_position.y() + block.pixel_size.y();
+        block.layer == index && tile.level_index == Vec2(0, 0) && first_line < end
+    })?;
+
+    chunks
+        .on_progress(|_| ())
+        .cancel_with(Some(cancellation))
+        .decompress_sequential(false, |_, block| {
+            for line in block.lines(&header.channels) {
+                let position = line.location.position;
+                if position.y() % stride != 0 {
+                    continue;
+                }
+
+                let row = position.y() / stride * size.x();
+                let row = row..row + size.x();
+
+                match &mut channels[line.location.channel] {
+                    FlatSamples::F16(samples) => insert_strided(line, stride, &mut samples[row])?,
+                    FlatSamples::F32(samples) => insert_strided(line, stride, &mut samples[row])?,
+                    FlatSamples::U32(samples) => insert_strided(line, stride, &mut samples[row])?,
+                }
+            }
+
+            Ok(())
+        })?;
+
+    let channels = header
+        .channels
+        .list
+        .iter()
+        .zip(channels)
+        .map(|(channel, samples)| AnyChannel::new(channel.name.clone(), samples))
+        .collect();
+
+    let layer = Layer::new(
+        size,
+        header.own_attributes.clone(),
+        Encoding::default(),
+        AnyChannels::sort(channels),
+    );
+
+    Ok(Image::from_layers(
+        ImageAttributes::new(header.shared_attributes.display_window),
+        smallvec::smallvec![layer],
+    ))
+}
+
+/// Copy every `stride`-th sample of the line into the row of the preview.
+fn insert_strided<T: crate::io::Data>(
+    line: LineRef<'_>,
+    stride: usize,
+    row: &mut [T],
+) -> crate::error::UnitResult {
+    for (x, sample) in (line.location.position.x()..).zip(line.read_samples::<T>()) {
+        if x % stride == 0 {
+            row[x / stride] = sample?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reconstruct rgb channels from luminance and chroma channels, and upsample other subsampled
+/// channels, such that all channels can be displayed with the resolution of their layer.
+fn full_resolution(mut image: FlatImage) -> FlatImage {
+    let chromaticities = image.attributes.chromaticities;
+
+    for layer in &mut image.layer_data {
+        if layer.has_luminance_chroma() {
+            if let Ok(rgb) = layer.luminance_chroma_to_rgb(chromaticities) {
+                *layer = rgb;
+            }
+        }
+
+        let channels = &layer.channel_data.list;
+        if channels.iter().any(|channel| channel.sampling != Vec2(1, 1)) {
+            *layer = layer.to_full_resolution();
+        }
+    }
+
+    image
+}
+
+fn chromaticities(image: &LoadedImage) -> Option<crate::meta::attribute::Chromaticities> {
+    match image {
+        LoadedImage::Flat(flat) => flat.attributes.chromaticities,
+        LoadedImage::Deep(deep) => deep.attributes.chromaticities,
+    }
+}
+
+/// The layout of the displayed layer, if it is an environment map.
+fn environment_map(image: &LoadedImage) -> Option<EnvironmentMap> {
+    match image {
+        LoadedImage::Flat(flat) => flat.layer_data.first()?.attributes.environment_map,
+        LoadedImage::Deep(deep) => deep.layer_data.attributes.environment_map,
+    }
+}
+
+/// The views of the displayed layer of a flat multi-view image.
+fn multi_view(image: &LoadedImage) -> Option<MultiView> {
+    match image {
+        LoadedImage::Flat(flat) => flat.layer_data.first()?.multi_view()?.ok(),
+        LoadedImage::Deep(_) => None,
+    }
+}
+
+/// The name of each view of a multi-view layer, and the indices of its channels.
+fn view_channels(layer: &Layer<AnyChannels<FlatSamples>>) -> Vec<(String, Vec<usize>)> {
+    let Some(Ok(views)) = layer.multi_view() else { return Vec::new() };
+    let channels = &layer.channel_data.list;
+
+    views
+        .views()
+        .iter()
+        .map(|view| {
+            let indices = (0..channels.len())
+                .filter(|&index| views.view_of_channel(&channels[index].name) == Some(view))
+                .collect();
+
+            (view.to_string(), indices)
+        })
+        .collect()
+}
+
+/// The data window of the displayed layer, and the display window of the image.
+fn windows(image: &LoadedImage) -> (IntegerBounds, IntegerBounds) {
+    match image {
+        LoadedImage::Flat(flat) => {
+            let data_window = flat.layer_data.first().map_or_else(IntegerBounds::zero, |layer| {
+                IntegerBounds::new(layer.attributes.layer_position, layer.size)
+            });
+
+            (data_window, flat.attributes.display_window)
+        }
+        LoadedImage::Deep(deep) => {
+            let layer = &deep.layer_data;
+            let data_window = IntegerBounds::new(layer.attributes.layer_position, layer.size);
+            (data_window, deep.attributes.display_window)
+        }
+    }
+}
+
+/// The file name of a path, for messages.
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned()
+}
+
+/// The size of the displayed layer of an image.
+fn image_size(image: &LoadedImage) -> (usize, usize) {
+    match image {
+        LoadedImage::Flat(f) => f
+            .layer_data
+            .first()
+            .map(|l| (l.size.x(), l.size.y()))
+            .unwrap_or((0, 0)),
+        LoadedImage::Deep(d) => (d.layer_data.size.x(), d.layer_data.size.y()),
+    }
+}
+
+/// The absolute difference of two values, or the difference relative to the larger absolute value.
+fn difference(a: f32, b: f32, relative: bool) -> f32 {
+    let difference = (a - b).abs();
+    let largest = a.abs().max(b.abs());
+
+    if relative && largest > 0.0 {
+        difference / largest
     } else {
-        1.055 * x.powf(1.0 / 2.4) - 0.055
+        difference
     }
 }
 
+/// Clamp a display value and convert it to 8 bits.
+fn quantize(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0) as u8
+}
+
 /// Heatmap: 0=blue, 0.25=cyan, 0.5=green, 0.75=yellow, 1=red
 fn heatmap_color(t: f32) -> (f32, f32, f32) {
     let t = t.clamp(0.0, 1.0);
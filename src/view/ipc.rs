@@ -0,0 +1,189 @@
+//! Local socket server for external tools, such as renderers streaming progressive results.
+//!
+//! The server accepts the commands of the tev protocol, see `crate::interop::tev`:
+//! opening files, creating images, and updating regions of created images.
+//! Display settings are sent with the additional packet type `DISPLAY_SETTINGS`.
+
+use std::convert::TryInto;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use crate::interop::tev::{read_command, TevCommand};
+use crate::view::messages::ViewerMsg;
+
+/// The packet type of display settings, which tev does not define.
+/// Send with `TevClient::send_custom(DISPLAY_SETTINGS, &settings.to_payload())`.
+pub const DISPLAY_SETTINGS: u8 = 128;
+
+/// Changes to how the viewer displays the image. Settings that are `None` are not changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplaySettings {
+    /// Exposure in stops.
+    pub exposure: Option<f32>,
+
+    /// Whether to apply the sRGB transfer function.
+    pub srgb: Option<bool>,
+
+    /// A channel mode like `color`, `red`, or `luminance`, or the name of a channel.
+    pub channel: Option<String>,
+}
+
+impl DisplaySettings {
+    const EXPOSURE: u8 = 1;
+    const SRGB: u8 = 2;
+    const CHANNEL: u8 = 4;
+
+    /// A byte with the flags of the present settings, the exposure as a little-endian `f32`,
+    /// the sRGB flag as a byte, and the channel as a zero-terminated string.
+    pub fn to_payload(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.exposure.is_some() {
+            flags |= Self::EXPOSURE;
+        }
+        if self.srgb.is_some() {
+            flags |= Self::SRGB;
+        }
+        if self.channel.is_some() {
+            flags |= Self::CHANNEL;
+        }
+
+        let mut payload = vec![flags];
+        payload.extend_from_slice(&self.exposure.unwrap_or(0.0).to_le_bytes());
+        payload.push(self.srgb.unwrap_or(false) as u8);
+        payload.extend_from_slice(self.channel.as_deref().unwrap_or("").as_bytes());
+        payload.push(0);
+        payload
+    }
+
+    /// Returns `None` if the payload is too short or the channel is not terminated.
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let flags = *payload.first()?;
+        let exposure = f32::from_le_bytes(payload.get(1..5)?.try_into().ok()?);
+        let srgb = *payload.get(5)? != 0;
+
+        let channel = payload.get(6..)?;
+        let channel = &channel[..channel.iter().position(|&byte| byte == 0)?];
+
+        Some(DisplaySettings {
+            exposure: (flags & Self::EXPOSURE != 0).then(|| exposure),
+            srgb: (flags & Self::SRGB != 0).then(|| srgb),
+            channel: (flags & Self::CHANNEL != 0)
+                .then(|| String::from_utf8_lossy(channel).into_owned()),
+        })
+    }
+}
+
+/// Listen for connections on a background thread, each served by its own thread.
+/// Returns an error if the address cannot be bound.
+pub fn spawn_server(address: &str, tx: Sender<ViewerMsg>, verbose: u8) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+
+    if verbose > 0 {
+        eprintln!("[viewer] Listening on {}", listener.local_addr()?);
+    }
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let tx = tx.clone();
+                    thread::spawn(move || serve(stream, tx, verbose));
+                }
+                Err(e) if verbose > 0 => eprintln!("[viewer] Connection failed: {e}"),
+                Err(_) => {}
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn serve(stream: TcpStream, tx: Sender<ViewerMsg>, verbose: u8) {
+    let mut read = BufReader::new(stream);
+
+    loop {
+        match read_command(&mut read) {
+            Ok(Some(command)) => {
+                if let Some(msg) = to_message(command) {
+                    if tx.send(msg).is_err() {
+                        return; // the viewer was closed
+                    }
+                }
+            }
+            Ok(None) => return,
+            Err(e) => {
+                if verbose > 0 {
+                    eprintln!("[viewer] Invalid command: {e}");
+                }
+                return;
+            }
+        }
+    }
+}
+
+fn to_message(command: TevCommand) -> Option<ViewerMsg> {
+    match command {
+        TevCommand::OpenImage { path, .. } => Some(ViewerMsg::LoadImage(path.into())),
+
+        // opened images are named like their path
+        TevCommand::ReloadImage { name, .. } => Some(ViewerMsg::LoadImage(name.into())),
+
+        TevCommand::CreateImage {
+            name,
+            size,
+            channel_names,
+            ..
+        } => Some(ViewerMsg::CreateImage {
+            name,
+            dims: (size.width(), size.height()),
+            channels: channel_names,
+        }),
+
+        TevCommand::UpdateImage {
+            name,
+            position,
+            size,
+            channel_names,
+            channel_samples,
+            ..
+        } => Some(ViewerMsg::UpdateRegion {
+            name,
+            position: (position.x(), position.y()),
+            dims: (size.width(), size.height()),
+            channels: channel_names,
+            samples: channel_samples,
+        }),
+
+        TevCommand::Other {
+            packet_type: DISPLAY_SETTINGS,
+            payload,
+        } => DisplaySettings::from_payload(&payload).map(ViewerMsg::ApplyDisplaySettings),
+
+        // the viewer shows a single image, which stays open
+        TevCommand::CloseImage { .. } | TevCommand::Other { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_settings_round_trip() {
+        let settings = DisplaySettings {
+            exposure: Some(-1.5),
+            srgb: None,
+            channel: Some("diffuse.R".into()),
+        };
+
+        let payload = settings.to_payload();
+        assert_eq!(DisplaySettings::from_payload(&payload), Some(settings));
+        assert_eq!(DisplaySettings::from_payload(&payload[..4]), None);
+        assert_eq!(
+            DisplaySettings::from_payload(&DisplaySettings::default().to_payload()),
+            Some(DisplaySettings::default())
+        );
+    }
+}
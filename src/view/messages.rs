@@ -3,7 +3,18 @@
 use std::path::PathBuf;
 use egui::Color32;
 
-use crate::view::state::{ChannelMode, DeepMode, DepthMode, View3DMode};
+use crate::block::cancel::CancellationToken;
+use crate::export::BitDepth;
+use crate::image::deep_stats::DeepStats;
+use crate::view::colormap::Colormap;
+use crate::view::display::{ColorSettings, OcioInfo, OcioSelection};
+use crate::view::ipc::DisplaySettings;
+use crate::view::motion::MotionField;
+use crate::view::scopes::{Histogram, ScopeMode, Waveform};
+use crate::view::state::{
+    Background, ChannelMode, CompareMode, CryptomatteObject, DeepMode, DeepPixelInfo, DepthMode, DisplayMode,
+    PartInfo, PartMetadata, StereoMode, View3DMode,
+};
 
 /// Generation counter for invalidating stale results.
 pub type Generation = u64;
@@ -14,6 +25,44 @@ pub enum ViewerMsg {
     /// Load an EXR file.
     LoadImage(PathBuf),
 
+    /// Stop the following loads when this token is cancelled.
+    /// The UI replaces the token after cancelling it, so that only the current load stops.
+    SetLoadCancellation(CancellationToken),
+
+    /// Load a second EXR file to compare the image with.
+    LoadImageB(PathBuf),
+
+    /// Set how the image is compared with the second image.
+    SetCompareMode(CompareMode),
+
+    /// Set which views of a multi-view image are displayed.
+    SetStereoMode(StereoMode),
+
+    /// Set the wipe position, from 0 (left) to 1 (right).
+    SetWipePosition(f32),
+
+    /// Highlight pixels whose difference exceeds this threshold.
+    SetDifferenceThreshold(f32),
+
+    /// Reload the displayed file when it is overwritten, and update the sequence
+    /// when frames are added to its directory.
+    SetWatch(bool),
+
+    /// Show the frame with this index in the detected sequence.
+    SeekFrame(usize),
+
+    /// Play the detected sequence in a loop, with this many frames per second.
+    Play { fps: f32 },
+
+    /// Stop playing the sequence.
+    Stop,
+
+    /// Decode and display the part of the file with this index.
+    SetPart(usize),
+
+    /// Decode and display the resolution level with this index, for tiled parts with mip or rip maps.
+    SetLevel(usize),
+
     /// Set current layer.
     SetLayer(String),
 
@@ -23,6 +72,12 @@ pub enum ViewerMsg {
     /// Set channel mode.
     SetChannelMode(ChannelMode),
 
+    /// Map the displayed values to colors, like false color or heatmaps.
+    SetDisplayMode(DisplayMode),
+
+    /// Load a 1D LUT for the LUT display mode, and switch to that mode.
+    LoadColormap(PathBuf),
+
     /// Set deep visualization mode.
     SetDeepMode(DeepMode),
 
@@ -38,8 +93,41 @@ pub enum ViewerMsg {
     /// Set exposure (EV stops).
     SetExposure(f32),
 
-    /// Toggle sRGB gamma.
-    SetSrgb(bool),
+    /// Select the input color space, view transform, and display.
+    SetColorSettings(ColorSettings),
+
+    /// Replace the display transform with a `.cube`, `.spi1d`, or `.spi3d` LUT file.
+    LoadLut(PathBuf),
+
+    /// Load an OpenColorIO config and use its default display and view.
+    LoadOcioConfig(PathBuf),
+
+    /// Use another display and view of the loaded OpenColorIO config.
+    SetOcioTransform(OcioSelection),
+
+    /// Return from a LUT or an OpenColorIO config to the built-in display transform.
+    ClearCustomTransform,
+
+    /// Show pixels above one in red and below zero in blue, after exposure.
+    SetClipping(bool),
+
+    /// Composite transparent pixels over a background, in the color channel mode.
+    SetBackground(Background),
+
+    /// Divide the colors by alpha before the display transform, in the color channel mode.
+    SetUnpremultiply(bool),
+
+    /// Tint pixels whose alpha is zero or less, in the color channel mode.
+    SetAlphaOverlay(bool),
+
+    /// Look around environment maps with a perspective view, instead of displaying their layout.
+    SetPanorama(bool),
+
+    /// Set the direction and field of view of the perspective view of environment maps.
+    SetPanoramaCamera(crate::view::panorama::PanoramaCamera),
+
+    /// Whether the UI can display float images with a shader, see `ViewerEvent::FloatImageReady`.
+    SetGpuDisplay(bool),
 
     /// Set invert depth.
     SetInvertDepth(bool),
@@ -65,6 +153,56 @@ pub enum ViewerMsg {
     /// Set viewport size.
     SetViewport([f32; 2]),
 
+    /// Read the original values of a pixel of the displayed image, for the pixel inspector.
+    QueryPixel { x: usize, y: usize },
+
+    /// List all samples of a pixel of the deep image, for the deep sample inspector.
+    InspectDeepPixel { x: usize, y: usize },
+
+    /// Find the objects of all cryptomattes in a pixel.
+    PickCryptomatte { x: usize, y: usize },
+
+    /// Display the matte of an object instead of the image, with the cryptomatte index and object id,
+    /// or display the image again.
+    IsolateMatte(Option<(usize, u32)>),
+
+    /// Compute this scope whenever the texture is regenerated, or stop computing scopes.
+    SetScopes(Option<ScopeMode>),
+
+    /// Create an empty image with f32 channels, from the IPC server.
+    CreateImage {
+        name: String,
+        dims: (usize, usize),
+        channels: Vec<String>,
+    },
+
+    /// Replace a region of some channels of a created image, from the IPC server.
+    UpdateRegion {
+        name: String,
+        position: (usize, usize),
+        dims: (usize, usize),
+        channels: Vec<String>,
+        samples: Vec<Vec<f32>>,
+    },
+
+    /// Change exposure, sRGB, or channel mode, from the IPC server.
+    ApplyDisplaySettings(DisplaySettings),
+
+    /// Send the loaded image to a tev viewer at this address.
+    SendToTev(String),
+
+    /// Save the displayed image with the current exposure and display transform as PNG or TIFF,
+    /// or with the current exposure only as HDR.
+    /// The format is chosen by the extension of the path.
+    ExportImage {
+        path: PathBuf,
+        bit_depth: BitDepth,
+    },
+
+    /// Save the samples of the deep image as a PLY or OBJ point cloud,
+    /// in world space if the file contains a camera.
+    ExportPointCloud(PathBuf),
+
     /// Close viewer.
     Close,
     
@@ -91,19 +229,83 @@ pub enum ViewerEvent {
     ImageLoaded {
         path: PathBuf,
         dims: (usize, usize),
+        /// All parts of the file. Only the displayed part is decoded.
+        parts: Vec<PartInfo>,
+        /// The index of the displayed part.
+        part: usize,
+        /// The index of the displayed resolution level.
+        level: usize,
         layers: Vec<String>,
         channels: Vec<String>,
-        is_deep: bool,
-        total_samples: usize,
+        /// The views of a multi-view layer, with the indices of their channels in `channels`.
+        /// Channels that belong to no view are not included. Empty for other layers.
+        views: Vec<(String, Vec<usize>)>,
+        /// The layout of the displayed layer, if it is an environment map.
+        environment_map: Option<crate::meta::attribute::EnvironmentMap>,
+        /// The names of the cryptomattes of the displayed layer.
+        cryptomattes: Vec<String>,
+        /// The sample statistics of deep images, `None` for flat images.
+        deep_stats: Option<DeepStats>,
         depth_range: Option<(f32, f32)>,
     },
 
+    /// All header attributes of the loaded file, for the metadata inspector.
+    Metadata(Vec<PartMetadata>),
+
+    /// Second image for comparison loaded successfully.
+    ImageBLoaded {
+        path: PathBuf,
+        dims: (usize, usize),
+    },
+
+    /// The loaded file is a frame of a sequence.
+    SequenceDetected {
+        /// The frame numbers of all files of the sequence.
+        frames: Vec<i64>,
+        /// The index of the loaded file.
+        index: usize,
+    },
+
+    /// The data window of the displayed layer and the display window of its image.
+    /// The displayed pixels cover the data window.
+    WindowsChanged {
+        data_window: crate::meta::attribute::IntegerBounds,
+        display_window: crate::meta::attribute::IntegerBounds,
+    },
+
+    /// The watched file changed and was read again, keeping the view and display settings.
+    ImageReloaded { path: PathBuf },
+
+    /// Another frame of the sequence is displayed.
+    FrameChanged { index: usize },
+
+    /// Playback stopped, because a frame could not be loaded.
+    PlaybackStopped,
+
     /// Texture ready for display.
+    /// While a large file is loading, a preview may be sent before the final texture.
     TextureReady {
         generation: Generation,
         width: usize,
         height: usize,
         pixels: Vec<Color32>,
+        /// The size of the image, if the texture is a preview with a lower resolution,
+        /// which is displayed stretched to this size.
+        preview_of: Option<(usize, usize)>,
+    },
+
+    /// The pixels of the first layer, for the shader of the UI,
+    /// instead of `TextureReady` if the shader supports the display settings.
+    /// Sent again only when the pixels change, not when the exposure changes.
+    #[cfg(feature = "view-gpu")]
+    FloatImageReady {
+        generation: Generation,
+        width: usize,
+        height: usize,
+        /// The red, green, blue, and alpha values of each pixel, in the primaries of the file.
+        pixels: Vec<[half::f16; 4]>,
+        /// Converts the primaries of the file to the display primaries.
+        input_matrix: crate::meta::attribute::Matrix3x3,
     },
 
     /// View state sync.
@@ -112,6 +314,60 @@ pub enum ViewerEvent {
         pan: [f32; 2],
     },
 
+    /// Display settings changed by the worker, from the IPC server.
+    DisplaySettingsChanged {
+        exposure: f32,
+        color: ColorSettings,
+        channel_mode: ChannelMode,
+    },
+
+    /// A colormap was loaded, and the LUT display mode is active.
+    ColormapLoaded(Colormap),
+
+    /// The custom display transform changed.
+    /// The name of the LUT file or config, or `None` for the built-in transform.
+    CustomTransformChanged {
+        name: Option<String>,
+        /// The choices of the OpenColorIO config, if a config is loaded.
+        ocio: Option<OcioInfo>,
+    },
+
+    /// The original values of a queried pixel, before exposure and sRGB.
+    PixelValue {
+        x: usize,
+        y: usize,
+        /// The name and value of each channel.
+        values: Vec<(String, f32)>,
+        /// The color of the pixel as displayed.
+        color: Color32,
+    },
+
+    /// The samples of an inspected deep pixel.
+    DeepPixelInfo(DeepPixelInfo),
+
+    /// The most covering object of each cryptomatte in a picked pixel.
+    CryptomattePicked {
+        x: usize,
+        y: usize,
+        objects: Vec<CryptomatteObject>,
+    },
+
+    /// Histogram of the displayed colors, after exposure.
+    Histogram(Histogram),
+
+    /// Waveform of the displayed colors, after exposure.
+    Waveform(Waveform),
+
+    /// The vectors of the motion vector display mode, for the arrow overlay.
+    /// `None` if the mode is off or the image has no vector layer.
+    MotionField(Option<MotionField>),
+
+    /// The fraction of the blocks of the loading file that have been read, from 0 to 1.
+    LoadProgress(f32),
+
+    /// Loading the file was cancelled, and the previous image is still displayed.
+    LoadCancelled,
+
     /// Error occurred.
     Error(String),
     
@@ -121,4 +377,14 @@ pub enum ViewerEvent {
         height: usize,
         depth: Vec<f32>,
     },
+
+    /// Every sample of the deep image as a point, for the 3D point cloud.
+    DeepPoints3DReady {
+        width: usize,
+        height: usize,
+        /// The pixel position and the depth of each sample, `[x, y, z]`.
+        points: Vec<[f32; 3]>,
+        /// The displayed color and the alpha of each sample.
+        colors: Vec<[u8; 4]>,
+    },
 }
@@ -5,7 +5,13 @@
 //! - Deep data visualization (sample count, flattened, depth slice)
 //! - Depth normalization (auto, manual range, log scale)
 //! - Exposure control, zoom/pan
+//! - A preview of large images while they are loading
+//! - Histogram, waveform, and parade scopes
+//! - Motion vector layers as colors by direction and magnitude, with an arrow overlay
+//! - A/B comparison: wipe, side by side, and absolute or relative difference
+//! - Playback of frame-numbered sequences, like `shot.1001.exr`
 //! - 3D mode: heightfield, point cloud (with view-3d feature)
+//! - IPC server for external tools, compatible with the tev protocol
 //!
 //! # Quick Start
 //!
@@ -20,15 +26,33 @@
 #![allow(missing_copy_implementations)]
 
 mod app;
+mod cache;
+mod colormap;
+mod display;
 mod handler;
+mod ipc;
 mod messages;
+mod motion;
+mod panorama;
+mod scopes;
+mod sequence;
+mod settings;
 mod state;
+mod watch;
+
+#[cfg(feature = "view-gpu")]
+mod gpu;
 
 #[cfg(feature = "view-3d")]
 mod view3d;
 
 pub use app::{ViewerApp, ViewerConfig};
-pub use state::{ChannelMode, DeepMode, DepthMode, ViewerState};
+pub use display::{
+    ColorSettings, DisplayDevice, InputSpace, OcioInfo, OcioSelection, ViewTransform,
+};
+pub use ipc::{DisplaySettings, DISPLAY_SETTINGS};
+pub use scopes::ScopeMode;
+pub use state::{ChannelMode, CompareMode, DeepMode, DepthMode, DisplayMode, ViewerState};
 
 use std::path::Path;
 
@@ -64,10 +88,17 @@ fn run_internal(
     title: String,
     config: ViewerConfig,
 ) -> i32 {
+    // the window is created before the app, which loads the other settings
+    let window_size = if config.ignore_settings {
+        None
+    } else {
+        settings::Settings::load().window_size
+    };
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_title(&title)
-            .with_inner_size([1400.0, 900.0])
+            .with_inner_size(window_size.unwrap_or([1400.0, 900.0]))
             .with_min_inner_size([800.0, 600.0]),
         ..Default::default()
     };
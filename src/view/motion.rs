@@ -0,0 +1,218 @@
+//! Motion vector display: the direction and the magnitude of a two-channel vector layer
+//! as colors, and the vectors of each pixel for the arrow overlay of the UI.
+//!
+//! Vectors are in pixels, with positive `y` pointing down the image like the rows.
+
+use std::f32::consts::PI;
+
+/// Words in the names of layers that contain motion vectors, like `velocity.x`.
+const VECTOR_LAYER_WORDS: &[&str] = &["motion", "velocity", "vector", "flow"];
+
+/// The vectors of each pixel of the displayed image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionField {
+    pub width: usize,
+    pub height: usize,
+    /// The horizontal and vertical component of each pixel, row by row.
+    pub vectors: Vec<[f32; 2]>,
+    /// The largest finite magnitude, which colors and arrows are relative to.
+    pub max_magnitude: f32,
+}
+
+impl MotionField {
+    /// Combine the horizontal and vertical components, which must have `width * height` values.
+    pub fn new(width: usize, height: usize, x: &[f32], y: &[f32]) -> Self {
+        let vectors: Vec<[f32; 2]> = x.iter().zip(y).map(|(&x, &y)| [x, y]).collect();
+        let max_magnitude = vectors
+            .iter()
+            .map(|&vector| magnitude(vector))
+            .filter(|magnitude| magnitude.is_finite())
+            .fold(0.0, f32::max);
+
+        Self {
+            width,
+            height,
+            vectors,
+            max_magnitude,
+        }
+    }
+
+    /// The vector of a pixel, or `None` outside of the image.
+    pub fn get(&self, x: usize, y: usize) -> Option<[f32; 2]> {
+        (x < self.width && y < self.height).then(|| self.vectors[y * self.width + x])
+    }
+
+    /// The display color of each pixel, see `motion_color`.
+    pub fn colors(&self, brightness: f32) -> impl Iterator<Item = [f32; 3]> + '_ {
+        let scale = brightness / self.max_magnitude.max(f32::MIN_POSITIVE);
+        self.vectors
+            .iter()
+            .map(move |&vector| motion_color(vector, scale))
+    }
+}
+
+/// The length of a vector.
+pub fn magnitude([x, y]: [f32; 2]) -> f32 {
+    x.hypot(y)
+}
+
+/// A color with the direction of the vector as hue and its scaled magnitude as value.
+/// Right is red, down is yellow green, left is cyan, and up is purple.
+/// Vectors that are not finite are black.
+pub fn motion_color(vector: [f32; 2], scale: f32) -> [f32; 3] {
+    let magnitude = magnitude(vector);
+    if !magnitude.is_finite() || magnitude == 0.0 {
+        return [0.0; 3];
+    }
+
+    let value = (magnitude * scale).clamp(0.0, 1.0);
+    let hue = vector[1].atan2(vector[0]).rem_euclid(2.0 * PI) / (2.0 * PI) * 6.0;
+    let fraction = hue - hue.floor();
+    let (rising, falling) = (value * fraction, value * (1.0 - fraction));
+
+    match hue as usize % 6 {
+        0 => [value, rising, 0.0],
+        1 => [falling, value, 0.0],
+        2 => [0.0, value, rising],
+        3 => [0.0, falling, value],
+        4 => [rising, 0.0, value],
+        _ => [value, 0.0, falling],
+    }
+}
+
+/// The indices of the horizontal and vertical channel of the motion vectors.
+///
+/// Channels are grouped into layers by the name before the last dot.
+/// Prefers the layer of the selected channel, then layers named like `motion` or `velocity`,
+/// then the first layer with exactly two channels.
+/// In each layer, `x`, `u`, or `r` is the horizontal and `y`, `v`, or `g` the vertical channel.
+pub fn vector_channels<S: AsRef<str>>(
+    names: &[S],
+    selected: Option<usize>,
+) -> Option<(usize, usize)> {
+    let mut layers: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        let layer = layer_name(name.as_ref());
+        match layers.iter_mut().find(|(name, _)| *name == layer) {
+            Some((_, channels)) => channels.push(index),
+            None => layers.push((layer, vec![index])),
+        }
+    }
+
+    let pair = |channels: &[usize]| {
+        let axis = |index: usize| axis(names[index].as_ref());
+        let horizontal = channels.iter().copied().find(|&c| axis(c) == Some(0));
+        let vertical = channels.iter().copied().find(|&c| axis(c) == Some(1));
+
+        match (horizontal, vertical, channels) {
+            (Some(x), Some(y), _) => Some((x, y)),
+            (_, _, &[x, y]) => Some((x, y)),
+            _ => None,
+        }
+    };
+
+    let selected_layer = selected
+        .filter(|&index| index < names.len())
+        .map(|index| layer_name(names[index].as_ref()));
+
+    let of_selected = layers
+        .iter()
+        .filter(|(name, _)| Some(*name) == selected_layer)
+        .find_map(|(_, channels)| pair(channels));
+
+    let named = || {
+        layers
+            .iter()
+            .filter(|(name, _)| is_vector_layer(name))
+            .find_map(|(_, channels)| pair(channels))
+    };
+
+    let any = || {
+        layers
+            .iter()
+            .filter(|(_, channels)| channels.len() == 2)
+            .find_map(|(_, channels)| pair(channels))
+    };
+
+    of_selected.or_else(named).or_else(any)
+}
+
+/// The name of the layer of a channel, which is empty for channels without a dot.
+fn layer_name(channel: &str) -> &str {
+    channel.rfind('.').map_or("", |dot| &channel[..dot])
+}
+
+/// The vector component of a channel: 0 for horizontal, 1 for vertical.
+fn axis(channel: &str) -> Option<usize> {
+    let suffix = channel.rsplit('.').next().unwrap_or(channel);
+    match suffix.to_ascii_lowercase().as_str() {
+        "x" | "u" | "r" | "red" => Some(0),
+        "y" | "v" | "g" | "green" => Some(1),
+        _ => None,
+    }
+}
+
+/// Whether the name of a layer suggests that it contains motion vectors.
+fn is_vector_layer(layer: &str) -> bool {
+    let name = layer
+        .rsplit('.')
+        .next()
+        .unwrap_or(layer)
+        .to_ascii_lowercase();
+    name == "mv" || VECTOR_LAYER_WORDS.iter().any(|word| name.contains(word))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_vector_channels() {
+        let names = ["A", "B", "G", "R", "velocity.x", "velocity.y", "Z"];
+        assert_eq!(vector_channels(&names, None), Some((4, 5)));
+
+        // the components are sorted by their axis
+        let names = ["forward.v", "forward.u", "N.x", "N.y", "N.z"];
+        assert_eq!(vector_channels(&names, None), Some((1, 0)));
+
+        // named layers are preferred over other layers with two channels
+        let names = ["uv.u", "uv.v", "motion.R", "motion.G", "motion.B"];
+        assert_eq!(vector_channels(&names, None), Some((2, 3)));
+
+        // unless another layer is selected
+        assert_eq!(vector_channels(&names, Some(1)), Some((0, 1)));
+        assert_eq!(vector_channels(&names, Some(9)), Some((2, 3)));
+
+        assert_eq!(vector_channels(&["B", "G", "R"], None), None);
+        assert_eq!(vector_channels(&["P.x", "P.y", "P.z"], None), None);
+        assert_eq!(vector_channels::<&str>(&[], None), None);
+    }
+
+    #[test]
+    fn colors_of_directions() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-4);
+
+        assert!(close(motion_color([2.0, 0.0], 0.5), [1.0, 0.0, 0.0]));
+        assert!(close(motion_color([-2.0, 0.0], 0.5), [0.0, 1.0, 1.0]));
+        assert!(close(motion_color([0.0, 1.0], 1.0), [0.5, 1.0, 0.0]));
+        assert!(close(motion_color([0.0, -1.0], 1.0), [0.5, 0.0, 1.0]));
+
+        // the magnitude is the value
+        assert!(close(motion_color([1.0, 0.0], 0.25), [0.25, 0.0, 0.0]));
+        assert!(close(motion_color([10.0, 0.0], 1.0), [1.0, 0.0, 0.0]));
+        assert_eq!(motion_color([0.0, 0.0], 1.0), [0.0; 3]);
+        assert_eq!(motion_color([f32::NAN, 0.0], 1.0), [0.0; 3]);
+    }
+
+    #[test]
+    fn field() {
+        let field = MotionField::new(2, 1, &[3.0, f32::INFINITY], &[4.0, 0.0]);
+        assert_eq!(field.max_magnitude, 5.0);
+        assert_eq!(field.get(1, 0), Some([f32::INFINITY, 0.0]));
+        assert_eq!(field.get(0, 1), None);
+
+        let colors: Vec<_> = field.colors(1.0).collect();
+        assert_eq!(colors[1], [0.0; 3]);
+        assert!(colors[0].iter().any(|&channel| channel == 1.0));
+    }
+}
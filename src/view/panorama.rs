@@ -0,0 +1,180 @@
+//! Perspective views into environment maps, to look around latitude-longitude and cube map panoramas
+//! instead of displaying their distorted layout.
+//!
+//! The camera follows the conventions of `crate::meta::environment_map`:
+//! `+y` points up, and a camera without rotation looks along `+z`,
+//! at the center of a latitude-longitude map.
+
+use std::f32::consts::FRAC_PI_2;
+
+use egui::Color32;
+
+use crate::math::Vec2;
+use crate::meta::attribute::EnvironmentMap;
+
+/// The largest width and height of the rendered view, in pixels.
+pub const MAX_VIEW_SIZE: usize = 2048;
+
+/// The direction and the field of view of the panorama camera, in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PanoramaCamera {
+    /// The rotation around the vertical axis. Positive values look to the left.
+    pub yaw: f32,
+    /// The rotation above the horizon. Negative values look down.
+    pub pitch: f32,
+    /// The vertical field of view.
+    pub fov: f32,
+}
+
+impl Default for PanoramaCamera {
+    fn default() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: 75_f32.to_radians(),
+        }
+    }
+}
+
+impl PanoramaCamera {
+    pub const MIN_FOV: f32 = 0.1;
+    pub const MAX_FOV: f32 = 2.8;
+
+    /// Rotate the camera, such that the dragged point of the view follows the mouse.
+    pub fn drag(&mut self, delta: [f32; 2], view_height: f32) {
+        let radians_per_pixel = self.fov / view_height.max(1.0);
+        self.yaw += delta[0] * radians_per_pixel;
+        self.pitch = (self.pitch + delta[1] * radians_per_pixel).clamp(-FRAC_PI_2, FRAC_PI_2);
+    }
+
+    /// Multiply the field of view by the factor, within the supported range.
+    pub fn zoom(&mut self, factor: f32) {
+        self.fov = (self.fov * factor).clamp(Self::MIN_FOV, Self::MAX_FOV);
+    }
+
+    /// The direction, not normalized, through a pixel position of a view with this size.
+    pub fn direction(&self, (width, height): (usize, usize), (x, y): (f32, f32)) -> [f32; 3] {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+
+        let forward = [sin_yaw * cos_pitch, sin_pitch, cos_yaw * cos_pitch];
+        let right = [-cos_yaw, 0.0, sin_yaw];
+        let up = [-sin_yaw * sin_pitch, cos_pitch, -cos_yaw * sin_pitch];
+
+        let scale = (self.fov * 0.5).tan();
+        let aspect = width as f32 / height.max(1) as f32;
+        let horizontal = (2.0 * x / width.max(1) as f32 - 1.0) * scale * aspect;
+        let vertical = (1.0 - 2.0 * y / height.max(1) as f32) * scale;
+
+        [0, 1, 2].map(|axis| forward[axis] + horizontal * right[axis] + vertical * up[axis])
+    }
+}
+
+/// Render the view of the camera from the displayed pixels of an environment map.
+/// Uses the nearest pixel of the map.
+pub fn project(
+    map: EnvironmentMap,
+    (map_width, map_height): (usize, usize),
+    pixels: &[Color32],
+    camera: PanoramaCamera,
+    (width, height): (usize, usize),
+) -> Vec<Color32> {
+    let map_size = Vec2(map_width, map_height);
+    if map.validate_size(map_size).is_err() || pixels.len() != map_size.area() {
+        return vec![Color32::BLACK; width * height];
+    }
+
+    (0..width * height)
+        .map(|index| {
+            let pixel = ((index % width) as f32 + 0.5, (index / width) as f32 + 0.5);
+            let direction = camera.direction((width, height), pixel);
+            let position = map.pixel_position(map_size, direction);
+
+            // wrap around the horizon of latitude-longitude maps
+            let x = position.x().round() as i64;
+            let x = match map {
+                EnvironmentMap::LatitudeLongitude => x.rem_euclid(map_width as i64) as usize,
+                EnvironmentMap::Cube => (x.max(0) as usize).min(map_width - 1),
+            };
+
+            let y = (position.y().round().max(0.0) as usize).min(map_height - 1);
+            pixels[y * map_width + x]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn camera_directions() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+        let mut camera = PanoramaCamera::default();
+
+        assert!(close(
+            camera.direction((10, 10), (5.0, 5.0)),
+            [0.0, 0.0, 1.0]
+        ));
+
+        // the right edge of the view looks to the right of the center of the map, along -x
+        assert!(camera.direction((10, 10), (10.0, 5.0))[0] < 0.0);
+
+        camera.yaw = FRAC_PI_2;
+        assert!(close(
+            camera.direction((10, 10), (5.0, 5.0)),
+            [1.0, 0.0, 0.0]
+        ));
+
+        camera.drag([0.0, 1.0e6], 100.0);
+        assert_eq!(camera.pitch, FRAC_PI_2);
+
+        camera.zoom(100.0);
+        assert_eq!(camera.fov, PanoramaCamera::MAX_FOV);
+    }
+
+    #[test]
+    fn project_lat_long_map() {
+        // the left half of the map is red, the right half is blue
+        let (width, height) = (8, 4);
+        let pixels: Vec<Color32> = (0..width * height)
+            .map(|index| {
+                if index % width < width / 2 {
+                    Color32::RED
+                } else {
+                    Color32::BLUE
+                }
+            })
+            .collect();
+
+        let mut camera = PanoramaCamera::default();
+        camera.yaw = 1.0;
+        let view = project(
+            EnvironmentMap::LatitudeLongitude,
+            (width, height),
+            &pixels,
+            camera,
+            (3, 3),
+        );
+        assert_eq!(view[4], Color32::RED);
+
+        camera.yaw = -1.0;
+        let view = project(
+            EnvironmentMap::LatitudeLongitude,
+            (width, height),
+            &pixels,
+            camera,
+            (3, 3),
+        );
+        assert_eq!(view[4], Color32::BLUE);
+
+        let cube = project(
+            EnvironmentMap::Cube,
+            (width, height),
+            &pixels,
+            camera,
+            (3, 3),
+        );
+        assert!(cube.iter().all(|&color| color == Color32::BLACK));
+    }
+}
@@ -0,0 +1,186 @@
+//! Histogram and waveform of the displayed channels, for lighting and exposure checks.
+
+/// Number of value bins of the histogram.
+pub const HISTOGRAM_BINS: usize = 256;
+
+/// Number of value bins per column of the waveform.
+pub const WAVEFORM_BINS: usize = 128;
+
+/// Maximum number of columns of the waveform. Wider images are downsampled.
+pub const WAVEFORM_COLUMNS: usize = 512;
+
+/// Which scope the analysis panel shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScopeMode {
+    /// Distribution of the values of each channel.
+    #[default]
+    Histogram,
+    /// Distribution of the values of each image column, with red, green and blue overlaid.
+    Waveform,
+    /// Waveform with red, green and blue side by side.
+    Parade,
+}
+
+impl ScopeMode {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Histogram => "Histogram",
+            Self::Waveform => "Waveform",
+            Self::Parade => "Parade",
+        }
+    }
+
+    pub const fn all() -> &'static [Self] {
+        &[Self::Histogram, Self::Waveform, Self::Parade]
+    }
+}
+
+/// Sample counts per value bin of the red, green, blue, and luminance channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// The values of the first and the last bin.
+    pub range: (f32, f32),
+    pub red: Vec<u32>,
+    pub green: Vec<u32>,
+    pub blue: Vec<u32>,
+    pub luma: Vec<u32>,
+}
+
+impl Histogram {
+    /// Count the finite values of each channel in bins covering the smallest and largest value.
+    /// All slices must have the same length.
+    pub fn compute(red: &[f32], green: &[f32], blue: &[f32]) -> Self {
+        let luma: Vec<f32> = red
+            .iter()
+            .zip(green)
+            .zip(blue)
+            .map(|((&r, &g), &b)| luminance(r, g, b))
+            .collect();
+
+        let channels = [red, green, blue, luma.as_slice()];
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for &value in channels.iter().flat_map(|channel| channel.iter()) {
+            if value.is_finite() {
+                min = min.min(value);
+                max = max.max(value);
+            }
+        }
+
+        if min > max {
+            (min, max) = (0.0, 1.0);
+        }
+
+        let count = |values: &[f32]| {
+            let mut bins = vec![0; HISTOGRAM_BINS];
+            for &value in values.iter().filter(|value| value.is_finite()) {
+                bins[bin_index(value, (min, max), HISTOGRAM_BINS)] += 1;
+            }
+            bins
+        };
+
+        Self {
+            range: (min, max),
+            red: count(red),
+            green: count(green),
+            blue: count(blue),
+            luma: count(&luma),
+        }
+    }
+}
+
+/// Sample counts per image column and value bin of the red, green, and blue channel.
+/// Values between zero and one are binned, larger and smaller values are clamped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+    pub columns: usize,
+    /// Each channel contains `columns * WAVEFORM_BINS` counts, one column after another.
+    pub red: Vec<u32>,
+    pub green: Vec<u32>,
+    pub blue: Vec<u32>,
+}
+
+impl Waveform {
+    /// Count the values of each column of an image with the specified width.
+    /// All slices must have the same length.
+    pub fn compute(width: usize, red: &[f32], green: &[f32], blue: &[f32]) -> Self {
+        let columns = width.min(WAVEFORM_COLUMNS).max(1);
+
+        let count = |values: &[f32]| {
+            let mut bins = vec![0; columns * WAVEFORM_BINS];
+            if width == 0 {
+                return bins;
+            }
+
+            for (index, &value) in values.iter().enumerate() {
+                if value.is_nan() {
+                    continue;
+                }
+
+                let column = (index % width) * columns / width;
+                bins[column * WAVEFORM_BINS + bin_index(value, (0.0, 1.0), WAVEFORM_BINS)] += 1;
+            }
+
+            bins
+        };
+
+        Self {
+            columns,
+            red: count(red),
+            green: count(green),
+            blue: count(blue),
+        }
+    }
+
+    /// The counts of one column of a channel, from the lowest to the highest value.
+    pub fn column(counts: &[u32], column: usize) -> &[u32] {
+        &counts[column * WAVEFORM_BINS..(column + 1) * WAVEFORM_BINS]
+    }
+}
+
+/// Rec. 709 luminance of a linear color.
+pub fn luminance(r: f32, g: f32, b: f32) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// The bin of a value, clamping values outside of the range to the first or last bin.
+fn bin_index(value: f32, (min, max): (f32, f32), bins: usize) -> usize {
+    if max <= min {
+        return 0;
+    }
+
+    let relative = ((value - min) / (max - min)).clamp(0.0, 1.0);
+    ((relative * bins as f32) as usize).min(bins - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn histogram_counts_finite_values() {
+        let red = [0.0, 1.0, 0.5, f32::NAN];
+        let green = [0.0, 1.0, 0.5, 0.0];
+        let blue = [0.0, 1.0, 0.5, f32::INFINITY];
+
+        let histogram = Histogram::compute(&red, &green, &blue);
+        assert_eq!(histogram.range, (0.0, 1.0));
+        assert_eq!(histogram.red.iter().sum::<u32>(), 3);
+        assert_eq!(histogram.green.iter().sum::<u32>(), 4);
+        assert_eq!(histogram.red[0], 1);
+        assert_eq!(histogram.red[HISTOGRAM_BINS / 2], 1);
+        assert_eq!(histogram.red[HISTOGRAM_BINS - 1], 1);
+    }
+
+    #[test]
+    fn waveform_bins_columns() {
+        // two columns, the right one is brighter
+        let values = [0.0, 1.0, 0.0, 2.0];
+        let waveform = Waveform::compute(2, &values, &values, &values);
+        assert_eq!(waveform.columns, 2);
+
+        let left = Waveform::column(&waveform.red, 0);
+        let right = Waveform::column(&waveform.red, 1);
+        assert_eq!(left[0], 2);
+        assert_eq!(right[WAVEFORM_BINS - 1], 2);
+    }
+}
@@ -0,0 +1,139 @@
+//! Frame-numbered image sequences, like `shot.1001.exr`, `shot.1002.exr`, ...
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// Number of frames decoded ahead during playback.
+pub const PREFETCH_FRAMES: usize = 4;
+
+/// The files of a sequence, sorted by frame number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    pub frames: Vec<(i64, PathBuf)>,
+}
+
+impl Sequence {
+    /// Find all files in the directory of the path that only differ in the frame number.
+    /// The frame number is the last group of digits in the file name,
+    /// and must have the same number of digits in all files.
+    /// Returns `None` if the file is not part of a sequence with at least two frames.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let (prefix, digits, suffix) = split_frame_number(file_name)?;
+        let directory = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+
+        let mut frames: Vec<(i64, PathBuf)> = std::fs::read_dir(directory)
+            .ok()?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name();
+                let (other_prefix, other_digits, other_suffix) =
+                    split_frame_number(name.to_str()?)?;
+
+                if other_prefix != prefix
+                    || other_suffix != suffix
+                    || other_digits.len() != digits.len()
+                {
+                    return None;
+                }
+
+                Some((other_digits.parse().ok()?, path.with_file_name(&name)))
+            })
+            .collect();
+
+        if frames.len() < 2 {
+            return None;
+        }
+
+        frames.sort_by_key(|(frame, _)| *frame);
+        Some(Self { frames })
+    }
+
+    /// The index of the frame with this path.
+    pub fn index_of(&self, path: &Path) -> Option<usize> {
+        self.frames.iter().position(|(_, frame_path)| frame_path == path)
+    }
+}
+
+/// Split a file name like `shot.1001.exr` into `shot.`, `1001`, and `.exr`.
+/// The frame number is the last group of digits.
+pub fn split_frame_number(file_name: &str) -> Option<(&str, &str, &str)> {
+    let end = file_name.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = file_name[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |index| index + 1);
+
+    Some((&file_name[..start], &file_name[start..end], &file_name[end..]))
+}
+
+/// A small ring buffer of decoded frames, keyed by their index in the sequence.
+/// Inserting into a full cache drops the oldest frame.
+#[derive(Debug)]
+pub struct FrameCache<T> {
+    frames: VecDeque<(usize, T)>,
+    capacity: usize,
+}
+
+impl<T> FrameCache<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.frames.iter().any(|(cached, _)| *cached == index)
+    }
+
+    pub fn insert(&mut self, index: usize, frame: T) {
+        if self.contains(index) || self.capacity == 0 {
+            return;
+        }
+
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back((index, frame));
+    }
+
+    /// Remove the frame from the cache, to display it.
+    pub fn take(&mut self, index: usize) -> Option<T> {
+        let position = self.frames.iter().position(|(cached, _)| *cached == index)?;
+        self.frames.remove(position).map(|(_, frame)| frame)
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_file_names() {
+        assert_eq!(split_frame_number("shot.1001.exr"), Some(("shot.", "1001", ".exr")));
+        assert_eq!(split_frame_number("shot_v2_0042.exr"), Some(("shot_v2_", "0042", ".exr")));
+        assert_eq!(split_frame_number("12.exr"), Some(("", "12", ".exr")));
+        assert_eq!(split_frame_number("shot.exr"), None);
+    }
+
+    #[test]
+    fn frame_cache_drops_oldest() {
+        let mut cache = FrameCache::new(2);
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+        cache.insert(3, "three");
+
+        assert!(!cache.contains(1));
+        assert_eq!(cache.take(2), Some("two"));
+        assert_eq!(cache.take(2), None);
+        assert!(cache.contains(3));
+    }
+}
@@ -0,0 +1,645 @@
+//! Settings of the viewer that persist between sessions.
+//!
+//! Stored in `viewer.toml` in the `exrs` directory of the user configuration directory,
+//! or in the file named by the `EXRS_VIEWER_CONFIG` environment variable.
+//! The file contains flat `name = value` pairs and a `[shortcuts]` table:
+//!
+//! ```toml
+//! window_size = [1400, 900]
+//! last_directory = "/projects/shot/renders"
+//! recent_files = ["/projects/shot/renders/beauty.1001.exr"]
+//! exposure = 0.5
+//! input = "From File"
+//! view = "Raw"
+//! display = "sRGB"
+//! background = "checkerboard"
+//!
+//! [shortcuts]
+//! fit = "F"
+//! home = "H, Num0"
+//! ```
+//!
+//! Unknown names and invalid values are ignored,
+//! so that a broken file never prevents the viewer from starting.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::view::display::{ColorSettings, DisplayDevice, InputSpace, ViewTransform};
+use crate::view::state::Background;
+
+/// The environment variable that overrides the path of the settings file.
+pub const CONFIG_VARIABLE: &str = "EXRS_VIEWER_CONFIG";
+
+/// The maximum number of files in the recent files list.
+pub const MAX_RECENT_FILES: usize = 10;
+
+/// The settings of the viewer that persist between sessions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// The inner size of the window, in points.
+    pub window_size: Option<[f32; 2]>,
+
+    /// Where the file dialogs start.
+    pub last_directory: Option<PathBuf>,
+
+    /// The most recently opened files, the latest first.
+    pub recent_files: Vec<PathBuf>,
+
+    pub exposure: f32,
+    pub color: ColorSettings,
+    pub background: Background,
+    pub shortcuts: Shortcuts,
+}
+
+/// An action of the viewer that can be triggered by a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Fit,
+    Home,
+    ZoomIn,
+    ZoomOut,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    Color,
+    Depth,
+    Luminance,
+    PlayPause,
+    NextFrame,
+    PreviousFrame,
+    /// Triggered by the key together with Ctrl.
+    Open,
+}
+
+/// The keys that trigger each action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shortcuts {
+    keys: HashMap<Action, Vec<egui::Key>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window_size: None,
+            last_directory: None,
+            recent_files: Vec::new(),
+            exposure: 0.0,
+            color: ColorSettings::default(),
+            background: Background::default(),
+            shortcuts: Shortcuts::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// The path of the settings file, or `None` if the configuration directory is unknown.
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_VARIABLE) {
+            return Some(PathBuf::from(path));
+        }
+
+        let var = |name: &str| std::env::var_os(name).map(PathBuf::from);
+
+        let directory = if cfg!(windows) {
+            var("APPDATA")?
+        } else if cfg!(target_os = "macos") {
+            var("HOME")?.join("Library").join("Application Support")
+        } else {
+            var("XDG_CONFIG_HOME").or_else(|| Some(var("HOME")?.join(".config")))?
+        };
+
+        Some(directory.join("exrs").join("viewer.toml"))
+    }
+
+    /// Load the settings file, or return the default settings if it does not exist.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map_or_else(Self::default, |text| Self::parse(&text))
+    }
+
+    /// Write the settings file, creating its directory if necessary.
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = Self::path().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "unknown config directory")
+        })?;
+
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+
+        std::fs::write(path, self.to_text())
+    }
+
+    /// Move the file to the front of the recent files, and remember its directory.
+    pub fn add_recent_file(&mut self, path: &Path) {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        self.recent_files.retain(|recent| *recent != path);
+        self.recent_files.insert(0, path.clone());
+        self.recent_files.truncate(MAX_RECENT_FILES);
+        self.last_directory = path.parent().map(Path::to_path_buf);
+    }
+
+    /// Read the settings from the contents of a settings file, ignoring invalid lines.
+    pub fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+        let mut section = String::new();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                section = name.trim().to_string();
+                continue;
+            }
+
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            let Some(value) = Value::parse(value.trim()) else {
+                continue;
+            };
+            let name = name.trim();
+
+            if section == "shortcuts" {
+                if let (Some(action), Some(text)) = (Action::from_name(name), value.text()) {
+                    settings.shortcuts.set_from_text(action, text);
+                }
+            } else if section.is_empty() {
+                settings.set(name, value);
+            }
+        }
+
+        settings
+    }
+
+    fn set(&mut self, name: &str, value: Value) {
+        match name {
+            "window_size" => {
+                if let Some(&[width, height]) = value.numbers().as_deref() {
+                    self.window_size = Some([width as f32, height as f32]);
+                }
+            }
+            "last_directory" => self.last_directory = value.text().map(PathBuf::from),
+            "recent_files" => {
+                if let Value::List(items) = value {
+                    self.recent_files = items
+                        .iter()
+                        .filter_map(Value::text)
+                        .map(PathBuf::from)
+                        .collect();
+                    self.recent_files.truncate(MAX_RECENT_FILES);
+                }
+            }
+            "exposure" => {
+                if let Value::Number(exposure) = value {
+                    self.exposure = (exposure as f32).clamp(-10.0, 10.0);
+                }
+            }
+            "input" => {
+                if let Some(input) = find_label(InputSpace::all(), InputSpace::label, &value) {
+                    self.color.input = input;
+                }
+            }
+            "view" => {
+                if let Some(view) = find_label(ViewTransform::all(), ViewTransform::label, &value) {
+                    self.color.view = view;
+                }
+            }
+            "display" => {
+                if let Some(display) =
+                    find_label(DisplayDevice::all(), DisplayDevice::label, &value)
+                {
+                    self.color.display = display;
+                }
+            }
+            "background" => {
+                if let Some(background) = value.text().and_then(parse_background) {
+                    self.background = background;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The contents of the settings file.
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# exrs viewer settings\n");
+        let mut line = |name: &str, value: Value| {
+            let _ = writeln!(text, "{name} = {value}");
+        };
+
+        if let Some([width, height]) = self.window_size {
+            let size = vec![
+                Value::Number(width.round() as f64),
+                Value::Number(height.round() as f64),
+            ];
+            line("window_size", Value::List(size));
+        }
+
+        if let Some(directory) = &self.last_directory {
+            line("last_directory", Value::path(directory));
+        }
+
+        line(
+            "recent_files",
+            Value::List(
+                self.recent_files
+                    .iter()
+                    .map(|path| Value::path(path))
+                    .collect(),
+            ),
+        );
+        line("exposure", Value::Number(self.exposure as f64));
+        line("input", Value::Text(self.color.input.label().to_string()));
+        line("view", Value::Text(self.color.view.label().to_string()));
+        line(
+            "display",
+            Value::Text(self.color.display.label().to_string()),
+        );
+        line("background", Value::Text(background_name(self.background)));
+
+        text.push_str("\n[shortcuts]\n");
+        for &action in Action::all() {
+            let keys = self.shortcuts.keys(action);
+            let names: Vec<&str> = keys.iter().map(|key| key.name()).collect();
+            let _ = writeln!(
+                text,
+                "{} = {}",
+                action.name(),
+                Value::Text(names.join(", "))
+            );
+        }
+
+        text
+    }
+}
+
+impl Action {
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::Quit,
+            Self::Fit,
+            Self::Home,
+            Self::ZoomIn,
+            Self::ZoomOut,
+            Self::Red,
+            Self::Green,
+            Self::Blue,
+            Self::Alpha,
+            Self::Color,
+            Self::Depth,
+            Self::Luminance,
+            Self::PlayPause,
+            Self::NextFrame,
+            Self::PreviousFrame,
+            Self::Open,
+        ]
+    }
+
+    /// The name of the action in the settings file.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::Fit => "fit",
+            Self::Home => "home",
+            Self::ZoomIn => "zoom_in",
+            Self::ZoomOut => "zoom_out",
+            Self::Red => "red",
+            Self::Green => "green",
+            Self::Blue => "blue",
+            Self::Alpha => "alpha",
+            Self::Color => "color",
+            Self::Depth => "depth",
+            Self::Luminance => "luminance",
+            Self::PlayPause => "play_pause",
+            Self::NextFrame => "next_frame",
+            Self::PreviousFrame => "previous_frame",
+            Self::Open => "open",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [egui::Key] {
+        use egui::Key;
+
+        match self {
+            Self::Quit => &[Key::Escape],
+            Self::Fit => &[Key::F],
+            Self::Home => &[Key::H, Key::Num0],
+            Self::ZoomIn => &[Key::Plus, Key::Equals],
+            Self::ZoomOut => &[Key::Minus],
+            Self::Red => &[Key::R],
+            Self::Green => &[Key::G],
+            Self::Blue => &[Key::B],
+            Self::Alpha => &[Key::A],
+            Self::Color => &[Key::C],
+            Self::Depth => &[Key::Z],
+            Self::Luminance => &[Key::L],
+            Self::PlayPause => &[Key::Space],
+            Self::NextFrame => &[Key::ArrowRight],
+            Self::PreviousFrame => &[Key::ArrowLeft],
+            Self::Open => &[Key::O],
+        }
+    }
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        let keys = Action::all()
+            .iter()
+            .map(|&action| (action, action.default_keys().to_vec()))
+            .collect();
+
+        Self { keys }
+    }
+}
+
+impl Shortcuts {
+    /// The keys that trigger the action.
+    pub fn keys(&self, action: Action) -> &[egui::Key] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether any key of the action was pressed this frame.
+    /// Opening needs Ctrl, and the channel shortcuts are ignored while Ctrl is held.
+    pub fn pressed(&self, input: &egui::InputState, action: Action) -> bool {
+        let ctrl = input.modifiers.ctrl;
+        let modifiers_match = match action {
+            Action::Open => ctrl,
+            Action::Red
+            | Action::Green
+            | Action::Blue
+            | Action::Alpha
+            | Action::Color
+            | Action::Depth => !ctrl,
+            _ => true,
+        };
+
+        modifiers_match && self.keys(action).iter().any(|&key| input.key_pressed(key))
+    }
+
+    /// Replace the keys of the action with comma-separated key names, like `H, Num0`.
+    /// An empty text disables the action. Keeps the keys if any name is unknown.
+    fn set_from_text(&mut self, action: Action, text: &str) {
+        let keys: Option<Vec<egui::Key>> = text
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(egui::Key::from_name)
+            .collect();
+
+        if let Some(keys) = keys {
+            self.keys.insert(action, keys);
+        }
+    }
+}
+
+/// A value in the settings file: a quoted text, a number, or a list in brackets.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Number(f64),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn path(path: &Path) -> Self {
+        Value::Text(path.to_string_lossy().into_owned())
+    }
+
+    fn text(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn numbers(&self) -> Option<Vec<f64>> {
+        match self {
+            Value::List(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::Number(number) => Some(*number),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// Parse a complete value, or return `None` if there are unexpected characters.
+    fn parse(text: &str) -> Option<Self> {
+        let mut chars = text.chars().peekable();
+        let value = Self::parse_next(&mut chars)?;
+        chars.all(char::is_whitespace).then_some(value)
+    }
+
+    fn parse_next(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<Self> {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        match chars.peek()? {
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+
+                loop {
+                    match chars.next()? {
+                        '"' => return Some(Value::Text(text)),
+                        '\\' => text.push(match chars.next()? {
+                            'n' => '\n',
+                            't' => '\t',
+                            other => other,
+                        }),
+                        other => text.push(other),
+                    }
+                }
+            }
+
+            '[' => {
+                chars.next();
+                let mut items = Vec::new();
+
+                loop {
+                    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+                    if chars.next_if_eq(&']').is_some() {
+                        return Some(Value::List(items));
+                    }
+
+                    items.push(Self::parse_next(chars)?);
+                    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+                    if chars.next_if_eq(&',').is_none() && chars.peek() != Some(&']') {
+                        return None;
+                    }
+                }
+            }
+
+            _ => {
+                let mut number = String::new();
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || "+-._".contains(*c))
+                {
+                    number.push(c);
+                }
+
+                number.replace('_', "").parse().ok().map(Value::Number)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Text(text) => {
+                f.write_char('"')?;
+                for c in text.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        other => f.write_char(other)?,
+                    }
+                }
+                f.write_char('"')
+            }
+
+            Value::Number(number) => write!(f, "{number}"),
+
+            Value::List(items) => {
+                f.write_char('[')?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+        }
+    }
+}
+
+/// The option whose label equals the text value, ignoring case.
+fn find_label<T: Copy>(all: &[T], label: fn(T) -> &'static str, value: &Value) -> Option<T> {
+    let text = value.text()?;
+    all.iter()
+        .copied()
+        .find(|&option| label(option).eq_ignore_ascii_case(text))
+}
+
+/// `none`, `checkerboard`, or a solid color like `#808080`.
+fn parse_background(text: &str) -> Option<Background> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "none" => Some(Background::None),
+        "checkerboard" => Some(Background::Checkerboard),
+        color => {
+            let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+            let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+            Some(Background::Solid([channel(0)?, channel(2)?, channel(4)?]))
+        }
+    }
+}
+
+fn background_name(background: Background) -> String {
+    match background {
+        Background::None => "none".to_string(),
+        Background::Checkerboard => "checkerboard".to_string(),
+        Background::Solid([r, g, b]) => format!("#{r:02x}{g:02x}{b:02x}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings_roundtrip() {
+        let mut settings = Settings {
+            window_size: Some([1200.0, 800.0]),
+            exposure: -1.5,
+            background: Background::Solid([16, 128, 255]),
+            ..Settings::default()
+        };
+
+        settings.color.display = DisplayDevice::Rec1886;
+        settings.color.view = ViewTransform::Filmic;
+        settings.recent_files = vec![PathBuf::from("/renders/a \"quoted\" name.exr")];
+        settings.last_directory = Some(PathBuf::from(r"C:\renders"));
+        settings.shortcuts.set_from_text(Action::Fit, "Space, F");
+        settings.shortcuts.set_from_text(Action::Quit, "");
+
+        let parsed = Settings::parse(&settings.to_text());
+        assert_eq!(parsed, settings);
+        assert_eq!(
+            parsed.shortcuts.keys(Action::Fit),
+            &[egui::Key::Space, egui::Key::F]
+        );
+        assert!(parsed.shortcuts.keys(Action::Quit).is_empty());
+    }
+
+    #[test]
+    fn ignore_invalid_lines() {
+        let settings = Settings::parse(
+            "exposure = 2\n\
+             window_size = [100, \"wide\"]\n\
+             display = \"Unknown Monitor\"\n\
+             background = \"checkerboard\"\n\
+             recent_files = [\"a.exr\", \n\
+             garbage\n\
+             [shortcuts]\n\
+             home = \"H, NoSuchKey\"\n\
+             red = \"X\"\n",
+        );
+
+        assert_eq!(settings.exposure, 2.0);
+        assert_eq!(settings.window_size, None);
+        assert_eq!(settings.color.display, DisplayDevice::Srgb);
+        assert_eq!(settings.background, Background::Checkerboard);
+        assert!(settings.recent_files.is_empty());
+        assert_eq!(
+            settings.shortcuts.keys(Action::Home),
+            &[egui::Key::H, egui::Key::Num0]
+        );
+        assert_eq!(settings.shortcuts.keys(Action::Red), &[egui::Key::X]);
+    }
+
+    #[test]
+    fn recent_files_are_unique() {
+        let mut settings = Settings::default();
+        for index in 0..12 {
+            settings.add_recent_file(Path::new(&format!("/no/such/dir/{index}.exr")));
+        }
+
+        settings.add_recent_file(Path::new("/no/such/dir/5.exr"));
+        assert_eq!(settings.recent_files.len(), MAX_RECENT_FILES);
+        assert_eq!(
+            settings.recent_files[0],
+            PathBuf::from("/no/such/dir/5.exr")
+        );
+        assert_eq!(
+            settings.recent_files[1],
+            PathBuf::from("/no/such/dir/11.exr")
+        );
+        assert_eq!(settings.last_directory, Some(PathBuf::from("/no/such/dir")));
+    }
+}
@@ -2,6 +2,13 @@
 
 use std::path::PathBuf;
 
+use crate::image::deep_stats::DeepStats;
+use crate::meta::attribute::{EnvironmentMap, IntegerBounds};
+use crate::view::colormap::Colormap;
+use crate::view::display::{ColorSettings, OcioInfo};
+use crate::view::panorama::PanoramaCamera;
+use crate::view::scopes::{Histogram, ScopeMode};
+
 /// Channel display mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum ChannelMode {
@@ -66,6 +73,48 @@ impl ChannelMode {
 
 
 
+/// How the displayed values are mapped to colors, after the channel mode and exposure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayMode {
+    /// The display transform.
+    #[default]
+    Normal,
+    /// Bands of exposure relative to middle gray, for checking the exposure.
+    FalseColor,
+    /// Turbo heatmap of the luminance from 0 to 1.
+    Turbo,
+    /// Viridis heatmap of the luminance from 0 to 1.
+    Viridis,
+    /// A loaded 1D LUT of the luminance from 0 to 1.
+    Lut,
+    /// Direction and magnitude of a two-channel vector layer, like `velocity.x` and `velocity.y`.
+    MotionVectors,
+}
+
+impl DisplayMode {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Normal => "Normal",
+            Self::FalseColor => "False Color",
+            Self::Turbo => "Turbo",
+            Self::Viridis => "Viridis",
+            Self::Lut => "LUT",
+            Self::MotionVectors => "Motion Vectors",
+        }
+    }
+
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::Normal,
+            Self::FalseColor,
+            Self::Turbo,
+            Self::Viridis,
+            Self::Lut,
+            Self::MotionVectors,
+        ]
+    }
+}
+
 /// Deep data visualization mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum DeepMode {
@@ -141,6 +190,133 @@ impl DepthMode {
     }
 }
 
+/// How the image is compared with the second image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompareMode {
+    /// Show only the first image.
+    #[default]
+    Off,
+    /// The first image left of the wipe position, the second image right of it.
+    Wipe,
+    /// Both images next to each other.
+    SideBySide,
+    /// Absolute difference per channel.
+    AbsoluteDifference,
+    /// Difference per channel relative to the larger value.
+    RelativeDifference,
+}
+
+impl CompareMode {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Wipe => "Wipe",
+            Self::SideBySide => "Side by Side",
+            Self::AbsoluteDifference => "Abs Difference",
+            Self::RelativeDifference => "Rel Difference",
+        }
+    }
+
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::Off,
+            Self::Wipe,
+            Self::SideBySide,
+            Self::AbsoluteDifference,
+            Self::RelativeDifference,
+        ]
+    }
+}
+
+/// Which views of a multi-view image are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StereoMode {
+    /// The left view, or the default view of images without a stereo pair.
+    #[default]
+    Left,
+    /// The right view.
+    Right,
+    /// Red from the left view, green and blue from the right view, for red-cyan glasses.
+    Anaglyph,
+    /// The left view next to the right view.
+    SideBySide,
+}
+
+impl StereoMode {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Anaglyph => "Anaglyph",
+            Self::SideBySide => "Side by Side",
+        }
+    }
+
+    pub const fn all() -> &'static [Self] {
+        &[Self::Left, Self::Right, Self::Anaglyph, Self::SideBySide]
+    }
+}
+
+/// What is displayed behind transparent pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Background {
+    /// Ignore the alpha channel, and display the colors as they are.
+    #[default]
+    None,
+    /// A gray checkerboard pattern, in squares of eight image pixels.
+    Checkerboard,
+    /// A single display color.
+    Solid([u8; 3]),
+}
+
+impl Background {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Checkerboard => "Checkerboard",
+            Self::Solid(_) => "Solid",
+        }
+    }
+
+    /// The display color of the background at the pixel, or `None` if alpha is ignored.
+    pub fn color(self, x: usize, y: usize) -> Option<[u8; 3]> {
+        match self {
+            Self::None => None,
+            Self::Checkerboard if (x / 8 + y / 8) % 2 == 0 => Some([102, 102, 102]),
+            Self::Checkerboard => Some([153, 153, 153]),
+            Self::Solid(color) => Some(color),
+        }
+    }
+}
+
+/// How the alpha channel affects the displayed colors of the color channel mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AlphaDisplay {
+    /// Composite the pixels over this background.
+    pub background: Background,
+    /// Divide the colors by alpha before the display transform,
+    /// to reveal the colors of semi-transparent pixels.
+    pub unpremultiply: bool,
+    /// Tint pixels without coverage, where alpha is zero or less.
+    pub overlay: bool,
+}
+
+impl AlphaDisplay {
+    /// Whether the displayed colors depend on the alpha channel.
+    pub fn is_active(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+/// One of the two images that can be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageSlot {
+    /// The main image.
+    A,
+    /// The image it is compared with.
+    B,
+}
+
 /// 3D visualization mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum View3DMode {
@@ -167,25 +343,135 @@ impl View3DMode {
     }
 }
 
+/// One part of a multi-part file, listed in the part selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartInfo {
+    pub name: String,
+    pub deep: bool,
+    pub tiled: bool,
+    pub compression: String,
+    /// The resolution of each mip map level, or of each rip map level with the same index in x and y.
+    /// Contains only the full resolution if the part has no smaller levels.
+    pub levels: Vec<(usize, usize)>,
+}
+
+impl PartInfo {
+    /// The type of the part, like `deep scan lines`.
+    pub const fn kind(&self) -> &'static str {
+        match (self.deep, self.tiled) {
+            (false, false) => "scan lines",
+            (false, true) => "tiles",
+            (true, false) => "deep scan lines",
+            (true, true) => "deep tiles",
+        }
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} ({}, {})", self.name, self.kind(), self.compression)
+    }
+}
+
+/// The most covering object of a cryptomatte in the clicked pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CryptomatteObject {
+    /// The index of the cryptomatte in the layer.
+    pub cryptomatte: usize,
+    /// The name of the cryptomatte, like `CryptoObject`.
+    pub cryptomatte_name: String,
+    pub id: u32,
+    /// The name from the manifest, or the hexadecimal id if the manifest does not contain it.
+    pub name: String,
+    pub coverage: f32,
+}
+
+/// All header attributes of one part of the loaded file, for the metadata inspector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartMetadata {
+    pub name: String,
+    /// The attributes, sorted by name.
+    pub attributes: Vec<AttributeInfo>,
+}
+
+/// One header attribute, formatted for display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeInfo {
+    pub name: String,
+    /// The exr type name, like `box2i` or `chromaticities`.
+    pub kind: String,
+    pub value: String,
+}
+
+/// Original values of the pixel under the cursor, from the worker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelInfo {
+    pub x: usize,
+    pub y: usize,
+    /// The name and value of each channel, before exposure and sRGB.
+    pub values: Vec<(String, f32)>,
+    /// The color of the pixel as displayed.
+    pub color: egui::Color32,
+}
+
+/// All samples of one pixel of a deep image, for the deep sample inspector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeepPixelInfo {
+    pub x: usize,
+    pub y: usize,
+    /// The samples in the order they are stored in the file.
+    pub samples: Vec<DeepSampleInfo>,
+}
+
+/// One sample of a deep pixel.
+/// Missing color channels are zero, a missing alpha channel is opaque,
+/// and a missing `ZBack` channel equals `Z`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeepSampleInfo {
+    pub z: f32,
+    pub z_back: f32,
+    pub rgba: [f32; 4],
+}
+
 /// Runtime viewer state.
 #[derive(Debug, Clone)]
 pub struct ViewerState {
     // Image info
     pub image_path: Option<PathBuf>,
     pub image_dims: Option<(usize, usize)>,
+    /// The data window of the displayed pixels.
+    pub data_window: Option<IntegerBounds>,
+    /// The display window of the image, which pixel coordinates are relative to.
+    pub display_window: Option<IntegerBounds>,
+    /// Draw the display window border and shade the overscan, if the windows differ.
+    pub show_windows: bool,
+    /// The layout of the displayed layer, if it is an environment map.
+    pub environment_map: Option<EnvironmentMap>,
+    /// Look around environment maps instead of displaying their layout.
+    pub panorama: bool,
+    pub panorama_camera: PanoramaCamera,
     pub is_deep: bool,
-    pub total_samples: usize,
-    pub avg_samples: f32,
+    /// The sample statistics of the displayed deep image.
+    pub deep_stats: Option<DeepStats>,
 
-    // Layer/channel selection
+    // Part/layer/channel selection
+    pub parts: Vec<PartInfo>,
+    pub current_part: usize,
+    pub current_level: usize,
     pub layers: Vec<String>,
     pub current_layer: String,
     pub channels: Vec<String>,
     pub current_channel: String,
+    /// The views of a multi-view layer, and the indices of their channels.
+    pub views: Vec<(String, Vec<usize>)>,
+    pub stereo_mode: StereoMode,
 
     // Display settings
     pub show_3d: bool,
     pub channel_mode: ChannelMode,
+    pub display_mode: DisplayMode,
+    /// The loaded colormap of the LUT display mode, for the legend.
+    pub colormap: Option<Colormap>,
+    /// Draw arrows of the motion vectors over the image, in the motion vector display mode.
+    pub show_motion_arrows: bool,
     pub deep_mode: DeepMode,
     pub depth_mode: DepthMode,
     pub view_3d_mode: View3DMode,
@@ -193,7 +479,16 @@ pub struct ViewerState {
     // Exposure and color
     pub exposure: f32,
     pub gamma: f32,
-    pub apply_srgb: bool,
+    pub color: ColorSettings,
+    /// Show pixels above one in red and below zero in blue.
+    pub show_clipping: bool,
+    pub alpha_display: AlphaDisplay,
+
+    /// The name of the LUT file or OpenColorIO config replacing the built-in display transform.
+    pub custom_transform: Option<String>,
+
+    /// The choices of the loaded OpenColorIO config.
+    pub ocio: Option<OcioInfo>,
 
     // Depth settings
     pub depth_near: f32,
@@ -210,12 +505,55 @@ pub struct ViewerState {
     pub pan: [f32; 2],
     pub viewport_size: [f32; 2],
 
+    // A/B comparison
+    pub compare_path: Option<PathBuf>,
+    pub compare_dims: Option<(usize, usize)>,
+    pub compare_mode: CompareMode,
+    pub wipe_position: f32,
+    pub difference_threshold: f32,
+
+    // Sequence playback
+    pub sequence_frames: Vec<i64>,
+    pub frame_index: usize,
+    pub playing: bool,
+    pub fps: f32,
+    /// Reload the file when it changes on disk.
+    pub watch: bool,
+
+    // Pixel inspector
+    pub hovered_pixel: Option<(usize, usize)>,
+    pub pixel_info: Option<PixelInfo>,
+    pub deep_pixel: Option<DeepPixelInfo>,
+
+    // Cryptomatte picking
+    pub cryptomattes: Vec<String>,
+    pub cryptomatte_objects: Vec<CryptomatteObject>,
+    /// The cryptomatte index and object id of the matte displayed instead of the image.
+    pub isolated_matte: Option<(usize, u32)>,
+
+    // Metadata inspector
+    pub show_metadata: bool,
+    pub metadata: Vec<PartMetadata>,
+    pub metadata_filter: String,
+
+    // Deep statistics panel
+    pub show_deep_stats: bool,
+
+    // Scopes panel
+    pub show_scopes: bool,
+    pub scope_mode: ScopeMode,
+    pub histogram_log: bool,
+    pub histogram: Option<Histogram>,
+
     // 3D camera
     pub camera_yaw: f32,
     pub camera_pitch: f32,
     pub camera_distance: f32,
     pub point_size: f32,
 
+    /// The fraction of the file that has been read, while a file is loading.
+    pub load_progress: Option<f32>,
+
     // Error display
     pub error: Option<String>,
 }
@@ -225,24 +563,41 @@ impl Default for ViewerState {
         Self {
             image_path: None,
             image_dims: None,
+            data_window: None,
+            display_window: None,
+            show_windows: true,
+            environment_map: None,
+            panorama: false,
+            panorama_camera: PanoramaCamera::default(),
             is_deep: false,
-            total_samples: 0,
-            avg_samples: 0.0,
+            deep_stats: None,
 
+            parts: Vec::new(),
+            current_part: 0,
+            current_level: 0,
             layers: Vec::new(),
             current_layer: String::new(),
             channels: Vec::new(),
             current_channel: String::new(),
+            views: Vec::new(),
+            stereo_mode: StereoMode::Left,
 
             show_3d: false,
             channel_mode: ChannelMode::Color,
+            display_mode: DisplayMode::Normal,
+            colormap: None,
+            show_motion_arrows: true,
             deep_mode: DeepMode::Flattened,
             depth_mode: DepthMode::AutoNormalize,
             view_3d_mode: View3DMode::Heightfield,
 
             exposure: 0.0,
             gamma: 2.2,
-            apply_srgb: true,
+            color: ColorSettings::default(),
+            show_clipping: false,
+            alpha_display: AlphaDisplay::default(),
+            custom_transform: None,
+            ocio: None,
 
             depth_near: 0.0,
             depth_far: 1.0,
@@ -256,11 +611,42 @@ impl Default for ViewerState {
             pan: [0.0, 0.0],
             viewport_size: [1280.0, 720.0],
 
+            compare_path: None,
+            compare_dims: None,
+            compare_mode: CompareMode::Off,
+            wipe_position: 0.5,
+            difference_threshold: 0.01,
+
+            sequence_frames: Vec::new(),
+            frame_index: 0,
+            playing: false,
+            fps: 24.0,
+            watch: false,
+
+            hovered_pixel: None,
+            pixel_info: None,
+            deep_pixel: None,
+
+            cryptomattes: Vec::new(),
+            cryptomatte_objects: Vec::new(),
+            isolated_matte: None,
+
+            show_metadata: false,
+            metadata: Vec::new(),
+            metadata_filter: String::new(),
+
+            show_deep_stats: false,
+            show_scopes: false,
+            scope_mode: ScopeMode::Histogram,
+            histogram_log: false,
+            histogram: None,
+
             camera_yaw: 0.0,
             camera_pitch: 0.3,
             camera_distance: 2.0,
             point_size: 2.0,
 
+            load_progress: None,
             error: None,
         }
     }
@@ -26,6 +26,11 @@ pub struct View3D {
     axes: Axes,
     grid: Vec<Gm<Mesh, ColorMaterial>>,
     
+    // Points, kept to rebuild the instances when the point size changes
+    point_positions: Vec<Vec3>,
+    point_colors: Vec<Srgba>,
+    point_size: f32,
+
     // State
     mode: Mode3D,
     show_grid: bool,
@@ -74,6 +79,9 @@ impl View3D {
             points: None,
             axes,
             grid,
+            point_positions: Vec::new(),
+            point_colors: Vec::new(),
+            point_size: 2.0,
             mode: Mode3D::Heightfield,
             show_grid: true,
             wireframe: false,
@@ -259,7 +267,7 @@ impl View3D {
             (0.5 * aspect, 0.5)
         };
         
-        let mut transforms = Vec::new();
+        let mut positions = Vec::new();
         let mut colors = Vec::new();
         
         for (i, &z) in depth.iter().enumerate() {
@@ -274,28 +282,62 @@ impl View3D {
             let fz = (y as f32 / height as f32 - 0.5) * 2.0 * scale_z;
             let fy = ((z - min_z) / range) * 0.3;
             
-            transforms.push(Mat4::from_translation(vec3(fx, fy, fz)) * Mat4::from_scale(0.002));
+            positions.push(vec3(fx, fy, fz));
             
             let t = fy / 0.3;
             colors.push(depth_to_color(t));
         }
         
-        if transforms.is_empty() {
-            return;
+        self.set_points(positions, colors, Mode3D::PointCloud);
+    }
+    
+    /// Set point cloud from deep samples.
+    /// Each point is the pixel position and the depth of a sample, `[x, y, z]`,
+    /// colored like the sample.
+    pub fn set_deep_pointcloud(
+        &mut self,
+        width: usize,
+        height: usize,
+        points: &[[f32; 3]],
+        colors: &[[u8; 4]],
+    ) {
+        // Find depth range
+        let (mut min_z, mut max_z) = (f32::MAX, f32::MIN);
+        for &[_, _, z] in points {
+            if z.is_finite() {
+                min_z = min_z.min(z);
+                max_z = max_z.max(z);
+            }
         }
+        if min_z > max_z {
+            min_z = 0.0;
+        }
+        if max_z <= min_z {
+            max_z = min_z + 1.0;
+        }
+        let range = max_z - min_z;
         
-        // Create sphere instances
-        let sphere = CpuMesh::sphere(4);
-        let instances = Instances {
-            transformations: transforms,
-            colors: Some(colors),
-            ..Default::default()
+        // Calculate aspect ratio
+        let aspect = width as f32 / height.max(1) as f32;
+        let (scale_x, scale_z) = if aspect > 1.0 {
+            (0.5, 0.5 / aspect)
+        } else {
+            (0.5 * aspect, 0.5)
         };
         
-        let instanced = InstancedMesh::new(&self.context, &instances, &sphere);
-        self.points = Some(Gm::new(instanced, ColorMaterial::default()));
-        self.mesh = None;
-        self.mode = Mode3D::PointCloud;
+        let (positions, colors) = points
+            .iter()
+            .zip(colors)
+            .filter(|([_, _, z], _)| z.is_finite())
+            .map(|(&[x, y, z], &[r, g, b, a])| {
+                let fx = (x / width as f32 - 0.5) * 2.0 * scale_x;
+                let fz = (y / height as f32 - 0.5) * 2.0 * scale_z;
+                let fy = ((z - min_z) / range) * 0.3;
+                (vec3(fx, fy, fz), Srgba::new(r, g, b, a))
+            })
+            .unzip();
+        
+        self.set_points(positions, colors, Mode3D::PointCloud);
     }
     
     /// Set position pass from P.xyz channels.
@@ -327,7 +369,7 @@ impl View3D {
         let max_points = 50000;
         let step = ((width * height) / max_points).max(1);
         
-        let mut transforms = Vec::new();
+        let mut positions = Vec::new();
         let mut colors = Vec::new();
         
         for i in 0..(width * height) {
@@ -348,28 +390,56 @@ impl View3D {
             let ny = (y - center.y) / scale;
             let nz = (z - center.z) / scale;
             
-            transforms.push(Mat4::from_translation(vec3(nx, ny, nz)) * Mat4::from_scale(0.002));
+            positions.push(vec3(nx, ny, nz));
             
             // Color by normalized Y
             let t = ny + 0.5;
             colors.push(depth_to_color(t));
         }
         
-        if transforms.is_empty() {
+        self.set_points(positions, colors, Mode3D::PositionPass);
+    }
+    
+    /// Replace the points, rendered as small spheres scaled by the point size.
+    fn set_points(&mut self, positions: Vec<Vec3>, colors: Vec<Srgba>, mode: Mode3D) {
+        self.point_positions = positions;
+        self.point_colors = colors;
+        self.mesh = None;
+        self.mode = mode;
+        self.rebuild_points();
+    }
+    
+    /// Create the sphere instances of the points.
+    fn rebuild_points(&mut self) {
+        if self.point_positions.is_empty() {
+            self.points = None;
             return;
         }
         
+        let scale = 0.001 * self.point_size;
+        let transforms = self
+            .point_positions
+            .iter()
+            .map(|&position| Mat4::from_translation(position) * Mat4::from_scale(scale))
+            .collect();
+        
         let sphere = CpuMesh::sphere(4);
         let instances = Instances {
             transformations: transforms,
-            colors: Some(colors),
+            colors: Some(self.point_colors.clone()),
             ..Default::default()
         };
         
         let instanced = InstancedMesh::new(&self.context, &instances, &sphere);
         self.points = Some(Gm::new(instanced, ColorMaterial::default()));
-        self.mesh = None;
-        self.mode = Mode3D::PositionPass;
+    }
+    
+    /// Set the size of the points, in thousandths of the scene size.
+    pub fn set_point_size(&mut self, size: f32) {
+        if (size - self.point_size).abs() > f32::EPSILON {
+            self.point_size = size;
+            self.rebuild_points();
+        }
     }
     
     /// Handle input events.
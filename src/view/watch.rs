@@ -0,0 +1,185 @@
+//! Watches the displayed file for changes by a renderer, polling its metadata.
+//! Changes are reported once the file stopped changing, so that partially written
+//! files are not loaded.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the file and its directory are checked.
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a file must stay unchanged before it is reloaded.
+pub const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// What changed since the last reported change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// The watched file was overwritten.
+    File,
+    /// Files were added to or removed from the directory, like new frames of a sequence.
+    Directory,
+}
+
+/// The size and modification time of a file, compared to detect changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl Stamp {
+    /// `None` if the file does not exist, for example while it is being replaced.
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Stamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// The last reported state of a path, and a change waiting for the debounce time.
+#[derive(Debug)]
+struct Watched {
+    path: PathBuf,
+    reported: Option<Stamp>,
+    pending: Option<(Option<Stamp>, Instant)>,
+}
+
+impl Watched {
+    fn new(path: PathBuf) -> Self {
+        let reported = Stamp::of(&path);
+        Watched {
+            path,
+            reported,
+            pending: None,
+        }
+    }
+
+    /// Whether the path changed, and then stayed unchanged for the debounce time.
+    fn poll(&mut self, now: Instant, debounce: Duration) -> bool {
+        let current = Stamp::of(&self.path);
+
+        if current == self.reported {
+            self.pending = None;
+            return false;
+        }
+
+        match self.pending {
+            Some((pending, since)) if pending == current => {
+                // a missing file is still being replaced
+                if current.is_none() || now.duration_since(since) < debounce {
+                    return false;
+                }
+
+                self.reported = current;
+                self.pending = None;
+                true
+            }
+            _ => {
+                self.pending = Some((current, now));
+                false
+            }
+        }
+    }
+}
+
+/// Polls a file, and optionally its directory, for changes.
+#[derive(Debug)]
+pub struct FileWatcher {
+    file: Watched,
+    directory: Option<Watched>,
+    debounce: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl FileWatcher {
+    /// Watch the file, and its directory if `directory` is true.
+    pub fn new(path: &Path, directory: bool, debounce: Duration) -> Self {
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+
+        FileWatcher {
+            file: Watched::new(path.to_path_buf()),
+            directory: directory.then(|| Watched::new(parent.to_path_buf())),
+            debounce,
+            last_poll: None,
+        }
+    }
+
+    /// Watch another file of the directory, like the next frame of a sequence,
+    /// without reporting its differences to the previous file.
+    pub fn set_file(&mut self, path: &Path) {
+        if self.file.path != path {
+            self.file = Watched::new(path.to_path_buf());
+        }
+    }
+
+    /// Check for changes, at most once per `WATCH_INTERVAL`.
+    /// A change of the file takes precedence over a change of the directory.
+    pub fn poll(&mut self, now: Instant) -> Option<Change> {
+        let polled_recently = self
+            .last_poll
+            .map_or(false, |last| now.duration_since(last) < WATCH_INTERVAL);
+
+        if polled_recently {
+            return None;
+        }
+
+        self.last_poll = Some(now);
+        let debounce = self.debounce;
+        let directory = self
+            .directory
+            .as_mut()
+            .map_or(false, |directory| directory.poll(now, debounce));
+
+        if self.file.poll(now, debounce) {
+            Some(Change::File)
+        } else if directory {
+            Some(Change::Directory)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reports_changes_after_debounce() {
+        let directory = std::env::temp_dir().join(format!("exrs_watch_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("render.exr");
+        std::fs::write(&path, b"first").unwrap();
+
+        let start = Instant::now();
+        let step = WATCH_INTERVAL;
+        let mut watcher = FileWatcher::new(&path, true, step);
+        assert_eq!(watcher.poll(start), None);
+
+        // the renderer writes the file in two steps
+        std::fs::write(&path, b"second").unwrap();
+        assert_eq!(watcher.poll(start + step), None);
+        std::fs::write(&path, b"second, longer").unwrap();
+        assert_eq!(watcher.poll(start + step * 2), None);
+        assert_eq!(watcher.poll(start + step * 3), Some(Change::File));
+        assert_eq!(watcher.poll(start + step * 4), None);
+
+        // a new frame changes the directory
+        let other = directory.join("render.0002.exr");
+        std::fs::write(&other, b"frame").unwrap();
+        assert_eq!(watcher.poll(start + step * 5), None);
+        assert_eq!(watcher.poll(start + step * 6), Some(Change::Directory));
+
+        // switching the file does not report a change
+        watcher.set_file(&other);
+        assert_eq!(watcher.poll(start + step * 7), None);
+        assert_eq!(watcher.poll(start + step * 8), None);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
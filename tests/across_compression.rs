@@ -266,3 +266,84 @@ fn compare_png_to_dwaa_f32() {
 fn compare_png_to_pxr24_f32() {
     expect_eq_png("f32_pxr24.exr");
 }
+
+/// The deep files of the OpenEXR test images, written with each compression permitted for deep data.
+#[test]
+fn compare_deep_compression_contents() {
+    use exr::image::read::deep::read_all_deep_layers_from_file;
+    use std::io::Cursor;
+
+    let permitted = [
+        Compression::Uncompressed,
+        Compression::RLE,
+        Compression::ZIP1,
+        Compression::ZIP16,
+    ];
+    let not_permitted = [Compression::PXR24, Compression::B44, Compression::PIZ];
+
+    for name in ["Balls.exr", "Ground.exr", "Leaves.exr", "Trunks.exr"] {
+        let path = Path::new("tests/images/valid/openexr/v2/LowResLeftView").join(name);
+        let original = read_all_deep_layers_from_file(&path).unwrap();
+
+        for layer in &original.layer_data {
+            assert!(layer.encoding.compression.supports_deep_data());
+        }
+
+        for &compression in &permitted {
+            let mut bytes = Vec::new();
+            original
+                .write_deep()
+                .with_compression(compression)
+                .to_buffered(Cursor::new(&mut bytes))
+                .unwrap();
+
+            let decompressed = exr::image::read::deep::read_deep()
+                .all_channels()
+                .all_layers()
+                .all_attributes()
+                .from_buffered(Cursor::new(&bytes))
+                .unwrap();
+
+            assert_eq!(decompressed.layer_data.len(), original.layer_data.len());
+
+            for (layer, expected) in decompressed.layer_data.iter().zip(&original.layer_data) {
+                assert_eq!(layer.encoding.compression, compression);
+
+                let samples = &layer.channel_data.list[0].sample_data;
+                let expected = &expected.channel_data.list[0].sample_data;
+                assert_eq!(samples.sample_offsets, expected.sample_offsets);
+
+                // compare the bits, because the files contain NaN samples
+                for (channel, expected) in samples.channels.iter().zip(&expected.channels) {
+                    assert_eq!(channel.sample_type(), expected.sample_type());
+                    assert!(
+                        channel
+                            .to_f32_vec()
+                            .iter()
+                            .map(|sample| sample.to_bits())
+                            .eq(expected.to_f32_vec().iter().map(|sample| sample.to_bits())),
+                        "{} with {}",
+                        name,
+                        compression
+                    );
+                }
+            }
+        }
+
+        for &compression in &not_permitted {
+            let mut bytes = Vec::new();
+            let result = original
+                .write_deep()
+                .with_compression(compression)
+                .to_buffered(Cursor::new(&mut bytes));
+
+            assert!(
+                matches!(result, Err(Error::Invalid(_))),
+                "{} with {}",
+                name,
+                compression
+            );
+            assert!(bytes.is_empty(), "nothing is written with {}", compression);
+        }
+    }
+}
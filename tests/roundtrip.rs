@@ -8,9 +8,11 @@ use std::panic;
 use std::panic::catch_unwind;
 use std::path::{Path, PathBuf};
 
+use exr::block::chunk::TileCoordinates;
 use exr::block::samples::IntoNativeSample;
 use exr::error::{Error, UnitResult};
 use exr::image::validate_results::ValidateResult;
+use exr::image::write::WriteImageWithOptions;
 use exr::prelude::pixel_vec::PixelVec;
 use exr::prelude::*;
 use rayon::iter::ParallelIterator;
@@ -80,6 +82,13 @@ fn round_trip_rgba_file(path: &Path, file: &[u8]) -> Result<()> {
     // these files are known to be invalid, because they do not contain any rgb channels
     let blacklist = [
         Path::new("tests/images/valid/openexr/LuminanceChroma/Garden.exr"),
+        Path::new("tests/images/valid/openexr/LuminanceChroma/CrissyField.exr"),
+        Path::new("tests/images/valid/openexr/LuminanceChroma/Flowers.exr"),
+        Path::new("tests/images/valid/openexr/LuminanceChroma/MtTamNorth.exr"),
+        Path::new("tests/images/valid/openexr/LuminanceChroma/StarField.exr"),
+        Path::new("tests/images/valid/openexr/Chromaticities/Rec709_YC.exr"),
+        Path::new("tests/images/valid/openexr/Chromaticities/XYZ_YC.exr"),
+        Path::new("tests/images/valid/openexr/IlmfmlmflmTest/comp_b44.exr"),
         Path::new("tests/images/valid/openexr/MultiView/Fog.exr"),
         Path::new("tests/images/valid/openexr/TestImages/GrayRampsDiagonal.exr"),
         Path::new("tests/images/valid/openexr/TestImages/GrayRampsHorizontal.exr"),
@@ -424,3 +433,188 @@ fn test_mixed_roundtrip_with_compression(compression: Compression) {
     lossy_image.assert_equals_result(&lossy_image);
     original_image.assert_equals_result(&lossy_image);
 }
+
+#[test]
+fn roundtrip_from_bytes() {
+    let pixels = vec![(0.25_f32, 0.5_f32, 1.0_f32, 0.75_f32); 6];
+    let image = Image::from_channels(
+        (3, 2),
+        SpecificChannels::rgba(PixelVec::new(Vec2(3, 2), pixels.clone())),
+    );
+
+    let mut file_bytes = Vec::new();
+    image
+        .write()
+        .to_buffered(Cursor::new(&mut file_bytes))
+        .unwrap();
+
+    let rgba = read_first_rgba_layer_from_bytes(
+        &file_bytes,
+        PixelVec::<(f32, f32, f32, f32)>::constructor,
+        PixelVec::set_pixel,
+    )
+    .unwrap();
+
+    assert_eq!(rgba.layer_data.channel_data.pixels.pixels, pixels);
+
+    let flat = read_all_flat_layers_from_bytes(&file_bytes).unwrap();
+    assert_eq!(flat.layer_data.len(), 1);
+    assert_eq!(flat.layer_data[0].channel_data.list.len(), 4);
+    assert_eq!(flat.layer_data[0].size, Vec2(3, 2));
+}
+
+#[test]
+fn roundtrip_custom_attributes() {
+    use exr::meta::attribute::{AttributeValue, TimeCode};
+    use exr::meta::MetaData;
+    use smallvec::SmallVec;
+
+    let unknown_type = AttributeValue::Custom {
+        kind: Text::from("nukeMetadata"),
+        bytes: SmallVec::from_slice(&[7, 0, 255, 3, 1]),
+    };
+
+    // too many bytes for a float, so it must not be parsed and written as a float
+    let malformed_float = AttributeValue::Custom {
+        kind: Text::from("float"),
+        bytes: SmallVec::from_slice(&[0, 0, 128, 63, 42, 42]),
+    };
+
+    let mut attributes = LayerAttributes::named("beauty");
+    let custom = [
+        ("renderMemory", AttributeValue::from(1536.5_f32)),
+        ("cameraJson", Text::from(r#"{"focal": 35.0}"#).into()),
+        ("nuke", unknown_type),
+        ("brokenFloat", malformed_float),
+    ];
+
+    for (name, value) in custom.iter().cloned() {
+        attributes.other.insert(Text::from(name), value);
+    }
+
+    let mut image = Image::from_layer(Layer::new(
+        (2, 2),
+        attributes,
+        Encoding::FAST_LOSSLESS,
+        SpecificChannels::rgb(|_| (0.5_f32, 0.25_f32, 1.0_f32)),
+    ));
+
+    image.attributes.time_code = Some(TimeCode {
+        hours: 1,
+        minutes: 2,
+        seconds: 3,
+        frame: 4,
+        ..TimeCode::default()
+    });
+
+    let mut bytes = Vec::new();
+    image.write().to_buffered(Cursor::new(&mut bytes)).unwrap();
+
+    // read, modify, and write the image again
+    let mut loaded = read_all_flat_layers_from_bytes(&bytes).unwrap();
+    loaded.layer_data[0]
+        .attributes
+        .standard_attributes()
+        .set_owner(Some("compositing"))
+        .unwrap();
+
+    let mut rewritten = Vec::new();
+    loaded.write().to_buffered(Cursor::new(&mut rewritten)).unwrap();
+
+    let headers = MetaData::read_from_buffered(rewritten.as_slice(), false)
+        .unwrap()
+        .headers;
+
+    let header = &headers[0];
+    for (name, value) in custom.iter() {
+        assert_eq!(header.own_attributes.other.get(&Text::from(*name)), Some(value));
+    }
+
+    assert_eq!(header.own_attributes.owner, Some(Text::from("compositing")));
+    assert_eq!(header.shared_attributes.time_code, image.attributes.time_code);
+}
+
+#[test]
+fn parallel_compression_is_deterministic() {
+    let size = Vec2(67, 211);
+    let pixels = (0..size.area())
+        .map(|index| (index as f32, (index % 7) as f32, 0.5))
+        .collect();
+
+    let image = Image::from_encoded_channels(
+        size,
+        Encoding::SMALL_LOSSLESS,
+        SpecificChannels::rgb(PixelVec::new(size, pixels)),
+    );
+
+    let write = |writer: WriteImageWithOptions<_, fn(f64)>| {
+        let mut bytes = Vec::new();
+        writer.to_buffered(Cursor::new(&mut bytes)).unwrap();
+        bytes
+    };
+
+    let sequential = write(image.write().non_parallel());
+    assert_eq!(write(image.write()), sequential);
+    assert_eq!(
+        write(image.write().with_max_blocks_in_flight(1)),
+        sequential
+    );
+    assert_eq!(
+        write(image.write().with_max_blocks_in_flight(3)),
+        sequential
+    );
+}
+
+#[test]
+fn random_access_tiles() {
+    let size = Vec2(10, 6);
+    let image = Image::from_encoded_channels(
+        size,
+        Encoding {
+            compression: Compression::RLE,
+            blocks: Blocks::Tiles(Vec2(4, 4)),
+            line_order: LineOrder::Increasing,
+        },
+        SpecificChannels::rgb(|position: Vec2<usize>| {
+            (position.x() as f32, position.y() as f32, 1.0_f32)
+        }),
+    );
+
+    let mut file_bytes = Vec::new();
+    image
+        .write()
+        .to_buffered(Cursor::new(&mut file_bytes))
+        .unwrap();
+
+    let mut tiles = exr::block::read(Cursor::new(&file_bytes), true)
+        .unwrap()
+        .tiled_random_access(true)
+        .unwrap();
+
+    let last = TileCoordinates {
+        tile_index: Vec2(2, 1),
+        level_index: Vec2(0, 0),
+    };
+    let missing = TileCoordinates {
+        tile_index: Vec2(3, 0),
+        level_index: Vec2(0, 0),
+    };
+
+    assert!(tiles.contains_tile(0, last));
+    assert!(!tiles.contains_tile(0, missing));
+    assert!(tiles.read_tile(0, missing).is_err());
+
+    // read the tiles in reverse order
+    let block = tiles.read_tile(0, last).unwrap();
+    assert_eq!(block.index.pixel_position, Vec2(8, 4));
+    assert_eq!(block.index.pixel_size, Vec2(2, 2));
+
+    let first = TileCoordinates {
+        tile_index: Vec2(0, 0),
+        level_index: Vec2(0, 0),
+    };
+    let block = tiles.read_tile(0, first).unwrap();
+    assert_eq!(block.index.pixel_position, Vec2(0, 0));
+    assert_eq!(block.index.pixel_size, Vec2(4, 4));
+    assert_eq!(block.data.len(), 4 * 4 * 3 * 4);
+}
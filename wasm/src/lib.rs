@@ -0,0 +1,109 @@
+//! Decode exr files in the browser.
+//!
+//! The first layer with rgb channels is read from the bytes of a file,
+//! exposed, tone mapped to sRGB, and returned as 8-bit rgba pixels
+//! that can be put into the `ImageData` of a canvas.
+//! A missing alpha channel is treated as opaque.
+
+use exr::prelude::pixel_vec::PixelVec;
+use exr::prelude::*;
+use wasm_bindgen::prelude::*;
+
+/// The pixels of a decoded image, ready for a canvas.
+#[wasm_bindgen]
+pub struct DecodedImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl DecodedImage {
+    /// The number of pixels in each row.
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The number of rows.
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Four bytes per pixel, red, green, blue and straight alpha, row after row.
+    pub fn pixels(&self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+/// Decode the first rgb layer of the file. The linear colors are multiplied
+/// by `2 ^ exposure` before they are converted to sRGB.
+#[wasm_bindgen]
+pub fn decode(bytes: &[u8], exposure: f32) -> Result<DecodedImage, JsError> {
+    let image = read_first_rgba_layer_from_bytes(
+        bytes,
+        PixelVec::<(f32, f32, f32, f32)>::constructor,
+        PixelVec::set_pixel,
+    )
+    .map_err(|error| JsError::new(&error.to_string()))?;
+
+    let pixels = &image.layer_data.channel_data.pixels;
+    Ok(DecodedImage {
+        width: pixels.resolution.width(),
+        height: pixels.resolution.height(),
+        pixels: tone_map(&pixels.pixels, exposure),
+    })
+}
+
+/// Convert premultiplied linear pixels to 8-bit sRGB with straight alpha.
+fn tone_map(pixels: &[(f32, f32, f32, f32)], exposure: f32) -> Vec<u8> {
+    let scale = exposure.exp2();
+    let mut bytes = Vec::with_capacity(pixels.len() * 4);
+
+    for &(r, g, b, a) in pixels {
+        let alpha = a.max(0.0).min(1.0);
+        let unpremultiply = if alpha > 0.0 { scale / alpha } else { scale };
+
+        bytes.push(linear_to_srgb8(r * unpremultiply));
+        bytes.push(linear_to_srgb8(g * unpremultiply));
+        bytes.push(linear_to_srgb8(b * unpremultiply));
+        bytes.push((alpha * 255.0).round() as u8);
+    }
+
+    bytes
+}
+
+fn linear_to_srgb8(linear: f32) -> u8 {
+    // also maps nan to zero
+    let linear = if linear > 0.0 { linear.min(1.0) } else { 0.0 };
+
+    let srgb = if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+
+    (srgb * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn srgb_curve() {
+        assert_eq!(linear_to_srgb8(0.0), 0);
+        assert_eq!(linear_to_srgb8(-1.0), 0);
+        assert_eq!(linear_to_srgb8(f32::NAN), 0);
+        assert_eq!(linear_to_srgb8(1.0), 255);
+        assert_eq!(linear_to_srgb8(100.0), 255);
+        assert_eq!(linear_to_srgb8(0.5), 188);
+    }
+
+    #[test]
+    fn exposure_and_alpha() {
+        let bytes = tone_map(&[(0.25, 0.5, 0.0, 0.5), (0.5, 0.5, 0.5, 1.0)], 1.0);
+        assert_eq!(bytes, vec![255, 255, 0, 128, 255, 255, 255, 255]);
+    }
+}
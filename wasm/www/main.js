@@ -0,0 +1,42 @@
+// Decodes the selected exr file with `exrs_wasm` and draws it into the canvas.
+// The `pkg` directory is created by `wasm-pack build --target web --out-dir www/pkg`.
+
+import init, { decode } from "./pkg/exrs_wasm.js";
+
+const file = document.getElementById("file");
+const exposure = document.getElementById("exposure");
+const status = document.getElementById("status");
+const canvas = document.getElementById("canvas");
+
+let bytes = null;
+
+function draw() {
+    if (bytes === null) return;
+
+    const start = performance.now();
+
+    try {
+        const image = decode(bytes, parseFloat(exposure.value));
+        const pixels = new Uint8ClampedArray(image.pixels());
+
+        canvas.width = image.width;
+        canvas.height = image.height;
+        canvas.getContext("2d").putImageData(new ImageData(pixels, image.width, image.height), 0, 0);
+
+        status.textContent = `${image.width} x ${image.height}, decoded in ${(performance.now() - start).toFixed(0)} ms`;
+        image.free();
+    }
+    catch (error) {
+        status.textContent = `cannot decode: ${error.message ?? error}`;
+    }
+}
+
+await init();
+
+file.addEventListener("change", async () => {
+    if (file.files.length === 0) return;
+    bytes = new Uint8Array(await file.files[0].arrayBuffer());
+    draw();
+});
+
+exposure.addEventListener("change", draw);